@@ -2,23 +2,30 @@
 
 #![deny(clippy::print_stdout)]
 
-use std::collections::BTreeMap;
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet, HashSet};
 
 use powdr_ast::{
-    asm_analysis::{AnalysisASMFile, Item, LinkDefinitionStatement, SubmachineDeclaration},
+    asm_analysis::{
+        AnalysisASMFile, Item, LinkDefinitionStatement, OperationDefinitionRef,
+        SubmachineDeclaration,
+    },
     object::{Link, LinkFrom, LinkTo, Location, Object, Operation, PILGraph},
     parsed::{
         asm::{parse_absolute_path, AbsoluteSymbolPath, CallableRef},
-        PilStatement,
+        types::{Type, TypeScheme},
+        visitor::ExpressionVisitable,
+        BlockExpression, Expression, LambdaExpression, PilStatement, TypedExpression,
     },
 };
 
 const MAIN_MACHINE: &str = "::Main";
 const MAIN_FUNCTION: &str = "main";
 
-pub fn compile(input: AnalysisASMFile) -> PILGraph {
-    let main_location = Location::main();
-
+/// Compiles the machines into a graph of objects, starting from the main machine:
+/// the only machine outside of std if there is a single one, otherwise `::Main`.
+/// The root of the graph is at location `main`.
+/// Fails if different utility definitions end up at the same path.
+pub fn compile(input: AnalysisASMFile) -> Result<PILGraph, String> {
     let non_std_machines = input
         .machines()
         .filter(|(k, _)| k.parts().next() != Some("std"))
@@ -36,6 +43,42 @@ pub fn compile(input: AnalysisASMFile) -> PILGraph {
         }
     };
 
+    compile_from(input, main_ty, Location::main())
+}
+
+/// Compiles the machines into a graph of objects, starting from the machine at `entry_point`.
+/// The root of the graph is named after the entry point machine.
+/// Fails if `entry_point` is not a machine, listing the available machines,
+/// or if different utility definitions end up at the same path.
+pub fn compile_with_entry_point(
+    input: AnalysisASMFile,
+    entry_point: &AbsoluteSymbolPath,
+) -> Result<PILGraph, String> {
+    let problem = match input.items.get(entry_point) {
+        // Machines do not have constructor parameters, so any machine can be instantiated.
+        Some(Item::Machine(_)) => None,
+        Some(Item::Expression(_)) => Some("is not a machine"),
+        None => Some("does not exist"),
+    };
+    if let Some(problem) = problem {
+        return Err(format!(
+            "Entry point {entry_point} {problem}. Available machines: {}",
+            input
+                .machines()
+                .map(|(name, _)| name.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    let root = Location::root(entry_point.parts().last().unwrap());
+    compile_from(input, entry_point.clone(), root)
+}
+
+fn compile_from(
+    input: AnalysisASMFile,
+    main_ty: AbsoluteSymbolPath,
+    main_location: Location,
+) -> Result<PILGraph, String> {
     // get a list of all machines to instantiate. The order does not matter.
     let mut queue = vec![(main_location.clone(), main_ty.clone())];
 
@@ -57,7 +100,7 @@ pub fn compile(input: AnalysisASMFile) -> PILGraph {
     }
 
     // visit the tree compiling the machines
-    let objects = instances
+    let mut objects: BTreeMap<_, _> = instances
         .into_iter()
         .map(|(location, ty)| {
             let object = ASMPILConverter::convert_machine(&location, &ty, &input);
@@ -73,6 +116,7 @@ pub fn compile(input: AnalysisASMFile) -> PILGraph {
         location: main_location,
         latch: main_ty.latch.clone(),
         operation_id: main_ty.operation_id.clone(),
+        call_selectors: None,
     };
     let entry_points = main_ty
         .operations()
@@ -84,7 +128,7 @@ pub fn compile(input: AnalysisASMFile) -> PILGraph {
         .collect();
 
     // Extract all the pil utility definitions
-    let definitions = input
+    let mut definitions = input
         .items
         .into_iter()
         .filter_map(|(n, v)| {
@@ -95,13 +139,16 @@ pub fn compile(input: AnalysisASMFile) -> PILGraph {
             }
         })
         .collect();
+    for object in objects.values_mut() {
+        hoist_utility_definitions(object, &mut definitions)?;
+    }
 
-    PILGraph {
+    Ok(PILGraph {
         main,
         entry_points,
         objects,
         definitions,
-    }
+    })
 }
 
 struct ASMPILConverter<'a> {
@@ -142,6 +189,11 @@ impl<'a> ASMPILConverter<'a> {
         };
 
         let degree = input.degree.map(|s| s.degree.try_into().unwrap());
+        let operations = input
+            .callable
+            .operation_definitions()
+            .map(to_operation)
+            .collect();
 
         self.submachines = input.submachines;
 
@@ -161,8 +213,13 @@ impl<'a> ASMPILConverter<'a> {
             .collect();
 
         Object {
+            ty: ty.clone(),
+            source: input.source,
             degree,
+            latch: input.latch,
+            operation_id: input.operation_id,
             pil: self.pil,
+            operations,
             links,
         }
     }
@@ -170,8 +227,9 @@ impl<'a> ASMPILConverter<'a> {
     fn handle_link_def(
         &mut self,
         LinkDefinitionStatement {
-            source: _,
+            source,
             flag,
+            is_permutation,
             to:
                 CallableRef {
                     instance,
@@ -210,15 +268,150 @@ impl<'a> ASMPILConverter<'a> {
                         location: instance_location,
                         latch: instance_ty.latch.clone(),
                         operation_id: instance_ty.operation_id.clone(),
+                        call_selectors: None,
                     },
-                    operation: Operation {
-                        name: d.name.to_string(),
-                        id: d.operation.id.id.clone(),
-                        params: d.operation.params.clone(),
-                    },
+                    operation: to_operation(d),
+                    selector_index: None,
                 })
                 .unwrap()
                 .clone(),
+            is_permutation,
+            source,
         }
     }
 }
+
+fn to_operation(d: OperationDefinitionRef) -> Operation {
+    Operation {
+        name: d.name.to_string(),
+        id: d.operation.id.id.clone(),
+        params: d.operation.params.clone(),
+    }
+}
+
+/// Moves the utility functions defined inside the machine of `object` to `definitions`,
+/// at the path of the machine type, so that all instances of a machine share one definition.
+/// References to them are replaced by their absolute path.
+/// A `let` statement defines a utility function if it has a function type (or is an untyped
+/// lambda) and does not reference any symbol of the machine except other utility functions.
+/// To keep replacing references simple, names that are also used as local variables
+/// (lambda parameters or `let` statements in blocks) in the machine are not moved.
+/// Fails if a different definition already exists at the same path.
+fn hoist_utility_definitions(
+    object: &mut Object,
+    definitions: &mut BTreeMap<AbsoluteSymbolPath, TypedExpression>,
+) -> Result<(), String> {
+    let local_names = object
+        .pil
+        .iter()
+        .flat_map(|s| s.symbol_definition_names())
+        .cloned()
+        .collect::<HashSet<_>>();
+    let mut local_variables = HashSet::new();
+    for e in object.pil.iter().flat_map(|s| s.expressions()) {
+        e.pre_visit_expressions(&mut |e| match e {
+            Expression::LambdaExpression(LambdaExpression { params, .. }) => {
+                local_variables.extend(params.iter().cloned());
+            }
+            Expression::BlockExpression(BlockExpression { statements, .. }) => {
+                local_variables.extend(statements.iter().map(|s| s.name.clone()));
+            }
+            _ => {}
+        });
+    }
+
+    // the utility functions with the local names they reference
+    let mut utilities = object
+        .pil
+        .iter()
+        .filter_map(|s| match s {
+            PilStatement::LetStatement(_, name, type_scheme, Some(value))
+                if is_function(type_scheme, value) && !local_variables.contains(name) =>
+            {
+                let mut referenced = BTreeSet::new();
+                value.pre_visit_expressions(&mut |e| {
+                    if let Expression::Reference(reference) = e {
+                        if let Some(name) = reference.try_to_identifier() {
+                            if local_names.contains(name) {
+                                referenced.insert(name.clone());
+                            }
+                        }
+                    }
+                });
+                Some((name.clone(), referenced))
+            }
+            _ => None,
+        })
+        .collect::<BTreeMap<_, _>>();
+    // only keep the ones that reference nothing but utility functions
+    loop {
+        let dependent = utilities
+            .iter()
+            .filter(|(_, referenced)| referenced.iter().any(|r| !utilities.contains_key(r)))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        if dependent.is_empty() {
+            break;
+        }
+        for name in dependent {
+            utilities.remove(&name);
+        }
+    }
+    if utilities.is_empty() {
+        return Ok(());
+    }
+
+    let ty = &object.ty;
+    let absolute_name = |name: &str| ty.with_part(name);
+    let replace_references = &mut |e: &mut Expression| {
+        if let Expression::Reference(reference) = e {
+            if let Some(name) = reference.try_to_identifier() {
+                if utilities.contains_key(name) {
+                    *reference = absolute_name(name).relative_to(&Default::default()).into();
+                }
+            }
+        }
+    };
+    for e in object.pil.iter_mut().flat_map(|s| s.expressions_mut()) {
+        e.pre_visit_expressions_mut(replace_references);
+    }
+    for link in &mut object.links {
+        link.from.flag.pre_visit_expressions_mut(replace_references);
+    }
+
+    let (hoisted, pil) = std::mem::take(&mut object.pil)
+        .into_iter()
+        .partition::<Vec<_>, _>(|s| {
+            matches!(s, PilStatement::LetStatement(_, name, _, Some(_)) if utilities.contains_key(name))
+        });
+    object.pil = pil;
+    for statement in hoisted {
+        let PilStatement::LetStatement(_, name, type_scheme, Some(e)) = statement else {
+            unreachable!()
+        };
+        let definition = TypedExpression { e, type_scheme };
+        match definitions.entry(absolute_name(&name)) {
+            Entry::Vacant(entry) => {
+                entry.insert(definition);
+            }
+            Entry::Occupied(entry) => {
+                if entry.get() != &definition {
+                    return Err(format!(
+                        "Conflicting definitions of {}: `{}` and `{}`",
+                        entry.key(),
+                        entry.get().e,
+                        definition.e
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_function(type_scheme: &Option<TypeScheme<Expression>>, value: &Expression) -> bool {
+    match type_scheme {
+        Some(type_scheme) => matches!(type_scheme.ty, Type::Function(_)),
+        None => matches!(value, Expression::LambdaExpression(_)),
+    }
+}
@@ -58,14 +58,42 @@ impl TypeChecker {
                         degree: degree_value,
                     });
                 }
-                MachineStatement::RegisterDeclaration(source, name, flag) => {
+                MachineStatement::RegisterDeclaration(source, name, flag, range) => {
                     let ty = match flag {
                         Some(RegisterFlag::IsAssignment) => RegisterTy::Assignment,
                         Some(RegisterFlag::IsPC) => RegisterTy::Pc,
                         Some(RegisterFlag::IsReadOnly) => RegisterTy::ReadOnly,
                         None => RegisterTy::Write,
                     };
-                    registers.push(RegisterDeclarationStatement { source, name, ty });
+                    let range = match range {
+                        Some(_) if ty.is_pc() || ty.is_assignment() => {
+                            errors.push(format!(
+                                "Register `{name}` in machine {ctx} cannot have a range as it is not a regular register"
+                            ));
+                            None
+                        }
+                        Some(range) => match range.bounds() {
+                            // registers are reset to zero at the start of the execution
+                            Ok((min, _)) if min != 0u32.into() => {
+                                errors.push(format!(
+                                    "Range {range} of register `{name}` in machine {ctx} has to include zero"
+                                ));
+                                None
+                            }
+                            Ok(bounds) => Some(bounds),
+                            Err(e) => {
+                                errors.push(format!("{e} for register `{name}`"));
+                                None
+                            }
+                        },
+                        None => None,
+                    };
+                    registers.push(RegisterDeclarationStatement {
+                        source,
+                        name,
+                        ty,
+                        range,
+                    });
                 }
                 MachineStatement::InstructionDeclaration(source, name, instruction) => {
                     match self.check_instruction(&name, instruction) {
@@ -77,12 +105,17 @@ impl TypeChecker {
                         Err(e) => errors.extend(e),
                     }
                 }
-                MachineStatement::LinkDeclaration(source, LinkDeclaration { flag, to }) => {
-                    match self.check_link_declaration(source, flag, to) {
-                        Ok(link_definition) => links.push(link_definition),
-                        Err(e) => errors.extend(e),
-                    }
-                }
+                MachineStatement::LinkDeclaration(
+                    source,
+                    LinkDeclaration {
+                        flag,
+                        to,
+                        is_permutation,
+                    },
+                ) => match self.check_link_declaration(source, flag, to, is_permutation) {
+                    Ok(link_definition) => links.push(link_definition),
+                    Err(e) => errors.extend(e),
+                },
                 MachineStatement::Pil(_source, statement) => {
                     pil.push(statement);
                 }
@@ -255,7 +288,10 @@ impl TypeChecker {
             errors.push(format!("Machine {} cannot have more than one pc", ctx));
         }
 
+        errors.extend(self.check_instruction_aliases(&instructions, ctx));
+
         let machine = Machine {
+            source: machine.source,
             degree,
             latch,
             operation_id,
@@ -370,11 +406,88 @@ impl TypeChecker {
         })
     }
 
+    /// Checks that aliases refer to existing instructions of the same machine
+    /// with matching parameters, and that they are not cyclic.
+    fn check_instruction_aliases(
+        &self,
+        instructions: &[InstructionDefinitionStatement],
+        ctx: &AbsoluteSymbolPath,
+    ) -> Vec<String> {
+        let by_name: BTreeMap<_, _> = instructions
+            .iter()
+            .map(|i| (i.name.as_str(), &i.instruction))
+            .collect();
+        let alias_target = |name: &str| match &by_name.get(name)?.body {
+            InstructionBody::Alias(alias) => Some(alias.instruction.as_str()),
+            _ => None,
+        };
+
+        let mut errors = vec![];
+        for i in instructions {
+            let InstructionBody::Alias(alias) = &i.instruction.body else {
+                continue;
+            };
+            let Some(target) = by_name.get(alias.instruction.as_str()) else {
+                errors.push(format!(
+                    "Instruction `{}` in machine {} is an alias of `{}`, which is not an instruction of this machine",
+                    i.name, ctx, alias.instruction
+                ));
+                continue;
+            };
+            if alias.inputs.len() != target.params.inputs.len() {
+                errors.push(format!(
+                    "Instruction alias `{}` passes {} inputs to `{}`, which expects {}",
+                    i.name,
+                    alias.inputs.len(),
+                    alias.instruction,
+                    target.params.inputs.len()
+                ));
+            }
+            let mut outputs = alias.outputs.clone();
+            outputs.sort();
+            let mut expected_outputs = i
+                .instruction
+                .params
+                .outputs
+                .iter()
+                .map(|o| o.name.clone())
+                .collect::<Vec<_>>();
+            expected_outputs.sort();
+            if alias.outputs.len() != target.params.outputs.len() || outputs != expected_outputs {
+                errors.push(format!(
+                    "Instruction alias `{}` has to bind each of its outputs to exactly one of the {} outputs of `{}`",
+                    i.name,
+                    target.params.outputs.len(),
+                    alias.instruction
+                ));
+            }
+
+            // Follow the chain of aliases and report each cycle once, starting from its smallest name.
+            let mut path = vec![i.name.as_str()];
+            while let Some(next) = alias_target(path.last().unwrap()) {
+                if let Some(pos) = path.iter().position(|n| *n == next) {
+                    if pos == 0 && path.iter().all(|n| *n >= i.name.as_str()) {
+                        errors.push(format!(
+                            "Cyclic instruction alias in machine {}: {} -> {}",
+                            ctx,
+                            path.join(" -> "),
+                            next
+                        ));
+                    }
+                    break;
+                }
+                path.push(next);
+            }
+        }
+        errors
+    }
+
     fn check_link_declaration(
         &self,
         source: SourceRef,
         flag: Expression,
         to: CallableRef,
+        is_permutation: bool,
     ) -> Result<LinkDefinitionStatement, Vec<String>> {
         let mut err = vec![];
 
@@ -388,7 +501,12 @@ impl TypeChecker {
         });
 
         if err.is_empty() {
-            Ok(LinkDefinitionStatement { source, flag, to })
+            Ok(LinkDefinitionStatement {
+                source,
+                flag,
+                to,
+                is_permutation,
+            })
         } else {
             Err(err)
         }
@@ -496,4 +614,100 @@ machine Arith(latch, _) {
 "#;
         expect_check_str(src, Err(vec!["Operation `add` in machine ::Arith can't have an operation id because the machine does not have an operation id column"]));
     }
+
+    #[test]
+    fn instruction_alias() {
+        let src = r#"
+machine Main {
+   reg pc[@pc];
+   reg X[<=];
+   reg Y[<=];
+   reg Z[<=];
+   instr add X, Y -> Z { X + Y = Z }
+   instr inc X -> Y = add(X, 1) -> Y;
+   instr add_two X -> Y = inc(X + 1) -> Y;
+}
+"#;
+        expect_check_str(src, Ok(()));
+    }
+
+    #[test]
+    fn instruction_alias_unknown_target() {
+        let src = r#"
+machine Main {
+   reg pc[@pc];
+   reg X[<=];
+   reg Y[<=];
+   instr inc X -> Y = add(X, 1) -> Y;
+}
+"#;
+        expect_check_str(src, Err(vec!["Instruction `inc` in machine ::Main is an alias of `add`, which is not an instruction of this machine"]));
+    }
+
+    #[test]
+    fn instruction_alias_wrong_params() {
+        let src = r#"
+machine Main {
+   reg pc[@pc];
+   reg X[<=];
+   reg Y[<=];
+   reg Z[<=];
+   instr add X, Y -> Z { X + Y = Z }
+   instr inc X -> Y = add(X) -> Z;
+}
+"#;
+        expect_check_str(
+            src,
+            Err(vec![
+                "Instruction alias `inc` passes 1 inputs to `add`, which expects 2",
+                "Instruction alias `inc` has to bind each of its outputs to exactly one of the 1 outputs of `add`",
+            ]),
+        );
+    }
+
+    #[test]
+    fn cyclic_instruction_alias() {
+        let src = r#"
+machine Main {
+   reg pc[@pc];
+   reg X[<=];
+   instr foo X = bar(X);
+   instr bar X = baz(X + 1);
+   instr baz X = foo(X);
+   instr loop X = loop(X);
+}
+"#;
+        expect_check_str(
+            src,
+            Err(vec![
+                "Cyclic instruction alias in machine ::Main: bar -> baz -> foo -> bar",
+                "Cyclic instruction alias in machine ::Main: loop -> loop",
+            ]),
+        );
+    }
+
+    #[test]
+    fn register_ranges() {
+        let src = r#"
+machine Main {
+   reg pc[@pc];
+   reg X[<=]: range(0, 1);
+   reg A: range(0, 2**8 - 1);
+   reg B: bits(0);
+   reg C: range(1, 2);
+   reg D: range(0, N);
+   reg E: range(3, 2);
+}
+"#;
+        expect_check_str(
+            src,
+            Err(vec![
+                "Register `X` in machine ::Main cannot have a range as it is not a regular register",
+                "Invalid bit width in register range: 0 for register `B`",
+                "Range range(1, 2) of register `C` in machine ::Main has to include zero",
+                "Expected a non-negative compile-time constant in register range, but got N for register `D`",
+                "Register range range(3, 2) is empty for register `E`",
+            ]),
+        );
+    }
 }
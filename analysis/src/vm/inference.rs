@@ -1,8 +1,11 @@
 //! Infer assignment registers in asm statements
 
 use powdr_ast::{
-    asm_analysis::{AnalysisASMFile, Expression, FunctionStatement, Item, Machine},
-    parsed::asm::AssignmentRegister,
+    asm_analysis::{
+        AnalysisASMFile, Expression, FunctionStatement, InstructionDefinitionStatement, Item,
+        Machine,
+    },
+    parsed::asm::{AssignmentRegister, InstructionBody},
 };
 
 pub fn infer(file: AnalysisASMFile) -> Result<AnalysisASMFile, Vec<String>> {
@@ -45,20 +48,9 @@ fn infer_machine(mut machine: Machine) -> Result<Machine, Vec<String>> {
                             } else {
                                 panic!("Only instructions allowed.");
                             };
-                        let def = machine
-                            .instructions
-                            .iter()
-                            .find(|i| i.name == *instr_name)
-                            .unwrap_or_else(|| panic!("invalid instruction: {}", instr_name));
-
-                        def.instruction
-                            .params
-                            .outputs
-                            .iter()
-                            .map(|o| {
-                                assert!(o.ty.is_none());
-                                AssignmentRegister::Register(o.name.clone())
-                            })
+                        output_registers(&machine.instructions, instr_name)
+                            .into_iter()
+                            .map(AssignmentRegister::Register)
                             .collect::<Vec<_>>()
                     }
                     _ => vec![AssignmentRegister::Wildcard; a.lhs_with_reg.len()],
@@ -95,6 +87,35 @@ fn infer_machine(mut machine: Machine) -> Result<Machine, Vec<String>> {
     }
 }
 
+/// Returns the assignment registers the instruction writes its outputs to.
+/// For an alias, these are the output registers of the aliased instruction.
+fn output_registers(
+    instructions: &[InstructionDefinitionStatement],
+    instr_name: &str,
+) -> Vec<String> {
+    let def = instructions
+        .iter()
+        .find(|i| i.name == *instr_name)
+        .unwrap_or_else(|| panic!("invalid instruction: {}", instr_name));
+
+    let outputs = def.instruction.params.outputs.iter().map(|o| {
+        assert!(o.ty.is_none());
+        o.name.clone()
+    });
+    match &def.instruction.body {
+        InstructionBody::Alias(alias) => {
+            let target_registers = output_registers(instructions, &alias.instruction);
+            outputs
+                .map(|o| {
+                    let index = alias.outputs.iter().position(|b| *b == o).unwrap();
+                    target_registers[index].clone()
+                })
+                .collect()
+        }
+        _ => outputs.collect(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use powdr_ast::{asm_analysis::AssignmentStatement, parsed::asm::parse_absolute_path};
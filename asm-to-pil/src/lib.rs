@@ -131,14 +131,19 @@ pub mod utils {
     ) -> RegisterDeclarationStatement {
         let ctx = ParserContext::new(None, input);
         match REGISTER_DECLARATION_PARSER.parse(&ctx, input).unwrap() {
-            MachineStatement::RegisterDeclaration(source, name, flag) => {
+            MachineStatement::RegisterDeclaration(source, name, flag, range) => {
                 let ty = match flag {
                     Some(RegisterFlag::IsAssignment) => RegisterTy::Assignment,
                     Some(RegisterFlag::IsPC) => RegisterTy::Pc,
                     Some(RegisterFlag::IsReadOnly) => RegisterTy::ReadOnly,
                     None => RegisterTy::Write,
                 };
-                RegisterDeclarationStatement { source, name, ty }
+                RegisterDeclarationStatement {
+                    source,
+                    name,
+                    ty,
+                    range: range.map(|range| range.bounds().unwrap()),
+                }
             }
             _ => unreachable!(),
         }
@@ -10,7 +10,7 @@ use powdr_ast::parsed::visitor::ExpressionVisitable;
 use powdr_ast::parsed::NamespacedPolynomialReference;
 use powdr_ast::parsed::{
     asm::{OperationId, Param, Params},
-    Expression,
+    Expression, Radix,
 };
 use powdr_ast::SourceRef;
 use powdr_number::{BigUint, FieldElement};
@@ -49,7 +49,11 @@ fn pad_return_arguments(s: &mut FunctionStatement, output_count: usize) {
     if let FunctionStatement::Return(ret) = s {
         ret.values = std::mem::take(&mut ret.values)
             .into_iter()
-            .chain(repeat(Expression::Number(0u32.into(), None)))
+            .chain(repeat(Expression::Number(
+                0u32.into(),
+                None,
+                Radix::Decimal,
+            )))
             .take(output_count)
             .collect();
     };
@@ -9,11 +9,11 @@ use powdr_ast::{
         LinkDefinitionStatement, Machine, RegisterDeclarationStatement, RegisterTy, Rom,
     },
     parsed::{
-        asm::{CallableRef, InstructionBody, Params},
+        asm::{CallableRef, InstructionAlias, InstructionBody, Params},
         build::{self, absolute_reference, direct_reference, next_reference},
         visitor::ExpressionVisitable,
         ArrayExpression, BinaryOperator, Expression, FunctionCall, FunctionDefinition,
-        LambdaExpression, MatchArm, MatchPattern, PilStatement, PolynomialName,
+        LambdaExpression, MatchArm, MatchPattern, PilStatement, PolynomialName, Radix,
         SelectedExpressions, UnaryOperator,
     },
     SourceRef,
@@ -31,6 +31,10 @@ pub fn convert_machine<T: FieldElement>(machine: Machine, rom: Option<Rom>) -> M
     ASMPILConverter::<T>::with_output_count(output_count).convert_machine(machine, rom)
 }
 
+/// The largest register range that is enforced by a product constraint instead of a lookup,
+/// chosen to keep the constraint at degree two.
+const MAX_PRODUCT_RANGE_SIZE: u32 = 2;
+
 pub enum Input {
     Register(String),
     Literal(String, LiteralKind),
@@ -58,6 +62,8 @@ struct ASMPILConverter<T> {
     rom_constant_names: Vec<String>,
     /// the maximum number of inputs in all functions
     output_count: usize,
+    /// Names of the fixed columns created to range-constrain registers.
+    range_columns: BTreeSet<String>,
     _phantom: std::marker::PhantomData<T>,
 }
 
@@ -115,7 +121,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
             SourceRef::unknown(),
             "first_step".to_string(),
             FunctionDefinition::Array(
-                ArrayExpression::value(vec![Expression::Number(1u32.into(), None)])
+                ArrayExpression::value(vec![Expression::Number(1u32.into(), None, Radix::Decimal)])
                     .pad_with_zeroes(),
             ),
         ));
@@ -271,7 +277,12 @@ impl<T: FieldElement> ASMPILConverter<T> {
 
     fn handle_register_declaration(
         &mut self,
-        RegisterDeclarationStatement { source, ty, name }: RegisterDeclarationStatement,
+        RegisterDeclarationStatement {
+            source,
+            ty,
+            name,
+            range,
+        }: RegisterDeclarationStatement,
     ) {
         let mut conditioned_updates = vec![];
         let mut default_update = None;
@@ -313,7 +324,73 @@ impl<T: FieldElement> ASMPILConverter<T> {
                 ty,
             },
         );
-        self.pil.push(witness_column(source, name, None));
+        self.pil.push(witness_column(source.clone(), &name, None));
+        if let Some((min, max)) = range {
+            self.constrain_register_range(source, &name, min, max);
+        }
+    }
+
+    /// Constrains the register to values between `min` and `max` (inclusive).
+    /// Tiny ranges use a product constraint, all others a lookup into a fixed column
+    /// that is shared by all registers with the same range.
+    fn constrain_register_range(
+        &mut self,
+        source: SourceRef,
+        name: &str,
+        min: BigUint,
+        max: BigUint,
+    ) {
+        let size = &max - &min + BigUint::from(1u32);
+        if size <= BigUint::from(MAX_PRODUCT_RANGE_SIZE) {
+            // (reg - min) * (reg - min - 1) * ... * (reg - max) = 0
+            let product = (0..MAX_PRODUCT_RANGE_SIZE)
+                .map(|i| &min + BigUint::from(i))
+                .take_while(|value| value <= &max)
+                .map(|value| match value == BigUint::from(0u32) {
+                    true => direct_reference(name),
+                    false => direct_reference(name) - value.into(),
+                })
+                .reduce(|acc, factor| acc * factor)
+                .unwrap();
+            self.pil.push(PilStatement::Expression(
+                source,
+                build::identity(product, 0.into()),
+            ));
+        } else {
+            let column = format!("range_{min}_{max}");
+            if self.range_columns.insert(column.clone()) {
+                // col fixed range_<min>_<max>(i) { min + i % size }
+                let mut value = Expression::BinaryOperation(
+                    Box::new(direct_reference("i")),
+                    BinaryOperator::Mod,
+                    Box::new(size.into()),
+                );
+                if min != BigUint::from(0u32) {
+                    value = Expression::from(min) + value;
+                }
+                self.pil.push(PilStatement::PolynomialConstantDefinition(
+                    source.clone(),
+                    column.clone(),
+                    FunctionDefinition::Expression(Expression::LambdaExpression(
+                        LambdaExpression {
+                            params: vec!["i".to_string()],
+                            body: Box::new(value),
+                        },
+                    )),
+                ));
+            }
+            self.pil.push(PilStatement::PlookupIdentity(
+                source,
+                SelectedExpressions {
+                    selector: None,
+                    expressions: vec![direct_reference(name)],
+                },
+                SelectedExpressions {
+                    selector: None,
+                    expressions: vec![direct_reference(column)],
+                },
+            ));
+        }
     }
 
     fn handle_instruction_def(&mut self, input: &mut Machine, s: InstructionDefinitionStatement) {
@@ -322,6 +399,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
         self.create_witness_fixed_pair(s.source.clone(), &instruction_flag);
 
         let params = s.instruction.params;
+        let mut alias = None;
 
         match s.instruction.body {
             InstructionBody::Local(body) => self.handle_local_instruction_def(
@@ -340,6 +418,20 @@ impl<T: FieldElement> ASMPILConverter<T> {
                 );
                 input.links.push(link);
             }
+            InstructionBody::Alias(a) => {
+                // The alias reuses the constraints of the aliased instruction,
+                // so its flag has to imply the flag of the aliased instruction.
+                self.pil.push(PilStatement::Expression(
+                    s.source,
+                    build::identity(
+                        direct_reference(&instruction_flag)
+                            * (Expression::from(1)
+                                - direct_reference(format!("instr_{}", a.instruction))),
+                        0.into(),
+                    ),
+                ));
+                alias = Some(a);
+            }
         }
 
         let inputs: Vec<_> = params
@@ -361,7 +453,11 @@ impl<T: FieldElement> ASMPILConverter<T> {
 
         let outputs = params.outputs.into_iter().map(|param| param.name).collect();
 
-        let instruction = Instruction { inputs, outputs };
+        let instruction = Instruction {
+            inputs,
+            outputs,
+            alias,
+        };
         self.instructions.insert(instruction_name, instruction);
     }
 
@@ -568,6 +664,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
             source,
             flag: direct_reference(flag),
             to: callable,
+            is_permutation: false,
         }
     }
 
@@ -602,11 +699,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
             panic!("Expected instruction name");
         };
         let instr_name = reference.try_to_identifier().unwrap();
-        let instr = &self
-            .instructions
-            .get(instr_name)
-            .unwrap_or_else(|| panic!("Instruction not found: {instr_name}"));
-        let output = instr.outputs.clone();
+        let output = self.output_registers(instr_name);
 
         for (o, (_, r)) in output.iter().zip(lhs_with_regs.iter()) {
             assert!(
@@ -631,6 +724,43 @@ impl<T: FieldElement> ASMPILConverter<T> {
             instr_name
         );
 
+        if let Some(alias) = &instr.alias {
+            // Call the aliased instruction with the arguments bound to the parameters
+            // of the alias and additionally set the flag of the alias.
+            let (inputs, outputs) = args.split_at(instr.inputs.len());
+            let substitutions: HashMap<_, _> = instr
+                .inputs
+                .iter()
+                .map(|input| match input {
+                    Input::Register(name) | Input::Literal(name, _) => name,
+                })
+                .zip(inputs)
+                .chain(instr.outputs.iter().zip(outputs))
+                .collect();
+            let target_args = alias
+                .inputs
+                .iter()
+                .cloned()
+                .map(|mut e| {
+                    e.post_visit_expressions_mut(&mut |e| {
+                        if let Expression::Reference(r) = e {
+                            if let Some(arg) =
+                                r.try_to_identifier().and_then(|n| substitutions.get(n))
+                            {
+                                *e = (*arg).clone();
+                            }
+                        }
+                    });
+                    e
+                })
+                .chain(alias.outputs.iter().map(|o| substitutions[o].clone()))
+                .collect();
+            let target = alias.instruction.clone();
+            let mut line = self.handle_instruction(target, target_args);
+            line.instructions.push((instr_name, vec![]));
+            return line;
+        }
+
         let mut args = args.into_iter();
 
         let (value, instruction_literal_args): (BTreeMap<_, _>, Vec<_>) =
@@ -654,7 +784,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
                         }
                         Input::Literal(_, LiteralKind::UnsignedConstant) => {
                             // TODO evaluate expression
-                            if let Expression::Number(n, _) = a {
+                            if let Expression::Number(n, _, _) = a {
                                 let half_modulus = T::modulus().to_arbitrary_integer() / BigUint::from(2u64);
                                 assert!(n < half_modulus, "Number passed to unsigned parameter is negative or too large: {n}");
                                 instruction_literal_arg.push(InstructionLiteralArg::Number(
@@ -666,13 +796,13 @@ impl<T: FieldElement> ASMPILConverter<T> {
                         }
                         Input::Literal(_, LiteralKind::SignedConstant) => {
                             // TODO evaluate expression
-                            if let Expression::Number(n, _) = a {
+                            if let Expression::Number(n, _, _) = a {
                                 instruction_literal_arg.push(InstructionLiteralArg::Number(
                                     T::checked_from(n).unwrap(),
                                 ));
                             } else if let Expression::UnaryOperation(UnaryOperator::Minus, expr) = a
                             {
-                                if let Expression::Number(n, _) = *expr {
+                                if let Expression::Number(n, _, _) = *expr {
                                     instruction_literal_arg.push(InstructionLiteralArg::Number(
                                         -T::checked_from(n).unwrap(),
                                     ))
@@ -712,6 +842,29 @@ impl<T: FieldElement> ASMPILConverter<T> {
         }
     }
 
+    /// Returns the assignment registers the instruction writes its outputs to.
+    /// For an alias, these are the output registers of the aliased instruction.
+    fn output_registers(&self, instr_name: &str) -> Vec<String> {
+        let instr = &self
+            .instructions
+            .get(instr_name)
+            .unwrap_or_else(|| panic!("Instruction not found: {instr_name}"));
+        match &instr.alias {
+            Some(alias) => {
+                let target_registers = self.output_registers(&alias.instruction);
+                instr
+                    .outputs
+                    .iter()
+                    .map(|o| {
+                        let index = alias.outputs.iter().position(|b| b == o).unwrap();
+                        target_registers[index].clone()
+                    })
+                    .collect()
+            }
+            None => instr.outputs.clone(),
+        }
+    }
+
     fn process_assignment_value(&self, value: Expression) -> Vec<(T, AffineExpressionComponent)> {
         match value {
             Expression::PublicReference(_) => panic!(),
@@ -722,7 +875,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
                 let name = reference.try_to_identifier().unwrap();
                 vec![(1.into(), AffineExpressionComponent::Register(name.clone()))]
             }
-            Expression::Number(value, _) => {
+            Expression::Number(value, _, _) => {
                 vec![(
                     T::try_from(value).unwrap(),
                     AffineExpressionComponent::Constant,
@@ -733,6 +886,7 @@ impl<T: FieldElement> ASMPILConverter<T> {
             Expression::ArrayLiteral(_) => panic!(),
             Expression::MatchExpression(_, _) => panic!(),
             Expression::IfExpression(_) => panic!(),
+            Expression::BlockExpression(_) => panic!(),
             Expression::FreeInput(expr) => {
                 vec![(1.into(), AffineExpressionComponent::FreeInput(*expr))]
             }
@@ -916,7 +1070,9 @@ impl<T: FieldElement> ASMPILConverter<T> {
                                 .get_mut(assign_reg)
                                 .unwrap()
                                 .push(MatchArm {
-                                    pattern: MatchPattern::Pattern(BigUint::from(i as u64).into()),
+                                    patterns: vec![MatchPattern::Pattern(
+                                        BigUint::from(i as u64).into(),
+                                    )],
                                     value: expr.clone(),
                                 });
                         }
@@ -1137,6 +1293,8 @@ impl Register {
 struct Instruction {
     inputs: Vec<Input>,
     outputs: Vec<String>,
+    /// The instruction this one is an alias of, if any.
+    alias: Option<InstructionAlias>,
 }
 
 impl Instruction {
@@ -4,26 +4,57 @@
 //! Do not use this to re-generate PIL files!
 
 use std::{
-    fmt::{Display, Formatter, Result},
-    str::FromStr,
+    fmt::{self, Display, Formatter, Result},
+    io,
 };
 
 use itertools::Itertools;
 
 use self::parsed::{
-    asm::{AbsoluteSymbolPath, SymbolPath},
-    display::format_type_scheme_around_name,
+    asm::AbsoluteSymbolPath,
+    display::{format_type_scheme_around_name, quote},
 };
 
 use super::*;
 
 impl<T: Display> Display for Analyzed<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let alternate = f.alternate();
+        self.write(f, false, alternate)
+    }
+}
+
+/// Displays an analyzed file with comments about the origin of the statements,
+/// see `Analyzed::display_with_sources`.
+pub struct DisplayWithSources<'a, T>(&'a Analyzed<T>);
+
+impl<'a, T: Display> Display for DisplayWithSources<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let alternate = f.alternate();
+        self.0.write(f, true, alternate)
+    }
+}
+
+impl<T: Display> Analyzed<T> {
+    /// Returns a value that displays like the analyzed file, but with a comment
+    /// `// from file:line` above each group of statements with the same origin.
+    pub fn display_with_sources(&self) -> DisplayWithSources<'_, T> {
+        DisplayWithSources(self)
+    }
+
+    /// Writes the analyzed file to `w`, producing the same output as `to_string()`
+    /// without building it in memory first.
+    pub fn write_pil<W: io::Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        crate::write_io(w, |f| self.write(f, false, false))
+    }
+
+    /// Writes the analyzed file. `alternate` corresponds to the `{:#}` format,
+    /// which adds the provenance of identities.
+    fn write<W: fmt::Write>(&self, f: &mut W, with_sources: bool, alternate: bool) -> Result {
         let degree = self.degree.unwrap_or_default();
         let mut current_namespace = AbsoluteSymbolPath::default();
-        let mut update_namespace = |name: &str, f: &mut Formatter<'_>| {
-            let mut namespace =
-                AbsoluteSymbolPath::default().join(SymbolPath::from_str(name).unwrap());
+        let mut update_namespace = |name: &AbsoluteSymbolPath, f: &mut W| {
+            let mut namespace = name.clone();
             let name = namespace.pop().unwrap();
             if namespace != current_namespace {
                 current_namespace = namespace;
@@ -35,12 +66,27 @@ impl<T: Display> Display for Analyzed<T> {
             };
             Ok((name, !current_namespace.is_empty()))
         };
+        let mut current_origin = None;
+        let mut write_origin = |source: &SourceRef, f: &mut W| {
+            let origin = (source.file.clone(), source.line);
+            if with_sources && current_origin.as_ref() != Some(&origin) {
+                writeln!(
+                    f,
+                    "// from {}:{}",
+                    source.file.as_deref().unwrap_or("input"),
+                    source.line
+                )?;
+                current_origin = Some(origin);
+            }
+            Ok(())
+        };
 
         for statement in &self.source_order {
             match statement {
                 StatementIdentifier::Definition(name) => {
                     if let Some((symbol, definition)) = self.definitions.get(name) {
                         let (name, is_local) = update_namespace(name, f)?;
+                        write_origin(&symbol.source, f)?;
                         match symbol.kind {
                             SymbolKind::Poly(poly_type) => {
                                 let kind = match &poly_type {
@@ -109,6 +155,7 @@ impl<T: Display> Display for Analyzed<T> {
                         }
                     } else if let Some((symbol, definition)) = self.intermediate_columns.get(name) {
                         let (name, _) = update_namespace(name, f)?;
+                        write_origin(&symbol.source, f)?;
                         assert_eq!(symbol.kind, SymbolKind::Poly(PolynomialType::Intermediate));
                         if let Some(length) = symbol.length {
                             writeln!(
@@ -126,19 +173,29 @@ impl<T: Display> Display for Analyzed<T> {
                 }
                 StatementIdentifier::PublicDeclaration(name) => {
                     let decl = &self.public_declarations[name];
-                    let (name, is_local) = update_namespace(&decl.name, f)?;
+                    let (name, is_local) = update_namespace(name, f)?;
+                    write_origin(&decl.source, f)?;
                     let indentation = if is_local { "    " } else { "" };
-                    writeln!(
-                        f,
-                        "{indentation}public {name} = {}{}({});",
-                        decl.polynomial,
-                        decl.array_index
-                            .map(|i| format!("[{i}]"))
-                            .unwrap_or_default(),
-                        decl.index
-                    )?;
+                    write!(f, "{indentation}public {name} = {}", decl.polynomial)?;
+                    if let Some(i) = decl.array_index {
+                        write!(f, "[{i}]")?;
+                    }
+                    writeln!(f, "({});", decl.index)?;
+                }
+                StatementIdentifier::Identity(i) => {
+                    write_origin(&self.identities[*i].source, f)?;
+                    write!(f, "    {}", &self.identities[*i])?;
+                    // In alternate mode, print where the identity originates from.
+                    match self.identity_provenance(*i).filter(|_| alternate) {
+                        Some(provenance) => writeln!(f, " // {provenance}")?,
+                        None => writeln!(f)?,
+                    }
                 }
-                StatementIdentifier::Identity(i) => writeln!(f, "    {}", &self.identities[*i])?,
+            }
+        }
+        if alternate {
+            for provenance in &self.dead_identities {
+                writeln!(f, "// removed because of zero selector: {provenance}")?;
             }
         }
 
@@ -152,6 +209,7 @@ impl Display for FunctionValueDefinition {
             FunctionValueDefinition::Array(items) => {
                 write!(f, " = {}", items.iter().format(" + "))
             }
+            FunctionValueDefinition::Sparse(sparse) => write!(f, " = {sparse}"),
             FunctionValueDefinition::Query(e) => format_outer_function(e, Some("query"), f),
             FunctionValueDefinition::Expression(TypedExpression {
                 e,
@@ -200,12 +258,31 @@ impl Display for RepeatedArray {
     }
 }
 
+impl Display for SparseArray {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{{ ")?;
+        for (row, value) in &self.entries {
+            write!(f, "{row}: {value}, ")?;
+        }
+        write!(f, "_: {} }}", self.default)
+    }
+}
+
 impl Display for Identity<Expression> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if let Some(label) = &self.label {
+            write!(f, "#[label({})] ", quote(label))?;
+        }
         match self.kind {
             IdentityKind::Polynomial => {
                 let expression = self.expression_for_poly_id();
-                if let Expression::BinaryOperation(left, BinaryOperator::Sub, right) = expression {
+                // Before condensation, the expression is a constraint `left = right`.
+                if let Expression::BinaryOperation(
+                    left,
+                    BinaryOperator::Sub | BinaryOperator::Identity,
+                    right,
+                ) = expression
+                {
                     write!(f, "{left} = {right};")
                 } else {
                     write!(f, "{expression} = 0;")
@@ -220,6 +297,9 @@ impl Display for Identity<Expression> {
 
 impl<T: Display> Display for Identity<AlgebraicExpression<T>> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if let Some(label) = &self.label {
+            write!(f, "#[label({})] ", quote(label))?;
+        }
         match self.kind {
             IdentityKind::Polynomial => {
                 let expression = self.expression_for_poly_id();
@@ -241,6 +321,55 @@ impl<T: Display> Display for Identity<AlgebraicExpression<T>> {
     }
 }
 
+impl Display for IdentityKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let name = match self {
+            IdentityKind::Polynomial => "polynomial",
+            IdentityKind::Plookup => "plookup",
+            IdentityKind::Permutation => "permutation",
+            IdentityKind::Connect => "connect",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl Display for IdentityProvenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "identity {} at {}:{}:{}",
+            self.statement_index,
+            self.source.file.as_deref().unwrap_or("input"),
+            self.source.line,
+            self.source.col
+        )?;
+        if let Some(index) = self.array_index {
+            write!(f, ", element {index}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DuplicateIdentity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let format_source = |s: &SourceRef| {
+            format!(
+                "{}:{}:{}",
+                s.file.as_deref().unwrap_or("input"),
+                s.line,
+                s.col
+            )
+        };
+        write!(
+            f,
+            "{}: removed {:?} identity identical to the one at {}",
+            format_source(&self.source),
+            self.kind,
+            format_source(&self.duplicate_of)
+        )
+    }
+}
+
 impl<Expr: Display> Display for SelectedExpressions<Expr> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(
@@ -0,0 +1,466 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+
+use crate::parsed::asm::AbsoluteSymbolPath;
+use crate::parsed::visitor::ExpressionVisitable;
+
+use super::visitor::AlgebraicExpressionVisitable;
+use super::{
+    AlgebraicExpression, Analyzed, Expression, PolyID, PolynomialType, Reference,
+    StatementIdentifier, Symbol, SymbolKind,
+};
+
+/// An error when extracting a namespace from an analyzed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractError {
+    /// There is nothing in the namespace.
+    UnknownNamespace(String),
+    /// The namespace references these columns or public declarations outside of it.
+    DanglingReferences(Vec<String>),
+}
+
+impl Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::UnknownNamespace(name) => write!(f, "Namespace {name} not found."),
+            ExtractError::DanglingReferences(names) => write!(
+                f,
+                "The namespace references symbols outside of it: {}",
+                names.iter().format(", ")
+            ),
+        }
+    }
+}
+
+/// How `Analyzed::extract_namespace_with` treats references to columns outside of the namespace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DanglingReferences {
+    /// Fail with `ExtractError::DanglingReferences`.
+    #[default]
+    Error,
+    /// Declare the referenced columns as unconstrained witness columns, keeping their names.
+    ToWitness,
+}
+
+impl<T: Clone> Analyzed<T> {
+    /// Returns a new analyzed file that only contains the definitions, intermediate columns,
+    /// public declarations and identities of the given namespace, together with the
+    /// symbols outside of it (other than columns) that these definitions use.
+    /// Identities belong to the namespace of the definition or public declaration
+    /// preceding them in the source order, and so do public declarations outside of
+    /// any namespace.
+    /// The IDs of symbols, public declarations and identities are re-allocated.
+    /// Fails if the namespace references columns or public declarations outside of it.
+    pub fn extract_namespace(&self, namespace: &str) -> Result<Self, ExtractError> {
+        self.extract_namespace_with(namespace, DanglingReferences::Error)
+    }
+
+    /// Like `extract_namespace`, but `dangling` determines how references to columns
+    /// outside of the namespace are handled. References to public declarations outside
+    /// of the namespace are always an error.
+    pub fn extract_namespace_with(
+        &self,
+        namespace: &str,
+        dangling: DanglingReferences,
+    ) -> Result<Self, ExtractError> {
+        let unknown = || ExtractError::UnknownNamespace(namespace.to_string());
+        let namespace_path = AbsoluteSymbolPath::from_dotted_string(namespace);
+        let in_namespace = |name: &AbsoluteSymbolPath| namespace_of(name) == namespace_path;
+
+        let mut current_namespace = AbsoluteSymbolPath::default();
+        let mut identities = BTreeSet::new();
+        let mut publics = BTreeSet::new();
+        for statement in &self.source_order {
+            match statement {
+                StatementIdentifier::Definition(name) => current_namespace = namespace_of(name),
+                StatementIdentifier::PublicDeclaration(name) => {
+                    let public_namespace = namespace_of(name);
+                    if !public_namespace.is_empty() {
+                        current_namespace = public_namespace;
+                    }
+                    if current_namespace == namespace_path {
+                        publics.insert(name.clone());
+                    }
+                }
+                StatementIdentifier::Identity(index) => {
+                    if current_namespace == namespace_path {
+                        identities.insert(*index);
+                    }
+                }
+            }
+        }
+        let mut symbols: BTreeSet<AbsoluteSymbolPath> = self
+            .definitions
+            .keys()
+            .chain(self.intermediate_columns.keys())
+            .filter(|name| in_namespace(name))
+            .cloned()
+            .collect();
+        if symbols.is_empty() && publics.is_empty() && identities.is_empty() {
+            return Err(unknown());
+        }
+
+        let is_column = |name: &AbsoluteSymbolPath| {
+            self.intermediate_columns.contains_key(name)
+                || matches!(
+                    self.definitions.get(name),
+                    Some((
+                        Symbol {
+                            kind: SymbolKind::Poly(_),
+                            ..
+                        },
+                        _
+                    ))
+                )
+        };
+        // Add the non-column symbols used by the definitions (transitively).
+        let mut dangling_columns = BTreeSet::new();
+        let mut to_visit = symbols.iter().cloned().collect::<Vec<_>>();
+        while let Some(name) = to_visit.pop() {
+            let Some((_, Some(value))) = self.definitions.get(&name) else {
+                continue;
+            };
+            let mut referenced = vec![];
+            value.pre_visit_expressions(&mut |e| {
+                if let Expression::Reference(Reference::Poly(reference)) = e {
+                    referenced.push(AbsoluteSymbolPath::from_dotted_string(&reference.name));
+                }
+            });
+            for name in referenced {
+                if symbols.contains(&name) {
+                    continue;
+                }
+                if is_column(&name) {
+                    dangling_columns.insert(name);
+                } else if self.definitions.contains_key(&name) {
+                    symbols.insert(name.clone());
+                    to_visit.push(name);
+                }
+            }
+        }
+
+        let column_names: HashMap<PolyID, &AbsoluteSymbolPath> = self
+            .definitions
+            .iter()
+            .map(|(name, (symbol, _))| (name, symbol))
+            .chain(
+                self.intermediate_columns
+                    .iter()
+                    .map(|(name, (symbol, _))| (name, symbol)),
+            )
+            .filter(|(_, symbol)| matches!(symbol.kind, SymbolKind::Poly(_)))
+            .flat_map(|(name, symbol)| symbol.array_elements().map(move |(_, id)| (id, name)))
+            .collect();
+        let mut dangling_publics = BTreeSet::new();
+        let mut check_references = |e: &AlgebraicExpression<T>| match e {
+            AlgebraicExpression::Reference(reference) => {
+                let name = column_names[&reference.poly_id];
+                if !symbols.contains(name) {
+                    dangling_columns.insert(name.clone());
+                }
+            }
+            AlgebraicExpression::PublicReference(name)
+                if !publics.contains(&AbsoluteSymbolPath::from_dotted_string(name)) =>
+            {
+                dangling_publics.insert(name.clone());
+            }
+            _ => {}
+        };
+        for index in &identities {
+            self.identities[*index].pre_visit_algebraic_expressions(&mut check_references);
+        }
+        for (name, (_, values)) in &self.intermediate_columns {
+            if symbols.contains(name) {
+                values
+                    .iter()
+                    .for_each(|e| e.pre_visit_algebraic_expressions(&mut check_references));
+            }
+        }
+        for name in &publics {
+            let column = AbsoluteSymbolPath::from_dotted_string(
+                &self.public_declarations[name].polynomial.name,
+            );
+            if !symbols.contains(&column) {
+                dangling_columns.insert(column);
+            }
+        }
+        if !dangling_publics.is_empty()
+            || (!dangling_columns.is_empty() && dangling == DanglingReferences::Error)
+        {
+            return Err(ExtractError::DanglingReferences(
+                dangling_columns
+                    .iter()
+                    .map(|name| name.to_dotted_string())
+                    .chain(dangling_publics)
+                    .sorted()
+                    .collect(),
+            ));
+        }
+
+        let mut definitions: HashMap<_, _> = self
+            .definitions
+            .iter()
+            .filter(|(name, _)| symbols.contains(*name))
+            .map(|(name, definition)| (name.clone(), definition.clone()))
+            .collect();
+        for name in &dangling_columns {
+            let symbol = self.symbol(name);
+            let symbol = Symbol {
+                kind: SymbolKind::Poly(PolynomialType::Committed),
+                ..symbol.clone()
+            };
+            definitions.insert(name.clone(), (symbol, None));
+        }
+        let mut result = Analyzed {
+            degree: self.degree,
+            definitions,
+            public_declarations: self
+                .public_declarations
+                .iter()
+                .filter(|(name, _)| publics.contains(*name))
+                .map(|(name, declaration)| (name.clone(), declaration.clone()))
+                .collect(),
+            intermediate_columns: self
+                .intermediate_columns
+                .iter()
+                .filter(|(name, _)| symbols.contains(*name))
+                .map(|(name, definition)| (name.clone(), definition.clone()))
+                .collect(),
+            identities: self.identities.clone(),
+            identity_provenance: self.identity_provenance.clone(),
+            // We do not know which namespace these belonged to.
+            dead_identities: vec![],
+            // The converted columns come first, so that their namespace is left
+            // before the first statement of the extracted namespace.
+            source_order: dangling_columns
+                .iter()
+                .map(|name| StatementIdentifier::Definition(name.clone()))
+                .chain(
+                    self.source_order
+                        .iter()
+                        .filter(|statement| match statement {
+                            StatementIdentifier::Definition(name) => symbols.contains(name),
+                            StatementIdentifier::PublicDeclaration(name) => publics.contains(name),
+                            StatementIdentifier::Identity(_) => true,
+                        })
+                        .cloned(),
+                )
+                .collect(),
+            names: Default::default(),
+            identity_index: Default::default(),
+        };
+        result.remove_identities(
+            &(0..self.identities.len())
+                .filter(|index| !identities.contains(index))
+                .collect(),
+        );
+
+        // Re-allocate the symbol IDs per kind in source order.
+        let mut next_ids: BTreeMap<SymbolKind, u64> = BTreeMap::new();
+        let mut poly_ids: HashMap<PolyID, PolyID> = HashMap::new();
+        for statement in &result.source_order {
+            let StatementIdentifier::Definition(name) = statement else {
+                continue;
+            };
+            let symbol = match result.definitions.get_mut(name) {
+                Some((symbol, _)) => symbol,
+                None => &mut result.intermediate_columns.get_mut(name).unwrap().0,
+            };
+            let next_id = next_ids.entry(symbol.kind).or_default();
+            symbol.id = *next_id;
+            *next_id += symbol.length.unwrap_or(1);
+            let old = self.symbol(name);
+            if let SymbolKind::Poly(_) = old.kind {
+                poly_ids.insert(old.into(), (&*symbol).into());
+                poly_ids.extend(
+                    old.array_elements()
+                        .map(|(_, id)| id)
+                        .zip(symbol.array_elements().map(|(_, id)| id)),
+                );
+            }
+        }
+        let new_poly_id = |id: PolyID| poly_ids.get(&id).copied().unwrap_or(id);
+        result.post_visit_algebraic_expressions_mut(&mut |e| {
+            if let AlgebraicExpression::Reference(reference) = e {
+                reference.poly_id = new_poly_id(reference.poly_id);
+            }
+        });
+        result.post_visit_expressions_in_definitions_mut(&mut |e| {
+            if let Expression::Reference(Reference::Poly(reference)) = e {
+                reference.poly_id = reference.poly_id.map(new_poly_id);
+            }
+        });
+        let public_ids: HashMap<u64, u64> = result
+            .public_declarations
+            .values()
+            .map(|declaration| declaration.id)
+            .sorted()
+            .zip(0..)
+            .collect();
+        for declaration in result.public_declarations.values_mut() {
+            declaration.id = public_ids[&declaration.id];
+            declaration.polynomial.poly_id = declaration.polynomial.poly_id.map(new_poly_id);
+        }
+        result.intern_names();
+        Ok(result)
+    }
+
+    /// Returns the symbol of a definition or intermediate column.
+    fn symbol(&self, name: &AbsoluteSymbolPath) -> &Symbol {
+        self.definitions
+            .get(name)
+            .map(|(symbol, _)| symbol)
+            .unwrap_or_else(|| &self.intermediate_columns[name].0)
+    }
+}
+
+/// Returns the namespace of a symbol, which is the root for symbols outside of any namespace.
+fn namespace_of(name: &AbsoluteSymbolPath) -> AbsoluteSymbolPath {
+    name.clone().parent()
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::analyzed::test_utils::*;
+    use crate::analyzed::{AlgebraicExpression, PolyID, PolynomialType};
+    use crate::parsed::types::Type;
+
+    use super::{DanglingReferences, ExtractError};
+
+    fn input() -> Pil {
+        let mut pil = new_pil(16);
+        add_let(&mut pil, "K", Type::Int, number(255));
+        add_let(&mut pil, "unused", Type::Int, number(7));
+        add_fixed(&mut pil, "Bin.BYTE", reference("K"));
+        add_column(&mut pil, "Bin.A", PolynomialType::Committed, None);
+        add_column(&mut pil, "Bin.B", PolynomialType::Committed, Some(2));
+        let sum = col(&pil, "Bin.A") + col(&pil, "Bin.B[0]");
+        add_intermediate(&mut pil, "Bin.sum", vec![sum]);
+        let identity = col(&pil, "Bin.B[1]") - col(&pil, "Bin.sum") * col(&pil, "Bin.BYTE");
+        add_identity(&mut pil, identity);
+        add_column(&mut pil, "Main.x", PolynomialType::Committed, None);
+        add_column(&mut pil, "Main.y", PolynomialType::Committed, None);
+        let identity = next(col(&pil, "Main.x")) - (col(&pil, "Main.x") + col(&pil, "Main.y"));
+        add_identity(&mut pil, identity);
+        let (y, b) = (col(&pil, "Main.y"), col(&pil, "Bin.B[0]"));
+        add_lookup(&mut pil, None, vec![y], vec![b]);
+        pil
+    }
+
+    #[test]
+    fn extract() {
+        let extracted = input().extract_namespace("Bin").unwrap();
+        let expected = r#"    let K: int = 255;
+namespace Bin(16);
+    col fixed BYTE = K;
+    col witness A;
+    col witness B[2];
+    col sum = (Bin.A + Bin.B[0]);
+    Bin.B[1] = (Bin.sum * Bin.BYTE);
+"#;
+        assert_eq!(extracted.to_string(), expected);
+        assert_eq!(extracted.identities.len(), 1);
+        assert_eq!(extracted.identities[0].id, 0);
+        let ids = extracted
+            .columns()
+            .iter()
+            .map(|c| (c.name.to_string(), c.poly_id))
+            .collect::<Vec<_>>();
+        let poly_id = |id, ptype| PolyID { id, ptype };
+        assert_eq!(
+            ids,
+            [
+                ("Bin.A".to_string(), poly_id(0, PolynomialType::Committed)),
+                ("Bin.B".to_string(), poly_id(1, PolynomialType::Committed)),
+                ("Bin.BYTE".to_string(), poly_id(0, PolynomialType::Constant)),
+                (
+                    "Bin.sum".to_string(),
+                    poly_id(0, PolynomialType::Intermediate)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn dangling_references() {
+        assert_eq!(
+            input().extract_namespace("Main"),
+            Err(ExtractError::DanglingReferences(vec!["Bin.B".to_string()]))
+        );
+    }
+
+    #[test]
+    fn dangling_references_to_witness() {
+        let extracted = input()
+            .extract_namespace_with("Main", DanglingReferences::ToWitness)
+            .unwrap();
+        let expected = r#"namespace Bin(16);
+    col witness B[2];
+namespace Main(16);
+    col witness x;
+    col witness y;
+    Main.x' = (Main.x + Main.y);
+    { Main.y } in { Bin.B[0] };
+"#;
+        assert_eq!(extracted.to_string(), expected);
+        assert_eq!(extracted.commitment_count(), 4);
+        assert_eq!(
+            extracted
+                .column_by_poly_id(PolyID {
+                    id: 1,
+                    ptype: PolynomialType::Committed
+                })
+                .unwrap()
+                .name,
+            "Bin.B"
+        );
+    }
+
+    #[test]
+    fn public_declarations() {
+        let mut pil = new_pil(16);
+        add_column(&mut pil, "Main.x", PolynomialType::Committed, None);
+        pil.add_public("Main.first", "Main.x", None, 0).unwrap();
+        let identity =
+            col(&pil, "Main.x") - AlgebraicExpression::PublicReference("Main.first".to_string());
+        add_identity(&mut pil, identity);
+        add_column(&mut pil, "Bin.A", PolynomialType::Committed, None);
+        pil.add_public("Bin.out", "Bin.A", None, 15).unwrap();
+        let identity =
+            next(col(&pil, "Bin.A")) - AlgebraicExpression::PublicReference("Bin.out".to_string());
+        add_identity(&mut pil, identity);
+
+        let extracted = pil.extract_namespace("Bin").unwrap();
+        assert_eq!(
+            extracted
+                .public_declarations
+                .keys()
+                .map(|name| name.to_dotted_string())
+                .collect::<Vec<_>>(),
+            ["Bin.out"]
+        );
+        let out = extracted.public_declaration("Bin.out").unwrap();
+        assert_eq!(out.id, 0);
+        assert_eq!(
+            out.polynomial.poly_id,
+            Some(PolyID {
+                id: 0,
+                ptype: PolynomialType::Committed
+            })
+        );
+        assert_eq!(extracted.identities.len(), 1);
+        assert_eq!(extracted.identities[0].to_string(), "Bin.A' = :Bin.out;");
+    }
+
+    #[test]
+    fn unknown_namespace() {
+        assert_eq!(
+            input().extract_namespace("Other"),
+            Err(ExtractError::UnknownNamespace("Other".to_string()))
+        );
+    }
+}
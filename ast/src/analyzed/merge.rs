@@ -0,0 +1,374 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use powdr_number::DegreeType;
+
+use crate::parsed::asm::{AbsoluteSymbolPath, SymbolPath};
+
+use super::{
+    AlgebraicExpression, Analyzed, Expression, IdentityKind, IdentityProvenance, PolyID, Reference,
+    StatementIdentifier, SymbolKind, SymbolName,
+};
+
+/// An error when merging two analyzed files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    /// Both files have a degree, but they differ.
+    DegreeMismatch(DegreeType, DegreeType),
+    /// A symbol or public declaration outside of any namespace is defined in both files,
+    /// and the definitions cannot be shared.
+    Conflict(String),
+}
+
+impl Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::DegreeMismatch(degree, other) => {
+                write!(f, "Cannot merge files of degree {degree} and {other}.")
+            }
+            MergeError::Conflict(name) => {
+                write!(f, "Symbol {name} is defined differently in both files.")
+            }
+        }
+    }
+}
+
+impl<T> Analyzed<T> {
+    /// Adds the definitions, identities and public declarations of `other` to this file.
+    /// All symbols and public declarations of `other` inside a namespace are moved into the
+    /// module `prefix`, i.e. `N.x` becomes `prefix::N::x`, and the IDs of its columns, public
+    /// declarations and identities are shifted to come after the ones of this file.
+    /// Symbols outside of any namespace keep their names, which means that constants
+    /// defined identically in both files are shared. Any other symbol (or public declaration)
+    /// defined in both files is an error.
+    pub fn merge(mut self, mut other: Analyzed<T>, prefix: &str) -> Result<Self, MergeError> {
+        let degree = match (self.degree, other.degree) {
+            (Some(degree), Some(other)) if degree != other => {
+                return Err(MergeError::DegreeMismatch(degree, other))
+            }
+            (degree, other) => degree.or(other),
+        };
+        let prefix = AbsoluteSymbolPath::default().join(SymbolPath::from_str(prefix).unwrap());
+        let names: HashMap<AbsoluteSymbolPath, AbsoluteSymbolPath> = other
+            .definitions
+            .keys()
+            .chain(other.intermediate_columns.keys())
+            .chain(other.public_declarations.keys())
+            .map(|name| (name.clone(), prefixed_name(&prefix, name)))
+            .collect();
+        if let Some(name) = other
+            .public_declarations
+            .keys()
+            .map(|name| &names[name])
+            .find(|name| self.public_declarations.contains_key(*name))
+        {
+            return Err(MergeError::Conflict(name.to_dotted_string()));
+        }
+        let rename_path = |name: &mut AbsoluteSymbolPath| {
+            if let Some(new) = names.get(name) {
+                *name = new.clone();
+            }
+        };
+        // Names in symbols and references are in the form of `AbsoluteSymbolPath::to_dotted_string`.
+        let rename = |name: &mut String| {
+            if let Some(new) = names.get(&AbsoluteSymbolPath::from_dotted_string(name)) {
+                *name = new.to_dotted_string();
+            }
+        };
+        // Algebraic references to array elements end in `[i]`.
+        let rename_element = |name: &mut SymbolName| {
+            let base = name.split_once('[').map_or(name.as_str(), |(base, _)| base);
+            if let Some(new) = names.get(&AbsoluteSymbolPath::from_dotted_string(base)) {
+                *name = format!("{}{}", new.to_dotted_string(), &name[base.len()..]).into();
+            }
+        };
+
+        // The IDs of symbols are counted per kind, with multiplicities for arrays.
+        let next_ids = self
+            .definitions
+            .values()
+            .map(|(symbol, _)| symbol)
+            .chain(self.intermediate_columns.values().map(|(symbol, _)| symbol))
+            .fold(BTreeMap::<SymbolKind, u64>::new(), |mut ids, symbol| {
+                let id = ids.entry(symbol.kind).or_default();
+                *id = (*id).max(symbol.id + symbol.length.unwrap_or(1));
+                ids
+            });
+        let shift_id = |kind: SymbolKind, id: u64| id + next_ids.get(&kind).copied().unwrap_or(0);
+        let shift_poly_id = |poly_id: PolyID| PolyID {
+            id: shift_id(SymbolKind::Poly(poly_id.ptype), poly_id.id),
+            ..poly_id
+        };
+
+        other.post_visit_expressions_in_identities_mut(&mut |e| match e {
+            AlgebraicExpression::Reference(reference) => {
+                rename_element(&mut reference.name);
+                reference.poly_id = shift_poly_id(reference.poly_id);
+            }
+            AlgebraicExpression::PublicReference(name) => rename(name),
+            _ => {}
+        });
+        other.post_visit_expressions_in_definitions_mut(&mut |e| match e {
+            Expression::Reference(Reference::Poly(reference)) => {
+                rename(&mut reference.name);
+                reference.poly_id = reference.poly_id.map(shift_poly_id);
+            }
+            Expression::PublicReference(name) => rename(name),
+            _ => {}
+        });
+
+        let mut shared = HashSet::new();
+        for (mut name, (mut symbol, definition)) in other.definitions {
+            rename_path(&mut name);
+            rename(&mut symbol.absolute_name);
+            symbol.id = shift_id(symbol.kind, symbol.id);
+            if let Some((existing, existing_definition)) = self.definitions.get(&name) {
+                if matches!(symbol.kind, SymbolKind::Poly(_))
+                    || (existing.kind, existing.length) != (symbol.kind, symbol.length)
+                    || existing_definition != &definition
+                {
+                    return Err(MergeError::Conflict(name.to_dotted_string()));
+                }
+                shared.insert(name);
+            } else if self.intermediate_columns.contains_key(&name) {
+                return Err(MergeError::Conflict(name.to_dotted_string()));
+            } else {
+                self.definitions.insert(name, (symbol, definition));
+            }
+        }
+        for (mut name, (mut symbol, value)) in other.intermediate_columns {
+            rename_path(&mut name);
+            rename(&mut symbol.absolute_name);
+            symbol.id = shift_id(symbol.kind, symbol.id);
+            if self.definitions.contains_key(&name) || self.intermediate_columns.contains_key(&name)
+            {
+                return Err(MergeError::Conflict(name.to_dotted_string()));
+            }
+            self.intermediate_columns.insert(name, (symbol, value));
+        }
+
+        let next_public_id = self
+            .public_declarations
+            .values()
+            .map(|declaration| declaration.id + 1)
+            .max()
+            .unwrap_or_default();
+        for (mut name, mut declaration) in other.public_declarations {
+            rename_path(&mut name);
+            declaration.name = name.to_dotted_string();
+            declaration.id += next_public_id;
+            rename(&mut declaration.polynomial.name);
+            declaration.polynomial.poly_id = declaration.polynomial.poly_id.map(shift_poly_id);
+            self.public_declarations.insert(name, declaration);
+        }
+
+        // Identity IDs are counted per kind.
+        let mut next_identity_ids: HashMap<IdentityKind, u64> = HashMap::new();
+        for identity in &self.identities {
+            let id = next_identity_ids.entry(identity.kind).or_default();
+            *id = (*id).max(identity.id + 1);
+        }
+        let identity_offset = self.identities.len();
+        self.identity_index.invalidate();
+        self.identities
+            .extend(other.identities.into_iter().map(|mut identity| {
+                identity.id += next_identity_ids.get(&identity.kind).copied().unwrap_or(0);
+                identity
+            }));
+        let next_statement_index = self
+            .identity_provenance
+            .iter()
+            .chain(&self.dead_identities)
+            .map(|provenance| provenance.statement_index + 1)
+            .max()
+            .unwrap_or_default();
+        let shift_statement_index = |mut provenance: IdentityProvenance| {
+            provenance.statement_index += next_statement_index;
+            provenance
+        };
+        // The provenance of the identities of `other` can only be kept
+        // if it is known for all identities of this file.
+        if self.identity_provenance.len() == identity_offset {
+            self.identity_provenance.extend(
+                other
+                    .identity_provenance
+                    .into_iter()
+                    .map(shift_statement_index),
+            );
+        }
+        self.dead_identities
+            .extend(other.dead_identities.into_iter().map(shift_statement_index));
+
+        self.source_order
+            .extend(other.source_order.into_iter().filter_map(|statement| {
+                Some(match statement {
+                    StatementIdentifier::Definition(mut name) => {
+                        rename_path(&mut name);
+                        if shared.contains(&name) {
+                            return None;
+                        }
+                        StatementIdentifier::Definition(name)
+                    }
+                    StatementIdentifier::PublicDeclaration(mut name) => {
+                        rename_path(&mut name);
+                        StatementIdentifier::PublicDeclaration(name)
+                    }
+                    StatementIdentifier::Identity(index) => {
+                        StatementIdentifier::Identity(index + identity_offset)
+                    }
+                })
+            }));
+        self.degree = degree;
+        self.intern_names();
+        Ok(self)
+    }
+}
+
+/// Moves the name into the module `prefix`, unless it is not inside any namespace.
+fn prefixed_name(prefix: &AbsoluteSymbolPath, name: &AbsoluteSymbolPath) -> AbsoluteSymbolPath {
+    if name.len() == 1 {
+        name.clone()
+    } else {
+        name.parts()
+            .fold(prefix.clone(), |path, part| path.with_part(part))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::analyzed::test_utils::*;
+    use crate::analyzed::{PolyID, PolynomialType};
+    use crate::parsed::types::Type;
+
+    use super::MergeError;
+
+    fn main_pil() -> Pil {
+        let mut pil = new_pil(16);
+        add_let(&mut pil, "K", Type::Int, number(255));
+        add_fixed(&mut pil, "N.BYTE", reference("K"));
+        add_column(&mut pil, "N.a", PolynomialType::Committed, None);
+        let inter = col(&pil, "N.a") * col(&pil, "N.BYTE");
+        add_intermediate(&mut pil, "N.inter", vec![inter]);
+        let identity = col(&pil, "N.a") - col(&pil, "N.inter");
+        add_identity(&mut pil, identity);
+        pil
+    }
+
+    fn sub_pil() -> Pil {
+        let mut pil = new_pil(16);
+        add_let(&mut pil, "K", Type::Int, number(255));
+        add_column(&mut pil, "N.a", PolynomialType::Committed, None);
+        add_column(&mut pil, "N.b", PolynomialType::Committed, Some(2));
+        let double = num(2) * col(&pil, "N.a");
+        add_intermediate(&mut pil, "N.double", vec![double]);
+        let identity = col(&pil, "N.b[0]") - next(col(&pil, "N.double"));
+        add_identity(&mut pil, identity);
+        let (a, b) = (col(&pil, "N.a"), col(&pil, "N.b[1]"));
+        add_lookup(&mut pil, None, vec![a], vec![b]);
+        pil
+    }
+
+    #[test]
+    fn merge() {
+        let merged = main_pil().merge(sub_pil(), "sub").unwrap();
+        let expected = r#"    let K: int = 255;
+namespace N(16);
+    col fixed BYTE = K;
+    col witness a;
+    col inter = (N.a * N.BYTE);
+    N.a = N.inter;
+namespace sub::N(16);
+    col witness a;
+    col witness b[2];
+    col double = (2 * sub::N::a);
+    sub::N::b[0] = sub::N::double';
+    { sub::N::a } in { sub::N::b[1] };
+"#;
+        assert_eq!(merged.to_string(), expected);
+
+        assert_eq!(merged.commitment_count(), 4);
+        let ids = merged
+            .columns()
+            .iter()
+            .map(|c| (c.name.to_string(), c.poly_id))
+            .collect::<Vec<_>>();
+        let poly_id = |id, ptype| PolyID { id, ptype };
+        assert_eq!(
+            ids,
+            [
+                ("N.a".to_string(), poly_id(0, PolynomialType::Committed)),
+                (
+                    "sub::N::a".to_string(),
+                    poly_id(1, PolynomialType::Committed)
+                ),
+                (
+                    "sub::N::b".to_string(),
+                    poly_id(2, PolynomialType::Committed)
+                ),
+                ("N.BYTE".to_string(), poly_id(0, PolynomialType::Constant)),
+                (
+                    "N.inter".to_string(),
+                    poly_id(0, PolynomialType::Intermediate)
+                ),
+                (
+                    "sub::N::double".to_string(),
+                    poly_id(1, PolynomialType::Intermediate)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_public_declarations() {
+        let mut main = main_pil();
+        main.add_public("N.first", "N.a", None, 0).unwrap();
+        let mut sub = sub_pil();
+        sub.add_public("N.out", "N.b", Some(1), 2).unwrap();
+        let merged = main.clone().merge(sub, "sub").unwrap();
+        assert!(merged.public_declaration("N.first").is_some());
+        let out = merged.public_declaration("sub::N::out").unwrap();
+        assert_eq!(out.name, "sub::N::out");
+        assert_eq!(out.id, 1);
+        assert_eq!(out.referenced_poly_name(), "sub::N::b[1]");
+        assert_eq!(
+            out.polynomial.poly_id,
+            Some(PolyID {
+                id: 2,
+                ptype: PolynomialType::Committed
+            })
+        );
+        assert!(merged.to_string().contains("public out = sub::N::b[1](2);"));
+
+        // Only public declarations outside of any namespace can conflict.
+        let top_level = |row| {
+            let mut pil = new_pil(16);
+            add_column(&mut pil, "N.a", PolynomialType::Committed, None);
+            pil.add_public("first", "N.a", None, row).unwrap();
+            pil
+        };
+        assert!(main.merge(top_level(0), "sub").is_ok());
+        assert_eq!(
+            top_level(0).merge(top_level(1), "sub").unwrap_err(),
+            MergeError::Conflict("first".to_string())
+        );
+    }
+
+    #[test]
+    fn merge_errors() {
+        let mut other = new_pil(32);
+        add_column(&mut other, "N.a", PolynomialType::Committed, None);
+        assert_eq!(
+            main_pil().merge(other, "sub").unwrap_err(),
+            MergeError::DegreeMismatch(16, 32)
+        );
+        let mut other = Pil::default();
+        add_let(&mut other, "K", Type::Int, number(256));
+        assert_eq!(
+            main_pil().merge(other, "sub").unwrap_err(),
+            MergeError::Conflict("K".to_string())
+        );
+    }
+}
@@ -1,15 +1,32 @@
 mod display;
+mod extract;
+mod merge;
+mod monomials;
+mod names;
+mod public;
+mod rename;
+mod rewrite;
+mod shared;
+mod source_map;
+mod stats;
+#[cfg(test)]
+mod test_utils;
+mod usage;
 pub mod visitor;
 
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::iter::once;
 use std::ops::{self, ControlFlow};
 
-use powdr_number::{DegreeType, FieldElement};
+use itertools::Itertools;
+use powdr_number::{BigUint, DegreeType, FieldElement, LargeInt};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::parsed::asm::AbsoluteSymbolPath;
 use crate::parsed::types::{ArrayType, Type, TypeScheme};
 use crate::parsed::utils::expr_any;
 use crate::parsed::visitor::ExpressionVisitable;
@@ -18,26 +35,75 @@ pub use crate::parsed::UnaryOperator;
 use crate::parsed::{self, SelectedExpressions};
 use crate::SourceRef;
 
+use self::visitor::AlgebraicExpressionVisitable;
+pub use display::DisplayWithSources;
+pub use extract::{DanglingReferences, ExtractError};
+pub use merge::MergeError;
+pub use monomials::{Monomial, MonomialError, MonomialVariable, Polynomial};
+pub use names::{NameInterner, SymbolName};
+pub use public::PublicDeclarationError;
+pub use rename::RenameError;
+pub use shared::{ExprId, ExprNode, ExpressionArena, SharedIdentities};
+pub use source_map::{ColumnSource, IdentitySource, SourceMap};
+pub use stats::AnalysisStats;
+pub use usage::{IdentityIndex, IdentityPosition, IdentityRef};
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
 pub enum StatementIdentifier {
     /// Either an intermediate column or a definition.
-    Definition(String),
-    PublicDeclaration(String),
+    Definition(
+        #[serde(with = "dotted_name")]
+        #[schemars(with = "String")]
+        AbsoluteSymbolPath,
+    ),
+    PublicDeclaration(
+        #[serde(with = "dotted_name")]
+        #[schemars(with = "String")]
+        AbsoluteSymbolPath,
+    ),
     /// Index into the vector of identities.
     Identity(usize),
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+/// The magic bytes at the start of an analyzed file written by `Analyzed::serialize_to`.
+const BINARY_MAGIC: &[u8; 8] = b"powdrpil";
+/// The version of the binary format, to be increased whenever the serialized types change.
+const BINARY_FORMAT_VERSION: u32 = 8;
+/// The maximum length of the field modulus in the header, in bytes.
+const MAX_MODULUS_BYTES: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Analyzed<T> {
     /// The degree of all namespaces, which must match. If there are no namespaces, then `None`.
     pub degree: Option<DegreeType>,
-    pub definitions: HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
-    pub public_declarations: HashMap<String, PublicDeclaration>,
-    pub intermediate_columns: HashMap<String, (Symbol, Vec<AlgebraicExpression<T>>)>,
+    #[serde(with = "dotted_keys")]
+    #[schemars(with = "HashMap<String, (Symbol, Option<FunctionValueDefinition>)>")]
+    pub definitions: HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    #[serde(with = "dotted_keys")]
+    #[schemars(with = "HashMap<String, PublicDeclaration>")]
+    pub public_declarations: HashMap<AbsoluteSymbolPath, PublicDeclaration>,
+    #[serde(with = "dotted_keys")]
+    #[schemars(with = "HashMap<String, (Symbol, Vec<AlgebraicExpression<T>>)>")]
+    pub intermediate_columns: HashMap<AbsoluteSymbolPath, (Symbol, Vec<AlgebraicExpression<T>>)>,
     pub identities: Vec<Identity<AlgebraicExpression<T>>>,
+    /// The origin of the identities: `identity_provenance[i]` describes `identities[i]`.
+    /// Identities added after condensation have no entry, so this can be shorter
+    /// than `identities`.
+    #[serde(default)]
+    pub identity_provenance: Vec<IdentityProvenance>,
+    /// Lookups and permutations that were removed during condensation
+    /// because their selectors are zero.
+    #[serde(default)]
+    pub dead_identities: Vec<IdentityProvenance>,
     /// The order in which definitions and identities
     /// appear in the source.
     pub source_order: Vec<StatementIdentifier>,
+    /// The names of the columns referenced in identities and intermediate columns.
+    #[serde(skip)]
+    pub names: NameInterner,
+    /// The identities referencing each column, see `Analyzed::identities_referencing`.
+    #[serde(skip)]
+    pub identity_index: IdentityIndex,
 }
 
 impl<T> Analyzed<T> {
@@ -115,7 +181,9 @@ impl<T> Analyzed<T> {
             .collect()
     }
 
-    pub fn public_declarations_in_source_order(&self) -> Vec<(&String, &PublicDeclaration)> {
+    pub fn public_declarations_in_source_order(
+        &self,
+    ) -> Vec<(&AbsoluteSymbolPath, &PublicDeclaration)> {
         self.source_order
             .iter()
             .filter_map(move |statement| {
@@ -129,6 +197,79 @@ impl<T> Analyzed<T> {
             .collect()
     }
 
+    /// Returns the source of the definition, public declaration or identity
+    /// the statement refers to.
+    pub fn source_of(&self, statement: &StatementIdentifier) -> Option<&SourceRef> {
+        match statement {
+            StatementIdentifier::Definition(name) => self
+                .definitions
+                .get(name)
+                .map(|(symbol, _)| &symbol.source)
+                .or_else(|| {
+                    self.intermediate_columns
+                        .get(name)
+                        .map(|(symbol, _)| &symbol.source)
+                }),
+            StatementIdentifier::PublicDeclaration(name) => self
+                .public_declarations
+                .get(name)
+                .map(|declaration| &declaration.source),
+            StatementIdentifier::Identity(index) => {
+                self.identities.get(*index).map(|identity| &identity.source)
+            }
+        }
+    }
+
+    /// @returns all fixed, witness and intermediate columns, ordered by their type
+    /// (witness, fixed, intermediate) and by their ID within each type.
+    /// Arrays of columns are returned as a single column, see `ColumnInfo::elements`.
+    pub fn columns(&self) -> Vec<ColumnInfo<'_>> {
+        self.definitions
+            .values()
+            .map(|(symbol, _)| symbol)
+            .chain(self.intermediate_columns.values().map(|(symbol, _)| symbol))
+            .filter_map(|symbol| match symbol.kind {
+                SymbolKind::Poly(ptype) => Some(ColumnInfo {
+                    name: &symbol.absolute_name,
+                    poly_id: PolyID {
+                        id: symbol.id,
+                        ptype,
+                    },
+                    length: symbol.length,
+                    symbol,
+                }),
+                _ => None,
+            })
+            .sorted_by_key(|column| (column.poly_id.ptype, column.poly_id.id))
+            .collect()
+    }
+
+    /// @returns the witness columns, ordered by ID.
+    pub fn witness_columns(&self) -> Vec<ColumnInfo<'_>> {
+        self.columns_of_type(PolynomialType::Committed)
+    }
+
+    /// @returns the fixed columns, ordered by ID.
+    pub fn fixed_columns(&self) -> Vec<ColumnInfo<'_>> {
+        self.columns_of_type(PolynomialType::Constant)
+    }
+
+    /// @returns the column with the given ID, or the array of columns containing it.
+    pub fn column_by_poly_id(&self, poly_id: PolyID) -> Option<ColumnInfo<'_>> {
+        self.columns().into_iter().find(|column| {
+            column.poly_id.ptype == poly_id.ptype
+                && (column.poly_id.id..column.poly_id.id + column.length.unwrap_or(1))
+                    .contains(&poly_id.id)
+        })
+    }
+
+    fn columns_of_type(&self, ptype: PolynomialType) -> Vec<ColumnInfo<'_>> {
+        self.columns()
+            .into_iter()
+            .filter(|column| column.poly_id.ptype == ptype)
+            .collect()
+    }
+
     fn declaration_type_count(&self, poly_type: PolynomialType) -> usize {
         self.definitions
             .iter()
@@ -143,10 +284,36 @@ impl<T> Analyzed<T> {
 
     /// Returns the type (scheme) of a symbol with the given name.
     pub fn type_of_symbol(&self, name: &str) -> TypeScheme {
-        let (sym, value) = &self.definitions[name];
+        let (sym, value) = self
+            .definition(name)
+            .unwrap_or_else(|| panic!("Symbol {name} not found."));
         type_from_definition(sym, value).unwrap()
     }
 
+    /// Returns the definition of the symbol with the given absolute name, which can
+    /// use `::` or `.` as separators, e.g. `std::utils::unwrap_or_else` or `Main::x`.
+    pub fn definition(&self, name: &str) -> Option<&(Symbol, Option<FunctionValueDefinition>)> {
+        self.definitions
+            .get(&AbsoluteSymbolPath::from_dotted_string(name))
+    }
+
+    /// Returns the intermediate column with the given absolute name, which can
+    /// use `::` or `.` as separators.
+    pub fn intermediate_column(
+        &self,
+        name: &str,
+    ) -> Option<&(Symbol, Vec<AlgebraicExpression<T>>)> {
+        self.intermediate_columns
+            .get(&AbsoluteSymbolPath::from_dotted_string(name))
+    }
+
+    /// Returns the public declaration with the given absolute name, which can
+    /// use `::` or `.` as separators.
+    pub fn public_declaration(&self, name: &str) -> Option<&PublicDeclaration> {
+        self.public_declarations
+            .get(&AbsoluteSymbolPath::from_dotted_string(name))
+    }
+
     /// Removes the specified polynomials and updates the IDs of the other polynomials
     /// so that they are contiguous again.
     /// There must not be any reference to the removed polynomials left.
@@ -209,7 +376,7 @@ impl<T> Analyzed<T> {
             });
         }
 
-        let mut names_to_remove: HashSet<String> = Default::default();
+        let mut names_to_remove: HashSet<AbsoluteSymbolPath> = Default::default();
         self.definitions.retain(|name, (poly, _def)| {
             if matches!(poly.kind, SymbolKind::Poly(_))
                 && to_remove.contains(&(poly as &Symbol).into())
@@ -268,14 +435,31 @@ impl<T> Analyzed<T> {
             + 1;
         self.identities
             .push(Identity::from_polynomial_identity(id, source, identity));
+        self.identity_index.invalidate();
         self.source_order
             .push(StatementIdentifier::Identity(self.identities.len() - 1));
         id
     }
 
-    /// Remove some identities by their index (not their ID).
-    /// Does not re-allocate IDs.
-    pub fn remove_identities(&mut self, to_remove: &BTreeSet<usize>) {
+    /// Returns the provenance of the identity at the given index (not its ID), if known.
+    pub fn identity_provenance(&self, index: usize) -> Option<&IdentityProvenance> {
+        self.identity_provenance.get(index)
+    }
+
+    /// Returns the first identity with the given label.
+    pub fn identity_by_label(&self, label: &str) -> Option<&Identity<AlgebraicExpression<T>>> {
+        self.identities
+            .iter()
+            .find(|identity| identity.label.as_deref() == Some(label))
+    }
+
+    /// Removes some identities by their index (not their ID) and updates the source order.
+    /// Afterwards, the IDs of the remaining identities are re-allocated (per kind, in order).
+    /// @returns the new ID of every remaining identity by its kind and old ID.
+    pub fn remove_identities(
+        &mut self,
+        to_remove: &BTreeSet<usize>,
+    ) -> BTreeMap<(IdentityKind, u64), u64> {
         let mut shift = 0;
         self.source_order.retain_mut(|s| {
             if let StatementIdentifier::Identity(index) = s {
@@ -287,28 +471,61 @@ impl<T> Analyzed<T> {
             }
             true
         });
+        self.identity_index.invalidate();
         let mut index = 0;
         self.identities.retain(|_| {
             let retain = !to_remove.contains(&index);
             index += 1;
             retain
-        })
+        });
+        let mut index = 0;
+        self.identity_provenance.retain(|_| {
+            let retain = !to_remove.contains(&index);
+            index += 1;
+            retain
+        });
+        self.reallocate_identity_ids()
+    }
+
+    /// Keeps only the identities for which `f` returns true, see `remove_identities`.
+    /// @returns the new ID of every remaining identity by its kind and old ID.
+    pub fn retain_identities<F>(&mut self, mut f: F) -> BTreeMap<(IdentityKind, u64), u64>
+    where
+        F: FnMut(&Identity<AlgebraicExpression<T>>) -> bool,
+    {
+        let to_remove = self
+            .identities
+            .iter()
+            .enumerate()
+            .filter(|(_, identity)| !f(identity))
+            .map(|(index, _)| index)
+            .collect();
+        self.remove_identities(&to_remove)
+    }
+
+    /// Re-allocates the identity IDs per kind, starting from zero.
+    fn reallocate_identity_ids(&mut self) -> BTreeMap<(IdentityKind, u64), u64> {
+        // Identities created from the same statement share their ID,
+        // so we map old IDs to new IDs instead of just counting.
+        let mut new_ids: BTreeMap<(IdentityKind, u64), u64> = BTreeMap::new();
+        let mut counters: HashMap<IdentityKind, u64> = HashMap::new();
+        for identity in &mut self.identities {
+            identity.id = *new_ids
+                .entry((identity.kind, identity.id))
+                .or_insert_with(|| {
+                    let counter = counters.entry(identity.kind).or_default();
+                    *counter += 1;
+                    *counter - 1
+                });
+        }
+        new_ids
     }
 
     pub fn post_visit_expressions_in_identities_mut<F>(&mut self, f: &mut F)
     where
         F: FnMut(&mut AlgebraicExpression<T>),
     {
-        self.identities
-            .iter_mut()
-            .for_each(|i| i.post_visit_expressions_mut(f));
-        self.intermediate_columns
-            .values_mut()
-            .for_each(|(_sym, value)| {
-                value
-                    .iter_mut()
-                    .for_each(|v| v.post_visit_expressions_mut(f))
-            });
+        self.post_visit_algebraic_expressions_mut(f);
     }
 
     pub fn post_visit_expressions_in_definitions_mut<F>(&mut self, f: &mut F)
@@ -324,6 +541,9 @@ impl<T> Analyzed<T> {
                     .iter_mut()
                     .flat_map(|e| e.pattern.iter_mut())
                     .for_each(|e| e.post_visit_expressions_mut(f)),
+                Some(FunctionValueDefinition::Sparse(sparse)) => sparse
+                    .values_mut()
+                    .for_each(|e| e.post_visit_expressions_mut(f)),
                 Some(FunctionValueDefinition::Expression(TypedExpression {
                     e,
                     type_scheme: _,
@@ -352,6 +572,55 @@ impl<T: FieldElement> Analyzed<T> {
         substitute_intermediate(self.identities.clone(), intermediates)
     }
 
+    /// Removes condensed identities that are identical to an earlier identity
+    /// (same kind and same expressions, including selectors), keeping the first occurrence.
+    /// Afterwards, identity IDs are re-allocated (per kind) and the source order is updated.
+    /// @returns a report of the removed identities.
+    pub fn deduplicate_identities(&mut self) -> Vec<DuplicateIdentity> {
+        let mut first_occurrence = BTreeMap::new();
+        let mut duplicates = vec![];
+        let mut to_remove = BTreeSet::new();
+        for (index, identity) in self.identities.iter().enumerate() {
+            let key = (identity.kind, &identity.left, &identity.right);
+            match first_occurrence.get(&key) {
+                Some(&first) => {
+                    let kept: &Identity<_> = &self.identities[first];
+                    duplicates.push(DuplicateIdentity {
+                        kind: identity.kind,
+                        source: identity.source.clone(),
+                        duplicate_of: kept.source.clone(),
+                    });
+                    to_remove.insert(index);
+                }
+                None => {
+                    first_occurrence.insert(key, index);
+                }
+            }
+        }
+        if to_remove.is_empty() {
+            return duplicates;
+        }
+        self.remove_identities(&to_remove);
+        duplicates
+    }
+
+    /// @returns the polynomial degree of each identity, with intermediate columns inlined.
+    pub fn identity_degrees(&self) -> Vec<usize> {
+        self.identities_with_inlined_intermediate_polynomials()
+            .iter()
+            .map(|identity| identity.degree())
+            .collect()
+    }
+
+    /// @returns the maximum polynomial degree of all identities, with intermediate columns inlined,
+    /// or zero if there are no identities.
+    pub fn max_identity_degree(&self) -> usize {
+        self.identity_degrees()
+            .into_iter()
+            .max()
+            .unwrap_or_default()
+    }
+
     pub fn get_struct_schema() -> schemars::schema::RootSchema {
         schemars::schema_for!(Self)
     }
@@ -361,7 +630,62 @@ impl<T: FieldElement> Analyzed<T> {
     }
 
     pub fn deserialize(bytes: &[u8]) -> Result<Self, String> {
-        serde_cbor::from_slice(bytes).map_err(|e| format!("Failed to deserialize analyzed: {}", e))
+        let mut analyzed: Self = serde_cbor::from_slice(bytes)
+            .map_err(|e| format!("Failed to deserialize analyzed: {}", e))?;
+        analyzed.intern_names();
+        Ok(analyzed)
+    }
+
+    /// Writes the analyzed file in a compact binary format, preceded by a header consisting
+    /// of magic bytes, the format version and the modulus of the field.
+    /// Field elements are written as fixed-width little-endian bytes.
+    pub fn serialize_to<W: Write>(&self, mut writer: W) -> Result<(), String> {
+        let modulus = T::modulus().to_arbitrary_integer().to_le_bytes();
+        writer
+            .write_all(BINARY_MAGIC)
+            .and_then(|_| writer.write_all(&BINARY_FORMAT_VERSION.to_le_bytes()))
+            .and_then(|_| writer.write_all(&(modulus.len() as u32).to_le_bytes()))
+            .and_then(|_| writer.write_all(&modulus))
+            .map_err(|e| format!("Failed to write analyzed header: {e}"))?;
+        serde_cbor::to_writer(writer, self)
+            .map_err(|e| format!("Failed to serialize analyzed: {e}"))
+    }
+
+    /// Reads an analyzed file written by `serialize_to`. Fails if the file was written
+    /// in a different format version or for a different field.
+    pub fn deserialize_from<R: Read>(mut reader: R) -> Result<Self, String> {
+        let read_error = |e: io::Error| format!("Failed to read analyzed header: {e}");
+        let mut magic = [0u8; BINARY_MAGIC.len()];
+        reader.read_exact(&mut magic).map_err(read_error)?;
+        if &magic != BINARY_MAGIC {
+            return Err("Not a binary analyzed file: invalid magic bytes".to_string());
+        }
+        let mut word = [0u8; 4];
+        reader.read_exact(&mut word).map_err(read_error)?;
+        let version = u32::from_le_bytes(word);
+        if version != BINARY_FORMAT_VERSION {
+            return Err(format!(
+                "Unsupported analyzed format version {version}, expected version {BINARY_FORMAT_VERSION}"
+            ));
+        }
+        reader.read_exact(&mut word).map_err(read_error)?;
+        let modulus_len = u32::from_le_bytes(word) as usize;
+        if modulus_len > MAX_MODULUS_BYTES {
+            return Err(format!("Invalid field modulus length {modulus_len}"));
+        }
+        let mut modulus = vec![0u8; modulus_len];
+        reader.read_exact(&mut modulus).map_err(read_error)?;
+        let modulus = BigUint::from_le_bytes(&modulus);
+        let expected = T::modulus().to_arbitrary_integer();
+        if modulus != expected {
+            return Err(format!(
+                "The analyzed file was written for the field with modulus {modulus:#x}, but the field with modulus {expected:#x} is used"
+            ));
+        }
+        let mut analyzed: Self = serde_cbor::from_reader(reader)
+            .map_err(|e| format!("Failed to deserialize analyzed: {e}"))?;
+        analyzed.intern_names();
+        Ok(analyzed)
     }
 }
 
@@ -374,7 +698,7 @@ fn substitute_intermediate<T: Copy + Display>(
     identities
         .into_iter()
         .scan(HashMap::default(), |cache, mut identity| {
-            identity.post_visit_expressions_mut(&mut |e| {
+            identity.post_visit_algebraic_expressions_mut(&mut |e| {
                 if let AlgebraicExpression::Reference(poly) = e {
                     match poly.poly_id.ptype {
                         PolynomialType::Committed => {}
@@ -443,9 +767,9 @@ pub fn type_from_definition(
 ) -> Option<TypeScheme> {
     if let Some(value) = value {
         match value {
-            FunctionValueDefinition::Array(_) | FunctionValueDefinition::Query(_) => {
-                Some(Type::Col.into())
-            }
+            FunctionValueDefinition::Array(_)
+            | FunctionValueDefinition::Sparse(_)
+            | FunctionValueDefinition::Query(_) => Some(Type::Col.into()),
             FunctionValueDefinition::Expression(TypedExpression { e: _, type_scheme }) => {
                 type_scheme.clone()
             }
@@ -469,7 +793,7 @@ pub fn type_from_definition(
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Symbol {
     pub id: u64,
     pub source: SourceRef,
@@ -524,6 +848,34 @@ impl Symbol {
     }
 }
 
+/// A fixed, witness or intermediate column, or an array of columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColumnInfo<'a> {
+    /// The absolute name of the column, without an index for arrays.
+    pub name: &'a str,
+    /// The ID of the column, or of the first element for arrays.
+    pub poly_id: PolyID,
+    /// The number of elements if this is an array of columns.
+    pub length: Option<DegreeType>,
+    symbol: &'a Symbol,
+}
+
+impl<'a> ColumnInfo<'a> {
+    pub fn ptype(&self) -> PolynomialType {
+        self.poly_id.ptype
+    }
+
+    /// Returns the symbol declaring the column.
+    pub fn symbol(&self) -> &'a Symbol {
+        self.symbol
+    }
+
+    /// Returns the name and ID of each element for arrays, or of the column itself otherwise.
+    pub fn elements(&self) -> impl Iterator<Item = (String, PolyID)> + 'a {
+        self.symbol.array_elements()
+    }
+}
+
 /// The "kind" of a symbol. In the future, this will be mostly
 /// replaced by its type.
 #[derive(
@@ -539,15 +891,16 @@ pub enum SymbolKind {
     Other(),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum FunctionValueDefinition {
     Array(Vec<RepeatedArray>),
+    Sparse(SparseArray),
     Query(Expression),
     Expression(TypedExpression),
 }
 
 /// An array of elements that might be repeated.
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct RepeatedArray {
     /// The pattern to be repeated
     pattern: Vec<Expression>,
@@ -592,7 +945,83 @@ impl RepeatedArray {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+/// A fixed column that has the given values at specific rows and a default value at all other rows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SparseArray {
+    /// Pairs of row and value, sorted by row.
+    entries: Vec<(DegreeType, Expression)>,
+    /// The value at all rows that do not have an entry.
+    default: Expression,
+    /// The number of rows.
+    size: DegreeType,
+}
+
+impl SparseArray {
+    /// Creates a sparse array of `size` rows. The rows of the entries have to be
+    /// distinct and smaller than `size`, but do not have to be sorted.
+    pub fn new(
+        mut entries: Vec<(DegreeType, Expression)>,
+        default: Expression,
+        size: DegreeType,
+    ) -> Self {
+        entries.sort_by_key(|(row, _)| *row);
+        assert!(
+            entries.windows(2).all(|w| w[0].0 != w[1].0),
+            "duplicate rows in sparse array"
+        );
+        assert!(
+            entries.last().map_or(true, |(row, _)| *row < size),
+            "row out of bounds in sparse array of {size} rows"
+        );
+        Self {
+            entries,
+            default,
+            size,
+        }
+    }
+
+    /// Returns the number of rows.
+    pub fn size(&self) -> DegreeType {
+        self.size
+    }
+
+    /// Returns the pairs of row and value, sorted by row.
+    pub fn entries(&self) -> &[(DegreeType, Expression)] {
+        &self.entries
+    }
+
+    /// Returns the value at all rows that do not have an entry.
+    pub fn default_value(&self) -> &Expression {
+        &self.default
+    }
+
+    /// Returns the expression for the given row or `None` if the row is out of bounds.
+    pub fn value_at(&self, row: DegreeType) -> Option<&Expression> {
+        (row < self.size).then(|| {
+            self.entries
+                .binary_search_by_key(&row, |(r, _)| *r)
+                .map_or(&self.default, |i| &self.entries[i].1)
+        })
+    }
+
+    /// Returns an iterator over all values, i.e. those of the entries followed by the default value.
+    pub fn values(&self) -> impl Iterator<Item = &Expression> {
+        self.entries
+            .iter()
+            .map(|(_, e)| e)
+            .chain(once(&self.default))
+    }
+
+    /// Returns an iterator over all values, i.e. those of the entries followed by the default value.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Expression> {
+        self.entries
+            .iter_mut()
+            .map(|(_, e)| e)
+            .chain(once(&mut self.default))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PublicDeclaration {
     pub id: u64,
     pub source: SourceRef,
@@ -612,12 +1041,38 @@ impl PublicDeclaration {
     }
 }
 
+/// Describes which identity statement in the source a condensed identity originates from.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct IdentityProvenance {
+    /// Index of the identity statement in the list of identities before condensation.
+    pub statement_index: usize,
+    /// Source of the identity statement.
+    pub source: SourceRef,
+    /// If the identity statement evaluated to an array of constraints,
+    /// the index of this identity in the array.
+    pub array_index: Option<usize>,
+}
+
+/// Record of an identity that was removed by `Analyzed::deduplicate_identities`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DuplicateIdentity {
+    pub kind: IdentityKind,
+    /// Source of the removed identity.
+    pub source: SourceRef,
+    /// Source of the identical identity that was kept.
+    pub duplicate_of: SourceRef,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Identity<Expr> {
     /// The ID is specific to the identity kind.
     pub id: u64,
     pub kind: IdentityKind,
     pub source: SourceRef,
+    /// The label given in the source. If an identity statement evaluates to
+    /// an array of identities, their labels are suffixed with the array index.
+    #[serde(default)]
+    pub label: Option<String>,
     /// For a simple polynomial identity, the selector contains
     /// the actual expression (see expression_for_poly_id).
     pub left: SelectedExpressions<Expr>,
@@ -631,6 +1086,7 @@ impl<Expr> Identity<Expr> {
             id,
             kind: IdentityKind::Polynomial,
             source,
+            label: None,
             left: SelectedExpressions {
                 selector: Some(identity),
                 expressions: vec![],
@@ -651,12 +1107,40 @@ impl<Expr> Identity<Expr> {
     }
 }
 
+impl<Expr> Identity<Expr>
+where
+    Self: Display,
+{
+    /// Describes the identity for error messages and debug output, including its ID,
+    /// kind and source, e.g. `#17 [plookup] file.pil:42:4: { a, b } in { line, c }`.
+    pub fn describe(&self) -> String {
+        let constraint = self.to_string();
+        format!(
+            "#{} [{}] {}: {}",
+            self.id,
+            self.kind,
+            self.source,
+            constraint.strip_suffix(';').unwrap_or(&constraint)
+        )
+    }
+}
+
 impl<T> Identity<AlgebraicExpression<T>> {
     pub fn contains_next_ref(&self) -> bool {
         self.left.contains_next_ref() || self.right.contains_next_ref()
     }
 }
 
+impl<T: FieldElement> Identity<AlgebraicExpression<T>> {
+    /// @returns the polynomial degree of the identity: the degree of the expression for
+    /// polynomial identities, and the maximum degree of the selector times each expression
+    /// on either side for all other identities.
+    /// References to intermediate columns count as degree one, see `Analyzed::identity_degrees`.
+    pub fn degree(&self) -> usize {
+        self.left.degree().max(self.right.degree())
+    }
+}
+
 #[derive(
     Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize, JsonSchema,
 )]
@@ -678,10 +1162,23 @@ impl<T> SelectedExpressions<AlgebraicExpression<T>> {
     }
 }
 
+impl<T: FieldElement> SelectedExpressions<AlgebraicExpression<T>> {
+    /// @returns the maximum degree of the selector times each expression,
+    /// or the degree of the selector if there are no expressions.
+    pub fn degree(&self) -> usize {
+        let selector_degree = self.selector.as_ref().map_or(0, |s| s.degree());
+        self.expressions
+            .iter()
+            .map(|e| selector_degree + e.degree())
+            .max()
+            .unwrap_or(selector_degree)
+    }
+}
+
 pub type Expression = parsed::Expression<Reference>;
 pub type TypedExpression = crate::parsed::TypedExpression<Reference, u64>;
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Reference {
     LocalVar(u64, String),
     Poly(PolynomialReference),
@@ -692,7 +1189,7 @@ pub struct AlgebraicReference {
     /// Name of the polynomial - just for informational purposes.
     /// Comparisons are based on polynomial ID.
     /// In case of an array element, this ends in `[i]`.
-    pub name: String,
+    pub name: SymbolName,
     /// Identifier for a polynomial reference, already contains
     /// the element offset in case of an array element.
     pub poly_id: PolyID,
@@ -749,7 +1246,7 @@ pub enum AlgebraicExpression<T> {
 }
 
 #[derive(
-    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize, JsonSchema,
+    Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize, JsonSchema,
 )]
 pub enum AlgebraicBinaryOperator {
     Add,
@@ -787,7 +1284,7 @@ impl TryFrom<BinaryOperator> for AlgebraicBinaryOperator {
 }
 
 #[derive(
-    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize, JsonSchema,
+    Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize, JsonSchema,
 )]
 pub enum AlgebraicUnaryOperator {
     Minus,
@@ -845,6 +1342,30 @@ impl<T> AlgebraicExpression<T> {
     }
 }
 
+impl<T: FieldElement> AlgebraicExpression<T> {
+    /// @returns the polynomial degree of the expression in the columns: references have
+    /// degree one (also to next rows), numbers and public references degree zero.
+    pub fn degree(&self) -> usize {
+        match self {
+            AlgebraicExpression::Reference(_) => 1,
+            AlgebraicExpression::PublicReference(_) | AlgebraicExpression::Number(_) => 0,
+            AlgebraicExpression::BinaryOperation(left, op, right) => match op {
+                AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                    left.degree().max(right.degree())
+                }
+                AlgebraicBinaryOperator::Mul => left.degree() + right.degree(),
+                AlgebraicBinaryOperator::Pow => match right.as_ref() {
+                    AlgebraicExpression::Number(exponent) => {
+                        left.degree() * exponent.to_degree() as usize
+                    }
+                    _ => panic!("Exponent has to be a number: {right}"),
+                },
+            },
+            AlgebraicExpression::UnaryOperation(AlgebraicUnaryOperator::Minus, e) => e.degree(),
+        }
+    }
+}
+
 impl<T> ops::Add for AlgebraicExpression<T> {
     type Output = Self;
 
@@ -875,7 +1396,7 @@ impl<T> From<T> for AlgebraicExpression<T> {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PolynomialReference {
     /// Name of the polynomial - just for informational purposes.
     /// Comparisons are based on polynomial ID.
@@ -939,11 +1460,76 @@ impl Display for PolynomialType {
     }
 }
 
+/// Serializes an absolute symbol path as a string in the form of
+/// `AbsoluteSymbolPath::to_dotted_string`, which is also the form of
+/// the names in references.
+mod dotted_name {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::parsed::asm::AbsoluteSymbolPath;
+
+    pub fn serialize<S: Serializer>(
+        path: &AbsoluteSymbolPath,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&path.to_dotted_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<AbsoluteSymbolPath, D::Error> {
+        Ok(AbsoluteSymbolPath::from_dotted_string(
+            &String::deserialize(deserializer)?,
+        ))
+    }
+}
+
+/// Serializes a map keyed by absolute symbol paths with the keys in the form
+/// of `AbsoluteSymbolPath::to_dotted_string`, since maps need string keys in
+/// formats like JSON.
+mod dotted_keys {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::parsed::asm::AbsoluteSymbolPath;
+
+    pub fn serialize<V: Serialize, S: Serializer>(
+        map: &HashMap<AbsoluteSymbolPath, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            map.iter()
+                .map(|(name, value)| (name.to_dotted_string(), value)),
+        )
+    }
+
+    pub fn deserialize<'de, V, D>(
+        deserializer: D,
+    ) -> Result<HashMap<AbsoluteSymbolPath, V>, D::Error>
+    where
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(HashMap::<String, V>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(name, value)| (AbsoluteSymbolPath::from_dotted_string(&name), value))
+            .collect())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::SourceRef;
+    use powdr_number::GoldilocksField;
+
+    use crate::{parsed::SelectedExpressions, SourceRef};
 
-    use super::{AlgebraicExpression, Analyzed};
+    use super::test_utils::*;
+    use super::{
+        AlgebraicExpression, Analyzed, ColumnInfo, Identity, IdentityKind, PolyID, PolynomialType,
+        StatementIdentifier,
+    };
+    use crate::parsed::types::Type;
 
     #[test]
     fn insert_remove_identities() {
@@ -978,8 +1564,155 @@ mod tests {
         let mut pil_result = Analyzed::default();
         pil_result.append_polynomial_identity(AlgebraicExpression::Number(0), SourceRef::unknown());
         pil_result.append_polynomial_identity(AlgebraicExpression::Number(5), SourceRef::unknown());
-        pil_result.identities[1].id = 6;
+        // the IDs are re-allocated
+        pil_result.identities[0].id = 0;
+        pil_result.identities[1].id = 1;
         assert_eq!(pil.identities, pil_result.identities);
         assert_eq!(pil.source_order, pil_result.source_order);
     }
+
+    #[test]
+    fn deduplicate_identities() {
+        let source = |line| SourceRef {
+            file: None,
+            line,
+            col: 0,
+        };
+        let lookup = |id, selector: u64| Identity {
+            id,
+            kind: IdentityKind::Plookup,
+            source: source(10 + id as usize),
+            label: None,
+            left: SelectedExpressions {
+                selector: Some(AlgebraicExpression::Number(selector.into())),
+                expressions: vec![AlgebraicExpression::Number(7.into())],
+            },
+            right: SelectedExpressions {
+                selector: None,
+                expressions: vec![AlgebraicExpression::Number(8.into())],
+            },
+        };
+        let mut pil = Analyzed::<GoldilocksField>::default();
+        pil.append_polynomial_identity(AlgebraicExpression::Number(1.into()), source(1));
+        pil.append_polynomial_identity(AlgebraicExpression::Number(2.into()), source(2));
+        pil.append_polynomial_identity(AlgebraicExpression::Number(1.into()), source(3));
+        pil.append_polynomial_identity(AlgebraicExpression::Number(3.into()), source(4));
+        // Same tuples, different selectors: not a duplicate.
+        for (id, selector) in [(0, 1), (1, 2), (2, 1)] {
+            pil.identities.push(lookup(id, selector));
+            pil.source_order
+                .push(StatementIdentifier::Identity(pil.identities.len() - 1));
+        }
+
+        let removed = pil.deduplicate_identities();
+        assert_eq!(
+            removed
+                .iter()
+                .map(|d| (d.kind, d.source.line, d.duplicate_of.line))
+                .collect::<Vec<_>>(),
+            vec![
+                (IdentityKind::Polynomial, 3, 1),
+                (IdentityKind::Plookup, 12, 10)
+            ]
+        );
+        assert_eq!(
+            pil.identities
+                .iter()
+                .map(|i| (i.kind, i.id, i.source.line))
+                .collect::<Vec<_>>(),
+            vec![
+                (IdentityKind::Polynomial, 0, 1),
+                (IdentityKind::Polynomial, 1, 2),
+                (IdentityKind::Polynomial, 2, 4),
+                (IdentityKind::Plookup, 0, 10),
+                (IdentityKind::Plookup, 1, 11),
+            ]
+        );
+        assert_eq!(
+            pil.source_order,
+            (0..5)
+                .map(StatementIdentifier::Identity)
+                .collect::<Vec<_>>()
+        );
+        assert!(pil.deduplicate_identities().is_empty());
+    }
+
+    fn columns_input() -> Pil {
+        let mut pil = new_pil(16);
+        add_column(&mut pil, "N.a", PolynomialType::Committed, None);
+        add_column(&mut pil, "N.LINE", PolynomialType::Constant, None);
+        add_column(&mut pil, "N.b", PolynomialType::Committed, Some(3));
+        let inter = col(&pil, "N.a") * col(&pil, "N.LINE");
+        add_intermediate(&mut pil, "N.inter", vec![inter]);
+        add_column(&mut pil, "N.ONES", PolynomialType::Constant, None);
+        add_column(&mut pil, "N.c", PolynomialType::Committed, None);
+        let inters = vec![col(&pil, "N.a"), col(&pil, "N.c")];
+        add_intermediate(&mut pil, "N.inters", inters);
+        add_let(&mut pil, "N.n", Type::Int, number(4));
+        let identity = col(&pil, "N.a") - (col(&pil, "N.inter") + col(&pil, "N.b[1]"));
+        add_identity(&mut pil, identity);
+        pil
+    }
+
+    #[test]
+    fn columns() {
+        let pil = columns_input();
+        let columns = pil
+            .columns()
+            .iter()
+            .map(|c| (c.name.to_string(), c.ptype(), c.poly_id.id, c.length))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            [
+                ("N.a".to_string(), PolynomialType::Committed, 0, None),
+                ("N.b".to_string(), PolynomialType::Committed, 1, Some(3)),
+                ("N.c".to_string(), PolynomialType::Committed, 4, None),
+                ("N.LINE".to_string(), PolynomialType::Constant, 0, None),
+                ("N.ONES".to_string(), PolynomialType::Constant, 1, None),
+                ("N.inter".to_string(), PolynomialType::Intermediate, 0, None),
+                (
+                    "N.inters".to_string(),
+                    PolynomialType::Intermediate,
+                    1,
+                    Some(2)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn column_counts() {
+        let pil = columns_input();
+        let element_count =
+            |columns: Vec<ColumnInfo>| columns.iter().flat_map(|c| c.elements()).count();
+        assert_eq!(element_count(pil.witness_columns()), pil.commitment_count());
+        assert_eq!(element_count(pil.fixed_columns()), pil.constant_count());
+        assert_eq!(
+            element_count(pil.columns()),
+            pil.commitment_count() + pil.constant_count() + pil.intermediate_count()
+        );
+    }
+
+    #[test]
+    fn column_by_poly_id() {
+        let pil = columns_input();
+        let name = |id, ptype| pil.column_by_poly_id(PolyID { id, ptype }).map(|c| c.name);
+        assert_eq!(name(0, PolynomialType::Committed), Some("N.a"));
+        assert_eq!(name(3, PolynomialType::Committed), Some("N.b"));
+        assert_eq!(name(1, PolynomialType::Constant), Some("N.ONES"));
+        assert_eq!(name(2, PolynomialType::Intermediate), Some("N.inters"));
+        assert_eq!(name(5, PolynomialType::Committed), None);
+
+        let b = pil
+            .column_by_poly_id(PolyID {
+                id: 2,
+                ptype: PolynomialType::Committed,
+            })
+            .unwrap();
+        assert_eq!(
+            b.elements().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["N.b[0]", "N.b[1]", "N.b[2]"]
+        );
+    }
 }
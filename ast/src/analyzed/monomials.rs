@@ -0,0 +1,413 @@
+//! Expansion of algebraic expressions into a canonical sum of monomials.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+use powdr_number::{FieldElement, LargeInt};
+use serde::{Deserialize, Serialize};
+
+use super::{AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference};
+
+/// A variable of a polynomial. References to the current and to the
+/// next row of the same column are different variables.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MonomialVariable {
+    Column(AlgebraicReference),
+    Public(String),
+}
+
+/// A coefficient times a product of powers of variables.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Monomial<T> {
+    pub coefficient: T,
+    /// The variables with their (positive) exponents, sorted by variable.
+    pub variables: Vec<(MonomialVariable, u64)>,
+}
+
+impl<T> Monomial<T> {
+    /// @returns the sum of the exponents of all variables.
+    pub fn degree(&self) -> u64 {
+        self.variables.iter().map(|(_, exponent)| exponent).sum()
+    }
+}
+
+/// A polynomial in canonical form: a sum of monomials with non-zero coefficients
+/// and pairwise different variables, sorted by degree and then by variables.
+/// The zero polynomial has no monomials.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Polynomial<T> {
+    pub monomials: Vec<Monomial<T>>,
+}
+
+/// An error when expanding an expression that is not a polynomial.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonomialError {
+    /// The exponent of a power is not a constant.
+    NonConstantExponent(String),
+    /// The exponent of a power of a variable does not fit u32.
+    ExponentTooLarge(String),
+}
+
+impl Display for MonomialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MonomialError::NonConstantExponent(exponent) => write!(
+                f,
+                "The exponent {exponent} is not a constant, so the expression is not a polynomial."
+            ),
+            MonomialError::ExponentTooLarge(exponent) => {
+                write!(f, "The exponent {exponent} is too large.")
+            }
+        }
+    }
+}
+
+impl<T: FieldElement> AlgebraicExpression<T> {
+    /// Expands the expression into a sum of monomials by distributing products
+    /// over sums and combining like terms over the field.
+    /// Fails if the expression is not a polynomial, i.e. if an exponent is not a constant.
+    pub fn to_monomials(&self) -> Result<Polynomial<T>, MonomialError> {
+        let terms = self.fold(
+            |e| {
+                Ok(match e {
+                    AlgebraicExpression::Reference(reference) => {
+                        Terms::variable(MonomialVariable::Column(reference.clone()))
+                    }
+                    AlgebraicExpression::PublicReference(name) => {
+                        Terms::variable(MonomialVariable::Public(name.clone()))
+                    }
+                    AlgebraicExpression::Number(n) => Terms::constant(*n),
+                    AlgebraicExpression::BinaryOperation(..)
+                    | AlgebraicExpression::UnaryOperation(..) => unreachable!(),
+                })
+            },
+            |left, op, right| {
+                let (left, right) = (left?, right?);
+                match op {
+                    AlgebraicBinaryOperator::Add => Ok(left.add(right)),
+                    AlgebraicBinaryOperator::Sub => Ok(left.add(right.neg())),
+                    AlgebraicBinaryOperator::Mul => Ok(left.mul(&right)),
+                    AlgebraicBinaryOperator::Pow => {
+                        let exponent = right
+                            .as_constant()
+                            .ok_or_else(|| MonomialError::NonConstantExponent(right.to_string()))?;
+                        left.pow(exponent)
+                    }
+                }
+            },
+            |_, inner| inner.map(Terms::neg),
+        )?;
+        let mut monomials = terms
+            .0
+            .into_iter()
+            .map(|(variables, coefficient)| Monomial {
+                coefficient,
+                variables,
+            })
+            .collect::<Vec<_>>();
+        monomials.sort_by(|a, b| (a.degree(), &a.variables).cmp(&(b.degree(), &b.variables)));
+        Ok(Polynomial { monomials })
+    }
+}
+
+/// The monomials of a polynomial during expansion, as a map from the variables to
+/// the coefficient. Zero coefficients are never stored.
+struct Terms<T>(BTreeMap<Vec<(MonomialVariable, u64)>, T>);
+
+impl<T: FieldElement> Terms<T> {
+    fn constant(value: T) -> Self {
+        Self::monomial(vec![], value)
+    }
+
+    fn variable(variable: MonomialVariable) -> Self {
+        Self::monomial(vec![(variable, 1)], 1.into())
+    }
+
+    fn monomial(variables: Vec<(MonomialVariable, u64)>, coefficient: T) -> Self {
+        let mut terms = Terms(BTreeMap::new());
+        terms.add_monomial(variables, coefficient);
+        terms
+    }
+
+    fn add_monomial(&mut self, variables: Vec<(MonomialVariable, u64)>, coefficient: T) {
+        let sum = self.0.get(&variables).copied().unwrap_or(0.into()) + coefficient;
+        if sum == 0.into() {
+            self.0.remove(&variables);
+        } else {
+            self.0.insert(variables, sum);
+        }
+    }
+
+    /// @returns the value if the polynomial is constant.
+    fn as_constant(&self) -> Option<T> {
+        match self.0.iter().next() {
+            None => Some(0.into()),
+            Some((variables, value)) if variables.is_empty() && self.0.len() == 1 => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn add(mut self, other: Self) -> Self {
+        for (variables, coefficient) in other.0 {
+            self.add_monomial(variables, coefficient);
+        }
+        self
+    }
+
+    fn neg(self) -> Self {
+        Terms(self.0.into_iter().map(|(v, c)| (v, -c)).collect())
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let mut result = Terms(BTreeMap::new());
+        for ((left_vars, left_coeff), (right_vars, right_coeff)) in
+            self.0.iter().cartesian_product(&other.0)
+        {
+            let variables = left_vars
+                .iter()
+                .merge_join_by(right_vars, |(l, _), (r, _)| l.cmp(r))
+                .map(|item| match item {
+                    itertools::EitherOrBoth::Both((v, l), (_, r)) => (v.clone(), l + r),
+                    itertools::EitherOrBoth::Left(x) | itertools::EitherOrBoth::Right(x) => {
+                        x.clone()
+                    }
+                })
+                .collect();
+            result.add_monomial(variables, *left_coeff * *right_coeff);
+        }
+        result
+    }
+
+    fn pow(self, exponent: T) -> Result<Self, MonomialError> {
+        if let Some(value) = self.as_constant() {
+            return Ok(Self::constant(value.pow(exponent.to_integer())));
+        }
+        let too_large = || MonomialError::ExponentTooLarge(exponent.to_string());
+        // Limiting the exponent to u32 means that exponents of variables cannot overflow u64
+        // when multiplying monomials.
+        let exponent = u64::from(exponent.to_integer().try_into_u32().ok_or_else(too_large)?);
+        if exponent == 0 {
+            return Ok(Self::constant(1.into()));
+        }
+        if self.0.len() == 1 {
+            let (variables, coefficient) = self.0.into_iter().next().unwrap();
+            let variables = variables
+                .into_iter()
+                .map(|(v, e)| Ok((v, e.checked_mul(exponent).ok_or_else(too_large)?)))
+                .collect::<Result<_, _>>()?;
+            return Ok(Self::monomial(variables, coefficient.pow(exponent.into())));
+        }
+        // Exponentiation by squaring.
+        let mut result = Self::constant(1.into());
+        let mut base = self;
+        let mut exponent = exponent;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&base);
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.mul(&base);
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl<T: FieldElement> Display for Terms<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let monomials = self
+            .0
+            .iter()
+            .map(|(variables, coefficient)| Monomial {
+                coefficient: *coefficient,
+                variables: variables.clone(),
+            })
+            .collect();
+        write!(f, "{}", Polynomial { monomials })
+    }
+}
+
+impl Display for MonomialVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MonomialVariable::Column(reference) => write!(f, "{reference}"),
+            MonomialVariable::Public(name) => write!(f, ":{name}"),
+        }
+    }
+}
+
+impl<T: FieldElement> Display for Monomial<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let variables = self.variables.iter().map(|(variable, exponent)| {
+            if *exponent == 1 {
+                variable.to_string()
+            } else {
+                format!("{variable}**{exponent}")
+            }
+        });
+        if self.variables.is_empty() {
+            write!(f, "{}", self.coefficient)
+        } else if self.coefficient.is_one() {
+            write!(f, "{}", variables.format(" * "))
+        } else {
+            write!(f, "{} * {}", self.coefficient, variables.format(" * "))
+        }
+    }
+}
+
+impl<T: FieldElement> Display for Polynomial<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.monomials.is_empty() {
+            write!(f, "0")
+        } else {
+            write!(f, "{}", self.monomials.iter().format(" + "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+
+    use crate::analyzed::{
+        AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator,
+        PolyID, PolynomialType,
+    };
+
+    use super::{MonomialError, Polynomial};
+
+    type Expr = AlgebraicExpression<GoldilocksField>;
+
+    fn col(name: &str, id: u64, next: bool) -> Expr {
+        AlgebraicExpression::Reference(AlgebraicReference {
+            name: name.into(),
+            poly_id: PolyID {
+                id,
+                ptype: PolynomialType::Committed,
+            },
+            next,
+        })
+    }
+
+    fn num(n: u64) -> Expr {
+        AlgebraicExpression::Number(n.into())
+    }
+
+    fn pow(base: Expr, exponent: Expr) -> Expr {
+        AlgebraicExpression::new_binary(base, AlgebraicBinaryOperator::Pow, exponent)
+    }
+
+    fn neg(e: Expr) -> Expr {
+        AlgebraicExpression::UnaryOperation(AlgebraicUnaryOperator::Minus, Box::new(e))
+    }
+
+    fn fixed(name: &str, id: u64) -> Expr {
+        AlgebraicExpression::Reference(AlgebraicReference {
+            name: name.into(),
+            poly_id: PolyID {
+                id,
+                ptype: PolynomialType::Constant,
+            },
+            next: false,
+        })
+    }
+
+    #[test]
+    fn expand() {
+        let (x, y) = (col("x", 0, false), col("y", 1, false));
+        let e = (x.clone() + y.clone()) * (x.clone() - y.clone()) + num(3) * col("x", 0, true);
+        assert_eq!(
+            e.to_monomials().unwrap().to_string(),
+            "3 * x' + x**2 + 18446744069414584320 * y**2"
+        );
+        assert_eq!(
+            pow(x.clone() + num(1), num(2))
+                .to_monomials()
+                .unwrap()
+                .to_string(),
+            "1 + 2 * x + x**2"
+        );
+        assert_eq!(
+            (x.clone() * y.clone() - y * x)
+                .to_monomials()
+                .unwrap()
+                .to_string(),
+            "0"
+        );
+    }
+
+    #[test]
+    fn non_constant_exponent() {
+        let (x, y) = (col("x", 0, false), col("y", 1, false));
+        assert_eq!(
+            pow(x, y).to_monomials(),
+            Err(MonomialError::NonConstantExponent("y".to_string()))
+        );
+    }
+
+    #[test]
+    fn equal_constraints() {
+        let (x, y, first) = (
+            col("N.x", 0, false),
+            col("N.y", 1, false),
+            fixed("N.FIRST", 0),
+        );
+        let (x_next, y_next) = (col("N.x", 0, true), col("N.y", 1, true));
+        let polynomials = [
+            (x.clone() + y.clone()) * (x.clone() - y.clone()) - x_next.clone() * num(3),
+            x.clone() * x.clone() - y.clone() * y - num(3) * x_next - num(0),
+            first.clone() * (y_next.clone() - x.clone()) - num(0),
+            neg(x * first.clone()) - neg(y_next) * first,
+        ]
+        .map(|e| e.to_monomials().unwrap());
+        assert_eq!(polynomials[0], polynomials[1]);
+        assert_eq!(polynomials[2], polynomials[3]);
+        assert_eq!(
+            polynomials[0].to_string(),
+            "18446744069414584318 * N.x' + N.x**2 + 18446744069414584320 * N.y**2"
+        );
+        assert_eq!(
+            polynomials[2].to_string(),
+            "18446744069414584320 * N.x * N.FIRST + N.FIRST * N.y'"
+        );
+    }
+
+    #[test]
+    fn powers_and_publics() {
+        let x = col("N.x", 0, false);
+        let out = AlgebraicExpression::PublicReference("N.out".to_string());
+        let e =
+            pow(x.clone() + num(1), num(2)) - (x.clone() * x.clone() + num(2) * x.clone() + out);
+        assert_eq!(
+            e.to_monomials().unwrap().to_string(),
+            "1 + 18446744069414584320 * :N.out"
+        );
+        assert_eq!(
+            (pow(x, num(0)) - num(1))
+                .to_monomials()
+                .unwrap()
+                .to_string(),
+            "0"
+        );
+    }
+
+    #[test]
+    fn serialize() {
+        let e = col("N.x", 0, true) * (col("N.x", 0, false) - num(1));
+        let polynomial = e.to_monomials().unwrap();
+        let json = serde_json::to_string(&polynomial).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Polynomial<GoldilocksField>>(&json).unwrap(),
+            polynomial
+        );
+        assert_eq!(
+            polynomial.to_string(),
+            "18446744069414584320 * N.x' + N.x * N.x'"
+        );
+        let monomial = &polynomial.monomials[1];
+        assert_eq!(monomial.degree(), 2);
+        assert_eq!(monomial.variables.len(), 2);
+    }
+}
@@ -0,0 +1,172 @@
+use std::{borrow::Borrow, collections::HashSet, fmt, ops::Deref, sync::Arc};
+
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use super::{AlgebraicExpression, Analyzed};
+use crate::parsed::visitor::ExpressionVisitable;
+
+/// An immutable symbol name that is cheap to clone.
+/// Names obtained from the same [`NameInterner`] share their allocation.
+///
+/// It is serialized as a plain string, so the serialized form does not depend
+/// on the order in which names were interned.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SymbolName(Arc<str>);
+
+impl SymbolName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for SymbolName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for SymbolName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for SymbolName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for SymbolName {
+    fn from(name: &str) -> Self {
+        SymbolName(name.into())
+    }
+}
+
+impl From<String> for SymbolName {
+    fn from(name: String) -> Self {
+        SymbolName(name.into())
+    }
+}
+
+impl From<SymbolName> for String {
+    fn from(name: SymbolName) -> Self {
+        name.0.to_string()
+    }
+}
+
+impl PartialEq<str> for SymbolName {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for SymbolName {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for SymbolName {
+    fn eq(&self, other: &String) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl fmt::Display for SymbolName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Debug for SymbolName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl JsonSchema for SymbolName {
+    fn schema_name() -> String {
+        String::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+}
+
+/// A set of symbol names that hands out shared copies of equal names.
+#[derive(Debug, Default, Clone)]
+pub struct NameInterner {
+    names: HashSet<SymbolName>,
+}
+
+impl NameInterner {
+    /// Returns the interned copy of `name`, inserting it if it is new.
+    pub fn intern(&mut self, name: &str) -> SymbolName {
+        if let Some(interned) = self.names.get(name) {
+            return interned.clone();
+        }
+        let interned = SymbolName::from(name);
+        self.names.insert(interned.clone());
+        interned
+    }
+
+    /// Replaces `name` by its interned copy.
+    pub fn intern_in_place(&mut self, name: &mut SymbolName) {
+        match self.names.get(name.as_str()) {
+            Some(interned) => *name = interned.clone(),
+            None => {
+                self.names.insert(name.clone());
+            }
+        }
+    }
+
+    /// Replaces the names of all references in `e` by their interned copies.
+    pub fn intern_references<T, E: ExpressionVisitable<AlgebraicExpression<T>>>(
+        &mut self,
+        e: &mut E,
+    ) {
+        e.post_visit_expressions_mut(&mut |e| {
+            if let AlgebraicExpression::Reference(reference) = e {
+                self.intern_in_place(&mut reference.name);
+            }
+        });
+    }
+
+    /// The number of distinct names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// The interner only determines which names share memory,
+/// so all interners are considered equal.
+impl PartialEq for NameInterner {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Analyzed<T> {
+    /// Rebuilds the interned names from the references in identities and intermediate
+    /// columns, so that all references to the same column share one copy of its name.
+    /// This is needed after deserialization or after renaming columns.
+    pub fn intern_names(&mut self) {
+        let mut names = NameInterner::default();
+        self.post_visit_expressions_in_identities_mut(&mut |e| {
+            if let AlgebraicExpression::Reference(reference) = e {
+                names.intern_in_place(&mut reference.name);
+            }
+        });
+        self.names = names;
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt::{self, Display};
+
+use powdr_number::DegreeType;
+
+use crate::{parsed::asm::AbsoluteSymbolPath, SourceRef};
+
+use super::{
+    Analyzed, PolynomialReference, PolynomialType, PublicDeclaration, StatementIdentifier,
+    SymbolKind,
+};
+
+/// An error when adding a public declaration to an analyzed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicDeclarationError {
+    /// There already is a public declaration with this name.
+    DuplicateName(String),
+    /// There is no symbol with this name.
+    UnknownColumn(String),
+    /// The symbol is not a witness column.
+    NotWitnessColumn(String),
+    /// The column is an array, but no array index was given.
+    MissingArrayIndex(String),
+    /// An array index was given, but the column is not an array.
+    NotAnArray(String),
+    ArrayIndexOutOfBounds {
+        column: String,
+        index: usize,
+        length: DegreeType,
+    },
+    RowOutOfBounds {
+        row: DegreeType,
+        degree: DegreeType,
+    },
+}
+
+impl Display for PublicDeclarationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublicDeclarationError::DuplicateName(name) => {
+                write!(f, "Public declaration {name} already exists.")
+            }
+            PublicDeclarationError::UnknownColumn(column) => {
+                write!(f, "Unknown witness column {column}.")
+            }
+            PublicDeclarationError::NotWitnessColumn(column) => {
+                write!(f, "{column} is not a witness column.")
+            }
+            PublicDeclarationError::MissingArrayIndex(column) => {
+                write!(f, "The array {column} is referenced without an index.")
+            }
+            PublicDeclarationError::NotAnArray(column) => {
+                write!(f, "An array index is used, but {column} is not an array.")
+            }
+            PublicDeclarationError::ArrayIndexOutOfBounds {
+                column,
+                index,
+                length,
+            } => write!(
+                f,
+                "Index {index} is out of bounds for {column}, which only has length {length}."
+            ),
+            PublicDeclarationError::RowOutOfBounds { row, degree } => {
+                write!(f, "Row {row} is out of bounds for degree {degree}.")
+            }
+        }
+    }
+}
+
+impl<T> Analyzed<T> {
+    /// Adds a public declaration `name` exposing the witness column `column`
+    /// (element `array_index` if it is an array) at row `row`. The name is absolute,
+    /// so it is only printed inside a namespace if it is qualified with it.
+    /// Both names can use `::` or `.` as separators.
+    /// The declaration is appended to the source order.
+    /// @returns the ID of the new public declaration.
+    pub fn add_public(
+        &mut self,
+        name: &str,
+        column: &str,
+        array_index: Option<usize>,
+        row: DegreeType,
+    ) -> Result<u64, PublicDeclarationError> {
+        let path = AbsoluteSymbolPath::from_dotted_string(name);
+        if self.public_declarations.contains_key(&path) {
+            return Err(PublicDeclarationError::DuplicateName(name.to_string()));
+        }
+        let (symbol, _) = self
+            .definitions
+            .get(&AbsoluteSymbolPath::from_dotted_string(column))
+            .ok_or_else(|| PublicDeclarationError::UnknownColumn(column.to_string()))?;
+        if symbol.kind != SymbolKind::Poly(PolynomialType::Committed) {
+            return Err(PublicDeclarationError::NotWitnessColumn(column.to_string()));
+        }
+        match (array_index, symbol.length) {
+            (Some(index), Some(length)) if index as u64 >= length => {
+                return Err(PublicDeclarationError::ArrayIndexOutOfBounds {
+                    column: column.to_string(),
+                    index,
+                    length,
+                })
+            }
+            (Some(_), None) => return Err(PublicDeclarationError::NotAnArray(column.to_string())),
+            (None, Some(_)) => {
+                return Err(PublicDeclarationError::MissingArrayIndex(
+                    column.to_string(),
+                ))
+            }
+            _ => {}
+        }
+        if let Some(degree) = self.degree {
+            if row >= degree {
+                return Err(PublicDeclarationError::RowOutOfBounds { row, degree });
+            }
+        }
+
+        let id = self
+            .public_declarations
+            .values()
+            .map(|declaration| declaration.id + 1)
+            .max()
+            .unwrap_or_default();
+        let polynomial = PolynomialReference {
+            name: symbol.absolute_name.clone(),
+            poly_id: Some(symbol.into()),
+            generic_args: Some(vec![]),
+        };
+        self.public_declarations.insert(
+            path.clone(),
+            PublicDeclaration {
+                id,
+                source: SourceRef::unknown(),
+                name: path.to_dotted_string(),
+                polynomial,
+                array_index,
+                index: row,
+            },
+        );
+        self.source_order
+            .push(StatementIdentifier::PublicDeclaration(path));
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::analyzed::test_utils::*;
+    use crate::analyzed::PolynomialType;
+
+    use super::PublicDeclarationError;
+
+    fn input() -> Pil {
+        let mut pil = new_pil(16);
+        add_column(&mut pil, "N.a", PolynomialType::Committed, None);
+        add_column(&mut pil, "N.b", PolynomialType::Committed, Some(2));
+        add_column(&mut pil, "N.LINE", PolynomialType::Constant, None);
+        pil.add_public("N.first", "N.a", None, 0).unwrap();
+        let identity = next(col(&pil, "N.a")) - (col(&pil, "N.a") + col(&pil, "N.b[1]"));
+        add_identity(&mut pil, identity);
+        pil
+    }
+
+    #[test]
+    fn add_public() {
+        let mut pil = input();
+        assert_eq!(pil.add_public("last", "N.a", None, 15), Ok(1));
+        assert_eq!(pil.add_public("out", "N.b", Some(1), 3), Ok(2));
+        let expected = r#"namespace N(16);
+    col witness a;
+    col witness b[2];
+    col fixed LINE;
+    public first = N.a(0);
+    N.a' = (N.a + N.b[1]);
+namespace (16);
+public last = N.a(15);
+public out = N.b[1](3);
+"#;
+        assert_eq!(pil.to_string(), expected);
+        let names = pil
+            .public_declarations_in_source_order()
+            .into_iter()
+            .map(|(name, declaration)| (name.to_dotted_string(), declaration.id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ("N.first".to_string(), 0),
+                ("last".to_string(), 1),
+                ("out".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn add_public_errors() {
+        let mut pil = input();
+        let original = pil.clone();
+        assert_eq!(
+            pil.add_public("N.first", "N.a", None, 1),
+            Err(PublicDeclarationError::DuplicateName("N.first".to_string()))
+        );
+        assert_eq!(
+            pil.add_public("out", "N.c", None, 1),
+            Err(PublicDeclarationError::UnknownColumn("N.c".to_string()))
+        );
+        assert_eq!(
+            pil.add_public("out", "N.LINE", None, 1),
+            Err(PublicDeclarationError::NotWitnessColumn(
+                "N.LINE".to_string()
+            ))
+        );
+        assert_eq!(
+            pil.add_public("out", "N.b", None, 1),
+            Err(PublicDeclarationError::MissingArrayIndex("N.b".to_string()))
+        );
+        assert_eq!(
+            pil.add_public("out", "N.a", Some(0), 1),
+            Err(PublicDeclarationError::NotAnArray("N.a".to_string()))
+        );
+        assert_eq!(
+            pil.add_public("out", "N.b", Some(2), 1),
+            Err(PublicDeclarationError::ArrayIndexOutOfBounds {
+                column: "N.b".to_string(),
+                index: 2,
+                length: 2
+            })
+        );
+        assert_eq!(
+            pil.add_public("out", "N.a", None, 16),
+            Err(PublicDeclarationError::RowOutOfBounds {
+                row: 16,
+                degree: 16
+            })
+        );
+        assert_eq!(pil, original);
+    }
+}
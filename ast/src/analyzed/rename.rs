@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+
+use super::{
+    AlgebraicExpression, Analyzed, Expression, Reference, StatementIdentifier, SymbolName,
+};
+use crate::parsed::asm::AbsoluteSymbolPath;
+
+/// An error when renaming symbols of an analyzed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameError {
+    /// There is no symbol or public declaration with this name.
+    UnknownSymbol(String),
+    /// The new name is already used by a symbol or public declaration which is not renamed,
+    /// or by another renamed symbol.
+    Collision(String),
+}
+
+impl Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenameError::UnknownSymbol(name) => write!(f, "Symbol {name} not found."),
+            RenameError::Collision(name) => write!(f, "Symbol {name} already exists."),
+        }
+    }
+}
+
+impl<T> Analyzed<T> {
+    /// Renames a symbol (or public declaration) and updates all references to it.
+    pub fn rename_symbol(&mut self, old: &str, new: &str) -> Result<(), RenameError> {
+        self.rename_symbols(&[(old.to_string(), new.to_string())].into_iter().collect())
+    }
+
+    /// Renames symbols (or public declarations) from the keys of `renames` to its values and
+    /// updates all references to them. Symbols can swap their names.
+    /// Both the old and the new names can use `::` or `.` as separators.
+    /// Fails without changing anything if a symbol does not exist or a new name is already used.
+    pub fn rename_symbols(
+        &mut self,
+        renames: &BTreeMap<String, String>,
+    ) -> Result<(), RenameError> {
+        let renames: BTreeMap<AbsoluteSymbolPath, AbsoluteSymbolPath> = renames
+            .iter()
+            .map(|(old, new)| {
+                (
+                    AbsoluteSymbolPath::from_dotted_string(old),
+                    AbsoluteSymbolPath::from_dotted_string(new),
+                )
+            })
+            .collect();
+        let exists = |name: &AbsoluteSymbolPath| {
+            self.definitions.contains_key(name)
+                || self.intermediate_columns.contains_key(name)
+                || self.public_declarations.contains_key(name)
+        };
+        if let Some(old) = renames.keys().find(|old| !exists(*old)) {
+            return Err(RenameError::UnknownSymbol(old.to_dotted_string()));
+        }
+        let mut new_names = BTreeSet::new();
+        if let Some(new) = renames
+            .values()
+            .find(|new| !new_names.insert(*new) || (exists(*new) && !renames.contains_key(*new)))
+        {
+            return Err(RenameError::Collision(new.to_dotted_string()));
+        }
+
+        let rename_path = |name: &mut AbsoluteSymbolPath| {
+            if let Some(new) = renames.get(name) {
+                *name = new.clone();
+            }
+        };
+        let rename = |name: &mut String| {
+            if let Some(new) = renames.get(&AbsoluteSymbolPath::from_dotted_string(name)) {
+                *name = new.to_dotted_string();
+            }
+        };
+        // Algebraic references to array elements end in `[i]`.
+        let rename_element = |name: &mut SymbolName| {
+            let base = name.split_once('[').map_or(name.as_str(), |(base, _)| base);
+            if let Some(new) = renames.get(&AbsoluteSymbolPath::from_dotted_string(base)) {
+                *name = format!("{}{}", new.to_dotted_string(), &name[base.len()..]).into();
+            }
+        };
+
+        self.definitions = std::mem::take(&mut self.definitions)
+            .into_iter()
+            .map(|(mut name, (mut symbol, value))| {
+                rename_path(&mut name);
+                rename(&mut symbol.absolute_name);
+                (name, (symbol, value))
+            })
+            .collect();
+        self.intermediate_columns = std::mem::take(&mut self.intermediate_columns)
+            .into_iter()
+            .map(|(mut name, (mut symbol, value))| {
+                rename_path(&mut name);
+                rename(&mut symbol.absolute_name);
+                (name, (symbol, value))
+            })
+            .collect();
+        self.public_declarations = std::mem::take(&mut self.public_declarations)
+            .into_iter()
+            .map(|(mut name, mut declaration)| {
+                rename_path(&mut name);
+                rename(&mut declaration.name);
+                rename(&mut declaration.polynomial.name);
+                (name, declaration)
+            })
+            .collect();
+        for statement in &mut self.source_order {
+            match statement {
+                StatementIdentifier::Definition(name)
+                | StatementIdentifier::PublicDeclaration(name) => rename_path(name),
+                StatementIdentifier::Identity(_) => {}
+            }
+        }
+        self.post_visit_expressions_in_identities_mut(&mut |e| match e {
+            AlgebraicExpression::Reference(reference) => rename_element(&mut reference.name),
+            AlgebraicExpression::PublicReference(name) => rename(name),
+            _ => {}
+        });
+        self.post_visit_expressions_in_definitions_mut(&mut |e| {
+            if let Expression::Reference(Reference::Poly(reference)) = e {
+                rename(&mut reference.name);
+            }
+        });
+        self.intern_names();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::analyzed::test_utils::*;
+    use crate::analyzed::PolynomialType;
+
+    use super::RenameError;
+
+    fn input() -> Pil {
+        let mut pil = new_pil(16);
+        add_fixed(&mut pil, "N.BYTE", number(255));
+        add_column(&mut pil, "N.a", PolynomialType::Committed, None);
+        add_column(&mut pil, "N.b", PolynomialType::Committed, Some(2));
+        let inter = col(&pil, "N.a") * col(&pil, "N.BYTE");
+        add_intermediate(&mut pil, "N.inter", vec![inter]);
+        pil.add_public("N.out", "N.a", None, 2).unwrap();
+        let identity = col(&pil, "N.b[0]") - col(&pil, "N.inter");
+        add_identity(&mut pil, identity);
+        let (a, byte) = (col(&pil, "N.a"), col(&pil, "N.BYTE"));
+        add_lookup(&mut pil, None, vec![a], vec![byte]);
+        pil
+    }
+
+    #[test]
+    fn rename_column() {
+        let mut pil = input();
+        pil.rename_symbol("N.a", "N.x").unwrap();
+        let expected = r#"namespace N(16);
+    col fixed BYTE = 255;
+    col witness x;
+    col witness b[2];
+    col inter = (N.x * N.BYTE);
+    public out = N.x(2);
+    N.b[0] = N.inter;
+    { N.x } in { N.BYTE };
+"#;
+        assert_eq!(pil.to_string(), expected);
+    }
+
+    #[test]
+    fn rename_symbols() {
+        let mut pil = input();
+        pil.rename_symbols(
+            &[
+                ("N.b".to_string(), "M.b".to_string()),
+                ("N.BYTE".to_string(), "N.inter".to_string()),
+                ("N.inter".to_string(), "N.BYTE".to_string()),
+                ("N.out".to_string(), "N.result".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        )
+        .unwrap();
+        let formatted = pil.to_string();
+        assert!(formatted.contains("M.b[0] = N.BYTE;"), "{formatted}");
+        assert!(formatted.contains("{ N.a } in { N.inter };"), "{formatted}");
+        assert!(formatted.contains("public result = N.a(2);"), "{formatted}");
+    }
+
+    #[test]
+    fn rename_errors() {
+        let mut pil = input();
+        let original = pil.to_string();
+        assert_eq!(
+            pil.rename_symbol("N.c", "N.d"),
+            Err(RenameError::UnknownSymbol("N.c".to_string()))
+        );
+        assert_eq!(
+            pil.rename_symbol("N.a", "N.BYTE"),
+            Err(RenameError::Collision("N.BYTE".to_string()))
+        );
+        assert_eq!(
+            pil.rename_symbol("N.a", "N.out"),
+            Err(RenameError::Collision("N.out".to_string()))
+        );
+        assert_eq!(
+            pil.rename_symbols(
+                &[
+                    ("N.a".to_string(), "N.c".to_string()),
+                    ("N.b".to_string(), "N.c".to_string()),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            Err(RenameError::Collision("N.c".to_string()))
+        );
+        assert_eq!(pil.to_string(), original);
+    }
+
+    #[test]
+    fn rename_with_either_separator() {
+        let mut pil = input();
+        pil.rename_symbol("N::a", "M.sub.x").unwrap();
+        for name in ["M::sub::x", "M.sub.x", "::M::sub.x"] {
+            assert_eq!(pil.definition(name).unwrap().0.absolute_name, "M::sub::x");
+        }
+        assert!(pil.intermediate_column("::N::inter").is_some());
+        let formatted = pil.to_string();
+        assert!(
+            formatted.contains("{ M::sub::x } in { N.BYTE };"),
+            "{formatted}"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Substitution and simplification of algebraic expressions.
+//!
+//! All functions here traverse the expression with an explicit stack instead of
+//! recursion, so that they also work for very deeply nested expressions.
+
+use std::collections::BTreeMap;
+
+use powdr_number::FieldElement;
+
+use super::{
+    AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator,
+    PolyID,
+};
+
+enum Task<'a, T> {
+    Visit(&'a AlgebraicExpression<T>),
+    Binary(AlgebraicBinaryOperator),
+    Unary(AlgebraicUnaryOperator),
+}
+
+impl<T> AlgebraicExpression<T> {
+    /// Computes a value bottom-up: `leaf` is called on references, public references and
+    /// numbers, `binary` and `unary` on the values of the operands of operations.
+    pub(super) fn fold<'a, R>(
+        &'a self,
+        mut leaf: impl FnMut(&'a Self) -> R,
+        mut binary: impl FnMut(R, AlgebraicBinaryOperator, R) -> R,
+        mut unary: impl FnMut(AlgebraicUnaryOperator, R) -> R,
+    ) -> R {
+        let mut tasks = vec![Task::Visit(self)];
+        let mut values = vec![];
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Visit(AlgebraicExpression::BinaryOperation(left, op, right)) => {
+                    tasks.extend([Task::Binary(*op), Task::Visit(right), Task::Visit(left)]);
+                }
+                Task::Visit(AlgebraicExpression::UnaryOperation(op, inner)) => {
+                    tasks.extend([Task::Unary(*op), Task::Visit(inner)]);
+                }
+                Task::Visit(e) => values.push(leaf(e)),
+                Task::Binary(op) => {
+                    let right = values.pop().unwrap();
+                    let left = values.pop().unwrap();
+                    values.push(binary(left, op, right));
+                }
+                Task::Unary(op) => {
+                    let inner = values.pop().unwrap();
+                    values.push(unary(op, inner));
+                }
+            }
+        }
+        values.pop().unwrap()
+    }
+}
+
+impl<T: Clone> AlgebraicExpression<T> {
+    /// Replaces all references to the columns in `map` by their expressions.
+    /// A reference to the next row of such a column is replaced by the expression
+    /// with all its references moved to the next row, which panics if the expression
+    /// already references a next row.
+    pub fn substitute_columns(&self, map: &BTreeMap<PolyID, Self>) -> Self {
+        self.fold(
+            |e| match e {
+                AlgebraicExpression::Reference(reference) => {
+                    match (map.get(&reference.poly_id), reference.next) {
+                        (Some(replacement), false) => replacement.clone(),
+                        (Some(replacement), true) => replacement.shifted_to_next_row(),
+                        (None, _) => e.clone(),
+                    }
+                }
+                _ => e.clone(),
+            },
+            AlgebraicExpression::new_binary,
+            |op, inner| AlgebraicExpression::UnaryOperation(op, Box::new(inner)),
+        )
+    }
+
+    /// Moves all references to the next row, panics if there already is a next reference.
+    fn shifted_to_next_row(&self) -> Self {
+        self.fold(
+            |e| match e {
+                AlgebraicExpression::Reference(reference) => {
+                    assert!(
+                        !reference.next,
+                        "Cannot substitute a next reference by an expression with next references: {}'",
+                        reference.name
+                    );
+                    AlgebraicExpression::Reference(AlgebraicReference {
+                        next: true,
+                        ..reference.clone()
+                    })
+                }
+                _ => e.clone(),
+            },
+            AlgebraicExpression::new_binary,
+            |op, inner| AlgebraicExpression::UnaryOperation(op, Box::new(inner)),
+        )
+    }
+}
+
+impl<T: FieldElement> AlgebraicExpression<T> {
+    /// Simplifies the expression by folding constants, removing neutral elements
+    /// and double negations and combining constant factors.
+    pub fn simplify(&self) -> Self {
+        self.fold(
+            Clone::clone,
+            simplify_binary_operation,
+            |op, inner| match (op, inner) {
+                (AlgebraicUnaryOperator::Minus, AlgebraicExpression::Number(n)) => {
+                    AlgebraicExpression::Number(-n)
+                }
+                (
+                    AlgebraicUnaryOperator::Minus,
+                    AlgebraicExpression::UnaryOperation(AlgebraicUnaryOperator::Minus, inner),
+                ) => *inner,
+                (op, inner) => AlgebraicExpression::UnaryOperation(op, Box::new(inner)),
+            },
+        )
+    }
+}
+
+/// Simplifies a binary operation, assuming that its operands are already simplified.
+fn simplify_binary_operation<T: FieldElement>(
+    left: AlgebraicExpression<T>,
+    op: AlgebraicBinaryOperator,
+    right: AlgebraicExpression<T>,
+) -> AlgebraicExpression<T> {
+    use AlgebraicBinaryOperator::*;
+    use AlgebraicExpression::Number;
+    match (left, op, right) {
+        (Number(l), Add, Number(r)) => Number(l + r),
+        (Number(l), Sub, Number(r)) => Number(l - r),
+        (Number(l), Mul, Number(r)) => Number(l * r),
+        (Number(l), Pow, Number(r)) => Number(l.pow(r.to_integer())),
+        (Number(n), Mul, _) | (_, Mul, Number(n)) if n == 0.into() => Number(0.into()),
+        (Number(n), Mul, e) | (e, Mul, Number(n)) if n.is_one() => e,
+        (Number(n), Add, e) | (e, Add, Number(n)) | (e, Sub, Number(n)) if n == 0.into() => e,
+        (Number(c1), Mul, AlgebraicExpression::BinaryOperation(inner_left, Mul, inner_right))
+        | (AlgebraicExpression::BinaryOperation(inner_left, Mul, inner_right), Mul, Number(c1))
+            if matches!(
+                (inner_left.as_ref(), inner_right.as_ref()),
+                (Number(_), _) | (_, Number(_))
+            ) =>
+        {
+            let (c2, e) = match (*inner_left, *inner_right) {
+                (Number(c2), e) | (e, Number(c2)) => (c2, e),
+                _ => unreachable!(),
+            };
+            // The product might be zero or one, so simplify again.
+            simplify_binary_operation(Number(c1 * c2), Mul, e)
+        }
+        (left, op, right) => AlgebraicExpression::new_binary(left, op, right),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use powdr_number::GoldilocksField;
+
+    use crate::analyzed::{
+        AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator, PolyID, PolynomialType,
+    };
+
+    type Expr = AlgebraicExpression<GoldilocksField>;
+
+    fn col(name: &str, id: u64, next: bool) -> Expr {
+        AlgebraicExpression::Reference(AlgebraicReference {
+            name: name.into(),
+            poly_id: PolyID {
+                id,
+                ptype: PolynomialType::Committed,
+            },
+            next,
+        })
+    }
+
+    fn num(n: u64) -> Expr {
+        AlgebraicExpression::Number(n.into())
+    }
+
+    fn poly_id(id: u64) -> PolyID {
+        PolyID {
+            id,
+            ptype: PolynomialType::Committed,
+        }
+    }
+
+    fn sample() -> Expr {
+        (col("x", 0, true) - col("x", 0, false) * num(2))
+            * AlgebraicExpression::UnaryOperation(
+                AlgebraicUnaryOperator::Minus,
+                Box::new(col("y", 1, false) + num(0) * col("x", 0, false)),
+            )
+    }
+
+    #[test]
+    fn substitute_by_itself() {
+        let e = sample();
+        let map = [
+            (poly_id(0), col("x", 0, false)),
+            (poly_id(1), col("y", 1, false)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(e.substitute_columns(&map), e);
+        assert_eq!(e.substitute_columns(&BTreeMap::new()), e);
+    }
+
+    #[test]
+    fn substitute() {
+        let map = [(poly_id(0), col("y", 1, false) + num(1))]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            sample().substitute_columns(&map).to_string(),
+            "(((y' + 1) - ((y + 1) * 2)) * -(y + (0 * (y + 1))))"
+        );
+    }
+
+    #[test]
+    #[should_panic = "Cannot substitute a next reference by an expression with next references: y'"]
+    fn substitute_next_by_next() {
+        let map = [(poly_id(0), col("y", 1, true))].into_iter().collect();
+        sample().substitute_columns(&map);
+    }
+
+    #[test]
+    fn simplify() {
+        let simplified = sample().simplify();
+        assert_eq!(simplified.to_string(), "((x' - (x * 2)) * -y)");
+        assert_eq!(simplified.simplify(), simplified);
+        assert_eq!(
+            (num(2) * (num(3) * col("x", 0, false)) + (num(4) - num(4))).simplify(),
+            num(6) * col("x", 0, false)
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! Hash-consed storage of algebraic expressions.
+//!
+//! Condensation often produces structurally equal subexpressions in many identities.
+//! An [`ExpressionArena`] stores every distinct subexpression exactly once and refers to
+//! children by index, so that shared subtrees are also shared in serialized form.
+//!
+//! This is an export-only view: it is built from the identities of an existing
+//! [`Analyzed`] file, which still keeps all expression trees in full. It makes
+//! serialized identities smaller, but it does not reduce the memory used during
+//! or after condensation.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use crate::parsed::SelectedExpressions;
+
+use super::{
+    AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator,
+    Analyzed, Identity,
+};
+
+/// The index of an expression in an [`ExpressionArena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ExprId(u32);
+
+impl From<usize> for ExprId {
+    fn from(index: usize) -> Self {
+        ExprId(index.try_into().expect("Too many expressions."))
+    }
+}
+
+/// A node of an expression in an [`ExpressionArena`], with its children replaced by their indices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ExprNode<T> {
+    Reference(AlgebraicReference),
+    PublicReference(String),
+    Number(T),
+    BinaryOperation(ExprId, AlgebraicBinaryOperator, ExprId),
+    UnaryOperation(AlgebraicUnaryOperator, ExprId),
+}
+
+/// A set of algebraic expressions in which structurally equal subexpressions are stored once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    into = "Vec<ExprNode<T>>",
+    from = "Vec<ExprNode<T>>",
+    bound(
+        serialize = "T: Clone + Serialize",
+        deserialize = "T: Deserialize<'de>"
+    )
+)]
+pub struct ExpressionArena<T> {
+    nodes: Vec<ExprNode<T>>,
+    ids: HashMap<ExprNode<T>, ExprId>,
+    /// The number of nodes of all expressions inserted so far, counting shared nodes each time.
+    inserted_nodes: usize,
+}
+
+impl<T> Default for ExpressionArena<T> {
+    fn default() -> Self {
+        Self {
+            nodes: Default::default(),
+            ids: Default::default(),
+            inserted_nodes: 0,
+        }
+    }
+}
+
+impl<T: Clone + Hash + Eq> ExpressionArena<T> {
+    /// Inserts the expression and all its subexpressions and returns the index of the expression.
+    /// Structurally equal expressions get the same index.
+    pub fn insert(&mut self, e: &AlgebraicExpression<T>) -> ExprId {
+        // Post-order traversal with an explicit stack, because expressions can be deeply nested.
+        let mut stack = vec![(e, false)];
+        let mut ids = vec![];
+        while let Some((e, children_done)) = stack.pop() {
+            let node = match e {
+                AlgebraicExpression::BinaryOperation(left, _, right) if !children_done => {
+                    stack.extend([(e, true), (right.as_ref(), false), (left.as_ref(), false)]);
+                    continue;
+                }
+                AlgebraicExpression::UnaryOperation(_, inner) if !children_done => {
+                    stack.extend([(e, true), (inner.as_ref(), false)]);
+                    continue;
+                }
+                AlgebraicExpression::BinaryOperation(_, op, _) => {
+                    let right = ids.pop().unwrap();
+                    let left = ids.pop().unwrap();
+                    ExprNode::BinaryOperation(left, *op, right)
+                }
+                AlgebraicExpression::UnaryOperation(op, _) => {
+                    ExprNode::UnaryOperation(*op, ids.pop().unwrap())
+                }
+                AlgebraicExpression::Reference(r) => ExprNode::Reference(r.clone()),
+                AlgebraicExpression::PublicReference(name) => {
+                    ExprNode::PublicReference(name.clone())
+                }
+                AlgebraicExpression::Number(n) => ExprNode::Number(n.clone()),
+            };
+            ids.push(self.insert_node(node));
+        }
+        ids.pop().unwrap()
+    }
+
+    /// Frees the memory used to find equal nodes. It is rebuilt on the next insertion.
+    pub fn compact(&mut self) {
+        self.ids = Default::default();
+    }
+
+    fn insert_node(&mut self, node: ExprNode<T>) -> ExprId {
+        self.inserted_nodes += 1;
+        if self.ids.len() < self.nodes.len() {
+            self.ids = index_nodes(&self.nodes);
+        }
+        if let Some(id) = self.ids.get(&node) {
+            return *id;
+        }
+        let id = ExprId::from(self.nodes.len());
+        self.nodes.push(node.clone());
+        self.ids.insert(node, id);
+        id
+    }
+}
+
+impl<T: Clone> ExpressionArena<T> {
+    /// Returns the node with the given index.
+    pub fn node(&self, id: ExprId) -> &ExprNode<T> {
+        &self.nodes[id.0 as usize]
+    }
+
+    /// Reconstructs the expression with the given index as a tree.
+    pub fn expression(&self, id: ExprId) -> AlgebraicExpression<T> {
+        let mut stack = vec![(id, false)];
+        let mut expressions = vec![];
+        while let Some((id, children_done)) = stack.pop() {
+            let e = match (self.node(id), children_done) {
+                (ExprNode::BinaryOperation(left, _, right), false) => {
+                    stack.extend([(id, true), (*right, false), (*left, false)]);
+                    continue;
+                }
+                (ExprNode::UnaryOperation(_, inner), false) => {
+                    stack.extend([(id, true), (*inner, false)]);
+                    continue;
+                }
+                (ExprNode::BinaryOperation(_, op, _), true) => {
+                    let right = expressions.pop().unwrap();
+                    let left = expressions.pop().unwrap();
+                    AlgebraicExpression::BinaryOperation(Box::new(left), *op, Box::new(right))
+                }
+                (ExprNode::UnaryOperation(op, _), true) => {
+                    AlgebraicExpression::UnaryOperation(*op, Box::new(expressions.pop().unwrap()))
+                }
+                (ExprNode::Reference(r), _) => AlgebraicExpression::Reference(r.clone()),
+                (ExprNode::PublicReference(name), _) => {
+                    AlgebraicExpression::PublicReference(name.clone())
+                }
+                (ExprNode::Number(n), _) => AlgebraicExpression::Number(n.clone()),
+            };
+            expressions.push(e);
+        }
+        expressions.pop().unwrap()
+    }
+}
+
+impl<T> ExpressionArena<T> {
+    /// The number of distinct subexpressions.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The number of nodes of all inserted expressions, counting shared subexpressions
+    /// each time they were inserted.
+    pub fn inserted_nodes(&self) -> usize {
+        self.inserted_nodes
+    }
+
+    /// The number of inserted nodes per stored node, which is at least one.
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.nodes.is_empty() {
+            1.0
+        } else {
+            self.inserted_nodes as f64 / self.nodes.len() as f64
+        }
+    }
+}
+
+impl<T> From<ExpressionArena<T>> for Vec<ExprNode<T>> {
+    fn from(arena: ExpressionArena<T>) -> Self {
+        arena.nodes
+    }
+}
+
+impl<T> From<Vec<ExprNode<T>>> for ExpressionArena<T> {
+    fn from(nodes: Vec<ExprNode<T>>) -> Self {
+        Self {
+            inserted_nodes: nodes.len(),
+            nodes,
+            ids: Default::default(),
+        }
+    }
+}
+
+fn index_nodes<T: Clone + Hash + Eq>(nodes: &[ExprNode<T>]) -> HashMap<ExprNode<T>, ExprId> {
+    nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.clone(), ExprId::from(i)))
+        .collect()
+}
+
+/// The identities of an analyzed file with all their expressions stored in one [`ExpressionArena`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T: Clone + Serialize",
+    deserialize = "T: Deserialize<'de>"
+))]
+pub struct SharedIdentities<T> {
+    pub arena: ExpressionArena<T>,
+    pub identities: Vec<Identity<ExprId>>,
+}
+
+impl<T: Clone> SharedIdentities<T> {
+    /// Reconstructs the identity with the given index, equal to the original identity.
+    pub fn identity(&self, index: usize) -> Identity<AlgebraicExpression<T>> {
+        let identity = &self.identities[index];
+        let expand = |s: &SelectedExpressions<ExprId>| SelectedExpressions {
+            selector: s.selector.map(|id| self.arena.expression(id)),
+            expressions: s
+                .expressions
+                .iter()
+                .map(|id| self.arena.expression(*id))
+                .collect(),
+        };
+        Identity {
+            id: identity.id,
+            kind: identity.kind,
+            source: identity.source.clone(),
+            label: identity.label.clone(),
+            left: expand(&identity.left),
+            right: expand(&identity.right),
+        }
+    }
+}
+
+impl<T: Clone + Display> Display for SharedIdentities<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for index in 0..self.identities.len() {
+            writeln!(f, "{}", self.identity(index))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Clone + Hash + Eq> Analyzed<T> {
+    /// Returns a copy of the identities with structurally equal subexpressions stored
+    /// only once, for example to serialize them compactly.
+    /// The identities of `self` are not changed.
+    pub fn shared_identities(&self) -> SharedIdentities<T> {
+        let mut arena = ExpressionArena::default();
+        let mut share = |s: &SelectedExpressions<AlgebraicExpression<T>>| SelectedExpressions {
+            selector: s.selector.as_ref().map(|e| arena.insert(e)),
+            expressions: s.expressions.iter().map(|e| arena.insert(e)).collect(),
+        };
+        let identities = self
+            .identities
+            .iter()
+            .map(|identity| Identity {
+                id: identity.id,
+                kind: identity.kind,
+                source: identity.source.clone(),
+                label: identity.label.clone(),
+                left: share(&identity.left),
+                right: share(&identity.right),
+            })
+            .collect();
+        arena.compact();
+        SharedIdentities { arena, identities }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+
+    use crate::analyzed::test_utils::*;
+    use crate::analyzed::PolynomialType;
+
+    use super::{ExprId, SharedIdentities};
+
+    fn input() -> Pil {
+        let mut pil = new_pil(16);
+        for name in ["N.flag", "N.x", "N.y", "N.z"] {
+            add_column(&mut pil, name, PolynomialType::Committed, None);
+        }
+        let [flag, x, y, z] = ["N.flag", "N.x", "N.y", "N.z"].map(|name| col(&pil, name));
+        let flag_x = flag.clone() * x.clone();
+        add_identity(&mut pil, flag.clone() * (num(1) - flag));
+        add_identity(&mut pil, flag_x.clone() * (y.clone() - z.clone()));
+        add_identity(&mut pil, flag_x.clone() * (y.clone() + z.clone()));
+        add_identity(&mut pil, flag_x.clone() * (y.clone() - z) + num(1) - num(1));
+        add_lookup(&mut pil, None, vec![flag_x.clone(), y], vec![x, flag_x]);
+        pil
+    }
+
+    #[test]
+    fn shared_subexpressions_are_stored_once() {
+        let pil = input();
+        let shared = pil.shared_identities();
+        assert!(shared.arena.len() < shared.arena.inserted_nodes());
+        assert!(
+            shared.arena.dedup_ratio() > 1.5,
+            "{}",
+            shared.arena.dedup_ratio()
+        );
+        let subexpressions = (0..shared.arena.len())
+            .map(|id| shared.arena.expression(ExprId::from(id)).to_string())
+            .collect::<Vec<_>>();
+        let count = |e: &str| subexpressions.iter().filter(|s| *s == e).count();
+        assert_eq!(count("(N.flag * N.x)"), 1);
+        assert_eq!(count("((N.flag * N.x) * (N.y - N.z))"), 1);
+
+        // Inserting an existing expression into the compacted arena does not add nodes.
+        let mut arena = shared.arena;
+        let selector = pil.identities[1].left.selector.as_ref().unwrap();
+        assert_eq!(
+            arena.insert(selector),
+            shared.identities[1].left.selector.unwrap()
+        );
+        assert_eq!(arena.len(), subexpressions.len());
+    }
+
+    #[test]
+    fn identities_are_reconstructed() {
+        let pil = input();
+        let shared = pil.shared_identities();
+        for (index, identity) in pil.identities.iter().enumerate() {
+            assert_eq!(&shared.identity(index), identity);
+        }
+        let expected = pil
+            .identities
+            .iter()
+            .map(|i| format!("{i}\n"))
+            .collect::<String>();
+        assert_eq!(shared.to_string(), expected);
+    }
+
+    #[test]
+    fn serialization_keeps_sharing() {
+        let shared = input().shared_identities();
+        let json = serde_json::to_value(&shared).unwrap();
+        assert_eq!(json["arena"].as_array().unwrap().len(), shared.arena.len());
+        let deserialized: SharedIdentities<GoldilocksField> = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized.arena.len(), shared.arena.len());
+        assert_eq!(deserialized.to_string(), shared.to_string());
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::SourceRef;
+
+use super::{Analyzed, IdentityKind, PolyID};
+
+/// Maps the identities and columns of an analyzed file back to the source
+/// they were created from, see `Analyzed::source_map`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    /// The identities, in the order of `Analyzed::identities`.
+    pub identities: Vec<IdentitySource>,
+    /// The columns, ordered by type and ID. Arrays have one entry per element.
+    pub columns: Vec<ColumnSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentitySource {
+    /// The index of the identity in `Analyzed::identities`.
+    pub index: usize,
+    pub kind: IdentityKind,
+    /// The ID of the identity, which is specific to its kind.
+    pub id: u64,
+    pub label: Option<String>,
+    /// The source of the identity, followed by the source of the identity statement
+    /// it was condensed from if that is different. For PIL generated from asm,
+    /// these point to the asm code that created the identity.
+    pub sources: Vec<SourceRef>,
+    /// If the identity statement evaluated to an array of constraints,
+    /// the index of this identity in the array.
+    pub array_index: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnSource {
+    pub poly_id: PolyID,
+    /// The name of the column, in the form `array[index]` for array elements.
+    pub name: String,
+    /// The source of the declaration of the column (or of its array).
+    pub source: SourceRef,
+}
+
+impl<T> Analyzed<T> {
+    /// @returns the sources of all identities and columns.
+    pub fn source_map(&self) -> SourceMap {
+        let identities = self
+            .identities
+            .iter()
+            .enumerate()
+            .map(|(index, identity)| {
+                let provenance = self.identity_provenance(index);
+                let mut sources = vec![identity.source.clone()];
+                if let Some(provenance) = provenance {
+                    if provenance.source != identity.source {
+                        sources.push(provenance.source.clone());
+                    }
+                }
+                IdentitySource {
+                    index,
+                    kind: identity.kind,
+                    id: identity.id,
+                    label: identity.label.clone(),
+                    sources,
+                    array_index: provenance.and_then(|p| p.array_index),
+                }
+            })
+            .collect();
+        let columns = self
+            .columns()
+            .into_iter()
+            .flat_map(|column| {
+                let source = &column.symbol().source;
+                column.elements().map(|(name, poly_id)| ColumnSource {
+                    poly_id,
+                    name,
+                    source: source.clone(),
+                })
+            })
+            .collect();
+        SourceMap {
+            identities,
+            columns,
+        }
+    }
+}
+
+impl SourceMap {
+    /// @returns the sources of the identity of the given kind and ID.
+    pub fn identity(&self, kind: IdentityKind, id: u64) -> Option<&IdentitySource> {
+        self.identities
+            .iter()
+            .find(|identity| identity.kind == kind && identity.id == id)
+    }
+
+    /// @returns the source of the column with the given ID.
+    pub fn column(&self, poly_id: PolyID) -> Option<&ColumnSource> {
+        self.columns.iter().find(|column| column.poly_id == poly_id)
+    }
+
+    /// Writes the source map as pretty-printed JSON.
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), String> {
+        serde_json::to_writer_pretty(writer, self)
+            .map_err(|e| format!("Failed to write source map: {e}"))
+    }
+}
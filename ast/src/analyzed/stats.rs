@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use powdr_number::FieldElement;
+use serde::{Deserialize, Serialize};
+
+use crate::parsed::visitor::ExpressionVisitable;
+
+use super::{Analyzed, IdentityKind};
+
+/// Summary statistics of an analyzed file, see `Analyzed::stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalysisStats {
+    /// The number of witness columns, with multiplicities for arrays.
+    pub witness_columns: usize,
+    /// The number of fixed columns, with multiplicities for arrays.
+    pub fixed_columns: usize,
+    /// The number of intermediate columns, with multiplicities for arrays.
+    pub intermediate_columns: usize,
+    /// The number of identities of each kind.
+    pub identities: BTreeMap<IdentityKind, usize>,
+    /// The maximum degree of all identities, with intermediate columns inlined.
+    pub max_degree: usize,
+    /// The number of identities of each degree, with intermediate columns inlined.
+    pub degree_histogram: BTreeMap<usize, usize>,
+    pub publics: usize,
+    /// The number of nodes in the expressions of all identities and intermediate columns.
+    pub expression_nodes: usize,
+}
+
+impl Display for AnalysisStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<24}{}", "witness columns", self.witness_columns)?;
+        writeln!(f, "{:<24}{}", "fixed columns", self.fixed_columns)?;
+        writeln!(
+            f,
+            "{:<24}{}",
+            "intermediate columns", self.intermediate_columns
+        )?;
+        for (kind, count) in &self.identities {
+            writeln!(f, "{:<24}{count}", format!("{kind} identities"))?;
+        }
+        writeln!(f, "{:<24}{}", "publics", self.publics)?;
+        writeln!(f, "{:<24}{}", "max degree", self.max_degree)?;
+        for (degree, count) in &self.degree_histogram {
+            writeln!(f, "{:<24}{count}", format!("  of degree {degree}"))?;
+        }
+        writeln!(f, "{:<24}{}", "expression nodes", self.expression_nodes)
+    }
+}
+
+impl<T: FieldElement> Analyzed<T> {
+    /// @returns summary statistics about the columns, identities and public declarations.
+    pub fn stats(&self) -> AnalysisStats {
+        let mut identities = BTreeMap::new();
+        let mut expression_nodes = 0;
+        for identity in &self.identities {
+            *identities.entry(identity.kind).or_default() += 1;
+            identity.pre_visit_expressions(&mut |_| expression_nodes += 1);
+        }
+        for (_, values) in self.intermediate_columns.values() {
+            values
+                .iter()
+                .for_each(|v| v.pre_visit_expressions(&mut |_| expression_nodes += 1));
+        }
+        let mut degree_histogram = BTreeMap::new();
+        for degree in self.identity_degrees() {
+            *degree_histogram.entry(degree).or_default() += 1;
+        }
+        AnalysisStats {
+            witness_columns: self.commitment_count(),
+            fixed_columns: self.constant_count(),
+            intermediate_columns: self.intermediate_count(),
+            identities,
+            max_degree: self.max_identity_degree(),
+            degree_histogram,
+            publics: self.public_declarations.len(),
+            expression_nodes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::analyzed::test_utils::*;
+    use crate::analyzed::{IdentityKind, PolynomialType};
+
+    use super::AnalysisStats;
+
+    #[test]
+    fn stats() {
+        let mut pil = new_pil(16);
+        add_column(&mut pil, "N.x", PolynomialType::Committed, None);
+        add_column(&mut pil, "N.y", PolynomialType::Committed, None);
+        add_column(&mut pil, "N.FIRST", PolynomialType::Constant, None);
+        let inter = col(&pil, "N.x") * col(&pil, "N.y");
+        add_intermediate(&mut pil, "N.inter", vec![inter]);
+        pil.add_public("N.out", "N.x", None, 15).unwrap();
+        let (x, first) = (col(&pil, "N.x"), col(&pil, "N.FIRST"));
+        add_identity(&mut pil, first.clone() * (next(x.clone()) - x.clone()));
+        let identity = col(&pil, "N.inter") - col(&pil, "N.y");
+        add_identity(&mut pil, identity);
+        add_lookup(&mut pil, None, vec![x], vec![first]);
+
+        let stats = pil.stats();
+        assert_eq!(
+            stats,
+            AnalysisStats {
+                witness_columns: 2,
+                fixed_columns: 1,
+                intermediate_columns: 1,
+                identities: [(IdentityKind::Polynomial, 2), (IdentityKind::Plookup, 1)]
+                    .into_iter()
+                    .collect(),
+                max_degree: 2,
+                degree_histogram: [(1, 1), (2, 2)].into_iter().collect(),
+                publics: 1,
+                expression_nodes: 13,
+            }
+        );
+        let expected = "witness columns         2
+fixed columns           1
+intermediate columns    1
+polynomial identities   2
+plookup identities      1
+publics                 1
+max degree              2
+  of degree 1           1
+  of degree 2           2
+expression nodes        13
+";
+        assert_eq!(stats.to_string(), expected);
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(
+            json,
+            r#"{"witness_columns":2,"fixed_columns":1,"intermediate_columns":1,"identities":{"Polynomial":2,"Plookup":1},"max_degree":2,"degree_histogram":{"1":1,"2":2},"publics":1,"expression_nodes":13}"#
+        );
+        assert_eq!(serde_json::from_str::<AnalysisStats>(&json).unwrap(), stats);
+    }
+}
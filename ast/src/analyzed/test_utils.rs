@@ -0,0 +1,190 @@
+//! Helpers to build analyzed files by hand in unit tests.
+
+use powdr_number::GoldilocksField;
+
+use crate::parsed::asm::AbsoluteSymbolPath;
+use crate::parsed::types::Type;
+use crate::parsed::{Radix, SelectedExpressions};
+use crate::SourceRef;
+
+use super::{
+    AlgebraicExpression, AlgebraicReference, Analyzed, Expression, FunctionValueDefinition,
+    Identity, IdentityKind, PolyID, PolynomialReference, PolynomialType, Reference,
+    StatementIdentifier, Symbol, SymbolKind, TypedExpression,
+};
+
+pub type Pil = Analyzed<GoldilocksField>;
+pub type Expr = AlgebraicExpression<GoldilocksField>;
+
+/// Returns an empty file of the given degree.
+pub fn new_pil(degree: u64) -> Pil {
+    Analyzed {
+        degree: Some(degree),
+        ..Default::default()
+    }
+}
+
+/// Appends the declaration of a witness or fixed column (without definition),
+/// or of an array of columns if `length` is given.
+pub fn add_column(pil: &mut Pil, name: &str, ptype: PolynomialType, length: Option<u64>) {
+    add_definition(pil, name, SymbolKind::Poly(ptype), length, None);
+}
+
+/// Appends a fixed column defined by `value`, which is printed as `col fixed name = value;`.
+pub fn add_fixed(pil: &mut Pil, name: &str, value: Expression) {
+    let value = FunctionValueDefinition::Expression(TypedExpression {
+        e: value,
+        type_scheme: None,
+    });
+    let kind = SymbolKind::Poly(PolynomialType::Constant);
+    add_definition(pil, name, kind, None, Some(value));
+}
+
+/// Appends `let name: ty = value;`.
+pub fn add_let(pil: &mut Pil, name: &str, ty: Type, value: Expression) {
+    let value = FunctionValueDefinition::Expression(TypedExpression {
+        e: value,
+        type_scheme: Some(ty.into()),
+    });
+    add_definition(pil, name, SymbolKind::Other(), None, Some(value));
+}
+
+/// Appends an intermediate column, or an array of them if there is more than one value.
+pub fn add_intermediate(pil: &mut Pil, name: &str, values: Vec<Expr>) {
+    let length = (values.len() > 1).then_some(values.len() as u64);
+    let kind = SymbolKind::Poly(PolynomialType::Intermediate);
+    let (path, symbol) = new_symbol(pil, name, kind, length);
+    pil.intermediate_columns.insert(path, (symbol, values));
+}
+
+fn add_definition(
+    pil: &mut Pil,
+    name: &str,
+    kind: SymbolKind,
+    length: Option<u64>,
+    value: Option<FunctionValueDefinition>,
+) {
+    let (path, symbol) = new_symbol(pil, name, kind, length);
+    pil.definitions.insert(path, (symbol, value));
+}
+
+/// Creates a symbol with the next free ID of its kind and appends it to the source order.
+fn new_symbol(
+    pil: &mut Pil,
+    name: &str,
+    kind: SymbolKind,
+    length: Option<u64>,
+) -> (AbsoluteSymbolPath, Symbol) {
+    let path = AbsoluteSymbolPath::from_dotted_string(name);
+    let id = pil
+        .definitions
+        .values()
+        .map(|(symbol, _)| symbol)
+        .chain(pil.intermediate_columns.values().map(|(symbol, _)| symbol))
+        .filter(|symbol| symbol.kind == kind)
+        .map(|symbol| symbol.id + symbol.length.unwrap_or(1))
+        .max()
+        .unwrap_or_default();
+    pil.source_order
+        .push(StatementIdentifier::Definition(path.clone()));
+    let symbol = Symbol {
+        id,
+        source: SourceRef::unknown(),
+        absolute_name: path.to_dotted_string(),
+        kind,
+        length,
+    };
+    (path, symbol)
+}
+
+/// Returns a reference to the column or array element with the given name, like `N.b[1]`.
+pub fn col(pil: &Pil, name: &str) -> Expr {
+    let (base, index) = match name.split_once('[') {
+        Some((base, index)) => (base, Some(index.trim_end_matches(']').parse().unwrap())),
+        None => (name, None),
+    };
+    let path = AbsoluteSymbolPath::from_dotted_string(base);
+    let symbol = pil
+        .definitions
+        .get(&path)
+        .map(|(symbol, _)| symbol)
+        .unwrap_or_else(|| &pil.intermediate_columns[&path].0);
+    let poly_id = PolyID::from(symbol);
+    AlgebraicExpression::Reference(AlgebraicReference {
+        name: symbol.array_element_name(index.unwrap_or_default()).into(),
+        poly_id: PolyID {
+            id: poly_id.id + index.unwrap_or_default(),
+            ..poly_id
+        },
+        next: false,
+    })
+}
+
+/// Turns a column reference into a reference to the next row.
+pub fn next(e: Expr) -> Expr {
+    let AlgebraicExpression::Reference(reference) = e else {
+        panic!("Expected a column reference.");
+    };
+    AlgebraicExpression::Reference(AlgebraicReference {
+        next: true,
+        ..reference
+    })
+}
+
+pub fn num(n: u64) -> Expr {
+    AlgebraicExpression::Number(n.into())
+}
+
+/// Returns a reference to a symbol inside a definition.
+pub fn reference(name: &str) -> Expression {
+    Expression::Reference(Reference::Poly(PolynomialReference {
+        name: name.to_string(),
+        poly_id: None,
+        generic_args: Some(vec![]),
+    }))
+}
+
+/// Returns a number literal inside a definition.
+pub fn number(n: u32) -> Expression {
+    Expression::Number(n.into(), None, Radix::Decimal)
+}
+
+/// Appends the polynomial identity `e = 0`, which is printed as `left = right;`
+/// if `e` is `left - right`.
+pub fn add_identity(pil: &mut Pil, e: Expr) {
+    let id = next_identity_id(pil, IdentityKind::Polynomial);
+    push_identity(
+        pil,
+        Identity::from_polynomial_identity(id, SourceRef::unknown(), e),
+    );
+}
+
+/// Appends the lookup `selector { left } in { right }`.
+pub fn add_lookup(pil: &mut Pil, selector: Option<Expr>, left: Vec<Expr>, right: Vec<Expr>) {
+    let identity = Identity {
+        id: next_identity_id(pil, IdentityKind::Plookup),
+        kind: IdentityKind::Plookup,
+        source: SourceRef::unknown(),
+        label: None,
+        left: SelectedExpressions {
+            selector,
+            expressions: left,
+        },
+        right: SelectedExpressions {
+            selector: None,
+            expressions: right,
+        },
+    };
+    push_identity(pil, identity);
+}
+
+fn next_identity_id(pil: &Pil, kind: IdentityKind) -> u64 {
+    pil.identities.iter().filter(|i| i.kind == kind).count() as u64
+}
+
+fn push_identity(pil: &mut Pil, identity: Identity<Expr>) {
+    pil.identities.push(identity);
+    pil.source_order
+        .push(StatementIdentifier::Identity(pil.identities.len() - 1));
+    pil.invalidate_identity_index();
+}
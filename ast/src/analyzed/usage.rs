@@ -0,0 +1,295 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Display, Write};
+use std::sync::OnceLock;
+
+use crate::parsed::visitor::ExpressionVisitable;
+
+use super::{AlgebraicExpression, Analyzed, Identity, IdentityKind, PolyID};
+use crate::parsed::asm::AbsoluteSymbolPath;
+
+/// The part of an identity a column is referenced in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IdentityPosition {
+    /// The expression of a polynomial identity.
+    Expression,
+    /// The selector on the left side of a lookup, permutation or connect identity.
+    LeftSelector,
+    /// The element of the tuple on the left side with the given index.
+    LeftTuple(usize),
+    /// The selector on the right side of a lookup, permutation or connect identity.
+    RightSelector,
+    /// The element of the tuple on the right side with the given index.
+    RightTuple(usize),
+}
+
+impl Display for IdentityPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentityPosition::Expression => write!(f, "expression"),
+            IdentityPosition::LeftSelector => write!(f, "left selector"),
+            IdentityPosition::LeftTuple(i) => write!(f, "left tuple element {i}"),
+            IdentityPosition::RightSelector => write!(f, "right selector"),
+            IdentityPosition::RightTuple(i) => write!(f, "right tuple element {i}"),
+        }
+    }
+}
+
+/// A use of a column in an identity, see `Analyzed::identities_referencing`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IdentityRef {
+    /// The index of the identity in `Analyzed::identities` (not its ID).
+    pub index: usize,
+    pub kind: IdentityKind,
+    pub position: IdentityPosition,
+    /// Whether the column is referenced on the next row, i.e. using `'`.
+    pub next: bool,
+}
+
+/// The uses of each column in the identities. It is built on first use and
+/// reset whenever the identities are changed through methods of `Analyzed`.
+/// Since it is derived from the identities, all indices are considered equal.
+#[derive(Debug, Default, Clone)]
+pub struct IdentityIndex(OnceLock<HashMap<PolyID, Vec<IdentityRef>>>);
+
+impl PartialEq for IdentityIndex {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl IdentityIndex {
+    fn get<T>(
+        &self,
+        identities: &[Identity<AlgebraicExpression<T>>],
+    ) -> &HashMap<PolyID, Vec<IdentityRef>> {
+        self.0.get_or_init(|| build_index(identities))
+    }
+
+    pub(super) fn invalidate(&mut self) {
+        self.0.take();
+    }
+}
+
+fn build_index<T>(
+    identities: &[Identity<AlgebraicExpression<T>>],
+) -> HashMap<PolyID, Vec<IdentityRef>> {
+    let mut uses: HashMap<PolyID, BTreeSet<IdentityRef>> = HashMap::new();
+    for (index, identity) in identities.iter().enumerate() {
+        let parts = if identity.kind == IdentityKind::Polynomial {
+            vec![(
+                IdentityPosition::Expression,
+                identity.left.selector.as_ref(),
+            )]
+        } else {
+            [
+                (
+                    IdentityPosition::LeftSelector,
+                    identity.left.selector.as_ref(),
+                ),
+                (
+                    IdentityPosition::RightSelector,
+                    identity.right.selector.as_ref(),
+                ),
+            ]
+            .into_iter()
+            .chain(
+                identity
+                    .left
+                    .expressions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| (IdentityPosition::LeftTuple(i), Some(e))),
+            )
+            .chain(
+                identity
+                    .right
+                    .expressions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| (IdentityPosition::RightTuple(i), Some(e))),
+            )
+            .collect()
+        };
+        for (position, e) in parts {
+            let Some(e) = e else { continue };
+            e.pre_visit_expressions(&mut |e| {
+                if let AlgebraicExpression::Reference(reference) = e {
+                    uses.entry(reference.poly_id)
+                        .or_default()
+                        .insert(IdentityRef {
+                            index,
+                            kind: identity.kind,
+                            position,
+                            next: reference.next,
+                        });
+                }
+            });
+        }
+    }
+    uses.into_iter()
+        .map(|(poly_id, refs)| (poly_id, refs.into_iter().collect()))
+        .collect()
+}
+
+impl<T> Analyzed<T> {
+    /// @returns all uses of the column with the given ID in identities, ordered by
+    /// the index of the identity. Columns only used through intermediate columns
+    /// are not considered to be referenced.
+    pub fn identities_referencing(&self, poly_id: PolyID) -> Vec<IdentityRef> {
+        self.identity_index
+            .get(&self.identities)
+            .get(&poly_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Resets the index behind `identities_referencing`. This is only needed after
+    /// modifying `identities` directly instead of through methods of `Analyzed`.
+    pub fn invalidate_identity_index(&mut self) {
+        self.identity_index.invalidate();
+    }
+}
+
+impl<T: Display> Analyzed<T> {
+    /// @returns a report of the identities referencing the column with the given name,
+    /// with one section per element for arrays of columns. The name can use `::` or `.`
+    /// as separators and can refer to a single array element as `name[index]`.
+    /// Returns `None` if there is no such column.
+    pub fn column_usage_report(&self, name: &str) -> Option<String> {
+        let (base, index) = name.split_at(name.find('[').unwrap_or(name.len()));
+        let name = format!(
+            "{}{index}",
+            AbsoluteSymbolPath::from_dotted_string(base).to_dotted_string()
+        );
+        let name = name.as_str();
+        let elements = self
+            .columns()
+            .into_iter()
+            .flat_map(|column| {
+                let is_column = column.name == name;
+                column
+                    .elements()
+                    .filter(move |(element, _)| is_column || *element == name)
+            })
+            .collect::<Vec<_>>();
+        if elements.is_empty() {
+            return None;
+        }
+        let mut report = String::new();
+        for (element, poly_id) in elements {
+            let uses = self.identities_referencing(poly_id);
+            if uses.is_empty() {
+                writeln!(report, "{element}: not referenced in any identity").unwrap();
+                continue;
+            }
+            writeln!(report, "{element}:").unwrap();
+            for IdentityRef {
+                index,
+                kind,
+                position,
+                next,
+            } in uses
+            {
+                let row = if next { "next row" } else { "current row" };
+                writeln!(
+                    report,
+                    "  {kind} identity {index}, {position}, {row}: {}",
+                    self.identities[index]
+                )
+                .unwrap();
+            }
+        }
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::analyzed::test_utils::*;
+    use crate::analyzed::{IdentityKind, PolyID, PolynomialType};
+
+    use super::{IdentityPosition, IdentityRef};
+
+    fn input() -> Pil {
+        let mut pil = new_pil(16);
+        for name in ["N.x", "N.y", "N.sel"] {
+            add_column(&mut pil, name, PolynomialType::Committed, None);
+        }
+        add_column(&mut pil, "N.BYTE", PolynomialType::Constant, None);
+        let identity = next(col(&pil, "N.x")) - col(&pil, "N.y");
+        add_identity(&mut pil, identity);
+        let (sel, y, byte) = (col(&pil, "N.sel"), col(&pil, "N.y"), col(&pil, "N.BYTE"));
+        add_lookup(&mut pil, Some(sel), vec![y], vec![byte]);
+        pil
+    }
+
+    fn witness(id: u64) -> PolyID {
+        PolyID {
+            id,
+            ptype: PolynomialType::Committed,
+        }
+    }
+
+    #[test]
+    fn only_next_row() {
+        let pil = input();
+        assert_eq!(
+            pil.identities_referencing(witness(0)),
+            [IdentityRef {
+                index: 0,
+                kind: IdentityKind::Polynomial,
+                position: IdentityPosition::Expression,
+                next: true,
+            }]
+        );
+        assert_eq!(
+            pil.column_usage_report("N::x").unwrap(),
+            "N.x:\n  polynomial identity 0, expression, next row: N.x' = N.y;\n"
+        );
+    }
+
+    #[test]
+    fn only_lookup_selector() {
+        let pil = input();
+        assert_eq!(
+            pil.identities_referencing(witness(2)),
+            [IdentityRef {
+                index: 1,
+                kind: IdentityKind::Plookup,
+                position: IdentityPosition::LeftSelector,
+                next: false,
+            }]
+        );
+        assert_eq!(
+            pil.identities_referencing(witness(1))
+                .iter()
+                .map(|r| (r.index, r.position))
+                .collect::<Vec<_>>(),
+            [
+                (0, IdentityPosition::Expression),
+                (1, IdentityPosition::LeftTuple(0))
+            ]
+        );
+        let report = pil.column_usage_report("N.sel").unwrap();
+        assert!(
+            report.contains("plookup identity 1, left selector, current row"),
+            "{report}"
+        );
+        assert!(pil.column_usage_report("N.z").is_none());
+    }
+
+    #[test]
+    fn index_is_updated() {
+        let mut pil = input();
+        assert_eq!(pil.identities_referencing(witness(0)).len(), 1);
+        pil.remove_identities(&[0].into());
+        assert!(pil.identities_referencing(witness(0)).is_empty());
+        assert_eq!(
+            pil.column_usage_report("N.x").unwrap(),
+            "N.x: not referenced in any identity\n"
+        );
+        assert_eq!(pil.identities_referencing(witness(2))[0].index, 0);
+    }
+}
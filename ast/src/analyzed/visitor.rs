@@ -93,6 +93,7 @@ impl ExpressionVisitable<Expression> for FunctionValueDefinition {
             FunctionValueDefinition::Array(array) => array
                 .iter_mut()
                 .try_for_each(move |item| item.visit_expressions_mut(f, o)),
+            FunctionValueDefinition::Sparse(sparse) => sparse.visit_expressions_mut(f, o),
         }
     }
 
@@ -108,6 +109,7 @@ impl ExpressionVisitable<Expression> for FunctionValueDefinition {
             FunctionValueDefinition::Array(array) => array
                 .iter()
                 .try_for_each(move |item| item.visit_expressions(f, o)),
+            FunctionValueDefinition::Sparse(sparse) => sparse.visit_expressions(f, o),
         }
     }
 }
@@ -131,3 +133,394 @@ impl ExpressionVisitable<Expression> for RepeatedArray {
             .try_for_each(move |item| item.visit_expressions(f, o))
     }
 }
+
+impl ExpressionVisitable<Expression> for SparseArray {
+    fn visit_expressions_mut<F, B>(&mut self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&mut Expression) -> ControlFlow<B>,
+    {
+        self.values_mut()
+            .try_for_each(move |item| item.visit_expressions_mut(f, o))
+    }
+
+    fn visit_expressions<F, B>(&self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&Expression) -> ControlFlow<B>,
+    {
+        self.values()
+            .try_for_each(move |item| item.visit_expressions(f, o))
+    }
+}
+
+/// A trait to be implemented by nodes of the analyzed AST that contain algebraic expressions.
+/// It calls a callback function on each (sub-)expression.
+///
+/// In contrast to `ExpressionVisitable`, the traversal uses an explicit stack instead of
+/// recursion, so that it also works for very deeply nested expressions.
+pub trait AlgebraicExpressionVisitable<T> {
+    /// Traverses the AST and calls `f` on each algebraic expression in pre-order,
+    /// potentially break early and return a value.
+    fn pre_visit_algebraic_expressions_return_mut<F, B>(&mut self, f: &mut F) -> ControlFlow<B>
+    where
+        F: FnMut(&mut AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.visit_algebraic_expressions_mut(f, VisitOrder::Pre)
+    }
+
+    /// Traverses the AST and calls `f` on each algebraic expression in pre-order.
+    fn pre_visit_algebraic_expressions_mut<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut AlgebraicExpression<T>),
+    {
+        self.pre_visit_algebraic_expressions_return_mut(&mut move |e| {
+            f(e);
+            ControlFlow::Continue::<()>(())
+        });
+    }
+
+    /// Traverses the AST and calls `f` on each algebraic expression in pre-order,
+    /// potentially break early and return a value.
+    fn pre_visit_algebraic_expressions_return<F, B>(&self, f: &mut F) -> ControlFlow<B>
+    where
+        F: FnMut(&AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.visit_algebraic_expressions(f, VisitOrder::Pre)
+    }
+
+    /// Traverses the AST and calls `f` on each algebraic expression in pre-order.
+    fn pre_visit_algebraic_expressions<F>(&self, f: &mut F)
+    where
+        F: FnMut(&AlgebraicExpression<T>),
+    {
+        self.pre_visit_algebraic_expressions_return(&mut move |e| {
+            f(e);
+            ControlFlow::Continue::<()>(())
+        });
+    }
+
+    /// Traverses the AST and calls `f` on each algebraic expression in post-order,
+    /// potentially break early and return a value.
+    fn post_visit_algebraic_expressions_return_mut<F, B>(&mut self, f: &mut F) -> ControlFlow<B>
+    where
+        F: FnMut(&mut AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.visit_algebraic_expressions_mut(f, VisitOrder::Post)
+    }
+
+    /// Traverses the AST and calls `f` on each algebraic expression in post-order.
+    fn post_visit_algebraic_expressions_mut<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut AlgebraicExpression<T>),
+    {
+        self.post_visit_algebraic_expressions_return_mut(&mut move |e| {
+            f(e);
+            ControlFlow::Continue::<()>(())
+        });
+    }
+
+    /// Traverses the AST and calls `f` on each algebraic expression in post-order,
+    /// potentially break early and return a value.
+    fn post_visit_algebraic_expressions_return<F, B>(&self, f: &mut F) -> ControlFlow<B>
+    where
+        F: FnMut(&AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.visit_algebraic_expressions(f, VisitOrder::Post)
+    }
+
+    /// Traverses the AST and calls `f` on each algebraic expression in post-order.
+    fn post_visit_algebraic_expressions<F>(&self, f: &mut F)
+    where
+        F: FnMut(&AlgebraicExpression<T>),
+    {
+        self.post_visit_algebraic_expressions_return(&mut move |e| {
+            f(e);
+            ControlFlow::Continue::<()>(())
+        });
+    }
+
+    fn visit_algebraic_expressions<F, B>(&self, f: &mut F, order: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&AlgebraicExpression<T>) -> ControlFlow<B>;
+
+    fn visit_algebraic_expressions_mut<F, B>(
+        &mut self,
+        f: &mut F,
+        order: VisitOrder,
+    ) -> ControlFlow<B>
+    where
+        F: FnMut(&mut AlgebraicExpression<T>) -> ControlFlow<B>;
+}
+
+impl<T> AlgebraicExpressionVisitable<T> for AlgebraicExpression<T> {
+    fn visit_algebraic_expressions<F, B>(&self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        match o {
+            VisitOrder::Pre => {
+                let mut stack = vec![self];
+                while let Some(e) = stack.pop() {
+                    f(e)?;
+                    match e {
+                        AlgebraicExpression::BinaryOperation(left, _, right) => {
+                            stack.extend([right.as_ref(), left.as_ref()]);
+                        }
+                        AlgebraicExpression::UnaryOperation(_, inner) => stack.push(inner),
+                        _ => {}
+                    }
+                }
+            }
+            VisitOrder::Post => {
+                let mut stack = vec![(self, false)];
+                while let Some((e, children_done)) = stack.pop() {
+                    match e {
+                        AlgebraicExpression::BinaryOperation(left, _, right) if !children_done => {
+                            stack.extend([
+                                (e, true),
+                                (right.as_ref(), false),
+                                (left.as_ref(), false),
+                            ]);
+                        }
+                        AlgebraicExpression::UnaryOperation(_, inner) if !children_done => {
+                            stack.extend([(e, true), (inner.as_ref(), false)]);
+                        }
+                        _ => f(e)?,
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn visit_algebraic_expressions_mut<F, B>(&mut self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&mut AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        match o {
+            VisitOrder::Pre => {
+                let mut stack = vec![self];
+                while let Some(e) = stack.pop() {
+                    f(e)?;
+                    match e {
+                        AlgebraicExpression::BinaryOperation(left, _, right) => {
+                            stack.extend([right.as_mut(), left.as_mut()]);
+                        }
+                        AlgebraicExpression::UnaryOperation(_, inner) => stack.push(inner),
+                        _ => {}
+                    }
+                }
+                ControlFlow::Continue(())
+            }
+            VisitOrder::Post => post_visit_mut(self, f),
+        }
+    }
+}
+
+/// Traverses `root` in post-order with an explicit stack. In order to avoid holding
+/// mutable references to a node and its children at the same time, the children
+/// of a node are detached while they are visited and re-attached before `f` is
+/// called on the node. After an early exit, the remaining nodes are only re-attached.
+fn post_visit_mut<T, F, B>(root: &mut AlgebraicExpression<T>, f: &mut F) -> ControlFlow<B>
+where
+    F: FnMut(&mut AlgebraicExpression<T>) -> ControlFlow<B>,
+{
+    let mut result = ControlFlow::Continue(());
+    let mut stack = vec![(detach(root), false)];
+    let mut visited = vec![];
+    while let Some((mut e, children_done)) = stack.pop() {
+        match (&mut e, children_done) {
+            (AlgebraicExpression::BinaryOperation(left, _, right), false) => {
+                let (left, right) = (detach(left), detach(right));
+                stack.extend([(e, true), (right, false), (left, false)]);
+                continue;
+            }
+            (AlgebraicExpression::UnaryOperation(_, inner), false) => {
+                let inner = detach(inner);
+                stack.extend([(e, true), (inner, false)]);
+                continue;
+            }
+            (AlgebraicExpression::BinaryOperation(left, _, right), true) => {
+                **right = visited.pop().unwrap();
+                **left = visited.pop().unwrap();
+            }
+            (AlgebraicExpression::UnaryOperation(_, inner), true) => {
+                **inner = visited.pop().unwrap();
+            }
+            _ => {}
+        }
+        if result.is_continue() {
+            result = f(&mut e);
+        }
+        visited.push(e);
+    }
+    *root = visited.pop().unwrap();
+    result
+}
+
+/// Replaces the expression by a placeholder and returns it.
+fn detach<T>(e: &mut AlgebraicExpression<T>) -> AlgebraicExpression<T> {
+    std::mem::replace(e, AlgebraicExpression::PublicReference(String::new()))
+}
+
+impl<T> AlgebraicExpressionVisitable<T> for SelectedExpressions<AlgebraicExpression<T>> {
+    fn visit_algebraic_expressions<F, B>(&self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.selector
+            .iter()
+            .chain(self.expressions.iter())
+            .try_for_each(move |item| item.visit_algebraic_expressions(f, o))
+    }
+
+    fn visit_algebraic_expressions_mut<F, B>(&mut self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&mut AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.selector
+            .iter_mut()
+            .chain(self.expressions.iter_mut())
+            .try_for_each(move |item| item.visit_algebraic_expressions_mut(f, o))
+    }
+}
+
+impl<T> AlgebraicExpressionVisitable<T> for Identity<AlgebraicExpression<T>> {
+    fn visit_algebraic_expressions<F, B>(&self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.left.visit_algebraic_expressions(f, o)?;
+        self.right.visit_algebraic_expressions(f, o)
+    }
+
+    fn visit_algebraic_expressions_mut<F, B>(&mut self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&mut AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.left.visit_algebraic_expressions_mut(f, o)?;
+        self.right.visit_algebraic_expressions_mut(f, o)
+    }
+}
+
+/// Visits the identities and then the definitions of intermediate columns.
+impl<T> AlgebraicExpressionVisitable<T> for Analyzed<T> {
+    fn visit_algebraic_expressions<F, B>(&self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.identities
+            .iter()
+            .try_for_each(|identity| identity.visit_algebraic_expressions(f, o))?;
+        self.intermediate_columns
+            .values()
+            .flat_map(|(_, values)| values)
+            .try_for_each(|e| e.visit_algebraic_expressions(f, o))
+    }
+
+    fn visit_algebraic_expressions_mut<F, B>(&mut self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&mut AlgebraicExpression<T>) -> ControlFlow<B>,
+    {
+        self.identity_index.invalidate();
+        self.identities
+            .iter_mut()
+            .try_for_each(|identity| identity.visit_algebraic_expressions_mut(f, o))?;
+        self.intermediate_columns
+            .values_mut()
+            .flat_map(|(_, values)| values)
+            .try_for_each(|e| e.visit_algebraic_expressions_mut(f, o))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::ControlFlow;
+
+    use powdr_number::GoldilocksField;
+
+    use crate::analyzed::{
+        AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator, PolyID, PolynomialType,
+    };
+
+    use super::AlgebraicExpressionVisitable;
+
+    type Expr = AlgebraicExpression<GoldilocksField>;
+
+    fn col(name: &str, id: u64) -> Expr {
+        AlgebraicExpression::Reference(AlgebraicReference {
+            name: name.into(),
+            poly_id: PolyID {
+                id,
+                ptype: PolynomialType::Committed,
+            },
+            next: false,
+        })
+    }
+
+    fn sample() -> Expr {
+        (col("x", 0) + AlgebraicExpression::Number(2.into()))
+            * AlgebraicExpression::UnaryOperation(
+                AlgebraicUnaryOperator::Minus,
+                Box::new(col("y", 1)),
+            )
+    }
+
+    #[test]
+    fn order() {
+        let mut pre = vec![];
+        sample().pre_visit_algebraic_expressions(&mut |e| pre.push(e.to_string()));
+        assert_eq!(pre, ["((x + 2) * -y)", "(x + 2)", "x", "2", "-y", "y"]);
+        let mut post = vec![];
+        sample().post_visit_algebraic_expressions(&mut |e| post.push(e.to_string()));
+        assert_eq!(post, ["x", "2", "(x + 2)", "y", "-y", "((x + 2) * -y)"]);
+
+        let mut pre_mut = vec![];
+        sample().pre_visit_algebraic_expressions_mut(&mut |e| pre_mut.push(e.to_string()));
+        assert_eq!(pre_mut, pre);
+        let mut post_mut = vec![];
+        sample().post_visit_algebraic_expressions_mut(&mut |e| post_mut.push(e.to_string()));
+        assert_eq!(post_mut, post);
+    }
+
+    #[test]
+    fn post_order_sees_modified_children() {
+        let mut e = sample();
+        let mut visited = vec![];
+        e.post_visit_algebraic_expressions_mut(&mut |e| {
+            if let AlgebraicExpression::Reference(r) = e {
+                if r.name == "x" {
+                    *e = col("z", 2);
+                }
+            }
+            visited.push(e.to_string());
+        });
+        assert_eq!(e.to_string(), "((z + 2) * -y)");
+        assert_eq!(visited, ["z", "2", "(z + 2)", "y", "-y", "((z + 2) * -y)"]);
+    }
+
+    #[test]
+    fn early_exit() {
+        let mut e = sample();
+        let mut visited = 0;
+        let result = e.post_visit_algebraic_expressions_return_mut(&mut |e| {
+            visited += 1;
+            match e {
+                AlgebraicExpression::Number(n) => ControlFlow::Break(*n),
+                _ => {
+                    *e = col("w", 3);
+                    ControlFlow::Continue(())
+                }
+            }
+        });
+        assert_eq!(result, ControlFlow::Break(2.into()));
+        assert_eq!(visited, 2);
+        // Only the nodes visited before the break are modified.
+        assert_eq!(e.to_string(), "((w + 2) * -y)");
+
+        let result = sample().pre_visit_algebraic_expressions_return(&mut |e| match e {
+            AlgebraicExpression::Reference(r) => ControlFlow::Break(r.name.to_string()),
+            _ => ControlFlow::Continue(()),
+        });
+        assert_eq!(result, ControlFlow::Break("x".to_string()));
+    }
+}
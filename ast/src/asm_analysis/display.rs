@@ -91,7 +91,13 @@ impl Display for Machine {
 
 impl Display for LinkDefinitionStatement {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "link {} => {};", self.flag, self.to)
+        write!(
+            f,
+            "link {} {} {};",
+            self.flag,
+            if self.is_permutation { "~>" } else { "=>" },
+            self.to
+        )
     }
 }
 
@@ -182,7 +188,16 @@ impl Display for LabelStatement {
 
 impl Display for RegisterDeclarationStatement {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "reg {}{};", self.name, self.ty,)
+        write!(
+            f,
+            "reg {}{}{};",
+            self.name,
+            self.ty,
+            self.range
+                .as_ref()
+                .map(|(min, max)| format!(": range({min}, {max})"))
+                .unwrap_or_default()
+        )
     }
 }
 
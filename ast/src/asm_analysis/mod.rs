@@ -28,6 +28,8 @@ pub struct RegisterDeclarationStatement {
     pub source: SourceRef,
     pub name: String,
     pub ty: RegisterTy,
+    /// The inclusive bounds of the values the register can hold, if constrained.
+    pub range: Option<(BigUint, BigUint)>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -76,6 +78,8 @@ pub struct LinkDefinitionStatement {
     pub flag: Expression,
     /// the callable to invoke when the flag is on. TODO: check this during type checking
     pub to: CallableRef,
+    /// whether the link is a permutation instead of a lookup
+    pub is_permutation: bool,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -683,6 +687,8 @@ impl Item {
 
 #[derive(Clone, Default, Debug)]
 pub struct Machine {
+    /// The location of the machine definition in the source
+    pub source: SourceRef,
     /// The degree if any, i.e. the number of rows in instances of this machine type
     pub degree: Option<DegreeStatement>,
     /// The latch, i.e. the boolean column whose values must be 1 in order for this machine to be accessed. Must be defined in one of the constraint blocks of this machine.
@@ -3,7 +3,8 @@
 use itertools::Itertools;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::fmt::{Display, Result, Write};
+use std::fmt::{self, Display, Result, Write};
+use std::io;
 use std::sync::Arc;
 
 /// Analyzed PIL
@@ -15,7 +16,14 @@ pub mod object;
 /// A parsed ASM + PIL AST
 pub mod parsed;
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+/// A location in a source file.
+///
+/// It is serialized as `{"file": <name or null>, "line": <line>, "col": <column>}`.
+/// This is part of the serialized form of both the parsed and the analyzed AST,
+/// so it should not be changed.
+#[derive(
+    Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct SourceRef {
     pub file: Option<Arc<str>>,
     pub line: usize,
@@ -32,6 +40,18 @@ impl SourceRef {
     }
 }
 
+impl Display for SourceRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.file.as_deref().unwrap_or("input"),
+            self.line,
+            self.col
+        )
+    }
+}
+
 /// quick and dirty String to String indentation
 pub fn indent<S: ToString>(s: S, indentation: usize) -> String {
     s.to_string()
@@ -48,7 +68,44 @@ where
     S: Display,
     W: Write,
 {
-    write!(f, "{}", indent(s, indentation))
+    if indentation == 0 {
+        write!(f, "{s}")
+    } else {
+        write!(f, "{}", indent(s, indentation))
+    }
+}
+
+/// Forwards formatted output to an [`io::Write`], remembering the first I/O error.
+pub(crate) struct IoWriter<'a, W: ?Sized> {
+    inner: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<'a, W: io::Write + ?Sized> Write for IoWriter<'a, W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/// Streams the output of `write` into `w` without collecting it in a string.
+pub(crate) fn write_io<W, F>(w: &mut W, write: F) -> io::Result<()>
+where
+    W: io::Write + ?Sized,
+    F: FnOnce(&mut IoWriter<'_, W>) -> Result,
+{
+    let mut writer = IoWriter {
+        inner: w,
+        error: None,
+    };
+    write(&mut writer).map_err(|_| {
+        writer
+            .error
+            .take()
+            .unwrap_or_else(|| io::Error::other("formatting error"))
+    })
 }
 
 fn write_items<S, I, W>(f: &mut W, items: I) -> Result
@@ -1,4 +1,7 @@
-use std::fmt::{Display, Formatter, Result};
+use std::{
+    fmt::{self, Display, Formatter, Result},
+    io,
+};
 
 use crate::parsed::{display::format_type_scheme_around_name, TypedExpression};
 
@@ -12,12 +15,35 @@ impl Display for Location {
 
 impl Display for PILGraph {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        writeln!(f, "// Utilities")?;
-        for (name, TypedExpression { e, type_scheme }) in &self.definitions {
+        self.write(f)
+    }
+}
+
+impl PILGraph {
+    /// Writes the graph to `w`, producing the same output as `to_string()`
+    /// without building it in memory first.
+    pub fn write_pil<W: io::Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        crate::write_io(w, |f| self.write(f))
+    }
+
+    fn write<W: fmt::Write>(&self, f: &mut W) -> Result {
+        writeln!(f, "// Main {}", self.main)?;
+        for operation in &self.entry_points {
+            writeln!(f, "// Entry point {operation}")?;
+        }
+        // The definitions are sorted by path, so the ones of the same namespace are adjacent.
+        let mut current_namespace = None;
+        for (path, TypedExpression { e, type_scheme }) in &self.definitions {
+            let mut namespace = path.clone();
+            let name = namespace.pop().unwrap();
+            if current_namespace.as_ref() != Some(&namespace) {
+                writeln!(f, "// Utilities in {namespace}")?;
+                current_namespace = Some(namespace);
+            }
             writeln!(
                 f,
                 "let{} = {e};",
-                format_type_scheme_around_name(&name.to_string(), type_scheme)
+                format_type_scheme_around_name(&name, type_scheme)
             )?;
         }
         for (location, object) in &self.objects {
@@ -31,6 +57,7 @@ impl Display for PILGraph {
 
 impl Display for Object {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "// Defined at {}", self.source)?;
         if let Some(degree) = self.degree {
             writeln!(f, "// Degree {}", degree)?;
         }
@@ -40,7 +67,7 @@ impl Display for Object {
         if !self.links.is_empty() {
             writeln!(f, "// Links:")?;
             for link in &self.links {
-                writeln!(f, "// {link}")?;
+                writeln!(f, "// {link} (declared at {})", link.source)?;
             }
         }
         Ok(())
@@ -49,7 +76,11 @@ impl Display for Object {
 
 impl Display for Link {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "{} links to {}", self.from, self.to)
+        write!(f, "{} links to {}", self.from, self.to)?;
+        if self.is_permutation {
+            write!(f, " as a permutation")?;
+        }
+        Ok(())
     }
 }
 
@@ -61,28 +92,41 @@ impl Display for LinkFrom {
 
 impl Display for LinkTo {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "{} in {}", self.operation, self.machine)
+        write!(f, "{} in {}", self.operation, self.machine)?;
+        if let Some(index) = self.selector_index {
+            write!(f, " using call selector {index}")?;
+        }
+        Ok(())
     }
 }
 
 impl Display for Machine {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(
-            f,
-            "object at location \"{}\" with latch \"{:?}\" and operation_id \"{:?}\"",
-            self.location, self.latch, self.operation_id
-        )
+        write!(f, "object {}", self.location)?;
+        let properties = [
+            ("latch", &self.latch),
+            ("operation_id", &self.operation_id),
+            ("call_selectors", &self.call_selectors),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_ref().map(|value| format!("{name}: {value}")))
+        .collect::<Vec<_>>();
+        if !properties.is_empty() {
+            write!(f, " with {}", properties.join(", "))?;
+        }
+        Ok(())
     }
 }
 
 impl Display for Operation {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(
-            f,
-            "operation \"{}\" with id {:?} with params {}",
-            self.name,
-            self.id.as_ref().map(|id| id.to_string()),
-            self.params,
-        )
+        write!(f, "operation {}", self.name)?;
+        if let Some(id) = &self.id {
+            write!(f, "<{id}>")?;
+        }
+        if !self.params.is_empty() {
+            write!(f, " {}", self.params)?;
+        }
+        Ok(())
     }
 }
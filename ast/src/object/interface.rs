@@ -0,0 +1,88 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::parsed::asm::Param;
+
+use super::{Operation, PILGraph};
+
+/// How the machines of a linked program can be called, e.g. by external witness generators.
+/// The machines are ordered by location and their operations by name.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct InterfaceDescription {
+    pub machines: Vec<MachineInterface>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct MachineInterface {
+    /// the location of the machine instance, in the "main::a::b" notation
+    pub location: String,
+    /// the degree of the machine, if it is known
+    pub degree: Option<u64>,
+    pub latch: Option<String>,
+    pub operation_id: Option<String>,
+    pub operations: Vec<OperationInterface>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct OperationInterface {
+    pub name: String,
+    /// the value of the operation id activating this operation, in decimal
+    pub id: Option<String>,
+    pub inputs: Vec<ParamInterface>,
+    pub outputs: Vec<ParamInterface>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ParamInterface {
+    pub name: String,
+    /// the declared type of the parameter, if any
+    pub ty: Option<String>,
+}
+
+impl PILGraph {
+    /// Returns the interfaces of all machines of the graph.
+    pub fn interface_description(&self) -> InterfaceDescription {
+        InterfaceDescription {
+            machines: self
+                .objects
+                .iter()
+                .map(|(location, object)| MachineInterface {
+                    location: location.limbs().join("::"),
+                    degree: object.degree,
+                    latch: object.latch.clone(),
+                    operation_id: object.operation_id.clone(),
+                    operations: object.operations.iter().map(Into::into).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl InterfaceDescription {
+    /// Writes the description as pretty-printed JSON.
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), String> {
+        serde_json::to_writer_pretty(writer, self)
+            .map_err(|e| format!("Failed to write interface description: {e}"))
+    }
+}
+
+impl From<&Operation> for OperationInterface {
+    fn from(operation: &Operation) -> Self {
+        let params = |params: &[Param]| {
+            params
+                .iter()
+                .map(|p| ParamInterface {
+                    name: p.name.clone(),
+                    ty: p.ty.clone(),
+                })
+                .collect()
+        };
+        OperationInterface {
+            name: operation.name.clone(),
+            id: operation.id.as_ref().map(|id| id.to_string()),
+            inputs: params(&operation.params.inputs),
+            outputs: params(&operation.params.outputs),
+        }
+    }
+}
@@ -1,23 +1,40 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, str::FromStr};
 
 use powdr_number::BigUint;
+use serde::{Deserialize, Serialize};
 
-use crate::parsed::{
-    asm::{AbsoluteSymbolPath, Params},
-    Expression, PilStatement, TypedExpression,
+use crate::{
+    parsed::{
+        asm::{AbsoluteSymbolPath, Params},
+        Expression, PilStatement, TypedExpression,
+    },
+    SourceRef,
 };
 
 mod display;
+mod interface;
+mod order;
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub use interface::{InterfaceDescription, MachineInterface, OperationInterface, ParamInterface};
+pub use order::CycleError;
+
+/// The location of a machine instance in the tree of instances.
+/// Locations are ordered by their instance names from the root, so a
+/// machine comes before its submachines.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub struct Location {
     limbs: Vec<String>,
 }
 
 impl Location {
     pub fn main() -> Self {
+        Self::root("main")
+    }
+
+    /// The location of the root machine of a graph, named `name`.
+    pub fn root<S: Into<String>>(name: S) -> Self {
         Self {
-            limbs: vec!["main".into()],
+            limbs: vec![name.into()],
         }
     }
 
@@ -25,22 +42,79 @@ impl Location {
         self.limbs.push(limb.into());
         self
     }
+
+    /// The instance names from the main machine down to this location.
+    pub fn limbs(&self) -> &[String] {
+        &self.limbs
+    }
+
+    /// Returns the location of the machine this instance is declared in,
+    /// or `None` for the main machine.
+    pub fn parent(&self) -> Option<Self> {
+        (self.limbs.len() > 1).then(|| Self {
+            limbs: self.limbs[..self.limbs.len() - 1].to_vec(),
+        })
+    }
+
+    /// Returns true if `other` is a (direct or indirect) submachine of this location.
+    pub fn is_ancestor_of(&self, other: &Location) -> bool {
+        other.limbs.len() > self.limbs.len() && other.limbs.starts_with(&self.limbs)
+    }
 }
 
-#[derive(Clone)]
+impl FromStr for Location {
+    type Err = String;
+
+    /// Parses a location in the "main::a::b" notation, starting at the root machine.
+    /// The `_`-separated notation used by `Display` cannot be parsed, since
+    /// instance names can contain underscores. Instance names are identifiers,
+    /// so no escaping is needed for the `::` separator.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let limbs = s.split("::").map(|l| l.to_string()).collect::<Vec<_>>();
+        if let Some(limb) = limbs
+            .iter()
+            .find(|l| l.is_empty() || !l.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        {
+            return Err(format!("Invalid instance name \"{limb}\" in location {s}"));
+        }
+        Ok(Self { limbs })
+    }
+}
+
+/// The machine objects and their links, as produced by airgen.
+/// The maps are serialized as lists of key-value pairs, since their
+/// keys are not strings.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct PILGraph {
     pub main: Machine,
     pub entry_points: Vec<Operation>,
+    /// the objects ordered by location, i.e. each machine before its submachines
+    #[serde(with = "map_as_pairs")]
     pub objects: BTreeMap<Location, Object>,
+    /// the utility definitions ordered by path, including the utility functions
+    /// defined inside machines, which are at the path of their machine type
+    #[serde(with = "map_as_pairs")]
     pub definitions: BTreeMap<AbsoluteSymbolPath, TypedExpression>,
 }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Object {
+    /// the machine type this object is an instance of
+    pub ty: AbsoluteSymbolPath,
+    /// the location of the machine definition in the source
+    pub source: SourceRef,
+    /// the degree of this machine, if it is explicitly set
+    /// otherwise, the linker uses the degree of the main machine
     pub degree: Option<u64>,
+    /// the latch of this machine, if it has one
+    pub latch: Option<String>,
+    /// the operation id column of this machine, if it has one
+    pub operation_id: Option<String>,
     /// the pil identities for this machine
     pub pil: Vec<PilStatement>,
-    /// the links from this machine to its children
+    /// the operations of this machine, ordered by name
+    pub operations: Vec<Operation>,
+    /// the links from this machine to its children, in declaration order
     pub links: Vec<Link>,
 }
 
@@ -51,30 +125,36 @@ impl Object {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 /// A link between two machines
 pub struct Link {
     /// the link source, i.e. a flag and some arguments
     pub from: LinkFrom,
     /// the link target, i.e. a callable in some machine
     pub to: LinkTo,
+    /// whether the link is a permutation instead of a lookup
+    pub is_permutation: bool,
+    /// the location of the link declaration in the source
+    pub source: SourceRef,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct LinkFrom {
     pub flag: Expression,
     pub params: Params,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct LinkTo {
     /// the machine we link to
     pub machine: Machine,
     /// the operation we link to
     pub operation: Operation,
+    /// the index of the call selector of the calling machine, if the machine we link to uses call selectors
+    pub selector_index: Option<usize>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Machine {
     /// the location of this instance
     pub location: Location,
@@ -82,9 +162,11 @@ pub struct Machine {
     pub latch: Option<String>,
     /// its operation id
     pub operation_id: Option<String>,
+    /// the name of its call selector array, with one selector per calling machine
+    pub call_selectors: Option<String>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Operation {
     /// the name of the operation
     pub name: String,
@@ -93,3 +175,239 @@ pub struct Operation {
     /// the parameters
     pub params: Params,
 }
+
+/// (De)serializes a map as a list of key-value pairs.
+mod map_as_pairs {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K: Serialize, V: Serialize, S: Serializer>(
+        map: &BTreeMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parsed::{
+        asm::{parse_absolute_path, Param},
+        build::direct_reference,
+        types::{Type, TypeBounds, TypeScheme},
+        PolynomialName,
+    };
+
+    use super::*;
+
+    fn location(s: &str) -> Location {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(location("main"), Location::main());
+        assert_eq!(
+            location("main::arith::byte_binary"),
+            Location::main().join("arith").join("byte_binary")
+        );
+        assert_eq!(
+            location("main::arith").limbs(),
+            ["main".to_string(), "arith".to_string()]
+        );
+        assert_eq!(
+            "main::".parse::<Location>(),
+            Err("Invalid instance name \"\" in location main::".to_string())
+        );
+        assert_eq!(
+            "main::a.b".parse::<Location>(),
+            Err("Invalid instance name \"a.b\" in location main::a.b".to_string())
+        );
+        assert_eq!(
+            location("Arith::byte"),
+            Location::root("Arith").join("byte")
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            location("main::arith::byte_binary").to_string(),
+            "main_arith_byte_binary"
+        );
+    }
+
+    #[test]
+    fn parent() {
+        assert_eq!(location("main::a::b").parent(), Some(location("main::a")));
+        assert_eq!(location("main::a").parent(), Some(Location::main()));
+        assert_eq!(Location::main().parent(), None);
+    }
+
+    #[test]
+    fn is_ancestor_of() {
+        assert!(Location::main().is_ancestor_of(&location("main::a::b")));
+        assert!(location("main::a").is_ancestor_of(&location("main::a::b")));
+        assert!(!location("main::a").is_ancestor_of(&location("main::a")));
+        assert!(!location("main::a::b").is_ancestor_of(&location("main::a")));
+        assert!(!location("main::a").is_ancestor_of(&location("main::ab::c")));
+        assert!(!location("main::a").is_ancestor_of(&location("main::b::a")));
+    }
+
+    fn params(inputs: &[&str], outputs: &[&str]) -> Params {
+        let param = |name: &&str| Param {
+            name: name.to_string(),
+            index: None,
+            ty: None,
+        };
+        Params::new(
+            inputs.iter().map(param).collect(),
+            outputs.iter().map(param).collect(),
+        )
+    }
+
+    fn witness(name: &str) -> PilStatement {
+        PilStatement::PolynomialCommitDeclaration(
+            SourceRef::unknown(),
+            vec![PolynomialName {
+                name: name.to_string(),
+                array_size: None,
+            }],
+            None,
+        )
+    }
+
+    #[test]
+    fn display_graph() {
+        let arith = Machine {
+            location: location("main::arith"),
+            latch: Some("latch".into()),
+            operation_id: Some("operation_id".into()),
+            call_selectors: None,
+        };
+        let add = Operation {
+            name: "add".into(),
+            id: Some(7u32.into()),
+            params: params(&["x", "y"], &["z"]),
+        };
+        let graph = PILGraph {
+            main: Machine {
+                location: Location::main(),
+                latch: None,
+                operation_id: Some("_operation_id".into()),
+                call_selectors: None,
+            },
+            entry_points: vec![Operation {
+                name: "main".into(),
+                id: Some(2u32.into()),
+                params: Params::default(),
+            }],
+            objects: [
+                (
+                    Location::main(),
+                    Object {
+                        ty: parse_absolute_path("::Main"),
+                        source: SourceRef {
+                            file: Some("main.asm".into()),
+                            line: 3,
+                            col: 0,
+                        },
+                        degree: Some(8),
+                        latch: None,
+                        operation_id: None,
+                        pil: vec![witness("A")],
+                        operations: vec![],
+                        links: vec![
+                            Link {
+                                from: LinkFrom {
+                                    flag: direct_reference("instr_add"),
+                                    params: params(&["A", "B"], &["C"]),
+                                },
+                                to: LinkTo {
+                                    machine: arith.clone(),
+                                    operation: add.clone(),
+                                    selector_index: None,
+                                },
+                                is_permutation: false,
+                                source: SourceRef {
+                                    file: Some("main.asm".into()),
+                                    line: 9,
+                                    col: 4,
+                                },
+                            },
+                            Link {
+                                from: LinkFrom {
+                                    flag: direct_reference("instr_add_perm"),
+                                    params: params(&["A", "B"], &["C"]),
+                                },
+                                to: LinkTo {
+                                    machine: Machine {
+                                        call_selectors: Some("sel".into()),
+                                        ..arith.clone()
+                                    },
+                                    operation: add,
+                                    selector_index: Some(0),
+                                },
+                                is_permutation: true,
+                                source: SourceRef::unknown(),
+                            },
+                        ],
+                    },
+                ),
+                (
+                    location("main::arith"),
+                    Object {
+                        pil: vec![witness("x")],
+                        ..Default::default()
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            definitions: [(
+                parse_absolute_path("::std::utils::id"),
+                TypedExpression {
+                    e: direct_reference("x"),
+                    type_scheme: Some(TypeScheme {
+                        vars: TypeBounds::new([("T".to_string(), ["Add".to_string()])].into_iter()),
+                        ty: Type::TypeVar("T".into()),
+                    }),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        let expected = r#"// Main object main with operation_id: _operation_id
+// Entry point operation main<2>
+// Utilities in ::std::utils
+let<T: Add> id: T = x;
+// Object main
+// Defined at main.asm:3:0
+// Degree 8
+    pol commit A;
+// Links:
+// instr_add A, B -> C links to operation add<7> x, y -> z in object main_arith with latch: latch, operation_id: operation_id (declared at main.asm:9:4)
+// instr_add_perm A, B -> C links to operation add<7> x, y -> z in object main_arith with latch: latch, operation_id: operation_id, call_selectors: sel using call selector 0 as a permutation (declared at input:0:0)
+
+
+// Object main_arith
+// Defined at input:0:0
+    pol commit x;
+
+
+"#;
+        assert_eq!(graph.to_string(), expected);
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+
+use super::{Location, PILGraph};
+
+/// The machines of a graph call each other in a cycle, so they cannot be ordered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// The machines on the cycle, each one calling (or containing) the next one
+    /// and the last one calling the first one.
+    pub cycle: Vec<Location>,
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Machines call each other in a cycle: {} -> {}",
+            self.cycle.iter().format(" -> "),
+            self.cycle[0]
+        )
+    }
+}
+
+impl PILGraph {
+    /// Returns the locations of the machines that have links into the machine at `location`,
+    /// ordered by location.
+    pub fn callers_of(&self, location: &Location) -> Vec<Location> {
+        self.link_edges()
+            .into_iter()
+            .filter(|(_, callees)| callees.contains(location))
+            .map(|(caller, _)| caller.clone())
+            .collect()
+    }
+
+    /// Returns the locations of the machines the machine at `location` has links into,
+    /// ordered by location.
+    pub fn callees_of(&self, location: &Location) -> Vec<Location> {
+        self.link_edges()
+            .remove(location)
+            .map(|callees| callees.into_iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the locations of all machines such that each machine comes before the
+    /// machines it calls and before its submachines. Reverse the result to process callees
+    /// before their callers. Machines that do not depend on each other are ordered by location.
+    /// Fails with one of the cycles if machines call each other.
+    pub fn topological_order(&self) -> Result<Vec<Location>, CycleError> {
+        let mut successors = self.link_edges();
+        for location in self.objects.keys() {
+            if let Some(parent) = location.parent().filter(|p| self.objects.contains_key(p)) {
+                let (parent, _) = self.objects.get_key_value(&parent).unwrap();
+                successors.entry(parent).or_default().insert(location);
+            }
+        }
+        // Links to machines that are not part of the graph are ignored.
+        for callees in successors.values_mut() {
+            callees.retain(|callee| self.objects.contains_key(*callee));
+        }
+        let mut predecessors: BTreeMap<&Location, BTreeSet<&Location>> =
+            self.objects.keys().map(|l| (l, BTreeSet::new())).collect();
+        for (location, callees) in &successors {
+            for callee in callees {
+                predecessors.get_mut(callee).unwrap().insert(*location);
+            }
+        }
+
+        let mut in_degrees: BTreeMap<&Location, usize> = predecessors
+            .iter()
+            .map(|(location, callers)| (*location, callers.len()))
+            .collect();
+        let mut ready: BTreeSet<&Location> = in_degrees
+            .iter()
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(location, _)| *location)
+            .collect();
+        let mut order = vec![];
+        while let Some(location) = ready.pop_first() {
+            in_degrees.remove(location);
+            order.push(location.clone());
+            for callee in successors.get(location).into_iter().flatten() {
+                let degree = in_degrees.get_mut(callee).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.insert(callee);
+                }
+            }
+        }
+        if in_degrees.is_empty() {
+            return Ok(order);
+        }
+
+        // Every remaining machine has a remaining predecessor, so walking backwards
+        // from any of them eventually reaches a machine for the second time.
+        let mut path = vec![*in_degrees.keys().next().unwrap()];
+        loop {
+            let current = path.last().unwrap();
+            let predecessor = predecessors[current]
+                .iter()
+                .find(|p| in_degrees.contains_key(*p))
+                .unwrap();
+            if let Some(start) = path.iter().position(|l| l == predecessor) {
+                let mut cycle = path[start..]
+                    .iter()
+                    .rev()
+                    .map(|l| (*l).clone())
+                    .collect::<Vec<_>>();
+                // Start the cycle at its smallest machine.
+                let first = cycle.iter().position_min().unwrap();
+                cycle.rotate_left(first);
+                return Err(CycleError { cycle });
+            }
+            path.push(predecessor);
+        }
+    }
+
+    /// Returns, for each machine with links, the machines it has links into.
+    fn link_edges(&self) -> BTreeMap<&Location, BTreeSet<&Location>> {
+        self.objects
+            .iter()
+            .filter(|(_, object)| !object.links.is_empty())
+            .map(|(location, object)| {
+                let callees = object
+                    .links
+                    .iter()
+                    .map(|link| &link.to.machine.location)
+                    .collect();
+                (location, callees)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        object::{Link, LinkFrom, LinkTo, Machine, Object, Operation},
+        parsed::build::direct_reference,
+        SourceRef,
+    };
+
+    use super::*;
+
+    fn location(s: &str) -> Location {
+        s.parse().unwrap()
+    }
+
+    fn machine(location: &str) -> Machine {
+        Machine {
+            location: self::location(location),
+            latch: Some("latch".into()),
+            operation_id: None,
+            call_selectors: None,
+        }
+    }
+
+    fn link(to: &str) -> Link {
+        Link {
+            from: LinkFrom {
+                flag: direct_reference("flag"),
+                params: Default::default(),
+            },
+            to: LinkTo {
+                machine: machine(to),
+                operation: Operation {
+                    name: "op".into(),
+                    id: None,
+                    params: Default::default(),
+                },
+                selector_index: None,
+            },
+            is_permutation: false,
+            source: SourceRef::unknown(),
+        }
+    }
+
+    /// A graph with the given objects and the links between them.
+    fn graph(objects: &[(&str, &[&str])]) -> PILGraph {
+        PILGraph {
+            main: machine("main"),
+            entry_points: vec![],
+            objects: objects
+                .iter()
+                .map(|(location, links)| {
+                    let object = Object {
+                        links: links.iter().map(|to| link(to)).collect(),
+                        ..Default::default()
+                    };
+                    (self::location(location), object)
+                })
+                .collect(),
+            definitions: Default::default(),
+        }
+    }
+
+    fn locations(locations: &[&str]) -> Vec<Location> {
+        locations.iter().map(|l| location(l)).collect()
+    }
+
+    #[test]
+    fn callers_and_callees() {
+        let graph = graph(&[
+            ("main", &["main::b", "main::a", "main::b"]),
+            ("main::a", &["main::b"]),
+            ("main::b", &[]),
+        ]);
+        assert_eq!(
+            graph.callees_of(&location("main")),
+            locations(&["main::a", "main::b"])
+        );
+        assert_eq!(
+            graph.callers_of(&location("main::b")),
+            locations(&["main", "main::a"])
+        );
+        assert_eq!(graph.callers_of(&location("main")), vec![]);
+        assert_eq!(graph.callees_of(&location("main::b")), vec![]);
+    }
+
+    #[test]
+    fn topological_order() {
+        // `main::b` calls `main::a`, and `main::x::y` is contained in `main::x`.
+        let graph = graph(&[
+            ("main", &["main::b", "main::x::y"]),
+            ("main::a", &[]),
+            ("main::b", &["main::a"]),
+            ("main::x", &[]),
+            ("main::x::y", &[]),
+            ("main::z", &["main::x::y"]),
+        ]);
+        assert_eq!(
+            graph.topological_order(),
+            Ok(locations(&[
+                "main",
+                "main::b",
+                "main::a",
+                "main::x",
+                "main::z",
+                "main::x::y"
+            ]))
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        let mutual = graph(&[
+            ("main", &["main::c"]),
+            ("main::a", &["main::b"]),
+            ("main::b", &["main::c"]),
+            ("main::c", &["main::a"]),
+        ]);
+        let error = mutual.topological_order().unwrap_err();
+        assert_eq!(error.cycle, locations(&["main::a", "main::b", "main::c"]));
+        assert_eq!(
+            error.to_string(),
+            "Machines call each other in a cycle: main_a -> main_b -> main_c -> main_a"
+        );
+
+        let self_call = graph(&[("main", &["main"])]);
+        assert_eq!(
+            self_call.topological_order().unwrap_err().cycle,
+            locations(&["main"])
+        );
+    }
+}
@@ -1,24 +1,40 @@
 use std::{
-    fmt::{Display, Formatter, Result},
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::{Debug, Display, Formatter, Result},
     iter::{empty, once, repeat},
     str::FromStr,
+    sync::OnceLock,
 };
 
 use itertools::Itertools;
-use powdr_number::BigUint;
+use powdr_number::{BigInt, BigUint};
 
 use derive_more::From;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use crate::SourceRef;
 
-use super::{Expression, PilStatement, TypedExpression};
+use super::{
+    ArrayLiteral, BinaryOperator, BlockExpression, Expression, FunctionCall, IfExpression,
+    IndexAccess, LambdaExpression, LetStatementInsideBlock, MatchArm, MatchPattern, PilStatement,
+    TypedExpression, UnaryOperator,
+};
 
-#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[derive(Default, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct ASMProgram {
     pub main: ASMModule,
 }
 
-#[derive(Default, Clone, Debug, PartialEq, Eq)]
+impl ASMProgram {
+    /// Returns the JSON schema of serialized asm programs.
+    pub fn get_struct_schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Self)
+    }
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct ASMModule {
     pub statements: Vec<ModuleStatement>,
 }
@@ -31,18 +47,18 @@ impl ASMModule {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, From)]
+#[derive(Debug, Clone, PartialEq, Eq, From, Serialize, Deserialize, JsonSchema)]
 pub enum ModuleStatement {
     SymbolDefinition(SymbolDefinition),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct SymbolDefinition {
     pub name: String,
     pub value: SymbolValue,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, From)]
+#[derive(Debug, Clone, PartialEq, Eq, From, Serialize, Deserialize, JsonSchema)]
 pub enum SymbolValue {
     /// A machine definition
     Machine(Machine),
@@ -77,7 +93,7 @@ pub enum SymbolValueRef<'a> {
     Expression(&'a TypedExpression),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, From)]
+#[derive(Debug, Clone, PartialEq, Eq, From, Serialize, Deserialize, JsonSchema)]
 pub enum Module {
     External(String),
     Local(ASMModule),
@@ -98,7 +114,7 @@ pub enum ModuleRef<'a> {
     Local(&'a ASMModule),
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Import {
     /// the path imported in the source
     pub path: SymbolPath,
@@ -107,35 +123,45 @@ pub struct Import {
 /// A symbol path is a sequence of strings separated by ``::`.
 /// It can contain the special word `super`, which goes up a level.
 /// If it does not start with `::`, it is relative.
-#[derive(Default, Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+#[derive(Default, PartialEq, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
 pub struct SymbolPath {
     /// The parts between each `::`.
     parts: Vec<Part>,
+    /// The result of `to_dotted_string`, computed on first use.
+    #[serde(skip)]
+    dotted: CachedString,
 }
 
 impl SymbolPath {
     pub fn from_identifier(name: String) -> Self {
-        Self {
-            parts: vec![Part::Named(name)],
-        }
+        Self::from_parts([Part::Named(name)])
     }
 
     pub fn from_parts<P: IntoIterator<Item = Part>>(parts: P) -> Self {
         Self {
             parts: parts.into_iter().collect(),
+            dotted: Default::default(),
         }
     }
 
     pub fn join<P: Into<Self>>(mut self, other: P) -> Self {
         self.parts.extend(other.into().parts);
+        self.dotted = Default::default();
         self
     }
 
     /// Formats the path and uses `.` as separator if
     /// there are at most two components.
     pub fn to_dotted_string(&self) -> String {
-        let separator = if self.parts.len() <= 2 { "." } else { "::" };
-        self.parts.iter().format(separator).to_string()
+        self.dotted_str().to_string()
+    }
+
+    /// Same as `to_dotted_string`, but only formats the path once.
+    pub fn dotted_str(&self) -> &str {
+        self.dotted.0.get_or_init(|| {
+            let separator = if self.parts.len() <= 2 { "." } else { "::" };
+            self.parts.iter().format(separator).to_string()
+        })
     }
 
     pub fn try_to_identifier(&self) -> Option<&String> {
@@ -146,6 +172,7 @@ impl SymbolPath {
     }
 
     pub fn try_last_part_mut(&mut self) -> Option<&mut String> {
+        self.dotted = Default::default();
         self.parts.last_mut().and_then(|p| match p {
             Part::Super => None,
             Part::Named(n) => Some(n),
@@ -187,19 +214,14 @@ impl FromStr for SymbolPath {
                     Part::Named(s.to_string())
                 }
             })
-            .collect();
-        Ok(Self { parts })
+            .collect::<Vec<_>>();
+        Ok(Self::from_parts(parts))
     }
 }
 
 impl From<AbsoluteSymbolPath> for SymbolPath {
     fn from(value: AbsoluteSymbolPath) -> Self {
-        Self {
-            parts: once(String::new())
-                .chain(value.parts)
-                .map(Part::Named)
-                .collect(),
-        }
+        Self::from_parts(once(String::new()).chain(value.parts).map(Part::Named))
     }
 }
 
@@ -209,10 +231,45 @@ impl Display for SymbolPath {
     }
 }
 
+impl Debug for SymbolPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.debug_struct("SymbolPath")
+            .field("parts", &self.parts)
+            .finish()
+    }
+}
+
+/// A lazily computed string that is derived from the value containing it,
+/// so it is ignored in comparisons.
+#[derive(Default, Clone)]
+struct CachedString(OnceLock<String>);
+
+impl PartialEq for CachedString {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for CachedString {}
+
+impl PartialOrd for CachedString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CachedString {
+    fn cmp(&self, _other: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
 /// An absolute symbol path is a resolved SymbolPath,
 /// which means it has to start with `::` and it cannot contain
 /// the word `super`.
-#[derive(Default, Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+#[derive(
+    Default, Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub struct AbsoluteSymbolPath {
     /// Contains the parts after the initial `::`.
     parts: Vec<String>,
@@ -283,9 +340,8 @@ impl AbsoluteSymbolPath {
                     .skip(common_prefix_len)
                     .cloned()
                     .map(Part::Named),
-            )
-            .collect();
-        SymbolPath { parts }
+            );
+        SymbolPath::from_parts(parts)
     }
 
     /// Returns the common prefix of two paths.
@@ -333,6 +389,20 @@ impl AbsoluteSymbolPath {
         let separator = if self.parts.len() <= 2 { "." } else { "::" };
         self.parts.join(separator)
     }
+
+    /// Parses an absolute name as used in analyzed files, which is the inverse of
+    /// `to_dotted_string`. Both `.` and `::` are accepted as separators, even mixed,
+    /// and a leading `::` is ignored.
+    pub fn from_dotted_string(name: &str) -> Self {
+        let parts = name
+            .trim_start_matches("::")
+            .split("::")
+            .flat_map(|part| part.split('.'))
+            .filter(|part| !part.is_empty())
+            .map(|part| part.to_string())
+            .collect();
+        Self { parts }
+    }
 }
 
 impl Display for AbsoluteSymbolPath {
@@ -341,7 +411,7 @@ impl Display for AbsoluteSymbolPath {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
 pub enum Part {
     Super,
     Named(String),
@@ -368,8 +438,9 @@ impl Display for Part {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Machine {
+    pub source: SourceRef,
     pub arguments: MachineArguments,
     pub statements: Vec<MachineStatement>,
 }
@@ -378,7 +449,7 @@ impl Machine {
     /// Returns a vector of all local variables / names defined in the machine.
     pub fn local_names(&self) -> Box<dyn Iterator<Item = &String> + '_> {
         Box::new(self.statements.iter().flat_map(|s| match s {
-            MachineStatement::RegisterDeclaration(_, name, _) => Box::new(once(name)),
+            MachineStatement::RegisterDeclaration(_, name, _, _) => Box::new(once(name)),
             MachineStatement::Pil(_, statement) => statement.symbol_definition_names(),
             MachineStatement::Degree(_, _)
             | MachineStatement::Submachine(_, _, _)
@@ -390,13 +461,17 @@ impl Machine {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone)]
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone, Serialize, Deserialize, JsonSchema,
+)]
 pub struct MachineArguments {
     pub latch: Option<String>,
     pub operation_id: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default)]
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default, Serialize, Deserialize, JsonSchema,
+)]
 pub struct Params {
     pub inputs: Vec<Param>,
     pub outputs: Vec<Param>,
@@ -430,50 +505,72 @@ impl Params {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 /// the operation id necessary to call this function from the outside
 pub struct OperationId {
+    #[schemars(with = "Option<Vec<u64>>")]
     pub id: Option<BigUint>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
 pub struct Instruction {
     pub params: Params,
     pub body: InstructionBody,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum MachineStatement {
-    Degree(SourceRef, BigUint),
+    Degree(SourceRef, #[schemars(with = "Vec<u64>")] BigUint),
     Pil(SourceRef, PilStatement),
     Submachine(SourceRef, SymbolPath, String),
-    RegisterDeclaration(SourceRef, String, Option<RegisterFlag>),
+    RegisterDeclaration(
+        SourceRef,
+        String,
+        Option<RegisterFlag>,
+        Option<RegisterRange>,
+    ),
     InstructionDeclaration(SourceRef, String, Instruction),
     LinkDeclaration(SourceRef, LinkDeclaration),
     FunctionDeclaration(SourceRef, String, Params, Vec<FunctionStatement>),
     OperationDeclaration(SourceRef, String, OperationId, Params),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LinkDeclaration {
     pub flag: Expression,
     pub to: CallableRef,
+    /// whether the link is a permutation (`~>`) instead of a lookup (`=>`)
+    pub is_permutation: bool,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CallableRef {
     pub instance: String,
     pub callable: String,
     pub params: Params,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+/// An instruction defined in terms of another instruction of the same machine,
+/// like `instr inc X -> Y = add(X, 1) -> Y;`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct InstructionAlias {
+    /// the name of the aliased instruction
+    pub instruction: String,
+    /// the arguments passed to the inputs of the aliased instruction,
+    /// which can refer to the inputs of the alias
+    pub inputs: Vec<Expression>,
+    /// the outputs of the alias bound to the outputs of the aliased instruction, in order
+    pub outputs: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum InstructionBody {
     Local(Vec<PilStatement>),
     CallableRef(CallableRef),
+    Alias(InstructionAlias),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
 pub enum AssignmentRegister {
     Register(String),
     Wildcard,
@@ -488,7 +585,7 @@ impl AssignmentRegister {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum FunctionStatement {
     Assignment(
         SourceRef,
@@ -502,27 +599,354 @@ pub enum FunctionStatement {
     Return(SourceRef, Vec<Expression>),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum DebugDirective {
     File(usize, String, String),
     Loc(usize, usize, usize),
     OriginalInstruction(String),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum RegisterFlag {
     IsPC,
     IsAssignment,
     IsReadOnly,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+/// A constraint on the values a register can hold.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub enum RegisterRange {
+    /// `range(min, max)`, both bounds inclusive
+    Range(Expression, Expression),
+    /// `bits(n)`, equivalent to `range(0, 2**n - 1)`
+    Bits(Expression),
+}
+
+impl RegisterRange {
+    /// Evaluates the bounds of the range, which have to be compile-time constants.
+    /// @returns the inclusive lower and upper bound.
+    pub fn bounds(&self) -> std::result::Result<(BigUint, BigUint), String> {
+        let evaluate = |e: &Expression| {
+            evaluate_constant(e)
+                .and_then(|v| BigUint::try_from(v).ok())
+                .ok_or_else(|| {
+                    format!("Expected a non-negative compile-time constant in register range, but got {e}")
+                })
+        };
+        let (min, max) = match self {
+            RegisterRange::Range(min, max) => (evaluate(min)?, evaluate(max)?),
+            RegisterRange::Bits(bits) => {
+                let bits = usize::try_from(&evaluate(bits)?)
+                    .ok()
+                    .filter(|bits| *bits > 0)
+                    .ok_or_else(|| format!("Invalid bit width in register range: {bits}"))?;
+                (
+                    0u32.into(),
+                    (BigUint::from(1u32) << bits) - BigUint::from(1u32),
+                )
+            }
+        };
+        if min > max {
+            return Err(format!("Register range {self} is empty"));
+        }
+        Ok((min, max))
+    }
+}
+
+/// Evaluates integer literals combined by arithmetic operators.
+fn evaluate_constant(e: &Expression) -> Option<BigInt> {
+    match e {
+        Expression::Number(n, _, _) => Some(n.clone().into()),
+        Expression::UnaryOperation(UnaryOperator::Minus, inner) => Some(-evaluate_constant(inner)?),
+        Expression::BinaryOperation(left, op, right) => {
+            let left = evaluate_constant(left)?;
+            let right = evaluate_constant(right)?;
+            match op {
+                BinaryOperator::Add => Some(left + right),
+                BinaryOperator::Sub => Some(left - right),
+                BinaryOperator::Mul => Some(left * right),
+                BinaryOperator::Pow => Some(left.pow(usize::try_from(&right).ok()?)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Param {
     pub name: String,
+    #[schemars(with = "Option<Vec<u64>>")]
     pub index: Option<BigUint>,
     pub ty: Option<String>,
 }
 
+/// Returns, for each machine, module-level symbol and import in the (local) modules of the
+/// program, the paths of the symbols it references.
+/// The paths are resolved relative to the module of the referencing symbol and through
+/// imports, and are absolute unless they leave the root module through `super`.
+/// Names local to machines, like registers, columns and parameters, are not included.
+pub fn referenced_paths(
+    program: &ASMProgram,
+) -> BTreeMap<AbsoluteSymbolPath, BTreeSet<SymbolPath>> {
+    let mut imports = BTreeMap::new();
+    collect_imports(&AbsoluteSymbolPath::default(), &program.main, &mut imports);
+    let mut result = BTreeMap::new();
+    collect_referenced_paths(
+        &AbsoluteSymbolPath::default(),
+        &program.main,
+        &imports,
+        &mut result,
+    );
+    result
+}
+
+fn collect_imports(
+    location: &AbsoluteSymbolPath,
+    module: &ASMModule,
+    imports: &mut BTreeMap<AbsoluteSymbolPath, SymbolPath>,
+) {
+    for SymbolDefinition { name, value } in module.symbol_definitions() {
+        match value {
+            SymbolValue::Import(Import { path }) => {
+                imports.insert(location.with_part(name), path.clone());
+            }
+            SymbolValue::Module(Module::Local(module)) => {
+                collect_imports(&location.with_part(name), module, imports)
+            }
+            _ => {}
+        }
+    }
+}
+
+// The cached string inside `SymbolPath` does not take part in its ordering.
+#[allow(clippy::mutable_key_type)]
+fn collect_referenced_paths(
+    location: &AbsoluteSymbolPath,
+    module: &ASMModule,
+    imports: &BTreeMap<AbsoluteSymbolPath, SymbolPath>,
+    result: &mut BTreeMap<AbsoluteSymbolPath, BTreeSet<SymbolPath>>,
+) {
+    for SymbolDefinition { name, value } in module.symbol_definitions() {
+        let symbol = location.with_part(name);
+        let mut references = vec![];
+        match value {
+            SymbolValue::Module(Module::Local(module)) => {
+                collect_referenced_paths(&symbol, module, imports, result);
+                continue;
+            }
+            SymbolValue::Module(Module::External(_)) => continue,
+            SymbolValue::Import(Import { path }) => references.push(path.clone()),
+            SymbolValue::Machine(machine) => machine_references(machine, &mut references),
+            SymbolValue::Expression(TypedExpression { e, type_scheme }) => {
+                let locals = HashSet::new();
+                if let Some(type_scheme) = type_scheme {
+                    for e in type_scheme.ty.expressions() {
+                        expression_references(e, &locals, &mut references);
+                    }
+                }
+                expression_references(e, &locals, &mut references);
+            }
+        }
+        let references = references
+            .into_iter()
+            .map(|path| resolve_path(location, path, imports))
+            .collect();
+        result.insert(symbol, references);
+    }
+}
+
+/// Collects the paths referenced by the statements of a machine, relative to its module.
+fn machine_references<'a>(machine: &'a Machine, references: &mut Vec<SymbolPath>) {
+    let mut locals: HashSet<&str> = machine.local_names().map(|n| n.as_str()).collect();
+    for statement in &machine.statements {
+        if let MachineStatement::Submachine(_, _, name)
+        | MachineStatement::InstructionDeclaration(_, name, _)
+        | MachineStatement::FunctionDeclaration(_, name, _, _)
+        | MachineStatement::OperationDeclaration(_, name, _, _) = statement
+        {
+            locals.insert(name);
+        }
+    }
+    let with_params = |params: &'a Params| {
+        let mut locals = locals.clone();
+        locals.extend(params.inputs_and_outputs().map(|p| p.name.as_str()));
+        locals
+    };
+    for statement in &machine.statements {
+        match statement {
+            MachineStatement::Submachine(_, path, _) => references.push(path.clone()),
+            MachineStatement::Pil(_, statement) => {
+                for e in statement.expressions() {
+                    expression_references(e, &locals, references);
+                }
+            }
+            MachineStatement::InstructionDeclaration(_, _, Instruction { params, body }) => {
+                let locals = with_params(params);
+                match body {
+                    InstructionBody::Local(statements) => {
+                        for e in statements.iter().flat_map(|s| s.expressions()) {
+                            expression_references(e, &locals, references);
+                        }
+                    }
+                    InstructionBody::Alias(InstructionAlias { inputs, .. }) => {
+                        for e in inputs {
+                            expression_references(e, &locals, references);
+                        }
+                    }
+                    InstructionBody::CallableRef(_) => {}
+                }
+            }
+            MachineStatement::LinkDeclaration(_, LinkDeclaration { flag, .. }) => {
+                expression_references(flag, &locals, references);
+            }
+            MachineStatement::FunctionDeclaration(_, _, params, statements) => {
+                let mut locals = with_params(params);
+                locals.extend(statements.iter().filter_map(|s| match s {
+                    FunctionStatement::Label(_, label) => Some(label.as_str()),
+                    _ => None,
+                }));
+                for statement in statements {
+                    let expressions = match statement {
+                        FunctionStatement::Assignment(_, _, _, e) => {
+                            std::slice::from_ref(e.as_ref())
+                        }
+                        FunctionStatement::Instruction(_, _, inputs)
+                        | FunctionStatement::Return(_, inputs) => &inputs[..],
+                        FunctionStatement::Label(_, _)
+                        | FunctionStatement::DebugDirective(_, _) => &[],
+                    };
+                    for e in expressions {
+                        expression_references(e, &locals, references);
+                    }
+                }
+            }
+            MachineStatement::RegisterDeclaration(_, _, _, Some(range)) => {
+                let range = match range {
+                    RegisterRange::Range(min, max) => vec![min, max],
+                    RegisterRange::Bits(bits) => vec![bits],
+                };
+                for e in range {
+                    expression_references(e, &locals, references);
+                }
+            }
+            MachineStatement::Degree(_, _)
+            | MachineStatement::RegisterDeclaration(_, _, _, None)
+            | MachineStatement::OperationDeclaration(_, _, _, _) => {}
+        }
+    }
+}
+
+/// Collects the paths referenced by an expression, except for the names in `locals`
+/// and the local variables (lambda parameters and `let` statements in blocks) around the references.
+fn expression_references(e: &Expression, locals: &HashSet<&str>, references: &mut Vec<SymbolPath>) {
+    // We cannot use the visitor here because the parameters of lambda
+    // expressions are only local inside their body.
+    let mut visit = |e| expression_references(e, locals, references);
+    match e {
+        Expression::Reference(reference) => {
+            if !matches!(reference.path.try_to_identifier(), Some(name) if locals.contains(name.as_str()))
+            {
+                references.push(reference.path.clone());
+            }
+        }
+        Expression::PublicReference(_) | Expression::Number(..) | Expression::String(_) => {}
+        Expression::LambdaExpression(LambdaExpression { params, body }) => {
+            let mut locals = locals.clone();
+            locals.extend(params.iter().map(|p| p.as_str()));
+            expression_references(body, &locals, references);
+        }
+        Expression::Tuple(items) | Expression::ArrayLiteral(ArrayLiteral { items }) => {
+            items.iter().for_each(visit)
+        }
+        Expression::BinaryOperation(a, _, b)
+        | Expression::IndexAccess(IndexAccess { array: a, index: b }) => {
+            visit(a);
+            visit(b);
+        }
+        Expression::UnaryOperation(_, e) | Expression::FreeInput(e) => visit(e),
+        Expression::FunctionCall(FunctionCall {
+            function,
+            arguments,
+        }) => {
+            visit(function);
+            arguments.iter().for_each(visit);
+        }
+        Expression::MatchExpression(scrutinee, arms) => {
+            visit(scrutinee);
+            for MatchArm { patterns, value } in arms {
+                for pattern in patterns {
+                    match pattern {
+                        MatchPattern::CatchAll => {}
+                        MatchPattern::Pattern(e) => visit(e),
+                        MatchPattern::Range(start, end, _) => {
+                            visit(start);
+                            visit(end);
+                        }
+                    }
+                }
+                visit(value);
+            }
+        }
+        Expression::IfExpression(IfExpression {
+            condition,
+            body,
+            else_body,
+        }) => {
+            visit(condition);
+            visit(body);
+            visit(else_body);
+        }
+        Expression::BlockExpression(BlockExpression { statements, expr }) => {
+            // Each variable is only local in the statements after it.
+            let mut locals = locals.clone();
+            for LetStatementInsideBlock { name, value } in statements {
+                expression_references(value, &locals, references);
+                locals.insert(name.as_str());
+            }
+            expression_references(expr, &locals, references);
+        }
+    }
+}
+
+/// Resolves a path relative to `location` and through `imports` as far as possible.
+/// Returns the path unchanged if it leaves the root module.
+fn resolve_path(
+    location: &AbsoluteSymbolPath,
+    path: SymbolPath,
+    imports: &BTreeMap<AbsoluteSymbolPath, SymbolPath>,
+) -> SymbolPath {
+    let Some(mut resolved) = try_join(location, &path) else {
+        return path;
+    };
+    // Every import can be used at most once, otherwise they form a cycle.
+    for _ in 0..=imports.len() {
+        let Some((import, target)) = resolved
+            .iter_to_root()
+            .find_map(|prefix| imports.get_key_value(&prefix))
+        else {
+            break;
+        };
+        let Some(target) = try_join(&import.clone().parent(), target) else {
+            break;
+        };
+        let rest = resolved.parts[import.len()..].to_vec();
+        resolved = AbsoluteSymbolPath {
+            parts: target.parts.into_iter().chain(rest).collect(),
+        };
+    }
+    resolved.into()
+}
+
+/// Like `AbsoluteSymbolPath::join`, but returns None if the path leaves the root module.
+fn try_join(location: &AbsoluteSymbolPath, path: &SymbolPath) -> Option<AbsoluteSymbolPath> {
+    let supers = path
+        .parts()
+        .take_while(|part| matches!(part, Part::Super))
+        .count();
+    (supers <= location.len()).then(|| location.clone().join(path.clone()))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -598,4 +1022,19 @@ mod test {
         let rel = v.relative_to(&base);
         assert_eq!(base.join(rel), v);
     }
+
+    #[test]
+    fn dotted_string_is_updated() {
+        let mut path = SymbolPath::from_str("a::b").unwrap();
+        assert_eq!(path.to_dotted_string(), "a.b");
+        *path.try_last_part_mut().unwrap() = "c".to_string();
+        assert_eq!(path.dotted_str(), "a.c");
+        let path = path.join(SymbolPath::from_identifier("d".to_string()));
+        assert_eq!(path.dotted_str(), "a::c::d");
+        assert_eq!(path, SymbolPath::from_str("a::c::d").unwrap());
+        assert_eq!(
+            format!("{path:?}"),
+            r#"SymbolPath { parts: [Named("a"), Named("c"), Named("d")] }"#
+        );
+    }
 }
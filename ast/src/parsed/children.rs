@@ -0,0 +1,203 @@
+//! Iterators over the (top-level) expressions of AST nodes.
+//!
+//! Most nodes store their expressions in at most a few vectors or options,
+//! so the iterators keep up to four slices and only collect the expressions
+//! into a vector for nodes with more parts, like nested types.
+
+use std::{slice, vec};
+
+const MAX_SLICES: usize = 4;
+
+/// An iterator over references to the (top-level) expressions of an AST node.
+pub enum Children<'a, E> {
+    Slices([slice::Iter<'a, E>; MAX_SLICES]),
+    Collected(vec::IntoIter<&'a E>),
+}
+
+impl<'a, E> Children<'a, E> {
+    pub fn empty() -> Self {
+        Children::Slices([[].iter(), [].iter(), [].iter(), [].iter()])
+    }
+
+    /// Iterates over the given slices in order. Panics for more than four slices.
+    pub fn new<const N: usize>(slices: [&'a [E]; N]) -> Self {
+        assert!(N <= MAX_SLICES);
+        let mut iters = [[].iter(), [].iter(), [].iter(), [].iter()];
+        for (iter, slice) in iters.iter_mut().zip(slices) {
+            *iter = slice.iter();
+        }
+        Children::Slices(iters)
+    }
+
+    /// Iterates over the elements of `self` and then over those of `other`.
+    /// Only allocates if the two iterators consist of more than four non-empty slices together.
+    pub fn chain(self, other: Self) -> Self {
+        match (self, other) {
+            (Children::Slices(first), Children::Slices(second))
+                if non_empty_count(&first) + non_empty_count(&second) <= MAX_SLICES =>
+            {
+                let mut iters = [[].iter(), [].iter(), [].iter(), [].iter()];
+                for (iter, part) in iters
+                    .iter_mut()
+                    .zip(first.into_iter().chain(second).filter(|i| i.len() > 0))
+                {
+                    *iter = part;
+                }
+                Children::Slices(iters)
+            }
+            (first, second) => Iterator::chain(first, second).collect(),
+        }
+    }
+}
+
+impl<'a, E> Iterator for Children<'a, E> {
+    type Item = &'a E;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Children::Slices(iters) => iters.iter_mut().find_map(|i| i.next()),
+            Children::Collected(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match self {
+            Children::Slices(iters) => iters.iter().map(|i| i.len()).sum(),
+            Children::Collected(iter) => iter.len(),
+        };
+        (len, Some(len))
+    }
+}
+
+impl<'a, E> FromIterator<&'a E> for Children<'a, E> {
+    fn from_iter<I: IntoIterator<Item = &'a E>>(iter: I) -> Self {
+        Children::Collected(iter.into_iter().collect::<Vec<_>>().into_iter())
+    }
+}
+
+/// An iterator over mutable references to the (top-level) expressions of an AST node.
+pub enum ChildrenMut<'a, E> {
+    Slices([slice::IterMut<'a, E>; MAX_SLICES]),
+    Collected(vec::IntoIter<&'a mut E>),
+}
+
+impl<'a, E> ChildrenMut<'a, E> {
+    pub fn empty() -> Self {
+        ChildrenMut::Slices([[].iter_mut(), [].iter_mut(), [].iter_mut(), [].iter_mut()])
+    }
+
+    /// Iterates over the given slices in order. Panics for more than four slices.
+    pub fn new<const N: usize>(slices: [&'a mut [E]; N]) -> Self {
+        assert!(N <= MAX_SLICES);
+        let mut iters = [[].iter_mut(), [].iter_mut(), [].iter_mut(), [].iter_mut()];
+        for (iter, slice) in iters.iter_mut().zip(slices) {
+            *iter = slice.iter_mut();
+        }
+        ChildrenMut::Slices(iters)
+    }
+
+    /// Iterates over the elements of `self` and then over those of `other`.
+    /// Only allocates if the two iterators consist of more than four non-empty slices together.
+    pub fn chain(self, other: Self) -> Self {
+        match (self, other) {
+            (ChildrenMut::Slices(first), ChildrenMut::Slices(second))
+                if non_empty_count_mut(&first) + non_empty_count_mut(&second) <= MAX_SLICES =>
+            {
+                let mut iters = [[].iter_mut(), [].iter_mut(), [].iter_mut(), [].iter_mut()];
+                for (iter, part) in iters
+                    .iter_mut()
+                    .zip(first.into_iter().chain(second).filter(|i| i.len() > 0))
+                {
+                    *iter = part;
+                }
+                ChildrenMut::Slices(iters)
+            }
+            (first, second) => Iterator::chain(first, second).collect(),
+        }
+    }
+}
+
+impl<'a, E> Iterator for ChildrenMut<'a, E> {
+    type Item = &'a mut E;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ChildrenMut::Slices(iters) => iters.iter_mut().find_map(|i| i.next()),
+            ChildrenMut::Collected(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match self {
+            ChildrenMut::Slices(iters) => iters.iter().map(|i| i.len()).sum(),
+            ChildrenMut::Collected(iter) => iter.len(),
+        };
+        (len, Some(len))
+    }
+}
+
+impl<'a, E> FromIterator<&'a mut E> for ChildrenMut<'a, E> {
+    fn from_iter<I: IntoIterator<Item = &'a mut E>>(iter: I) -> Self {
+        ChildrenMut::Collected(iter.into_iter().collect::<Vec<_>>().into_iter())
+    }
+}
+
+/// Returns the value of the option as a slice of length zero or one.
+pub fn option_slice<E>(o: &Option<E>) -> &[E] {
+    o.as_ref().map_or(&[], slice::from_ref)
+}
+
+/// Returns the value of the option as a mutable slice of length zero or one.
+pub fn option_slice_mut<E>(o: &mut Option<E>) -> &mut [E] {
+    o.as_mut().map_or(&mut [], slice::from_mut)
+}
+
+fn non_empty_count<E>(iters: &[slice::Iter<'_, E>]) -> usize {
+    iters.iter().filter(|i| i.len() > 0).count()
+}
+
+fn non_empty_count_mut<E>(iters: &[slice::IterMut<'_, E>]) -> usize {
+    iters.iter().filter(|i| i.len() > 0).count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slices_in_order() {
+        let (a, b, c) = (vec![1, 2], vec![], vec![3]);
+        let children = Children::new([&a[..], &b, &c]);
+        assert_eq!(children.size_hint(), (3, Some(3)));
+        assert_eq!(children.copied().collect::<Vec<_>>(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn chain_without_allocation() {
+        let (a, b, c) = (vec![1], vec![2, 3], vec![4]);
+        let chained = Children::new([&a[..], &[]]).chain(Children::new([&b[..], &c]));
+        assert!(matches!(chained, Children::Slices(_)));
+        assert_eq!(chained.copied().collect::<Vec<_>>(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn chain_with_allocation() {
+        let v = (0..6).map(|i| vec![i]).collect::<Vec<_>>();
+        let chained = Children::new([&v[0][..], &v[1], &v[2]]).chain(Children::new([
+            &v[3][..],
+            &v[4],
+            &v[5],
+        ]));
+        assert!(matches!(chained, Children::Collected(_)));
+        assert_eq!(chained.copied().collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn mutable() {
+        let (mut a, mut b) = (Some(1), vec![2, 3]);
+        ChildrenMut::new([option_slice_mut(&mut a), &mut b])
+            .chain(ChildrenMut::empty())
+            .for_each(|x| *x *= 10);
+        assert_eq!((a, b), (Some(10), vec![20, 30]));
+    }
+}
@@ -19,6 +19,14 @@ impl Display for PILFile {
     }
 }
 
+impl PILFile {
+    /// Writes the file to `w`, producing the same output as `to_string()`
+    /// without building it in memory first.
+    pub fn write_pil<W: std::io::Write + ?Sized>(&self, w: &mut W) -> std::io::Result<()> {
+        crate::write_io(w, |f| write_items(f, &self.0))
+    }
+}
+
 impl Display for ASMProgram {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(f, "{}", self.main)
@@ -110,7 +118,23 @@ impl Display for InstructionBody {
                     .format(", ")
             ),
             InstructionBody::CallableRef(r) => write!(f, " = {r};"),
+            InstructionBody::Alias(alias) => write!(f, " = {alias};"),
+        }
+    }
+}
+
+impl Display for InstructionAlias {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{}({})",
+            self.instruction,
+            self.inputs.iter().format(", ")
+        )?;
+        if !self.outputs.is_empty() {
+            write!(f, " -> {}", self.outputs.iter().format(", "))?;
         }
+        Ok(())
     }
 }
 
@@ -142,7 +166,13 @@ impl Display for Instruction {
 
 impl Display for LinkDeclaration {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "link {} => {};", self.flag, self.to)
+        write!(
+            f,
+            "link {} {} {};",
+            self.flag,
+            if self.is_permutation { "~>" } else { "=>" },
+            self.to
+        )
     }
 }
 
@@ -158,12 +188,16 @@ impl Display for MachineStatement {
             MachineStatement::Degree(_, degree) => write!(f, "degree {};", degree),
             MachineStatement::Pil(_, statement) => write!(f, "{statement}"),
             MachineStatement::Submachine(_, ty, name) => write!(f, "{ty} {name};"),
-            MachineStatement::RegisterDeclaration(_, name, flag) => write!(
+            MachineStatement::RegisterDeclaration(_, name, flag, range) => write!(
                 f,
-                "reg {}{};",
+                "reg {}{}{};",
                 name,
                 flag.as_ref()
                     .map(|flag| format!("[{flag}]"))
+                    .unwrap_or_default(),
+                range
+                    .as_ref()
+                    .map(|range| format!(": {range}"))
                     .unwrap_or_default()
             ),
             MachineStatement::InstructionDeclaration(_, name, instruction) => {
@@ -264,6 +298,15 @@ impl Display for DebugDirective {
     }
 }
 
+impl Display for RegisterRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            RegisterRange::Range(min, max) => write!(f, "range({min}, {max})"),
+            RegisterRange::Bits(bits) => write!(f, "bits({bits})"),
+        }
+    }
+}
+
 impl Display for RegisterFlag {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
@@ -311,7 +354,12 @@ impl<Ref: Display> Display for FunctionCall<Ref> {
 
 impl<Ref: Display> Display for MatchArm<Ref> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "{} => {},", self.pattern, self.value,)
+        write!(
+            f,
+            "{} => {},",
+            self.patterns.iter().format(" | "),
+            self.value,
+        )
     }
 }
 
@@ -320,6 +368,10 @@ impl<Ref: Display> Display for MatchPattern<Ref> {
         match self {
             MatchPattern::CatchAll => write!(f, "_"),
             MatchPattern::Pattern(p) => write!(f, "{p}"),
+            MatchPattern::Range(start, end, RangeKind::Exclusive) => write!(f, "{start}..{end}"),
+            MatchPattern::Range(start, end, RangeKind::Inclusive) => {
+                write!(f, "{start}..={end}")
+            }
         }
     }
 }
@@ -334,6 +386,22 @@ impl<Ref: Display> Display for IfExpression<Ref> {
     }
 }
 
+impl<Ref: Display> Display for BlockExpression<Ref> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{{ ")?;
+        for statement in &self.statements {
+            write!(f, "{statement} ")?;
+        }
+        write!(f, "{} }}", self.expr)
+    }
+}
+
+impl<Ref: Display> Display for LetStatementInsideBlock<Ref> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "let {} = {};", self.name, self.value)
+    }
+}
+
 impl Display for Param {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(
@@ -352,8 +420,23 @@ impl Display for Param {
     }
 }
 
+/// Quotes and escapes a string such that the parser reads it back as the same string.
 pub fn quote(input: &str) -> String {
-    format!("\"{}\"", input.escape_default())
+    let mut result = String::from('"');
+    for c in input.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\u{8}' => result.push_str("\\b"),
+            '\u{c}' => result.push_str("\\f"),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
 }
 
 impl Display for PilStatement {
@@ -417,6 +500,14 @@ impl Display for PilStatement {
             PilStatement::Expression(_, e) => {
                 write!(f, "    {e};")
             }
+            PilStatement::LabeledIdentity(_, label, identity) => {
+                write!(
+                    f,
+                    "    #[label({})] {}",
+                    quote(label),
+                    identity.to_string().trim_start()
+                )
+            }
         }
     }
 }
@@ -441,6 +532,7 @@ impl Display for FunctionDefinition {
             FunctionDefinition::Array(array_expression) => {
                 write!(f, " = {array_expression}")
             }
+            FunctionDefinition::Sparse(sparse) => write!(f, " = {sparse}"),
             FunctionDefinition::Query(Expression::LambdaExpression(lambda)) => write!(
                 f,
                 "({}) query {}",
@@ -465,6 +557,16 @@ impl Display for FunctionDefinition {
     }
 }
 
+impl Display for SparseArrayExpression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{{ ")?;
+        for (row, value) in &self.entries {
+            write!(f, "{row}: {value}, ")?;
+        }
+        write!(f, "_: {} }}", self.default)
+    }
+}
+
 pub fn format_expressions<Ref: Display>(expressions: &[Expression<Ref>]) -> String {
     format!("{}", expressions.iter().format(", "))
 }
@@ -474,7 +576,12 @@ impl<Ref: Display> Display for Expression<Ref> {
         match self {
             Expression::Reference(reference) => write!(f, "{reference}"),
             Expression::PublicReference(name) => write!(f, ":{name}"),
-            Expression::Number(value, _) => write!(f, "{value}"),
+            Expression::Number(value, Some(LiteralType::Explicit(ty)), _) => {
+                write!(f, "{value}_{ty}")
+            }
+            Expression::Number(value, _, Radix::Decimal) => write!(f, "{value}"),
+            Expression::Number(value, _, Radix::Hexadecimal) => write!(f, "0x{value:x}"),
+            Expression::Number(value, _, Radix::Binary) => write!(f, "0b{value:b}"),
             Expression::String(value) => write!(f, "{}", quote(value)),
             Expression::Tuple(items) => write!(f, "({})", format_expressions(items)),
             Expression::LambdaExpression(lambda) => write!(f, "{}", lambda),
@@ -494,6 +601,7 @@ impl<Ref: Display> Display for Expression<Ref> {
                 write!(f, "match {scrutinee} {{ {} }}", arms.iter().format(" "))
             }
             Expression::IfExpression(e) => write!(f, "{e}"),
+            Expression::BlockExpression(e) => write!(f, "{e}"),
         }
     }
 }
@@ -514,7 +622,7 @@ impl Display for PolynomialName {
 
 impl Display for NamespacedPolynomialReference {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "{}", self.path.to_dotted_string())
+        write!(f, "{}", self.path.dotted_str())
     }
 }
 
@@ -3,8 +3,8 @@ use super::{
         ASMModule, ASMProgram, Import, Machine, Module, ModuleStatement, SymbolDefinition,
         SymbolValue,
     },
-    ArrayLiteral, Expression, FunctionCall, IfExpression, IndexAccess, LambdaExpression, MatchArm,
-    MatchPattern,
+    ArrayLiteral, BlockExpression, Expression, FunctionCall, IfExpression, IndexAccess,
+    LambdaExpression, LetStatementInsideBlock, MatchArm, MatchPattern,
 };
 
 pub trait Folder {
@@ -67,7 +67,7 @@ pub trait ExpressionFolder<Ref> {
         Ok(match e {
             Expression::Reference(r) => Expression::Reference(self.fold_reference(r)?),
             Expression::PublicReference(r) => Expression::PublicReference(r),
-            Expression::Number(n, t) => Expression::Number(n, t),
+            Expression::Number(n, t, r) => Expression::Number(n, t, r),
             Expression::String(s) => Expression::String(s),
             Expression::Tuple(t) => Expression::Tuple(self.fold_expressions(t)?),
             Expression::LambdaExpression(l) => Expression::LambdaExpression(self.fold_lambda(l)?),
@@ -100,6 +100,9 @@ pub trait ExpressionFolder<Ref> {
             Expression::IfExpression(if_expr) => {
                 Expression::IfExpression(self.fold_if_expression(if_expr)?)
             }
+            Expression::BlockExpression(block) => {
+                Expression::BlockExpression(self.fold_block_expression(block)?)
+            }
         })
     }
 
@@ -142,10 +145,13 @@ pub trait ExpressionFolder<Ref> {
 
     fn fold_match_arm(
         &mut self,
-        MatchArm { pattern, value }: MatchArm<Ref>,
+        MatchArm { patterns, value }: MatchArm<Ref>,
     ) -> Result<MatchArm<Ref>, Self::Error> {
         Ok(MatchArm {
-            pattern: self.fold_match_pattern(pattern)?,
+            patterns: patterns
+                .into_iter()
+                .map(|p| self.fold_match_pattern(p))
+                .collect::<Result<_, _>>()?,
             value: self.fold_expression(value)?,
         })
     }
@@ -157,6 +163,11 @@ pub trait ExpressionFolder<Ref> {
         Ok(match pattern {
             MatchPattern::CatchAll => MatchPattern::CatchAll,
             MatchPattern::Pattern(p) => MatchPattern::Pattern(self.fold_expression(p)?),
+            MatchPattern::Range(start, end, kind) => MatchPattern::Range(
+                self.fold_expression(start)?,
+                self.fold_expression(end)?,
+                kind,
+            ),
         })
     }
 
@@ -175,6 +186,24 @@ pub trait ExpressionFolder<Ref> {
         })
     }
 
+    fn fold_block_expression(
+        &mut self,
+        BlockExpression { statements, expr }: BlockExpression<Ref>,
+    ) -> Result<BlockExpression<Ref>, Self::Error> {
+        Ok(BlockExpression {
+            statements: statements
+                .into_iter()
+                .map(|s| {
+                    Ok(LetStatementInsideBlock {
+                        name: s.name,
+                        value: self.fold_expression(s.value)?,
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            expr: self.fold_boxed_expression(*expr)?,
+        })
+    }
+
     fn fold_boxed_expression(
         &mut self,
         e: Expression<Ref>,
@@ -1,5 +1,6 @@
 pub mod asm;
 pub mod build;
+pub mod children;
 pub mod display;
 pub mod folder;
 pub mod types;
@@ -9,7 +10,7 @@ pub mod visitor;
 use std::{
     collections::BTreeSet,
     iter::{empty, once},
-    ops,
+    ops, slice,
 };
 
 use powdr_number::{BigUint, DegreeType};
@@ -18,14 +19,22 @@ use serde::{Deserialize, Serialize};
 
 use self::{
     asm::{Part, SymbolPath},
+    children::{option_slice, option_slice_mut, Children, ChildrenMut},
     types::{Type, TypeScheme},
 };
 use crate::SourceRef;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PILFile(pub Vec<PilStatement>);
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+impl PILFile {
+    /// Returns the JSON schema of serialized PIL files.
+    pub fn get_struct_schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Self)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum PilStatement {
     /// File name
     Include(SourceRef, String),
@@ -65,6 +74,8 @@ pub enum PilStatement {
     ConnectIdentity(SourceRef, Vec<Expression>, Vec<Expression>),
     ConstantDefinition(SourceRef, String, Expression),
     Expression(SourceRef, Expression),
+    /// An identity statement with a label, written as `#[label("...")]` before the identity.
+    LabeledIdentity(SourceRef, String, Box<PilStatement>),
 }
 
 impl PilStatement {
@@ -86,72 +97,98 @@ impl PilStatement {
             | PilStatement::PlookupIdentity(_, _, _)
             | PilStatement::PermutationIdentity(_, _, _)
             | PilStatement::ConnectIdentity(_, _, _)
-            | PilStatement::Expression(_, _) => Box::new(empty()),
+            | PilStatement::Expression(_, _)
+            | PilStatement::LabeledIdentity(_, _, _) => Box::new(empty()),
+        }
+    }
+
+    /// Returns the location of the statement in the source.
+    pub fn source(&self) -> &SourceRef {
+        match self {
+            PilStatement::Include(source, _)
+            | PilStatement::Namespace(source, _, _)
+            | PilStatement::LetStatement(source, _, _, _)
+            | PilStatement::PolynomialDefinition(source, _, _)
+            | PilStatement::PublicDeclaration(source, _, _, _, _)
+            | PilStatement::PolynomialConstantDeclaration(source, _)
+            | PilStatement::PolynomialConstantDefinition(source, _, _)
+            | PilStatement::PolynomialCommitDeclaration(source, _, _)
+            | PilStatement::PlookupIdentity(source, _, _)
+            | PilStatement::PermutationIdentity(source, _, _)
+            | PilStatement::ConnectIdentity(source, _, _)
+            | PilStatement::ConstantDefinition(source, _, _)
+            | PilStatement::Expression(source, _)
+            | PilStatement::LabeledIdentity(source, _, _) => source,
         }
     }
 
     /// Returns an iterator over all (top-level) expressions in this statement.
-    pub fn expressions(&self) -> Box<dyn Iterator<Item = &Expression> + '_> {
+    pub fn expressions(&self) -> Children<'_, Expression> {
         match self {
             PilStatement::PlookupIdentity(_, left, right)
-            | PilStatement::PermutationIdentity(_, left, right) => {
-                Box::new(left.expressions().chain(right.expressions()))
-            }
-            PilStatement::ConnectIdentity(_start, left, right) => {
-                Box::new(left.iter().chain(right.iter()))
-            }
+            | PilStatement::PermutationIdentity(_, left, right) => Children::new([
+                option_slice(&left.selector),
+                &left.expressions,
+                option_slice(&right.selector),
+                &right.expressions,
+            ]),
+            PilStatement::ConnectIdentity(_start, left, right) => Children::new([left, right]),
             PilStatement::Expression(_, e)
             | PilStatement::Namespace(_, _, e)
             | PilStatement::PolynomialDefinition(_, _, e)
-            | PilStatement::ConstantDefinition(_, _, e) => Box::new(once(e)),
+            | PilStatement::ConstantDefinition(_, _, e) => Children::new([slice::from_ref(e)]),
 
-            PilStatement::LetStatement(_, _, type_scheme, value) => Box::new(
-                type_scheme
-                    .iter()
-                    .flat_map(|t| t.ty.expressions())
-                    .chain(value),
-            ),
+            PilStatement::LetStatement(_, _, type_scheme, value) => type_scheme
+                .as_ref()
+                .map_or_else(Children::empty, |t| t.ty.expressions())
+                .chain(Children::new([option_slice(value)])),
 
-            PilStatement::PublicDeclaration(_, _, _, i, e) => Box::new(i.iter().chain(once(e))),
+            PilStatement::PublicDeclaration(_, _, _, i, e) => {
+                Children::new([option_slice(i), slice::from_ref(e)])
+            }
 
             PilStatement::PolynomialConstantDefinition(_, _, fundef)
             | PilStatement::PolynomialCommitDeclaration(_, _, Some(fundef)) => fundef.expressions(),
+            PilStatement::LabeledIdentity(_, _, identity) => identity.expressions(),
             PilStatement::PolynomialCommitDeclaration(_, _, None)
             | PilStatement::Include(_, _)
-            | PilStatement::PolynomialConstantDeclaration(_, _) => Box::new(empty()),
+            | PilStatement::PolynomialConstantDeclaration(_, _) => Children::empty(),
         }
     }
 
     /// Returns an iterator over all (top-level) expressions in this statement.
-    pub fn expressions_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression> + '_> {
+    pub fn expressions_mut(&mut self) -> ChildrenMut<'_, Expression> {
         match self {
             PilStatement::PlookupIdentity(_, left, right)
-            | PilStatement::PermutationIdentity(_, left, right) => {
-                Box::new(left.expressions_mut().chain(right.expressions_mut()))
-            }
-            PilStatement::ConnectIdentity(_start, left, right) => {
-                Box::new(left.iter_mut().chain(right.iter_mut()))
-            }
+            | PilStatement::PermutationIdentity(_, left, right) => ChildrenMut::new([
+                option_slice_mut(&mut left.selector),
+                &mut left.expressions,
+                option_slice_mut(&mut right.selector),
+                &mut right.expressions,
+            ]),
+            PilStatement::ConnectIdentity(_start, left, right) => ChildrenMut::new([left, right]),
             PilStatement::Expression(_, e)
             | PilStatement::Namespace(_, _, e)
             | PilStatement::PolynomialDefinition(_, _, e)
-            | PilStatement::ConstantDefinition(_, _, e) => Box::new(once(e)),
+            | PilStatement::ConstantDefinition(_, _, e) => ChildrenMut::new([slice::from_mut(e)]),
 
-            PilStatement::LetStatement(_, _, ty, value) => Box::new(
-                ty.iter_mut()
-                    .flat_map(|t| t.ty.expressions_mut())
-                    .chain(value),
-            ),
+            PilStatement::LetStatement(_, _, ty, value) => ty
+                .as_mut()
+                .map_or_else(ChildrenMut::empty, |t| t.ty.expressions_mut())
+                .chain(ChildrenMut::new([option_slice_mut(value)])),
 
-            PilStatement::PublicDeclaration(_, _, _, i, e) => Box::new(i.iter_mut().chain(once(e))),
+            PilStatement::PublicDeclaration(_, _, _, i, e) => {
+                ChildrenMut::new([option_slice_mut(i), slice::from_mut(e)])
+            }
 
             PilStatement::PolynomialConstantDefinition(_, _, fundef)
             | PilStatement::PolynomialCommitDeclaration(_, _, Some(fundef)) => {
                 fundef.expressions_mut()
             }
+            PilStatement::LabeledIdentity(_, _, identity) => identity.expressions_mut(),
             PilStatement::PolynomialCommitDeclaration(_, _, None)
             | PilStatement::Include(_, _)
-            | PilStatement::PolynomialConstantDeclaration(_, _) => Box::new(empty()),
+            | PilStatement::PolynomialConstantDeclaration(_, _) => ChildrenMut::empty(),
         }
     }
 }
@@ -187,8 +224,12 @@ impl<Expr> SelectedExpressions<Expr> {
 pub enum Expression<Ref = NamespacedPolynomialReference> {
     Reference(Ref),
     PublicReference(String),
-    // A number literal and its type.
-    Number(#[schemars(skip)] BigUint, Option<Type>),
+    // A number literal, its type and the radix it was written in.
+    Number(
+        #[schemars(with = "Vec<u64>")] BigUint,
+        Option<LiteralType>,
+        Radix,
+    ),
     String(String),
     Tuple(Vec<Expression<Ref>>),
     LambdaExpression(LambdaExpression<Ref>),
@@ -200,6 +241,42 @@ pub enum Expression<Ref = NamespacedPolynomialReference> {
     FreeInput(Box<Expression<Ref>>),
     MatchExpression(Box<Expression<Ref>>, Vec<MatchArm<Ref>>),
     IfExpression(IfExpression<Ref>),
+    BlockExpression(BlockExpression<Ref>),
+}
+
+/// The type of a number literal.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub enum LiteralType {
+    /// The type was given as a suffix of the literal, like in `5_fe`.
+    Explicit(Type),
+    /// The type was determined by type inference.
+    Inferred(Type),
+}
+
+/// The radix a number literal was written in, like `0xff` or `0b1010`.
+/// It only affects how the literal is displayed, but literals with the same value
+/// and different radixes are still different expressions, so compare the values
+/// of `Expression::Number` where only the value matters.
+#[derive(
+    Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize, JsonSchema,
+)]
+pub enum Radix {
+    #[default]
+    Decimal,
+    Hexadecimal,
+    Binary,
+}
+
+impl LiteralType {
+    pub fn ty(&self) -> &Type {
+        match self {
+            LiteralType::Explicit(ty) | LiteralType::Inferred(ty) => ty,
+        }
+    }
+
+    pub fn is_explicit(&self) -> bool {
+        matches!(self, LiteralType::Explicit(_))
+    }
 }
 
 impl<Ref> Expression<Ref> {
@@ -225,13 +302,13 @@ impl<Ref> Expression<Ref> {
 
 impl From<u32> for Expression {
     fn from(value: u32) -> Self {
-        Expression::Number(value.into(), None)
+        Expression::Number(value.into(), None, Radix::Decimal)
     }
 }
 
 impl From<BigUint> for Expression {
     fn from(value: BigUint) -> Self {
-        Expression::Number(value, None)
+        Expression::Number(value, None, Radix::Decimal)
     }
 }
 
@@ -261,7 +338,7 @@ impl<Ref> ops::Mul for Expression<Ref> {
 impl<Ref> std::iter::Sum for Expression<Ref> {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
         iter.reduce(|a, b| a + b)
-            .unwrap_or_else(|| Expression::Number(0u32.into(), None))
+            .unwrap_or_else(|| Expression::Number(0u32.into(), None, Radix::Decimal))
     }
 }
 
@@ -271,13 +348,17 @@ impl From<NamespacedPolynomialReference> for Expression {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone)]
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Default, Clone, Serialize, Deserialize, JsonSchema,
+)]
 pub struct PolynomialName {
     pub name: String,
     pub array_size: Option<Expression>,
 }
 
-#[derive(Debug, PartialEq, Eq, Default, Clone, PartialOrd, Ord)]
+#[derive(
+    Debug, PartialEq, Eq, Default, Clone, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 /// A polynomial with an optional namespace
 /// This is different from SymbolPath mainly due to different formatting.
 pub struct NamespacedPolynomialReference {
@@ -370,7 +451,11 @@ pub struct FunctionCall<Ref = NamespacedPolynomialReference> {
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MatchArm<Ref = NamespacedPolynomialReference> {
-    pub pattern: MatchPattern<Ref>,
+    /// The alternatives of the pattern, written as `p1 | p2 | ...`.
+    /// The arm matches if any of them matches, they are tried from left to right.
+    /// Patterns do not bind variables yet, but once they do, all alternatives
+    /// have to bind the same variables, see `MatchPattern::bound_variables`.
+    pub patterns: Vec<MatchPattern<Ref>>,
     pub value: Expression<Ref>,
 }
 
@@ -379,6 +464,32 @@ pub struct MatchArm<Ref = NamespacedPolynomialReference> {
 pub enum MatchPattern<Ref = NamespacedPolynomialReference> {
     CatchAll,
     Pattern(Expression<Ref>),
+    /// A range of integers from the first to the second expression, which are
+    /// (possibly negated) number literals. The range is never empty.
+    Range(Expression<Ref>, Expression<Ref>, RangeKind),
+}
+
+impl<Ref> MatchPattern<Ref> {
+    /// Returns the names of the variables bound by the pattern.
+    /// None of the current kinds of patterns binds variables.
+    pub fn bound_variables(&self) -> BTreeSet<&str> {
+        match self {
+            MatchPattern::CatchAll | MatchPattern::Pattern(_) | MatchPattern::Range(..) => {
+                BTreeSet::new()
+            }
+        }
+    }
+}
+
+/// Whether the end of a range pattern is part of the range.
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize, JsonSchema,
+)]
+pub enum RangeKind {
+    /// `start..end`, matching values `v` with `start <= v < end`.
+    Exclusive,
+    /// `start..=end`, matching values `v` with `start <= v <= end`.
+    Inclusive,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
@@ -388,11 +499,28 @@ pub struct IfExpression<Ref = NamespacedPolynomialReference> {
     pub else_body: Box<Expression<Ref>>,
 }
 
+/// A block of local `let` statements followed by an expression, like `{ let x = i * i; x + 1 }`.
+/// Each statement can reference the variables bound before it, the final expression all of them.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BlockExpression<Ref = NamespacedPolynomialReference> {
+    pub statements: Vec<LetStatementInsideBlock<Ref>>,
+    pub expr: Box<Expression<Ref>>,
+}
+
+/// A statement `let name = value;` inside a block expression.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LetStatementInsideBlock<Ref = NamespacedPolynomialReference> {
+    pub name: String,
+    pub value: Expression<Ref>,
+}
+
 /// The definition of a function (excluding its name):
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum FunctionDefinition {
     /// Array expression.
     Array(ArrayExpression),
+    /// Values at specific rows and a default value for all other rows.
+    Sparse(SparseArrayExpression),
     /// Prover query. The Expression usually is a LambdaExpression.
     Query(Expression),
     /// Generic expression
@@ -401,23 +529,60 @@ pub enum FunctionDefinition {
 
 impl FunctionDefinition {
     /// Returns an iterator over all (top-level) expressions.
-    pub fn expressions(&self) -> Box<dyn Iterator<Item = &Expression> + '_> {
+    pub fn expressions(&self) -> Children<'_, Expression> {
         match self {
             FunctionDefinition::Array(ae) => ae.expressions(),
-            FunctionDefinition::Query(e) | FunctionDefinition::Expression(e) => Box::new(once(e)),
+            FunctionDefinition::Sparse(se) => se.expressions(),
+            FunctionDefinition::Query(e) | FunctionDefinition::Expression(e) => {
+                Children::new([slice::from_ref(e)])
+            }
         }
     }
 
     /// Returns an iterator over all (top-level) expressions.
-    pub fn expressions_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression> + '_> {
+    pub fn expressions_mut(&mut self) -> ChildrenMut<'_, Expression> {
         match self {
             FunctionDefinition::Array(ae) => ae.expressions_mut(),
-            FunctionDefinition::Query(e) | FunctionDefinition::Expression(e) => Box::new(once(e)),
+            FunctionDefinition::Sparse(se) => se.expressions_mut(),
+            FunctionDefinition::Query(e) | FunctionDefinition::Expression(e) => {
+                ChildrenMut::new([slice::from_mut(e)])
+            }
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+/// A fixed column given by `{ row: value, ..., _: default }`, where the rows are
+/// constant expressions and all rows that are not listed have the default value.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SparseArrayExpression {
+    /// Pairs of row and value, in source order.
+    pub entries: Vec<(Expression, Expression)>,
+    pub default: Expression,
+}
+
+impl SparseArrayExpression {
+    /// Returns an iterator over all (top-level) expressions, i.e. the rows
+    /// and values of all entries followed by the default value.
+    pub fn expressions(&self) -> Children<'_, Expression> {
+        self.entries
+            .iter()
+            .flat_map(|(row, value)| [row, value])
+            .chain(once(&self.default))
+            .collect()
+    }
+
+    /// Returns an iterator over all (top-level) expressions, i.e. the rows
+    /// and values of all entries followed by the default value.
+    pub fn expressions_mut(&mut self) -> ChildrenMut<'_, Expression> {
+        self.entries
+            .iter_mut()
+            .flat_map(|(row, value)| [row, value])
+            .chain(once(&mut self.default))
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum ArrayExpression {
     Value(Vec<Expression>),
     RepeatedValue(Vec<Expression>),
@@ -442,7 +607,7 @@ impl ArrayExpression {
     }
 
     pub fn pad_with_zeroes(self) -> Self {
-        self.pad_with(Expression::Number(0u32.into(), None))
+        self.pad_with(Expression::Number(0u32.into(), None, Radix::Decimal))
     }
 
     fn last(&self) -> Option<&Expression> {
@@ -461,36 +626,34 @@ impl ArrayExpression {
 
 impl ArrayExpression {
     /// solve for `*`
-    pub fn solve(&self, degree: DegreeType) -> DegreeType {
-        assert!(
-            self.number_of_repetitions() <= 1,
-            "`*` can be used only once in rhs of array definition"
-        );
+    pub fn solve(&self, degree: DegreeType) -> Result<DegreeType, String> {
+        if self.number_of_repetitions() > 1 {
+            return Err("`*` can be used only once in rhs of array definition".to_string());
+        }
         let len = self.constant_length();
-        assert!(
-            len <= degree,
-            "Array literal is too large ({len}) for degree ({degree})."
-        );
+        if len > degree {
+            return Err(format!(
+                "Array literal is too large ({len}) for degree ({degree})."
+            ));
+        }
         // Fill up the remaining space with the repeated array
-        degree - len
+        Ok(degree - len)
     }
 
     /// Returns an iterator over all (top-level) expressions.
-    pub fn expressions(&self) -> Box<dyn Iterator<Item = &Expression> + '_> {
+    pub fn expressions(&self) -> Children<'_, Expression> {
         match self {
-            ArrayExpression::Value(v) | ArrayExpression::RepeatedValue(v) => Box::new(v.iter()),
-            ArrayExpression::Concat(left, right) => {
-                Box::new(left.expressions().chain(right.expressions()))
-            }
+            ArrayExpression::Value(v) | ArrayExpression::RepeatedValue(v) => Children::new([v]),
+            ArrayExpression::Concat(left, right) => left.expressions().chain(right.expressions()),
         }
     }
 
     /// Returns all (top-level) expressions.
-    pub fn expressions_mut(&mut self) -> Box<dyn Iterator<Item = &mut Expression> + '_> {
+    pub fn expressions_mut(&mut self) -> ChildrenMut<'_, Expression> {
         match self {
-            ArrayExpression::Value(v) | ArrayExpression::RepeatedValue(v) => Box::new(v.iter_mut()),
+            ArrayExpression::Value(v) | ArrayExpression::RepeatedValue(v) => ChildrenMut::new([v]),
             ArrayExpression::Concat(left, right) => {
-                Box::new(left.expressions_mut().chain(right.expressions_mut()))
+                left.expressions_mut().chain(right.expressions_mut())
             }
         }
     }
@@ -1,14 +1,16 @@
 use std::{
     collections::{BTreeSet, HashMap},
     fmt::Display,
-    iter::empty,
 };
 
 use itertools::Itertools;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::Expression;
+use super::{
+    children::{option_slice, option_slice_mut, Children, ChildrenMut},
+    Expression,
+};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum Type<E = u64> {
@@ -63,10 +65,10 @@ impl<E> Type<E> {
     }
 
     /// Returns an iterator over all (top-level) expressions in this type name.
-    pub fn expressions(&self) -> Box<dyn Iterator<Item = &E> + '_> {
+    pub fn expressions(&self) -> Children<'_, E> {
         match self {
-            _ if self.is_elementary() => Box::new(empty()),
-            Type::TypeVar(_) => Box::new(empty()),
+            _ if self.is_elementary() => Children::empty(),
+            Type::TypeVar(_) => Children::empty(),
             Type::Array(a) => a.expressions(),
             Type::Tuple(t) => t.expressions(),
             Type::Function(f) => f.expressions(),
@@ -75,10 +77,10 @@ impl<E> Type<E> {
     }
 
     /// Returns an iterator over all (top-level) expressions in this type name.
-    pub fn expressions_mut(&mut self) -> Box<dyn Iterator<Item = &mut E> + '_> {
+    pub fn expressions_mut(&mut self) -> ChildrenMut<'_, E> {
         match self {
-            _ if self.is_elementary() => Box::new(empty()),
-            Type::TypeVar(_) => Box::new(empty()),
+            _ if self.is_elementary() => ChildrenMut::empty(),
+            Type::TypeVar(_) => ChildrenMut::empty(),
             Type::Array(a) => a.expressions_mut(),
             Type::Tuple(t) => t.expressions_mut(),
             Type::Function(f) => f.expressions_mut(),
@@ -187,19 +189,23 @@ pub struct ArrayType<E = u64> {
 
 impl<E> ArrayType<E> {
     /// Returns an iterator over all (top-level) expressions in this type name.
-    pub fn expressions(&self) -> Box<dyn Iterator<Item = &E> + '_> {
-        Box::new(self.base.expressions().chain(self.length.iter()))
+    pub fn expressions(&self) -> Children<'_, E> {
+        self.base
+            .expressions()
+            .chain(Children::new([option_slice(&self.length)]))
     }
     /// Returns an iterator over all (top-level) expressions in this type name.
-    pub fn expressions_mut(&mut self) -> Box<dyn Iterator<Item = &mut E> + '_> {
-        Box::new(self.base.expressions_mut().chain(self.length.iter_mut()))
+    pub fn expressions_mut(&mut self) -> ChildrenMut<'_, E> {
+        self.base
+            .expressions_mut()
+            .chain(ChildrenMut::new([option_slice_mut(&mut self.length)]))
     }
 }
 
 impl<R: Display> From<ArrayType<Expression<R>>> for ArrayType<u64> {
     fn from(value: ArrayType<Expression<R>>) -> Self {
         let length = value.length.as_ref().map(|l| {
-            if let Expression::Number(n, ty) = l {
+            if let Expression::Number(n, ty, _) = l {
                 assert!(ty.is_none(), "Literal inside type name has assigned type. This should be done during analysis on the types instead.");
                 n.try_into().expect("Array length expression too large.")
             } else {
@@ -222,12 +228,16 @@ pub struct TupleType<E = u64> {
 
 impl<E> TupleType<E> {
     /// Returns an iterator over all (top-level) expressions in this type name.
-    pub fn expressions(&self) -> Box<dyn Iterator<Item = &E> + '_> {
-        Box::new(self.items.iter().flat_map(|t| t.expressions()))
+    pub fn expressions(&self) -> Children<'_, E> {
+        self.items
+            .iter()
+            .fold(Children::empty(), |acc, t| acc.chain(t.expressions()))
     }
     /// Returns an iterator over all (top-level) expressions in this type name.
-    pub fn expressions_mut(&mut self) -> Box<dyn Iterator<Item = &mut E> + '_> {
-        Box::new(self.items.iter_mut().flat_map(|t| t.expressions_mut()))
+    pub fn expressions_mut(&mut self) -> ChildrenMut<'_, E> {
+        self.items.iter_mut().fold(ChildrenMut::empty(), |acc, t| {
+            acc.chain(t.expressions_mut())
+        })
     }
 }
 
@@ -247,22 +257,20 @@ pub struct FunctionType<E = u64> {
 
 impl<E> FunctionType<E> {
     /// Returns an iterator over all (top-level) expressions in this type name.
-    pub fn expressions(&self) -> Box<dyn Iterator<Item = &E> + '_> {
-        Box::new(
-            self.params
-                .iter()
-                .flat_map(|t| t.expressions())
-                .chain(self.value.expressions()),
-        )
+    pub fn expressions(&self) -> Children<'_, E> {
+        self.params
+            .iter()
+            .fold(Children::empty(), |acc, t| acc.chain(t.expressions()))
+            .chain(self.value.expressions())
     }
     /// Returns an iterator over all (top-level) expressions in this type name.
-    pub fn expressions_mut(&mut self) -> Box<dyn Iterator<Item = &mut E> + '_> {
-        Box::new(
-            self.params
-                .iter_mut()
-                .flat_map(|t| t.expressions_mut())
-                .chain(self.value.expressions_mut()),
-        )
+    pub fn expressions_mut(&mut self) -> ChildrenMut<'_, E> {
+        self.params
+            .iter_mut()
+            .fold(ChildrenMut::empty(), |acc, t| {
+                acc.chain(t.expressions_mut())
+            })
+            .chain(self.value.expressions_mut())
     }
 }
 
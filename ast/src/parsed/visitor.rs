@@ -2,9 +2,9 @@ use std::{iter::once, ops::ControlFlow};
 
 use super::{
     types::{ArrayType, FunctionType, TupleType, Type},
-    ArrayExpression, ArrayLiteral, Expression, FunctionCall, FunctionDefinition, IfExpression,
-    IndexAccess, LambdaExpression, MatchArm, MatchPattern, NamespacedPolynomialReference,
-    PilStatement, SelectedExpressions,
+    ArrayExpression, ArrayLiteral, BlockExpression, Expression, FunctionCall, FunctionDefinition,
+    IfExpression, IndexAccess, LambdaExpression, MatchArm, MatchPattern,
+    NamespacedPolynomialReference, PilStatement, SelectedExpressions, SparseArrayExpression,
 };
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -117,7 +117,7 @@ impl<Ref> ExpressionVisitable<Expression<Ref>> for Expression<Ref> {
         match self {
             Expression::Reference(_)
             | Expression::PublicReference(_)
-            | Expression::Number(_, _)
+            | Expression::Number(..)
             | Expression::String(_) => {}
             Expression::BinaryOperation(left, _, right) => {
                 left.visit_expressions_mut(f, o)?;
@@ -139,6 +139,7 @@ impl<Ref> ExpressionVisitable<Expression<Ref>> for Expression<Ref> {
                     .try_for_each(|arm| arm.visit_expressions_mut(f, o))?;
             }
             Expression::IfExpression(if_expr) => if_expr.visit_expressions_mut(f, o)?,
+            Expression::BlockExpression(block) => block.visit_expressions_mut(f, o)?,
         };
         if o == VisitOrder::Post {
             f(self)?;
@@ -156,7 +157,7 @@ impl<Ref> ExpressionVisitable<Expression<Ref>> for Expression<Ref> {
         match self {
             Expression::Reference(_)
             | Expression::PublicReference(_)
-            | Expression::Number(_, _)
+            | Expression::Number(..)
             | Expression::String(_) => {}
             Expression::BinaryOperation(left, _, right) => {
                 left.visit_expressions(f, o)?;
@@ -178,6 +179,7 @@ impl<Ref> ExpressionVisitable<Expression<Ref>> for Expression<Ref> {
                     .try_for_each(|arm| arm.visit_expressions(f, o))?;
             }
             Expression::IfExpression(if_expr) => if_expr.visit_expressions(f, o)?,
+            Expression::BlockExpression(block) => block.visit_expressions(f, o)?,
         };
         if o == VisitOrder::Post {
             f(self)?;
@@ -225,6 +227,7 @@ impl ExpressionVisitable<Expression<NamespacedPolynomialReference>> for PilState
             | PilStatement::PolynomialCommitDeclaration(_, _, Some(fundef)) => {
                 fundef.visit_expressions_mut(f, o)
             }
+            PilStatement::LabeledIdentity(_, _, identity) => identity.visit_expressions_mut(f, o),
             PilStatement::PolynomialCommitDeclaration(_, _, None)
             | PilStatement::Include(_, _)
             | PilStatement::PolynomialConstantDeclaration(_, _) => ControlFlow::Continue(()),
@@ -269,6 +272,7 @@ impl ExpressionVisitable<Expression<NamespacedPolynomialReference>> for PilState
             | PilStatement::PolynomialCommitDeclaration(_, _, Some(fundef)) => {
                 fundef.visit_expressions(f, o)
             }
+            PilStatement::LabeledIdentity(_, _, identity) => identity.visit_expressions(f, o),
             PilStatement::PolynomialCommitDeclaration(_, _, None)
             | PilStatement::Include(_, _)
             | PilStatement::PolynomialConstantDeclaration(_, _) => ControlFlow::Continue(()),
@@ -310,6 +314,7 @@ impl ExpressionVisitable<Expression> for FunctionDefinition {
                 e.visit_expressions_mut(f, o)
             }
             FunctionDefinition::Array(ae) => ae.visit_expressions_mut(f, o),
+            FunctionDefinition::Sparse(se) => se.visit_expressions_mut(f, o),
         }
     }
 
@@ -322,6 +327,7 @@ impl ExpressionVisitable<Expression> for FunctionDefinition {
                 e.visit_expressions(f, o)
             }
             FunctionDefinition::Array(ae) => ae.visit_expressions(f, o),
+            FunctionDefinition::Sparse(se) => se.visit_expressions(f, o),
         }
     }
 }
@@ -360,6 +366,24 @@ impl ExpressionVisitable<Expression> for ArrayExpression {
     }
 }
 
+impl ExpressionVisitable<Expression> for SparseArrayExpression {
+    fn visit_expressions_mut<F, B>(&mut self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&mut Expression) -> ControlFlow<B>,
+    {
+        self.expressions_mut()
+            .try_for_each(|e| e.visit_expressions_mut(f, o))
+    }
+
+    fn visit_expressions<F, B>(&self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&Expression) -> ControlFlow<B>,
+    {
+        self.expressions()
+            .try_for_each(|e| e.visit_expressions(f, o))
+    }
+}
+
 impl<Ref> ExpressionVisitable<Expression<Ref>> for LambdaExpression<Ref> {
     fn visit_expressions_mut<F, B>(&mut self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
     where
@@ -439,7 +463,9 @@ impl<Ref> ExpressionVisitable<Expression<Ref>> for MatchArm<Ref> {
     where
         F: FnMut(&mut Expression<Ref>) -> ControlFlow<B>,
     {
-        self.pattern.visit_expressions_mut(f, o)?;
+        self.patterns
+            .iter_mut()
+            .try_for_each(|p| p.visit_expressions_mut(f, o))?;
         self.value.visit_expressions_mut(f, o)
     }
 
@@ -447,7 +473,9 @@ impl<Ref> ExpressionVisitable<Expression<Ref>> for MatchArm<Ref> {
     where
         F: FnMut(&Expression<Ref>) -> ControlFlow<B>,
     {
-        self.pattern.visit_expressions(f, o)?;
+        self.patterns
+            .iter()
+            .try_for_each(|p| p.visit_expressions(f, o))?;
         self.value.visit_expressions(f, o)
     }
 }
@@ -460,6 +488,10 @@ impl<Ref> ExpressionVisitable<Expression<Ref>> for MatchPattern<Ref> {
         match self {
             MatchPattern::CatchAll => ControlFlow::Continue(()),
             MatchPattern::Pattern(e) => e.visit_expressions_mut(f, o),
+            MatchPattern::Range(start, end, _) => {
+                start.visit_expressions_mut(f, o)?;
+                end.visit_expressions_mut(f, o)
+            }
         }
     }
 
@@ -470,6 +502,10 @@ impl<Ref> ExpressionVisitable<Expression<Ref>> for MatchPattern<Ref> {
         match self {
             MatchPattern::CatchAll => ControlFlow::Continue(()),
             MatchPattern::Pattern(e) => e.visit_expressions(f, o),
+            MatchPattern::Range(start, end, _) => {
+                start.visit_expressions(f, o)?;
+                end.visit_expressions(f, o)
+            }
         }
     }
 }
@@ -494,6 +530,30 @@ impl<Ref> ExpressionVisitable<Expression<Ref>> for IfExpression<Ref> {
     }
 }
 
+impl<Ref> ExpressionVisitable<Expression<Ref>> for BlockExpression<Ref> {
+    fn visit_expressions_mut<F, B>(&mut self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&mut Expression<Ref>) -> ControlFlow<B>,
+    {
+        self.statements
+            .iter_mut()
+            .map(|s| &mut s.value)
+            .chain(once(self.expr.as_mut()))
+            .try_for_each(|e| e.visit_expressions_mut(f, o))
+    }
+
+    fn visit_expressions<F, B>(&self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
+    where
+        F: FnMut(&Expression<Ref>) -> ControlFlow<B>,
+    {
+        self.statements
+            .iter()
+            .map(|s| &s.value)
+            .chain(once(self.expr.as_ref()))
+            .try_for_each(|e| e.visit_expressions(f, o))
+    }
+}
+
 impl<E: ExpressionVisitable<E>> ExpressionVisitable<E> for Type<E> {
     fn visit_expressions_mut<F, B>(&mut self, f: &mut F, o: VisitOrder) -> ControlFlow<B>
     where
@@ -9,6 +9,8 @@ use powdr_number::{DegreeType, FieldElement};
 use std::{io, path::Path};
 use strum::{Display, EnumString, EnumVariantNames};
 
+pub use pilstark::json_importer::{import_pilcom, import_pilcom_json, ImportError};
+
 #[derive(Clone, EnumString, EnumVariantNames, Display, Copy)]
 pub enum BackendType {
     #[cfg(feature = "halo2")]
@@ -86,7 +86,7 @@ impl<'a, F: FieldElement> Backend<'a, F> for EStark<'a, F> {
 
         let degree = self.pil.degree();
 
-        let mut pil: PIL = pilstark::json_exporter::export(self.pil);
+        let mut pil: PIL = pilstark::json_exporter::export(self.pil).map_err(|e| e.to_string())?;
 
         // TODO starky requires a fixed column with the equivalent
         // semantics to Polygon zkEVM's `L1` column.
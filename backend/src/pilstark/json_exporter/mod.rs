@@ -1,4 +1,20 @@
-use powdr_number::FieldElement;
+//! Exports analyzed PIL to the JSON format of pilcom (`*.pil.json`), which is used by
+//! eSTARK and other tooling of Polygon zkEVM.
+//!
+//! Witness, fixed and intermediate columns are exported as references with their ids
+//! and the common degree, and each identity kind goes to its own section, with all
+//! expressions stored in one list and referenced by index.
+//!
+//! Some PIL files cannot be represented in this format, which results in an [`ExportError`]:
+//! - columns in files without a namespace, since pilcom needs the degree of all columns,
+//! - columns outside of a namespace, since pilcom names have the form `namespace.name`,
+//! - exponentiation with non-constant operands, which pilcom does not support.
+//!
+//! Per-namespace degrees and challenges need no check here, because the analyzed file
+//! has a single degree (the analyzer rejects namespaces with differing degrees)
+//! and there are no challenges in analyzed PIL.
+
+use powdr_number::{DegreeType, FieldElement};
 use std::collections::HashMap;
 use std::{cmp, path::PathBuf};
 
@@ -27,8 +43,23 @@ const DEFAULT_EXPR: StarkyExpr = StarkyExpr {
     idQ: None,
     const_: None,
 };
+
+/// A construct of the analyzed file that cannot be represented in the pilcom format.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ExportError {
+    #[error(
+        "The PIL file has columns but no namespace, and pilcom requires a degree for all columns."
+    )]
+    MissingDegree,
+    #[error("Column {0} is not inside a namespace, but pilcom requires names of the form namespace.name.")]
+    ColumnOutsideNamespace(String),
+    #[error("Exponentiation can only be used on constants, but found {0}.")]
+    NonConstantExponent(String),
+}
+
 struct Exporter<'a, T> {
     analyzed: &'a Analyzed<T>,
+    degree: Option<DegreeType>,
     expressions: Vec<StarkyExpr>,
     /// Translates from polynomial IDs to expression IDs for intermediate
     /// polynomials.
@@ -36,7 +67,7 @@ struct Exporter<'a, T> {
     number_q: u64,
 }
 
-pub fn export<T: FieldElement>(analyzed: &Analyzed<T>) -> PIL {
+pub fn export<T: FieldElement>(analyzed: &Analyzed<T>) -> Result<PIL, ExportError> {
     let mut exporter = Exporter::new(analyzed);
     let mut publics = Vec::new();
     let mut pol_identities = Vec::new();
@@ -49,7 +80,7 @@ pub fn export<T: FieldElement>(analyzed: &Analyzed<T>) -> PIL {
                 if let Some((poly, value)) = analyzed.intermediate_columns.get(name) {
                     assert_eq!(poly.kind, SymbolKind::Poly(PolynomialType::Intermediate));
                     for ((_, id), value) in poly.array_elements().zip(value) {
-                        let expression_id = exporter.extract_expression(value, 1);
+                        let expression_id = exporter.extract_expression(value, 1)?;
                         assert_eq!(
                             expression_id,
                             exporter.intermediate_poly_expression_ids[&id.id] as usize
@@ -74,7 +105,7 @@ pub fn export<T: FieldElement>(analyzed: &Analyzed<T>) -> PIL {
                     polId: expr.id.unwrap(),
                     idx: pub_def.index as usize,
                     id,
-                    name: name.clone(),
+                    name: name.to_dotted_string(),
                 });
             }
             StatementIdentifier::Identity(id) => {
@@ -97,11 +128,11 @@ pub fn export<T: FieldElement>(analyzed: &Analyzed<T>) -> PIL {
                 } else {
                     1
                 };
-                let left = exporter.extract_expression_vec(&identity.left.expressions, 1);
+                let left = exporter.extract_expression_vec(&identity.left.expressions, 1)?;
                 let sel_left =
-                    exporter.extract_expression_opt(&identity.left.selector, selector_degree);
-                let right = exporter.extract_expression_vec(&identity.right.expressions, 1);
-                let sel_right = exporter.extract_expression_opt(&identity.right.selector, 1);
+                    exporter.extract_expression_opt(&identity.left.selector, selector_degree)?;
+                let right = exporter.extract_expression_vec(&identity.right.expressions, 1)?;
+                let sel_right = exporter.extract_expression_opt(&identity.right.selector, 1)?;
                 match identity.kind {
                     IdentityKind::Polynomial => pol_identities.push(PolIdentity {
                         e: sel_left.unwrap(),
@@ -140,13 +171,13 @@ pub fn export<T: FieldElement>(analyzed: &Analyzed<T>) -> PIL {
             }
         }
     }
-    PIL {
+    Ok(PIL {
         nCommitments: analyzed.commitment_count(),
         nQ: exporter.number_q as usize,
         nIm: analyzed.intermediate_count(),
         nConstants: analyzed.constant_count(),
         publics,
-        references: exporter.references(),
+        references: exporter.references()?,
         expressions: exporter.expressions,
         polIdentities: pol_identities,
         plookupIdentities: plookup_identities,
@@ -154,7 +185,7 @@ pub fn export<T: FieldElement>(analyzed: &Analyzed<T>) -> PIL {
         connectionIdentities: Some(connection_identities),
         cm_dims: Vec::new(),
         q2exp: Vec::new(),
-    }
+    })
 }
 
 fn symbol_kind_to_json_string(k: SymbolKind) -> &'static str {
@@ -188,13 +219,13 @@ fn polynomial_reference_type_to_type(t: &str) -> &'static str {
 
 /// Makes names compatible with estark, which sometimes require that
 /// there is exactly one `.` in the name.
-fn fixup_name(name: &str) -> String {
+fn fixup_name(name: &str) -> Result<String, ExportError> {
     if name.contains('.') {
-        name.to_string()
+        Ok(name.to_string())
     } else if let Some(last) = name.rfind("::") {
-        format!("{}.{}", &name[..last], &name[last + 1..])
+        Ok(format!("{}.{}", &name[..last], &name[last + 1..]))
     } else {
-        panic!("Witness or intermediate column is not inside a namespace: {name}");
+        Err(ExportError::ColumnOutsideNamespace(name.to_string()))
     }
 }
 
@@ -202,17 +233,23 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
     fn new(analyzed: &'a Analyzed<T>) -> Self {
         Self {
             analyzed,
+            degree: analyzed.degree,
             expressions: vec![],
             intermediate_poly_expression_ids: compute_intermediate_expression_ids(analyzed),
             number_q: 0,
         }
     }
 
-    fn references(&self) -> HashMap<String, Reference> {
+    /// The degree of all columns, which is only known if the file has a namespace.
+    fn degree(&self) -> Result<DegreeType, ExportError> {
+        self.degree.ok_or(ExportError::MissingDegree)
+    }
+
+    fn references(&self) -> Result<HashMap<String, Reference>, ExportError> {
         self.analyzed
             .definitions
-            .iter()
-            .filter_map(|(name, (symbol, _value))| {
+            .values()
+            .filter_map(|(symbol, _value)| {
                 let id = match symbol.kind {
                     SymbolKind::Poly(PolynomialType::Intermediate) => {
                         panic!("Should be in intermediates")
@@ -221,22 +258,24 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
                     SymbolKind::Other() | SymbolKind::Constant() => None,
                 }?;
 
-                let out = Reference {
-                    polType: None,
-                    type_: symbol_kind_to_json_string(symbol.kind).to_string(),
-                    id: id as usize,
-                    polDeg: self.analyzed.degree() as usize,
-                    isArray: symbol.is_array(),
-                    elementType: None,
-                    len: symbol.length.map(|l| l as usize),
-                };
-                Some((fixup_name(name), out))
+                Some(self.degree().and_then(|degree| {
+                    let out = Reference {
+                        polType: None,
+                        type_: symbol_kind_to_json_string(symbol.kind).to_string(),
+                        id: id as usize,
+                        polDeg: degree as usize,
+                        isArray: symbol.is_array(),
+                        elementType: None,
+                        len: symbol.length.map(|l| l as usize),
+                    };
+                    fixup_name(&symbol.absolute_name).map(|name| (name, out))
+                }))
             })
             .chain(
                 self.analyzed
                     .intermediate_columns
-                    .iter()
-                    .map(|(name, (symbol, _))| {
+                    .values()
+                    .map(|(symbol, _)| {
                         assert_eq!(symbol.kind, SymbolKind::Poly(PolynomialType::Intermediate));
                         let id = self.intermediate_poly_expression_ids[&symbol.id];
 
@@ -244,49 +283,58 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
                             polType: None,
                             type_: symbol_kind_to_json_string(symbol.kind).to_string(),
                             id: id as usize,
-                            polDeg: self.analyzed.degree() as usize,
+                            polDeg: self.degree()? as usize,
                             isArray: symbol.is_array(),
                             elementType: None,
                             len: symbol.length.map(|l| l as usize),
                         };
-                        (fixup_name(name), out)
+                        Ok((fixup_name(&symbol.absolute_name)?, out))
                     }),
             )
-            .collect::<HashMap<String, Reference>>()
+            .collect()
     }
 
     /// Processes the given expression
     /// @returns the expression ID
-    fn extract_expression(&mut self, expr: &Expression<T>, max_degree: u32) -> usize {
+    fn extract_expression(
+        &mut self,
+        expr: &Expression<T>,
+        max_degree: u32,
+    ) -> Result<usize, ExportError> {
         let id = self.expressions.len();
-        let (degree, mut expr) = self.expression_to_json(expr);
+        let (degree, mut expr) = self.expression_to_json(expr)?;
         if degree > max_degree {
             expr.idQ = Some(self.number_q as usize);
             expr.deg = 1;
             self.number_q += 1;
         }
         self.expressions.push(expr);
-        id
+        Ok(id)
     }
 
     fn extract_expression_opt(
         &mut self,
         expr: &Option<Expression<T>>,
         max_degree: u32,
-    ) -> Option<usize> {
+    ) -> Result<Option<usize>, ExportError> {
         expr.as_ref()
             .map(|e| self.extract_expression(e, max_degree))
+            .transpose()
     }
 
-    fn extract_expression_vec(&mut self, expr: &[Expression<T>], max_degree: u32) -> Vec<usize> {
+    fn extract_expression_vec(
+        &mut self,
+        expr: &[Expression<T>],
+        max_degree: u32,
+    ) -> Result<Vec<usize>, ExportError> {
         expr.iter()
             .map(|e| self.extract_expression(e, max_degree))
             .collect()
     }
 
     /// returns the degree and the JSON value (intermediate polynomial IDs)
-    fn expression_to_json(&self, expr: &Expression<T>) -> (u32, StarkyExpr) {
-        match expr {
+    fn expression_to_json(&self, expr: &Expression<T>) -> Result<(u32, StarkyExpr), ExportError> {
+        Ok(match expr {
             Expression::Reference(reference) => {
                 self.polynomial_reference_to_json(reference.poly_id, reference.next)
             }
@@ -295,7 +343,7 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
                 StarkyExpr {
                     op: "public".to_string(),
                     deg: 0,
-                    id: Some(self.analyzed.public_declarations[name].id as usize),
+                    id: Some(self.analyzed.public_declaration(name).unwrap().id as usize),
                     ..DEFAULT_EXPR
                 },
             ),
@@ -309,19 +357,17 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
                 },
             ),
             Expression::BinaryOperation(left, op, right) => {
-                let (deg_left, left) = self.expression_to_json(left);
-                let (deg_right, right) = self.expression_to_json(right);
+                let (deg_left, left) = self.expression_to_json(left)?;
+                let (deg_right, right) = self.expression_to_json(right)?;
                 let (op, degree) = match op {
                     AlgebraicBinaryOperator::Add => ("add", cmp::max(deg_left, deg_right)),
                     AlgebraicBinaryOperator::Sub => ("sub", cmp::max(deg_left, deg_right)),
                     AlgebraicBinaryOperator::Mul => ("mul", deg_left + deg_right),
                     AlgebraicBinaryOperator::Pow => {
-                        assert_eq!(
-                            deg_left + deg_right,
-                            0,
-                            "Exponentiation can only be used on constants."
-                        );
-                        ("pow", deg_left + deg_right)
+                        if deg_left + deg_right != 0 {
+                            return Err(ExportError::NonConstantExponent(expr.to_string()));
+                        }
+                        ("pow", 0)
                     }
                 };
                 (
@@ -335,7 +381,7 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
                 )
             }
             Expression::UnaryOperation(op, value) => {
-                let (deg, value) = self.expression_to_json(value);
+                let (deg, value) = self.expression_to_json(value)?;
                 match op {
                     AlgebraicUnaryOperator::Minus => (
                         deg,
@@ -348,7 +394,7 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
                     ),
                 }
             }
-        }
+        })
     }
 
     fn polynomial_reference_to_json(
@@ -374,7 +420,7 @@ impl<'a, T: FieldElement> Exporter<'a, T> {
 
 #[cfg(test)]
 mod test {
-    use powdr_pil_analyzer::analyze_file;
+    use powdr_pil_analyzer::{analyze_file, analyze_string};
     use pretty_assertions::assert_eq;
     use serde_json::Value as JsonValue;
     use std::{fs, process::Command};
@@ -395,7 +441,7 @@ mod test {
         .join(file);
 
         let analyzed = analyze_file::<GoldilocksField>(&file);
-        let pil_out = export(&analyzed);
+        let pil_out = export(&analyzed).unwrap();
 
         let pilcom = std::env::var("PILCOM").expect(
             "Please set the PILCOM environment variable to the path to the pilcom repository.",
@@ -494,6 +540,37 @@ mod test {
         assert_eq!(json_out, pilcom_parsed);
     }
 
+    #[test]
+    fn golden_fibonacci() {
+        let file = PathBuf::from(format!(
+            "{}/../test_data/pil/fibonacci.pil",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+        let analyzed = analyze_file::<GoldilocksField>(&file);
+        let json_out = serde_json::to_value(export(&analyzed).unwrap()).unwrap();
+        let golden = fs::read_to_string(file.with_extension("pil.json")).unwrap();
+        let expected: JsonValue = serde_json::from_str(&golden).unwrap();
+        assert_eq!(json_out, expected);
+    }
+
+    #[test]
+    fn unsupported_features() {
+        let export_string = |input: &str| match export(&analyze_string::<GoldilocksField>(input)) {
+            Ok(_) => panic!("Expected an export error for {input}"),
+            Err(error) => error,
+        };
+        assert_eq!(export_string("col witness x;"), ExportError::MissingDegree);
+        assert_eq!(
+            export_string("col witness x;\nnamespace T(4);\n    col witness y;\n    y = x;\n"),
+            ExportError::ColumnOutsideNamespace("x".to_string())
+        );
+        let error = export_string("namespace T(4);\n    col witness x;\n    x ** 2 = 0;\n");
+        assert_eq!(
+            error.to_string(),
+            "Exponentiation can only be used on constants, but found (T.x ** 2)."
+        );
+    }
+
     #[test]
     fn export_config() {
         compare_export_file("config.pil");
@@ -0,0 +1,766 @@
+//! Imports PIL in the JSON format of pilcom (`*.pil.json`) as analyzed PIL,
+//! which is the reverse of the [`json_exporter`](super::json_exporter).
+//!
+//! pilcom names have the form `Namespace.name`. Characters that cannot appear in powdr
+//! identifiers are replaced by `_`, and names that become equal this way are reported.
+//! Intermediate polynomials (`imP`) are referenced by the index of their defining expression
+//! and become intermediate columns, while references to other expressions are inlined.
+//! The JSON only declares fixed columns, so their values have to be provided separately.
+//!
+//! Identities and intermediate columns are ordered by the index of their first expression,
+//! so exporting an imported file reproduces the expression indices of the original export.
+
+use std::collections::{BTreeMap, HashMap};
+
+use powdr_ast::{
+    analyzed::{
+        AlgebraicBinaryOperator, AlgebraicExpression as Expression, AlgebraicReference,
+        AlgebraicUnaryOperator, Analyzed, Identity, IdentityKind, PolyID, PolynomialReference,
+        PolynomialType, PublicDeclaration, StatementIdentifier, Symbol, SymbolKind,
+    },
+    parsed::{asm::AbsoluteSymbolPath, SelectedExpressions},
+    SourceRef,
+};
+use powdr_number::{DegreeType, FieldElement};
+use starky::types::{Expression as StarkyExpr, PIL};
+
+/// A construct of a pilcom file that cannot be represented in powdr.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    #[error("Invalid pilcom JSON: {0}")]
+    InvalidJson(String),
+    #[error("Polynomial {0} is not inside a namespace.")]
+    MissingNamespace(String),
+    #[error("Polynomials {} have the same name after replacing invalid characters.", .0.join(", "))]
+    NameCollision(Vec<String>),
+    #[error("Polynomial {name} has degree {degree}, but other polynomials have degree {expected}. Namespaces with different degrees are not supported.")]
+    DegreeMismatch {
+        name: String,
+        degree: DegreeType,
+        expected: DegreeType,
+    },
+    #[error("Polynomial {name} has the unknown type {ty}.")]
+    UnknownPolynomialType { name: String, ty: String },
+    #[error("There is no polynomial of type {ty} with id {id}.")]
+    UnknownPolynomial { ty: String, id: usize },
+    #[error("There is no public with id {0}.")]
+    UnknownPublic(usize),
+    #[error("Expression {0} does not exist.")]
+    UnknownExpression(usize),
+    #[error("Expression {0} depends on itself.")]
+    CyclicExpression(usize),
+    #[error("Operation {0} is not supported.")]
+    UnsupportedOperation(String),
+    #[error("{0} is missing the field {1}.")]
+    MissingField(String, &'static str),
+    #[error("Invalid number {0}.")]
+    InvalidNumber(String),
+}
+
+/// Parses PIL in the JSON format of pilcom and converts it to analyzed PIL.
+pub fn import_pilcom_json<T: FieldElement>(json: &str) -> Result<Analyzed<T>, Vec<ImportError>> {
+    let pil: PIL =
+        serde_json::from_str(json).map_err(|e| vec![ImportError::InvalidJson(e.to_string())])?;
+    import_pilcom(&pil)
+}
+
+/// Converts PIL in the representation of pilcom to analyzed PIL.
+/// Errors are collected across all polynomials, identities and publics.
+pub fn import_pilcom<T: FieldElement>(pil: &PIL) -> Result<Analyzed<T>, Vec<ImportError>> {
+    let mut errors = vec![];
+    let columns = import_columns(pil, &mut errors);
+    let degree = import_degree(pil, &mut errors);
+    let importer = Importer::new(pil, &columns);
+
+    let mut definitions = HashMap::new();
+    let mut source_order = vec![];
+    let mut intermediate_columns = HashMap::new();
+    let mut statements = vec![];
+    for column in &columns {
+        let name = AbsoluteSymbolPath::from_dotted_string(&column.symbol.absolute_name);
+        if column.ptype == PolynomialType::Intermediate {
+            let values = (0..column.symbol.length.unwrap_or(1) as usize)
+                .map(|index| importer.root_expression::<T>(column.first + index))
+                .collect::<Result<Vec<_>, _>>();
+            match values {
+                Ok(values) => {
+                    intermediate_columns.insert(name.clone(), (column.symbol.clone(), values));
+                    statements.push((column.first, Statement::Intermediate(name)));
+                }
+                Err(error) => errors.push(error),
+            }
+        } else {
+            source_order.push(StatementIdentifier::Definition(name.clone()));
+            definitions.insert(name, (column.symbol.clone(), None));
+        }
+    }
+
+    let identities = pil
+        .polIdentities
+        .iter()
+        .map(|identity| -> Result<_, ImportError> {
+            let source = source(&identity.fileName, identity.line);
+            let expression = importer.root_expression::<T>(identity.e)?;
+            Ok((
+                identity.e,
+                Identity::from_polynomial_identity(0, source, expression),
+            ))
+        })
+        .chain(pil.plookupIdentities.iter().map(|identity| {
+            importer.selected_identity(
+                IdentityKind::Plookup,
+                source(&identity.fileName, identity.line),
+                (identity.selF, &identity.f),
+                (identity.selT, &identity.t),
+            )
+        }))
+        .chain(pil.permutationIdentities.iter().flatten().map(|identity| {
+            importer.selected_identity(
+                IdentityKind::Permutation,
+                source(&identity.fileName, identity.line),
+                (identity.selF, &identity.f),
+                (identity.selT, &identity.t),
+            )
+        }))
+        .chain(pil.connectionIdentities.iter().flatten().map(|identity| {
+            importer.selected_identity(
+                IdentityKind::Connect,
+                source(&identity.fileName, identity.line),
+                (None, &identity.pols),
+                (None, &identity.connections),
+            )
+        }));
+    for identity in identities {
+        match identity {
+            Ok((first, identity)) => statements.push((first, Statement::Identity(identity))),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    // Identity IDs are specific to the kind, so they are assigned in order per kind.
+    statements.sort_by_key(|(first, _)| *first);
+    let mut identity_counters = BTreeMap::<IdentityKind, u64>::new();
+    let mut condensed_identities = vec![];
+    for (_, statement) in statements {
+        match statement {
+            Statement::Intermediate(name) => {
+                source_order.push(StatementIdentifier::Definition(name));
+            }
+            Statement::Identity(mut identity) => {
+                let counter = identity_counters.entry(identity.kind).or_default();
+                identity.id = *counter;
+                *counter += 1;
+                source_order.push(StatementIdentifier::Identity(condensed_identities.len()));
+                condensed_identities.push(identity);
+            }
+        }
+    }
+
+    let mut publics = pil.publics.iter().collect::<Vec<_>>();
+    publics.sort_by_key(|public| public.id);
+    let mut public_declarations = HashMap::new();
+    for public in publics {
+        match importer.public_declaration(public) {
+            Ok(declaration) => {
+                let name = AbsoluteSymbolPath::from_dotted_string(&declaration.name);
+                source_order.push(StatementIdentifier::PublicDeclaration(name.clone()));
+                public_declarations.insert(name, declaration);
+            }
+            Err(error) => errors.push(error),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut analyzed = Analyzed {
+        degree,
+        definitions,
+        public_declarations,
+        intermediate_columns,
+        identities: condensed_identities,
+        identity_provenance: vec![],
+        dead_identities: vec![],
+        source_order,
+        names: Default::default(),
+        identity_index: Default::default(),
+    };
+    analyzed.intern_names();
+    Ok(analyzed)
+}
+
+/// A polynomial declared in the pilcom file.
+struct Column {
+    ptype: PolynomialType,
+    /// The pilcom ID of the polynomial, or of its first element for arrays.
+    /// For intermediate polynomials, this is the index of the defining expression.
+    first: usize,
+    symbol: Symbol,
+}
+
+enum Statement<T> {
+    Intermediate(AbsoluteSymbolPath),
+    Identity(Identity<Expression<T>>),
+}
+
+/// Converts the references of the pilcom file to columns, ordered such that
+/// intermediate columns get their IDs in the order of their expressions.
+fn import_columns(pil: &PIL, errors: &mut Vec<ImportError>) -> Vec<Column> {
+    let mut columns = vec![];
+    let mut names = BTreeMap::<String, Vec<String>>::new();
+    for (pilcom_name, reference) in pil.references.iter().collect::<BTreeMap<_, _>>() {
+        let ptype = match reference.type_.as_str() {
+            "cmP" => PolynomialType::Committed,
+            "constP" => PolynomialType::Constant,
+            "imP" => PolynomialType::Intermediate,
+            ty => {
+                errors.push(ImportError::UnknownPolynomialType {
+                    name: pilcom_name.clone(),
+                    ty: ty.to_string(),
+                });
+                continue;
+            }
+        };
+        let absolute_name = match demangle(pilcom_name) {
+            Ok(name) => name,
+            Err(error) => {
+                errors.push(error);
+                continue;
+            }
+        };
+        let length = match (reference.isArray, reference.len) {
+            (false, _) => None,
+            (true, Some(len)) => Some(len as DegreeType),
+            (true, None) => {
+                errors.push(ImportError::MissingField(pilcom_name.clone(), "len"));
+                continue;
+            }
+        };
+        names
+            .entry(absolute_name.clone())
+            .or_default()
+            .push(pilcom_name.clone());
+        columns.push(Column {
+            ptype,
+            first: reference.id,
+            symbol: Symbol {
+                id: reference.id as u64,
+                source: SourceRef::unknown(),
+                absolute_name,
+                kind: SymbolKind::Poly(ptype),
+                length,
+            },
+        });
+    }
+    errors.extend(
+        names
+            .into_values()
+            .filter(|names| names.len() > 1)
+            .map(ImportError::NameCollision),
+    );
+
+    // Fixed and witness columns are ordered by their IDs, and intermediate columns
+    // are numbered in the order of their expressions.
+    columns.sort_by_key(|column| {
+        (
+            column.ptype == PolynomialType::Intermediate,
+            column.ptype == PolynomialType::Committed,
+            column.first,
+        )
+    });
+    let mut intermediate_id = 0;
+    for column in &mut columns {
+        if column.ptype == PolynomialType::Intermediate {
+            column.symbol.id = intermediate_id;
+            intermediate_id += column.symbol.length.unwrap_or(1);
+        }
+    }
+    columns
+}
+
+/// Returns the common degree of all polynomials, if there are any.
+fn import_degree(pil: &PIL, errors: &mut Vec<ImportError>) -> Option<DegreeType> {
+    let mut references = pil.references.iter().collect::<Vec<_>>();
+    references.sort_by_key(|(name, _)| *name);
+    let (_, first) = references.first()?;
+    let expected = first.polDeg as DegreeType;
+    errors.extend(
+        references
+            .iter()
+            .filter(|(_, reference)| reference.polDeg as DegreeType != expected)
+            .map(|(name, reference)| ImportError::DegreeMismatch {
+                name: name.to_string(),
+                degree: reference.polDeg as DegreeType,
+                expected,
+            }),
+    );
+    Some(expected)
+}
+
+/// Turns a pilcom name `Namespace.name` into an absolute powdr name.
+fn demangle(name: &str) -> Result<String, ImportError> {
+    let (namespace, name) = name
+        .split_once('.')
+        .ok_or_else(|| ImportError::MissingNamespace(name.to_string()))?;
+    let sanitize = |s: &str| {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>()
+    };
+    Ok(format!("{}.{}", sanitize(namespace), sanitize(name)))
+}
+
+fn source(file_name: &str, line: usize) -> SourceRef {
+    SourceRef {
+        file: (!file_name.is_empty()).then(|| file_name.into()),
+        line,
+        col: 0,
+    }
+}
+
+fn polynomial_type_to_json_string(ptype: PolynomialType) -> &'static str {
+    match ptype {
+        PolynomialType::Committed => "cmP",
+        PolynomialType::Constant => "constP",
+        PolynomialType::Intermediate => "imP",
+    }
+}
+
+/// A column or an element of an array of columns.
+struct Element<'a> {
+    column: &'a Column,
+    /// The index in the array, if the column is an array.
+    index: Option<usize>,
+    poly_id: PolyID,
+}
+
+impl<'a> Element<'a> {
+    fn name(&self) -> String {
+        match self.index {
+            Some(index) => self.column.symbol.array_element_name(index as u64),
+            None => self.column.symbol.absolute_name.clone(),
+        }
+    }
+}
+
+struct Importer<'a> {
+    pil: &'a PIL,
+    /// The columns by their pilcom type and ID.
+    elements: HashMap<(PolynomialType, usize), Element<'a>>,
+    /// The names of the publics by their ID.
+    publics: HashMap<usize, &'a str>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(pil: &'a PIL, columns: &'a [Column]) -> Self {
+        let elements = columns
+            .iter()
+            .flat_map(|column| {
+                let length = column.symbol.length;
+                (0..length.unwrap_or(1) as usize).map(move |i| {
+                    let element = Element {
+                        column,
+                        index: length.map(|_| i),
+                        poly_id: PolyID {
+                            id: column.symbol.id + i as u64,
+                            ptype: column.ptype,
+                        },
+                    };
+                    ((column.ptype, column.first + i), element)
+                })
+            })
+            .collect();
+        let publics = pil
+            .publics
+            .iter()
+            .map(|public| (public.id, public.name.as_str()))
+            .collect();
+        Self {
+            pil,
+            elements,
+            publics,
+        }
+    }
+
+    fn root_expression<T: FieldElement>(&self, index: usize) -> Result<Expression<T>, ImportError> {
+        self.expression(index, &mut vec![])
+    }
+
+    /// Converts the expression with the given index, where `in_progress` contains
+    /// the indices of the expressions currently being converted.
+    fn expression<T: FieldElement>(
+        &self,
+        index: usize,
+        in_progress: &mut Vec<usize>,
+    ) -> Result<Expression<T>, ImportError> {
+        let e = self
+            .pil
+            .expressions
+            .get(index)
+            .ok_or(ImportError::UnknownExpression(index))?;
+        if in_progress.contains(&index) {
+            return Err(ImportError::CyclicExpression(index));
+        }
+        in_progress.push(index);
+        let result = self.convert(e, in_progress);
+        in_progress.pop();
+        result
+    }
+
+    fn convert<T: FieldElement>(
+        &self,
+        e: &StarkyExpr,
+        in_progress: &mut Vec<usize>,
+    ) -> Result<Expression<T>, ImportError> {
+        let id = || {
+            e.id.ok_or_else(|| ImportError::MissingField(e.op.clone(), "id"))
+        };
+        let next = e.next.unwrap_or_default();
+        Ok(match e.op.as_str() {
+            "add" | "sub" | "mul" | "pow" => {
+                let op = match e.op.as_str() {
+                    "add" => AlgebraicBinaryOperator::Add,
+                    "sub" => AlgebraicBinaryOperator::Sub,
+                    "mul" => AlgebraicBinaryOperator::Mul,
+                    _ => AlgebraicBinaryOperator::Pow,
+                };
+                let [left, right]: [Expression<T>; 2] = self
+                    .operands::<T>(e, in_progress)?
+                    .try_into()
+                    .map_err(|_| ImportError::MissingField(e.op.clone(), "values"))?;
+                Expression::new_binary(left, op, right)
+            }
+            "neg" => {
+                let [inner]: [Expression<T>; 1] = self
+                    .operands::<T>(e, in_progress)?
+                    .try_into()
+                    .map_err(|_| ImportError::MissingField(e.op.clone(), "values"))?;
+                Expression::UnaryOperation(AlgebraicUnaryOperator::Minus, Box::new(inner))
+            }
+            "number" => Expression::Number(parse_number(e.value.as_deref())?),
+            "cm" => self.reference(PolynomialType::Committed, id()?, next)?,
+            "const" => self.reference(PolynomialType::Constant, id()?, next)?,
+            "exp" => {
+                let id = id()?;
+                if self
+                    .elements
+                    .contains_key(&(PolynomialType::Intermediate, id))
+                {
+                    self.reference(PolynomialType::Intermediate, id, next)?
+                } else if next {
+                    // Only references to intermediate columns can be shifted in powdr.
+                    return Err(ImportError::UnsupportedOperation(format!(
+                        "exp with next (shifted expression {id})"
+                    )));
+                } else {
+                    self.expression(id, in_progress)?
+                }
+            }
+            "public" => {
+                let id = id()?;
+                let name = self
+                    .publics
+                    .get(&id)
+                    .ok_or(ImportError::UnknownPublic(id))?;
+                Expression::PublicReference(name.to_string())
+            }
+            op => return Err(ImportError::UnsupportedOperation(op.to_string())),
+        })
+    }
+
+    fn operands<T: FieldElement>(
+        &self,
+        e: &StarkyExpr,
+        in_progress: &mut Vec<usize>,
+    ) -> Result<Vec<Expression<T>>, ImportError> {
+        e.values
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|value| self.convert(value, in_progress))
+            .collect()
+    }
+
+    fn element(&self, ptype: PolynomialType, id: usize) -> Result<&Element<'a>, ImportError> {
+        self.elements
+            .get(&(ptype, id))
+            .ok_or_else(|| ImportError::UnknownPolynomial {
+                ty: polynomial_type_to_json_string(ptype).to_string(),
+                id,
+            })
+    }
+
+    fn reference<T>(
+        &self,
+        ptype: PolynomialType,
+        id: usize,
+        next: bool,
+    ) -> Result<Expression<T>, ImportError> {
+        let element = self.element(ptype, id)?;
+        Ok(Expression::Reference(AlgebraicReference {
+            name: element.name().into(),
+            poly_id: element.poly_id,
+            next,
+        }))
+    }
+
+    /// Converts a lookup, permutation or connection identity and returns it together with
+    /// the index of its first expression.
+    fn selected_identity<T: FieldElement>(
+        &self,
+        kind: IdentityKind,
+        source: SourceRef,
+        left: (Option<usize>, &Option<Vec<usize>>),
+        right: (Option<usize>, &Option<Vec<usize>>),
+    ) -> Result<(usize, Identity<Expression<T>>), ImportError> {
+        let first = [left, right]
+            .iter()
+            .flat_map(|(selector, expressions)| selector.iter().chain(expressions.iter().flatten()))
+            .min()
+            .copied()
+            .unwrap_or(usize::MAX);
+        let selected = |(selector, expressions): (Option<usize>, &Option<Vec<usize>>)| {
+            Ok::<_, ImportError>(SelectedExpressions {
+                selector: selector.map(|s| self.root_expression::<T>(s)).transpose()?,
+                expressions: expressions
+                    .iter()
+                    .flatten()
+                    .map(|e| self.root_expression::<T>(*e))
+                    .collect::<Result<_, _>>()?,
+            })
+        };
+        Ok((
+            first,
+            Identity {
+                id: 0,
+                kind,
+                source,
+                label: None,
+                left: selected(left)?,
+                right: selected(right)?,
+            },
+        ))
+    }
+
+    fn public_declaration(
+        &self,
+        public: &starky::types::Public,
+    ) -> Result<PublicDeclaration, ImportError> {
+        let ptype = match public.polType.as_str() {
+            "cmP" => PolynomialType::Committed,
+            "constP" => PolynomialType::Constant,
+            "imP" => PolynomialType::Intermediate,
+            ty => {
+                return Err(ImportError::UnknownPolynomialType {
+                    name: public.name.clone(),
+                    ty: ty.to_string(),
+                })
+            }
+        };
+        let element = self.element(ptype, public.polId)?;
+        Ok(PublicDeclaration {
+            id: public.id as u64,
+            source: SourceRef::unknown(),
+            name: public.name.clone(),
+            polynomial: PolynomialReference {
+                name: element.column.symbol.absolute_name.clone(),
+                poly_id: Some(PolyID {
+                    id: element.column.symbol.id,
+                    ptype,
+                }),
+                generic_args: None,
+            },
+            array_index: element.index,
+            index: public.idx as DegreeType,
+        })
+    }
+}
+
+/// Parses a number in decimal or, with the prefix `0x`, hexadecimal notation.
+fn parse_number<T: FieldElement>(value: Option<&str>) -> Result<T, ImportError> {
+    let value = value.ok_or_else(|| ImportError::MissingField("number".to_string(), "value"))?;
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let number = match digits.strip_prefix("0x") {
+        Some(hex) => T::from_str_radix(hex, 16),
+        None => T::from_str_radix(digits, 10),
+    }
+    .map_err(|_| ImportError::InvalidNumber(value.to_string()))?;
+    Ok(if negative { -number } else { number })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf};
+
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_file;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use test_log::test;
+
+    use crate::pilstark::json_exporter::export;
+
+    use super::*;
+
+    fn test_file(path: &str) -> PathBuf {
+        PathBuf::from(format!(
+            "{}/../test_data/{path}",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+    }
+
+    #[test]
+    fn round_trip() {
+        for file in [
+            "pil/fibonacci.pil",
+            "pil/block_lookup_or_permutation.pil",
+            "pil/pair_lookup.pil",
+            "polygon-hermez/arith.pil",
+            "polygon-hermez/keccakf.pil",
+            "polygon-hermez/padding_kkbit.pil",
+        ] {
+            let analyzed = analyze_file::<GoldilocksField>(&test_file(file));
+            let exported = export(&analyzed).unwrap();
+            let imported = import_pilcom::<GoldilocksField>(&exported).unwrap();
+            assert_eq!(
+                serde_json::to_value(export(&imported).unwrap()).unwrap(),
+                serde_json::to_value(&exported).unwrap(),
+                "{file}"
+            );
+        }
+    }
+
+    #[test]
+    fn import_golden_file() {
+        let json = fs::read_to_string(test_file("pil/fibonacci.pil.json")).unwrap();
+        let imported = import_pilcom_json::<GoldilocksField>(&json).unwrap();
+        let expected = r#"namespace Fibonacci(4);
+    col fixed ISLAST;
+    col witness x;
+    col witness y;
+    (Fibonacci.ISLAST * (Fibonacci.y' - 1)) = 0;
+    (Fibonacci.ISLAST * (Fibonacci.x' - 1)) = 0;
+    ((1 - Fibonacci.ISLAST) * (Fibonacci.x' - Fibonacci.y)) = 0;
+    ((1 - Fibonacci.ISLAST) * (Fibonacci.y' - (Fibonacci.x + Fibonacci.y))) = 0;
+namespace (4);
+public out = Fibonacci.y(3);
+"#;
+        assert_eq!(imported.to_string(), expected);
+    }
+
+    fn reference(ty: &str, id: usize, len: Option<usize>) -> serde_json::Value {
+        let mut reference = json!({"type": ty, "id": id, "polDeg": 8, "isArray": len.is_some()});
+        if let Some(len) = len {
+            reference["len"] = json!(len);
+        }
+        reference
+    }
+
+    fn pil_json(
+        references: serde_json::Value,
+        expressions: serde_json::Value,
+        pol_identities: &[usize],
+        connection_identities: serde_json::Value,
+    ) -> String {
+        json!({
+            "nCommitments": 0,
+            "nQ": 0,
+            "nIm": 0,
+            "nConstants": 0,
+            "publics": [],
+            "references": references,
+            "expressions": expressions,
+            "polIdentities": pol_identities
+                .iter()
+                .map(|e| json!({"e": e, "fileName": "test.pil", "line": 1}))
+                .collect::<Vec<_>>(),
+            "plookupIdentities": [],
+            "permutationIdentities": [],
+            "connectionIdentities": connection_identities,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn intermediate_and_connection() {
+        let cm = |id, next| json!({"op": "cm", "deg": 1, "id": id, "next": next});
+        let json = pil_json(
+            json!({
+                "Main.a": reference("cmP", 0, None),
+                "Main.S": reference("constP", 0, Some(2)),
+                "Main.inter": reference("imP", 0, Some(2)),
+            }),
+            json!([
+                {"op": "mul", "deg": 2, "values": [cm(0, false), cm(0, false)]},
+                {"op": "add", "deg": 1, "values": [cm(0, false), {"op": "number", "deg": 0, "value": "0x10"}]},
+                // Not a column, so references to it are inlined.
+                {"op": "neg", "deg": 1, "values": [cm(0, true)]},
+                {"op": "sub", "deg": 2, "values": [
+                    {"op": "exp", "deg": 2, "id": 1, "next": true},
+                    {"op": "exp", "deg": 1, "id": 2, "next": false},
+                ]},
+                cm(0, false),
+                {"op": "const", "deg": 1, "id": 1, "next": false},
+            ]),
+            &[3],
+            json!([{"pols": [4], "connections": [5], "fileName": "test.pil", "line": 2}]),
+        );
+        let imported = import_pilcom_json::<GoldilocksField>(&json).unwrap();
+        let expected = r#"namespace Main(8);
+    col fixed S;
+    col witness a;
+    col inter[2] = [(Main.a * Main.a), (Main.a + 16)];
+    Main.inter[1]' = -Main.a';
+    { Main.a } connect { Main.S[1] };
+"#;
+        assert_eq!(imported.to_string(), expected);
+        assert_eq!(imported.intermediate_count(), 2);
+        let pil = export(&imported).unwrap();
+        assert_eq!(pil.references["Main.inter"].id, 0);
+        assert_eq!(pil.connectionIdentities.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn unsupported_constructs() {
+        let json = pil_json(
+            json!({
+                "Main.a.b": reference("cmP", 0, None),
+                "Main.a_b": reference("cmP", 1, None),
+                "global": reference("cmP", 2, None),
+                "Other.c": {"type": "cmP", "id": 3, "polDeg": 16, "isArray": false},
+            }),
+            json!([
+                {"op": "x", "deg": 1},
+                {"op": "exp", "deg": 1, "id": 1, "next": false},
+            ]),
+            &[0, 1, 2],
+            json!([]),
+        );
+        let errors = import_pilcom_json::<GoldilocksField>(&json)
+            .err()
+            .unwrap()
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "Polynomial global is not inside a namespace.",
+                "Polynomials Main.a.b, Main.a_b have the same name after replacing invalid characters.",
+                "Polynomial Other.c has degree 16, but other polynomials have degree 8. Namespaces with different degrees are not supported.",
+                "Operation x is not supported.",
+                "Expression 1 depends on itself.",
+                "Expression 2 does not exist.",
+            ]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 pub mod estark;
 mod json_exporter;
+pub mod json_importer;
 
 use std::{
     fs::File,
@@ -54,8 +55,11 @@ impl<'a, F: FieldElement> Backend<'a, F> for PilStarkCli<'a, F> {
         if let Some(output_dir) = self.output_dir {
             let path = output_dir.join("constraints.json");
             let mut writer = BufWriter::new(File::create(path)?);
-            serde_json::to_writer(&mut writer, &json_exporter::export(self.analyzed))
-                .map_err(|e| e.to_string())?;
+            serde_json::to_writer(
+                &mut writer,
+                &json_exporter::export(self.analyzed).map_err(|e| e.to_string())?,
+            )
+            .map_err(|e| e.to_string())?;
             writer.flush()?;
         } else {
             // If we were going to call the prover-cpp, we could write the
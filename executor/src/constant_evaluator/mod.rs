@@ -5,15 +5,14 @@ use std::{
 
 use itertools::Itertools;
 use powdr_ast::{
-    analyzed::{Analyzed, Expression, FunctionValueDefinition, Symbol, TypedExpression},
+    analyzed::{Analyzed, FunctionValueDefinition, Symbol, TypedExpression},
     parsed::{
+        asm::AbsoluteSymbolPath,
         types::{ArrayType, Type},
-        IndexAccess,
     },
 };
-use powdr_number::{BigInt, DegreeType, FieldElement};
-use powdr_pil_analyzer::evaluator::{self, Definitions, SymbolLookup, Value};
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use powdr_number::{DegreeType, FieldElement};
+use powdr_pil_analyzer::evaluator::{self, CallCache, Definitions, SymbolLookup, Value};
 
 /// Generates the fixed column values for all fixed columns that are defined
 /// (and not just declared).
@@ -28,7 +27,8 @@ pub fn generate<T: FieldElement>(analyzed: &Analyzed<T>) -> Vec<(String, Vec<T>)
             // for non-arrays, set index to None.
             for (index, (name, id)) in poly.array_elements().enumerate() {
                 let index = poly.is_array().then_some(index as u64);
-                let values = generate_values(analyzed, analyzed.degree(), &name, value, index);
+                let values =
+                    generate_values(analyzed, analyzed.degree(), poly, &name, value, index);
                 assert!(fixed_cols.insert(name, (id, values)).is_none());
             }
         }
@@ -44,6 +44,7 @@ pub fn generate<T: FieldElement>(analyzed: &Analyzed<T>) -> Vec<(String, Vec<T>)
 fn generate_values<T: FieldElement>(
     analyzed: &Analyzed<T>,
     degree: DegreeType,
+    poly: &Symbol,
     name: &str,
     body: &FunctionValueDefinition,
     index: Option<u64>,
@@ -51,78 +52,39 @@ fn generate_values<T: FieldElement>(
     let symbols = CachedSymbols {
         symbols: &analyzed.definitions,
         cache: Arc::new(RwLock::new(HashMap::new())),
+        call_cache: Default::default(),
     };
-    let result = match body {
-        FunctionValueDefinition::Expression(TypedExpression { e, type_scheme }) => {
-            if let Some(type_scheme) = type_scheme {
-                assert!(type_scheme.vars.is_empty());
-                let ty = &type_scheme.ty;
-                if ty == &Type::Col {
-                    assert!(index.is_none());
-                } else if let Type::Array(ArrayType { base, length: _ }) = ty {
-                    assert!(index.is_some());
-                    assert_eq!(base.as_ref(), &Type::Col);
-                } else {
-                    panic!("Invalid fixed column type: {}", ty);
-                }
-            };
-            let index_expr;
-            let e = if let Some(index) = index {
-                index_expr = Expression::IndexAccess(IndexAccess {
-                    array: e.clone().into(),
-                    index: Box::new(Expression::Number(index.into(), None)),
-                });
-                &index_expr
-            } else {
-                e
-            };
-            (0..degree)
-                .into_par_iter()
-                .map(|i| {
-                    let symbols = symbols.clone();
-                    let fun = evaluator::evaluate(e, &symbols).unwrap();
-                    evaluator::evaluate_function_call(
-                        fun,
-                        vec![Arc::new(Value::Integer(BigInt::from(i)))],
-                        &symbols,
-                    )
-                    .and_then(|v| v.try_to_field_element())
-                })
-                .collect::<Result<Vec<_>, _>>()
-        }
-        FunctionValueDefinition::Array(values) => {
+    if let FunctionValueDefinition::Expression(TypedExpression {
+        type_scheme: Some(type_scheme),
+        ..
+    }) = body
+    {
+        assert!(type_scheme.vars.is_empty());
+        let ty = &type_scheme.ty;
+        if ty == &Type::Col {
             assert!(index.is_none());
-            values
-                .iter()
-                .map(|elements| {
-                    let items = elements
-                        .pattern()
-                        .iter()
-                        .map(|v| {
-                            let symbols = symbols.clone();
-                            evaluator::evaluate(v, &symbols).and_then(|v| v.try_to_field_element())
-                        })
-                        .collect::<Result<Vec<_>, _>>()?;
-
-                    Ok(items
-                        .into_iter()
-                        .cycle()
-                        .take(elements.size() as usize)
-                        .collect::<Vec<_>>())
-                })
-                .collect::<Result<Vec<_>, _>>()
-                .map(|values| {
-                    let values: Vec<T> = values.into_iter().flatten().collect();
-                    assert_eq!(values.len(), degree as usize);
-                    values
-                })
+        } else if let Type::Array(ArrayType { base, length: _ }) = ty {
+            assert!(index.is_some());
+            assert_eq!(base.as_ref(), &Type::Col);
+        } else {
+            panic!("Invalid fixed column type: {}", ty);
         }
-        FunctionValueDefinition::Query(_) => panic!("Query used for fixed column."),
-    };
+    }
+    if let FunctionValueDefinition::Array(_) | FunctionValueDefinition::Sparse(_) = body {
+        assert!(index.is_none());
+    }
+    let result = evaluator::evaluate_fixed_column(
+        body,
+        index,
+        degree,
+        rayon::current_num_threads(),
+        &symbols,
+    );
     match result {
         Err(err) => {
-            eprintln!("Error evaluating fixed polynomial {name}{body}:\n{err}");
-            panic!("{err}");
+            let source = &poly.source;
+            eprintln!("{source}: Error evaluating fixed polynomial {name}{body}:\n{err}");
+            panic!("{source}: {err}");
         }
         Ok(v) => v,
     }
@@ -130,8 +92,10 @@ fn generate_values<T: FieldElement>(
 
 #[derive(Clone)]
 pub struct CachedSymbols<'a, T> {
-    symbols: &'a HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
+    symbols: &'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
     cache: Arc<RwLock<HashMap<String, Arc<Value<'a, T>>>>>,
+    /// Memoized results of function calls, shared by all rows.
+    call_cache: Arc<CallCache<'a, T>>,
 }
 
 impl<'a, T: FieldElement> SymbolLookup<'a, T> for CachedSymbols<'a, T> {
@@ -150,11 +114,22 @@ impl<'a, T: FieldElement> SymbolLookup<'a, T> for CachedSymbols<'a, T> {
             .insert(name.to_string(), result.clone());
         Ok(result)
     }
+
+    fn fixed_column_definition(
+        &self,
+        name: &str,
+    ) -> Result<&'a FunctionValueDefinition, evaluator::EvalError> {
+        Definitions(self.symbols).lookup_fixed_column(name)
+    }
+
+    fn call_cache(&self) -> Option<&CallCache<'a, T>> {
+        Some(&self.call_cache)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use powdr_number::GoldilocksField;
+    use powdr_number::{Bn254Field, GoldilocksField};
     use powdr_pil_analyzer::analyze_string;
     use pretty_assertions::assert_eq;
     use test_log::test;
@@ -359,6 +334,37 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn sparse_arrays() {
+        let src = r#"
+            let N: int = 8;
+            namespace F(N);
+            let f = |i| i + 20;
+            col fixed boundary = { 0: 1, N - 1: 1, _: 0 };
+            col fixed values = { 3: f(1), 1: 5, _: 7 };
+            col fixed shifted(i) { values((i + 1) % N) };
+        "#;
+        let analyzed = analyze_string(src);
+        let constants = generate(&analyzed);
+        assert_eq!(
+            constants,
+            vec![
+                (
+                    "F.boundary".to_string(),
+                    convert([1i32, 0, 0, 0, 0, 0, 0, 1].to_vec())
+                ),
+                (
+                    "F.values".to_string(),
+                    convert([7i32, 5, 7, 21, 7, 7, 7, 7].to_vec())
+                ),
+                (
+                    "F.shifted".to_string(),
+                    convert([5i32, 7, 21, 7, 7, 7, 7, 7].to_vec())
+                ),
+            ]
+        );
+    }
+
     #[test]
     pub fn repetition_front() {
         let src = r#"
@@ -452,7 +458,7 @@ mod test {
     }
 
     #[test]
-    #[should_panic = "got `expr` when calling function F.w"]
+    #[should_panic = "Only fixed columns can be evaluated at a row index, but F.w is a witness column."]
     pub fn calling_witness() {
         let src = r#"
             constant %N = 10;
@@ -479,7 +485,6 @@ mod test {
     }
 
     #[test]
-    #[should_panic = "got `expr` when calling function F.y"]
     pub fn forward_reference_to_array() {
         let src = r#"
             constant %N = 10;
@@ -489,7 +494,14 @@ mod test {
         "#;
         let analyzed = analyze_string::<GoldilocksField>(src);
         assert_eq!(analyzed.degree(), 10);
-        generate(&analyzed);
+        let constants = generate(&analyzed);
+        assert_eq!(
+            constants[0],
+            (
+                "F.x".to_string(),
+                convert([2, 3, 4, 2, 3, 4, 2, 3, 4, 2].to_vec())
+            )
+        );
     }
 
     #[test]
@@ -577,4 +589,170 @@ mod test {
             ("F.y[1]".to_string(), convert([1, 2, 3, 4].to_vec()))
         );
     }
+
+    #[test]
+    pub fn returned_closures() {
+        let src = r#"
+            namespace F(4);
+                let adder: int -> (int -> int) = |x| |y| x + y;
+                let add3: int -> int = adder(3);
+                let twice: (int -> int) -> (int -> int) = |f| |x| f(f(x));
+                let sum3: int -> (int -> (int -> int)) = |x| |y| |z| x + y + z;
+                let a: col = |i| std::convert::fe(add3(i));
+                col fixed b(i) { std::convert::fe(adder(i)(10)) };
+                col fixed c(i) { std::convert::fe(twice(adder(i))(1)) };
+                col fixed d(i) { std::convert::fe(sum3(i)(2 * i)(100)) };
+                let e: col = |i| std::convert::fe(twice(twice(add3))(i));
+            namespace std::convert(4);
+                let fe = [];
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        assert_eq!(
+            constants,
+            vec![
+                ("F.a".to_string(), convert(vec![3, 4, 5, 6])),
+                ("F.b".to_string(), convert(vec![10, 11, 12, 13])),
+                ("F.c".to_string(), convert(vec![1, 3, 5, 7])),
+                ("F.d".to_string(), convert(vec![100, 103, 106, 109])),
+                ("F.e".to_string(), convert(vec![12, 13, 14, 15])),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn field_modulus() {
+        let src = r#"
+            constant %N = 4;
+            namespace std::convert(%N);
+                let fe = [];
+            namespace std::field(%N);
+                let modulus = [];
+            namespace F(%N);
+                let small_field: bool = std::field::modulus() < 2**64;
+                col fixed X(i) { std::convert::fe(if small_field { i } else { std::field::modulus() - 1 - i }) };
+        "#;
+        let constants = generate(&analyze_string::<GoldilocksField>(src));
+        assert_eq!(
+            constants,
+            vec![("F.X".to_string(), convert(vec![0, 1, 2, 3]))]
+        );
+        let constants = generate(&analyze_string::<Bn254Field>(src));
+        assert_eq!(
+            constants,
+            vec![(
+                "F.X".to_string(),
+                [-1, -2, -3, -4].into_iter().map(Bn254Field::from).collect()
+            )]
+        );
+    }
+
+    #[test]
+    pub fn bit_decomposition() {
+        let src = r#"
+            constant %N = 4;
+            namespace std::convert(%N);
+                let fe = [];
+            namespace std::utils(%N);
+                let to_bits = [];
+                let from_bits = [];
+            namespace F(%N);
+                let bits = |i| std::utils::to_bits(i, 2);
+                col fixed HIGH(i) { std::convert::fe(bits(i)[1]) };
+                col fixed SWAPPED(i) { std::convert::fe(std::utils::from_bits([bits(i)[1], bits(i)[0]])) };
+        "#;
+        let constants = generate(&analyze_string::<GoldilocksField>(src));
+        assert_eq!(
+            constants,
+            vec![
+                ("F.HIGH".to_string(), convert(vec![0, 0, 1, 1])),
+                ("F.SWAPPED".to_string(), convert(vec![0, 2, 1, 3])),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic = "std::utils::to_bits: Value 4 does not fit into 2 bits."]
+    pub fn bit_decomposition_overflow() {
+        let src = r#"
+            constant %N = 8;
+            namespace std::convert(%N);
+                let fe = [];
+            namespace std::utils(%N);
+                let to_bits = [];
+            namespace F(%N);
+                col fixed X(i) { std::convert::fe(std::utils::to_bits(i, 2)[0]) };
+        "#;
+        generate(&analyze_string::<GoldilocksField>(src));
+    }
+
+    #[test]
+    pub fn byte_decomposition() {
+        let src = r#"
+            constant %N = 4;
+            namespace std::convert(%N);
+                let fe = [];
+            namespace std::utils(%N);
+                let to_bytes = [];
+                let from_bytes = [];
+            namespace F(%N);
+                let bytes = |i| std::utils::to_bytes(i * 0x5555, 2);
+                col fixed LOW(i) { std::convert::fe(bytes(i)[0]) };
+                col fixed HIGH(i) { std::convert::fe(bytes(i)[1]) };
+                col fixed ROUND_TRIP(i) { std::convert::fe(std::utils::from_bytes(bytes(i))) };
+        "#;
+        let constants = generate(&analyze_string::<GoldilocksField>(src));
+        assert_eq!(
+            constants,
+            vec![
+                ("F.LOW".to_string(), convert(vec![0, 0x55, 0xaa, 0xff])),
+                ("F.HIGH".to_string(), convert(vec![0, 0x55, 0xaa, 0xff])),
+                (
+                    "F.ROUND_TRIP".to_string(),
+                    convert(vec![0, 0x5555, 0xaaaa, 0xffff])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic = "std::utils::to_bytes: Value 65536 does not fit into 2 bytes."]
+    pub fn byte_decomposition_overflow() {
+        let src = r#"
+            constant %N = 4;
+            namespace std::convert(%N);
+                let fe = [];
+            namespace std::utils(%N);
+                let to_bytes = [];
+            namespace F(%N);
+                col fixed X(i) { std::convert::fe(std::utils::to_bytes(0xfffd + i, 2)[0]) };
+        "#;
+        generate(&analyze_string::<GoldilocksField>(src));
+    }
+
+    #[test]
+    pub fn literal_below_modulus() {
+        let src = r#"
+            constant %N = 4;
+            namespace F(%N);
+            let big: int = 0xffffffffffffffffffff;
+            col fixed X = [0xffffffff00000000]*;
+        "#;
+        let constants = generate(&analyze_string::<GoldilocksField>(src));
+        assert_eq!(
+            constants,
+            vec![("F.X".to_string(), vec![GoldilocksField::from(-1); 4])]
+        );
+    }
+
+    #[test]
+    #[should_panic = "input:4:12: Field overflow: Number 18446744069414584321 is too large for the Goldilocks field with modulus 18446744069414584321."]
+    pub fn literal_above_modulus() {
+        let src = r#"
+            constant %N = 4;
+            namespace F(%N);
+            col fixed X = [0xffffffff00000001]*;
+        "#;
+        generate(&analyze_string::<GoldilocksField>(src));
+    }
 }
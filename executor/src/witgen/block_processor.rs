@@ -130,7 +130,7 @@ mod tests {
     fn name_to_poly_id<T: FieldElement>(fixed_data: &FixedData<T>) -> BTreeMap<String, PolyID> {
         let mut name_to_poly_id = BTreeMap::new();
         for (poly_id, col) in fixed_data.witness_cols.iter() {
-            name_to_poly_id.insert(col.poly.name.clone(), poly_id);
+            name_to_poly_id.insert(col.poly.name.to_string(), poly_id);
         }
         for (poly_id, col) in fixed_data.fixed_cols.iter() {
             name_to_poly_id.insert(col.name.clone(), poly_id);
@@ -264,7 +264,7 @@ impl<T: FieldElement> FixedLookup<T> {
             .ok_or_else(|| {
                 let input_assignment = input_assignment
                     .into_iter()
-                    .map(|(poly_ref, v)| (poly_ref.name.clone(), v))
+                    .map(|(poly_ref, v)| (poly_ref.name.to_string(), v))
                     .collect();
                 EvalError::FixedLookupFailed(input_assignment)
             })?;
@@ -193,7 +193,7 @@ pub fn extract_publics<T: FieldElement>(
             let poly_name = &public_declaration.referenced_poly_name();
             let poly_index = public_declaration.index;
             let value = witness[poly_name][poly_index as usize];
-            ((*name).clone(), value)
+            (name.to_dotted_string(), value)
         })
         .collect()
 }
@@ -259,9 +259,9 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
             witness_cols,
             column_by_name: analyzed
                 .definitions
-                .iter()
-                .filter(|(_, (symbol, _))| matches!(symbol.kind, SymbolKind::Poly(_)))
-                .map(|(name, (symbol, _))| (name.clone(), symbol.into()))
+                .values()
+                .filter(|(symbol, _)| matches!(symbol.kind, SymbolKind::Poly(_)))
+                .map(|(symbol, _)| (symbol.absolute_name.clone(), symbol.into()))
                 .collect(),
         }
     }
@@ -334,7 +334,7 @@ impl<'a, T> WitnessColumn<'a, T> {
                 id: id as u64,
                 ptype: PolynomialType::Committed,
             },
-            name: name.to_string(),
+            name: name.into(),
             next: false,
         };
         WitnessColumn {
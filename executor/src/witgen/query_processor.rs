@@ -1,7 +1,8 @@
 use std::sync::Arc;
 
 use powdr_ast::analyzed::{
-    AlgebraicExpression, AlgebraicReference, Expression, PolyID, PolynomialType,
+    AlgebraicExpression, AlgebraicReference, Expression, FunctionValueDefinition, PolyID,
+    PolynomialType,
 };
 use powdr_ast::parsed::types::Type;
 use powdr_number::{BigInt, FieldElement};
@@ -110,6 +111,13 @@ impl<'a, T: FieldElement> SymbolLookup<'a, T> for Symbols<'a, T> {
         )
     }
 
+    fn fixed_column_definition(
+        &self,
+        name: &str,
+    ) -> Result<&'a FunctionValueDefinition, EvalError> {
+        Definitions(&self.fixed_data.analyzed.definitions).lookup_fixed_column(name)
+    }
+
     fn eval_expr(&self, expr: &AlgebraicExpression<T>) -> Result<Arc<Value<'a, T>>, EvalError> {
         let AlgebraicExpression::Reference(poly_ref) = expr else {
             return Err(EvalError::TypeError(format!(
@@ -56,7 +56,7 @@ impl Folder for Loader {
                         )),
                     }
                     .map(|(file, path)| {
-                        powdr_parser::parse_module(None, &file)
+                        powdr_parser::parse_module(file_path.to_str(), &file)
                             .map(|res| (res, path))
                             .unwrap_or_else(|err| {
                                 eprintln!(
@@ -80,23 +80,43 @@ impl Folder for Loader {
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::{convert::Infallible, path::Path};
 
+    use powdr_ast::{parsed::asm::Machine, SourceRef};
     use powdr_parser::parse_asm;
 
     use super::*;
 
+    /// Clears the source references of machines, which differ between
+    /// the loaded files and the expected file.
+    struct ClearMachineSources;
+
+    impl Folder for ClearMachineSources {
+        type Error = Infallible;
+
+        fn fold_machine(&mut self, machine: Machine) -> Result<Machine, Self::Error> {
+            Ok(Machine {
+                source: SourceRef::unknown(),
+                ..machine
+            })
+        }
+    }
+
+    fn clear_machine_sources(program: ASMProgram) -> ASMProgram {
+        ClearMachineSources.fold_program(program).unwrap()
+    }
+
     fn test_dir(dir: &str, expected: Result<(), &str>) {
         let dir = Path::new(dir);
         let main_path = dir.join("main.asm").to_owned();
         let main_str = std::fs::read_to_string(&main_path).unwrap();
         let main = parse_asm(None, &main_str).unwrap();
-        let main = load_module_files(Some(main_path), main);
+        let main = load_module_files(Some(main_path), main).map(clear_machine_sources);
 
         let expected = expected
             .map(|_| {
                 let expected_str = std::fs::read_to_string(dir.join("expected.asm")).unwrap();
-                parse_asm(None, &expected_str).unwrap()
+                clear_machine_sources(parse_asm(None, &expected_str).unwrap())
             })
             .map_err(|e| e.to_string());
 
@@ -13,7 +13,8 @@ use powdr_ast::{
         },
         folder::Folder,
         visitor::ExpressionVisitable,
-        ArrayLiteral, FunctionCall, IndexAccess, LambdaExpression, MatchArm, TypedExpression,
+        ArrayLiteral, BlockExpression, FunctionCall, IndexAccess, LambdaExpression,
+        LetStatementInsideBlock, MatchArm, TypedExpression,
     },
 };
 
@@ -376,7 +377,7 @@ fn check_expression(
     local_variables: &HashSet<String>,
 ) -> Result<(), String> {
     // We cannot use the visitor here because we need to change the local variables
-    // inside lambda expressions.
+    // inside lambda and block expressions.
     match e {
         Expression::Reference(reference) => {
             if let Some(name) = reference.try_to_identifier() {
@@ -386,7 +387,7 @@ fn check_expression(
             }
             check_path(location.clone().join(reference.path.clone()), state)
         }
-        Expression::PublicReference(_) | Expression::Number(_, _) | Expression::String(_) => Ok(()),
+        Expression::PublicReference(_) | Expression::Number(..) | Expression::String(_) => Ok(()),
         Expression::Tuple(items) | Expression::ArrayLiteral(ArrayLiteral { items }) => {
             check_expressions(location, items, state, local_variables)
         }
@@ -413,13 +414,17 @@ fn check_expression(
         }
         Expression::MatchExpression(scrutinee, arms) => {
             check_expression(location, scrutinee, state, local_variables)?;
-            arms.iter().try_for_each(|MatchArm { pattern, value }| {
-                match pattern {
+            arms.iter().try_for_each(|MatchArm { patterns, value }| {
+                patterns.iter().try_for_each(|pattern| match pattern {
                     powdr_ast::parsed::MatchPattern::CatchAll => Ok(()),
                     powdr_ast::parsed::MatchPattern::Pattern(e) => {
                         check_expression(location, e, state, local_variables)
                     }
-                }?;
+                    powdr_ast::parsed::MatchPattern::Range(start, end, _) => {
+                        check_expression(location, start, state, local_variables)?;
+                        check_expression(location, end, state, local_variables)
+                    }
+                })?;
                 check_expression(location, value, state, local_variables)
             })
         }
@@ -432,6 +437,15 @@ fn check_expression(
             check_expression(location, body, state, local_variables)?;
             check_expression(location, else_body, state, local_variables)
         }
+        Expression::BlockExpression(BlockExpression { statements, expr }) => {
+            // Each variable is only local in the statements after it.
+            let mut local_variables = local_variables.clone();
+            for LetStatementInsideBlock { name, value } in statements {
+                check_expression(location, value, state, &local_variables)?;
+                local_variables.insert(name.clone());
+            }
+            check_expression(location, expr, state, &local_variables)
+        }
     }
 }
 
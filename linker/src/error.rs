@@ -0,0 +1,310 @@
+use std::fmt::{self, Display};
+
+use powdr_ast::{
+    object::{Location, Operation},
+    SourceRef,
+};
+use powdr_number::BigUint;
+
+/// An error in a link from a caller machine to an operation of a callee machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// The callee is not a machine of the graph.
+    UnknownCallee {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+    },
+    /// The callee does not have a latch, so it cannot be called.
+    MissingLatch {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+    },
+    /// The operation has an id but the callee has no operation id column, or the other way around.
+    OperationIdMismatch {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+        operation_id: Option<BigUint>,
+        callee_operation_id: Option<String>,
+    },
+    /// The callee does not have the operation, looked up by id if the link has one and by name otherwise.
+    UnknownOperation {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+        operation_id: Option<BigUint>,
+    },
+    /// The link passes a different number of inputs or outputs than the operation declares.
+    ParamCountMismatch {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+        /// The number of inputs and outputs passed by the link.
+        found: (usize, usize),
+        /// The number of inputs and outputs declared by the operation.
+        expected: (usize, usize),
+        declaration: Operation,
+    },
+    /// The link passes a parameter of a different type than the operation declares.
+    ParamTypeMismatch {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+        /// The name of the parameter in the declaration of the operation.
+        param: String,
+        found: String,
+        expected: String,
+        declaration: Operation,
+    },
+    /// The link passes a parameter with a type. Only registers written by the link
+    /// (type `write`) can be passed with a type, other types cannot be lowered to a lookup.
+    UnsupportedParamType {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+        /// The name of the parameter in the declaration of the operation.
+        param: String,
+        ty: String,
+        declaration: Operation,
+    },
+    /// The link is a permutation, but the latch of the callee is not one of its columns.
+    IncompatibleLatch {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+        latch: String,
+    },
+    /// The link is a permutation, but the caller and callee have different degrees.
+    PermutationDegreeMismatch {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+        caller_degree: u64,
+        callee_degree: u64,
+    },
+    /// The caller and callee have different degrees. This is fine for a lookup, but the
+    /// PIL analyzer does not support namespaces of different degrees yet.
+    DegreeMismatch {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+        caller_degree: u64,
+        callee_degree: u64,
+    },
+    /// The callee does not declare a degree and inherits different degrees from its callers.
+    DegreeConflict {
+        source: SourceRef,
+        caller: Location,
+        callee: Location,
+        operation: String,
+        caller_degree: u64,
+        /// The caller the callee inherited its degree from.
+        first_caller: Location,
+        /// The location of the link the callee inherited its degree through.
+        first_source: SourceRef,
+        first_degree: u64,
+    },
+}
+
+impl LinkError {
+    /// The location of the machine the link starts from.
+    pub fn caller(&self) -> &Location {
+        match self {
+            LinkError::UnknownCallee { caller, .. }
+            | LinkError::MissingLatch { caller, .. }
+            | LinkError::OperationIdMismatch { caller, .. }
+            | LinkError::UnknownOperation { caller, .. }
+            | LinkError::ParamCountMismatch { caller, .. }
+            | LinkError::ParamTypeMismatch { caller, .. }
+            | LinkError::UnsupportedParamType { caller, .. }
+            | LinkError::IncompatibleLatch { caller, .. }
+            | LinkError::PermutationDegreeMismatch { caller, .. }
+            | LinkError::DegreeMismatch { caller, .. }
+            | LinkError::DegreeConflict { caller, .. } => caller,
+        }
+    }
+
+    /// The location of the machine the link calls into.
+    pub fn callee(&self) -> &Location {
+        match self {
+            LinkError::UnknownCallee { callee, .. }
+            | LinkError::MissingLatch { callee, .. }
+            | LinkError::OperationIdMismatch { callee, .. }
+            | LinkError::UnknownOperation { callee, .. }
+            | LinkError::ParamCountMismatch { callee, .. }
+            | LinkError::ParamTypeMismatch { callee, .. }
+            | LinkError::UnsupportedParamType { callee, .. }
+            | LinkError::IncompatibleLatch { callee, .. }
+            | LinkError::PermutationDegreeMismatch { callee, .. }
+            | LinkError::DegreeMismatch { callee, .. }
+            | LinkError::DegreeConflict { callee, .. } => callee,
+        }
+    }
+
+    /// The name of the called operation.
+    pub fn operation(&self) -> &str {
+        match self {
+            LinkError::UnknownCallee { operation, .. }
+            | LinkError::MissingLatch { operation, .. }
+            | LinkError::OperationIdMismatch { operation, .. }
+            | LinkError::UnknownOperation { operation, .. }
+            | LinkError::ParamCountMismatch { operation, .. }
+            | LinkError::ParamTypeMismatch { operation, .. }
+            | LinkError::UnsupportedParamType { operation, .. }
+            | LinkError::IncompatibleLatch { operation, .. }
+            | LinkError::PermutationDegreeMismatch { operation, .. }
+            | LinkError::DegreeMismatch { operation, .. }
+            | LinkError::DegreeConflict { operation, .. } => operation,
+        }
+    }
+
+    /// The location of the link declaration in the source.
+    pub fn source(&self) -> &SourceRef {
+        match self {
+            LinkError::UnknownCallee { source, .. }
+            | LinkError::MissingLatch { source, .. }
+            | LinkError::OperationIdMismatch { source, .. }
+            | LinkError::UnknownOperation { source, .. }
+            | LinkError::ParamCountMismatch { source, .. }
+            | LinkError::ParamTypeMismatch { source, .. }
+            | LinkError::UnsupportedParamType { source, .. }
+            | LinkError::IncompatibleLatch { source, .. }
+            | LinkError::PermutationDegreeMismatch { source, .. }
+            | LinkError::DegreeMismatch { source, .. }
+            | LinkError::DegreeConflict { source, .. } => source,
+        }
+    }
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: Link from {} to operation {} of {}: ",
+            self.source(),
+            self.caller(),
+            self.operation(),
+            self.callee()
+        )?;
+        match self {
+            LinkError::UnknownCallee { callee, .. } => {
+                write!(f, "there is no machine at location {callee}")
+            }
+            LinkError::MissingLatch { callee, .. } => {
+                write!(f, "machine {callee} does not have a latch")
+            }
+            LinkError::OperationIdMismatch {
+                callee,
+                operation_id,
+                callee_operation_id,
+                ..
+            } => match (operation_id, callee_operation_id) {
+                (Some(id), None) => write!(
+                    f,
+                    "the operation has id {id}, but machine {callee} does not have an operation id column"
+                ),
+                (None, Some(column)) => write!(
+                    f,
+                    "the operation does not have an id, but machine {callee} has the operation id column {column}"
+                ),
+                _ => unreachable!(),
+            },
+            LinkError::UnknownOperation {
+                callee,
+                operation,
+                operation_id,
+                ..
+            } => match operation_id {
+                Some(id) => write!(f, "machine {callee} does not have an operation with id {id}"),
+                None => write!(f, "machine {callee} does not have an operation {operation}"),
+            },
+            LinkError::ParamCountMismatch {
+                found,
+                expected,
+                declaration,
+                ..
+            } => {
+                write!(
+                    f,
+                    "the link passes {} inputs and {} outputs, but `{declaration}` expects {} inputs and {} outputs",
+                    found.0, found.1, expected.0, expected.1
+                )?;
+                if found.0 + found.1 == expected.0 + expected.1 {
+                    if found.0 > expected.0 {
+                        write!(f, " (an output is passed where an input is expected)")?;
+                    } else {
+                        write!(f, " (an input is passed where an output is expected)")?;
+                    }
+                }
+                Ok(())
+            }
+            LinkError::ParamTypeMismatch {
+                param,
+                found,
+                expected,
+                declaration,
+                ..
+            } => write!(
+                f,
+                "the link passes a value of type {found} for parameter {param} of type {expected} in `{declaration}`"
+            ),
+            LinkError::UnsupportedParamType {
+                param,
+                ty,
+                declaration,
+                ..
+            } => write!(
+                f,
+                "the link passes parameter {param} of `{declaration}` with type {ty}, but only untyped parameters and registers of type write are supported"
+            ),
+            LinkError::IncompatibleLatch { callee, latch, .. } => write!(
+                f,
+                "the latch {latch} of a permutation target has to be a column defined in machine {callee}"
+            ),
+            LinkError::PermutationDegreeMismatch {
+                caller,
+                callee,
+                caller_degree,
+                callee_degree,
+                ..
+            } => write!(
+                f,
+                "a permutation from {caller} (degree {caller_degree}) to {callee} (degree {callee_degree}) requires both machines to have the same degree"
+            ),
+            LinkError::DegreeMismatch {
+                caller,
+                callee,
+                caller_degree,
+                callee_degree,
+                ..
+            } => write!(
+                f,
+                "machines of different degrees cannot be linked yet, but {caller} has degree {caller_degree} and {callee} has degree {callee_degree}"
+            ),
+            LinkError::DegreeConflict {
+                caller,
+                callee,
+                caller_degree,
+                first_caller,
+                first_source,
+                first_degree,
+                ..
+            } => write!(
+                f,
+                "machine {callee} does not declare a degree and gets degree {caller_degree} from {caller}, but degree {first_degree} from {first_caller} (at {first_source})"
+            ),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use powdr_ast::{
+    object::Location,
+    parsed::{PILFile, PilStatement},
+    SourceRef,
+};
+
+/// The output of `link_to_files`: the PIL of every object in its own file
+/// and the utility definitions shared by all objects in a separate file.
+/// All references to symbols of other files are absolute, so the files
+/// only need to be included by a root file, see `LinkedFiles::root`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkedFiles {
+    /// The utility definitions, sorted into namespaces.
+    pub utils: PILFile,
+    /// The PIL of each object, starting with the namespace of the object.
+    pub objects: BTreeMap<Location, PILFile>,
+}
+
+impl LinkedFiles {
+    /// The path of the file of the utility definitions, relative to the root file.
+    pub const UTILS_PATH: &'static str = "utils.pil";
+
+    /// The path of the file of the object at `location`, relative to the root file.
+    pub fn object_path(location: &Location) -> String {
+        format!("objects/{location}.pil")
+    }
+
+    /// Returns the root file, which includes the utility definitions and then all objects,
+    /// a machine before its submachines. This is the order in which `link` outputs them,
+    /// so analyzing the root file yields the same result as analyzing the output of `link`.
+    pub fn root(&self) -> PILFile {
+        let utils = (!self.utils.0.is_empty()).then(|| Self::UTILS_PATH.to_string());
+        PILFile(
+            utils
+                .into_iter()
+                .chain(self.objects.keys().map(Self::object_path))
+                .map(|path| PilStatement::Include(SourceRef::unknown(), path))
+                .collect(),
+        )
+    }
+
+    /// Writes the root file to `root` and all other files relative to its directory.
+    pub fn write_to(&self, root: &Path) -> io::Result<()> {
+        let dir = root.parent().unwrap_or(Path::new(""));
+        fs::create_dir_all(dir.join("objects"))?;
+        if !self.utils.0.is_empty() {
+            fs::write(dir.join(Self::UTILS_PATH), self.utils.to_string())?;
+        }
+        for (location, file) in &self.objects {
+            fs::write(dir.join(Self::object_path(location)), file.to_string())?;
+        }
+        fs::write(root, self.root().to_string())
+    }
+}
@@ -1,25 +1,89 @@
 #![deny(clippy::print_stdout)]
 
 use powdr_analysis::utils::parse_pil_statement;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
+    iter::once,
+};
+
 use powdr_ast::{
-    object::{Location, PILGraph},
+    object::{Link, Location, Object, Operation, PILGraph},
     parsed::{
-        asm::AbsoluteSymbolPath,
-        asm::SymbolPath,
+        asm::{AbsoluteSymbolPath, Params, SymbolPath},
         build::{direct_reference, index_access, namespaced_reference, next_reference},
-        Expression, PILFile, PilStatement, SelectedExpressions, TypedExpression,
+        types::{ArrayType, Type},
+        Expression, PILFile, PilStatement, Radix, SelectedExpressions, TypedExpression,
     },
     SourceRef,
 };
 
 use itertools::Itertools;
 
+mod error;
+mod files;
+
+pub use error::LinkError;
+pub use files::LinkedFiles;
+
 const DEFAULT_DEGREE: u64 = 1024;
 const MAIN_OPERATION_NAME: &str = "main";
+const CALL_SELECTORS: &str = "_call_selectors";
 
 /// a monolithic linker which outputs a single AIR
-/// It sets the degree of submachines to the degree of the main machine, and errors out if a submachine has an explicit degree which doesn't match the main one
-pub fn link(graph: PILGraph) -> Result<PILFile, Vec<String>> {
+/// Each submachine keeps its own degree, submachines without an explicit degree get their degree from their callers, see `infer_degrees`.
+/// Permutations require both machines to have the same degree. Lookups may go into a machine of any degree, but are
+/// rejected for now, as the PIL analyzer does not support namespaces of different degrees yet.
+/// Machines called from more than one machine get call selectors, see `assign_call_selectors`.
+/// Links are checked against the declarations of the operations they call before lowering, see `validate_links`.
+/// All errors in links are collected before failing.
+pub fn link(graph: PILGraph) -> Result<PILFile, Vec<LinkError>> {
+    let files = link_to_files(graph)?;
+    Ok(PILFile(
+        files
+            .utils
+            .0
+            .into_iter()
+            .chain(files.objects.into_values().flat_map(|file| file.0))
+            .collect(),
+    ))
+}
+
+/// Like `link`, but puts the PIL of every object into its own file and the utility
+/// definitions into a separate file, see `LinkedFiles`.
+pub fn link_to_files(mut graph: PILGraph) -> Result<LinkedFiles, Vec<LinkError>> {
+    let mut errors = vec![];
+    if let Err(e) = infer_degrees(&mut graph) {
+        errors.extend(e);
+    }
+    // links failing validation are reported and not lowered
+    let operations = operations(&graph);
+    for (location, object) in &mut graph.objects {
+        object
+            .links
+            .retain(|link| match validate_link(&operations, location, link) {
+                Ok(()) => true,
+                Err(e) => {
+                    errors.push(*e);
+                    false
+                }
+            });
+    }
+    assign_call_selectors(&mut graph);
+    let callers = callers(&graph);
+    // the call selector array and latch of each machine which uses call selectors
+    let call_selectors = graph
+        .objects
+        .values()
+        .flat_map(|object| &object.links)
+        .filter_map(|link| {
+            let machine = &link.to.machine;
+            Some((
+                machine.location.clone(),
+                (machine.call_selectors.clone()?, machine.latch.clone()?),
+            ))
+        })
+        .collect::<BTreeMap<_, _>>();
+
     let main_machine = graph.main;
     let main_degree = graph
         .objects
@@ -28,11 +92,32 @@ pub fn link(graph: PILGraph) -> Result<PILFile, Vec<String>> {
         .degree
         .unwrap_or(DEFAULT_DEGREE);
 
-    let mut errors = vec![];
+    let degrees = graph
+        .objects
+        .iter()
+        .map(|(location, object)| (location.clone(), object.degree.unwrap_or(main_degree)))
+        .collect::<BTreeMap<_, _>>();
+
+    // the symbols defined in each machine
+    let definitions = graph
+        .objects
+        .iter()
+        .map(|(location, object)| {
+            (
+                location.clone(),
+                object
+                    .pil
+                    .iter()
+                    .flat_map(|s| s.symbol_definition_names())
+                    .cloned()
+                    .collect::<HashSet<_>>(),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
 
     // Extract the utilities and sort them into namespaces where possible.
     let mut current_namespace = Default::default();
-    let mut pil = graph
+    let utils = graph
         .definitions
         .into_iter()
         .sorted_by_cached_key(|(namespace, _)| {
@@ -57,7 +142,7 @@ pub fn link(graph: PILGraph) -> Result<PILFile, Vec<String>> {
                     PilStatement::Namespace(
                         SourceRef::unknown(),
                         namespace.relative_to(&AbsoluteSymbolPath::default()),
-                        Expression::Number(main_degree.into(), None),
+                        Expression::Number(main_degree.into(), None, Radix::Decimal),
                     ),
                     def,
                 ]
@@ -66,143 +151,644 @@ pub fn link(graph: PILGraph) -> Result<PILFile, Vec<String>> {
             }
         })
         .collect::<Vec<_>>();
-    pil.extend(graph.objects.into_iter().flat_map(|(location, object)| {
-        let mut pil = vec![];
-
-        if let Some(degree) = object.degree {
-            if degree != main_degree {
-                errors.push(format!(
-                    "Machine {location} should have degree {main_degree}, found {}",
-                    degree
-                ))
+    let objects = graph
+        .objects
+        .into_iter()
+        .map(|(location, object)| {
+            let mut pil = vec![];
+            let degree = degrees[&location];
+
+            // create a namespace for this object
+            pil.push(PilStatement::Namespace(
+                SourceRef::unknown(),
+                SymbolPath::from_identifier(location.to_string()),
+                Expression::Number(degree.into(), None, Radix::Decimal),
+            ));
+            let links = merge_links(object.links, &object.pil);
+            pil.extend(object.pil);
+            if let Some((selectors, latch)) = call_selectors.get(&location) {
+                pil.extend(call_selector_constraints(
+                    selectors,
+                    callers[&location].len(),
+                    latch,
+                ));
+            }
+            for (link, _) in links {
+                // add the link to this namespace as a lookup or permutation
+                match link_to_identity(&location, link, &degrees, &definitions) {
+                    Ok(identity) => pil.push(identity),
+                    Err(e) => errors.push(*e),
+                }
             }
-        }
 
-        // create a namespace for this object
-        pil.push(PilStatement::Namespace(
-            SourceRef::unknown(),
-            SymbolPath::from_identifier(location.to_string()),
-            Expression::Number(main_degree.into(), None),
-        ));
-        pil.extend(object.pil);
-        for link in object.links {
-            // add the link to this namespace as a lookup
+            if location == main_machine.location {
+                if let Some(main_operation) = graph
+                    .entry_points
+                    .iter()
+                    .find(|f| f.name == MAIN_OPERATION_NAME)
+                {
+                    let main_operation_id = main_operation.id.clone();
+                    let operation_id = main_machine.operation_id.clone();
+                    match (operation_id, main_operation_id) {
+                        (Some(operation_id), Some(main_operation_id)) => {
+                            // call the main operation by initialising `operation_id` to that of the main operation
+                            let linker_first_step = "_linker_first_step";
+                            pil.extend([
+                                parse_pil_statement(&format!(
+                                    "col fixed {linker_first_step} = [1] + [0]*;"
+                                )),
+                                parse_pil_statement(&format!(
+                                "{linker_first_step} * ({operation_id} - {main_operation_id}) = 0;"
+                            )),
+                            ]);
+                        }
+                        (None, None) => {}
+                        _ => unreachable!(),
+                    }
+                }
+            }
 
-            let from = link.from;
-            let to = link.to;
+            (location, PILFile(pil))
+        })
+        .collect();
 
-            // the lhs is `instr_flag { operation_id, inputs, outputs }`
-            let op_id = to
-                .operation
-                .id
-                .iter()
-                .cloned()
-                .map(|n| Expression::Number(n, None));
-            let inputs = from
-                .params
-                .inputs
-                .into_iter()
-                .map(|i| {
-                    assert!(i.ty.is_none() || i.ty == Some("write".to_string()));
-                    (i.name, i.index)
-                })
-                .map(|(name, index)| index_access(direct_reference(name), index));
-            let outputs = from.params.outputs.into_iter().map(|p| match p.ty {
-                None => index_access(direct_reference(p.name), p.index),
-                // write register as output is mapped as a next ref in the plookup
-                Some(s) if s == "write" => index_access(next_reference(p.name), p.index),
-                _ => panic!(),
-            });
-            let lhs = SelectedExpressions {
-                selector: Some(from.flag),
-                expressions: op_id.chain(inputs).chain(outputs).collect(),
-            };
+    if !errors.is_empty() {
+        Err(errors)
+    } else {
+        Ok(LinkedFiles {
+            utils: PILFile(utils),
+            objects,
+        })
+    }
+}
 
-            // the rhs is `latch { operation_id, inputs, outputs }`
-            let to_namespace = to.machine.location.clone().to_string();
-            let op_id = to
-                .machine
-                .operation_id
-                .map(|oid| namespaced_reference(to_namespace.clone(), oid))
-                .into_iter();
-
-            let rhs = SelectedExpressions {
-                selector: Some(namespaced_reference(
-                    to_namespace.clone(),
-                    to.machine.latch.unwrap(),
-                )),
-                expressions: op_id
-                    .chain(to.operation.params.inputs_and_outputs().map(|i| {
-                        index_access(
-                            namespaced_reference(to_namespace.clone(), &i.name),
-                            i.index.clone(),
-                        )
-                    }))
-                    .collect(),
-            };
+/// Checks the parameters of every link against the declaration of the operation it calls.
+/// The operation is resolved in the operations of the callee by id if the link has one, and by name otherwise.
+/// Links to unknown machines are skipped, they are reported when lowering the links.
+pub fn validate_links(graph: &PILGraph) -> Result<(), Vec<LinkError>> {
+    let operations = operations(graph);
+    let errors = graph
+        .objects
+        .iter()
+        .flat_map(|(location, object)| object.links.iter().map(move |link| (location, link)))
+        .filter_map(|(location, link)| validate_link(&operations, location, link).err())
+        .map(|e| *e)
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
 
-            let lookup = PilStatement::PlookupIdentity(SourceRef::unknown(), lhs, rhs);
-            pil.push(lookup);
+/// Lets instances of the same machine type share their fixed columns: every instance with the same
+/// degree and the same fixed column definitions as an instance before it uses the fixed columns of
+/// that instance. Its fixed columns are replaced by intermediate columns referencing the shared ones,
+/// so its identities and the links into it stay the same, and only its witness columns are kept.
+/// Machines do not have constructor arguments, so identical definitions describe identical columns.
+/// Instances without a degree, or with fixed columns which are declared without a definition or
+/// through `let`, are not shared. Degrees have to be inferred before, see `infer_degrees`.
+pub fn share_fixed_columns(graph: &mut PILGraph) {
+    // the instances using the fixed columns of another instance
+    let mut sharing = vec![];
+    // the instances whose fixed columns are shared, with their type, degree and fixed columns
+    let mut shared: Vec<(&AbsoluteSymbolPath, u64, Vec<&PilStatement>, &Location)> = vec![];
+    for (location, object) in &graph.objects {
+        let (Some(degree), Some(fixed_columns)) = (object.degree, shareable_fixed_columns(object))
+        else {
+            continue;
+        };
+        match shared
+            .iter()
+            .find(|(ty, d, f, _)| *ty == &object.ty && *d == degree && f == &fixed_columns)
+        {
+            Some((_, _, _, owner)) => sharing.push((location.clone(), (*owner).clone())),
+            None => shared.push((&object.ty, degree, fixed_columns, location)),
         }
+    }
 
-        if location == Location::main() {
-            if let Some(main_operation) = graph
-                .entry_points
-                .iter()
-                .find(|f| f.name == MAIN_OPERATION_NAME)
+    for (location, owner) in sharing {
+        for statement in &mut graph.objects.get_mut(&location).unwrap().pil {
+            if let PilStatement::PolynomialConstantDefinition(source, name, _) = statement {
+                *statement = PilStatement::PolynomialDefinition(
+                    source.clone(),
+                    name.clone(),
+                    namespaced_reference(owner.to_string(), name.clone()),
+                );
+            }
+        }
+    }
+}
+
+/// Returns the fixed column definitions of an object, or `None` if it has none or any of them cannot be shared.
+fn shareable_fixed_columns(object: &Object) -> Option<Vec<&PilStatement>> {
+    let mut fixed_columns = vec![];
+    for statement in &object.pil {
+        match statement {
+            PilStatement::PolynomialConstantDefinition(..) => fixed_columns.push(statement),
+            PilStatement::PolynomialConstantDeclaration(..) => return None,
+            PilStatement::LetStatement(_, _, Some(type_scheme), _)
+                if is_column_type(&type_scheme.ty) =>
             {
-                let main_operation_id = main_operation.id.clone();
-                let operation_id = main_machine.operation_id.clone();
-                match (operation_id, main_operation_id) {
-                    (Some(operation_id), Some(main_operation_id)) => {
-                        // call the main operation by initialising `operation_id` to that of the main operation
-                        let linker_first_step = "_linker_first_step";
-                        pil.extend([
-                            parse_pil_statement(&format!(
-                                "col fixed {linker_first_step} = [1] + [0]*;"
-                            )),
-                            parse_pil_statement(&format!(
-                                "{linker_first_step} * ({operation_id} - {main_operation_id}) = 0;"
-                            )),
-                        ]);
-                    }
-                    (None, None) => {}
-                    _ => unreachable!(),
+                return None
+            }
+            _ => {}
+        }
+    }
+    (!fixed_columns.is_empty()).then_some(fixed_columns)
+}
+
+fn is_column_type(ty: &Type<Expression>) -> bool {
+    match ty {
+        Type::Col => true,
+        Type::Array(ArrayType { base, .. }) => base.as_ref() == &Type::Col,
+        _ => false,
+    }
+}
+
+/// Returns the operations declared by each machine.
+fn operations(graph: &PILGraph) -> BTreeMap<Location, Vec<Operation>> {
+    graph
+        .objects
+        .iter()
+        .map(|(location, object)| (location.clone(), object.operations.clone()))
+        .collect()
+}
+
+fn validate_link(
+    operations: &BTreeMap<Location, Vec<Operation>>,
+    location: &Location,
+    link: &Link,
+) -> Result<(), Box<LinkError>> {
+    let source = link.source.clone();
+    let caller = location.clone();
+    let callee = link.to.machine.location.clone();
+    let operation = link.to.operation.name.clone();
+
+    let Some(callee_operations) = operations.get(&callee) else {
+        return Ok(());
+    };
+    let declaration = match &link.to.operation.id {
+        Some(id) => callee_operations.iter().find(|o| o.id.as_ref() == Some(id)),
+        None => callee_operations.iter().find(|o| o.name == operation),
+    };
+    let Some(declaration) = declaration.cloned() else {
+        return Err(Box::new(LinkError::UnknownOperation {
+            source,
+            caller,
+            callee,
+            operation,
+            operation_id: link.to.operation.id.clone(),
+        }));
+    };
+
+    let params = &link.from.params;
+    let found = (params.inputs.len(), params.outputs.len());
+    let expected = (
+        declaration.params.inputs.len(),
+        declaration.params.outputs.len(),
+    );
+    if found != expected {
+        return Err(Box::new(LinkError::ParamCountMismatch {
+            source,
+            caller,
+            callee,
+            operation,
+            found,
+            expected,
+            declaration,
+        }));
+    }
+    // the `write` type only marks registers written to by the link, it is not a declared type
+    let mismatch = params
+        .inputs_and_outputs()
+        .zip(declaration.params.inputs_and_outputs())
+        .find_map(|(passed, declared)| match (&passed.ty, &declared.ty) {
+            (Some(found), Some(expected)) if found != "write" && found != expected => {
+                Some((declared.name.clone(), found.clone(), expected.clone()))
+            }
+            _ => None,
+        });
+    if let Some((param, found, expected)) = mismatch {
+        return Err(Box::new(LinkError::ParamTypeMismatch {
+            source,
+            caller,
+            callee,
+            operation,
+            param,
+            found,
+            expected,
+            declaration,
+        }));
+    }
+    // lowering only supports untyped parameters and `write`, even if the types match
+    if let Some((param, ty)) = unsupported_param_type(params, &declaration) {
+        return Err(Box::new(LinkError::UnsupportedParamType {
+            source,
+            caller,
+            callee,
+            operation,
+            param,
+            ty,
+            declaration,
+        }));
+    }
+    Ok(())
+}
+
+/// The caller a machine inherits its degree from, and the link it is inherited through.
+type Inheritance<'a> = (Location, &'a Link);
+
+/// Sets the degrees of the machines without an explicit degree:
+/// The main machine gets the default degree, and every other machine inherits the degree of
+/// the first machine calling into it, starting from the main machine.
+/// Machines which are not called keep no degree, the linker uses the degree of the main machine for them.
+/// Fails if two callers would pass different degrees to the same machine. In that case, the
+/// degrees are still set, using the degree inherited first.
+pub fn infer_degrees(graph: &mut PILGraph) -> Result<(), Vec<LinkError>> {
+    let main = graph.main.location.clone();
+    let Some(main_object) = graph.objects.get(&main) else {
+        return Ok(());
+    };
+    // the degree of each machine reached so far and, if it was inherited, the caller and link it was inherited through
+    let mut degrees: BTreeMap<Location, (u64, Option<Inheritance>)> = BTreeMap::new();
+    degrees.insert(
+        main.clone(),
+        (main_object.degree.unwrap_or(DEFAULT_DEGREE), None),
+    );
+    let mut errors = vec![];
+    let mut queue = VecDeque::from([main]);
+    while let Some(caller) = queue.pop_front() {
+        let caller_degree = degrees[&caller].0;
+        for link in &graph.objects[&caller].links {
+            let callee = &link.to.machine.location;
+            let Some(callee_object) = graph.objects.get(callee) else {
+                // reported when linking
+                continue;
+            };
+            match (callee_object.degree, degrees.get(callee)) {
+                (_, None) => {
+                    let inherited = callee_object
+                        .degree
+                        .is_none()
+                        .then(|| (caller.clone(), link));
+                    let degree = callee_object.degree.unwrap_or(caller_degree);
+                    degrees.insert(callee.clone(), (degree, inherited));
+                    queue.push_back(callee.clone());
+                }
+                (None, Some((degree, Some((first_caller, first_link)))))
+                    if *degree != caller_degree =>
+                {
+                    errors.push(LinkError::DegreeConflict {
+                        source: link.source.clone(),
+                        caller: caller.clone(),
+                        callee: callee.clone(),
+                        operation: link.to.operation.name.clone(),
+                        caller_degree,
+                        first_caller: first_caller.clone(),
+                        first_source: first_link.source.clone(),
+                        first_degree: *degree,
+                    });
                 }
+                _ => {}
             }
         }
+    }
 
-        pil
-    }));
+    let degrees = degrees
+        .into_iter()
+        .map(|(location, (degree, _))| (location, degree))
+        .collect::<Vec<_>>();
+    for (location, degree) in degrees {
+        graph.objects.get_mut(&location).unwrap().degree = Some(degree);
+    }
 
-    if !errors.is_empty() {
+    if errors.is_empty() {
+        Ok(())
+    } else {
         Err(errors)
+    }
+}
+
+/// Returns the machines calling into each machine, in the order of their locations.
+fn callers(graph: &PILGraph) -> BTreeMap<Location, Vec<Location>> {
+    graph
+        .objects
+        .values()
+        .flat_map(|object| &object.links)
+        .map(|link| &link.to.machine.location)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|callee| (callee.clone(), graph.callers_of(callee)))
+        .collect()
+}
+
+/// Assigns call selectors to the links into machines which declare call selectors,
+/// which are called from more than one machine or which are the target of a permutation,
+/// such that the called machine knows which caller is active in each row.
+/// Each calling machine gets its own selector, all its links into the machine use it.
+pub fn assign_call_selectors(graph: &mut PILGraph) {
+    let callers = callers(graph);
+    let permutation_targets = graph
+        .objects
+        .values()
+        .flat_map(|object| &object.links)
+        .filter(|link| link.is_permutation)
+        .map(|link| link.to.machine.location.clone())
+        .collect::<BTreeSet<_>>();
+    for (location, object) in &mut graph.objects {
+        for link in &mut object.links {
+            let machine = &mut link.to.machine;
+            let callers = &callers[&machine.location];
+            if machine.call_selectors.is_none()
+                && (callers.len() > 1 || permutation_targets.contains(&machine.location))
+            {
+                machine.call_selectors = Some(CALL_SELECTORS.to_string());
+            }
+            if machine.call_selectors.is_some() {
+                link.to.selector_index = callers.iter().position(|caller| caller == location);
+            }
+        }
+    }
+}
+
+/// Declares `count` call selectors and constrains them to be boolean and to sum up to the latch.
+fn call_selector_constraints(selectors: &str, count: usize, latch: &str) -> Vec<PilStatement> {
+    let sum = (0..count).map(|i| format!("{selectors}[{i}]")).join(" + ");
+    once(format!("pol commit {selectors}[{count}];"))
+        .chain((0..count).map(|i| format!("{selectors}[{i}] * (1 - {selectors}[{i}]) = 0;")))
+        .chain(once(format!("{sum} = {latch};")))
+        .map(|s| parse_pil_statement(&s))
+        .collect()
+}
+
+/// Turns a link from the machine at `location` into a lookup or permutation identity.
+fn link_to_identity(
+    location: &Location,
+    link: Link,
+    degrees: &BTreeMap<Location, u64>,
+    definitions: &BTreeMap<Location, HashSet<String>>,
+) -> Result<PilStatement, Box<LinkError>> {
+    let Link {
+        from,
+        to,
+        is_permutation,
+        source,
+    } = link;
+    let caller = location.clone();
+    let callee = to.machine.location.clone();
+    let operation = to.operation.name.clone();
+
+    let Some(&callee_degree) = degrees.get(&callee) else {
+        return Err(Box::new(LinkError::UnknownCallee {
+            source,
+            caller,
+            callee,
+            operation,
+        }));
+    };
+    let Some(latch) = to.machine.latch else {
+        return Err(Box::new(LinkError::MissingLatch {
+            source,
+            caller,
+            callee,
+            operation,
+        }));
+    };
+    if to.operation.id.is_some() != to.machine.operation_id.is_some() {
+        return Err(Box::new(LinkError::OperationIdMismatch {
+            source,
+            caller,
+            callee,
+            operation,
+            operation_id: to.operation.id,
+            callee_operation_id: to.machine.operation_id,
+        }));
+    }
+    let caller_degree = degrees[location];
+    // a permutation covers all rows of both machines, so they need to be of the same size
+    if is_permutation && caller_degree != callee_degree {
+        return Err(Box::new(LinkError::PermutationDegreeMismatch {
+            source,
+            caller,
+            callee,
+            operation,
+            caller_degree,
+            callee_degree,
+        }));
+    }
+    // TODO remove once the PIL analyzer supports namespaces of different degrees
+    if caller_degree != callee_degree {
+        return Err(Box::new(LinkError::DegreeMismatch {
+            source,
+            caller,
+            callee,
+            operation,
+            caller_degree,
+            callee_degree,
+        }));
+    }
+    // the call selectors gating a permutation sum up to the latch, so it has to be a column of the callee
+    if is_permutation && !definitions[&callee].contains(&latch) {
+        return Err(Box::new(LinkError::IncompatibleLatch {
+            source,
+            caller,
+            callee,
+            operation,
+            latch,
+        }));
+    }
+
+    if let Some((param, ty)) = unsupported_param_type(&from.params, &to.operation) {
+        return Err(Box::new(LinkError::UnsupportedParamType {
+            source,
+            caller,
+            callee,
+            operation,
+            param,
+            ty,
+            declaration: to.operation,
+        }));
+    }
+
+    // the lhs is `instr_flag { operation_id, inputs, outputs }`
+    let op_id = to
+        .operation
+        .id
+        .iter()
+        .cloned()
+        .map(|n| Expression::Number(n, None, Radix::Decimal));
+    let inputs = from
+        .params
+        .inputs
+        .into_iter()
+        .map(|i| index_access(direct_reference(i.name), i.index));
+    // the only type left is `write`, a write register as output is mapped as a next ref in the plookup
+    let outputs = from.params.outputs.into_iter().map(|p| match p.ty {
+        None => index_access(direct_reference(p.name), p.index),
+        Some(_) => index_access(next_reference(p.name), p.index),
+    });
+    let lhs = SelectedExpressions {
+        selector: Some(from.flag),
+        expressions: op_id.chain(inputs).chain(outputs).collect(),
+    };
+
+    // the rhs is `latch { operation_id, inputs, outputs }`
+    let to_namespace = callee.to_string();
+    let op_id = to
+        .machine
+        .operation_id
+        .map(|oid| namespaced_reference(to_namespace.clone(), oid))
+        .into_iter();
+
+    // the selector is the call selector of the caller if there is one, otherwise the latch
+    let selector = match (to.machine.call_selectors, to.selector_index) {
+        (Some(selectors), Some(index)) => index_access(
+            namespaced_reference(to_namespace.clone(), selectors),
+            Some(index.into()),
+        ),
+        _ => namespaced_reference(to_namespace.clone(), latch),
+    };
+    let rhs = SelectedExpressions {
+        selector: Some(selector),
+        expressions: op_id
+            .chain(to.operation.params.inputs_and_outputs().map(|i| {
+                index_access(
+                    namespaced_reference(to_namespace.clone(), &i.name),
+                    i.index.clone(),
+                )
+            }))
+            .collect(),
+    };
+
+    Ok(if is_permutation {
+        PilStatement::PermutationIdentity(SourceRef::unknown(), lhs, rhs)
     } else {
-        Ok(PILFile(pil))
+        PilStatement::PlookupIdentity(SourceRef::unknown(), lhs, rhs)
+    })
+}
+
+/// Returns the first parameter passed by a link with a type other than `write`, with the name
+/// it has in the declaration of the called operation and its type. Such parameters cannot be lowered.
+fn unsupported_param_type(params: &Params, declaration: &Operation) -> Option<(String, String)> {
+    params
+        .inputs_and_outputs()
+        .zip(declaration.params.inputs_and_outputs())
+        .find_map(|(passed, declared)| match &passed.ty {
+            Some(ty) if ty != "write" => Some((declared.name.clone(), ty.clone())),
+            _ => None,
+        })
+}
+
+/// Links from a machine to an operation of a submachine that are
+/// combined into a single lookup by `link`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedLinks {
+    /// The location of the machine the links start from.
+    pub location: Location,
+    /// The combined link, its flag is the sum of the original flags.
+    pub link: Link,
+    /// The flags of the original links.
+    pub flags: Vec<Expression>,
+}
+
+/// Returns the links that are merged by `link`, for inspection.
+pub fn merged_links(graph: &PILGraph) -> Vec<MergedLinks> {
+    graph
+        .objects
+        .iter()
+        .flat_map(|(location, object)| {
+            merge_links(object.links.clone(), &object.pil)
+                .into_iter()
+                .filter(|(_, flags)| flags.len() > 1)
+                .map(|(link, flags)| MergedLinks {
+                    location: location.clone(),
+                    link,
+                    flags,
+                })
+        })
+        .collect()
+}
+
+/// Combines links to the same operation with the same parameters into a single link
+/// whose flag is the sum of their flags, such that only one lookup is needed.
+/// This is only done if the flags are mutually exclusive, which we know for the flags
+/// of instructions: The ROM activates at most one instruction in each row.
+/// Returns the links in the order of their first occurrence, each with the original flags.
+fn merge_links(links: Vec<Link>, pil: &[PilStatement]) -> Vec<(Link, Vec<Expression>)> {
+    let rom_flags = pil
+        .iter()
+        .filter_map(|s| match s {
+            PilStatement::PolynomialConstantDefinition(_, name, _) => name.strip_prefix("p_"),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    let is_instruction_flag = |flag: &Expression| match flag {
+        Expression::Reference(r) => r
+            .try_to_identifier()
+            .is_some_and(|name| name.starts_with("instr_") && rom_flags.contains(name.as_str())),
+        _ => false,
+    };
+
+    let mut merged: Vec<(Link, Vec<Expression>)> = vec![];
+    for link in links {
+        let flag = link.from.flag.clone();
+        let group = is_instruction_flag(&flag)
+            .then(|| {
+                merged.iter_mut().find(|(other, flags)| {
+                    other.to == link.to
+                        && !other.is_permutation
+                        && !link.is_permutation
+                        && other.from.params == link.from.params
+                        && flags.iter().all(|f| is_instruction_flag(f) && *f != flag)
+                })
+            })
+            .flatten();
+        match group {
+            Some((other, flags)) => {
+                other.from.flag = other.from.flag.clone() + flag.clone();
+                flags.push(flag);
+            }
+            None => merged.push((link, vec![flag])),
+        }
     }
+    merged
 }
 
 #[cfg(test)]
 mod test {
-    use std::fs;
+    use std::{collections::BTreeMap, fs};
 
     use powdr_ast::{
-        object::{Location, Object, PILGraph},
-        parsed::{Expression, PILFile},
+        asm_analysis::{AnalysisASMFile, Item},
+        object::{Link, Location, Object, PILGraph},
+        parsed::{
+            asm::parse_absolute_path, build::direct_reference, Expression, PILFile, Radix,
+            TypedExpression,
+        },
     };
     use powdr_number::{FieldElement, GoldilocksField};
 
+    use powdr_airgen::compile_with_entry_point;
     use powdr_analysis::convert_asm_to_pil;
     use powdr_parser::parse_asm;
+    use powdr_pil_analyzer::{analyze_file, analyze_string};
 
     use pretty_assertions::assert_eq;
 
-    use crate::{link, DEFAULT_DEGREE};
+    use crate::{
+        assign_call_selectors, infer_degrees, link, link_to_files, link_to_identity, merged_links,
+        share_fixed_columns, validate_links, LinkError, DEFAULT_DEGREE,
+    };
 
-    fn parse_analyse_and_compile<T: FieldElement>(input: &str) -> PILGraph {
+    fn parse_and_analyse<T: FieldElement>(input: &str) -> AnalysisASMFile {
         let parsed = parse_asm(None, input).unwrap();
         let resolved = powdr_importer::load_dependencies_and_resolve(None, parsed).unwrap();
-        powdr_airgen::compile(convert_asm_to_pil::<T>(resolved).unwrap())
+        convert_asm_to_pil::<T>(resolved).unwrap()
+    }
+
+    fn parse_analyse_and_compile<T: FieldElement>(input: &str) -> PILGraph {
+        powdr_airgen::compile(parse_and_analyse::<T>(input)).unwrap()
     }
 
     #[test]
@@ -213,13 +799,14 @@ mod test {
                 location: Location::main(),
                 operation_id: Some("operation_id".into()),
                 latch: Some("latch".into()),
+                call_selectors: None,
             },
             entry_points: vec![],
             definitions: Default::default(),
             objects: [
                 (Location::main(), Object::default().with_degree(main_degree)),
                 (
-                    Location::main().join("foo"),
+                    "main::foo".parse().unwrap(),
                     Object::default().with_degree(foo_degree),
                 ),
             ]
@@ -230,7 +817,7 @@ mod test {
         let all_namespaces_have_degree = |f: PILFile, n: u64| {
             f.0.iter().all(|s| match s {
                 powdr_ast::parsed::PilStatement::Namespace(_, _, e) => {
-                    *e == Expression::Number(n.into(), None)
+                    matches!(e, Expression::Number(value, None, _) if *value == n.into())
                 }
                 _ => true,
             })
@@ -245,18 +832,983 @@ mod test {
             link(default_infer).unwrap(),
             1024
         ));
-        let default_no_match: PILGraph = test_graph(None, Some(8));
+        let different: PILGraph = test_graph(None, Some(8));
+        let namespace_degrees = link(different)
+            .unwrap()
+            .0
+            .into_iter()
+            .filter_map(|s| match s {
+                powdr_ast::parsed::PilStatement::Namespace(_, name, degree) => {
+                    Some((name.to_string(), degree.to_string()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
         assert_eq!(
-            link(default_no_match),
-            Err(vec![
-                "Machine main_foo should have degree 1024, found 8".to_string()
-            ])
+            namespace_degrees,
+            [
+                ("main".to_string(), "1024".to_string()),
+                ("main_foo".to_string(), "8".to_string())
+            ]
         );
     }
 
-    fn extract_main(code: &str) -> &str {
-        let start = code.find("namespace main").unwrap();
-        &code[start..]
+    const CROSS_DEGREE_SOURCE: &str = r#"
+machine Arith(latch, operation_id) {
+    degree 8;
+
+    operation add<0> x, y -> z;
+    col witness operation_id;
+    col fixed latch = [1]*;
+    col witness x;
+    col witness y;
+    col witness z;
+    z = x + y;
+}
+
+machine Main {
+    degree 16;
+
+    Arith arith;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg A;
+
+    instr add X, Y -> Z = arith.add;
+
+    function main {
+        A <== add(2, 1);
+        return;
+    }
+}
+"#;
+
+    /// `CROSS_DEGREE_SOURCE` with both machines of degree 16, as machines of different degrees cannot be linked yet.
+    fn same_degree_source() -> String {
+        CROSS_DEGREE_SOURCE.replace("degree 8;", "degree 16;")
+    }
+
+    #[test]
+    fn lookup_into_smaller_machine() {
+        let graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let display = graph.to_string();
+        assert!(
+            display.contains("// Object main_arith\n// Defined at input:2:0\n// Degree 8\n"),
+            "{display}"
+        );
+        let mut written = vec![];
+        graph.write_pil(&mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), display);
+        assert!(
+            display.contains(" in object main_arith with latch: latch, operation_id: operation_id (declared at input:25:4)\n"),
+            "{display}"
+        );
+        let errors = link(graph).unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["input:25:4: Link from main to operation add of main_arith: machines of different degrees cannot be linked yet, but main has degree 16 and main_arith has degree 8"]
+        );
+
+        let graph = parse_analyse_and_compile::<GoldilocksField>(&same_degree_source());
+        let linked = link(graph).unwrap();
+        let pil = linked.to_string();
+        let mut written = vec![];
+        linked.write_pil(&mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), pil);
+        assert!(pil.contains("namespace main(16);"), "{pil}");
+        assert!(pil.contains("namespace main_arith(16);"), "{pil}");
+        assert!(
+            pil.contains("    instr_add { 0, X, Y, Z } in main_arith.latch { main_arith.operation_id, main_arith.x, main_arith.y, main_arith.z };"),
+            "{pil}"
+        );
+    }
+
+    #[test]
+    fn permutation_requires_equal_degrees() {
+        let with_permutations = |mut graph: PILGraph| {
+            for object in graph.objects.values_mut() {
+                for link in &mut object.links {
+                    link.is_permutation = true;
+                }
+            }
+            graph
+        };
+
+        let graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let errors = link(with_permutations(graph)).unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [LinkError::PermutationDegreeMismatch {
+                caller_degree: 16,
+                callee_degree: 8,
+                ..
+            }]
+        ));
+        assert_eq!(
+            errors[0].to_string(),
+            "input:25:4: Link from main to operation add of main_arith: a permutation from main (degree 16) to main_arith (degree 8) requires both machines to have the same degree"
+        );
+
+        let graph = parse_analyse_and_compile::<GoldilocksField>(&same_degree_source());
+        let pil = link(with_permutations(graph)).unwrap().to_string();
+        assert!(
+            pil.contains("    instr_add { 0, X, Y, Z } is main_arith._call_selectors[0] { main_arith.operation_id, main_arith.x, main_arith.y, main_arith.z };"),
+            "{pil}"
+        );
+    }
+
+    #[test]
+    fn degree_inherited_from_caller() {
+        let source = CROSS_DEGREE_SOURCE.replace("    degree 8;\n", "");
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(&source);
+        infer_degrees(&mut graph).unwrap();
+        let display = graph.to_string();
+        assert!(
+            display.contains("// Object main_arith\n// Defined at input:2:0\n// Degree 16\n"),
+            "{display}"
+        );
+        let pil = link(graph).unwrap().to_string();
+        assert!(pil.contains("namespace main_arith(16);"), "{pil}");
+
+        // declared degrees are kept
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        infer_degrees(&mut graph).unwrap();
+        assert_eq!(
+            graph.objects[&"main::arith".parse().unwrap()].degree,
+            Some(8)
+        );
+    }
+
+    #[test]
+    fn conflicting_inherited_degrees() {
+        let source = CROSS_DEGREE_SOURCE.replace("    degree 8;\n", "");
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(&source);
+        // add a second machine of degree 8 calling into `main_arith`, called by `main`
+        let other: Location = "main::other".parse().unwrap();
+        let links = graph.objects[&Location::main()].links.clone();
+        let mut link_to_other = links[0].clone();
+        link_to_other.to.machine.location = other.clone();
+        graph.objects.insert(
+            other,
+            Object {
+                degree: Some(8),
+                links,
+                ..Default::default()
+            },
+        );
+        graph
+            .objects
+            .get_mut(&Location::main())
+            .unwrap()
+            .links
+            .push(link_to_other);
+
+        let errors = infer_degrees(&mut graph).unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["input:24:4: Link from main_other to operation add of main_arith: machine main_arith does not declare a degree and gets degree 8 from main_other, but degree 16 from main (at input:24:4)"]
+        );
+        assert_eq!(
+            graph.objects[&"main::arith".parse().unwrap()].degree,
+            Some(16)
+        );
+    }
+
+    #[test]
+    fn shared_fixed_columns() {
+        let source = same_degree_source()
+            .replace(
+                "    Arith arith;\n",
+                "    Arith arith;\n    Arith arith2;\n",
+            )
+            .replace(
+                "    instr add X, Y -> Z = arith.add;\n",
+                "    instr add X, Y -> Z = arith.add;\n    instr add2 X, Y -> Z = arith2.add;\n",
+            );
+        let graph = parse_analyse_and_compile::<GoldilocksField>(&source);
+
+        // sharing is opt-in
+        let pil = link(graph.clone()).unwrap().to_string();
+        assert_eq!(
+            pil.matches("pol constant latch = [1]*;").count(),
+            2,
+            "{pil}"
+        );
+
+        let mut shared = graph.clone();
+        share_fixed_columns(&mut shared);
+        let pil = link(shared).unwrap().to_string();
+        for expected in [
+            "namespace main_arith(16);\n    pol commit operation_id;\n    pol constant latch = [1]*;\n",
+            "namespace main_arith2(16);\n    pol commit operation_id;\n    pol latch = main_arith.latch;\n    pol commit x;\n",
+            "    instr_add2 { 0, X, Y, Z } in main_arith2.latch { main_arith2.operation_id, main_arith2.x, main_arith2.y, main_arith2.z };",
+        ] {
+            assert!(pil.contains(expected), "{expected}\n{pil}");
+        }
+
+        // instances of different degrees are not shared
+        let mut different = graph;
+        different
+            .objects
+            .get_mut(&"main::arith2".parse().unwrap())
+            .unwrap()
+            .degree = Some(8);
+        let unshared = different.clone();
+        share_fixed_columns(&mut different);
+        assert_eq!(different, unshared);
+    }
+
+    #[test]
+    fn permutation_link() {
+        let file_name = format!(
+            "{}/../test_data/asm/block_to_block_permutation.asm",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let contents = fs::read_to_string(file_name).unwrap();
+        let graph = parse_analyse_and_compile::<GoldilocksField>(&contents);
+        let display = graph.to_string();
+        assert!(
+            display.contains("// instr_add x, y -> z links to operation add<0> x, y -> z in object main_arith with latch: latch, operation_id: operation_id as a permutation"),
+            "{display}"
+        );
+
+        let pil = link(graph).unwrap().to_string();
+        let expectation = r#"namespace main_arith(8);
+    pol constant operation_id = [0]*;
+    pol constant latch = [1]*;
+    pol commit x;
+    pol commit y;
+    pol commit z;
+    (z = (x + y));
+    pol commit _call_selectors[1];
+    ((_call_selectors[0] * (1 - _call_selectors[0])) = 0);
+    (_call_selectors[0] = latch);
+"#;
+        assert!(pil.contains(expectation), "{pil}");
+        assert!(
+            pil.contains("    instr_add { 0, x, y, z } is main_arith._call_selectors[0] { main_arith.operation_id, main_arith.x, main_arith.y, main_arith.z };"),
+            "{pil}"
+        );
+    }
+
+    #[test]
+    fn permutation_needs_latch_column() {
+        let file_name = format!(
+            "{}/../test_data/asm/block_to_block_permutation.asm",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let contents = fs::read_to_string(file_name).unwrap();
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(&contents);
+        let links = &mut graph.objects.get_mut(&Location::main()).unwrap().links;
+        links[0].to.machine.latch = Some("undefined_latch".into());
+        let errors = link(graph).unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [LinkError::IncompatibleLatch { latch, .. }] if latch == "undefined_latch"
+        ));
+        assert_eq!(
+            errors[0].to_string(),
+            "input:25:4: Link from main to operation add of main_arith: the latch undefined_latch of a permutation target has to be a column defined in machine main_arith"
+        );
+    }
+
+    /// Links the graph of `CROSS_DEGREE_SOURCE` after breaking its link with `f`.
+    fn link_errors_with_broken_link(f: impl Fn(&mut Link)) -> Vec<LinkError> {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let links = &mut graph.objects.get_mut(&Location::main()).unwrap().links;
+        assert_eq!(links.len(), 1);
+        f(&mut links[0]);
+        link(graph).unwrap_err()
+    }
+
+    #[test]
+    fn unknown_callee() {
+        let errors = link_errors_with_broken_link(|link| {
+            link.to.machine.location = "main::binary".parse().unwrap();
+        });
+        assert_eq!(errors[0].caller(), &Location::main());
+        assert_eq!(errors[0].callee(), &"main::binary".parse().unwrap());
+        assert_eq!(errors[0].operation(), "add");
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["input:25:4: Link from main to operation add of main_binary: there is no machine at location main_binary"]
+        );
+    }
+
+    #[test]
+    fn missing_latch() {
+        let errors = link_errors_with_broken_link(|link| link.to.machine.latch = None);
+        assert!(matches!(
+            errors.as_slice(),
+            [LinkError::MissingLatch { .. }]
+        ));
+        assert_eq!(
+            errors[0].to_string(),
+            "input:25:4: Link from main to operation add of main_arith: machine main_arith does not have a latch"
+        );
+    }
+
+    #[test]
+    fn operation_id_mismatch() {
+        let errors = link_errors_with_broken_link(|link| link.to.operation.id = None);
+        assert!(matches!(
+            errors.as_slice(),
+            [LinkError::OperationIdMismatch {
+                operation_id: None,
+                callee_operation_id: Some(_),
+                ..
+            }]
+        ));
+        assert_eq!(
+            errors[0].to_string(),
+            "input:25:4: Link from main to operation add of main_arith: the operation does not have an id, but machine main_arith has the operation id column operation_id"
+        );
+
+        let errors = link_errors_with_broken_link(|link| link.to.machine.operation_id = None);
+        assert_eq!(
+            errors[0].to_string(),
+            "input:25:4: Link from main to operation add of main_arith: the operation has id 0, but machine main_arith does not have an operation id column"
+        );
+    }
+
+    #[test]
+    fn param_count_mismatch() {
+        let errors = link_errors_with_broken_link(|link| {
+            link.from.params.inputs.pop();
+        });
+        assert!(matches!(
+            errors.as_slice(),
+            [LinkError::ParamCountMismatch {
+                found: (1, 1),
+                expected: (2, 1),
+                ..
+            }]
+        ));
+        assert_eq!(
+            errors[0].to_string(),
+            "input:25:4: Link from main to operation add of main_arith: the link passes 1 inputs and 1 outputs, but `operation add<0> x, y -> z` expects 2 inputs and 1 outputs"
+        );
+
+        let errors = link_errors_with_broken_link(|link| {
+            let output = link.from.params.outputs.pop().unwrap();
+            link.from.params.inputs.push(output);
+        });
+        assert_eq!(
+            errors[0].to_string(),
+            "input:25:4: Link from main to operation add of main_arith: the link passes 3 inputs and 0 outputs, but `operation add<0> x, y -> z` expects 2 inputs and 1 outputs (an output is passed where an input is expected)"
+        );
+    }
+
+    #[test]
+    fn unknown_operation() {
+        let errors = link_errors_with_broken_link(|link| link.to.operation.id = Some(1u32.into()));
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["input:25:4: Link from main to operation add of main_arith: machine main_arith does not have an operation with id 1"]
+        );
+
+        let errors = link_errors_with_broken_link(|link| {
+            link.to.operation.name = "sub".into();
+            link.to.operation.id = None;
+        });
+        assert_eq!(
+            errors[0].to_string(),
+            "input:25:4: Link from main to operation sub of main_arith: machine main_arith does not have an operation sub"
+        );
+    }
+
+    #[test]
+    fn operation_resolved_by_id() {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let link = &mut graph.objects.get_mut(&Location::main()).unwrap().links[0];
+        link.to.operation.name = "plus".into();
+        link.from.params.inputs.pop();
+        assert_eq!(
+            validate_links(&graph).unwrap_err()[0].to_string(),
+            "input:25:4: Link from main to operation plus of main_arith: the link passes 1 inputs and 1 outputs, but `operation add<0> x, y -> z` expects 2 inputs and 1 outputs"
+        );
+    }
+
+    #[test]
+    fn param_type_mismatch() {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let arith = graph
+            .objects
+            .get_mut(&"main::arith".parse().unwrap())
+            .unwrap();
+        arith.operations[0].params.inputs[1].ty = Some("int".into());
+        // the types are only compared if both sides declare one
+        assert!(validate_links(&graph).is_ok());
+
+        let main = graph.objects.get_mut(&Location::main()).unwrap();
+        main.links[0].from.params.inputs[1].ty = Some("fe".into());
+        let errors = link(graph).unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["input:25:4: Link from main to operation add of main_arith: the link passes a value of type fe for parameter y of type int in `operation add<0> x, y: int -> z`"]
+        );
+    }
+
+    #[test]
+    fn typed_param_for_untyped_declaration() {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let main = graph.objects.get_mut(&Location::main()).unwrap();
+        main.links[0].from.params.outputs[0].ty = Some("fe".into());
+        let errors = validate_links(&graph).unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["input:25:4: Link from main to operation add of main_arith: the link passes parameter z of `operation add<0> x, y -> z` with type fe, but only untyped parameters and registers of type write are supported"]
+        );
+        assert_eq!(link(graph).unwrap_err(), errors);
+    }
+
+    #[test]
+    fn typed_param_matching_declaration() {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let arith = graph
+            .objects
+            .get_mut(&"main::arith".parse().unwrap())
+            .unwrap();
+        arith.operations[0].params.inputs[1].ty = Some("int".into());
+        let main = graph.objects.get_mut(&Location::main()).unwrap();
+        main.links[0].from.params.inputs[1].ty = Some("int".into());
+        let errors = validate_links(&graph).unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            ["input:25:4: Link from main to operation add of main_arith: the link passes parameter y of `operation add<0> x, y: int -> z` with type int, but only untyped parameters and registers of type write are supported"]
+        );
+        assert_eq!(link(graph).unwrap_err(), errors);
+    }
+
+    #[test]
+    fn written_registers_are_valid() {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(&same_degree_source());
+        let main = graph.objects.get_mut(&Location::main()).unwrap();
+        main.links[0].from.params.outputs[0].ty = Some("write".into());
+        assert!(validate_links(&graph).is_ok());
+        let pil = link(graph).unwrap().to_string();
+        assert!(pil.contains("Z' }"), "{pil}");
+    }
+
+    #[test]
+    fn unsupported_param_type_is_not_lowered() {
+        let graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let mut link = graph.objects[&Location::main()].links[0].clone();
+        link.from.params.inputs[0].ty = Some("fe".into());
+        let degrees = [(Location::main(), 16), ("main::arith".parse().unwrap(), 16)]
+            .into_iter()
+            .collect();
+        let error =
+            link_to_identity(&Location::main(), link, &degrees, &BTreeMap::new()).unwrap_err();
+        assert!(matches!(
+            *error,
+            LinkError::UnsupportedParamType { ref param, ref ty, .. } if param == "x" && ty == "fe"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "input:25:4: Link from main to operation add of main_arith: the link passes parameter x of `operation add<0> x, y -> z` with type fe, but only untyped parameters and registers of type write are supported"
+        );
+    }
+
+    #[test]
+    fn all_link_errors_are_collected() {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let links = &mut graph.objects.get_mut(&Location::main()).unwrap().links;
+        let mut second = links[0].clone();
+        second.from.flag = Expression::Number(1u32.into(), None, Radix::Decimal);
+        second.to.machine.latch = None;
+        links[0].from.params.outputs.clear();
+        links.push(second);
+        let errors = link(graph).unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                LinkError::ParamCountMismatch { .. },
+                LinkError::MissingLatch { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn call_selectors_for_several_callers() {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(&same_degree_source());
+        // add a second machine calling into `main_arith`
+        let links = graph.objects[&Location::main()].links.clone();
+        graph.objects.insert(
+            "main::other".parse().unwrap(),
+            Object {
+                links,
+                ..Default::default()
+            },
+        );
+
+        let mut assigned = graph.clone();
+        assign_call_selectors(&mut assigned);
+        let selector_indices = assigned
+            .objects
+            .values()
+            .flat_map(|object| &object.links)
+            .map(|link| {
+                (
+                    link.to.machine.call_selectors.clone(),
+                    link.to.selector_index,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            selector_indices,
+            [
+                (Some("_call_selectors".to_string()), Some(0)),
+                (Some("_call_selectors".to_string()), Some(1))
+            ]
+        );
+
+        let pil = link(graph).unwrap().to_string();
+        for expected in [
+            "    pol commit _call_selectors[2];\n",
+            "    ((_call_selectors[0] * (1 - _call_selectors[0])) = 0);\n",
+            "    ((_call_selectors[1] * (1 - _call_selectors[1])) = 0);\n",
+            "    ((_call_selectors[0] + _call_selectors[1]) = latch);\n",
+            "namespace main(16);",
+            "    instr_add { 0, X, Y, Z } in main_arith._call_selectors[0] { main_arith.operation_id, main_arith.x, main_arith.y, main_arith.z };",
+            "namespace main_other(16);\n    instr_add { 0, X, Y, Z } in main_arith._call_selectors[1] { main_arith.operation_id, main_arith.x, main_arith.y, main_arith.z };",
+        ] {
+            assert!(pil.contains(expected), "{expected}\n{pil}");
+        }
+    }
+
+    #[test]
+    fn declared_call_selectors() {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(&same_degree_source());
+        for link in &mut graph.objects.get_mut(&Location::main()).unwrap().links {
+            link.to.machine.call_selectors = Some("sel".into());
+        }
+        let pil = link(graph).unwrap().to_string();
+        for expected in [
+            "    pol commit sel[1];\n",
+            "    ((sel[0] * (1 - sel[0])) = 0);\n",
+            "    (sel[0] = latch);\n",
+            "    instr_add { 0, X, Y, Z } in main_arith.sel[0] { main_arith.operation_id, main_arith.x, main_arith.y, main_arith.z };",
+        ] {
+            assert!(pil.contains(expected), "{expected}\n{pil}");
+        }
+    }
+
+    #[test]
+    fn no_call_selectors_for_single_caller() {
+        let graph = parse_analyse_and_compile::<GoldilocksField>(&same_degree_source());
+        let pil = link(graph).unwrap().to_string();
+        assert!(!pil.contains("_call_selectors"), "{pil}");
+    }
+
+    #[test]
+    fn shared_std_helper_is_defined_once() {
+        let source = r#"
+mod arith {
+    use std::utils::force_bool;
+
+    machine Arith(latch, operation_id) {
+        operation add<0> x, y -> z;
+        col witness operation_id;
+        col fixed latch = [1]*;
+        col witness x;
+        col witness y;
+        col witness z;
+        col witness b;
+        force_bool(b);
+        z = x + y;
+    }
+}
+
+mod helpers {
+    let boolean = std::utils::force_bool;
+}
+
+use std::utils::force_bool as make_bool;
+
+machine Main {
+    degree 8;
+
+    arith::Arith arith;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg A;
+
+    col witness b;
+    make_bool(b);
+    col witness c;
+    helpers::boolean(c);
+
+    instr add X, Y -> Z = arith.add;
+
+    function main {
+        A <== add(2, 1);
+        return;
+    }
+}
+"#;
+        let graph = parse_analyse_and_compile::<GoldilocksField>(source);
+        let display = graph.to_string();
+        assert!(
+            display.contains("// Utilities in ::std::utils\n"),
+            "{display}"
+        );
+        assert!(
+            display.contains("\nlet<T: Add + FromLiteral> sum: int, (int -> T) -> T = "),
+            "{display}"
+        );
+
+        let pil = link(graph).unwrap().to_string();
+        let definitions = pil
+            .lines()
+            .filter(|l| l.contains("let force_bool"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            definitions,
+            ["    let force_bool: expr -> constr = (|c| ((c * (1 - c)) = 0));"]
+        );
+        // the definition is in its own namespace, with the full type scheme
+        let std_utils = &pil[pil.find("namespace std::utils(8);").unwrap()..];
+        let std_utils = &std_utils[..std_utils[1..].find("namespace").unwrap()];
+        assert!(std_utils.contains("    let force_bool"), "{std_utils}");
+        assert!(
+            std_utils.contains("    let<T: Add + FromLiteral> sum: int, (int -> T) -> T = "),
+            "{std_utils}"
+        );
+        // both machines use the same definition
+        assert_eq!(pil.matches("    std::utils::force_bool(b);").count(), 2);
+    }
+
+    #[test]
+    fn machine_helper_is_defined_once() {
+        let source = r#"
+machine Arith(latch, operation_id) {
+    operation double<0> x -> y;
+    col witness operation_id;
+    col fixed latch = [1]*;
+    let twice: expr -> expr = |e| e + e;
+    let four_times = |e| twice(twice(e));
+    let plus_x: expr -> expr = |e| e + x;
+    col witness x;
+    col witness y;
+    y = four_times(x) - twice(x);
+    y = plus_x(x);
+}
+
+machine Main {
+    degree 8;
+
+    Arith first;
+    Arith second;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg A;
+
+    instr double_first X -> Y = first.double;
+    instr double_second X -> Y = second.double;
+
+    function main {
+        A <== double_first(2);
+        A <== double_second(A);
+        return;
+    }
+}
+"#;
+        let graph = parse_analyse_and_compile::<GoldilocksField>(source);
+        let helpers = graph
+            .definitions
+            .keys()
+            .filter(|path| path.parts().next() == Some("Arith"))
+            .map(|path| path.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(helpers, ["::Arith::four_times", "::Arith::twice"]);
+        for object in graph
+            .objects
+            .values()
+            .filter(|o| o.ty.to_string() == "::Arith")
+        {
+            let pil = object
+                .pil
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(!pil.contains("let twice"), "{pil}");
+            // the helper references a column of the machine, so it stays there
+            assert!(pil.contains("let plus_x"), "{pil}");
+            assert!(
+                pil.contains("y = (Arith.four_times(x) - Arith.twice(x));"),
+                "{pil}"
+            );
+        }
+
+        let pil = link(graph).unwrap().to_string();
+        assert_eq!(pil.matches("let twice").count(), 1, "{pil}");
+        assert_eq!(pil.matches("let four_times").count(), 1, "{pil}");
+        assert_eq!(pil.matches("let plus_x").count(), 2, "{pil}");
+        let arith = &pil[pil.find("namespace Arith(8);").unwrap()..];
+        assert!(
+            arith.starts_with(
+                "namespace Arith(8);\n    let four_times = (|e| Arith.twice(Arith.twice(e)));\n    let twice: expr -> expr = (|e| (e + e));\n"
+            ),
+            "{pil}"
+        );
+    }
+
+    #[test]
+    fn conflicting_machine_helper() {
+        let source = r#"
+machine Arith(latch, operation_id) {
+    operation double<0> x -> y;
+    col witness operation_id;
+    col fixed latch = [1]*;
+    let twice: expr -> expr = |e| e + e;
+    col witness x;
+    col witness y;
+    y = twice(x);
+}
+"#;
+        let mut analyzed = parse_and_analyse::<GoldilocksField>(source);
+        analyzed.items.insert(
+            parse_absolute_path("::Arith::twice"),
+            Item::Expression(TypedExpression {
+                e: 2u32.into(),
+                type_scheme: None,
+            }),
+        );
+        assert_eq!(
+            compile_with_entry_point(analyzed, &parse_absolute_path("::Arith")).unwrap_err(),
+            "Conflicting definitions of ::Arith::twice: `2` and `(|e| (e + e))`"
+        );
+    }
+
+    #[test]
+    fn entry_point() {
+        let analyzed = parse_and_analyse::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+        let graph = compile_with_entry_point(analyzed, &parse_absolute_path("::Arith")).unwrap();
+        assert_eq!(graph.main.location, Location::root("Arith"));
+        assert_eq!(
+            graph.objects.keys().collect::<Vec<_>>(),
+            [&Location::root("Arith")]
+        );
+        let pil = link(graph).unwrap().to_string();
+        assert!(pil.contains("namespace Arith(8);\n"), "{pil}");
+        assert!(!pil.contains("namespace main"), "{pil}");
+    }
+
+    #[test]
+    fn invalid_entry_point() {
+        let error = |entry_point: &str| {
+            let analyzed = parse_and_analyse::<GoldilocksField>(CROSS_DEGREE_SOURCE);
+            compile_with_entry_point(analyzed, &parse_absolute_path(entry_point)).unwrap_err()
+        };
+        let missing = error("::Binary");
+        assert!(
+            missing.starts_with(
+                "Entry point ::Binary does not exist. Available machines: ::Arith, ::Main"
+            ),
+            "{missing}"
+        );
+        let not_a_machine = error("::std::utils::force_bool");
+        assert!(
+            not_a_machine.starts_with(
+                "Entry point ::std::utils::force_bool is not a machine. Available machines: ::Arith, ::Main"
+            ),
+            "{not_a_machine}"
+        );
+    }
+
+    #[test]
+    fn deterministic_output() {
+        let file_name = format!(
+            "{}/../test_data/asm/vm_to_block_to_block.asm",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let contents = fs::read_to_string(file_name).unwrap();
+        let link_program = || {
+            let graph = parse_analyse_and_compile::<GoldilocksField>(&contents);
+            (graph.to_string(), link(graph).unwrap().to_string())
+        };
+        assert_eq!(link_program(), link_program());
+    }
+
+    #[test]
+    fn machines_before_submachines() {
+        let file_name = format!(
+            "{}/../test_data/asm/vm_to_block_to_block.asm",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let contents = fs::read_to_string(file_name).unwrap();
+        let graph = parse_analyse_and_compile::<GoldilocksField>(&contents);
+        let pil = link(graph).unwrap().to_string();
+        let namespaces = pil
+            .lines()
+            .filter(|l| l.starts_with("namespace main"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            namespaces,
+            [
+                "namespace main(8);",
+                "namespace main_assert1(8);",
+                "namespace main_assert1_inc(8);"
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_file_output() {
+        let file_name = format!(
+            "{}/../test_data/asm/vm_to_block_to_block.asm",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let contents = fs::read_to_string(file_name).unwrap();
+        let graph = parse_analyse_and_compile::<GoldilocksField>(&contents);
+        let monolithic = link(graph.clone()).unwrap();
+        let files = link_to_files(graph).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root.pil");
+        files.write_to(&root).unwrap();
+        assert_eq!(
+            fs::read_to_string(&root).unwrap(),
+            r#"include "utils.pil";
+include "objects/main.pil";
+include "objects/main_assert1.pil";
+include "objects/main_assert1_inc.pil";
+"#
+        );
+        assert!(
+            fs::read_to_string(dir.path().join("objects/main_assert1.pil"))
+                .unwrap()
+                .starts_with("namespace main_assert1(8);")
+        );
+
+        let analyzed = analyze_file::<GoldilocksField>(&root);
+        let expected = analyze_string::<GoldilocksField>(&monolithic.to_string());
+        assert_eq!(analyzed.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn links_in_declaration_order() {
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(&same_degree_source());
+        let links = &mut graph.objects.get_mut(&Location::main()).unwrap().links;
+        let mut second = links[0].clone();
+        second.from.flag = direct_reference("instr_second");
+        let mut third = links[0].clone();
+        third.from.flag = direct_reference("instr_third");
+        links.insert(0, second);
+        links.push(third);
+        let pil = link(graph).unwrap().to_string();
+        let flags = pil
+            .lines()
+            .filter(|l| l.contains(" in main_arith.latch "))
+            .map(|l| l.split_whitespace().next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(flags, ["instr_second", "instr_add", "instr_third"]);
+    }
+
+    fn extract_main(code: &str) -> &str {
+        let start = code.find("namespace main").unwrap();
+        &code[start..]
+    }
+
+    #[test]
+    fn serialize_graph() {
+        let file_name = format!(
+            "{}/../test_data/asm/different_signatures.asm",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let contents = fs::read_to_string(file_name).unwrap();
+        let graph = parse_analyse_and_compile::<GoldilocksField>(&contents);
+        assert!(graph.objects.len() > 1 && !graph.entry_points.is_empty());
+        let json = serde_json::to_string(&graph).unwrap();
+        let deserialized: PILGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, graph);
+        assert_eq!(link(deserialized).unwrap(), link(graph).unwrap());
+    }
+
+    #[test]
+    fn interface_description() {
+        let path = |name: &str| format!("{}/../test_data/asm/{name}", env!("CARGO_MANIFEST_DIR"));
+        let contents = fs::read_to_string(path("block_to_block.asm")).unwrap();
+        let mut graph = parse_analyse_and_compile::<GoldilocksField>(&contents);
+        infer_degrees(&mut graph).unwrap();
+        let mut json = vec![];
+        graph.interface_description().write_json(&mut json).unwrap();
+        let expected = fs::read_to_string(path("block_to_block.interface.json")).unwrap();
+        assert_eq!(String::from_utf8(json).unwrap(), expected.trim_end());
+    }
+
+    #[test]
+    fn merge_links_to_same_operation() {
+        let source = r#"
+machine Arith(latch, operation_id) {
+    operation add<0> x, y -> z;
+    col witness operation_id;
+    col fixed latch = [1]*;
+    col witness x;
+    col witness y;
+    col witness z;
+    z = x + y;
+}
+
+machine Main {
+    degree 8;
+
+    Arith arith;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg A;
+
+    instr add X, Y -> Z = arith.add;
+    instr plus X, Y -> Z = arith.add;
+    instr add_swapped Y, X -> Z = arith.add;
+
+    function main {
+        A <== add(2, 1);
+        A <== plus(A, 1);
+        A <== add_swapped(A, 1);
+        return;
+    }
+}
+"#;
+        let graph = parse_analyse_and_compile::<GoldilocksField>(source);
+        let merged = merged_links(&graph);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].location, Location::main());
+        assert_eq!(
+            merged[0]
+                .flags
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>(),
+            ["instr_add", "instr_plus"]
+        );
+        assert_eq!(merged[0].link.to.operation.name, "add");
+
+        let pil = link(graph).unwrap().to_string();
+        let lookups = pil
+            .lines()
+            .filter(|l| l.contains(" in main_arith.latch "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lookups,
+            [
+                "    (instr_add + instr_plus) { 0, X, Y, Z } in main_arith.latch { main_arith.operation_id, main_arith.x, main_arith.y, main_arith.z };",
+                "    instr_add_swapped { 0, Y, X, Z } in main_arith.latch { main_arith.operation_id, main_arith.x, main_arith.y, main_arith.z };"
+            ]
+        );
     }
 
     #[test]
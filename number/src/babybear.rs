@@ -0,0 +1,110 @@
+use ark_ff::{Fp64, MontBackend, MontConfig};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(MontConfig)]
+#[modulus = "2013265921"]
+#[generator = "31"]
+pub struct BabyBearBaseFieldConfig;
+pub type BabyBearBaseField = Fp64<MontBackend<BabyBearBaseFieldConfig, 1>>;
+
+powdr_field!(BabyBearField, BabyBearBaseField);
+
+#[cfg(test)]
+mod test {
+    use crate::traits::int_from_hex_str;
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn modulus() {
+        assert_eq!(BabyBearField::BITS, 31);
+        assert_eq!(BabyBearField::modulus(), 2013265921u64.into());
+        let minus_one = BabyBearField::from(0) - BabyBearField::from(1);
+        assert_eq!(
+            minus_one.to_arbitrary_integer(),
+            BigUint::from(2013265920u32)
+        );
+    }
+
+    #[test]
+    fn bitwise() {
+        let n = int_from_hex_str::<BabyBearField>("00ff00ff");
+        let p = int_from_hex_str::<BabyBearField>("000ff00f");
+        let n_shr_4 = int_from_hex_str::<BabyBearField>("000ff00f");
+        let n_shl_4 = int_from_hex_str::<BabyBearField>("0ff00ff0");
+        let n_or_p = int_from_hex_str::<BabyBearField>("00fff0ff");
+        let n_and_p = int_from_hex_str::<BabyBearField>("000f000f");
+        let n_xor_p = int_from_hex_str::<BabyBearField>("00f0f0f0");
+
+        assert_eq!(n.not().not(), n);
+        assert_eq!(n >> 4, n_shr_4);
+        assert_eq!(n << 4, n_shl_4);
+        assert_eq!(n & p, n_and_p);
+        assert_eq!(n | p, n_or_p);
+        assert_eq!(n ^ p, n_xor_p);
+    }
+
+    #[test]
+    fn inverse() {
+        let half = BabyBearField::from(1) / BabyBearField::from(2);
+        assert_eq!(half.to_arbitrary_integer(), BigUint::from(1006632961u32));
+        let x = BabyBearField::from(123456789);
+        assert_eq!(x * (BabyBearField::from(1) / x), BabyBearField::from(1));
+        assert_eq!(
+            BabyBearField::from(-1).pow(2u64.into()),
+            BabyBearField::from(1)
+        );
+    }
+
+    #[test]
+    fn lower_half() {
+        let x = BabyBearField::from(0);
+        assert!(x.is_in_lower_half());
+        assert!(!(x - 1.into()).is_in_lower_half());
+
+        let y = BabyBearField::from_str_radix("3c000000", 16).unwrap();
+        assert!(y.is_in_lower_half());
+        assert!(!(y + 1.into()).is_in_lower_half());
+    }
+
+    #[test]
+    fn conversion() {
+        let max = BigUint::from(2013265920u32);
+        assert_eq!(
+            BabyBearField::checked_from(max.clone())
+                .unwrap()
+                .to_arbitrary_integer(),
+            max
+        );
+        assert_eq!(BabyBearField::checked_from(max + BigUint::from(1u32)), None);
+        assert_eq!(
+            BabyBearField::from_str("2013265921").unwrap_err(),
+            "Decimal number \"2013265921\" too large for field."
+        );
+        assert_eq!(
+            BabyBearField::from_str_radix("78000001", 16).unwrap_err(),
+            "Hexadecimal number \"0x78000001\" too large for field."
+        );
+        let x = BabyBearField::from(-42);
+        assert_eq!(BabyBearField::from_bytes_le(&x.to_bytes_le()), x);
+        assert_eq!(
+            serde_json::from_str::<BabyBearField>(&serde_json::to_string(&x).unwrap()).unwrap(),
+            x
+        );
+    }
+
+    #[test]
+    fn try_into_i32() {
+        for value in [-1006632960, -3456, -1, 0, 1, 3456, 1006632960] {
+            assert_eq!(BabyBearField::from(value).try_into_i32(), Some(value));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn div_by_zero() {
+        let _ = BabyBearField::from(1) / BabyBearField::from(0);
+    }
+}
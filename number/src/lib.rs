@@ -4,8 +4,10 @@
 
 #[macro_use]
 mod macros;
+mod babybear;
 mod bn254;
 mod goldilocks;
+mod mersenne31;
 mod serialize;
 mod traits;
 
@@ -13,8 +15,10 @@ pub use serialize::{
     read_polys_csv_file, read_polys_file, write_polys_csv_file, write_polys_file, CsvRenderMode,
 };
 
+pub use babybear::BabyBearField;
 pub use bn254::Bn254Field;
 pub use goldilocks::GoldilocksField;
+pub use mersenne31::Mersenne31Field;
 pub use traits::KnownField;
 
 pub use ibig::{IBig as BigInt, UBig as BigUint};
@@ -10,29 +10,82 @@ macro_rules! powdr_field {
         use std::ops::*;
         use std::str::FromStr;
 
-        #[derive(
-            Clone,
-            Copy,
-            PartialEq,
-            Eq,
-            Debug,
-            Default,
-            PartialOrd,
-            Ord,
-            Hash,
-            Serialize,
-            Deserialize,
-            JsonSchema,
-        )]
+        #[derive(Clone, Copy, PartialEq, Eq, Debug, Default, PartialOrd, Ord, Hash, JsonSchema)]
         pub struct $name {
-            #[serde(
-                serialize_with = "crate::serialize::ark_se",
-                deserialize_with = "crate::serialize::ark_de"
-            )]
             #[schemars(skip)]
             value: $ark_type,
         }
 
+        /// The number of bytes of an element in binary serialization formats.
+        const SERIALIZED_BYTES: usize = <$ark_type as PrimeField>::BigInt::NUM_LIMBS * 8;
+
+        /// Human-readable formats like JSON use the canonical representative as a decimal
+        /// string, binary formats use its fixed-width little-endian bytes.
+        impl Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.serialize_str(&self.to_arbitrary_integer().to_string())
+                } else {
+                    use serde::ser::SerializeTuple;
+                    let mut tuple = serializer.serialize_tuple(SERIALIZED_BYTES)?;
+                    for byte in self.to_bytes_le() {
+                        tuple.serialize_element(&byte)?;
+                    }
+                    tuple.end()
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct ElementVisitor;
+
+                impl ElementVisitor {
+                    fn checked<E: serde::de::Error>(value: BigUint) -> Result<$name, E> {
+                        $name::checked_from(value.clone()).ok_or_else(|| {
+                            E::custom(format!(
+                                "{value} is not a canonical element of {}",
+                                stringify!($name)
+                            ))
+                        })
+                    }
+                }
+
+                impl<'de> serde::de::Visitor<'de> for ElementVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        write!(f, "an element of {}", stringify!($name))
+                    }
+
+                    fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<$name, E> {
+                        let value = BigUint::from_str_radix(s, 10)
+                            .map_err(|_| E::invalid_value(serde::de::Unexpected::Str(s), &self))?;
+                        Self::checked(value)
+                    }
+
+                    fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> Result<$name, A::Error> {
+                        let mut bytes = [0u8; SERIALIZED_BYTES];
+                        for (i, byte) in bytes.iter_mut().enumerate() {
+                            *byte = seq
+                                .next_element()?
+                                .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+                        }
+                        Self::checked(BigUint::from_le_bytes(&bytes))
+                    }
+                }
+
+                if deserializer.is_human_readable() {
+                    deserializer.deserialize_str(ElementVisitor)
+                } else {
+                    deserializer.deserialize_tuple(SERIALIZED_BYTES, ElementVisitor)
+                }
+            }
+        }
+
         #[derive(Clone, Copy, PartialEq, Eq, Debug, Default, PartialOrd, Ord, Hash)]
         pub struct LargeIntImpl {
             value: <$ark_type as PrimeField>::BigInt,
@@ -396,6 +449,18 @@ macro_rules! powdr_field {
             }
 
             fn try_into_i32(&self) -> Option<i32> {
+                if Self::BITS <= 32 {
+                    // The shift below would wrap around in fields with a modulus of at most
+                    // 32 bits, so we map the upper half of the field to negative values directly.
+                    let value = self.to_integer().try_into_u64().unwrap() as i64;
+                    let modulus = Self::modulus().try_into_u64().unwrap() as i64;
+                    let value = if self.is_in_lower_half() {
+                        value
+                    } else {
+                        value - modulus
+                    };
+                    return value.try_into().ok();
+                }
                 // Shifts range [-2**31, 2**31) into [0, 2**32).
                 const SHIFT: u64 = (-(i32::MIN as i64)) as u64;
                 // We need to explicitly call to_integer() to decode the value
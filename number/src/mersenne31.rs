@@ -0,0 +1,92 @@
+use ark_ff::{Fp64, MontBackend, MontConfig};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(MontConfig)]
+#[modulus = "2147483647"]
+#[generator = "7"]
+pub struct Mersenne31BaseFieldConfig;
+pub type Mersenne31BaseField = Fp64<MontBackend<Mersenne31BaseFieldConfig, 1>>;
+
+powdr_field!(Mersenne31Field, Mersenne31BaseField);
+
+#[cfg(test)]
+mod test {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn modulus() {
+        assert_eq!(Mersenne31Field::BITS, 31);
+        assert_eq!(Mersenne31Field::modulus(), 2147483647u64.into());
+        let minus_one = Mersenne31Field::from(0) - Mersenne31Field::from(1);
+        assert_eq!(
+            minus_one.to_arbitrary_integer(),
+            BigUint::from(2147483646u32)
+        );
+        // 2**31 wraps around to one.
+        assert_eq!(Mersenne31Field::from(1u64 << 31), Mersenne31Field::from(1));
+    }
+
+    #[test]
+    fn inverse() {
+        let half = Mersenne31Field::from(1) / Mersenne31Field::from(2);
+        assert_eq!(half.to_arbitrary_integer(), BigUint::from(1073741824u32));
+        let x = Mersenne31Field::from(123456789);
+        assert_eq!(x * (Mersenne31Field::from(1) / x), Mersenne31Field::from(1));
+    }
+
+    #[test]
+    fn lower_half() {
+        let x = Mersenne31Field::from(0);
+        assert!(x.is_in_lower_half());
+        assert!(!(x - 1.into()).is_in_lower_half());
+
+        let y = Mersenne31Field::from_str_radix("3fffffff", 16).unwrap();
+        assert!(y.is_in_lower_half());
+        assert!(!(y + 1.into()).is_in_lower_half());
+    }
+
+    #[test]
+    fn conversion() {
+        let max = BigUint::from(2147483646u32);
+        assert_eq!(
+            Mersenne31Field::checked_from(max.clone())
+                .unwrap()
+                .to_arbitrary_integer(),
+            max
+        );
+        assert_eq!(
+            Mersenne31Field::checked_from(max + BigUint::from(1u32)),
+            None
+        );
+        assert_eq!(
+            Mersenne31Field::from_str("2147483647").unwrap_err(),
+            "Decimal number \"2147483647\" too large for field."
+        );
+        assert_eq!(
+            Mersenne31Field::from_str_radix("7fffffff", 16).unwrap_err(),
+            "Hexadecimal number \"0x7fffffff\" too large for field."
+        );
+        let x = Mersenne31Field::from(-42);
+        assert_eq!(Mersenne31Field::from_bytes_le(&x.to_bytes_le()), x);
+        assert_eq!(
+            serde_json::from_str::<Mersenne31Field>(&serde_json::to_string(&x).unwrap()).unwrap(),
+            x
+        );
+    }
+
+    #[test]
+    fn try_into_i32() {
+        for value in [-1073741823, -3456, -1, 0, 1, 3456, 1073741823] {
+            assert_eq!(Mersenne31Field::from(value).try_into_i32(), Some(value));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn div_by_zero() {
+        let _ = Mersenne31Field::from(1) / Mersenne31Field::from(0);
+    }
+}
@@ -1,8 +1,6 @@
 use std::io::{Read, Write};
 
-use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
 use csv::{Reader, Writer};
-use serde_with::{DeserializeAs, SerializeAs};
 
 use crate::{DegreeType, FieldElement};
 
@@ -143,30 +141,10 @@ pub fn read_polys_file<T: FieldElement>(
     }
 }
 
-// Serde wrappers for serialize/deserialize
-
-pub fn ark_se<S, A: CanonicalSerialize>(a: &A, s: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
-    let mut bytes = vec![];
-    a.serialize_with_mode(&mut bytes, Compress::Yes)
-        .map_err(serde::ser::Error::custom)?;
-    serde_with::Bytes::serialize_as(&bytes, s)
-}
-
-pub fn ark_de<'de, D, A: CanonicalDeserialize>(data: D) -> Result<A, D::Error>
-where
-    D: serde::de::Deserializer<'de>,
-{
-    let s: Vec<u8> = serde_with::Bytes::deserialize_as(data)?;
-    let a = A::deserialize_with_mode(s.as_slice(), Compress::Yes, Validate::Yes);
-    a.map_err(serde::de::Error::custom)
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::Bn254Field;
+    use crate::traits::LargeInt;
+    use crate::{Bn254Field, GoldilocksField};
     use std::io::Cursor;
 
     use super::*;
@@ -219,4 +197,76 @@ mod tests {
             assert_eq!(read_polys, polys);
         }
     }
+
+    fn serde_round_trip<T: FieldElement>() {
+        let values = [0, 1, 42, -1, i64::MAX, i64::MIN].map(T::from);
+        for x in values {
+            let json = serde_json::to_string(&x).unwrap();
+            assert_eq!(json, format!("\"{}\"", x.to_arbitrary_integer()));
+            assert_eq!(serde_json::from_str::<T>(&json).unwrap(), x);
+
+            let bytes = bincode::serialize(&x).unwrap();
+            assert_eq!(bytes, x.to_bytes_le());
+            assert_eq!(bincode::deserialize::<T>(&bytes).unwrap(), x);
+        }
+        let json = serde_json::to_string(&values).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<T>>(&json).unwrap(), values);
+        let bytes = bincode::serialize(&values).unwrap();
+        assert_eq!(bincode::deserialize::<Vec<T>>(&bytes).unwrap(), values);
+    }
+
+    #[test]
+    fn serde_round_trip_goldilocks() {
+        serde_round_trip::<GoldilocksField>();
+    }
+
+    #[test]
+    fn serde_round_trip_bn254() {
+        serde_round_trip::<Bn254Field>();
+    }
+
+    #[test]
+    fn serde_representation() {
+        assert_eq!(
+            serde_json::to_string(&GoldilocksField::from(-1)).unwrap(),
+            "\"18446744069414584320\""
+        );
+        assert_eq!(
+            bincode::serialize(&GoldilocksField::from(0x0102)).unwrap(),
+            [2, 1, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(bincode::serialize(&Bn254Field::from(7)).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn serde_rejects_non_canonical() {
+        let json_error = serde_json::from_str::<GoldilocksField>("\"18446744069414584321\"")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            json_error
+                .starts_with("18446744069414584321 is not a canonical element of GoldilocksField"),
+            "{json_error}"
+        );
+        let bincode_error = bincode::deserialize::<GoldilocksField>(&[0xff; 8])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            bincode_error,
+            "18446744073709551615 is not a canonical element of GoldilocksField"
+        );
+        let modulus = Bn254Field::modulus().to_arbitrary_integer();
+        let bn254_error = serde_json::from_str::<Bn254Field>(&format!("\"{modulus}\""))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            bn254_error.starts_with(&format!(
+                "{modulus} is not a canonical element of Bn254Field"
+            )),
+            "{bn254_error}"
+        );
+        assert!(serde_json::from_str::<GoldilocksField>("\"-1\"").is_err());
+        assert!(serde_json::from_str::<GoldilocksField>("42").is_err());
+        assert!(bincode::deserialize::<GoldilocksField>(&[1, 2, 3]).is_err());
+    }
 }
@@ -59,10 +59,24 @@ pub trait LargeInt:
     fn try_into_u32(&self) -> Option<u32>;
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum KnownField {
     GoldilocksField,
     Bn254Field,
+    BabyBearField,
+    Mersenne31Field,
+}
+
+impl fmt::Display for KnownField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            KnownField::GoldilocksField => "Goldilocks",
+            KnownField::Bn254Field => "BN254",
+            KnownField::BabyBearField => "BabyBear",
+            KnownField::Mersenne31Field => "Mersenne31",
+        };
+        write!(f, "{name}")
+    }
 }
 
 /// A field element
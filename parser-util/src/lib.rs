@@ -13,6 +13,12 @@ pub struct ParseError<'a> {
     message: String,
 }
 
+impl<'a> std::fmt::Display for ParseError<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.file_name, self.message)
+    }
+}
+
 impl<'a> ParseError<'a> {
     pub fn output_to_stderr(&self) {
         use codespan_reporting::diagnostic::{Diagnostic, Label};
@@ -3,8 +3,12 @@
 #![deny(clippy::print_stdout)]
 
 use lalrpop_util::*;
-use powdr_ast::parsed::{asm::ASMProgram, types::Type, types::TypeBounds};
+use powdr_ast::parsed::{
+    asm::ASMProgram, build::absolute_reference, types::Type, types::TypeBounds, Expression,
+    FunctionCall, MatchPattern, RangeKind, UnaryOperator,
+};
 use powdr_ast::SourceRef;
+use powdr_number::BigInt;
 
 use powdr_parser_util::{handle_parse_error, ParseError};
 
@@ -42,6 +46,7 @@ impl ParserContext {
 lazy_static::lazy_static! {
     static ref PIL_FILE_PARSER: powdr::PILFileParser = powdr::PILFileParser::new();
     static ref ASM_MODULE_PARSER: powdr::ASMModuleParser = powdr::ASMModuleParser::new();
+    static ref EXPRESSION_PARSER: powdr::ExpressionParser = powdr::ExpressionParser::new();
     static ref TYPE_PARSER: powdr::TypeParser = powdr::TypeParser::new();
     static ref TYPE_VAR_BOUNDS_PARSER: powdr::TypeVarBoundsParser = powdr::TypeVarBoundsParser::new();
 }
@@ -89,6 +94,112 @@ pub fn parse_type_var_bounds(input: &str) -> Result<TypeBounds, ParseError<'_>>
         .map_err(|err| handle_parse_error(err, None, input))
 }
 
+/// Creates a range pattern - used in the grammar.
+/// Fails if the endpoints are not (possibly negated) number literals
+/// or if the range is empty, e.g. because it is reversed.
+pub fn range_pattern<T>(
+    start: Expression,
+    end: Expression,
+    kind: RangeKind,
+) -> Result<MatchPattern, lalrpop_util::ParseError<usize, T, &'static str>> {
+    let (Some(start_value), Some(end_value)) = (literal_value(&start), literal_value(&end)) else {
+        return Err(lalrpop_util::ParseError::User {
+            error: "The endpoints of range patterns have to be integer literals.",
+        });
+    };
+    let is_empty = match kind {
+        RangeKind::Exclusive => start_value >= end_value,
+        RangeKind::Inclusive => start_value > end_value,
+    };
+    if is_empty {
+        Err(lalrpop_util::ParseError::User {
+            error: "Range patterns must not be empty or reversed.",
+        })
+    } else {
+        Ok(MatchPattern::Range(start, end, kind))
+    }
+}
+
+fn literal_value(e: &Expression) -> Option<BigInt> {
+    match e {
+        Expression::Number(n, None, _) => Some(n.clone().into()),
+        Expression::UnaryOperation(UnaryOperator::Minus, inner) => match inner.as_ref() {
+            Expression::Number(n, None, _) => Some(-BigInt::from(n.clone())),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Desugars the (unescaped) contents of a format string like `f"x = {x}"` into a
+/// concatenation of its literal parts and its placeholders, converted to strings
+/// by `std::convert::to_string` - used in the grammar.
+/// Literal braces are written as `{{` and `}}`.
+/// Fails on unmatched braces and on placeholders that are empty or not an expression.
+pub fn format_string<T>(
+    ctx: &ParserContext,
+    s: &str,
+) -> Result<Expression, lalrpop_util::ParseError<usize, T, &'static str>> {
+    let error = |error| lalrpop_util::ParseError::User { error };
+    let mut parts = vec![];
+    let mut literal = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' if chars.peek() == Some(&c) => {
+                chars.next();
+                literal.push(c);
+            }
+            '}' => {
+                return Err(error(
+                    "Unmatched `}` in format string, use `}}` for a literal brace.",
+                ))
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                let mut depth = 0;
+                loop {
+                    match chars.next() {
+                        None => {
+                            return Err(error(
+                                "Unterminated `{` in format string, use `{{` for a literal brace.",
+                            ))
+                        }
+                        Some('}') if depth == 0 => break,
+                        Some(c) => {
+                            match c {
+                                '{' => depth += 1,
+                                '}' => depth -= 1,
+                                _ => {}
+                            }
+                            placeholder.push(c);
+                        }
+                    }
+                }
+                if placeholder.trim().is_empty() {
+                    return Err(error("Empty placeholder in format string."));
+                }
+                let placeholder_ctx = ParserContext::new(ctx.file_name.as_deref(), &placeholder);
+                let value = EXPRESSION_PARSER
+                    .parse(&placeholder_ctx, &placeholder)
+                    .map_err(|_| error("Invalid expression in format string placeholder."))?;
+                if !literal.is_empty() {
+                    parts.push(Expression::String(std::mem::take(&mut literal)));
+                }
+                parts.push(Expression::FunctionCall(FunctionCall {
+                    function: Box::new(absolute_reference("::std::convert::to_string")),
+                    arguments: vec![value],
+                }));
+            }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() || parts.is_empty() {
+        parts.push(Expression::String(literal));
+    }
+    Ok(parts.into_iter().reduce(|a, b| a + b).unwrap())
+}
+
 /// Parse an escaped string - used in the grammar.
 pub fn unescape_string(s: &str) -> String {
     assert!(s.len() >= 2);
@@ -247,6 +358,10 @@ mod test {
             | PilStatement::ConnectIdentity(s, _, _)
             | PilStatement::ConstantDefinition(s, _, _)
             | PilStatement::Expression(s, _) => *s = SourceRef::unknown(),
+            PilStatement::LabeledIdentity(s, _, identity) => {
+                *s = SourceRef::unknown();
+                pil_statement_clear_source_ref(identity);
+            }
         }
     }
 
@@ -261,7 +376,7 @@ mod test {
             match stmt {
                 MachineStatement::Degree(s, _)
                 | MachineStatement::Submachine(s, _, _)
-                | MachineStatement::RegisterDeclaration(s, _, _)
+                | MachineStatement::RegisterDeclaration(s, _, _, _)
                 | MachineStatement::OperationDeclaration(s, _, _, _)
                 | MachineStatement::LinkDeclaration(s, _) => {
                     *s = SourceRef::unknown();
@@ -296,7 +411,10 @@ mod test {
         fn clear_module_stmt(stmt: &mut ModuleStatement) {
             let ModuleStatement::SymbolDefinition(SymbolDefinition { value, .. }) = stmt;
             match value {
-                SymbolValue::Machine(Machine { statements, .. }) => {
+                SymbolValue::Machine(Machine {
+                    source, statements, ..
+                }) => {
+                    *source = SourceRef::unknown();
                     statements.iter_mut().for_each(clear_machine_stmt)
                 }
                 SymbolValue::Module(Module::Local(ASMModule { statements })) => {
@@ -345,6 +463,88 @@ mod test {
         }
     }
 
+    #[test]
+    /// Test that (source -> AST -> JSON -> AST) results in the same AST for asm files
+    fn serialize_deserialize_asm() {
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let basedir = std::path::PathBuf::from(format!("{crate_dir}/../test_data/"));
+        let asm_files = find_files_with_ext(basedir, "asm".into());
+        for (file, orig_string) in asm_files {
+            let orig_asm = parse_asm(Some(&file), &orig_string).unwrap_err_to_stderr();
+            let json = serde_json::to_string(&orig_asm).unwrap();
+            let deserialized: ASMProgram = serde_json::from_str(&json).unwrap();
+            assert_eq!(orig_asm, deserialized, "{file}");
+        }
+    }
+
+    #[test]
+    fn referenced_paths() {
+        let input = r#"
+use std::binary::Binary;
+mod utils {
+    let one: int = 1;
+    let inc = |x| x + one;
+    let<T> id: T -> T = |one| one;
+}
+mod machines {
+    use super::utils::inc;
+    use super::utils as u;
+    machine Main {
+        degree 8;
+        super::Binary binary;
+        reg pc[@pc];
+        reg X[<=];
+        reg A;
+        col witness w;
+        instr add X -> A = binary.add;
+        instr incr X -> A { A' = inc(X) }
+        link 1 => binary.and X -> A;
+        w = u::inc(w) * u::one;
+        function main {
+            A <=X= inc(u::one);
+            start:
+            jmp start;
+        }
+    }
+}
+"#;
+        let program = parse_asm(None, input).unwrap();
+        let references = powdr_ast::parsed::asm::referenced_paths(&program)
+            .into_iter()
+            .map(|(symbol, paths)| {
+                let paths = paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                format!("{symbol}: {}", paths.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            references,
+            "::Binary: ::std::binary::Binary
+::machines::Main: ::std::binary::Binary, ::utils::inc, ::utils::one
+::machines::inc: ::utils::inc
+::machines::u: ::utils
+::utils::id: 
+::utils::inc: ::utils::one
+::utils::one: "
+        );
+    }
+
+    #[test]
+    fn source_ref_representation() {
+        let source = SourceRef {
+            file: Some("main.asm".into()),
+            line: 3,
+            col: 8,
+        };
+        let json = serde_json::to_string(&source).unwrap();
+        assert_eq!(json, r#"{"file":"main.asm","line":3,"col":8}"#);
+        assert_eq!(serde_json::from_str::<SourceRef>(&json).unwrap(), source);
+        assert_eq!(
+            serde_json::to_string(&SourceRef::unknown()).unwrap(),
+            r#"{"file":null,"line":0,"col":0}"#
+        );
+    }
+
     #[test]
     /// Test that (source -> AST -> source -> AST) works properly for pil files
     fn parse_write_reparse_pil() {
@@ -419,6 +619,13 @@ namespace Fibonacci(%N);
             assert_eq!(input.trim(), printed.trim());
         }
 
+        #[test]
+        fn reparse_sparse_fixed_columns() {
+            let input = "    pol constant boundary = { 0: 1, (%N - 1): 1, _: 0 };\n    pol constant zero = { _: 0 };";
+            let printed = format!("{}", parse(Some("input"), input).unwrap());
+            assert_eq!(input.trim(), printed.trim());
+        }
+
         #[test]
         fn reparse_strings_and_tuples() {
             let input = r#"constant %N = ("abc", 3);"#;
@@ -426,6 +633,126 @@ namespace Fibonacci(%N);
             assert_eq!(input.trim(), printed.trim());
         }
 
+        #[test]
+        fn reparse_escaped_strings() {
+            let input =
+                r#"let x = ["\"quoted\"", "a\\b", "line\nbreak\r\ttab\b\f", "ünïcode 'single'"];"#;
+            let printed = format!("{}", parse(Some("input"), input).unwrap());
+            assert_eq!(input.trim(), printed.trim());
+        }
+
+        #[test]
+        fn reparse_labels() {
+            let input = r#"    pol commit x, y;
+    #[label("pc \"update\"")] (x' = (x + 1));
+    #[label("lookup")] { x } in { y };
+    #[label("permutation")] { x } is { y };
+    #[label("connect")] { x } connect { y };"#;
+            let printed = format!("{}", parse(Some("input"), input).unwrap());
+            assert_eq!(input.trim(), printed.trim());
+        }
+
+        #[test]
+        fn unknown_attribute() {
+            let input = r#"#[inline("x")] x = 1;"#;
+            let error = parse(Some("input"), input).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "input: Only the attribute label is supported on identities."
+            );
+        }
+
+        #[test]
+        fn range_patterns() {
+            let input =
+                r#"let f = (|i| match i { 0..16 => 1, -4..=(-1) => 2, -8..-4 => 3, _ => 0, });"#;
+            let printed = parse(Some("input"), input).unwrap().to_string();
+            assert_eq!(
+                printed.trim(),
+                "let f = (|i| match i { 0..16 => 1, -4..=-1 => 2, -8..-4 => 3, _ => 0, });"
+            );
+            assert_eq!(parse(Some("input"), &printed).unwrap().to_string(), printed);
+        }
+
+        #[test]
+        fn or_patterns() {
+            let input = r#"let f = (|i| match i { 2 | 3 | 5 => 1, (1 | 2) => 2, 0..2 | 9 => 3, _ => 0, });"#;
+            let printed = parse(Some("input"), input).unwrap().to_string();
+            assert_eq!(
+                printed.trim(),
+                "let f = (|i| match i { 2 | 3 | 5 => 1, (1 | 2) => 2, 0..2 | 9 => 3, _ => 0, });"
+            );
+            assert_eq!(parse(Some("input"), &printed).unwrap().to_string(), printed);
+        }
+
+        #[test]
+        fn reversed_range_pattern() {
+            for input in [
+                "let f = |i| match i { 16..0 => 1 };",
+                "let f = |i| match i { 3..3 => 1 };",
+            ] {
+                assert_eq!(
+                    parse(Some("input"), input).unwrap_err().to_string(),
+                    "input: Range patterns must not be empty or reversed."
+                );
+            }
+            assert_eq!(
+                parse(Some("input"), "let f = |i| match i { 3..=3 => 1 };")
+                    .unwrap()
+                    .to_string()
+                    .trim(),
+                "let f = (|i| match i { 3..=3 => 1, });"
+            );
+        }
+
+        #[test]
+        fn range_pattern_with_non_literal_endpoint() {
+            assert_eq!(
+                parse(Some("input"), "let f = |i| match i { 0..N => 1 };")
+                    .unwrap_err()
+                    .to_string(),
+                "input: The endpoints of range patterns have to be integer literals."
+            );
+        }
+
+        #[test]
+        fn format_strings() {
+            let input =
+                r#"let x = [f"value {v} at row {i + 1}", f"{v}", f"{{literal}} {f(\"a\")}", f""];"#;
+            let printed = parse(Some("input"), input).unwrap().to_string();
+            assert_eq!(
+                printed.trim(),
+                r#"let x = [((("value " + std::convert::to_string(v)) + " at row ") + std::convert::to_string((i + 1))), std::convert::to_string(v), ("{literal} " + std::convert::to_string(f("a"))), ""];"#
+            );
+        }
+
+        #[test]
+        fn format_string_errors() {
+            for (input, error) in [
+                (
+                    r#"let x = f"a } b";"#,
+                    "Unmatched `}` in format string, use `}}` for a literal brace.",
+                ),
+                (
+                    r#"let x = f"a { b";"#,
+                    "Unterminated `{` in format string, use `{{` for a literal brace.",
+                ),
+                (
+                    r#"let x = f"a { } b";"#,
+                    "Empty placeholder in format string.",
+                ),
+                (
+                    r#"let x = f"a {1 +} b";"#,
+                    "Invalid expression in format string placeholder.",
+                ),
+            ] {
+                assert_eq!(
+                    parse(Some("input"), input).unwrap_err().to_string(),
+                    format!("input: {error}")
+                );
+            }
+        }
+
         #[test]
         fn array_literals() {
             let input = r#"let x = [[1], [2], [(3 + 7)]];"#;
@@ -433,6 +760,21 @@ namespace Fibonacci(%N);
             assert_eq!(input.trim(), printed.trim());
         }
 
+        #[test]
+        fn block_expressions() {
+            let input =
+                r#"let f = (|i| { let x = (i + 1); let y = { let x = (x * x); x }; (x + y) });"#;
+            let printed = format!("{}", parse(Some("input"), input).unwrap_err_to_stderr());
+            assert_eq!(input.trim(), printed.trim());
+        }
+
+        #[test]
+        fn hex_and_binary_literals() {
+            let input = r#"let x = [0xFF_FF, 0b10_1, 1_0, 0x0];"#;
+            let printed = parse(Some("input"), input).unwrap().to_string();
+            assert_eq!(printed.trim(), "let x = [0xffff, 0b101, 10, 0x0];");
+        }
+
         #[test]
         fn type_names_simple() {
             let input = r#"
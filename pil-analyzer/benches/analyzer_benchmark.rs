@@ -0,0 +1,137 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use powdr_ast::{
+    analyzed::{FunctionValueDefinition, TypedExpression},
+    parsed::visitor::ExpressionVisitable,
+};
+use powdr_number::GoldilocksField;
+use powdr_pil_analyzer::{
+    analyze_string,
+    evaluator::{evaluate, Definitions},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// Counts the currently allocated bytes and their maximum since the last reset.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Runs `f` and returns its result together with the maximum number of bytes
+/// allocated in addition to the ones already allocated before.
+fn peak_memory<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    let result = f();
+    (result, PEAK.load(Ordering::Relaxed) - before)
+}
+
+/// Prints the peak memory of analyzing `input`, compared to the memory needed
+/// for a deep copy of its definitions, which condensation used to make.
+fn report_peak_memory(input: &str) {
+    let (analyzed, peak) = peak_memory(|| analyze_string::<GoldilocksField>(input));
+    let (definitions, copy) = peak_memory(|| analyzed.definitions.clone());
+    drop(definitions);
+    println!("Peak memory of analyzing the generated input: {peak} bytes");
+    println!("  a copy of its definitions would need another {copy} bytes");
+}
+
+/// Generates a PIL file with `namespaces` namespaces, each containing
+/// `columns` witness columns that are connected through function calls.
+fn generate_pil(namespaces: usize, columns: usize) -> String {
+    (0..namespaces)
+        .map(|n| {
+            let witness_columns = (0..columns)
+                .map(|c| format!("    col witness w{c};\n"))
+                .collect::<String>();
+            let identities = (1..columns)
+                .map(|c| format!("    w{c}' = square(w{}) + LINE * w{c};\n", c - 1))
+                .collect::<String>();
+            format!(
+                "namespace N{n}(65536);\n    let square = |x| x * x;\n    col fixed LINE(i) {{ i }};\n{witness_columns}{identities}"
+            )
+        })
+        .collect()
+}
+
+fn analyzer_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("analyzer-benchmark");
+    group.sample_size(10);
+
+    let input = generate_pil(20, 100);
+    report_peak_memory(&input);
+    group.bench_function("analyze_generated", |b| {
+        b.iter(|| analyze_string::<GoldilocksField>(&input))
+    });
+
+    let mut parsed = powdr_parser::parse(None, &input).unwrap();
+    group.bench_function("format_generated_parsed", |b| b.iter(|| parsed.to_string()));
+    let analyzed = analyze_string::<GoldilocksField>(&input);
+    group.bench_function("format_generated_analyzed", |b| {
+        b.iter(|| analyzed.to_string())
+    });
+    group.bench_function("visit_generated_expressions", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for statement in &parsed.0 {
+                for e in statement.expressions() {
+                    e.pre_visit_expressions(&mut |_| count += 1);
+                }
+            }
+            count
+        })
+    });
+    group.bench_function("visit_generated_expressions_mut", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for statement in &mut parsed.0 {
+                for e in statement.expressions_mut() {
+                    e.pre_visit_expressions_mut(&mut |_| count += 1);
+                }
+            }
+            count
+        })
+    });
+
+    let input = r#"
+        namespace std::array(65536);
+        let len = 1;
+        let new = 1;
+        let map = 1;
+        namespace N(65536);
+        let arr: int[] = std::array::new(65536, |i| i);
+        let mapped: int[] = std::array::map(arr, |x| x * 2);
+    "#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    let Some(FunctionValueDefinition::Expression(TypedExpression { e: mapped, .. })) =
+        &analyzed.definition("N.mapped").unwrap().1
+    else {
+        panic!()
+    };
+    group.bench_function("evaluate_array_map", |b| {
+        b.iter(|| evaluate::<GoldilocksField>(mapped, &Definitions(&analyzed.definitions)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, analyzer_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+//! Measures the memory used by number literals when parsing a fixed column
+//! defined by a large array of small constants.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use powdr_ast::parsed::{Expression, PilStatement};
+
+/// Counts the currently allocated bytes and the number of allocations.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ENTRIES: usize = 1 << 16;
+
+fn main() {
+    let values = (0..ENTRIES)
+        .map(|i| (i % 256).to_string())
+        .collect::<Vec<_>>();
+    let input = format!(
+        "namespace main({ENTRIES});\n    col fixed TABLE = [{}];\n",
+        values.join(", ")
+    );
+
+    let (allocated, allocations) = (
+        ALLOCATED.load(Ordering::Relaxed),
+        ALLOCATIONS.load(Ordering::Relaxed),
+    );
+    let parsed = powdr_parser::parse(None, &input).unwrap();
+    let retained = ALLOCATED.load(Ordering::Relaxed) - allocated;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    let table = &parsed.0[1];
+    assert!(matches!(
+        table,
+        PilStatement::PolynomialConstantDefinition(..)
+    ));
+    assert!(table
+        .expressions()
+        .all(|e| matches!(e, Expression::Number(..))));
+    assert_eq!(table.expressions().count(), ENTRIES);
+
+    println!("Parsed a fixed array of {ENTRIES} small constants:");
+    println!(
+        "  retained: {retained} bytes ({:.1} bytes per literal)",
+        retained as f64 / ENTRIES as f64
+    );
+    println!(
+        "  allocations: {allocations} ({:.2} per literal)",
+        allocations as f64 / ENTRIES as f64
+    );
+    println!(
+        "  size of an expression: {} bytes",
+        std::mem::size_of::<Expression>()
+    );
+}
@@ -20,6 +20,60 @@ pub fn sort_called_first<'a, I: Iterator<Item = (&'a str, Option<&'a Expression>
     result
 }
 
+/// Returns a sorted list of symbols such that referenced symbols appear before the symbols
+/// that reference them, only taking references between the given symbols into account.
+/// In contrast to `sort_called_first`, circular dependencies are an error: The error
+/// contains the names along the cycle, starting and ending with the same name.
+pub fn sort_referenced_first_acyclic<'a, I: Iterator<Item = (&'a str, Option<&'a Expression>)>>(
+    symbols: I,
+) -> Result<Vec<String>, Vec<String>> {
+    let mut graph = call_graph(symbols);
+    let names = graph.keys().map(|n| n.to_string()).collect::<HashSet<_>>();
+    for called in graph.values_mut() {
+        called.retain(|c| names.contains(c));
+    }
+    // Process the names in a fixed order so that the reported cycle is deterministic.
+    let mut names = graph.keys().copied().collect::<Vec<_>>();
+    names.sort();
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut result: Vec<String> = Vec::new();
+    for name in names {
+        acyclic_topo_sort_visit(name, &graph, &mut visited, &mut stack, &mut result)?;
+    }
+    Ok(result)
+}
+
+fn acyclic_topo_sort_visit<'a>(
+    name: &'a str,
+    graph: &HashMap<&'a str, HashSet<String>>,
+    visited: &mut HashSet<&'a str>,
+    stack: &mut Vec<&'a str>,
+    result: &mut Vec<String>,
+) -> Result<(), Vec<String>> {
+    if let Some(pos) = stack.iter().position(|n| *n == name) {
+        return Err(stack[pos..]
+            .iter()
+            .chain(std::iter::once(&name))
+            .map(|n| n.to_string())
+            .collect());
+    }
+    if visited.contains(name) {
+        return Ok(());
+    }
+    stack.push(name);
+    let mut called = graph[name].iter().collect::<Vec<_>>();
+    called.sort();
+    for c in called {
+        let n = graph.get_key_value(c.as_str()).unwrap().0;
+        acyclic_topo_sort_visit(n, graph, visited, stack, result)?;
+    }
+    stack.pop();
+    visited.insert(name);
+    result.push(name.to_string());
+    Ok(())
+}
+
 fn topo_sort_visit<'a, 'b>(
     name: &'a str,
     graph: &'b HashMap<&'a str, HashSet<String>>,
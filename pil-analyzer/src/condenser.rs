@@ -1,217 +1,869 @@
 //! Component that turns data from the PILAnalyzer into Analyzed,
 //! i.e. it turns more complex expressions in identities to simpler expressions.
 
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    sync::Arc,
+};
 
 use powdr_ast::{
     analyzed::{
-        AlgebraicExpression, Analyzed, Expression, FunctionValueDefinition, Identity, IdentityKind,
-        PolynomialType, PublicDeclaration, StatementIdentifier, Symbol, SymbolKind,
+        AlgebraicExpression, AlgebraicReference, Analyzed, Expression, FunctionValueDefinition,
+        Identity, IdentityKind, IdentityProvenance, NameInterner, PolyID, PolynomialType,
+        PublicDeclaration, StatementIdentifier, Symbol, SymbolKind,
     },
     parsed::{
+        asm::AbsoluteSymbolPath,
         display::format_type_scheme_around_name,
         types::{ArrayType, Type},
+        visitor::ExpressionVisitable,
         SelectedExpressions,
     },
+    SourceRef,
 };
 use powdr_number::{DegreeType, FieldElement};
+use rayon::prelude::*;
+
+use crate::{
+    call_graph::sort_referenced_first_acyclic,
+    evaluator::{
+        self, CallCache, Definitions, EvalError, EvaluationLimits, HostSymbols, SymbolLookup, Value,
+    },
+    simplifier::simplify_analyzed,
+    timing::Timer,
+};
+
+/// An error that occurred while condensing an identity or an intermediate column.
+#[derive(Debug)]
+pub struct CondenseError {
+    /// The source location of the identity or definition that could not be condensed.
+    pub source: SourceRef,
+    pub error: EvalError,
+    /// The description of the identity (see `Identity::describe`), if an identity
+    /// could not be condensed.
+    pub identity: Option<String>,
+}
 
-use crate::evaluator::{self, Definitions, Value};
+impl Display for CondenseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.source.file.as_deref().unwrap_or("input"),
+            self.source.line,
+            self.source.col,
+            self.error
+        )?;
+        if let Some(identity) = &self.identity {
+            write!(f, "\nIn identity {identity}")?;
+        }
+        Ok(())
+    }
+}
 
+/// Condenses all identities and intermediate columns.
+/// Errors are collected across all identities and intermediate columns
+/// instead of stopping at the first one.
+/// Identities are condensed using `threads` threads, where `threads <= 1` means
+/// sequential condensation. The result does not depend on the number of threads.
+/// If `simplify` is set, the condensed identities and intermediate columns are
+/// simplified by constant folding, see `simplifier::simplify_analyzed`.
+/// `identity_namespaces` contains the namespace of each identity, which is used
+/// for witness columns created during condensation.
+/// Symbols that are not defined in PIL are looked up in `host_symbols`, if provided.
+/// The condensation of identities, the extraction of intermediate columns and the
+/// simplification are recorded as phases in `timer`.
+#[allow(clippy::too_many_arguments)]
 pub fn condense<T: FieldElement>(
     degree: Option<DegreeType>,
-    mut definitions: HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
-    mut public_declarations: HashMap<String, PublicDeclaration>,
+    mut definitions: HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    mut public_declarations: HashMap<AbsoluteSymbolPath, PublicDeclaration>,
     identities: &[Identity<Expression>],
+    identity_namespaces: &[AbsoluteSymbolPath],
     source_order: Vec<StatementIdentifier>,
-) -> Analyzed<T> {
+    threads: usize,
+    simplify: bool,
+    limits: EvaluationLimits,
+    host_symbols: Option<&dyn HostSymbols<T>>,
+    timer: &mut Timer,
+) -> Result<Analyzed<T>, Vec<CondenseError>> {
     let condenser = Condenser {
-        symbols: definitions.clone(),
-        _phantom: Default::default(),
+        symbols: &definitions,
+        public_declarations: &public_declarations,
+        host_symbols,
+        call_cache: Default::default(),
+        limits,
     };
 
+    timer.start("identities");
+    let mut condensed = condenser
+        .condense_identities(identities, threads)
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
+    let mut errors = vec![];
     let mut condensed_identities = vec![];
-    // Condense identities and update the source order.
+    let mut identity_provenance = vec![];
+    let mut dead_identities = vec![];
+    let mut new_columns = NewColumns::new(&definitions);
+    // Identities are condensed independently, so references to the same
+    // column only share their name after interning.
+    let mut names = NameInterner::default();
+    // Assemble the condensed identities, allocate the witness columns created
+    // during condensation and update the source order.
     let source_order = source_order
         .into_iter()
         .flat_map(|s| match s {
             StatementIdentifier::Identity(index) => {
-                let identity = &identities[index];
-                condenser
-                    .condense_identity(identity)
-                    .into_iter()
-                    .map(|identity| {
-                        let id = condensed_identities.len();
-                        condensed_identities.push(identity);
-                        StatementIdentifier::Identity(id)
-                    })
-                    .collect()
+                match condensed[index]
+                    .take()
+                    .expect("Identity referenced twice in source order.")
+                {
+                    Ok(CondensedIdentity {
+                        identities: condensed,
+                        is_array,
+                        new_columns: name_hints,
+                    }) => {
+                        let columns = name_hints
+                            .iter()
+                            .map(|hint| {
+                                new_columns.allocate(
+                                    &identity_namespaces[index],
+                                    hint,
+                                    &identities[index],
+                                )
+                            })
+                            .collect::<Vec<_>>();
+                        columns
+                            .iter()
+                            .map(|column| {
+                                StatementIdentifier::Definition(
+                                    AbsoluteSymbolPath::from_dotted_string(&column.absolute_name),
+                                )
+                            })
+                            .chain(condensed.into_iter().enumerate().filter_map(
+                                |(array_index, mut identity)| {
+                                    let provenance = IdentityProvenance {
+                                        statement_index: index,
+                                        source: identities[index].source.clone(),
+                                        array_index: is_array.then_some(array_index),
+                                    };
+                                    if let Some(label) =
+                                        identity.label.as_mut().filter(|_| is_array)
+                                    {
+                                        *label = format!("{label}[{array_index}]");
+                                    }
+                                    if has_zero_selector(&identity) {
+                                        dead_identities.push(provenance);
+                                        return None;
+                                    }
+                                    remove_unit_selectors(&mut identity);
+                                    replace_provisional_references(&mut identity, &columns);
+                                    names.intern_references(&mut identity);
+                                    let id = condensed_identities.len();
+                                    identity_provenance.push(provenance);
+                                    condensed_identities.push(identity);
+                                    Some(StatementIdentifier::Identity(id))
+                                },
+                            ))
+                            .collect::<Vec<_>>()
+                    }
+                    Err(e) => {
+                        errors.push(e);
+                        vec![]
+                    }
+                }
             }
             s => vec![s],
         })
         .collect();
 
-    // Extract intermediate columns
-    let intermediate_columns: HashMap<_, _> = definitions
+    timer.end();
+    let new_columns = new_columns.symbols;
+
+    timer.start("intermediate columns");
+    // Extract intermediate columns. They are processed such that referenced intermediate
+    // columns come first, which also rejects intermediate columns that depend on themselves.
+    // The call graph uses the names as they appear in references.
+    let intermediates = definitions
         .iter()
-        .filter_map(|(name, (symbol, definition))| {
-            if !matches!(symbol.kind, SymbolKind::Poly(PolynomialType::Intermediate)) {
-                return None;
-            }
-            let Some(FunctionValueDefinition::Expression(e)) = definition else {
-                panic!("Expected expression")
-            };
-            let value = if let Some(length) = symbol.length {
-                let scheme = e.type_scheme.as_ref();
-                assert!(
-                    scheme.unwrap().vars.is_empty()
-                        && matches!(
-                            &scheme.unwrap().ty,
-                            Type::Array(ArrayType { base, length: _ })
-                            if base.as_ref() == &Type::Expr),
-                    "Intermediate column type has to be expr[], but got: {}",
-                    format_type_scheme_around_name(name, &e.type_scheme)
-                );
-                let result = condenser.condense_to_array_of_algebraic_expressions(&e.e);
-                assert_eq!(result.len() as u64, length);
-                result
-            } else {
-                assert_eq!(
-                    e.type_scheme,
-                    Some(Type::Expr.into()),
-                    "Intermediate column type has to be expr, but got: {}",
-                    format_type_scheme_around_name(name, &e.type_scheme)
-                );
-                vec![condenser.condense_to_algebraic_expression(&e.e)]
+        .filter(|(_, (symbol, _))| {
+            matches!(symbol.kind, SymbolKind::Poly(PolynomialType::Intermediate))
+        })
+        .map(|(name, (_, definition))| {
+            let value = match definition {
+                Some(FunctionValueDefinition::Expression(e)) => Some(&e.e),
+                _ => None,
             };
-            Some((name.clone(), (symbol.clone(), value)))
+            (name.to_dotted_string(), value)
         })
-        .collect();
+        .collect::<Vec<_>>();
+    let mut intermediate_columns: HashMap<_, _> = match sort_referenced_first_acyclic(
+        intermediates
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value)),
+    ) {
+        Ok(order) => order
+            .into_iter()
+            .filter_map(|name| {
+                let path = AbsoluteSymbolPath::from_dotted_string(&name);
+                let (symbol, definition) = &definitions[&path];
+                match condenser.condense_intermediate_column(&name, symbol, definition) {
+                    Ok(value) => Some((path, (symbol.clone(), value))),
+                    Err(error) => {
+                        errors.push(error);
+                        None
+                    }
+                }
+            })
+            .collect(),
+        Err(cycle) => {
+            errors.push(CondenseError {
+                source: definitions[&AbsoluteSymbolPath::from_dotted_string(&cycle[0])]
+                    .0
+                    .source
+                    .clone(),
+                error: EvalError::TypeError(format!(
+                    "Cyclic dependency between intermediate columns: {}",
+                    cycle.join(" -> ")
+                )),
+                identity: None,
+            });
+            HashMap::new()
+        }
+    };
+    for (_, values) in intermediate_columns.values_mut() {
+        values.iter_mut().for_each(|v| names.intern_references(v));
+    }
+    timer.end();
     definitions.retain(|name, _| !intermediate_columns.contains_key(name));
+    definitions.extend(new_columns.into_iter().map(|symbol| {
+        (
+            AbsoluteSymbolPath::from_dotted_string(&symbol.absolute_name),
+            (symbol, None),
+        )
+    }));
 
     for decl in public_declarations.values_mut() {
-        let symbol = &definitions
-            .get(&decl.polynomial.name)
-            .unwrap_or_else(|| panic!("Symbol {} not found.", decl.polynomial))
-            .0;
-        let reference = &mut decl.polynomial;
-        // TODO this is the only point we still assign poly_id,
-        // maybe move it into PublicDeclaration.
-        reference.poly_id = Some(symbol.into());
-    }
-    Analyzed {
+        match public_declaration_target(decl, &definitions, degree) {
+            Ok(symbol) => {
+                let reference = &mut decl.polynomial;
+                // TODO this is the only point we still assign poly_id,
+                // maybe move it into PublicDeclaration.
+                reference.poly_id = Some(symbol.into());
+            }
+            Err(error) => errors.push(CondenseError {
+                source: decl.source.clone(),
+                error,
+                identity: None,
+            }),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut result = Analyzed {
         degree,
         definitions,
         public_declarations,
         intermediate_columns,
         identities: condensed_identities,
+        identity_provenance,
+        dead_identities,
         source_order,
+        names,
+        identity_index: Default::default(),
+    };
+    if simplify {
+        timer.start("simplification");
+        simplify_analyzed(&mut result);
+        timer.end();
     }
+    Ok(result)
 }
 
-pub struct Condenser<T> {
+/// Returns the symbol referenced by the public declaration after checking that it is
+/// a witness column and that the array index and row are in range.
+fn public_declaration_target<'a>(
+    decl: &PublicDeclaration,
+    definitions: &'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    degree: Option<DegreeType>,
+) -> Result<&'a Symbol, EvalError> {
+    let name = &decl.polynomial.name;
+    let (symbol, _) = definitions
+        .get(&AbsoluteSymbolPath::from_dotted_string(name))
+        .ok_or_else(|| {
+            EvalError::SymbolNotFound(format!(
+                "Public declaration {} references unknown witness column {name}.",
+                decl.name
+            ))
+        })?;
+    if symbol.kind != SymbolKind::Poly(PolynomialType::Committed) {
+        return Err(EvalError::TypeError(format!(
+            "Public declaration {} has to reference a witness column, but {name} is not a witness column.",
+            decl.name
+        )));
+    }
+    match (decl.array_index, symbol.length) {
+        (Some(index), Some(length)) if index as u64 >= length => {
+            Err(EvalError::OutOfBounds(format!(
+                "Public declaration {} references {name}[{index}], but {name} only has length {length}.",
+                decl.name
+            )))?
+        }
+        (Some(_), None) => Err(EvalError::TypeError(format!(
+            "Public declaration {} uses an array index, but {name} is not an array.",
+            decl.name
+        )))?,
+        (None, Some(_)) => Err(EvalError::TypeError(format!(
+            "Public declaration {} references the array {name} without an index.",
+            decl.name
+        )))?,
+        _ => {}
+    }
+    if let Some(degree) = degree {
+        if decl.index >= degree {
+            return Err(EvalError::OutOfBounds(format!(
+                "Public declaration {} references row {} of {}, but the degree is {degree}.",
+                decl.name,
+                decl.index,
+                decl.referenced_poly_name()
+            )));
+        }
+    }
+    Ok(symbol)
+}
+
+pub struct Condenser<'a, T> {
     /// All the definitions from the PIL file.
-    pub symbols: HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
-    _phantom: std::marker::PhantomData<T>,
+    pub symbols: &'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    /// The public declarations, which public references have to refer to.
+    public_declarations: &'a HashMap<AbsoluteSymbolPath, PublicDeclaration>,
+    /// Symbols provided by the host, consulted for names not defined in PIL.
+    host_symbols: Option<&'a dyn HostSymbols<T>>,
+    /// Memoized results of function calls, shared by all identities and intermediate columns.
+    call_cache: CallCache<'a, T>,
+    /// The limits for all evaluations during condensation.
+    limits: EvaluationLimits,
 }
 
-impl<T: FieldElement> Condenser<T> {
-    pub fn condense_identity(
+impl<'a, T: FieldElement> Condenser<'a, T> {
+    /// Condenses all identities using `threads` threads and returns the results
+    /// in the order of the input.
+    fn condense_identities(
+        &self,
+        identities: &[Identity<Expression>],
+        threads: usize,
+    ) -> Vec<Result<CondensedIdentity<T>, CondenseError>> {
+        if threads <= 1 {
+            return identities
+                .iter()
+                .map(|identity| self.condense_identity(identity))
+                .collect();
+        }
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| {
+                identities
+                    .par_iter()
+                    .map(|identity| self.condense_identity(identity))
+                    .collect()
+            })
+    }
+
+    /// Condenses an identity statement into identities, also returning the name hints
+    /// of the witness columns created in the process.
+    fn condense_identity(
         &self,
         identity: &Identity<Expression>,
-    ) -> Vec<Identity<AlgebraicExpression<T>>> {
-        if identity.kind == IdentityKind::Polynomial {
-            self.condense_to_constraint_or_array(identity.expression_for_poly_id())
-                .into_iter()
-                .map(|constraint| {
-                    Identity::from_polynomial_identity(
-                        identity.id,
-                        identity.source.clone(),
-                        constraint,
-                    )
-                })
-                .collect()
+    ) -> Result<CondensedIdentity<T>, CondenseError> {
+        let symbols = IdentitySymbols {
+            condenser: self,
+            new_columns: Default::default(),
+        };
+        self.condense_identity_inner(identity, &symbols)
+            .map(|condensed| CondensedIdentity {
+                new_columns: symbols.new_columns.into_inner(),
+                ..condensed
+            })
+            .map_err(|error| CondenseError {
+                source: identity.source.clone(),
+                error,
+                identity: Some(identity.describe()),
+            })
+    }
+
+    fn condense_identity_inner<'b>(
+        &self,
+        identity: &'b Identity<Expression>,
+        symbols: &impl SymbolLookup<'b, T>,
+    ) -> Result<CondensedIdentity<T>, EvalError> {
+        Ok(if identity.kind == IdentityKind::Polynomial {
+            self.condense_to_constraint_or_array(identity, symbols)?
         } else {
-            vec![Identity {
+            let condensed = Identity {
                 id: identity.id,
                 kind: identity.kind,
                 source: identity.source.clone(),
-                left: self.condense_selected_expressions(&identity.left),
-                right: self.condense_selected_expressions(&identity.right),
-            }]
-        }
+                label: identity.label.clone(),
+                left: self.condense_selected_expressions(&identity.left, symbols)?,
+                right: self.condense_selected_expressions(&identity.right, symbols)?,
+            };
+            CondensedIdentity {
+                identities: vec![condensed],
+                is_array: false,
+                new_columns: vec![],
+            }
+        })
     }
 
-    fn condense_selected_expressions(
+    /// Condenses the value of an intermediate column (or intermediate column array).
+    fn condense_intermediate_column(
         &self,
-        sel_expr: &SelectedExpressions<Expression>,
-    ) -> SelectedExpressions<AlgebraicExpression<T>> {
-        SelectedExpressions {
+        name: &str,
+        symbol: &Symbol,
+        definition: &Option<FunctionValueDefinition>,
+    ) -> Result<Vec<AlgebraicExpression<T>>, CondenseError> {
+        self.condense_intermediate_column_inner(name, symbol, definition)
+            .map_err(|error| CondenseError {
+                source: symbol.source.clone(),
+                error,
+                identity: None,
+            })
+    }
+
+    fn condense_intermediate_column_inner(
+        &self,
+        name: &str,
+        symbol: &Symbol,
+        definition: &Option<FunctionValueDefinition>,
+    ) -> Result<Vec<AlgebraicExpression<T>>, EvalError> {
+        let Some(FunctionValueDefinition::Expression(e)) = definition else {
+            return Err(EvalError::TypeError(format!(
+                "Intermediate column {name} has to be defined by an expression."
+            )));
+        };
+        let Some(scheme) = e.type_scheme.as_ref().filter(|s| s.vars.is_empty()) else {
+            return Err(EvalError::TypeError(format!(
+                "Intermediate column {name} has to have a concrete type."
+            )));
+        };
+        Ok(if let Some(length) = symbol.length {
+            if !is_array_of_expr(&scheme.ty) {
+                return Err(EvalError::TypeError(format!(
+                    "Intermediate column type has to be expr[], but got: {}",
+                    format_type_scheme_around_name(name, &e.type_scheme)
+                )));
+            }
+            let result = self
+                .condense_to_array_of_algebraic_expressions(&e.e, self)
+                .map_err(|e| match e {
+                    EvalError::TypeError(msg) => EvalError::TypeError(format!(
+                        "Error condensing intermediate column {name}: {msg}"
+                    )),
+                    e => e,
+                })?;
+            if result.len() as u64 != length {
+                return Err(EvalError::TypeError(format!(
+                    "Error condensing intermediate column {name}: Declared length is {length}, but its value has {} elements.",
+                    result.len()
+                )));
+            }
+            result
+        } else {
+            if scheme.ty != Type::Expr {
+                return Err(EvalError::TypeError(format!(
+                    "Intermediate column type has to be expr, but got: {}",
+                    format_type_scheme_around_name(name, &e.type_scheme)
+                )));
+            }
+            vec![self.condense_to_algebraic_expression(&e.e, self)?]
+        })
+    }
+
+    fn condense_selected_expressions<'b>(
+        &self,
+        sel_expr: &'b SelectedExpressions<Expression>,
+        symbols: &impl SymbolLookup<'b, T>,
+    ) -> Result<SelectedExpressions<AlgebraicExpression<T>>, EvalError> {
+        Ok(SelectedExpressions {
             selector: sel_expr
                 .selector
                 .as_ref()
-                .map(|expr| self.condense_to_algebraic_expression(expr)),
+                .map(|expr| self.condense_to_algebraic_expression(expr, symbols))
+                .transpose()?,
             expressions: sel_expr
                 .expressions
                 .iter()
-                .map(|expr| self.condense_to_algebraic_expression(expr))
-                .collect(),
-        }
+                .map(|expr| self.condense_to_algebraic_expression(expr, symbols))
+                .collect::<Result<_, _>>()?,
+        })
     }
 
     /// Evaluates the expression and expects it to result in an algebraic expression.
-    fn condense_to_algebraic_expression(&self, e: &Expression) -> AlgebraicExpression<T> {
-        let result = evaluator::evaluate(e, &self.symbols()).unwrap_or_else(|err| {
-            panic!("Error reducing expression to constraint:\nExpression: {e}\nError: {err:?}")
-        });
+    fn condense_to_algebraic_expression<'b>(
+        &self,
+        e: &'b Expression,
+        symbols: &impl SymbolLookup<'b, T>,
+    ) -> Result<AlgebraicExpression<T>, EvalError> {
+        let result = evaluator::evaluate(e, symbols)?;
         match result.as_ref() {
-            Value::Expression(expr) => expr.clone(),
-            _ => panic!("Expected expression but got {result}"),
+            Value::Expression(expr) => Ok(expr.clone()),
+            _ => Err(EvalError::TypeError(format!(
+                "Expected expression but got {result}"
+            ))),
         }
     }
 
     /// Evaluates the expression and expects it to result in an array of algebraic expressions.
-    fn condense_to_array_of_algebraic_expressions(
+    fn condense_to_array_of_algebraic_expressions<'b>(
         &self,
-        e: &Expression,
-    ) -> Vec<AlgebraicExpression<T>> {
-        let result = evaluator::evaluate(e, &self.symbols()).unwrap_or_else(|err| {
-            panic!("Error reducing expression to constraint:\nExpression: {e}\nError: {err:?}")
-        });
+        e: &'b Expression,
+        symbols: &impl SymbolLookup<'b, T>,
+    ) -> Result<Vec<AlgebraicExpression<T>>, EvalError> {
+        let result = evaluator::evaluate(e, symbols)?;
         match result.as_ref() {
-            Value::Array(items) => items
-                .iter()
-                .map(|item| match item.as_ref() {
-                    Value::Expression(expr) => expr.clone(),
-                    _ => panic!("Expected expression but got {item}"),
-                })
-                .collect(),
-            _ => panic!("Expected array of algebraic expressions, but got {result}"),
+            Value::Array(_) => {
+                let mut expressions = vec![];
+                flatten_expressions(&result, &mut expressions)?;
+                Ok(expressions)
+            }
+            _ => Err(EvalError::TypeError(format!(
+                "Expected array of algebraic expressions, but got {result}"
+            ))),
         }
     }
 
-    /// Evaluates an expression and expects a single constraint or an array of constraints.
-    fn condense_to_constraint_or_array(&self, e: &Expression) -> Vec<AlgebraicExpression<T>> {
-        let result = evaluator::evaluate(e, &self.symbols()).unwrap_or_else(|err| {
-            panic!("Error reducing expression to constraint:\nExpression: {e}\nError: {err:?}")
-        });
+    /// Evaluates the expression of a polynomial identity statement and expects a single
+    /// constraint or an array of constraints. Constraints can be polynomial identities,
+    /// lookups or permutations.
+    fn condense_to_constraint_or_array<'b>(
+        &self,
+        identity: &'b Identity<Expression>,
+        symbols: &impl SymbolLookup<'b, T>,
+    ) -> Result<CondensedIdentity<T>, EvalError> {
+        let result = evaluator::evaluate(identity.expression_for_poly_id(), symbols)?;
         match result.as_ref() {
-            Value::Identity(left, right) => vec![left.clone() - right.clone()],
             Value::Array(items) => items
                 .iter()
                 .map(|item| {
-                    if let Value::Identity(left, right) = item.as_ref() {
-                        left.clone() - right.clone()
-                    } else {
-                        panic!("Expected constraint, but got {item}")
-                    }
+                    to_constraint(item, identity).ok_or_else(|| {
+                        EvalError::TypeError(format!(
+                            "Expected constraint, but got {item}: {}",
+                            item.type_formatted()
+                        ))
+                    })
                 })
-                .collect::<Vec<_>>(),
-            _ => panic!("Expected constraint or array of constraints, but got {result}"),
+                .collect::<Result<_, _>>()
+                .map(|identities| CondensedIdentity {
+                    identities,
+                    is_array: true,
+                    new_columns: vec![],
+                }),
+            value => to_constraint(value, identity)
+                .map(|constraint| CondensedIdentity {
+                    identities: vec![constraint],
+                    is_array: false,
+                    new_columns: vec![],
+                })
+                .ok_or_else(|| {
+                    EvalError::TypeError(format!(
+                        "Expected constraint or array of constraints, but got {result}: {}",
+                        result.type_formatted()
+                    ))
+                }),
         }
     }
 
-    fn symbols(&self) -> Definitions<'_> {
-        Definitions(&self.symbols)
+    fn symbols(&self) -> Definitions<'a> {
+        Definitions(self.symbols)
+    }
+}
+
+impl<'a, T: FieldElement> SymbolLookup<'a, T> for Condenser<'a, T> {
+    fn lookup(
+        &self,
+        name: &'a str,
+        generic_args: Option<Vec<Type>>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        self.symbols()
+            .lookup_with_host(name, generic_args, self.host_symbols, self)
+    }
+
+    fn fixed_column_definition(
+        &self,
+        name: &str,
+    ) -> Result<&'a FunctionValueDefinition, EvalError> {
+        self.symbols().lookup_fixed_column(name)
+    }
+
+    fn lookup_public_reference(&self, name: &'a str) -> Result<Arc<Value<'a, T>>, EvalError> {
+        if !self
+            .public_declarations
+            .contains_key(&AbsoluteSymbolPath::from_dotted_string(name))
+        {
+            return Err(EvalError::SymbolNotFound(format!(
+                "Public {name} is referenced but not declared."
+            )));
+        }
+        Ok(Value::from(AlgebraicExpression::PublicReference(name.to_string())).into())
+    }
+
+    fn limits(&self) -> EvaluationLimits {
+        self.limits
+    }
+
+    fn call_cache(&self) -> Option<&CallCache<'a, T>> {
+        Some(&self.call_cache)
+    }
+}
+
+/// The result of condensing an identity statement.
+struct CondensedIdentity<T> {
+    identities: Vec<Identity<AlgebraicExpression<T>>>,
+    /// Whether the statement evaluated to an array of constraints.
+    is_array: bool,
+    /// The name hints of the witness columns created during condensation.
+    new_columns: Vec<String>,
+}
+
+/// Witness columns created during condensation refer to provisional
+/// polynomial IDs starting at this value until they are allocated.
+const PROVISIONAL_ID_START: u64 = u64::MAX / 2;
+
+/// Symbol lookup used when condensing identities. In addition to the definitions,
+/// it allows the creation of new witness columns. Since identities can be condensed
+/// in parallel, the new columns only receive provisional references here, which are
+/// replaced by references to the allocated columns afterwards.
+struct IdentitySymbols<'a, 'c, T> {
+    condenser: &'c Condenser<'a, T>,
+    /// The name hints of the witness columns created so far.
+    new_columns: RefCell<Vec<String>>,
+}
+
+impl<'a, 'c, T: FieldElement> SymbolLookup<'a, T> for IdentitySymbols<'a, 'c, T> {
+    fn lookup(
+        &self,
+        name: &'a str,
+        generic_args: Option<Vec<Type>>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        self.condenser.symbols().lookup_with_host(
+            name,
+            generic_args,
+            self.condenser.host_symbols,
+            self,
+        )
+    }
+
+    fn fixed_column_definition(
+        &self,
+        name: &str,
+    ) -> Result<&'a FunctionValueDefinition, EvalError> {
+        self.condenser.fixed_column_definition(name)
+    }
+
+    fn lookup_public_reference(&self, name: &'a str) -> Result<Arc<Value<'a, T>>, EvalError> {
+        self.condenser.lookup_public_reference(name)
+    }
+
+    fn limits(&self) -> EvaluationLimits {
+        self.condenser.limits
+    }
+
+    fn call_cache(&self) -> Option<&CallCache<'a, T>> {
+        Some(&self.condenser.call_cache)
+    }
+
+    fn new_witness_column(&self, name_hint: &str) -> Result<Arc<Value<'a, T>>, EvalError> {
+        let mut chars = name_hint.chars();
+        if !chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(EvalError::TypeError(format!(
+                "Invalid name for new witness column: \"{name_hint}\""
+            )));
+        }
+        let mut new_columns = self.new_columns.borrow_mut();
+        let index = new_columns.len() as u64;
+        new_columns.push(name_hint.to_string());
+        Ok(
+            Value::from(AlgebraicExpression::Reference(AlgebraicReference {
+                name: name_hint.into(),
+                poly_id: PolyID {
+                    id: PROVISIONAL_ID_START + index,
+                    ptype: PolynomialType::Committed,
+                },
+                next: false,
+            }))
+            .into(),
+        )
+    }
+}
+
+/// Allocates IDs and unique names for witness columns created during condensation.
+struct NewColumns<'a> {
+    definitions: &'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    next_id: u64,
+    names: HashSet<AbsoluteSymbolPath>,
+    symbols: Vec<Symbol>,
+}
+
+impl<'a> NewColumns<'a> {
+    fn new(
+        definitions: &'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    ) -> Self {
+        let next_id = definitions
+            .values()
+            .filter(|(symbol, _)| symbol.kind == SymbolKind::Poly(PolynomialType::Committed))
+            .map(|(symbol, _)| symbol.id + symbol.length.unwrap_or(1))
+            .max()
+            .unwrap_or_default();
+        NewColumns {
+            definitions,
+            next_id,
+            names: Default::default(),
+            symbols: vec![],
+        }
+    }
+
+    /// Allocates a witness column in the given namespace. The name is derived from the
+    /// name hint, adding a numeric suffix if the name is already taken.
+    fn allocate(
+        &mut self,
+        namespace: &AbsoluteSymbolPath,
+        name_hint: &str,
+        identity: &Identity<Expression>,
+    ) -> Symbol {
+        let name = (0..)
+            .map(|i| match i {
+                0 => namespace.with_part(name_hint),
+                i => namespace.with_part(&format!("{name_hint}_{i}")),
+            })
+            .find(|name| !self.definitions.contains_key(name) && !self.names.contains(name))
+            .unwrap();
+        let symbol = Symbol {
+            id: self.next_id,
+            source: identity.source.clone(),
+            absolute_name: name.to_dotted_string(),
+            kind: SymbolKind::Poly(PolynomialType::Committed),
+            length: None,
+        };
+        self.names.insert(name);
+        self.next_id += 1;
+        self.symbols.push(symbol.clone());
+        symbol
+    }
+}
+
+/// Replaces the provisional references to new witness columns by references
+/// to the allocated columns.
+fn replace_provisional_references<T>(
+    identity: &mut Identity<AlgebraicExpression<T>>,
+    columns: &[Symbol],
+) {
+    identity.post_visit_expressions_mut(&mut |e| {
+        if let AlgebraicExpression::Reference(reference) = e {
+            if reference.poly_id.ptype == PolynomialType::Committed
+                && reference.poly_id.id >= PROVISIONAL_ID_START
+            {
+                let column = &columns[(reference.poly_id.id - PROVISIONAL_ID_START) as usize];
+                reference.name = column.absolute_name.as_str().into();
+                reference.poly_id = column.into();
+            }
+        }
+    });
+}
+
+/// Returns true if the identity is a lookup or permutation that is trivially
+/// satisfied because its selectors are zero.
+fn has_zero_selector<T: FieldElement>(identity: &Identity<AlgebraicExpression<T>>) -> bool {
+    let is_zero = |selector: &Option<AlgebraicExpression<T>>| matches!(selector, Some(AlgebraicExpression::Number(n)) if *n == 0.into());
+    match identity.kind {
+        IdentityKind::Plookup => is_zero(&identity.left.selector),
+        IdentityKind::Permutation => {
+            is_zero(&identity.left.selector) && is_zero(&identity.right.selector)
+        }
+        IdentityKind::Polynomial | IdentityKind::Connect => false,
+    }
+}
+
+/// Removes selectors of lookups and permutations that are the constant one.
+fn remove_unit_selectors<T: FieldElement>(identity: &mut Identity<AlgebraicExpression<T>>) {
+    if matches!(
+        identity.kind,
+        IdentityKind::Plookup | IdentityKind::Permutation
+    ) {
+        for side in [&mut identity.left, &mut identity.right] {
+            if matches!(&side.selector, Some(AlgebraicExpression::Number(n)) if n.is_one()) {
+                side.selector = None;
+            }
+        }
+    }
+}
+
+/// Converts a constraint value into an identity, using the ID and source of
+/// the identity statement it originates from.
+/// Returns None if the value is not a constraint.
+fn to_constraint<T: FieldElement>(
+    value: &Value<'_, T>,
+    identity: &Identity<Expression>,
+) -> Option<Identity<AlgebraicExpression<T>>> {
+    let (kind, left, right) = match value {
+        Value::Identity(left, right) => {
+            return Some(Identity {
+                label: identity.label.clone(),
+                ..Identity::from_polynomial_identity(
+                    identity.id,
+                    identity.source.clone(),
+                    left.clone() - right.clone(),
+                )
+            })
+        }
+        Value::Lookup(left, right) => (IdentityKind::Plookup, left, right),
+        Value::Permutation(left, right) => (IdentityKind::Permutation, left, right),
+        _ => return None,
+    };
+    Some(Identity {
+        id: identity.id,
+        kind,
+        source: identity.source.clone(),
+        label: identity.label.clone(),
+        left: left.clone(),
+        right: right.clone(),
+    })
+}
+
+/// Returns true if the type is a (possibly nested) array with element type `expr`.
+fn is_array_of_expr(ty: &Type) -> bool {
+    match ty {
+        Type::Array(ArrayType { base, length: _ }) => {
+            base.as_ref() == &Type::Expr || is_array_of_expr(base)
+        }
+        _ => false,
+    }
+}
+
+/// Collects the algebraic expressions of a (possibly nested) array value in order.
+fn flatten_expressions<T: FieldElement>(
+    value: &Value<'_, T>,
+    result: &mut Vec<AlgebraicExpression<T>>,
+) -> Result<(), EvalError> {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .try_for_each(|item| flatten_expressions(item, result)),
+        Value::Expression(expr) => {
+            result.push(expr.clone());
+            Ok(())
+        }
+        _ => Err(EvalError::TypeError(format!(
+            "Expected expression but got {value}"
+        ))),
     }
 }
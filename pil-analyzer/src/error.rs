@@ -0,0 +1,30 @@
+use std::fmt::{self, Display};
+
+use powdr_ast::SourceRef;
+
+use crate::condenser::CondenseError;
+
+/// An error that prevents the analysis of a PIL source.
+#[derive(Debug)]
+pub enum AnalysisError {
+    /// The source could not be parsed.
+    Parse(String),
+    /// A statement is invalid, for example because it references an unknown symbol
+    /// or declares an array of columns whose length is not a non-negative integer.
+    Statement(SourceRef, String),
+    /// The types could not be inferred.
+    TypeInference(String),
+    /// An identity or definition could not be condensed.
+    Condensation(CondenseError),
+}
+
+impl Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalysisError::Parse(msg) => write!(f, "Error parsing .pil file: {msg}"),
+            AnalysisError::Statement(source, msg) => write!(f, "{source}: {msg}"),
+            AnalysisError::TypeInference(msg) => write!(f, "Error during type inference:\n{msg}"),
+            AnalysisError::Condensation(error) => write!(f, "{error}"),
+        }
+    }
+}
@@ -1,27 +1,39 @@
 use std::{
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display},
-    sync::Arc,
+    ops::Range,
+    sync::{Arc, RwLock},
 };
 
 use itertools::Itertools;
+use num_traits::{One, Signed, Zero};
 use powdr_ast::{
     analyzed::{
-        AlgebraicExpression, AlgebraicReference, Expression, FunctionValueDefinition, Reference,
-        Symbol, SymbolKind, TypedExpression,
+        type_from_definition, AlgebraicExpression, AlgebraicReference, Analyzed, Expression,
+        FunctionValueDefinition, PolynomialType, Reference, Symbol, SymbolKind, TypedExpression,
     },
     parsed::{
+        asm::AbsoluteSymbolPath,
         display::quote,
-        types::{Type, TypeScheme},
-        BinaryOperator, FunctionCall, LambdaExpression, MatchArm, MatchPattern, UnaryOperator,
+        types::{FunctionType, Type, TypeScheme},
+        BinaryOperator, FunctionCall, LambdaExpression, LiteralType, MatchArm, MatchPattern,
+        RangeKind, SelectedExpressions, UnaryOperator,
     },
 };
-use powdr_number::{BigInt, BigUint, FieldElement, LargeInt};
+use powdr_number::{
+    BabyBearField, BigInt, BigUint, Bn254Field, DegreeType, FieldElement, GoldilocksField,
+    KnownField, LargeInt, Mersenne31Field,
+};
+
+use rayon::prelude::*;
+
+use crate::type_builtins::{builtin_schemes, int_fe_mixing_error};
 
 /// Evaluates an expression given a hash map of definitions.
 pub fn evaluate_expression<'a, T: FieldElement>(
     expr: &'a Expression,
-    definitions: &'a HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
+    definitions: &'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
 ) -> Result<Arc<Value<'a, T>>, EvalError> {
     evaluate(expr, &Definitions(definitions))
 }
@@ -50,37 +62,189 @@ pub fn evaluate_function_call<'a, T: FieldElement>(
     arguments: Vec<Arc<Value<'a, T>>>,
     symbols: &impl SymbolLookup<'a, T>,
 ) -> Result<Arc<Value<'a, T>>, EvalError> {
-    match function.as_ref() {
-        Value::BuiltinFunction(b) => internal::evaluate_builtin_function(*b, arguments, symbols),
-        Value::Closure(Closure {
-            lambda,
-            environment,
-            generic_args,
-        }) => {
-            if lambda.params.len() != arguments.len() {
-                Err(EvalError::TypeError(format!(
-                    "Invalid function call: Supplied {} arguments to function that takes {} parameters.\nFunction: {lambda}\nArguments: {}",
-                    arguments.len(),
-                    lambda.params.len(),
-                    arguments.iter().format(", ")
+    internal::evaluate_function_call(function, arguments, symbols)
+}
 
-                )))?
+/// Evaluates a call of the function or fixed column with the given name,
+/// using the same definitions the condenser uses.
+/// The name is absolute and can use `::` or `.` as separators, e.g.
+/// `std::utils::unwrap_or_else` or `Main.f`.
+/// The number of arguments is checked against the type of the symbol.
+pub fn evaluate_function<'a, T: FieldElement>(
+    analyzed: &'a Analyzed<T>,
+    name: &str,
+    arguments: Vec<Value<'a, T>>,
+) -> Result<Value<'a, T>, EvalError> {
+    let (name, (symbol, value)) = resolve_symbol(&analyzed.definitions, name)?;
+    let ty = builtin_schemes()
+        .get(name)
+        .cloned()
+        .or_else(|| type_from_definition(symbol, value))
+        .map(|scheme| scheme.ty);
+    if let Some(ty) = ty {
+        let param_count = match &ty {
+            Type::Function(FunctionType { params, .. }) => params.len(),
+            // Fixed columns are functions from the row to a field element.
+            Type::Col => 1,
+            _ => Err(EvalError::TypeError(format!(
+                "Symbol {name} of type {ty} is not a function."
+            )))?,
+        };
+        if param_count != arguments.len() {
+            Err(EvalError::TypeError(format!(
+                "Function {name} of type {ty} expects {param_count} arguments, but {} were supplied.",
+                arguments.len()
+            )))?
+        }
+    }
+
+    let symbols = Definitions(&analyzed.definitions);
+    let function = if let Some(b) = builtin_function(name) {
+        Value::BuiltinFunction(b).into()
+    } else {
+        match value {
+            // A reference to a fixed column is an algebraic expression,
+            // but here we want to evaluate its definition.
+            Some(FunctionValueDefinition::Expression(TypedExpression { e, .. }))
+                if symbol.kind == SymbolKind::Poly(PolynomialType::Constant) =>
+            {
+                evaluate(e, &symbols)?
             }
+            _ => symbols.lookup(name, None)?,
+        }
+    };
+    let result = evaluate_function_call(
+        function,
+        arguments.into_iter().map(Arc::new).collect(),
+        &symbols,
+    )?;
+    Ok(Arc::try_unwrap(result).unwrap_or_else(|result| result.as_ref().clone()))
+}
 
-            let local_vars = environment
-                .iter()
-                .cloned()
-                .chain(arguments)
+/// Evaluates the fixed column defined by `definition` at the rows `0..degree` and returns
+/// the values in row order. For an array of fixed columns, `array_index` selects the element.
+/// The rows are split into chunks that are evaluated using `threads` threads, where
+/// `threads <= 1` means sequential evaluation.
+/// All chunks share the call cache of `symbols`. It only contains results of calls
+/// that do not depend on anything but their arguments, so the values and the reported
+/// error do not depend on the number of threads.
+pub fn evaluate_fixed_column<'a, T: FieldElement>(
+    definition: &'a FunctionValueDefinition,
+    array_index: Option<u64>,
+    degree: DegreeType,
+    threads: usize,
+    symbols: &(impl SymbolLookup<'a, T> + Sync),
+) -> Result<Vec<T>, EvalError> {
+    match definition {
+        FunctionValueDefinition::Expression(TypedExpression { e, .. }) => {
+            let evaluate_rows = |rows: Range<DegreeType>| {
+                let function = evaluate(e, symbols)?;
+                let function = match array_index {
+                    Some(index) => match function.as_ref() {
+                        Value::Array(items) => {
+                            items.get(index as usize).cloned().ok_or_else(|| {
+                                EvalError::OutOfBounds(format!(
+                                    "Index {index} out of bounds for an array of {} fixed columns.",
+                                    items.len()
+                                ))
+                            })?
+                        }
+                        _ => Err(EvalError::TypeError(format!(
+                            "Expected an array of fixed columns, but got {function}."
+                        )))?,
+                    },
+                    None => function,
+                };
+                rows.map(|row| {
+                    let argument = Value::Integer(BigInt::from(row)).into();
+                    evaluate_function_call(function.clone(), vec![argument], symbols)?
+                        .try_to_field_element()
+                })
+                .collect::<Result<Vec<_>, _>>()
+            };
+            if threads <= 1 {
+                return evaluate_rows(0..degree);
+            }
+            // Use a few chunks per thread to balance rows of different cost.
+            let chunk_size = degree.div_ceil(threads as DegreeType * 4).max(1);
+            let chunks = (0..degree)
+                .step_by(chunk_size as usize)
+                .map(|start| start..degree.min(start + chunk_size))
                 .collect::<Vec<_>>();
-
-            internal::evaluate(&lambda.body, &local_vars, generic_args, symbols)
+            let results = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| {
+                    chunks
+                        .into_par_iter()
+                        .map(evaluate_rows)
+                        .collect::<Vec<_>>()
+                });
+            // Collect sequentially so that the error of the first failing row is returned.
+            results
+                .into_iter()
+                .flatten_ok()
+                .collect::<Result<Vec<_>, _>>()
         }
-        e => Err(EvalError::TypeError(format!(
-            "Expected function but got {e}"
-        ))),
+        FunctionValueDefinition::Array(items) => {
+            let values = items
+                .iter()
+                .map(|item| {
+                    let pattern = item
+                        .pattern()
+                        .iter()
+                        .map(|e| evaluate(e, symbols)?.try_to_field_element())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(pattern
+                        .into_iter()
+                        .cycle()
+                        .take(item.size() as usize)
+                        .collect::<Vec<_>>())
+                })
+                .flatten_ok()
+                .collect::<Result<Vec<_>, EvalError>>()?;
+            if values.len() as DegreeType != degree {
+                Err(EvalError::TypeError(format!(
+                    "Fixed column has {} rows, but the degree is {degree}.",
+                    values.len()
+                )))?
+            }
+            Ok(values)
+        }
+        FunctionValueDefinition::Sparse(sparse) => {
+            if sparse.size() != degree {
+                Err(EvalError::TypeError(format!(
+                    "Fixed column has {} rows, but the degree is {degree}.",
+                    sparse.size()
+                )))?
+            }
+            // Only the default value and the entries are evaluated, not every row.
+            let default = evaluate(sparse.default_value(), symbols)?.try_to_field_element()?;
+            let mut values = vec![default; degree as usize];
+            for (row, e) in sparse.entries() {
+                values[*row as usize] = evaluate(e, symbols)?.try_to_field_element()?;
+            }
+            Ok(values)
+        }
+        FunctionValueDefinition::Query(_) => Err(EvalError::TypeError(
+            "Query used for fixed column.".to_string(),
+        )),
     }
 }
 
+/// Finds the definition of a symbol given its absolute name with `::` or `.` as separators.
+/// Returns the name as used in references together with the definition.
+fn resolve_symbol<'a>(
+    definitions: &'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    name: &str,
+) -> Result<(&'a str, &'a (Symbol, Option<FunctionValueDefinition>)), EvalError> {
+    definitions
+        .get(&AbsoluteSymbolPath::from_dotted_string(name))
+        .map(|definition| (definition.0.absolute_name.as_str(), definition))
+        .ok_or_else(|| EvalError::SymbolNotFound(format!("Symbol {name} not found.")))
+}
+
 /// Turns an optional type scheme and a list of generic type arguments into a mapping
 /// from type name to type.
 pub fn generic_arg_mapping(
@@ -131,6 +295,115 @@ pub enum EvalError {
     DataNotAvailable,
     /// Failed assertion, with reason.
     FailedAssertion(String),
+    /// Arithmetic error like division by zero, with the operation.
+    ArithmeticError(String),
+    /// The maximum call depth or number of evaluation steps was exceeded.
+    LimitExceeded(String),
+    /// A number that does not fit into the field it is converted into.
+    FieldOverflow(FieldOverflow),
+    /// An error that occurred inside of function calls, together with the
+    /// calls that were active at that point, innermost first, and the maximum
+    /// number of them shown when displaying the error.
+    InCall(Box<EvalError>, Vec<StackFrame>, usize),
+}
+
+impl EvalError {
+    /// Returns the error without the call stack.
+    pub fn without_call_stack(&self) -> &EvalError {
+        match self {
+            EvalError::InCall(error, _, _) => error,
+            e => e,
+        }
+    }
+
+    /// Returns the calls that were active when the error occurred, innermost first.
+    pub fn call_stack(&self) -> &[StackFrame] {
+        match self {
+            EvalError::InCall(_, frames, _) => frames,
+            _ => &[],
+        }
+    }
+
+    /// Adds the calls that were active when the error occurred, innermost first,
+    /// as outer frames to the existing call stack.
+    /// At most `max_printed_frames` of them are shown when displaying the error.
+    /// `NoMatch` and `DataNotAvailable` are used for control flow and thus not extended.
+    fn with_call_stack(
+        self,
+        frames: impl IntoIterator<Item = StackFrame>,
+        max_printed_frames: usize,
+    ) -> Self {
+        match self {
+            EvalError::NoMatch() | EvalError::DataNotAvailable => self,
+            EvalError::InCall(error, mut existing, _) => {
+                existing.extend(frames);
+                EvalError::InCall(error, existing, max_printed_frames)
+            }
+            error => {
+                let frames = frames.into_iter().collect::<Vec<_>>();
+                if frames.is_empty() {
+                    error
+                } else {
+                    EvalError::InCall(Box::new(error), frames, max_printed_frames)
+                }
+            }
+        }
+    }
+}
+
+/// A number that is not less than the modulus of the field it is converted into.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldOverflow {
+    pub value: BigUint,
+    /// The field, if it is a known field.
+    pub field: Option<KnownField>,
+    pub modulus: BigUint,
+}
+
+impl FieldOverflow {
+    /// Converts `value` into a field element if it is less than the modulus of `T`.
+    pub fn check<T: FieldElement>(value: BigUint) -> Result<T, EvalError> {
+        let modulus = T::modulus().to_arbitrary_integer();
+        if value < modulus {
+            Ok(T::from(value))
+        } else {
+            Err(EvalError::FieldOverflow(FieldOverflow {
+                value,
+                field: T::known_field(),
+                modulus,
+            }))
+        }
+    }
+}
+
+impl Display for FieldOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Number {} is too large for the ", self.value)?;
+        if let Some(field) = self.field {
+            write!(f, "{field} ")?;
+        }
+        write!(f, "field with modulus {}.", self.modulus)
+    }
+}
+
+/// A function call that was active when an error occurred.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    /// The called function, usually the name of a symbol.
+    pub function: String,
+    /// Short descriptions of the arguments.
+    pub arguments: Vec<String>,
+}
+
+impl Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}({})",
+            self.function,
+            self.arguments.iter().format(", ")
+        )
+    }
 }
 
 impl Display for EvalError {
@@ -143,6 +416,28 @@ impl Display for EvalError {
             EvalError::SymbolNotFound(msg) => write!(f, "Symbol not found: {msg}"),
             EvalError::DataNotAvailable => write!(f, "Data not (yet) available."),
             EvalError::FailedAssertion(msg) => write!(f, "Assertion failed: {msg}"),
+            EvalError::ArithmeticError(msg) => write!(f, "Arithmetic error: {msg}"),
+            EvalError::LimitExceeded(msg) => write!(f, "Evaluation limit exceeded: {msg}"),
+            EvalError::FieldOverflow(overflow) => write!(f, "Field overflow: {overflow}"),
+            EvalError::InCall(error, frames, max) => {
+                write!(f, "{error}\nCall stack (innermost call first):")?;
+                let max = *max;
+                if frames.len() <= max {
+                    for frame in frames {
+                        write!(f, "\n    {frame}")?;
+                    }
+                } else {
+                    let (inner, outer) = (max.div_ceil(2), max / 2);
+                    for frame in &frames[..inner] {
+                        write!(f, "\n    {frame}")?;
+                    }
+                    write!(f, "\n    … {} frames omitted", frames.len() - max)?;
+                    for frame in &frames[frames.len() - outer..] {
+                        write!(f, "\n    {frame}")?;
+                    }
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -157,14 +452,20 @@ pub enum Value<'a, T> {
     Array(Vec<Arc<Self>>),
     Closure(Closure<'a, T>),
     BuiltinFunction(BuiltinFunction),
+    /// A function implemented in Rust, provided by [HostSymbols].
+    HostFunction(HostFunction<T>),
     Expression(AlgebraicExpression<T>),
     Identity(AlgebraicExpression<T>, AlgebraicExpression<T>),
-}
-
-impl<'a, T: FieldElement> From<T> for Value<'a, T> {
-    fn from(value: T) -> Self {
-        Value::FieldElement(value)
-    }
+    /// A lookup constraint `left in right`.
+    Lookup(
+        SelectedExpressions<AlgebraicExpression<T>>,
+        SelectedExpressions<AlgebraicExpression<T>>,
+    ),
+    /// A permutation constraint `left is right`.
+    Permutation(
+        SelectedExpressions<AlgebraicExpression<T>>,
+        SelectedExpressions<AlgebraicExpression<T>>,
+    ),
 }
 
 impl<'a, T: FieldElement> From<AlgebraicExpression<T>> for Value<'a, T> {
@@ -173,27 +474,26 @@ impl<'a, T: FieldElement> From<AlgebraicExpression<T>> for Value<'a, T> {
     }
 }
 
+/// Converts an integer to a field element if it is non-negative and less than the
+/// field modulus. Integers that are too large result in a field overflow, negative
+/// integers in a type error with a description of the integer, prefixed by `context`.
+fn integer_to_field_element<T: FieldElement>(x: &BigInt, context: &str) -> Result<T, EvalError> {
+    match BigUint::try_from(x) {
+        Ok(x) => FieldOverflow::check(x),
+        Err(_) => Err(EvalError::TypeError(format!(
+            "{context} negative integer {x} (the field modulus is {})",
+            T::modulus().to_arbitrary_integer()
+        ))),
+    }
+}
+
 impl<'a, T: FieldElement> Value<'a, T> {
     /// Tries to convert the value to a field element. For integers, this only works
     /// if the integer is non-negative and less than the modulus.
     pub fn try_to_field_element(&self) -> Result<T, EvalError> {
         match self {
             Value::FieldElement(x) => Ok(*x),
-            Value::Integer(x) => {
-                if let Ok(x) = BigUint::try_from(x.clone()) {
-                    if x < T::modulus().to_arbitrary_integer() {
-                        Ok(T::from(x))
-                    } else {
-                        Err(EvalError::TypeError(format!(
-                            "Expected field element but got integer outside field range: {x}"
-                        )))
-                    }
-                } else {
-                    Err(EvalError::TypeError(format!(
-                        "Expected field element but got negative integer: {x}"
-                    )))
-                }
-            }
+            Value::Integer(x) => integer_to_field_element(x, "Expected field element but got"),
             v => Err(EvalError::TypeError(format!(
                 "Expected field element but got {v}"
             ))),
@@ -233,43 +533,341 @@ impl<'a, T: FieldElement> Value<'a, T> {
             }
             Value::Closure(c) => c.type_formatted(),
             Value::BuiltinFunction(b) => format!("builtin_{b:?}"),
+            Value::HostFunction(function) => format!("host_{}", function.name),
             Value::Expression(_) => "expr".to_string(),
-            Value::Identity(_, _) => "constr".to_string(),
+            Value::Identity(_, _) | Value::Lookup(_, _) | Value::Permutation(_, _) => {
+                "constr".to_string()
+            }
+        }
+    }
+}
+
+/// Returns the error for a failed conversion of a value to a Rust type.
+fn conversion_error<T: FieldElement>(expected: &str, v: &Value<'_, T>) -> EvalError {
+    EvalError::TypeError(format!(
+        "Expected {expected} but got {v}: {}",
+        v.type_formatted()
+    ))
+}
+
+/// Implements `TryFrom<Value>` for a type in terms of `TryFrom<&Value>`.
+macro_rules! impl_try_from_owned_value {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a, T: FieldElement> TryFrom<Value<'a, T>> for $ty {
+                type Error = EvalError;
+                fn try_from(v: Value<'a, T>) -> Result<Self, EvalError> {
+                    (&v).try_into()
+                }
+            }
+        )*
+    };
+}
+
+impl<'a, T: FieldElement> TryFrom<&Value<'a, T>> for bool {
+    type Error = EvalError;
+    fn try_from(v: &Value<'a, T>) -> Result<Self, EvalError> {
+        match v {
+            Value::Bool(b) => Ok(*b),
+            v => Err(conversion_error("bool", v)),
+        }
+    }
+}
+
+impl<'a, T: FieldElement> TryFrom<&Value<'a, T>> for String {
+    type Error = EvalError;
+    fn try_from(v: &Value<'a, T>) -> Result<Self, EvalError> {
+        match v {
+            Value::String(s) => Ok(s.clone()),
+            v => Err(conversion_error("string", v)),
+        }
+    }
+}
+
+/// Integers are converted from `int` values only, with a range check for
+/// the bounded types. Use `try_to_integer` to also accept field elements.
+impl<'a, T: FieldElement> TryFrom<&Value<'a, T>> for BigInt {
+    type Error = EvalError;
+    fn try_from(v: &Value<'a, T>) -> Result<Self, EvalError> {
+        match v {
+            Value::Integer(x) => Ok(x.clone()),
+            v => Err(conversion_error("int", v)),
+        }
+    }
+}
+
+macro_rules! impl_try_from_value_for_bounded_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a, T: FieldElement> TryFrom<&Value<'a, T>> for $ty {
+                type Error = EvalError;
+                fn try_from(v: &Value<'a, T>) -> Result<Self, EvalError> {
+                    match v {
+                        Value::Integer(x) => <$ty>::try_from(x).map_err(|_| {
+                            EvalError::TypeError(format!(
+                                "Integer {x} does not fit into {}.",
+                                stringify!($ty)
+                            ))
+                        }),
+                        v => Err(conversion_error("int", v)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_value_for_bounded_integer!(BigUint, i64, u64);
+
+/// Field elements are converted from `fe` values and from `int` values
+/// in the range of the field. The conversions are implemented for the concrete
+/// fields because of the orphan rules, generic code can use `try_to_field_element`
+/// and `Value::FieldElement` instead.
+macro_rules! impl_try_from_value_for_field {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a> TryFrom<&Value<'a, $ty>> for $ty {
+                type Error = EvalError;
+                fn try_from(v: &Value<'a, $ty>) -> Result<Self, EvalError> {
+                    v.try_to_field_element()
+                }
+            }
+
+            impl<'a> TryFrom<Value<'a, $ty>> for $ty {
+                type Error = EvalError;
+                fn try_from(v: Value<'a, $ty>) -> Result<Self, EvalError> {
+                    v.try_to_field_element()
+                }
+            }
+
+            impl<'a> From<$ty> for Value<'a, $ty> {
+                fn from(x: $ty) -> Self {
+                    Value::FieldElement(x)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_value_for_field!(GoldilocksField, Bn254Field, BabyBearField, Mersenne31Field);
+
+impl_try_from_owned_value!(bool, String, BigInt, BigUint, i64, u64);
+
+impl<'a, 'b, T: FieldElement, V> TryFrom<&'b Value<'a, T>> for Vec<V>
+where
+    V: TryFrom<&'b Value<'a, T>, Error = EvalError>,
+{
+    type Error = EvalError;
+    fn try_from(v: &'b Value<'a, T>) -> Result<Self, EvalError> {
+        match v {
+            Value::Array(items) => items.iter().map(|item| item.as_ref().try_into()).collect(),
+            v => Err(conversion_error("array", v)),
+        }
+    }
+}
+
+impl<'a, T: FieldElement, V> TryFrom<Value<'a, T>> for Vec<V>
+where
+    V: for<'b> TryFrom<&'b Value<'a, T>, Error = EvalError>,
+{
+    type Error = EvalError;
+    fn try_from(v: Value<'a, T>) -> Result<Self, EvalError> {
+        (&v).try_into()
+    }
+}
+
+/// Implements conversions from tuple values to Rust tuples of the given arity.
+macro_rules! impl_try_from_value_for_tuple {
+    ($len:literal: $($name:ident),*) => {
+        impl<'a, 'b, T: FieldElement, $($name),*> TryFrom<&'b Value<'a, T>> for ($($name,)*)
+        where
+            $($name: TryFrom<&'b Value<'a, T>, Error = EvalError>),*
+        {
+            type Error = EvalError;
+            fn try_from(v: &'b Value<'a, T>) -> Result<Self, EvalError> {
+                match v {
+                    Value::Tuple(items) if items.len() == $len => {
+                        let mut items = items.iter();
+                        Ok(($(<$name>::try_from(items.next().unwrap().as_ref())?,)*))
+                    }
+                    v => Err(conversion_error(concat!("tuple of length ", $len), v)),
+                }
+            }
+        }
+
+        impl<'a, T: FieldElement, $($name),*> TryFrom<Value<'a, T>> for ($($name,)*)
+        where
+            $($name: for<'b> TryFrom<&'b Value<'a, T>, Error = EvalError>),*
+        {
+            type Error = EvalError;
+            fn try_from(v: Value<'a, T>) -> Result<Self, EvalError> {
+                (&v).try_into()
+            }
         }
+    };
+}
+
+impl_try_from_value_for_tuple!(2: A, B);
+impl_try_from_value_for_tuple!(3: A, B, C);
+impl_try_from_value_for_tuple!(4: A, B, C, D);
+
+impl<'a, T: FieldElement> From<bool> for Value<'a, T> {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl<'a, T: FieldElement> From<String> for Value<'a, T> {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl<'a, T: FieldElement> From<&str> for Value<'a, T> {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl<'a, T: FieldElement> From<BigInt> for Value<'a, T> {
+    fn from(x: BigInt) -> Self {
+        Value::Integer(x)
+    }
+}
+
+macro_rules! impl_value_from_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a, T: FieldElement> From<$ty> for Value<'a, T> {
+                fn from(x: $ty) -> Self {
+                    Value::Integer(x.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_value_from_integer!(BigUint, i64, u64);
+
+impl<'a, T: FieldElement, V: Into<Value<'a, T>>> From<Vec<V>> for Value<'a, T> {
+    fn from(items: Vec<V>) -> Self {
+        Value::Array(
+            items
+                .into_iter()
+                .map(|item| Arc::new(item.into()))
+                .collect(),
+        )
     }
 }
 
-const BUILTINS: [(&str, BuiltinFunction); 8] = [
+macro_rules! impl_value_from_tuple {
+    ($($name:ident),*) => {
+        impl<'a, T: FieldElement, $($name: Into<Value<'a, T>>),*> From<($($name,)*)> for Value<'a, T> {
+            #[allow(non_snake_case)]
+            fn from(($($name,)*): ($($name,)*)) -> Self {
+                Value::Tuple(vec![$(Arc::new($name.into())),*])
+            }
+        }
+    };
+}
+
+impl_value_from_tuple!(A, B);
+impl_value_from_tuple!(A, B, C);
+impl_value_from_tuple!(A, B, C, D);
+
+const BUILTINS: [(&str, BuiltinFunction); 21] = [
+    ("std::array::fold", BuiltinFunction::ArrayFold),
     ("std::array::len", BuiltinFunction::ArrayLen),
+    ("std::array::map", BuiltinFunction::ArrayMap),
+    ("std::array::new", BuiltinFunction::ArrayNew),
+    ("std::array::sum", BuiltinFunction::ArraySum(None)),
     ("std::check::panic", BuiltinFunction::Panic),
+    ("std::constraints::lookup", BuiltinFunction::Lookup),
+    (
+        "std::constraints::permutation",
+        BuiltinFunction::Permutation,
+    ),
     ("std::convert::expr", BuiltinFunction::ToExpr),
     ("std::convert::fe", BuiltinFunction::ToFe),
     ("std::convert::int", BuiltinFunction::ToInt),
+    ("std::convert::to_string", BuiltinFunction::ToString),
     ("std::debug::print", BuiltinFunction::Print),
     ("std::field::modulus", BuiltinFunction::Modulus),
     ("std::prover::eval", BuiltinFunction::Eval),
+    (
+        "std::prover::new_witness_col",
+        BuiltinFunction::NewWitnessCol,
+    ),
+    ("std::string::len", BuiltinFunction::StringLen),
+    ("std::utils::from_bits", BuiltinFunction::FromBits),
+    ("std::utils::from_bytes", BuiltinFunction::FromBytes),
+    ("std::utils::to_bits", BuiltinFunction::ToBits),
+    ("std::utils::to_bytes", BuiltinFunction::ToBytes),
 ];
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+/// Returns the builtin function with the given absolute name as used in references.
+fn builtin_function(name: &str) -> Option<BuiltinFunction> {
+    BUILTINS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, b)| b.clone())
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub enum BuiltinFunction {
     /// std::array::len: _[] -> int, returns the length of an array
     ArrayLen,
+    /// std::array::new: int, (int -> T) -> T[], returns [f(0), f(1), ..., f(length - 1)]
+    ArrayNew,
+    /// std::array::map: T1[], (T1 -> T2) -> T2[], applies the function to each element
+    ArrayMap,
+    /// std::array::fold: T1[], T2, (T2, T1 -> T2) -> T2, combines the elements
+    /// from left to right using the folder function, starting with the initial value
+    ArrayFold,
+    /// std::array::sum: T[] -> T, returns the sum of the array elements.
+    /// The element type is filled in from the generic arguments of the reference,
+    /// it is needed to construct the zero for empty arrays.
+    ArraySum(Option<Type>),
     /// std::field::modulus: -> int, returns the field modulus as int
     Modulus,
     /// std::check::panic: string -> !, fails evaluation and uses its parameter for error reporting.
     /// Does not return.
     Panic,
-    /// std::debug::print: string -> [], prints its argument on stdout.
-    /// Returns an empty array.
+    /// std::debug::print: T -> constr[], prints its argument using SymbolLookup::print,
+    /// which writes to stderr by default. Returns an empty array.
     Print,
-    /// std::convert::expr: fe/int -> expr, converts fe to expr
+    /// std::convert::expr: fe/int -> expr, converts fe or int to a constant expression,
+    /// fails for integers outside the field range
     ToExpr,
-    /// std::convert::int: fe/int -> int, converts fe to int
+    /// std::convert::int: fe/int/expr -> int, converts fe or constant expression to int
     ToInt,
-    /// std::convert::fe: int/fe -> fe, converts int to fe
+    /// std::convert::fe: int/fe/expr -> fe, converts int or constant expression to fe,
+    /// fails for integers outside the field range
     ToFe,
+    /// std::convert::to_string: int/fe/expr -> string, formats the value as a string
+    ToString,
+    /// std::string::len: string -> int, returns the number of characters of a string
+    StringLen,
+    /// std::utils::to_bits: int, int -> int[], returns the given number of bits of a
+    /// non-negative integer, least significant bit first. Fails if the value does not fit.
+    ToBits,
+    /// std::utils::from_bits: int[] -> int, the inverse of `to_bits`
+    FromBits,
+    /// std::utils::to_bytes: int, int -> int[], like `to_bits`, but with bytes
+    ToBytes,
+    /// std::utils::from_bytes: int[] -> int, the inverse of `to_bytes`
+    FromBytes,
     /// std::prover::eval: expr -> fe, evaluates an expression on the current row
     Eval,
+    /// std::prover::new_witness_col: string -> expr, creates a new witness column
+    /// whose name is derived from the argument. Only valid during condensation of identities.
+    NewWitnessCol,
+    /// std::constraints::lookup: expr, expr[], expr, expr[] -> constr,
+    /// constructs the lookup `sel_l { left } in sel_r { right }`
+    Lookup,
+    /// std::constraints::permutation: expr, expr[], expr, expr[] -> constr,
+    /// constructs the permutation `sel_l { left } is sel_r { right }`
+    Permutation,
 }
 
 impl<'a, T: Display> Display for Value<'a, T> {
@@ -283,8 +881,11 @@ impl<'a, T: Display> Display for Value<'a, T> {
             Value::Array(elements) => write!(f, "[{}]", elements.iter().format(", ")),
             Value::Closure(closure) => write!(f, "{closure}"),
             Value::BuiltinFunction(b) => write!(f, "{b:?}"),
+            Value::HostFunction(function) => write!(f, "{}", function.name),
             Value::Expression(e) => write!(f, "{e}"),
             Value::Identity(left, right) => write!(f, "{left} = {right}"),
+            Value::Lookup(left, right) => write!(f, "{left} in {right}"),
+            Value::Permutation(left, right) => write!(f, "{left} is {right}"),
         }
     }
 }
@@ -292,6 +893,9 @@ impl<'a, T: Display> Display for Value<'a, T> {
 #[derive(Clone, Debug)]
 pub struct Closure<'a, T> {
     pub lambda: &'a LambdaExpression<Reference>,
+    /// The values of the local variables in scope where the lambda expression
+    /// was evaluated. They are captured by value, so the closure stays valid
+    /// after the function that created it has returned.
     pub environment: Vec<Arc<Value<'a, T>>>,
     pub generic_args: HashMap<String, Type>,
 }
@@ -323,9 +927,90 @@ impl<'a, T> Closure<'a, T> {
     }
 }
 
-pub struct Definitions<'a>(pub &'a HashMap<String, (Symbol, Option<FunctionValueDefinition>)>);
+/// The signature of functions implemented in Rust, see [HostFunction].
+pub type HostFunctionImpl<T> =
+    dyn for<'b> Fn(Vec<Arc<Value<'b, T>>>) -> Result<Value<'b, T>, EvalError> + Send + Sync;
+
+/// A function implemented in Rust that can be called from PIL like a closure.
+#[derive(Clone)]
+pub struct HostFunction<T> {
+    /// The name of the function, used for display and comparison.
+    pub name: String,
+    function: Arc<HostFunctionImpl<T>>,
+}
+
+impl<T> HostFunction<T> {
+    pub fn new(
+        name: impl Into<String>,
+        function: impl for<'b> Fn(Vec<Arc<Value<'b, T>>>) -> Result<Value<'b, T>, EvalError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        HostFunction {
+            name: name.into(),
+            function: Arc::new(function),
+        }
+    }
+
+    pub fn call<'a>(&self, arguments: Vec<Arc<Value<'a, T>>>) -> Result<Value<'a, T>, EvalError> {
+        (self.function)(arguments)
+    }
+}
+
+impl<T> PartialEq for HostFunction<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl<T> fmt::Debug for HostFunction<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostFunction({})", self.name)
+    }
+}
+
+impl<'a, T> From<HostFunction<T>> for Value<'a, T> {
+    fn from(value: HostFunction<T>) -> Self {
+        Value::HostFunction(value)
+    }
+}
+
+/// Symbols provided by the host application instead of the PIL source,
+/// for example functions implemented in Rust.
+/// They are consulted for names that are not defined in PIL. If a name is
+/// defined in both places, the PIL definition takes precedence.
+pub trait HostSymbols<T>: Sync {
+    /// Returns the absolute names (e.g. `ext::double`) and the types of all
+    /// provided symbols. The types are used during type inference.
+    fn declarations(&self) -> Vec<(String, TypeScheme)>;
+
+    /// Returns the value of the symbol with the given name, as declared
+    /// in [HostSymbols::declarations]. Functions are usually returned as
+    /// [Value::HostFunction].
+    fn lookup<'a>(&self, name: &str) -> Result<Value<'a, T>, EvalError>;
+}
+
+pub struct Definitions<'a>(
+    pub &'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+);
 
 impl<'a> Definitions<'a> {
+    /// Implementation of `lookup` that consults the host symbols for names
+    /// that are not defined in PIL.
+    pub fn lookup_with_host<T: FieldElement>(
+        &self,
+        name: &str,
+        generic_args: Option<Vec<Type>>,
+        host: Option<&dyn HostSymbols<T>>,
+        symbols: &impl SymbolLookup<'a, T>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        match host {
+            Some(host) if resolve_symbol(self.0, name).is_err() => host.lookup(name).map(Arc::new),
+            _ => self.lookup_with_symbols(name, generic_args, symbols),
+        }
+    }
+
     /// Implementation of `lookup` that allows to provide a different implementation
     /// of SymbolLookup for the recursive call.
     pub fn lookup_with_symbols<T: FieldElement>(
@@ -334,11 +1019,7 @@ impl<'a> Definitions<'a> {
         generic_args: Option<Vec<Type>>,
         symbols: &impl SymbolLookup<'a, T>,
     ) -> Result<Arc<Value<'a, T>>, EvalError> {
-        let name = name.to_string();
-        let (symbol, value) = &self
-            .0
-            .get(&name)
-            .ok_or_else(|| EvalError::SymbolNotFound(format!("Symbol {name} not found.")))?;
+        let (name, (symbol, value)) = resolve_symbol(self.0, name)?;
 
         Ok(if matches!(symbol.kind, SymbolKind::Poly(_)) {
             if symbol.is_array() {
@@ -346,7 +1027,7 @@ impl<'a> Definitions<'a> {
                     .array_elements()
                     .map(|(name, poly_id)| {
                         Value::from(AlgebraicExpression::Reference(AlgebraicReference {
-                            name,
+                            name: name.into(),
                             poly_id,
                             next: false,
                         }))
@@ -356,7 +1037,7 @@ impl<'a> Definitions<'a> {
                 Value::Array(items).into()
             } else {
                 Value::from(AlgebraicExpression::Reference(AlgebraicReference {
-                    name,
+                    name: name.into(),
                     poly_id: symbol.into(),
                     next: false,
                 }))
@@ -379,6 +1060,31 @@ impl<'a> Definitions<'a> {
     }
 }
 
+impl<'a> Definitions<'a> {
+    /// Returns the definition of the fixed column with the given name.
+    pub fn lookup_fixed_column(
+        &self,
+        name: &str,
+    ) -> Result<&'a FunctionValueDefinition, EvalError> {
+        match resolve_symbol(self.0, name)
+            .ok()
+            .map(|(_, definition)| definition)
+        {
+            Some((symbol, Some(value)))
+                if symbol.kind == SymbolKind::Poly(PolynomialType::Constant) =>
+            {
+                Ok(value)
+            }
+            Some(_) => Err(EvalError::TypeError(format!(
+                "Symbol {name} is not a fixed column with a definition."
+            ))),
+            None => Err(EvalError::SymbolNotFound(format!(
+                "Symbol {name} not found."
+            ))),
+        }
+    }
+}
+
 impl<'a, T: FieldElement> SymbolLookup<'a, T> for Definitions<'a> {
     fn lookup(
         &self,
@@ -388,204 +1094,1045 @@ impl<'a, T: FieldElement> SymbolLookup<'a, T> for Definitions<'a> {
         self.lookup_with_symbols::<T>(name, generic_args, self)
     }
 
+    fn fixed_column_definition(
+        &self,
+        name: &str,
+    ) -> Result<&'a FunctionValueDefinition, EvalError> {
+        self.lookup_fixed_column(name)
+    }
+
     fn lookup_public_reference(&self, name: &str) -> Result<Arc<Value<'a, T>>, EvalError> {
         Ok(Value::from(AlgebraicExpression::PublicReference(name.to_string())).into())
     }
 }
 
-impl<'a> From<&'a HashMap<String, (Symbol, Option<FunctionValueDefinition>)>> for Definitions<'a> {
-    fn from(value: &'a HashMap<String, (Symbol, Option<FunctionValueDefinition>)>) -> Self {
+impl<'a> From<&'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>>
+    for Definitions<'a>
+{
+    fn from(
+        value: &'a HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    ) -> Self {
         Definitions(value)
     }
 }
 
-pub trait SymbolLookup<'a, T> {
-    fn lookup(
-        &self,
-        name: &'a str,
-        generic_args: Option<Vec<Type>>,
-    ) -> Result<Arc<Value<'a, T>>, EvalError>;
+/// Limits that prevent non-terminating evaluations, together with other
+/// settings for evaluations. The limits apply to each evaluation separately, i.e. calls from builtin functions like
+/// `std::array::map` are counted separately.
+#[derive(Clone, Copy, Debug)]
+pub struct EvaluationLimits {
+    /// The maximum number of nested function calls.
+    pub max_call_depth: usize,
+    /// The maximum number of evaluation steps.
+    pub max_steps: u64,
+    /// The maximum number of stack frames shown when displaying an error.
+    /// If there are more frames, the innermost and outermost ones are shown.
+    pub max_printed_stack_frames: usize,
+    /// Whether `std::debug::print` produces any output. Disabling it
+    /// keeps for example the output of tests clean.
+    pub debug_print: bool,
+}
 
-    fn lookup_public_reference(&self, name: &'a str) -> Result<Arc<Value<'a, T>>, EvalError> {
-        Err(EvalError::Unsupported(format!(
-            "Cannot evaluate public reference: {name}"
-        )))
+impl Default for EvaluationLimits {
+    fn default() -> Self {
+        EvaluationLimits {
+            max_call_depth: 1 << 20,
+            max_steps: 1 << 32,
+            max_printed_stack_frames: 16,
+            debug_print: true,
+        }
     }
+}
 
-    fn eval_expr(&self, _expr: &AlgebraicExpression<T>) -> Result<Arc<Value<'a, T>>, EvalError> {
-        Err(EvalError::DataNotAvailable)
-    }
+/// A cache for the results of function calls, to avoid repeated evaluation of calls
+/// with the same arguments, for example of helper functions called for every row
+/// of a fixed column.
+/// Only calls to closures that do not capture any local variables and whose
+/// arguments and results are ground values (bool, int, fe, string and tuples
+/// or arrays thereof) are cached. Note that side effects like `std::debug::print`
+/// are not repeated for cached calls.
+/// The cache borrows the definitions the cached functions are defined in,
+/// so it cannot outlive them.
+pub struct CallCache<'a, T> {
+    entries: RwLock<BTreeMap<CallKey<'a, T>, GroundValue<T>>>,
+    /// The maximum number of entries, no new entries are added once it is reached.
+    max_entries: usize,
 }
 
-mod internal {
-    use num_traits::Signed;
-    use powdr_ast::analyzed::AlgebraicBinaryOperator;
-    use powdr_number::BigUint;
+impl<'a, T: FieldElement> CallCache<'a, T> {
+    pub fn new(max_entries: usize) -> Self {
+        CallCache {
+            entries: Default::default(),
+            max_entries,
+        }
+    }
 
-    use super::*;
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
 
-    pub fn evaluate<'a, 'b, T: FieldElement>(
-        expr: &'a Expression,
-        locals: &[Arc<Value<'a, T>>],
-        generic_args: &'b HashMap<String, Type>,
-        symbols: &impl SymbolLookup<'a, T>,
-    ) -> Result<Arc<Value<'a, T>>, EvalError> {
-        Ok(match expr {
-            Expression::Reference(reference) => {
-                evaluate_reference(reference, locals, generic_args, symbols)?
-            }
-            Expression::PublicReference(name) => symbols.lookup_public_reference(name)?,
-            Expression::Number(n, ty) => evaluate_literal(n.clone(), ty, generic_args)?,
-            Expression::String(s) => Value::String(s.clone()).into(),
-            Expression::Tuple(items) => Value::Tuple(
-                items
-                    .iter()
-                    .map(|e| evaluate(e, locals, generic_args, symbols))
-                    .collect::<Result<_, _>>()?,
-            )
-            .into(),
-            Expression::ArrayLiteral(elements) => (Value::Array(
-                elements
-                    .items
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get<'b>(&self, key: &CallKey<'a, T>) -> Option<Arc<Value<'b, T>>> {
+        self.entries
+            .read()
+            .unwrap()
+            .get(key)
+            .map(|v| v.to_value().into())
+    }
+
+    fn insert(&self, key: CallKey<'a, T>, result: &Value<'_, T>) {
+        let Some(result) = GroundValue::from_value(result) else {
+            return;
+        };
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() < self.max_entries {
+            entries.insert(key, result);
+        }
+    }
+}
+
+impl<'a, T: FieldElement> Default for CallCache<'a, T> {
+    /// Creates a cache with up to 2^20 entries.
+    fn default() -> Self {
+        Self::new(1 << 20)
+    }
+}
+
+/// Identifies a function call by the called lambda expression (i.e. the symbol
+/// it is defined in), the generic arguments and the argument values.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct CallKey<'a, T> {
+    lambda: LambdaByAddress<'a>,
+    generic_args: Vec<(String, Type)>,
+    arguments: Vec<GroundValue<T>>,
+}
+
+impl<'a, T: FieldElement> CallKey<'a, T> {
+    /// Returns the key for the call or None if the call cannot be cached.
+    fn new(closure: &Closure<'a, T>, arguments: &[Arc<Value<'a, T>>]) -> Option<Self> {
+        if !closure.environment.is_empty() {
+            return None;
+        }
+        Some(CallKey {
+            lambda: LambdaByAddress(closure.lambda),
+            generic_args: closure
+                .generic_args
+                .iter()
+                .map(|(name, ty)| (name.clone(), ty.clone()))
+                .sorted()
+                .collect(),
+            arguments: arguments
+                .iter()
+                .map(|a| GroundValue::from_value(a))
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
+/// A lambda expression compared by its address. Since the lambda expression is borrowed,
+/// the address cannot be reused by a different expression while the key exists.
+#[derive(Clone, Copy)]
+struct LambdaByAddress<'a>(&'a LambdaExpression<Reference>);
+
+impl PartialEq for LambdaByAddress<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for LambdaByAddress<'_> {}
+
+impl PartialOrd for LambdaByAddress<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LambdaByAddress<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let address = |l: &Self| l.0 as *const LambdaExpression<Reference> as usize;
+        address(self).cmp(&address(other))
+    }
+}
+
+/// A value that does not contain closures or expressions.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum GroundValue<T> {
+    Bool(bool),
+    Integer(BigInt),
+    FieldElement(T),
+    String(String),
+    Tuple(Vec<GroundValue<T>>),
+    Array(Vec<GroundValue<T>>),
+}
+
+impl<T: FieldElement> GroundValue<T> {
+    fn from_value(value: &Value<'_, T>) -> Option<Self> {
+        Some(match value {
+            Value::Bool(b) => GroundValue::Bool(*b),
+            Value::Integer(x) => GroundValue::Integer(x.clone()),
+            Value::FieldElement(x) => GroundValue::FieldElement(*x),
+            Value::String(s) => GroundValue::String(s.clone()),
+            Value::Tuple(items) => GroundValue::Tuple(
+                items
                     .iter()
-                    .map(|e| evaluate(e, locals, generic_args, symbols))
-                    .collect::<Result<_, _>>()?,
-            ))
-            .into(),
-            Expression::BinaryOperation(left, op, right) => {
-                let left = evaluate(left, locals, generic_args, symbols)?;
-                let right = evaluate(right, locals, generic_args, symbols)?;
-                evaluate_binary_operation(&left, *op, &right)?
-            }
-            Expression::UnaryOperation(op, expr) => {
-                match (op, evaluate(expr, locals, generic_args, symbols)?.as_ref()) {
-                    (UnaryOperator::Minus, Value::FieldElement(e)) => {
-                        Value::FieldElement(-*e).into()
+                    .map(|i| Self::from_value(i))
+                    .collect::<Option<_>>()?,
+            ),
+            Value::Array(items) => GroundValue::Array(
+                items
+                    .iter()
+                    .map(|i| Self::from_value(i))
+                    .collect::<Option<_>>()?,
+            ),
+            _ => None?,
+        })
+    }
+
+    fn to_value<'a>(&self) -> Value<'a, T> {
+        match self {
+            GroundValue::Bool(b) => Value::Bool(*b),
+            GroundValue::Integer(x) => Value::Integer(x.clone()),
+            GroundValue::FieldElement(x) => Value::FieldElement(*x),
+            GroundValue::String(s) => Value::String(s.clone()),
+            GroundValue::Tuple(items) => {
+                Value::Tuple(items.iter().map(|i| i.to_value().into()).collect())
+            }
+            GroundValue::Array(items) => {
+                Value::Array(items.iter().map(|i| i.to_value().into()).collect())
+            }
+        }
+    }
+}
+
+pub trait SymbolLookup<'a, T> {
+    fn lookup(
+        &self,
+        name: &'a str,
+        generic_args: Option<Vec<Type>>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError>;
+
+    fn lookup_public_reference(&self, name: &'a str) -> Result<Arc<Value<'a, T>>, EvalError> {
+        Err(EvalError::Unsupported(format!(
+            "Cannot evaluate public reference: {name}"
+        )))
+    }
+
+    /// Evaluates an expression on the current row, which is only possible
+    /// when evaluating query functions.
+    fn eval_expr(&self, _expr: &AlgebraicExpression<T>) -> Result<Arc<Value<'a, T>>, EvalError> {
+        Err(EvalError::Unsupported(
+            "std::prover::eval can only be evaluated in query functions of witness columns and in hints."
+                .to_string(),
+        ))
+    }
+
+    /// Returns the definition of the fixed column with the given name,
+    /// which is used to evaluate the column at a row index.
+    fn fixed_column_definition(
+        &self,
+        name: &str,
+    ) -> Result<&'a FunctionValueDefinition, EvalError> {
+        Err(EvalError::Unsupported(format!(
+            "Cannot evaluate fixed column {name} in this context."
+        )))
+    }
+
+    /// Returns the limits for the evaluation.
+    fn limits(&self) -> EvaluationLimits {
+        Default::default()
+    }
+
+    /// Returns the cache for function call results, if calls should be memoized.
+    fn call_cache(&self) -> Option<&CallCache<'a, T>> {
+        None
+    }
+
+    /// Outputs a message produced by `std::debug::print`. Prints to stderr by default.
+    fn print(&self, msg: &str) {
+        eprint!("{msg}");
+    }
+
+    /// Creates a new witness column whose name is derived from `name_hint`
+    /// and returns a reference to it.
+    fn new_witness_column(&self, name_hint: &str) -> Result<Arc<Value<'a, T>>, EvalError> {
+        Err(EvalError::Unsupported(format!(
+            "Cannot create new witness column {name_hint} in this context."
+        )))
+    }
+}
+
+mod internal {
+    use std::rc::Rc;
+
+    use num_traits::Signed;
+    use powdr_ast::{
+        analyzed::{AlgebraicBinaryOperator, AlgebraicUnaryOperator},
+        parsed::{ArrayLiteral, BlockExpression, IfExpression},
+    };
+    use powdr_number::BigUint;
+
+    use super::*;
+
+    pub fn evaluate<'a, 'b, T: FieldElement>(
+        expr: &'a Expression,
+        locals: &[Arc<Value<'a, T>>],
+        generic_args: &'b HashMap<String, Type>,
+        symbols: &impl SymbolLookup<'a, T>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        let frame = Rc::new(Frame {
+            locals: locals.to_vec(),
+            generic_args: generic_args.clone(),
+        });
+        Evaluator::new(symbols).evaluate(expr, frame)
+    }
+
+    pub fn evaluate_function_call<'a, T: FieldElement>(
+        function: Arc<Value<'a, T>>,
+        arguments: Vec<Arc<Value<'a, T>>>,
+        symbols: &impl SymbolLookup<'a, T>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        let mut evaluator = Evaluator::new(symbols);
+        evaluator
+            .op_stack
+            .push(Operation::Call(arguments.len(), None));
+        evaluator.value_stack.push(function);
+        evaluator.value_stack.extend(arguments);
+        evaluator.run()
+    }
+
+    /// The local variables and generic arguments of a function invocation.
+    struct Frame<'a, T> {
+        locals: Vec<Arc<Value<'a, T>>>,
+        generic_args: HashMap<String, Type>,
+    }
+
+    impl<'a, T> Frame<'a, T> {
+        /// A frame without local variables, used for top-level definitions.
+        fn empty() -> Rc<Self> {
+            Rc::new(Frame {
+                locals: vec![],
+                generic_args: Default::default(),
+            })
+        }
+    }
+
+    /// A pending step of the evaluation. Operations other than `Expand`
+    /// consume their inputs from the value stack and push their result.
+    enum Operation<'a, T> {
+        /// Evaluates the expression in the given frame.
+        Expand(&'a Expression, Rc<Frame<'a, T>>),
+        /// Combines the top `n` values into a tuple.
+        Tuple(usize),
+        /// Combines the top `n` values into an array.
+        Array(usize),
+        /// Applies the operator to the top two values.
+        Binary(BinaryOperator),
+        /// Applies the operator to the top value.
+        Unary(UnaryOperator),
+        /// Indexes into an array, the expression is used for error reporting.
+        IndexAccess(&'a Expression),
+        /// Calls the function below the top `n` values with these values as arguments.
+        /// The expression the function value resulted from is used for error reporting.
+        Call(usize, Option<&'a Expression>),
+        /// Leaves the current function.
+        Return,
+        /// Stores the value on top of the stack in the call cache.
+        CacheResult(CallKey<'a, T>),
+        /// Continues with the body of the first matching arm.
+        Match(&'a [MatchArm<Reference>], Rc<Frame<'a, T>>),
+        /// Continues with one of the branches depending on the condition.
+        /// The other branch is not evaluated.
+        If(&'a IfExpression<Reference>, Rc<Frame<'a, T>>),
+        /// Binds the value on top of the stack to the variable of the let statement
+        /// with the given index and continues with the rest of the block.
+        Let(&'a BlockExpression<Reference>, usize, Rc<Frame<'a, T>>),
+        /// Evaluates the right operand of `&&` or `||` and applies the operator
+        /// unless the value of the left operand on top of the stack already
+        /// determines the result.
+        ShortCircuit(BinaryOperator, &'a Expression, Rc<Frame<'a, T>>),
+    }
+
+    /// A function call currently being executed.
+    struct ActiveCall<'a, T> {
+        function: &'a dyn Display,
+        /// The frame of the call, the arguments are its last `argument_count` locals.
+        frame: Rc<Frame<'a, T>>,
+        argument_count: usize,
+    }
+
+    impl<'a, T: Display> ActiveCall<'a, T> {
+        fn to_stack_frame(&self) -> StackFrame {
+            let locals = &self.frame.locals;
+            StackFrame {
+                function: self.function.to_string(),
+                arguments: locals[locals.len() - self.argument_count..]
+                    .iter()
+                    .map(|a| summarize(a))
+                    .collect(),
+            }
+        }
+    }
+
+    /// Formats a value for a stack frame, truncating long values.
+    fn summarize<T: Display>(value: &Value<'_, T>) -> String {
+        const MAX_LENGTH: usize = 40;
+        let formatted = print_formatted(value);
+        if formatted.chars().count() <= MAX_LENGTH {
+            formatted
+        } else {
+            format!(
+                "{}…",
+                formatted.chars().take(MAX_LENGTH).collect::<String>()
+            )
+        }
+    }
+
+    /// Evaluates expressions using explicit stacks instead of native recursion,
+    /// such that deeply recursive PIL functions do not overflow the native stack.
+    struct Evaluator<'a, 's, T, S> {
+        symbols: &'s S,
+        op_stack: Vec<Operation<'a, T>>,
+        value_stack: Vec<Arc<Value<'a, T>>>,
+        /// The functions currently being executed, for error reporting.
+        call_stack: Vec<ActiveCall<'a, T>>,
+        limits: EvaluationLimits,
+        steps: u64,
+    }
+
+    impl<'a, 's, T: FieldElement, S: SymbolLookup<'a, T>> Evaluator<'a, 's, T, S> {
+        fn new(symbols: &'s S) -> Self {
+            Self {
+                symbols,
+                op_stack: vec![],
+                value_stack: vec![],
+                call_stack: vec![],
+                limits: symbols.limits(),
+                steps: 0,
+            }
+        }
+
+        fn evaluate(
+            mut self,
+            expr: &'a Expression,
+            frame: Rc<Frame<'a, T>>,
+        ) -> Result<Arc<Value<'a, T>>, EvalError> {
+            self.op_stack.push(Operation::Expand(expr, frame));
+            self.run()
+        }
+
+        /// Processes operations until the stack is empty and returns the final value.
+        /// Errors are annotated with the calls that were active when they occurred.
+        fn run(mut self) -> Result<Arc<Value<'a, T>>, EvalError> {
+            if let Err(error) = self.process_operations() {
+                Err(error.with_call_stack(
+                    self.call_stack
+                        .iter()
+                        .rev()
+                        .map(|call| call.to_stack_frame()),
+                    self.limits.max_printed_stack_frames,
+                ))?
+            }
+            assert_eq!(self.value_stack.len(), 1);
+            Ok(self.value_stack.pop().unwrap())
+        }
+
+        fn process_operations(&mut self) -> Result<(), EvalError> {
+            while let Some(op) = self.op_stack.pop() {
+                self.steps += 1;
+                if self.steps > self.limits.max_steps {
+                    Err(EvalError::LimitExceeded(format!(
+                        "Maximum number of {} evaluation steps reached in {}.",
+                        self.limits.max_steps,
+                        self.call_stack
+                            .last()
+                            .map(|call| call.function.to_string())
+                            .unwrap_or("top-level expression".to_string())
+                    )))?
+                }
+                match op {
+                    Operation::Expand(expr, frame) => self.expand(expr, frame)?,
+                    Operation::Tuple(n) => {
+                        let items = self.pop_values(n);
+                        self.value_stack.push(Value::Tuple(items).into());
+                    }
+                    Operation::Array(n) => {
+                        let items = self.pop_values(n);
+                        self.value_stack.push(Value::Array(items).into());
+                    }
+                    Operation::Binary(op) => {
+                        let right = self.value_stack.pop().unwrap();
+                        let left = self.value_stack.pop().unwrap();
+                        let result = evaluate_binary_operation(&left, op, &right)?;
+                        self.value_stack.push(result);
+                    }
+                    Operation::Unary(op) => {
+                        let inner = self.value_stack.pop().unwrap();
+                        let result = evaluate_unary_operation(op, &inner)?;
+                        self.value_stack.push(result);
+                    }
+                    Operation::IndexAccess(expr) => {
+                        let index = self.value_stack.pop().unwrap();
+                        let array = self.value_stack.pop().unwrap();
+                        let result = evaluate_index_access(expr, &array, &index)?;
+                        self.value_stack.push(result);
                     }
-                    (UnaryOperator::LogicalNot, Value::Bool(b)) => Value::Bool(!b).into(),
-                    (UnaryOperator::Minus, Value::Integer(n)) => Value::Integer(-n).into(),
-                    (UnaryOperator::Next, Value::Expression(e)) => {
-                        let AlgebraicExpression::Reference(reference) = e else {
-                            return Err(EvalError::TypeError(format!(
-                                "Expected column for \"'\" operator, but got: {e}"
-                            )));
+                    Operation::Call(n, function_expr) => {
+                        let arguments = self.pop_values(n);
+                        let function = self.value_stack.pop().unwrap();
+                        self.call(function, function_expr, arguments)?;
+                    }
+                    Operation::Return => {
+                        self.call_stack.pop();
+                    }
+                    Operation::CacheResult(key) => {
+                        if let Some(cache) = self.symbols.call_cache() {
+                            cache.insert(key, self.value_stack.last().unwrap());
+                        }
+                    }
+                    Operation::Match(arms, frame) => {
+                        let v = self.value_stack.pop().unwrap();
+                        let body = self.find_matching_arm(arms, &v, &frame)?;
+                        self.op_stack.push(Operation::Expand(body, frame));
+                    }
+                    Operation::If(if_expr, frame) => {
+                        let condition = match self.value_stack.pop().unwrap().as_ref() {
+                            Value::Bool(b) => *b,
+                            x => Err(EvalError::TypeError(format!(
+                                "Expected boolean value but got {x}"
+                            )))?,
                         };
-
-                        if reference.next {
-                            return Err(EvalError::TypeError(format!(
-                                "Double application of \"'\" on: {reference}"
-                            )));
+                        let body = if condition {
+                            &if_expr.body
+                        } else {
+                            &if_expr.else_body
+                        };
+                        self.op_stack.push(Operation::Expand(body, frame));
+                    }
+                    Operation::Let(block, index, frame) => {
+                        let value = self.value_stack.pop().unwrap();
+                        let frame = Rc::new(Frame {
+                            locals: frame.locals.iter().cloned().chain([value]).collect(),
+                            generic_args: frame.generic_args.clone(),
+                        });
+                        self.expand_block(block, index + 1, frame);
+                    }
+                    Operation::ShortCircuit(op, right, frame) => {
+                        let left = self.value_stack.last().unwrap();
+                        // If the left value determines the result, it stays on the stack.
+                        if !matches!(
+                            (op, left.as_ref()),
+                            (BinaryOperator::LogicalAnd, Value::Bool(false))
+                                | (BinaryOperator::LogicalOr, Value::Bool(true))
+                        ) {
+                            self.op_stack.push(Operation::Binary(op));
+                            self.op_stack.push(Operation::Expand(right, frame));
                         }
-                        Value::from(AlgebraicExpression::Reference(AlgebraicReference {
-                            next: true,
-                            ..reference.clone()
-                        }))
-                        .into()
                     }
-                    (op, Value::Expression(e)) => Value::from(AlgebraicExpression::UnaryOperation(
-                        (*op).try_into().unwrap(),
-                        e.clone().into(),
-                    ))
-                    .into(),
-                    (_, inner) => Err(EvalError::TypeError(format!(
-                        "Operator {op} not supported on types: {inner}: {}",
-                        inner.type_formatted()
-                    )))?,
                 }
             }
-            Expression::LambdaExpression(lambda) => {
-                // TODO only copy the part of the environment that is actually referenced?
-                Value::from(Closure {
-                    lambda,
-                    environment: locals.to_vec(),
-                    generic_args: generic_args.clone(),
-                })
-                .into()
+            Ok(())
+        }
+
+        fn pop_values(&mut self, n: usize) -> Vec<Arc<Value<'a, T>>> {
+            self.value_stack.split_off(self.value_stack.len() - n)
+        }
+
+        /// Pushes the value of a leaf expression or schedules the evaluation
+        /// of the sub-expressions of a composite expression.
+        fn expand(
+            &mut self,
+            expr: &'a Expression,
+            frame: Rc<Frame<'a, T>>,
+        ) -> Result<(), EvalError> {
+            match expr {
+                Expression::Reference(reference) => {
+                    let value = evaluate_reference(
+                        reference,
+                        &frame.locals,
+                        &frame.generic_args,
+                        self.symbols,
+                    )?;
+                    self.value_stack.push(value);
+                }
+                Expression::PublicReference(name) => {
+                    let value = self.symbols.lookup_public_reference(name)?;
+                    self.value_stack.push(value);
+                }
+                Expression::Number(n, ty, _) => {
+                    let value = evaluate_literal(
+                        n.clone(),
+                        ty.as_ref().map(LiteralType::ty),
+                        &frame.generic_args,
+                    )?;
+                    self.value_stack.push(value);
+                }
+                Expression::String(s) => self.value_stack.push(Value::String(s.clone()).into()),
+                Expression::Tuple(items) => {
+                    self.op_stack.push(Operation::Tuple(items.len()));
+                    self.expand_all(items, &frame);
+                }
+                Expression::ArrayLiteral(ArrayLiteral { items }) => {
+                    self.op_stack.push(Operation::Array(items.len()));
+                    self.expand_all(items, &frame);
+                }
+                Expression::BinaryOperation(
+                    left,
+                    op @ (BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr),
+                    right,
+                ) => {
+                    self.op_stack
+                        .push(Operation::ShortCircuit(*op, right, frame.clone()));
+                    self.op_stack.push(Operation::Expand(left, frame));
+                }
+                Expression::BinaryOperation(left, op, right) => {
+                    self.op_stack.push(Operation::Binary(*op));
+                    self.op_stack.push(Operation::Expand(right, frame.clone()));
+                    self.op_stack.push(Operation::Expand(left, frame));
+                }
+                Expression::UnaryOperation(op, inner) => {
+                    self.op_stack.push(Operation::Unary(*op));
+                    self.op_stack.push(Operation::Expand(inner, frame));
+                }
+                Expression::LambdaExpression(lambda) => {
+                    // TODO only copy the part of the environment that is actually referenced?
+                    self.value_stack.push(
+                        Value::from(Closure {
+                            lambda,
+                            environment: frame.locals.clone(),
+                            generic_args: frame.generic_args.clone(),
+                        })
+                        .into(),
+                    );
+                }
+                Expression::IndexAccess(index_access) => {
+                    self.op_stack.push(Operation::IndexAccess(expr));
+                    self.op_stack
+                        .push(Operation::Expand(&index_access.index, frame.clone()));
+                    self.op_stack
+                        .push(Operation::Expand(&index_access.array, frame));
+                }
+                Expression::FunctionCall(FunctionCall {
+                    function,
+                    arguments,
+                }) => {
+                    self.op_stack
+                        .push(Operation::Call(arguments.len(), Some(function)));
+                    self.expand_all(arguments, &frame);
+                    self.op_stack.push(Operation::Expand(function, frame));
+                }
+                Expression::MatchExpression(scrutinee, arms) => {
+                    self.op_stack.push(Operation::Match(arms, frame.clone()));
+                    self.op_stack.push(Operation::Expand(scrutinee, frame));
+                }
+                Expression::IfExpression(if_expr) => {
+                    self.op_stack.push(Operation::If(if_expr, frame.clone()));
+                    self.op_stack
+                        .push(Operation::Expand(&if_expr.condition, frame));
+                }
+                Expression::BlockExpression(block) => self.expand_block(block, 0, frame),
+                Expression::FreeInput(_) => Err(EvalError::Unsupported(
+                    "Cannot evaluate free input.".to_string(),
+                ))?,
             }
-            Expression::IndexAccess(index_access) => {
-                match evaluate(&index_access.array, locals, generic_args, symbols)?.as_ref() {
-                    Value::Array(elements) => {
-                        match evaluate(&index_access.index, locals, generic_args,symbols)?.as_ref() {
-                            Value::Integer(index)
-                                if index.is_negative()
-                                    || *index >= (elements.len() as u64).into() =>
-                            {
-                                Err(EvalError::OutOfBounds(format!(
-                                    "Index access out of bounds: Tried to access element {index} of array of size {} in: {expr}.",
-                                    elements.len()
-                                )))?
-                            }
-                            Value::Integer(index) => {
-                                elements[usize::try_from(index).unwrap()].clone()
+            Ok(())
+        }
+
+        /// Schedules the evaluation of the block starting at the let statement
+        /// with the given index, the variables of the previous statements
+        /// are already part of the frame.
+        fn expand_block(
+            &mut self,
+            block: &'a BlockExpression<Reference>,
+            index: usize,
+            frame: Rc<Frame<'a, T>>,
+        ) {
+            match block.statements.get(index) {
+                Some(statement) => {
+                    self.op_stack
+                        .push(Operation::Let(block, index, frame.clone()));
+                    self.op_stack
+                        .push(Operation::Expand(&statement.value, frame));
+                }
+                None => self.op_stack.push(Operation::Expand(&block.expr, frame)),
+            }
+        }
+
+        /// Schedules the evaluation of the expressions such that
+        /// their values end up on the value stack in order.
+        fn expand_all(&mut self, expressions: &'a [Expression], frame: &Rc<Frame<'a, T>>) {
+            self.op_stack.extend(
+                expressions
+                    .iter()
+                    .rev()
+                    .map(|e| Operation::Expand(e, frame.clone())),
+            );
+        }
+
+        /// Evaluates calls to builtin and host functions directly and schedules the
+        /// evaluation of the body for closures.
+        fn call(
+            &mut self,
+            function: Arc<Value<'a, T>>,
+            function_expr: Option<&'a Expression>,
+            arguments: Vec<Arc<Value<'a, T>>>,
+        ) -> Result<(), EvalError> {
+            match function.as_ref() {
+                Value::BuiltinFunction(b) => {
+                    let result = evaluate_builtin_function(b.clone(), arguments, self.symbols)?;
+                    self.value_stack.push(result);
+                }
+                Value::HostFunction(function) => {
+                    self.value_stack.push(function.call(arguments)?.into());
+                }
+                Value::Expression(AlgebraicExpression::Reference(column)) => {
+                    self.call_column(column, arguments)?
+                }
+                Value::Closure(
+                    closure @ Closure {
+                        lambda,
+                        environment,
+                        generic_args,
+                    },
+                ) => {
+                    if lambda.params.len() != arguments.len() {
+                        Err(EvalError::TypeError(format!(
+                            "Invalid function call: Supplied {} arguments to function that takes {} parameters.\nFunction: {lambda}\nArguments: {}",
+                            arguments.len(),
+                            lambda.params.len(),
+                            arguments.iter().format(", ")
+                        )))?
+                    }
+
+                    if let Some(cache) = self.symbols.call_cache() {
+                        if let Some(key) = CallKey::new(closure, &arguments) {
+                            if let Some(result) = cache.get(&key) {
+                                self.value_stack.push(result);
+                                return Ok(());
                             }
-                            index => Err(EvalError::TypeError(format!(
-                                    "Expected integer for array index access but got {index}: {}",
-                                    index.type_formatted()
-                            )))?,
+                            self.op_stack.push(Operation::CacheResult(key));
                         }
                     }
-                    e => Err(EvalError::TypeError(format!("Expected array, but got {e}")))?,
+
+                    let name: &'a dyn Display = match function_expr {
+                        Some(e) => e,
+                        None => *lambda,
+                    };
+                    if self.call_stack.len() >= self.limits.max_call_depth {
+                        Err(EvalError::LimitExceeded(format!(
+                            "Maximum call depth of {} reached when calling {name}.",
+                            self.limits.max_call_depth
+                        )))?
+                    }
+                    let argument_count = arguments.len();
+                    let locals = environment
+                        .iter()
+                        .cloned()
+                        .chain(arguments)
+                        .collect::<Vec<_>>();
+                    let frame = Rc::new(Frame {
+                        locals,
+                        generic_args: generic_args.clone(),
+                    });
+                    self.call_stack.push(ActiveCall {
+                        function: name,
+                        frame: frame.clone(),
+                        argument_count,
+                    });
+                    self.op_stack.push(Operation::Return);
+                    self.op_stack.push(Operation::Expand(&lambda.body, frame));
                 }
+                e => Err(EvalError::TypeError(format!(
+                    "Expected function but got {e}"
+                )))?,
             }
-            Expression::FunctionCall(FunctionCall {
-                function,
-                arguments,
-            }) => {
-                let function = evaluate(function, locals, generic_args, symbols)?;
-                let arguments = arguments
-                    .iter()
-                    .map(|a| evaluate(a, locals, generic_args, symbols))
-                    .collect::<Result<Vec<_>, _>>()?;
-                evaluate_function_call(function, arguments, symbols)?
+            Ok(())
+        }
+
+        /// Evaluates a fixed column at the row given as the only argument,
+        /// either by calling its defining function or by indexing into
+        /// its array definition.
+        fn call_column(
+            &mut self,
+            column: &AlgebraicReference,
+            arguments: Vec<Arc<Value<'a, T>>>,
+        ) -> Result<(), EvalError> {
+            let name = &column.name;
+            if column.poly_id.ptype != PolynomialType::Constant || column.next {
+                Err(EvalError::TypeError(format!(
+                    "Only fixed columns can be evaluated at a row index, but {column} is {}.",
+                    match column.poly_id.ptype {
+                        _ if column.next => "a next reference",
+                        PolynomialType::Committed => "a witness column",
+                        PolynomialType::Intermediate => "an intermediate column",
+                        PolynomialType::Constant => unreachable!(),
+                    }
+                )))?
             }
-            Expression::MatchExpression(scrutinee, arms) => {
-                let v = evaluate(scrutinee, locals, generic_args, symbols)?;
-                let body = arms
-                    .iter()
-                    .find_map(|MatchArm { pattern, value }| match pattern {
-                        MatchPattern::Pattern(p) => {
-                            // TODO this uses PartialEq. As soon as we have proper match patterns
-                            // instead of value, we can remove the PartialEq requirement on Value.
-                            let p = evaluate(p, locals, generic_args, symbols).unwrap();
-                            if p == v {
-                                Some(value)
+            let [row] = &arguments[..] else {
+                Err(EvalError::TypeError(format!(
+                    "Fixed column {name} has to be evaluated at a single row, but {} arguments were supplied.",
+                    arguments.len()
+                )))?
+            };
+            let Value::Integer(row) = row.as_ref() else {
+                Err(EvalError::TypeError(format!(
+                    "Fixed column {name} can only be evaluated at a row of type int, but got {row}: {}.\n\
+                    Inside constraints, use the column directly instead of applying it to a row.",
+                    row.type_formatted()
+                )))?
+            };
+            match self.symbols.fixed_column_definition(name)? {
+                FunctionValueDefinition::Expression(TypedExpression { e, .. }) => {
+                    let function = Evaluator::new(self.symbols).evaluate(e, Frame::empty())?;
+                    self.call(function, None, arguments)?;
+                }
+                FunctionValueDefinition::Array(items) => {
+                    let out_of_bounds = || {
+                        EvalError::OutOfBounds(format!(
+                            "Row {row} of fixed column {name} is out of bounds, it has {} rows.",
+                            items.iter().map(|item| item.size()).sum::<DegreeType>()
+                        ))
+                    };
+                    let mut row = u64::try_from(row).map_err(|_| out_of_bounds())?;
+                    let item = items
+                        .iter()
+                        .find(|item| {
+                            if row < item.size() {
+                                true
                             } else {
-                                // TODO I don't think this part is needed now that we have the type checker.
-                                match (p.try_to_integer(), v.try_to_integer()) {
-                                    (Ok(p), Ok(v)) if p == v => Some(value),
-                                    _ => None,
-                                }
+                                row -= item.size();
+                                false
                             }
-                        }
-                        MatchPattern::CatchAll => Some(value),
-                    })
-                    .ok_or_else(EvalError::NoMatch)?;
-                evaluate(body, locals, generic_args, symbols)?
-            }
-            Expression::IfExpression(if_expr) => {
-                let cond = evaluate(&if_expr.condition, locals, generic_args, symbols)?;
-                let condition = match cond.as_ref() {
-                    Value::Bool(b) => Ok(b),
-                    x => Err(EvalError::TypeError(format!(
-                        "Expected boolean value but got {x}"
-                    ))),
-                }?;
-                let body = if *condition {
-                    &if_expr.body
-                } else {
-                    &if_expr.else_body
-                };
-                evaluate(body.as_ref(), locals, generic_args, symbols)?
+                        })
+                        .ok_or_else(out_of_bounds)?;
+                    let pattern = item.pattern();
+                    let e = &pattern[(row % pattern.len() as u64) as usize];
+                    self.op_stack.push(Operation::Expand(e, Frame::empty()));
+                }
+                FunctionValueDefinition::Sparse(sparse) => {
+                    let e = u64::try_from(row)
+                        .ok()
+                        .and_then(|row| sparse.value_at(row))
+                        .ok_or_else(|| {
+                            EvalError::OutOfBounds(format!(
+                                "Row {row} of fixed column {name} is out of bounds, it has {} rows.",
+                                sparse.size()
+                            ))
+                        })?;
+                    self.op_stack.push(Operation::Expand(e, Frame::empty()));
+                }
+                FunctionValueDefinition::Query(_) => Err(EvalError::TypeError(format!(
+                    "Cannot evaluate {name}, which is defined by a query."
+                )))?,
             }
-            Expression::FreeInput(_) => Err(EvalError::Unsupported(
-                "Cannot evaluate free input.".to_string(),
-            ))?,
+            Ok(())
+        }
+
+        fn find_matching_arm(
+            &self,
+            arms: &'a [MatchArm<Reference>],
+            v: &Value<'a, T>,
+            frame: &Rc<Frame<'a, T>>,
+        ) -> Result<&'a Expression, EvalError> {
+            for MatchArm { patterns, value } in arms {
+                for pattern in patterns {
+                    if self.matches_arm_pattern(pattern, v, frame)? {
+                        return Ok(value);
+                    }
+                }
+            }
+            Err(EvalError::NoMatch())
+        }
+
+        fn matches_arm_pattern(
+            &self,
+            pattern: &'a MatchPattern<Reference>,
+            v: &Value<'a, T>,
+            frame: &Rc<Frame<'a, T>>,
+        ) -> Result<bool, EvalError> {
+            Ok(match pattern {
+                MatchPattern::Pattern(p) => {
+                    let p = Evaluator::new(self.symbols).evaluate(p, frame.clone())?;
+                    matches_pattern(v, &p)?
+                }
+                MatchPattern::Range(start, end, kind) => {
+                    let Value::Integer(v) = v else {
+                        return Err(EvalError::TypeError(format!(
+                            "Range patterns can only match integers, but got {v} of type {}.",
+                            v.type_formatted()
+                        )));
+                    };
+                    let start = Evaluator::new(self.symbols).evaluate(start, frame.clone())?;
+                    let end = Evaluator::new(self.symbols).evaluate(end, frame.clone())?;
+                    let (Value::Integer(start), Value::Integer(end)) =
+                        (start.as_ref(), end.as_ref())
+                    else {
+                        return Err(EvalError::TypeError(format!(
+                            "Expected integers as endpoints of range pattern, but got {start} and {end}."
+                        )));
+                    };
+                    let below_end = match kind {
+                        RangeKind::Exclusive => v < end,
+                        RangeKind::Inclusive => v <= end,
+                    };
+                    start <= v && below_end
+                }
+                MatchPattern::CatchAll => true,
+            })
+        }
+    }
+
+    /// Checks if a value matches an (evaluated) pattern of a match arm.
+    /// Strings only match if they are exactly equal, i.e. there is no normalization
+    /// apart from the decoding of escape sequences in string literals by the parser.
+    /// Tuples and arrays match if they have the same length and all their
+    /// elements match.
+    fn matches_pattern<'a, T: FieldElement>(
+        v: &Value<'a, T>,
+        pattern: &Value<'a, T>,
+    ) -> Result<bool, EvalError> {
+        Ok(match (v, pattern) {
+            (Value::Tuple(items), Value::Tuple(patterns))
+            | (Value::Array(items), Value::Array(patterns)) => {
+                if items.len() != patterns.len() {
+                    return Ok(false);
+                }
+                for (item, pattern) in items.iter().zip(patterns) {
+                    if !matches_pattern(item, pattern)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            (Value::Bool(v), Value::Bool(p)) => v == p,
+            (Value::Integer(v), Value::Integer(p)) => v == p,
+            (Value::FieldElement(v), Value::FieldElement(p)) => v == p,
+            (Value::String(v), Value::String(p)) => v == p,
+            (Value::Expression(v), Value::Expression(p)) => v == p,
+            _ => Err(EvalError::TypeError(format!(
+                "Cannot match value {v} of type {} against pattern {pattern} of type {}.",
+                v.type_formatted(),
+                pattern.type_formatted()
+            )))?,
         })
     }
 
+    fn evaluate_unary_operation<'a, T: FieldElement>(
+        op: UnaryOperator,
+        inner: &Value<'a, T>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        Ok(match (op, inner) {
+            (UnaryOperator::Minus, Value::FieldElement(e)) => Value::FieldElement(-*e).into(),
+            (UnaryOperator::LogicalNot, Value::Bool(b)) => Value::Bool(!b).into(),
+            (UnaryOperator::Minus, Value::Integer(n)) => Value::Integer(-n).into(),
+            // Negated constants stay constants, i.e. `-1` is `modulus - 1` like `0 - 1`.
+            (UnaryOperator::Minus, Value::Expression(AlgebraicExpression::Number(n))) => {
+                Value::from(AlgebraicExpression::Number(-*n)).into()
+            }
+            (UnaryOperator::Next, Value::Expression(e)) => Value::from(apply_next(e)?).into(),
+            (op, Value::Expression(e)) => Value::from(AlgebraicExpression::UnaryOperation(
+                op.try_into().unwrap(),
+                e.clone().into(),
+            ))
+            .into(),
+            (_, inner) => Err(EvalError::TypeError(format!(
+                "Operator {op} not supported on types: {inner}: {}",
+                inner.type_formatted()
+            )))?,
+        })
+    }
+
+    /// Applies the next-row operator to a column reference or a linear combination of
+    /// column references, by shifting each of the references to the next row.
+    /// Since the operator is applied to evaluated expressions, a lambda parameter
+    /// like `t` in `|t| t' - 7` is replaced by its argument before this check.
+    fn apply_next<T: FieldElement>(
+        e: &AlgebraicExpression<T>,
+    ) -> Result<AlgebraicExpression<T>, EvalError> {
+        fn shift<T: FieldElement>(
+            e: &AlgebraicExpression<T>,
+        ) -> Option<Result<AlgebraicExpression<T>, AlgebraicReference>> {
+            Some(match e {
+                AlgebraicExpression::Reference(reference) if reference.next => {
+                    Err(reference.clone())
+                }
+                AlgebraicExpression::Reference(reference) => {
+                    Ok(AlgebraicExpression::Reference(AlgebraicReference {
+                        next: true,
+                        ..reference.clone()
+                    }))
+                }
+                AlgebraicExpression::UnaryOperation(AlgebraicUnaryOperator::Minus, inner) => {
+                    shift(inner)?.map(|inner| {
+                        AlgebraicExpression::UnaryOperation(
+                            AlgebraicUnaryOperator::Minus,
+                            Box::new(inner),
+                        )
+                    })
+                }
+                AlgebraicExpression::BinaryOperation(left, op, right) => {
+                    let (left, right) = match (op, left.as_ref(), right.as_ref()) {
+                        (AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub, _, _) => {
+                            (shift(left)?, shift(right)?)
+                        }
+                        (AlgebraicBinaryOperator::Mul, AlgebraicExpression::Number(_), _) => {
+                            (Ok(left.as_ref().clone()), shift(right)?)
+                        }
+                        (AlgebraicBinaryOperator::Mul, _, AlgebraicExpression::Number(_)) => {
+                            (shift(left)?, Ok(right.as_ref().clone()))
+                        }
+                        _ => return None,
+                    };
+                    left.and_then(|left| Ok(AlgebraicExpression::new_binary(left, *op, right?)))
+                }
+                _ => return None,
+            })
+        }
+        match shift(e) {
+            Some(Ok(shifted)) => Ok(shifted),
+            Some(Err(reference)) => Err(EvalError::TypeError(format!(
+                "The next-row operator \"'\" is applied twice to {reference}, which already refers to the next row."
+            ))),
+            None => Err(EvalError::TypeError(format!(
+                "The next-row operator \"'\" can only be applied to columns and linear combinations of columns, but it is applied to {e}."
+            ))),
+        }
+    }
+
+    fn evaluate_index_access<'a, T: FieldElement>(
+        expr: &Expression,
+        array: &Value<'a, T>,
+        index: &Value<'a, T>,
+    ) -> Result<Arc<Value<'a, T>>, EvalError> {
+        let Value::Array(elements) = array else {
+            return Err(EvalError::TypeError(format!(
+                "Expected array, but got {array}"
+            )));
+        };
+        match index {
+            Value::Integer(index)
+                if index.is_negative() || *index >= (elements.len() as u64).into() =>
+            {
+                Err(EvalError::OutOfBounds(format!(
+                    "Index access out of bounds: Tried to access element {index} of array of size {} in: {expr}.",
+                    elements.len()
+                )))
+            }
+            Value::Integer(index) => Ok(elements[usize::try_from(index).unwrap()].clone()),
+            index => Err(EvalError::TypeError(format!(
+                "Expected integer for array index access but got {index}: {}",
+                index.type_formatted()
+            ))),
+        }
+    }
+
     fn evaluate_literal<'a, T: FieldElement>(
         n: BigUint,
-        ty: &Option<Type<u64>>,
+        ty: Option<&Type<u64>>,
         generic_args: &HashMap<String, Type>,
     ) -> Result<Arc<Value<'a, T>>, EvalError> {
         let ty = if let Some(Type::TypeVar(tv)) = ty {
@@ -598,16 +2145,12 @@ mod internal {
                 )))?,
             }
         } else {
-            ty.as_ref().cloned().unwrap_or_else(|| Type::Int)
+            ty.cloned().unwrap_or_else(|| Type::Int)
         };
         if ty == Type::Int {
             return Ok(Value::Integer(n.into()).into());
         }
-        let fe = T::checked_from(n.clone()).ok_or_else(|| {
-            EvalError::TypeError(format!(
-                "Number literal {n} is too large for field element."
-            ))
-        })?;
+        let fe = FieldOverflow::check(n.clone())?;
         Ok((if ty == Type::Fe {
             Value::FieldElement(fe)
         } else if ty == Type::Expr {
@@ -628,15 +2171,21 @@ mod internal {
             Reference::LocalVar(i, _name) => locals[*i as usize].clone(),
 
             Reference::Poly(poly) => {
-                if let Some((_, b)) = BUILTINS.iter().find(|(n, _)| (n == &poly.name)) {
-                    Value::BuiltinFunction(*b).into()
+                let generic_args = poly.generic_args.clone().map(|mut ga| {
+                    for ty in &mut ga {
+                        ty.substitute_type_vars(generic_args);
+                    }
+                    ga
+                });
+                if let Some(b) = builtin_function(&poly.name) {
+                    Value::BuiltinFunction(match b {
+                        BuiltinFunction::ArraySum(_) => BuiltinFunction::ArraySum(
+                            generic_args.and_then(|ga| ga.into_iter().next()),
+                        ),
+                        b => b,
+                    })
+                    .into()
                 } else {
-                    let generic_args = poly.generic_args.clone().map(|mut ga| {
-                        for ty in &mut ga {
-                            ty.substitute_type_vars(generic_args);
-                        }
-                        ga
-                    });
                     symbols.lookup(&poly.name, generic_args)?
                 }
             }
@@ -655,6 +2204,20 @@ mod internal {
             (Value::String(l), BinaryOperator::Add, Value::String(r)) => {
                 Value::String(l.clone() + r).into()
             }
+            (Value::String(l), op, Value::String(r)) => Value::Bool(match op {
+                BinaryOperator::Equal => l == r,
+                BinaryOperator::NotEqual => l != r,
+                BinaryOperator::Less => l < r,
+                BinaryOperator::LessEqual => l <= r,
+                BinaryOperator::GreaterEqual => l >= r,
+                BinaryOperator::Greater => l > r,
+                _ => Err(EvalError::TypeError(format!(
+                    "Invalid operator {op} on strings: {} {op} {}",
+                    quote(l),
+                    quote(r)
+                )))?,
+            })
+            .into(),
             (Value::Bool(l), BinaryOperator::LogicalOr, Value::Bool(r)) => {
                 Value::Bool(*l || *r).into()
             }
@@ -679,13 +2242,19 @@ mod internal {
                 })?;
                 match l {
                     AlgebraicExpression::Number(l) => {
-                        Value::Expression(AlgebraicExpression::Number(l.pow(exp.into()))).into()
+                        Value::Expression(AlgebraicExpression::Number(fold_constant_expressions(
+                            *l,
+                            BinaryOperator::Pow,
+                            r,
+                        )?))
+                        .into()
                     }
                     l => {
-                        assert!(
-                            BigUint::from(exp) < T::modulus().to_arbitrary_integer(),
-                            "Exponent too large: {exp}"
-                        );
+                        if BigUint::from(exp) >= T::modulus().to_arbitrary_integer() {
+                            Err(EvalError::TypeError(format!(
+                                "Exponent in {l}**{r} is too large for the field."
+                            )))?
+                        }
                         Value::from(AlgebraicExpression::BinaryOperation(
                             Box::new(l.clone()),
                             AlgebraicBinaryOperator::Pow,
@@ -699,6 +2268,15 @@ mod internal {
                 Value::Identity(l.clone(), r.clone()).into()
             }
             (Value::Expression(l), op, Value::Expression(r)) => match (l, r) {
+                (AlgebraicExpression::Number(l), AlgebraicExpression::Number(r))
+                    if op == BinaryOperator::Mul =>
+                {
+                    let r = field_element_to_signed_integer(*r);
+                    Value::from(AlgebraicExpression::Number(fold_constant_expressions(
+                        *l, op, &r,
+                    )?))
+                    .into()
+                }
                 (AlgebraicExpression::Number(l), AlgebraicExpression::Number(r)) => {
                     let res = evaluate_binary_operation_field::<'a, T>(*l, op, *r)?;
                     let Value::FieldElement(result) = res.as_ref() else {
@@ -713,6 +2291,16 @@ mod internal {
                 ))
                 .into(),
             },
+            (Value::Integer(_), op, Value::FieldElement(_))
+            | (Value::FieldElement(_), op, Value::Integer(_)) => {
+                let type_of = |v: &Value<'a, T>| match v {
+                    Value::Integer(_) => Type::Int,
+                    _ => Type::Fe,
+                };
+                Err(EvalError::TypeError(
+                    int_fe_mixing_error(left, &type_of(left), op, right, &type_of(right)).unwrap(),
+                ))?
+            }
             (l, op, r) => Err(EvalError::TypeError(format!(
                 "Operator {op} not supported on types: {l}: {}, {r}: {}",
                 l.type_formatted(),
@@ -721,7 +2309,6 @@ mod internal {
         })
     }
 
-    #[allow(clippy::print_stdout)]
     pub fn evaluate_builtin_function<'a, T: FieldElement>(
         b: BuiltinFunction,
         mut arguments: Vec<Arc<Value<'a, T>>>,
@@ -729,13 +2316,26 @@ mod internal {
     ) -> Result<Arc<Value<'a, T>>, EvalError> {
         let params = match b {
             BuiltinFunction::ArrayLen => 1,
+            BuiltinFunction::ArrayNew => 2,
+            BuiltinFunction::ArrayMap => 2,
+            BuiltinFunction::ArrayFold => 3,
+            BuiltinFunction::ArraySum(_) => 1,
             BuiltinFunction::Modulus => 0,
             BuiltinFunction::Panic => 1,
             BuiltinFunction::Print => 1,
             BuiltinFunction::ToExpr => 1,
             BuiltinFunction::ToFe => 1,
             BuiltinFunction::ToInt => 1,
+            BuiltinFunction::ToString => 1,
+            BuiltinFunction::StringLen => 1,
+            BuiltinFunction::ToBits => 2,
+            BuiltinFunction::FromBits => 1,
+            BuiltinFunction::ToBytes => 2,
+            BuiltinFunction::FromBytes => 1,
             BuiltinFunction::Eval => 1,
+            BuiltinFunction::NewWitnessCol => 1,
+            BuiltinFunction::Lookup => 4,
+            BuiltinFunction::Permutation => 4,
         };
 
         if arguments.len() != params {
@@ -752,42 +2352,189 @@ mod internal {
                     v.type_formatted()
                 ),
             },
-            BuiltinFunction::Panic => {
-                let msg = match arguments.pop().unwrap().as_ref() {
-                    Value::String(msg) => msg.clone(),
+            BuiltinFunction::ArrayNew => {
+                let f = arguments.pop().unwrap();
+                let length = match arguments.pop().unwrap().as_ref() {
+                    Value::Integer(length) if length <= &0.into() => 0,
+                    Value::Integer(length) => u64::try_from(length).map_err(|_| {
+                        EvalError::OutOfBounds(format!(
+                            "Array length too large for std::array::new: {length}"
+                        ))
+                    })?,
                     v => panic!(
-                        "Expected string for std::check::panic, but got {v}: {}",
+                        "Expected integer for std::array::new, but got {v}: {}",
                         v.type_formatted()
                     ),
                 };
-                Err(EvalError::FailedAssertion(msg))?
+                Value::Array(
+                    (0..length)
+                        .map(|i| {
+                            let i = Value::Integer(i.into()).into();
+                            evaluate_function_call(f.clone(), vec![i], symbols)
+                        })
+                        .collect::<Result<_, _>>()?,
+                )
+                .into()
             }
-            BuiltinFunction::Print => {
+            BuiltinFunction::ArrayMap => {
+                let f = arguments.pop().unwrap();
+                match arguments.pop().unwrap().as_ref() {
+                    Value::Array(arr) => Value::Array(
+                        arr.iter()
+                            .map(|x| evaluate_function_call(f.clone(), vec![x.clone()], symbols))
+                            .collect::<Result<_, _>>()?,
+                    )
+                    .into(),
+                    v => panic!(
+                        "Expected array for std::array::map, but got {v}: {}",
+                        v.type_formatted()
+                    ),
+                }
+            }
+            BuiltinFunction::ArrayFold => {
+                let folder = arguments.pop().unwrap();
+                let initial = arguments.pop().unwrap();
+                match arguments.pop().unwrap().as_ref() {
+                    Value::Array(arr) => arr.iter().try_fold(initial, |acc, x| {
+                        evaluate_function_call(folder.clone(), vec![acc, x.clone()], symbols)
+                    })?,
+                    v => panic!(
+                        "Expected array for std::array::fold, but got {v}: {}",
+                        v.type_formatted()
+                    ),
+                }
+            }
+            BuiltinFunction::ArraySum(element_type) => {
+                let zero = match element_type {
+                    Some(ty @ (Type::Int | Type::Fe | Type::Expr)) => {
+                        evaluate_literal(0u32.into(), Some(&ty), &Default::default())?
+                    }
+                    ty => Err(EvalError::TypeError(format!(
+                        "Invalid element type for std::array::sum: {}",
+                        ty.map(|ty| ty.to_string()).unwrap_or("unknown".to_string())
+                    )))?,
+                };
+                match arguments.pop().unwrap().as_ref() {
+                    Value::Array(arr) => arr.iter().try_fold(zero, |acc, x| {
+                        evaluate_binary_operation(&acc, BinaryOperator::Add, x)
+                    })?,
+                    v => panic!(
+                        "Expected array for std::array::sum, but got {v}: {}",
+                        v.type_formatted()
+                    ),
+                }
+            }
+            BuiltinFunction::Panic => {
                 let msg = match arguments.pop().unwrap().as_ref() {
                     Value::String(msg) => msg.clone(),
                     v => panic!(
-                        "Expected string for std::debug::print, but got {v}: {}",
+                        "Expected string for std::check::panic, but got {v}: {}",
                         v.type_formatted()
                     ),
                 };
-                print!("{msg}");
+                Err(EvalError::FailedAssertion(msg))?
+            }
+            BuiltinFunction::Print => {
+                if symbols.limits().debug_print {
+                    let msg = match arguments.pop().unwrap().as_ref() {
+                        Value::String(msg) => msg.clone(),
+                        v => print_formatted(v),
+                    };
+                    symbols.print(&msg);
+                }
                 Value::Array(Default::default()).into()
             }
             BuiltinFunction::ToExpr => {
                 let arg = arguments.pop().unwrap();
-                Value::from(AlgebraicExpression::Number(arg.try_to_field_element()?)).into()
-            }
-            BuiltinFunction::ToInt => {
-                let arg = arguments.pop().unwrap();
-                Value::Integer(arg.try_to_integer()?).into()
+                match arg.as_ref() {
+                    Value::Expression(_) => arg,
+                    v => Value::from(AlgebraicExpression::Number(convert_to_field_element(
+                        "std::convert::expr",
+                        v,
+                    )?))
+                    .into(),
+                }
             }
+            BuiltinFunction::ToInt => Value::Integer(match arguments.pop().unwrap().as_ref() {
+                Value::Integer(x) => x.clone(),
+                Value::FieldElement(x) | Value::Expression(AlgebraicExpression::Number(x)) => {
+                    x.to_arbitrary_integer().into()
+                }
+                Value::Expression(e) => Err(EvalError::TypeError(format!(
+                    "std::convert::int can only convert constant expressions, but got {e}"
+                )))?,
+                v => Err(EvalError::TypeError(format!(
+                    "Expected int, fe or expr for std::convert::int, but got {v}: {}",
+                    v.type_formatted()
+                )))?,
+            })
+            .into(),
             BuiltinFunction::ToFe => {
                 let arg = arguments.pop().unwrap();
-                Value::FieldElement(arg.try_to_field_element()?).into()
+                Value::FieldElement(convert_to_field_element("std::convert::fe", &arg)?).into()
+            }
+            BuiltinFunction::ToString => match arguments.pop().unwrap().as_ref() {
+                Value::String(s) => Value::String(s.clone()).into(),
+                v => Value::String(to_string_formatted(v)).into(),
+            },
+            BuiltinFunction::StringLen => match arguments.pop().unwrap().as_ref() {
+                Value::String(s) => Value::Integer((s.chars().count() as u64).into()).into(),
+                v => panic!(
+                    "Expected string for std::string::len, but got {v}: {}",
+                    v.type_formatted()
+                ),
+            },
+            BuiltinFunction::ToBits | BuiltinFunction::ToBytes => {
+                let (name, digit) = if b == BuiltinFunction::ToBits {
+                    ("std::utils::to_bits", ("bit", 1))
+                } else {
+                    ("std::utils::to_bytes", ("byte", 8))
+                };
+                let count = arguments.pop().unwrap();
+                let value = arguments.pop().unwrap();
+                match (value.as_ref(), count.as_ref()) {
+                    (Value::Integer(value), Value::Integer(count)) => Value::Array(
+                        to_digits(name, value, count, digit)?
+                            .into_iter()
+                            .map(|digit| Value::Integer(digit).into())
+                            .collect(),
+                    )
+                    .into(),
+                    (v, c) => panic!(
+                        "Expected two integers for {name}, but got {v}: {} and {c}: {}",
+                        v.type_formatted(),
+                        c.type_formatted()
+                    ),
+                }
+            }
+            BuiltinFunction::FromBits | BuiltinFunction::FromBytes => {
+                let (name, digit) = if b == BuiltinFunction::FromBits {
+                    ("std::utils::from_bits", ("bit", 1))
+                } else {
+                    ("std::utils::from_bytes", ("byte", 8))
+                };
+                match arguments.pop().unwrap().as_ref() {
+                    Value::Array(digits) => {
+                        Value::Integer(from_digits(name, digits, digit)?).into()
+                    }
+                    v => panic!(
+                        "Expected array for {name}, but got {v}: {}",
+                        v.type_formatted()
+                    ),
+                }
             }
             BuiltinFunction::Modulus => {
                 Value::Integer(T::modulus().to_arbitrary_integer().into()).into()
             }
+            BuiltinFunction::Lookup | BuiltinFunction::Permutation => {
+                let right = selected_expressions(&arguments[2], &arguments[3])?;
+                let left = selected_expressions(&arguments[0], &arguments[1])?;
+                if b == BuiltinFunction::Lookup {
+                    Value::Lookup(left, right).into()
+                } else {
+                    Value::Permutation(left, right).into()
+                }
+            }
             BuiltinFunction::Eval => {
                 let arg = arguments.pop().unwrap();
                 match arg.as_ref() {
@@ -798,6 +2545,147 @@ mod internal {
                     ),
                 }
             }
+            BuiltinFunction::NewWitnessCol => match arguments.pop().unwrap().as_ref() {
+                Value::String(name_hint) => symbols.new_witness_column(name_hint)?,
+                v => panic!(
+                    "Expected string for std::prover::new_witness_col, but got {v}: {}",
+                    v.type_formatted()
+                ),
+            },
+        })
+    }
+
+    /// Splits the non-negative `value` into `count` digits, least significant digit first,
+    /// as done by the builtin `function`. A digit is given by its name and number of bits.
+    fn to_digits(
+        function: &str,
+        value: &BigInt,
+        count: &BigInt,
+        (digit_name, digit_bits): (&str, usize),
+    ) -> Result<Vec<BigInt>, EvalError> {
+        let count = usize::try_from(count).map_err(|_| {
+            EvalError::OutOfBounds(format!(
+                "Number of {digit_name}s for {function} has to be a non-negative integer that fits usize, but it is {count}."
+            ))
+        })?;
+        let fits = BigUint::try_from(value).is_ok_and(|value| {
+            count
+                .checked_mul(digit_bits)
+                .map_or(true, |bits| value.bit_len() <= bits)
+        });
+        if !fits {
+            return Err(EvalError::OutOfBounds(format!(
+                "{function}: Value {value} does not fit into {count} {digit_name}s."
+            )));
+        }
+        let mask = BigInt::from((1u32 << digit_bits) - 1);
+        Ok((0..count)
+            .map(|i| (value >> (i * digit_bits)) & &mask)
+            .collect())
+    }
+
+    /// Combines digits, least significant digit first, into an integer, as done by
+    /// the builtin `function`. A digit is given by its name and number of bits.
+    fn from_digits<T: FieldElement>(
+        function: &str,
+        digits: &[Arc<Value<'_, T>>],
+        (digit_name, digit_bits): (&str, usize),
+    ) -> Result<BigInt, EvalError> {
+        let limit = BigInt::from(1u32 << digit_bits);
+        digits
+            .iter()
+            .rev()
+            .try_fold(BigInt::zero(), |acc, digit| match digit.as_ref() {
+                Value::Integer(digit) if !digit.is_negative() && digit < &limit => {
+                    Ok((acc << digit_bits) + digit)
+                }
+                v => Err(EvalError::OutOfBounds(format!(
+                    "{function}: Expected a {digit_name}, but got {v}."
+                ))),
+            })
+    }
+
+    /// Formats a value for output by `std::debug::print`.
+    /// Closures are formatted by their parameters only.
+    fn print_formatted<T: Display>(value: &Value<'_, T>) -> String {
+        match value {
+            Value::Tuple(items) => format!(
+                "({})",
+                items.iter().map(|i| print_formatted(i)).format(", ")
+            ),
+            Value::Array(items) => format!(
+                "[{}]",
+                items.iter().map(|i| print_formatted(i)).format(", ")
+            ),
+            Value::Closure(Closure { lambda, .. }) => {
+                format!("|{}| ...", lambda.params.iter().format(", "))
+            }
+            v => v.to_string(),
+        }
+    }
+
+    /// Formats a value for `std::convert::to_string`. In contrast to `print_formatted`,
+    /// functions are represented by their type, since their code is not useful in messages.
+    fn to_string_formatted<T: FieldElement>(value: &Value<'_, T>) -> String {
+        match value {
+            Value::Tuple(items) => format!(
+                "({})",
+                items.iter().map(|i| to_string_formatted(i)).format(", ")
+            ),
+            Value::Array(items) => format!(
+                "[{}]",
+                items.iter().map(|i| to_string_formatted(i)).format(", ")
+            ),
+            Value::Closure(_) | Value::BuiltinFunction(_) | Value::HostFunction(_) => {
+                format!("<{}>", value.type_formatted())
+            }
+            v => v.to_string(),
+        }
+    }
+
+    /// Converts an int, fe or constant expression to a field element, as done by the
+    /// conversion builtin `function`. Integers have to be in the range of the field.
+    fn convert_to_field_element<T: FieldElement>(
+        function: &str,
+        value: &Value<'_, T>,
+    ) -> Result<T, EvalError> {
+        match value {
+            Value::FieldElement(x) | Value::Expression(AlgebraicExpression::Number(x)) => Ok(*x),
+            Value::Integer(x) => integer_to_field_element(x, &format!("{function} cannot convert")),
+            Value::Expression(e) => Err(EvalError::TypeError(format!(
+                "{function} can only convert constant expressions, but got {e}"
+            ))),
+            v => Err(EvalError::TypeError(format!(
+                "Expected int, fe or expr for {function}, but got {v}: {}",
+                v.type_formatted()
+            ))),
+        }
+    }
+
+    /// Turns a selector value and an array of expression values into SelectedExpressions.
+    fn selected_expressions<T: FieldElement>(
+        selector: &Value<'_, T>,
+        expressions: &Value<'_, T>,
+    ) -> Result<SelectedExpressions<AlgebraicExpression<T>>, EvalError> {
+        let to_expr = |v: &Value<'_, T>| match v {
+            Value::Expression(e) => Ok(e.clone()),
+            v => Err(EvalError::TypeError(format!(
+                "Expected expression but got {v}: {}",
+                v.type_formatted()
+            ))),
+        };
+        let Value::Array(items) = expressions else {
+            return Err(EvalError::TypeError(format!(
+                "Expected array of expressions but got {expressions}: {}",
+                expressions.type_formatted()
+            )));
+        };
+        Ok(SelectedExpressions {
+            selector: Some(to_expr(selector)?),
+            expressions: items
+                .iter()
+                .map(|item| to_expr(item))
+                .collect::<Result<_, _>>()?,
         })
     }
 }
@@ -820,23 +2708,105 @@ pub fn evaluate_binary_operation_field<'a, T: FieldElement>(
     .into())
 }
 
+/// Returns the integer value of a field element, where the upper half of the field
+/// represents negative values.
+fn field_element_to_signed_integer<T: FieldElement>(x: T) -> BigInt {
+    if x.is_in_lower_half() {
+        x.to_arbitrary_integer().into()
+    } else {
+        -BigInt::from((-x).to_arbitrary_integer())
+    }
+}
+
+/// Folds a product or power of constant expressions. In contrast to operations on
+/// field elements, these are computed on integers, so that constants like `2**32`
+/// that do not fit into the field are an error instead of silently wrapping around.
+fn fold_constant_expressions<T: FieldElement>(
+    left: T,
+    op: BinaryOperator,
+    right: &BigInt,
+) -> Result<T, EvalError> {
+    let left_value = field_element_to_signed_integer(left);
+    let modulus = BigInt::from(T::modulus().to_arbitrary_integer());
+    let overflow = || {
+        EvalError::ArithmeticError(format!(
+            "Constant {left_value} {op} {right} does not fit into the field with modulus {modulus}"
+        ))
+    };
+    if op == BinaryOperator::Pow {
+        if left_value.clone() * &left_value <= BigInt::one() {
+            // Powers of 0, 1 and -1 never overflow.
+            let exponent = u64::try_from(right).map_err(|_| overflow())?;
+            return Ok(left.pow(exponent.into()));
+        } else if *right > BigInt::from(T::BITS) {
+            return Err(overflow());
+        }
+    }
+    let result = evaluate_binary_operation_integer::<T>(&left_value, op, right)?;
+    let Value::Integer(result) = result.as_ref() else {
+        unreachable!()
+    };
+    if result.is_negative() {
+        integer_to_field_element::<T>(&-result, "").map(|x| -x)
+    } else {
+        integer_to_field_element::<T>(result, "")
+    }
+    .map_err(|_| overflow())
+}
+
+/// The maximum amount an integer can be shifted left by.
+const MAX_SHIFT_LEFT: usize = 1 << 20;
+
+/// Evaluates a binary operation on integers, which have arbitrary precision.
+///
+/// - Division truncates towards zero and the remainder of `%` has the sign of the
+///   dividend, such that `(a / b) * b + a % b == a`, e.g. `-7 / 2 == -3` and `-7 % 2 == -1`.
+///   Division by zero is an error.
+/// - Shifts interpret integers in two's complement with infinite sign extension,
+///   i.e. `a >> b` is `a / 2**b` rounded towards negative infinity, so shifting by
+///   large amounts results in 0 or -1. Left shifts of non-zero values by more than
+///   2**20 bits are an error, as are negative shift amounts and negative exponents.
 pub fn evaluate_binary_operation_integer<'a, T>(
     left: &BigInt,
     op: BinaryOperator,
     right: &BigInt,
 ) -> Result<Arc<Value<'a, T>>, EvalError> {
+    let arithmetic_error =
+        |reason: &str| EvalError::ArithmeticError(format!("{reason} in {left} {op} {right}"));
     Ok(match op {
         BinaryOperator::Add => Value::Integer(left + right),
         BinaryOperator::Sub => Value::Integer(left - right),
         BinaryOperator::Mul => Value::Integer(left * right),
+        BinaryOperator::Div | BinaryOperator::Mod if right.is_zero() => {
+            Err(arithmetic_error("Division by zero"))?
+        }
         BinaryOperator::Div => Value::Integer(left / right),
-        BinaryOperator::Pow => Value::Integer(left.pow(usize::try_from(right).unwrap())),
         BinaryOperator::Mod => Value::Integer(left % right),
+        BinaryOperator::Pow => {
+            if right.is_negative() {
+                Err(arithmetic_error("Negative exponent"))?
+            }
+            let exponent =
+                u32::try_from(right).map_err(|_| arithmetic_error("Exponent too large"))?;
+            Value::Integer(left.pow(exponent as usize))
+        }
         BinaryOperator::BinaryAnd => Value::Integer(left & right),
         BinaryOperator::BinaryXor => Value::Integer(left ^ right),
         BinaryOperator::BinaryOr => Value::Integer(left | right),
-        BinaryOperator::ShiftLeft => Value::Integer(left << usize::try_from(right).unwrap()),
-        BinaryOperator::ShiftRight => Value::Integer(left >> usize::try_from(right).unwrap()),
+        BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight if right.is_negative() => {
+            Err(arithmetic_error("Negative shift amount"))?
+        }
+        BinaryOperator::ShiftLeft if left.is_zero() => Value::Integer(BigInt::zero()),
+        BinaryOperator::ShiftLeft => match usize::try_from(right) {
+            Ok(amount) if amount <= MAX_SHIFT_LEFT => Value::Integer(left << amount),
+            _ => Err(arithmetic_error("Shift amount too large"))?,
+        },
+        BinaryOperator::ShiftRight => match usize::try_from(right) {
+            Ok(amount) => Value::Integer(left >> amount),
+            // All bits are shifted out, only the sign remains.
+            _ if left.is_negative() => Value::Integer(-BigInt::one()),
+            _ => Value::Integer(BigInt::zero()),
+        },
         BinaryOperator::Less => Value::Bool(left < right),
         BinaryOperator::LessEqual => Value::Bool(left <= right),
         BinaryOperator::Equal => Value::Bool(left == right),
@@ -852,6 +2822,8 @@ pub fn evaluate_binary_operation_integer<'a, T>(
 
 #[cfg(test)]
 mod test {
+    use std::cell::RefCell;
+
     use powdr_number::GoldilocksField;
     use pretty_assertions::assert_eq;
 
@@ -860,17 +2832,20 @@ mod test {
     use super::*;
 
     fn parse_and_evaluate_symbol(input: &str, symbol: &str) -> String {
+        try_parse_and_evaluate_symbol(input, symbol).unwrap()
+    }
+
+    fn try_parse_and_evaluate_symbol(input: &str, symbol: &str) -> Result<String, EvalError> {
         let analyzed = analyze_string::<GoldilocksField>(input);
         let Some(FunctionValueDefinition::Expression(TypedExpression {
             e: symbol,
             type_scheme: _,
-        })) = &analyzed.definitions[symbol].1
+        })) = &analyzed.definition(symbol).unwrap().1
         else {
             panic!()
         };
         evaluate::<GoldilocksField>(symbol, &Definitions(&analyzed.definitions))
-            .unwrap()
-            .to_string()
+            .map(|v| v.to_string())
     }
 
     #[test]
@@ -921,6 +2896,19 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn block_expressions() {
+        let src = r#"namespace Main(16);
+            let f: int -> int = |i| { let x = i + 1; let g = |j| x * j; let x = 10; g(x) + x };
+            let result = f(2);
+        "#;
+        // The closure g captures the first x, the final expression sees the second one.
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.result"),
+            "40".to_string()
+        );
+    }
+
     #[test]
     pub fn array_len() {
         let src = r#"
@@ -973,6 +2961,14 @@ mod test {
         parse_and_evaluate_symbol(src, "N");
     }
 
+    #[test]
+    pub fn hex_and_binary_numbers() {
+        let src = r#"
+            let N: int = 0b1010 * 0x10 + 0b1_0000_0000 - 255;
+        "#;
+        assert_eq!(parse_and_evaluate_symbol(src, "N"), "161".to_string());
+    }
+
     #[test]
     pub fn decimal_number_outside_field() {
         // This tests that the parser does not lose precision when parsing large integers.
@@ -983,12 +2979,77 @@ mod test {
     }
 
     #[test]
-    #[should_panic = "Number literal 9999999999999999999999999999999 is too large for field element."]
+    pub fn bit_decomposition() {
+        let src = r#"
+            namespace std::utils(16);
+            let to_bits = [];
+            let from_bits = [];
+            let to_bytes = [];
+            let from_bytes = [];
+            namespace F(16);
+            let zero = std::utils::to_bits(0, 4);
+            let max = std::utils::to_bits(15, 4);
+            let mixed = std::utils::to_bits(6, 5);
+            let from_bits = std::utils::from_bits([0, 1, 1, 0, 1]);
+            let empty = std::utils::from_bits([]);
+            let bytes = std::utils::to_bytes(0x1234, 3);
+            let max_bytes = std::utils::to_bytes(0xffff, 2);
+            let round_trip = std::utils::from_bytes(std::utils::to_bytes(0x123456789abcdef0123, 10));
+            let too_large = std::utils::to_bits(16, 4);
+            let too_large_bytes = std::utils::to_bytes(0x10000, 2);
+            let negative = std::utils::to_bits(-1, 4);
+            let not_a_bit = std::utils::from_bits([1, 2]);
+            let not_a_byte = std::utils::from_bytes([256]);
+        "#;
+        assert_eq!(parse_and_evaluate_symbol(src, "F.zero"), "[0, 0, 0, 0]");
+        assert_eq!(parse_and_evaluate_symbol(src, "F.max"), "[1, 1, 1, 1]");
+        assert_eq!(parse_and_evaluate_symbol(src, "F.mixed"), "[0, 1, 1, 0, 0]");
+        assert_eq!(parse_and_evaluate_symbol(src, "F.from_bits"), "22");
+        assert_eq!(parse_and_evaluate_symbol(src, "F.empty"), "0");
+        assert_eq!(parse_and_evaluate_symbol(src, "F.bytes"), "[52, 18, 0]");
+        assert_eq!(parse_and_evaluate_symbol(src, "F.max_bytes"), "[255, 255]");
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "F.round_trip"),
+            "5373003642731685151011"
+        );
+        let error = |symbol| {
+            try_parse_and_evaluate_symbol(src, symbol)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("F.too_large"),
+            "Out of bounds access: std::utils::to_bits: Value 16 does not fit into 4 bits."
+        );
+        assert_eq!(
+            error("F.too_large_bytes"),
+            "Out of bounds access: std::utils::to_bytes: Value 65536 does not fit into 2 bytes."
+        );
+        assert_eq!(
+            error("F.negative"),
+            "Out of bounds access: std::utils::to_bits: Value -1 does not fit into 4 bits."
+        );
+        assert_eq!(
+            error("F.not_a_bit"),
+            "Out of bounds access: std::utils::from_bits: Expected a bit, but got 2."
+        );
+        assert_eq!(
+            error("F.not_a_byte"),
+            "Out of bounds access: std::utils::from_bytes: Expected a byte, but got 256."
+        );
+    }
+
+    #[test]
     pub fn decimal_number_outside_field_for_fe() {
         let src = r#"
             let N: fe = 9999999999999999999999999999999;
+            let M: fe = 18446744069414584320;
         "#;
-        parse_and_evaluate_symbol(src, "N");
+        assert_eq!(
+            try_parse_and_evaluate_symbol(src, "N").unwrap_err().to_string(),
+            "Field overflow: Number 9999999999999999999999999999999 is too large for the Goldilocks field with modulus 18446744069414584321."
+        );
+        assert_eq!(parse_and_evaluate_symbol(src, "M"), "18446744069414584320");
     }
 
     #[test]
@@ -1010,4 +3071,701 @@ mod test {
         "#;
         parse_and_evaluate_symbol(src, "std::debug::N");
     }
+
+    #[test]
+    pub fn native_array_functions() {
+        let src = r#"
+            namespace std::array(8);
+            let len = 1;
+            let new = 1;
+            let map = 1;
+            let fold = 1;
+            let sum = 1;
+            namespace std::utils(8);
+            let<T1, T2> fold: int, (int -> T1), T2, (T2, T1 -> T2) -> T2 = |length, f, initial, folder|
+                if length <= 0 {
+                    initial
+                } else {
+                    folder(fold((length - 1), f, initial, folder), f((length - 1)))
+                };
+            namespace reference(8);
+            let<T> new: int, (int -> T) -> T[] = |length, f| std::utils::fold(length, f, [], |acc, e| (acc + [e]));
+            let<T1, T2> map: T1[], (T1 -> T2) -> T2[] = |arr, f| new(std::array::len(arr), |i| f(arr[i]));
+            let<T1, T2> fold: T1[], T2, (T2, T1 -> T2) -> T2 = |arr, initial, folder| std::utils::fold(std::array::len(arr), |i| arr[i], initial, folder);
+            let<T: Add + FromLiteral> sum: T[] -> T = |arr| fold(arr, 0, |a, b| a + b);
+            namespace F(8);
+            let arr: int[] = [1, 2, 3, 4];
+            let empty: fe[] = [];
+            let new_native: int[] = std::array::new(5, |i| i * i);
+            let new_ref: int[] = reference::new(5, |i| i * i);
+            let new_negative_native: int[] = std::array::new(-2, |i| i);
+            let new_negative_ref: int[] = reference::new(-2, |i| i);
+            let map_native: string[] = std::array::map(arr, |x| if x % 2 == 0 { "even" } else { "odd" });
+            let map_ref: string[] = reference::map(arr, |x| if x % 2 == 0 { "even" } else { "odd" });
+            let fold_native: int[] = std::array::fold(arr, [0], |acc, x| [x] + acc);
+            let fold_ref: int[] = reference::fold(arr, [0], |acc, x| [x] + acc);
+            let sum_native: int = std::array::sum(arr);
+            let sum_ref: int = reference::sum(arr);
+            let sum_fe_native: fe = std::array::sum([7, 8]);
+            let sum_fe_ref: fe = reference::sum([7, 8]);
+            let sum_empty_native: fe = std::array::sum(empty);
+            let sum_empty_ref: fe = reference::sum(empty);
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let evaluate_symbol = |name: &str| {
+            let Some(FunctionValueDefinition::Expression(TypedExpression { e, .. })) =
+                &analyzed.definition(name).unwrap().1
+            else {
+                panic!()
+            };
+            let result =
+                evaluate::<GoldilocksField>(e, &Definitions(&analyzed.definitions)).unwrap();
+            format!("{result}: {}", result.type_formatted())
+        };
+        for (name, expected) in [
+            ("new", "[0, 1, 4, 9, 16]: [int, int, int, int, int]"),
+            ("new_negative", "[]: []"),
+            (
+                "map",
+                r#"["odd", "even", "odd", "even"]: [string, string, string, string]"#,
+            ),
+            ("fold", "[4, 3, 2, 1, 0]: [int, int, int, int, int]"),
+            ("sum", "10: int"),
+            ("sum_fe", "15: fe"),
+            ("sum_empty", "0: fe"),
+        ] {
+            let native = evaluate_symbol(&format!("F.{name}_native"));
+            assert_eq!(native, evaluate_symbol(&format!("F.{name}_ref")));
+            assert_eq!(native, expected);
+        }
+    }
+
+    #[test]
+    pub fn conversions() {
+        let src = r#"
+            namespace std::convert(8);
+            let int = 1;
+            let fe = 1;
+            let expr = 1;
+            namespace F(8);
+            col witness w;
+            let zero: int = 0;
+            let three: int = 3;
+            let max: int = 18446744069414584320;
+            let fe_zero: fe = std::convert::fe(zero);
+            let fe_max: fe = std::convert::fe(max);
+            let fe_modulus: fe = std::convert::fe(max + 1);
+            let fe_negative: fe = std::convert::fe(zero - 1);
+            let fe_of_expr: fe = std::convert::fe(std::convert::expr(three));
+            let int_of_fe: int = std::convert::int(std::convert::fe(max));
+            let int_of_expr: int = std::convert::int(std::convert::expr(max));
+            let int_of_column: int = std::convert::int(w);
+            let expr_modulus: int = std::convert::int(std::convert::expr(max + 1));
+            let expr_negative: int = std::convert::int(std::convert::expr(zero - 1));
+            let expr_of_fe: int = std::convert::int(std::convert::expr(std::convert::fe(three)));
+        "#;
+        let eval = |symbol: &str| {
+            try_parse_and_evaluate_symbol(src, &format!("F.{symbol}")).map_err(|e| e.to_string())
+        };
+        assert_eq!(eval("fe_zero").unwrap(), "0");
+        assert_eq!(eval("fe_max").unwrap(), "18446744069414584320");
+        assert_eq!(
+            eval("fe_modulus").unwrap_err(),
+            "Field overflow: Number 18446744069414584321 is too large for the Goldilocks field with modulus 18446744069414584321."
+        );
+        assert_eq!(
+            eval("fe_negative").unwrap_err(),
+            "Type error: std::convert::fe cannot convert negative integer -1 (the field modulus is 18446744069414584321)"
+        );
+        assert_eq!(eval("fe_of_expr").unwrap(), "3");
+        assert_eq!(eval("int_of_fe").unwrap(), "18446744069414584320");
+        assert_eq!(eval("int_of_expr").unwrap(), "18446744069414584320");
+        assert_eq!(
+            eval("int_of_column").unwrap_err(),
+            "Type error: std::convert::int can only convert constant expressions, but got F.w"
+        );
+        assert_eq!(
+            eval("expr_modulus").unwrap_err(),
+            "Field overflow: Number 18446744069414584321 is too large for the Goldilocks field with modulus 18446744069414584321."
+        );
+        assert_eq!(
+            eval("expr_negative").unwrap_err(),
+            "Type error: std::convert::expr cannot convert negative integer -1 (the field modulus is 18446744069414584321)"
+        );
+        assert_eq!(eval("expr_of_fe").unwrap(), "3");
+    }
+
+    #[test]
+    pub fn debug_print_values() {
+        struct CapturingSymbols<'a> {
+            definitions: Definitions<'a>,
+            limits: EvaluationLimits,
+            output: RefCell<String>,
+        }
+        impl<'a> SymbolLookup<'a, GoldilocksField> for CapturingSymbols<'a> {
+            fn lookup(
+                &self,
+                name: &'a str,
+                generic_args: Option<Vec<Type>>,
+            ) -> Result<Arc<Value<'a, GoldilocksField>>, EvalError> {
+                self.definitions.lookup(name, generic_args)
+            }
+            fn limits(&self) -> EvaluationLimits {
+                self.limits
+            }
+            fn print(&self, msg: &str) {
+                self.output.borrow_mut().push_str(msg);
+            }
+        }
+
+        let src = r#"
+            namespace std::debug(8);
+            let print = 2;
+            namespace F(8);
+            let x: int = 7;
+            let f: int, int -> int = |a, b| a + b;
+            let p = [
+                std::debug::print("text: "),
+                std::debug::print(x),
+                std::debug::print(["a", "b"]),
+                std::debug::print((x, "c", f))
+            ];
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let Some(FunctionValueDefinition::Expression(TypedExpression { e, .. })) =
+            &analyzed.definition("F.p").unwrap().1
+        else {
+            panic!()
+        };
+        let symbols = CapturingSymbols {
+            definitions: Definitions(&analyzed.definitions),
+            limits: Default::default(),
+            output: Default::default(),
+        };
+        evaluate::<GoldilocksField>(e, &symbols).unwrap();
+        assert_eq!(
+            symbols.output.take(),
+            r#"text: 7["a", "b"](7, "c", |a, b| ...)"#
+        );
+
+        let silent_symbols = CapturingSymbols {
+            definitions: Definitions(&analyzed.definitions),
+            limits: EvaluationLimits {
+                debug_print: false,
+                ..Default::default()
+            },
+            output: Default::default(),
+        };
+        let result = evaluate::<GoldilocksField>(e, &silent_symbols).unwrap();
+        assert_eq!(result.to_string(), "[[], [], [], []]");
+        assert_eq!(silent_symbols.output.take(), "");
+    }
+
+    #[test]
+    pub fn string_operations() {
+        let src = r#"
+            namespace std::string(8);
+            let len = 1;
+            namespace std::convert(8);
+            let to_string = 1;
+            let fe = 1;
+            namespace F(8);
+            let x: int = 7;
+            let s: string = "ab" + "c\n";
+            let concatenated = s;
+            let length = std::string::len(s + "ü");
+            let comparisons = ["abc" == "abc", "abc" != "abd", "ab" < "abc", "b" <= "a", "b" > "a", "a" >= "b"];
+            let matched: int = match "b" + "c" { "bc" => 1, _ => 2 };
+            let converted = "x = " + std::convert::to_string(x) + ", fe: " + std::convert::to_string(std::convert::fe(x));
+        "#;
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "F.concatenated"),
+            r#""abc\n""#
+        );
+        assert_eq!(parse_and_evaluate_symbol(src, "F.length"), "5");
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "F.comparisons"),
+            "[true, true, true, false, true, false]"
+        );
+        assert_eq!(parse_and_evaluate_symbol(src, "F.matched"), "1");
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "F.converted"),
+            r#""x = 7, fe: 7""#
+        );
+    }
+
+    #[test]
+    pub fn format_strings() {
+        let src = r#"
+            namespace std::convert(8);
+            let to_string = 1;
+            let fe = 1;
+            namespace F(8);
+            let x: int = 7;
+            let s = "abc";
+            let f = |i| i + 1;
+            let simple = f"x = {x}, x + 1 = {f(x)}, fe: {std::convert::fe(x)}";
+            let nested = f"{s}, {[s]}, {(x, x < 2)}, {[f]}, {f}";
+            let escaped = f"{{x}} = {{{x}}}";
+            let empty = f"";
+        "#;
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "F.simple"),
+            r#""x = 7, x + 1 = 8, fe: 7""#
+        );
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "F.nested"),
+            r#""abc, [\"abc\"], (7, false), [<closure>], <closure>""#
+        );
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "F.escaped"),
+            r#""{x} = {7}""#
+        );
+        assert_eq!(parse_and_evaluate_symbol(src, "F.empty"), r#""""#);
+    }
+
+    #[test]
+    pub fn memoized_calls() {
+        struct MemoizingSymbols<'a> {
+            definitions: Definitions<'a>,
+            call_cache: CallCache<'a, GoldilocksField>,
+        }
+        impl<'a> SymbolLookup<'a, GoldilocksField> for MemoizingSymbols<'a> {
+            fn lookup(
+                &self,
+                name: &'a str,
+                generic_args: Option<Vec<Type>>,
+            ) -> Result<Arc<Value<'a, GoldilocksField>>, EvalError> {
+                self.definitions
+                    .lookup_with_symbols(name, generic_args, self)
+            }
+            fn call_cache(&self) -> Option<&CallCache<'a, GoldilocksField>> {
+                Some(&self.call_cache)
+            }
+        }
+
+        let src = r#"namespace Main(16);
+            let fib: int -> int = |i| match i {
+                0 => 0,
+                1 => 1,
+                _ => fib(i - 1) + fib(i - 2),
+            };
+            let pair: int -> int[] = |i| [fib(i), i];
+            let adder: int -> (int -> int) = |i| |j| i + j;
+            let result = [fib(20), fib(20), pair(3)[0], adder(2)(3), adder(2)(4)];
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let Some(FunctionValueDefinition::Expression(TypedExpression { e, .. })) =
+            &analyzed.definition("Main.result").unwrap().1
+        else {
+            panic!()
+        };
+        let without_cache = evaluate::<GoldilocksField>(e, &Definitions(&analyzed.definitions))
+            .unwrap()
+            .to_string();
+        assert_eq!(without_cache, "[6765, 6765, 2, 5, 6]");
+
+        let symbols = MemoizingSymbols {
+            definitions: Definitions(&analyzed.definitions),
+            call_cache: CallCache::new(100),
+        };
+        let with_cache = evaluate::<GoldilocksField>(e, &symbols)
+            .unwrap()
+            .to_string();
+        assert_eq!(with_cache, without_cache);
+        // fib(0) to fib(20), pair(3) and adder(2), which returns a closure, is not cached.
+        assert_eq!(symbols.call_cache.len(), 22);
+
+        let symbols = MemoizingSymbols {
+            definitions: Definitions(&analyzed.definitions),
+            call_cache: CallCache::new(5),
+        };
+        let with_small_cache = evaluate::<GoldilocksField>(e, &symbols)
+            .unwrap()
+            .to_string();
+        assert_eq!(with_small_cache, without_cache);
+        assert_eq!(symbols.call_cache.len(), 5);
+    }
+
+    #[test]
+    pub fn deep_recursion() {
+        let src = r#"namespace Main(16);
+            let count: int -> int = |i| if i == 0 { 0 } else { 1 + count(i - 1) };
+            let build: int, int[] -> int[] = |i, acc| match i { 0 => acc, _ => build(i - 1, [i]) };
+            let result = count(100000);
+            let list = build(100000, []);
+        "#;
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.result"), "100000");
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.list"), "[1]");
+    }
+
+    #[test]
+    pub fn integer_operations() {
+        use BinaryOperator::*;
+        let cases: &[(i64, BinaryOperator, i64, &str)] = &[
+            (7, Div, 2, "3"),
+            (-7, Div, 2, "-3"),
+            (7, Div, -2, "-3"),
+            (-7, Div, -2, "3"),
+            (7, Mod, 2, "1"),
+            (-7, Mod, 2, "-1"),
+            (7, Mod, -2, "1"),
+            (-7, Mod, -2, "-1"),
+            (7, Div, 0, "Arithmetic error: Division by zero in 7 / 0"),
+            (-7, Mod, 0, "Arithmetic error: Division by zero in -7 % 0"),
+            (2, Pow, 3, "8"),
+            (-2, Pow, 3, "-8"),
+            (2, Pow, -1, "Arithmetic error: Negative exponent in 2 ** -1"),
+            (1, ShiftLeft, 300, "2037035976334486086268445688409378161051468393665936250636140449354381299763336706183397376"),
+            (-1, ShiftLeft, 3, "-8"),
+            (0, ShiftLeft, i64::MAX, "0"),
+            (1, ShiftLeft, (1 << 20) + 1, "Arithmetic error: Shift amount too large in 1 << 1048577"),
+            (1, ShiftLeft, -1, "Arithmetic error: Negative shift amount in 1 << -1"),
+            (7, ShiftRight, 1, "3"),
+            (-7, ShiftRight, 1, "-4"),
+            (7, ShiftRight, 2, "1"),
+            (7, ShiftRight, 3, "0"),
+            (-7, ShiftRight, 3, "-1"),
+            (7, ShiftRight, 500, "0"),
+            (-7, ShiftRight, 500, "-1"),
+            (i64::MAX, ShiftRight, 63, "0"),
+            (i64::MIN, ShiftRight, 63, "-1"),
+            (7, ShiftRight, i64::MAX, "0"),
+            (-7, ShiftRight, i64::MAX, "-1"),
+            (7, ShiftRight, -1, "Arithmetic error: Negative shift amount in 7 >> -1"),
+        ];
+        for (left, op, right, expected) in cases {
+            let result = evaluate_binary_operation_integer::<GoldilocksField>(
+                &(*left).into(),
+                *op,
+                &(*right).into(),
+            );
+            let result = match result {
+                Ok(v) => v.to_string(),
+                Err(e) => e.to_string(),
+            };
+            assert_eq!(&result, expected, "{left} {op} {right}");
+        }
+    }
+
+    #[test]
+    pub fn int_fe_mixing() {
+        // The type checker rejects mixing int and fe, but the evaluator can still
+        // encounter it when a closure is called with values from the outside.
+        let src = r#"namespace Main(16);
+            let<T: Add> add: T, T -> T = |a, b| a + b;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let Some(FunctionValueDefinition::Expression(TypedExpression { e, .. })) =
+            &analyzed.definition("Main.add").unwrap().1
+        else {
+            panic!()
+        };
+        let symbols = Definitions(&analyzed.definitions);
+        let add = evaluate::<GoldilocksField>(e, &symbols).unwrap();
+        let err = evaluate_function_call(
+            add,
+            vec![
+                Value::Integer(1.into()).into(),
+                Value::FieldElement(2.into()).into(),
+            ],
+            &symbols,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.without_call_stack().to_string(),
+            "Type error: Operator + cannot be applied to an int and an fe: 1 + 2\n\
+            Left operand type: int\n\
+            Right operand type: fe\n\
+            Hint: wrap the int operand with std::convert::fe(...) or the fe operand with std::convert::int(...)."
+        );
+    }
+
+    #[test]
+    pub fn returned_closures() {
+        let src = r#"namespace Main(16);
+            let w: -> int = || 2;
+            let x: int = (|i| || i + w())(w())();
+            let y: int = (|i| |j| || i * j + w())(3)(4)();
+            let adder: int -> (int -> int) = |a| |b| a + b;
+            let add3: int -> int = adder(3);
+            let z: int[] = [add3(1), adder(10)(add3(2)), add3(add3(0))];
+            let fs: (int -> int)[] = [adder(1), |k| adder(k)(k), add3];
+            let t: int[] = [fs[0](5), fs[1](5), fs[2](5)];
+        "#;
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.x"), "4".to_string());
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.y"), "14".to_string());
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.z"),
+            "[4, 15, 6]".to_string()
+        );
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.t"),
+            "[6, 10, 8]".to_string()
+        );
+    }
+
+    #[test]
+    pub fn match_strings_and_tuples() {
+        let src = r#"namespace Main(16);
+            let kind: string = "store";
+            let op_id: string -> int = |k| match k {
+                "load" => 1,
+                "store" => 2,
+                "a\"b" => 3,
+                _ => 0,
+            };
+            let ids: int[] = [op_id("load"), op_id(kind), op_id("a\"b"), op_id("a\\\"b"), op_id("LOAD")];
+            let classify: (int, string) -> int = |t| match t {
+                (0, "x") => 1,
+                (1, "x") => 2,
+                (0, "y") => 3,
+                _ => 4,
+            };
+            let classes: int[] = [classify((0, "x")), classify((1, "x")), classify((0, "y")), classify((1, "y"))];
+            let dispatch: int -> (string, int) = |i| match kind {
+                "load" => ("input", i),
+                "store" => ("hint", i + 1),
+                _ => ("none", 0),
+            };
+        "#;
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.ids"),
+            "[1, 2, 3, 0, 0]".to_string()
+        );
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.classes"),
+            "[1, 2, 3, 4]".to_string()
+        );
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let Some(FunctionValueDefinition::Expression(TypedExpression { e, .. })) =
+            &analyzed.definition("Main.dispatch").unwrap().1
+        else {
+            panic!()
+        };
+        let symbols = Definitions(&analyzed.definitions);
+        let query = evaluate::<GoldilocksField>(e, &symbols).unwrap();
+        let result =
+            evaluate_function_call(query, vec![Value::Integer(7.into()).into()], &symbols).unwrap();
+        assert_eq!(result.to_string(), "(\"hint\", 8)");
+    }
+
+    #[test]
+    pub fn match_ranges() {
+        let src = r#"namespace Main(16);
+            let classify: int -> int = |i| match i {
+                -3..0 => 1,
+                0..16 => 2,
+                8..=32 => 3,
+                33 => 4,
+                _ => 0,
+            };
+            let classes: int[] = [classify(-4), classify(-3), classify(0), classify(8), classify(15), classify(16), classify(32), classify(33), classify(34)];
+        "#;
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.classes"),
+            "[0, 1, 2, 2, 2, 3, 3, 4, 0]".to_string()
+        );
+    }
+
+    #[test]
+    pub fn match_or_patterns() {
+        let src = r#"namespace Main(16);
+            let kind: int -> string = |op| match op {
+                2 | 3 | 5 | 7 => "prime",
+                0 | 1 => "unit",
+                8..16 | 4 | 6 => "even",
+                _ => "other",
+            };
+            let kinds: string[] = [kind(0), kind(2), kind(4), kind(7), kind(20), kind(10)];
+            let name: string -> int = |s| match s { "a" | "b" => 1, _ => 0 };
+            let names: int[] = [name("a"), name("b"), name("c")];
+        "#;
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.kinds"),
+            r#"["unit", "prime", "even", "prime", "other", "even"]"#.to_string()
+        );
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.names"),
+            "[1, 1, 0]".to_string()
+        );
+    }
+
+    #[test]
+    pub fn lazy_branches_and_short_circuit() {
+        // All of these would fail or not terminate under strict evaluation.
+        let src = r#"namespace Main(16);
+            let loop: int -> bool = |i| loop(i + 1);
+            let safe_div: int -> bool = |i| (i != 0) && (1000 / i > 2);
+            let and_guard: bool[] = [safe_div(0), safe_div(10), safe_div(1000)];
+            let n: int = 2;
+            let t: bool = n == 2;
+            let or_guard: bool = t || loop(0);
+            let and_false: bool = !t && loop(0);
+            let if_guard: int = if t { 7 } else { 1000 / (n - 2) };
+            let fact: int -> int = |n| if n <= 1 { 1 } else { n * fact(n - 1) };
+            let f: int = fact(10);
+        "#;
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.and_guard"),
+            "[false, true, false]".to_string()
+        );
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.or_guard"),
+            "true".to_string()
+        );
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.and_false"),
+            "false".to_string()
+        );
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.if_guard"),
+            "7".to_string()
+        );
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.f"),
+            "3628800".to_string()
+        );
+    }
+
+    #[test]
+    pub fn value_conversions() {
+        type V = Value<'static, GoldilocksField>;
+        assert!(bool::try_from(V::from(true)).unwrap());
+        assert_eq!(String::try_from(V::from("abc")).unwrap(), "abc");
+        assert_eq!(i64::try_from(V::from(-7i64)).unwrap(), -7);
+        assert_eq!(u64::try_from(&V::from(7u64)).unwrap(), 7);
+        assert_eq!(
+            BigUint::try_from(V::from(BigUint::from(1u32) << 100)).unwrap(),
+            BigUint::from(1u32) << 100
+        );
+        assert_eq!(
+            GoldilocksField::try_from(V::from(BigInt::from(9))).unwrap(),
+            9.into()
+        );
+        assert_eq!(
+            GoldilocksField::try_from(V::from(GoldilocksField::from(9))).unwrap(),
+            9.into()
+        );
+        let array = V::from(vec![1u64, 2, 3]);
+        assert_eq!(array.to_string(), "[1, 2, 3]");
+        assert_eq!(Vec::<u64>::try_from(&array).unwrap(), vec![1, 2, 3]);
+        let tuple = V::from(("x", vec![true, false], 5i64));
+        assert_eq!(tuple.to_string(), "(\"x\", [true, false], 5)");
+        assert_eq!(
+            <(String, Vec<bool>, i64)>::try_from(tuple).unwrap(),
+            ("x".to_string(), vec![true, false], 5)
+        );
+
+        assert_eq!(
+            bool::try_from(V::from(1u64)).unwrap_err().to_string(),
+            "Type error: Expected bool but got 1: int"
+        );
+        assert_eq!(
+            u64::try_from(V::from(-1i64)).unwrap_err().to_string(),
+            "Type error: Integer -1 does not fit into u64."
+        );
+        assert_eq!(
+            Vec::<u64>::try_from(V::from(vec![1i64, -2]))
+                .unwrap_err()
+                .to_string(),
+            "Type error: Integer -2 does not fit into u64."
+        );
+        assert_eq!(
+            <(u64, u64)>::try_from(V::from((1u64, 2u64, 3u64)))
+                .unwrap_err()
+                .to_string(),
+            "Type error: Expected tuple of length 2 but got (1, 2, 3): (int, int, int)"
+        );
+        assert_eq!(
+            String::try_from(V::from(vec!["a"]))
+                .unwrap_err()
+                .to_string(),
+            "Type error: Expected string but got [\"a\"]: [string]"
+        );
+    }
+
+    #[test]
+    pub fn columns_at_row_index() {
+        let src = r#"namespace std::convert(8);
+            let fe = [];
+        namespace Main(8);
+            col fixed line(i) { std::convert::fe(i * 2) };
+            col fixed pattern = [1, 2] + [3, 4, 5]*;
+            col witness w;
+            let at_line: fe[] = [line(0), line(3), line(7)];
+            let at_pattern: fe[] = [pattern(0), pattern(1), pattern(2), pattern(4), pattern(5), pattern(7)];
+            let from_function: int -> fe = |i| line(i) + pattern(i);
+            let sum: fe = from_function(6);
+            let at_w: fe = w(1);
+            let out_of_bounds: fe = pattern(8);
+        "#;
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.at_line"), "[0, 6, 14]");
+        assert_eq!(
+            parse_and_evaluate_symbol(src, "Main.at_pattern"),
+            "[1, 2, 3, 5, 3, 5]"
+        );
+        assert_eq!(parse_and_evaluate_symbol(src, "Main.sum"), "16");
+        assert_eq!(
+            try_parse_and_evaluate_symbol(src, "Main.at_w")
+                .unwrap_err()
+                .to_string(),
+            "Type error: Only fixed columns can be evaluated at a row index, but Main.w is a witness column."
+        );
+        assert_eq!(
+            try_parse_and_evaluate_symbol(src, "Main.out_of_bounds")
+                .unwrap_err()
+                .to_string(),
+            "Out of bounds access: Row 8 of fixed column Main.pattern is out of bounds, it has 8 rows."
+        );
+    }
+
+    #[test]
+    pub fn call_stack_in_errors() {
+        let src = r#"namespace Main(16);
+            let inner: int -> int = |x| 10 / (x - 2);
+            let middle: int, string -> int = |x, s| inner(x * 2) + 1;
+            let outer: int -> int = |x| middle(x - 1, "a very long string argument that is truncated");
+            let result: int = outer(2);
+        "#;
+        let error = try_parse_and_evaluate_symbol(src, "Main.result").unwrap_err();
+        let frames = error
+            .call_stack()
+            .iter()
+            .map(|frame| frame.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            frames,
+            [
+                "Main.inner(2)",
+                "Main.middle(1, \"a very long string argument that is tru…)",
+                "Main.outer(2)"
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Arithmetic error: Division by zero in 10 / 0\nCall stack (innermost call first):\n    {}",
+                frames.join("\n    ")
+            )
+        );
+    }
+
+    #[test]
+    pub fn omitted_stack_frames() {
+        let src = r#"namespace Main(16);
+            let count_down: int -> int = |x| if x == 0 { std::check::panic("done") } else { count_down(x - 1) };
+            let result: int = count_down(30);
+        namespace std::check(16);
+            let panic = 1;
+        "#;
+        let error = try_parse_and_evaluate_symbol(src, "Main.result").unwrap_err();
+        assert_eq!(error.call_stack().len(), 31);
+        let formatted = error.to_string();
+        let lines = formatted.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2 + 16 + 1);
+        assert_eq!(lines[2], "    Main.count_down(0)");
+        assert_eq!(lines[10], "    … 15 frames omitted");
+        assert_eq!(lines[18], "    Main.count_down(30)");
+    }
 }
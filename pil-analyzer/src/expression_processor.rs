@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 
+use itertools::Itertools;
+
 use powdr_ast::{
     analyzed::{Expression, PolynomialReference, Reference, RepeatedArray},
     parsed::{
-        self, asm::SymbolPath, ArrayExpression, ArrayLiteral, IfExpression, LambdaExpression,
-        MatchArm, MatchPattern, NamespacedPolynomialReference, SelectedExpressions,
+        self, asm::SymbolPath, ArrayExpression, ArrayLiteral, BlockExpression, IfExpression,
+        LambdaExpression, LetStatementInsideBlock, MatchArm, MatchPattern,
+        NamespacedPolynomialReference, SelectedExpressions,
     },
 };
 use powdr_number::DegreeType;
@@ -32,21 +35,24 @@ impl<D: AnalysisDriver> ExpressionProcessor<D> {
     pub fn process_selected_expressions(
         &mut self,
         expr: SelectedExpressions<parsed::Expression>,
-    ) -> SelectedExpressions<Expression> {
-        SelectedExpressions {
-            selector: expr.selector.map(|e| self.process_expression(e)),
-            expressions: self.process_expressions(expr.expressions),
-        }
+    ) -> Result<SelectedExpressions<Expression>, String> {
+        Ok(SelectedExpressions {
+            selector: expr
+                .selector
+                .map(|e| self.process_expression(e))
+                .transpose()?,
+            expressions: self.process_expressions(expr.expressions)?,
+        })
     }
 
     pub fn process_array_expression(
         &mut self,
         array_expression: ::powdr_ast::parsed::ArrayExpression,
         size: DegreeType,
-    ) -> Vec<RepeatedArray> {
-        match array_expression {
+    ) -> Result<Vec<RepeatedArray>, String> {
+        Ok(match array_expression {
             ArrayExpression::Value(expressions) => {
-                let values = self.process_expressions(expressions);
+                let values = self.process_expressions(expressions)?;
                 let size = values.len() as DegreeType;
                 vec![RepeatedArray::new(values, size)]
             }
@@ -55,103 +61,174 @@ impl<D: AnalysisDriver> ExpressionProcessor<D> {
                     vec![]
                 } else {
                     vec![RepeatedArray::new(
-                        self.process_expressions(expressions),
+                        self.process_expressions(expressions)?,
                         size,
                     )]
                 }
             }
             ArrayExpression::Concat(left, right) => self
-                .process_array_expression(*left, size)
+                .process_array_expression(*left, size)?
                 .into_iter()
-                .chain(self.process_array_expression(*right, size))
+                .chain(self.process_array_expression(*right, size)?)
                 .collect(),
-        }
+        })
     }
 
-    pub fn process_expressions(&mut self, exprs: Vec<parsed::Expression>) -> Vec<Expression> {
+    pub fn process_expressions(
+        &mut self,
+        exprs: Vec<parsed::Expression>,
+    ) -> Result<Vec<Expression>, String> {
         exprs
             .into_iter()
             .map(|e| self.process_expression(e))
             .collect()
     }
 
-    pub fn process_expression(&mut self, expr: parsed::Expression) -> Expression {
+    /// Processes the expression. Fails if it references an unknown symbol
+    /// or contains a free input, which is only allowed in assembly.
+    pub fn process_expression(&mut self, expr: parsed::Expression) -> Result<Expression, String> {
         use parsed::Expression as PExpression;
-        match expr {
-            PExpression::Reference(poly) => Expression::Reference(self.process_reference(poly)),
-            PExpression::PublicReference(name) => Expression::PublicReference(name),
-            PExpression::Number(n, t) => Expression::Number(n, t),
+        Ok(match expr {
+            PExpression::Reference(poly) => Expression::Reference(self.process_reference(poly)?),
+            PExpression::PublicReference(name) => {
+                Expression::PublicReference(self.driver.resolve_public_ref(&name))
+            }
+            PExpression::Number(n, t, r) => Expression::Number(n, t, r),
             PExpression::String(value) => Expression::String(value),
-            PExpression::Tuple(items) => Expression::Tuple(self.process_expressions(items)),
+            PExpression::Tuple(items) => Expression::Tuple(self.process_expressions(items)?),
             PExpression::ArrayLiteral(ArrayLiteral { items }) => {
                 Expression::ArrayLiteral(ArrayLiteral {
-                    items: self.process_expressions(items),
+                    items: self.process_expressions(items)?,
                 })
             }
             PExpression::LambdaExpression(LambdaExpression { params, body }) => {
-                let body = Box::new(self.process_function(&params, *body));
+                let body = Box::new(self.process_function(&params, *body)?);
                 Expression::LambdaExpression(LambdaExpression { params, body })
             }
             PExpression::BinaryOperation(left, op, right) => Expression::BinaryOperation(
-                Box::new(self.process_expression(*left)),
+                Box::new(self.process_expression(*left)?),
                 op,
-                Box::new(self.process_expression(*right)),
+                Box::new(self.process_expression(*right)?),
             ),
             PExpression::UnaryOperation(op, value) => {
-                Expression::UnaryOperation(op, Box::new(self.process_expression(*value)))
+                Expression::UnaryOperation(op, Box::new(self.process_expression(*value)?))
             }
             PExpression::IndexAccess(index_access) => {
                 Expression::IndexAccess(parsed::IndexAccess {
-                    array: Box::new(self.process_expression(*index_access.array)),
-                    index: Box::new(self.process_expression(*index_access.index)),
+                    array: Box::new(self.process_expression(*index_access.array)?),
+                    index: Box::new(self.process_expression(*index_access.index)?),
                 })
             }
             PExpression::FunctionCall(c) => Expression::FunctionCall(parsed::FunctionCall {
-                function: Box::new(self.process_expression(*c.function)),
-                arguments: self.process_expressions(c.arguments),
+                function: Box::new(self.process_expression(*c.function)?),
+                arguments: self.process_expressions(c.arguments)?,
             }),
             PExpression::MatchExpression(scrutinee, arms) => Expression::MatchExpression(
-                Box::new(self.process_expression(*scrutinee)),
+                Box::new(self.process_expression(*scrutinee)?),
                 arms.into_iter()
-                    .map(|MatchArm { pattern, value }| MatchArm {
-                        pattern: match pattern {
-                            MatchPattern::CatchAll => MatchPattern::CatchAll,
-                            MatchPattern::Pattern(e) => {
-                                MatchPattern::Pattern(self.process_expression(e))
-                            }
-                        },
-                        value: self.process_expression(value),
-                    })
-                    .collect(),
+                    .map(|arm| self.process_match_arm(arm))
+                    .collect::<Result<_, String>>()?,
             ),
             PExpression::IfExpression(IfExpression {
                 condition,
                 body,
                 else_body,
             }) => Expression::IfExpression(IfExpression {
-                condition: Box::new(self.process_expression(*condition)),
-                body: Box::new(self.process_expression(*body)),
-                else_body: Box::new(self.process_expression(*else_body)),
+                condition: Box::new(self.process_expression(*condition)?),
+                body: Box::new(self.process_expression(*body)?),
+                else_body: Box::new(self.process_expression(*else_body)?),
             }),
-            PExpression::FreeInput(_) => panic!(),
+            PExpression::BlockExpression(block) => {
+                Expression::BlockExpression(self.process_block_expression(block)?)
+            }
+            PExpression::FreeInput(_) => {
+                return Err("Free inputs are only allowed in assembly.".to_string())
+            }
+        })
+    }
+
+    fn process_match_arm(
+        &mut self,
+        MatchArm { patterns, value }: MatchArm<parsed::NamespacedPolynomialReference>,
+    ) -> Result<MatchArm<Reference>, String> {
+        if let Some((first, other)) = patterns
+            .iter()
+            .tuple_windows()
+            .find(|(first, other)| first.bound_variables() != other.bound_variables())
+        {
+            return Err(format!(
+                "All alternatives of a match arm have to bind the same variables, but {first} and {other} do not."
+            ));
         }
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| {
+                Ok(match pattern {
+                    MatchPattern::CatchAll => MatchPattern::CatchAll,
+                    MatchPattern::Pattern(e) => MatchPattern::Pattern(self.process_expression(e)?),
+                    MatchPattern::Range(start, end, kind) => MatchPattern::Range(
+                        self.process_expression(start)?,
+                        self.process_expression(end)?,
+                        kind,
+                    ),
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(MatchArm {
+            patterns,
+            value: self.process_expression(value)?,
+        })
     }
 
-    fn process_reference(&mut self, reference: NamespacedPolynomialReference) -> Reference {
-        match reference.try_to_identifier() {
+    fn process_block_expression(
+        &mut self,
+        BlockExpression { statements, expr }: BlockExpression<NamespacedPolynomialReference>,
+    ) -> Result<BlockExpression<Reference>, String> {
+        let previous_local_vars = self.local_variables.clone();
+        let previous_counter = self.local_variable_counter;
+
+        // Each statement introduces a new local variable, visible in the following
+        // statements and the final expression, potentially shadowing an existing one.
+        let statements = statements
+            .into_iter()
+            .map(|LetStatementInsideBlock { name, value }| {
+                let value = self.process_expression(value)?;
+                self.local_variables
+                    .insert(name.clone(), self.local_variable_counter);
+                self.local_variable_counter += 1;
+                Ok(LetStatementInsideBlock { name, value })
+            })
+            .collect::<Result<Vec<_>, String>>();
+        let block = statements.and_then(|statements| {
+            Ok(BlockExpression {
+                statements,
+                expr: Box::new(self.process_expression(*expr)?),
+            })
+        });
+        // Reset the local variable mapping.
+        self.local_variables = previous_local_vars;
+        self.local_variable_counter = previous_counter;
+        block
+    }
+
+    fn process_reference(
+        &mut self,
+        reference: NamespacedPolynomialReference,
+    ) -> Result<Reference, String> {
+        Ok(match reference.try_to_identifier() {
             Some(name) if self.local_variables.contains_key(name) => {
                 let id = self.local_variables[name];
                 Reference::LocalVar(id, name.to_string())
             }
-            _ => Reference::Poly(self.process_namespaced_polynomial_reference(&reference.path)),
-        }
+            _ => Reference::Poly(self.process_namespaced_polynomial_reference(&reference.path)?),
+        })
     }
 
     pub fn process_function(
         &mut self,
         params: &[String],
         expression: ::powdr_ast::parsed::Expression,
-    ) -> Expression {
+    ) -> Result<Expression, String> {
         let previous_local_vars = self.local_variables.clone();
 
         // Add the new local variables, potentially overwriting existing variables.
@@ -172,14 +249,14 @@ impl<D: AnalysisDriver> ExpressionProcessor<D> {
     pub fn process_namespaced_polynomial_reference(
         &mut self,
         path: &SymbolPath,
-    ) -> PolynomialReference {
-        PolynomialReference {
-            name: self.driver.resolve_ref(path),
+    ) -> Result<PolynomialReference, String> {
+        Ok(PolynomialReference {
+            name: self.driver.resolve_ref(path)?,
             poly_id: None,
             // These will be filled by the type checker.
             // TODO at some point we should support the turbofish operator
             // in the parser.
             generic_args: Default::default(),
-        }
+        })
     }
 }
@@ -2,10 +2,14 @@
 
 mod call_graph;
 mod condenser;
+mod error;
 pub mod evaluator;
 pub mod expression_processor;
 mod pil_analyzer;
+mod query_context;
+mod simplifier;
 mod statement_processor;
+mod timing;
 mod type_builtins;
 mod type_inference;
 mod type_unifier;
@@ -14,15 +18,29 @@ use std::collections::HashMap;
 
 use powdr_ast::{
     analyzed::{FunctionValueDefinition, Symbol},
-    parsed::asm::SymbolPath,
+    parsed::asm::{AbsoluteSymbolPath, SymbolPath},
 };
 
-pub use pil_analyzer::{analyze_ast, analyze_file, analyze_string};
+pub use condenser::CondenseError;
+pub use error::AnalysisError;
+pub use evaluator::evaluate_function;
+pub use pil_analyzer::{
+    analyze_ast, analyze_ast_with_limits, analyze_file, analyze_file_with_timings, analyze_string,
+    analyze_string_with_host_symbols, analyze_string_with_limits, analyze_string_with_timings,
+    try_analyze_string, type_check_ast, type_check_file, type_check_string, TypeCheckedPIL,
+};
+pub use timing::PhaseTiming;
 
 pub trait AnalysisDriver: Clone + Copy {
     /// Turns a declaration into an absolute name.
     fn resolve_decl(&self, name: &str) -> String;
     /// Turns a reference to a name with an optional namespace into an absolute name.
-    fn resolve_ref(&self, path: &SymbolPath) -> String;
-    fn definitions(&self) -> &HashMap<String, (Symbol, Option<FunctionValueDefinition>)>;
+    /// Fails if there is no symbol with that name.
+    fn resolve_ref(&self, path: &SymbolPath) -> Result<String, String>;
+    /// Turns a reference to a public declaration into an absolute name, trying the
+    /// current namespace first. Unknown names are returned unchanged.
+    fn resolve_public_ref(&self, name: &str) -> String;
+    fn definitions(
+        &self,
+    ) -> &HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>;
 }
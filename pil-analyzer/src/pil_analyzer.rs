@@ -3,10 +3,13 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::iter::once;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use itertools::Itertools;
 
 use powdr_ast::parsed::asm::{AbsoluteSymbolPath, SymbolPath};
 
-use powdr_ast::parsed::types::Type;
+use powdr_ast::parsed::types::{Type, TypeScheme};
 use powdr_ast::parsed::{PILFile, PilStatement};
 use powdr_number::{DegreeType, FieldElement, GoldilocksField};
 
@@ -16,49 +19,286 @@ use powdr_ast::analyzed::{
 };
 use powdr_parser::parse_type;
 
+use crate::query_context;
 use crate::type_inference::{infer_types, ExpectedType};
 use crate::AnalysisDriver;
 
 use crate::statement_processor::{Counters, PILItem, StatementProcessor};
-use crate::{condenser, evaluator, expression_processor::ExpressionProcessor};
+use crate::{
+    condenser::{self, CondenseError},
+    evaluator::{self, EvalError, EvaluationLimits, HostSymbols, Value},
+    expression_processor::ExpressionProcessor,
+    timing::{PhaseTiming, Timer},
+    AnalysisError,
+};
 
 pub fn analyze_file<T: FieldElement>(path: &Path) -> Analyzed<T> {
+    analyze_file_with_timings(path).0
+}
+
+/// Analyzes the file and its dependencies and returns the wall time spent in each phase.
+pub fn analyze_file_with_timings<T: FieldElement>(path: &Path) -> (Analyzed<T>, PhaseTiming) {
+    let mut timer = Timer::new("analysis");
+    timer.start("parsing");
     let files = import_all_dependencies(path);
-    analyze::<T>(files)
+    timer.end();
+    expect_analysis(analyze::<T>(timer, files, Default::default(), None))
 }
 
 pub fn analyze_ast<T: FieldElement>(pil_file: PILFile) -> Analyzed<T> {
-    analyze::<T>(vec![pil_file])
+    analyze_ast_with_limits::<T>(pil_file, Default::default())
+}
+
+/// Analyzes the PIL file, applying the given limits to evaluations during condensation.
+pub fn analyze_ast_with_limits<T: FieldElement>(
+    pil_file: PILFile,
+    limits: EvaluationLimits,
+) -> Analyzed<T> {
+    expect_analysis(analyze::<T>(
+        Timer::new("analysis"),
+        vec![pil_file],
+        limits,
+        None,
+    ))
+    .0
 }
 
 pub fn analyze_string<T: FieldElement>(contents: &str) -> Analyzed<T> {
-    let pil_file = powdr_parser::parse(Some("input"), contents).unwrap_or_else(|err| {
+    analyze_string_with_limits::<T>(contents, Default::default())
+}
+
+/// Analyzes the PIL source, applying the given limits to evaluations during condensation.
+pub fn analyze_string_with_limits<T: FieldElement>(
+    contents: &str,
+    limits: EvaluationLimits,
+) -> Analyzed<T> {
+    analyze_string_timed(contents, limits, None).0
+}
+
+/// Analyzes the PIL source and returns the wall time spent in each phase.
+pub fn analyze_string_with_timings<T: FieldElement>(contents: &str) -> (Analyzed<T>, PhaseTiming) {
+    analyze_string_timed(contents, Default::default(), None)
+}
+
+/// Analyzes the PIL source, where symbols not defined in PIL can be provided by
+/// `host_symbols`. If a symbol is defined in both, the PIL definition is used.
+/// Note that the analyzed file can still reference host symbols, so
+/// later evaluations have to consult `host_symbols` as well.
+pub fn analyze_string_with_host_symbols<T: FieldElement>(
+    contents: &str,
+    host_symbols: &dyn HostSymbols<T>,
+) -> Analyzed<T> {
+    analyze_string_timed(contents, Default::default(), Some(host_symbols)).0
+}
+
+/// Analyzes the PIL source and returns all errors instead of panicking,
+/// which makes it suitable for hosts that cannot recover from a panic.
+pub fn try_analyze_string<T: FieldElement>(
+    contents: &str,
+) -> Result<Analyzed<T>, Vec<AnalysisError>> {
+    let file = powdr_parser::parse(Some("input"), contents)
+        .map_err(|e| vec![AnalysisError::Parse(e.to_string())])?;
+    analyze(Timer::new("analysis"), vec![file], Default::default(), None)
+        .map(|(analyzed, _)| analyzed)
+}
+
+fn analyze_string_timed<T: FieldElement>(
+    contents: &str,
+    limits: EvaluationLimits,
+    host_symbols: Option<&dyn HostSymbols<T>>,
+) -> (Analyzed<T>, PhaseTiming) {
+    let mut timer = Timer::new("analysis");
+    timer.start("parsing");
+    let file = parse_string(contents);
+    timer.end();
+    expect_analysis(analyze(timer, vec![file], limits, host_symbols))
+}
+
+/// Returns the result of an analysis or panics with all its errors.
+fn expect_analysis<R>(result: Result<R, Vec<AnalysisError>>) -> R {
+    result.unwrap_or_else(|errors| panic!("{}", errors.iter().format("\n")))
+}
+
+fn parse_string(contents: &str) -> PILFile {
+    powdr_parser::parse(Some("input"), contents).unwrap_or_else(|err| {
         eprintln!("Error parsing .pil file:");
         err.output_to_stderr();
         panic!();
-    });
-    analyze(vec![pil_file])
+    })
+}
+
+/// Parses, resolves and type checks the file and its dependencies, without choosing a field.
+pub fn type_check_file(path: &Path) -> TypeCheckedPIL {
+    let mut timer = Timer::new("type checking");
+    timer.start("parsing");
+    let files = import_all_dependencies(path);
+    timer.end();
+    let type_checked = expect_analysis(type_check(&mut timer, files, vec![]));
+    timer.finish();
+    type_checked
+}
+
+/// Resolves and type checks the PIL file, without choosing a field.
+pub fn type_check_ast(pil_file: PILFile) -> TypeCheckedPIL {
+    let mut timer = Timer::new("type checking");
+    let type_checked = expect_analysis(type_check(&mut timer, vec![pil_file], vec![]));
+    timer.finish();
+    type_checked
+}
+
+/// Parses, resolves and type checks the PIL source, without choosing a field.
+pub fn type_check_string(contents: &str) -> TypeCheckedPIL {
+    type_check_ast(parse_string(contents))
+}
+
+/// Runs all phases after parsing, recording them in `timer`,
+/// and returns the analyzed file together with the timings.
+fn analyze<T: FieldElement>(
+    mut timer: Timer,
+    files: Vec<PILFile>,
+    limits: EvaluationLimits,
+    host_symbols: Option<&dyn HostSymbols<T>>,
+) -> Result<(Analyzed<T>, PhaseTiming), Vec<AnalysisError>> {
+    let host_declarations = host_symbols
+        .map(|host_symbols| host_symbols.declarations())
+        .unwrap_or_default();
+    let type_checked = type_check(&mut timer, files, host_declarations)?;
+    timer.start("condensation");
+    let analyzed = type_checked
+        .condense_with::<T>(
+            rayon::current_num_threads(),
+            false,
+            limits,
+            host_symbols,
+            &mut timer,
+        )
+        .map_err(|errors| {
+            errors
+                .into_iter()
+                .map(AnalysisError::Condensation)
+                .collect::<Vec<_>>()
+        })?;
+    timer.end();
+    Ok((analyzed, timer.finish()))
 }
 
-fn analyze<T: FieldElement>(files: Vec<PILFile>) -> Analyzed<T> {
+/// Runs statement processing and type inference, recording them in `timer`.
+fn type_check(
+    timer: &mut Timer,
+    files: Vec<PILFile>,
+    host_declarations: Vec<(String, TypeScheme)>,
+) -> Result<TypeCheckedPIL, Vec<AnalysisError>> {
     let mut analyzer = PILAnalyzer::new();
+    analyzer.declare_host_symbols(host_declarations);
+    timer.start("statement processing");
     analyzer.process(files);
-    analyzer.type_check();
-    analyzer.condense::<T>()
+    timer.end();
+    if !analyzer.errors.is_empty() {
+        return Err(analyzer.errors);
+    }
+    timer.start("type inference");
+    let type_checked = analyzer.type_check().map_err(|e| vec![e]);
+    timer.end();
+    let type_checked = type_checked?;
+    let errors = query_context::check(&type_checked.definitions, &type_checked.identities);
+    if errors.is_empty() {
+        Ok(type_checked)
+    } else {
+        Err(errors)
+    }
+}
+
+/// A PIL file after name resolution and type inference, but before condensation.
+/// Nothing in it depends on the field: number literals are still arbitrary integers,
+/// and they are only converted and checked against the modulus when condensing.
+/// This means that the same file can be condensed for different fields.
+#[derive(Debug, Clone)]
+pub struct TypeCheckedPIL {
+    polynomial_degree: Option<DegreeType>,
+    definitions: HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    public_declarations: HashMap<AbsoluteSymbolPath, PublicDeclaration>,
+    identities: Vec<Identity<Expression>>,
+    identity_namespaces: Vec<AbsoluteSymbolPath>,
+    source_order: Vec<StatementIdentifier>,
+    host_declarations: HashMap<AbsoluteSymbolPath, (String, TypeScheme)>,
+}
+
+impl TypeCheckedPIL {
+    /// Condenses the file for the field `T`. Literals that are too large for the field
+    /// and constants that overflow it are reported as errors.
+    pub fn condense<T: FieldElement>(&self) -> Result<Analyzed<T>, Vec<CondenseError>> {
+        self.condense_with_limits(Default::default())
+    }
+
+    /// Condenses the file for the field `T`, applying the given limits to evaluations.
+    pub fn condense_with_limits<T: FieldElement>(
+        &self,
+        limits: EvaluationLimits,
+    ) -> Result<Analyzed<T>, Vec<CondenseError>> {
+        self.clone().condense_with(
+            rayon::current_num_threads(),
+            false,
+            limits,
+            None,
+            &mut Timer::new("condensation"),
+        )
+    }
+
+    /// Condenses the analyzed file, using `threads` threads for the identities.
+    /// If `simplify` is set, constants are folded in the condensed expressions.
+    /// `host_symbols` has to provide the symbols declared by the host during type checking.
+    /// The sub-phases of condensation are recorded in `timer`.
+    fn condense_with<T: FieldElement>(
+        self,
+        threads: usize,
+        simplify: bool,
+        limits: EvaluationLimits,
+        host_symbols: Option<&dyn HostSymbols<T>>,
+        timer: &mut Timer,
+    ) -> Result<Analyzed<T>, Vec<CondenseError>> {
+        let host_symbols = host_symbols.map(|host| DeclaredHostSymbols {
+            host,
+            declarations: &self.host_declarations,
+        });
+        condenser::condense::<T>(
+            self.polynomial_degree,
+            self.definitions,
+            self.public_declarations,
+            &self.identities,
+            &self.identity_namespaces,
+            self.source_order,
+            threads,
+            simplify,
+            limits,
+            host_symbols.as_ref().map(|h| h as &dyn HostSymbols<T>),
+            timer,
+        )
+    }
 }
 
 #[derive(Default)]
 struct PILAnalyzer {
-    known_symbols: HashSet<String>,
+    known_symbols: HashSet<AbsoluteSymbolPath>,
+    /// The absolute names of all public declarations.
+    known_publics: HashSet<AbsoluteSymbolPath>,
     current_namespace: AbsoluteSymbolPath,
     polynomial_degree: Option<DegreeType>,
-    definitions: HashMap<String, (Symbol, Option<FunctionValueDefinition>)>,
-    public_declarations: HashMap<String, PublicDeclaration>,
+    definitions: HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    public_declarations: HashMap<AbsoluteSymbolPath, PublicDeclaration>,
     identities: Vec<Identity<Expression>>,
+    /// The namespace of each identity.
+    identity_namespaces: Vec<AbsoluteSymbolPath>,
     /// The order in which definitions and identities
     /// appear in the source.
     source_order: Vec<StatementIdentifier>,
     symbol_counters: Option<Counters>,
+    /// The symbols provided by the host, by absolute name. The values are
+    /// the names as declared by the host and the types.
+    host_declarations: HashMap<AbsoluteSymbolPath, (String, TypeScheme)>,
+    /// The errors found while processing the statements.
+    errors: Vec<AnalysisError>,
+    /// The labels of the identities processed so far.
+    identity_labels: HashSet<String>,
 }
 
 /// Reads and parses the given path and all its imports.
@@ -112,6 +352,18 @@ impl PILAnalyzer {
         }
     }
 
+    /// Declares symbols provided by the host, see [HostSymbols].
+    /// Has to be called before `process`.
+    pub fn declare_host_symbols(&mut self, declarations: Vec<(String, TypeScheme)>) {
+        for (name, type_scheme) in declarations {
+            let path = SymbolPath::from_str(&name)
+                .unwrap_or_else(|e| panic!("Invalid name of host symbol: {e}"));
+            let absolute_name = AbsoluteSymbolPath::default().join(path);
+            self.host_declarations
+                .insert(absolute_name, (name, type_scheme));
+        }
+    }
+
     pub fn process(&mut self, files: Vec<PILFile>) {
         for PILFile(file) in &files {
             self.current_namespace = Default::default();
@@ -119,6 +371,20 @@ impl PILAnalyzer {
                 self.collect_names(statement);
             }
         }
+        if !self.errors.is_empty() {
+            // Statements can only be processed if all names are unique.
+            return;
+        }
+        self.host_declarations.retain(|name, _| {
+            let is_new = self.known_symbols.insert(name.clone());
+            if !is_new {
+                log::warn!(
+                    "Symbol {} is defined in PIL and provided by the host. Using the PIL definition.",
+                    name.to_dotted_string()
+                );
+            }
+            is_new
+        });
 
         for PILFile(file) in files {
             self.current_namespace = Default::default();
@@ -128,11 +394,11 @@ impl PILAnalyzer {
         }
     }
 
-    pub fn type_check(&mut self) {
+    pub fn type_check(mut self) -> Result<TypeCheckedPIL, AnalysisError> {
         let query_type: Type = parse_type("int -> (string, fe)").unwrap().into();
         let mut expressions = vec![];
         // Collect all definitions with their types and expressions.
-        // For Arrays, we also collect the inner expressions and expect them to be field elements.
+        // For (sparse) arrays, we also collect the inner expressions and expect them to be field elements.
         let definitions = self
             .definitions
             .iter_mut()
@@ -157,6 +423,11 @@ impl PILAnalyzer {
                                         .map(|e| (e, Type::Fe.into())),
                                 );
                             }
+                            Some(FunctionValueDefinition::Sparse(sparse)) => {
+                                // Expect all values to be field elements.
+                                expressions
+                                    .extend(sparse.values_mut().map(|e| (e, Type::Fe.into())));
+                            }
                             Some(FunctionValueDefinition::Query(query)) => {
                                 // Query functions are int -> (string, fe).
                                 // TODO replace this by an enum.
@@ -175,6 +446,13 @@ impl PILAnalyzer {
                     };
                 (name.clone(), (type_scheme, expr))
             })
+            .chain(
+                self.host_declarations
+                    .iter()
+                    .map(|(name, (_, type_scheme))| {
+                        (name.clone(), (Some(type_scheme.clone()), None))
+                    }),
+            )
             .collect();
         // Collect all expressions in identities.
         for id in &mut self.identities {
@@ -200,11 +478,7 @@ impl PILAnalyzer {
         }
 
         let inferred_types = infer_types(definitions, &mut expressions)
-            .map_err(|e| {
-                eprintln!("\nError during type inference:\n{e}");
-                e
-            })
-            .unwrap();
+            .map_err(|e| AnalysisError::TypeInference(e.to_string()))?;
         // Store the inferred types.
         for (name, ty) in inferred_types {
             let Some(FunctionValueDefinition::Expression(TypedExpression {
@@ -216,16 +490,15 @@ impl PILAnalyzer {
             };
             *ts = Some(ty.into());
         }
-    }
-
-    pub fn condense<T: FieldElement>(self) -> Analyzed<T> {
-        condenser::condense::<T>(
-            self.polynomial_degree,
-            self.definitions,
-            self.public_declarations,
-            &self.identities,
-            self.source_order,
-        )
+        Ok(TypeCheckedPIL {
+            polynomial_degree: self.polynomial_degree,
+            definitions: self.definitions,
+            public_declarations: self.public_declarations,
+            identities: self.identities,
+            identity_namespaces: self.identity_namespaces,
+            source_order: self.source_order,
+            host_declarations: self.host_declarations,
+        })
     }
 
     /// A step to collect all defined names in the statement.
@@ -237,9 +510,18 @@ impl PILAnalyzer {
             PilStatement::Include(_, _) => unreachable!(),
             _ => {
                 for name in statement.symbol_definition_names() {
-                    let absolute_name = self.driver().resolve_decl(name);
+                    let absolute_name = self.driver().resolve_decl_path(name);
+                    if matches!(statement, PilStatement::PublicDeclaration(..)) {
+                        self.known_publics.insert(absolute_name.clone());
+                    }
                     if !self.known_symbols.insert(absolute_name.clone()) {
-                        panic!("Duplicate symbol definition: {absolute_name}");
+                        self.errors.push(AnalysisError::Statement(
+                            statement.source().clone(),
+                            format!(
+                                "Duplicate symbol definition: {}",
+                                absolute_name.to_dotted_string()
+                            ),
+                        ));
                     }
                 }
             }
@@ -249,36 +531,59 @@ impl PILAnalyzer {
     fn handle_statement(&mut self, statement: PilStatement) {
         match statement {
             PilStatement::Include(_, _) => unreachable!(),
-            PilStatement::Namespace(_, name, degree) => self.handle_namespace(name, degree),
+            PilStatement::Namespace(source, name, degree) => {
+                if let Err(e) = self.handle_namespace(name, degree) {
+                    self.errors.push(AnalysisError::Statement(source, e));
+                }
+            }
             _ => {
+                let source = statement.source().clone();
                 // We need a mutable reference to the counter, but it is short-lived.
                 let mut counters = self.symbol_counters.take().unwrap();
                 let items =
                     StatementProcessor::new(self.driver(), &mut counters, self.polynomial_degree)
                         .handle_statement(statement);
                 self.symbol_counters = Some(counters);
+                let items = match items {
+                    Ok(items) => items,
+                    Err(e) => {
+                        self.errors.push(AnalysisError::Statement(source, e));
+                        return;
+                    }
+                };
                 for item in items {
                     match item {
                         PILItem::Definition(symbol, value) => {
-                            let name = symbol.absolute_name.clone();
+                            let name =
+                                AbsoluteSymbolPath::from_dotted_string(&symbol.absolute_name);
                             let is_new = self
                                 .definitions
                                 .insert(name.clone(), (symbol, value))
                                 .is_none();
-                            assert!(is_new, "{name} already defined.");
+                            assert!(is_new, "{} already defined.", name.to_dotted_string());
                             self.source_order
                                 .push(StatementIdentifier::Definition(name));
                         }
                         PILItem::PublicDeclaration(decl) => {
-                            let name = decl.name.clone();
+                            let name = AbsoluteSymbolPath::from_dotted_string(&decl.name);
                             self.public_declarations.insert(name.clone(), decl);
                             self.source_order
                                 .push(StatementIdentifier::PublicDeclaration(name));
                         }
                         PILItem::Identity(identity) => {
+                            if let Some(label) = &identity.label {
+                                if !self.identity_labels.insert(label.clone()) {
+                                    log::warn!(
+                                        "Duplicate identity label \"{label}\" at {}.",
+                                        identity.source
+                                    );
+                                }
+                            }
                             let index = self.identities.len();
                             self.source_order.push(StatementIdentifier::Identity(index));
-                            self.identities.push(identity)
+                            self.identities.push(identity);
+                            self.identity_namespaces
+                                .push(self.current_namespace.clone());
                         }
                     }
                 }
@@ -286,26 +591,32 @@ impl PILAnalyzer {
         }
     }
 
-    fn handle_namespace(&mut self, name: SymbolPath, degree: ::powdr_ast::parsed::Expression) {
+    /// Enters the namespace, even if its degree is invalid.
+    fn handle_namespace(
+        &mut self,
+        name: SymbolPath,
+        degree: ::powdr_ast::parsed::Expression,
+    ) -> Result<(), String> {
         let degree = ExpressionProcessor::new(self.driver()).process_expression(degree);
+        self.current_namespace = AbsoluteSymbolPath::default().join(name);
         // TODO we should maybe implement a separate evaluator that is able to run before type checking
         // and is field-independent (only uses integers)?
-        let namespace_degree: u64 = u64::try_from(
-            evaluator::evaluate_expression::<GoldilocksField>(&degree, &self.definitions)
-                .unwrap()
-                .try_to_integer()
-                .unwrap(),
-        )
-        .unwrap();
-        if let Some(degree) = self.polynomial_degree {
-            assert_eq!(
-                degree, namespace_degree,
-                "all namespaces must have the same degree"
-            );
-        } else {
-            self.polynomial_degree = Some(namespace_degree);
+        let degree = evaluator::evaluate_expression::<GoldilocksField>(&degree?, &self.definitions)
+            .and_then(|v| v.try_to_integer())
+            .map_err(|e| format!("Error evaluating the degree of the namespace:\n{e}"))?;
+        let namespace_degree = u64::try_from(&degree).map_err(|_| {
+            format!("The degree of a namespace has to be a non-negative integer that fits u64, but it is {degree}.")
+        })?;
+        match self.polynomial_degree {
+            Some(degree) if degree != namespace_degree => Err(format!(
+                "All namespaces must have the same degree, but found {namespace_degree} after {degree}."
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.polynomial_degree = Some(namespace_degree);
+                Ok(())
+            }
         }
-        self.current_namespace = AbsoluteSymbolPath::default().join(name);
     }
 
     fn driver(&self) -> Driver {
@@ -313,11 +624,40 @@ impl PILAnalyzer {
     }
 }
 
+/// Host symbols that are looked up by their absolute names, which
+/// can differ from the names declared by the host in the separator.
+/// Symbols that clash with PIL definitions are not provided.
+struct DeclaredHostSymbols<'a, T> {
+    host: &'a dyn HostSymbols<T>,
+    declarations: &'a HashMap<AbsoluteSymbolPath, (String, TypeScheme)>,
+}
+
+impl<'a, T> HostSymbols<T> for DeclaredHostSymbols<'a, T> {
+    fn declarations(&self) -> Vec<(String, TypeScheme)> {
+        self.declarations
+            .iter()
+            .map(|(name, (_, type_scheme))| (name.to_dotted_string(), type_scheme.clone()))
+            .collect()
+    }
+
+    fn lookup<'b>(&self, name: &str) -> Result<Value<'b, T>, EvalError> {
+        match self
+            .declarations
+            .get(&AbsoluteSymbolPath::from_dotted_string(name))
+        {
+            Some((declared_name, _)) => self.host.lookup(declared_name),
+            None => Err(EvalError::SymbolNotFound(format!(
+                "Symbol {name} not found."
+            ))),
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 struct Driver<'a>(&'a PILAnalyzer);
 
-impl<'a> AnalysisDriver for Driver<'a> {
-    fn resolve_decl(&self, name: &str) -> String {
+impl<'a> Driver<'a> {
+    fn resolve_decl_path(&self, name: &str) -> AbsoluteSymbolPath {
         (if name.starts_with('%') {
             // Constants are not namespaced
             AbsoluteSymbolPath::default()
@@ -325,24 +665,119 @@ impl<'a> AnalysisDriver for Driver<'a> {
             self.0.current_namespace.clone()
         })
         .with_part(name)
-        .to_dotted_string()
+    }
+}
+
+impl<'a> AnalysisDriver for Driver<'a> {
+    fn resolve_decl(&self, name: &str) -> String {
+        self.resolve_decl_path(name).to_dotted_string()
     }
 
-    fn resolve_ref(&self, path: &SymbolPath) -> String {
+    fn resolve_ref(&self, path: &SymbolPath) -> Result<String, String> {
         // Try to resolve the name starting at the current namespace and then
         // go up level by level until the root.
 
         self.0
             .current_namespace
             .iter_to_root()
-            .find_map(|prefix| {
-                let path = prefix.join(path.clone()).to_dotted_string();
-                self.0.known_symbols.contains(&path).then_some(path)
+            .map(|prefix| prefix.join(path.clone()))
+            .find(|path| self.0.known_symbols.contains(path))
+            .map(|path| path.to_dotted_string())
+            .ok_or_else(|| format!("Symbol not found: {}", path.to_dotted_string()))
+    }
+
+    fn resolve_public_ref(&self, name: &str) -> String {
+        let path = AbsoluteSymbolPath::from_dotted_string(name);
+        self.0
+            .current_namespace
+            .iter_to_root()
+            .map(|prefix| {
+                path.parts()
+                    .fold(prefix, |prefix, part| prefix.with_part(part))
             })
-            .unwrap_or_else(|| panic!("Symbol not found: {}", path.to_dotted_string()))
+            .find(|path| self.0.known_publics.contains(path))
+            .map(|path| path.to_dotted_string())
+            .unwrap_or_else(|| name.to_string())
     }
 
-    fn definitions(&self) -> &HashMap<String, (Symbol, Option<FunctionValueDefinition>)> {
+    fn definitions(
+        &self,
+    ) -> &HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)> {
         &self.0.definitions
     }
 }
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn condense_string(input: &str, threads: usize, simplify: bool) -> Analyzed<GoldilocksField> {
+        let pil_file = powdr_parser::parse(Some("input"), input).unwrap();
+        let mut analyzer = PILAnalyzer::new();
+        analyzer.process(vec![pil_file]);
+        analyzer
+            .type_check()
+            .unwrap()
+            .condense_with(
+                threads,
+                simplify,
+                Default::default(),
+                None,
+                &mut Timer::new("analysis"),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn parallel_condensation_is_deterministic() {
+        let mut input = r#"namespace N(16);
+    col witness x[32];
+    col witness y;
+    col fixed LINE(i) { i };
+    let bool_constraints = |arr, i, n| if i == n { [] } else { [arr[i] * (1 - arr[i]) = 0] + bool_constraints(arr, i + 1, n) };
+    let square = |e| e * e;
+"#
+        .to_string();
+        for i in 0..32 {
+            input += &format!("    x[{i}]' = square(x[{}]) + LINE * y;\n", (i + 1) % 32);
+            input += &format!("    bool_constraints(x, {i}, {});\n", (i + i % 5).min(32));
+            input += &format!("    {{ x[{i}], y }} in {{ LINE, LINE }};\n");
+        }
+        let sequential = condense_string(&input, 1, false);
+        let parallel = condense_string(&input, 4, false);
+        assert_eq!(sequential.identities, parallel.identities);
+        assert_eq!(sequential.source_order, parallel.source_order);
+        assert_eq!(sequential.to_string(), parallel.to_string());
+    }
+
+    #[test]
+    fn simplify_condensed() {
+        let input = r#"namespace T(16);
+    col fixed first_step = [1] + [0]*;
+    col witness x, y;
+    let scale = |c, e| c * e;
+    let inter: expr = 1 * x + 0;
+    x' * (1 - 1) + y * (1 + 1) = 0;
+    scale(2, scale(3, x)) = -(-y);
+    { first_step * 1, x } in { first_step, inter };
+"#;
+        let expected = r#"namespace T(16);
+    col fixed first_step = [1] + [0]*;
+    col witness x;
+    col witness y;
+    let scale: expr, expr -> expr = (|c, e| (c * e));
+    col inter = T.x;
+    (T.y * 2) = 0;
+    (6 * T.x) = T.y;
+    { T.first_step, T.x } in { T.first_step, T.inter };
+"#;
+        assert_eq!(condense_string(input, 1, true).to_string(), expected);
+        // Without simplification, the source arithmetic is kept.
+        assert!(condense_string(input, 1, false)
+            .to_string()
+            .contains("((T.x' * 0) + (T.y * 2)) = 0;"));
+    }
+}
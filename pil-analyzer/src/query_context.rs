@@ -0,0 +1,142 @@
+//! Checks that prover-only builtins are only used in query contexts.
+
+use std::collections::HashMap;
+
+use powdr_ast::{
+    analyzed::{
+        Expression, FunctionValueDefinition, Identity, Reference, Symbol, SymbolKind,
+        TypedExpression,
+    },
+    parsed::{asm::AbsoluteSymbolPath, visitor::ExpressionVisitable},
+};
+
+use crate::AnalysisError;
+
+/// Builtins that need the concept of a "current row" and thus
+/// can only be evaluated by the prover.
+const PROVER_ONLY_BUILTINS: [&str; 1] = ["std::prover::eval"];
+
+/// The context an expression is evaluated in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Context {
+    /// Query functions of witness columns, which includes the hints
+    /// generated from free inputs in asm.
+    Query,
+    /// Identities and definitions of fixed columns, intermediate columns and constants.
+    Constraint,
+    /// Other definitions, whose context is the one they are referenced from.
+    Inherited,
+}
+
+/// Returns an error for each use of a prover-only builtin outside of a query context,
+/// either directly or through a definition that uses it.
+pub fn check(
+    definitions: &HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+    identities: &[Identity<Expression>],
+) -> Vec<AnalysisError> {
+    let prover_only = prover_only_symbols(definitions);
+
+    let mut errors = vec![];
+    let mut definitions = definitions
+        .values()
+        .filter_map(|(symbol, value)| Some((symbol, value.as_ref()?)))
+        .filter(|(symbol, value)| context(symbol, value) == Context::Constraint)
+        .collect::<Vec<_>>();
+    definitions.sort_by_key(|(symbol, _)| &symbol.absolute_name);
+    for (symbol, value) in definitions {
+        if let Some(msg) = find_use(value, &prover_only) {
+            errors.push(AnalysisError::Statement(
+                symbol.source.clone(),
+                format!("{msg} in the definition of `{}`.", symbol.absolute_name),
+            ));
+        }
+    }
+    for identity in identities {
+        if let Some(msg) = find_use(identity, &prover_only) {
+            errors.push(AnalysisError::Statement(
+                identity.source.clone(),
+                format!("{msg} in the identity `{identity}`."),
+            ));
+        }
+    }
+    errors
+}
+
+fn context(symbol: &Symbol, value: &FunctionValueDefinition) -> Context {
+    match (&symbol.kind, value) {
+        (_, FunctionValueDefinition::Query(_)) => Context::Query,
+        (SymbolKind::Other(), FunctionValueDefinition::Expression(_)) => Context::Inherited,
+        _ => Context::Constraint,
+    }
+}
+
+/// Returns, for all symbols that use a prover-only builtin (directly or indirectly),
+/// the name of that builtin. The symbols are keyed by the names used in references.
+fn prover_only_symbols(
+    definitions: &HashMap<AbsoluteSymbolPath, (Symbol, Option<FunctionValueDefinition>)>,
+) -> HashMap<String, &'static str> {
+    let mut prover_only: HashMap<String, &'static str> = PROVER_ONLY_BUILTINS
+        .iter()
+        .map(|builtin| (builtin.to_string(), *builtin))
+        .collect();
+    let inherited = definitions
+        .values()
+        .filter_map(|(symbol, value)| match value {
+            Some(value @ FunctionValueDefinition::Expression(TypedExpression { e, .. }))
+                if context(symbol, value) == Context::Inherited =>
+            {
+                Some((&symbol.absolute_name, e))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    loop {
+        let newly_found = inherited
+            .iter()
+            .filter(|(name, _)| !prover_only.contains_key(*name))
+            .filter_map(|(name, e)| {
+                let builtin = referenced_names(e)
+                    .iter()
+                    .find_map(|r| prover_only.get(r))?;
+                Some((name.to_string(), *builtin))
+            })
+            .collect::<Vec<_>>();
+        if newly_found.is_empty() {
+            return prover_only;
+        }
+        prover_only.extend(newly_found);
+    }
+}
+
+/// Returns a message about the first use of a prover-only symbol in `item`, if any.
+fn find_use<E: ExpressionVisitable<Expression>>(
+    item: &E,
+    prover_only: &HashMap<String, &'static str>,
+) -> Option<String> {
+    let mut result = None;
+    item.pre_visit_expressions(&mut |e| {
+        if let (None, Expression::Reference(Reference::Poly(r))) = (&result, e) {
+            if let Some(builtin) = prover_only.get(&r.name) {
+                let through = if r.name == *builtin {
+                    String::new()
+                } else {
+                    format!(" (through `{}`)", r.name)
+                };
+                result = Some(format!(
+                    "`{builtin}` can only be used in query functions of witness columns and in hints, but it is used{through}"
+                ));
+            }
+        }
+    });
+    result
+}
+
+fn referenced_names(e: &Expression) -> Vec<String> {
+    let mut names = vec![];
+    e.pre_visit_expressions(&mut |e| {
+        if let Expression::Reference(Reference::Poly(r)) = e {
+            names.push(r.name.clone());
+        }
+    });
+    names
+}
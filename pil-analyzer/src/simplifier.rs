@@ -0,0 +1,101 @@
+//! Semantics-preserving simplification of condensed algebraic expressions.
+
+use powdr_ast::analyzed::{AlgebraicExpression, Analyzed};
+use powdr_number::FieldElement;
+
+/// Simplifies all expressions in identities and intermediate columns.
+pub fn simplify_analyzed<T: FieldElement>(pil: &mut Analyzed<T>) {
+    for identity in &mut pil.identities {
+        identity
+            .left
+            .selector
+            .iter_mut()
+            .chain(identity.left.expressions.iter_mut())
+            .chain(identity.right.selector.iter_mut())
+            .chain(identity.right.expressions.iter_mut())
+            .for_each(simplify_expression);
+    }
+    for (_, values) in pil.intermediate_columns.values_mut() {
+        values.iter_mut().for_each(simplify_expression);
+    }
+}
+
+/// Simplifies an algebraic expression by folding constants, removing
+/// neutral elements and double negations and combining constant factors.
+pub fn simplify_expression<T: FieldElement>(e: &mut AlgebraicExpression<T>) {
+    *e = e.simplify();
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_ast::analyzed::{
+        AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator, PolyID, PolynomialType,
+    };
+    use powdr_number::GoldilocksField;
+
+    use super::simplify_expression;
+
+    type Expr = AlgebraicExpression<GoldilocksField>;
+
+    fn col(name: &str, next: bool) -> Expr {
+        AlgebraicExpression::Reference(AlgebraicReference {
+            name: name.into(),
+            poly_id: PolyID {
+                id: 0,
+                ptype: PolynomialType::Committed,
+            },
+            next,
+        })
+    }
+
+    fn num(n: u64) -> Expr {
+        AlgebraicExpression::Number(n.into())
+    }
+
+    fn neg(e: Expr) -> Expr {
+        AlgebraicExpression::UnaryOperation(AlgebraicUnaryOperator::Minus, Box::new(e))
+    }
+
+    fn simplified(mut e: Expr) -> String {
+        simplify_expression(&mut e);
+        e.to_string()
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(simplified(num(2) + num(3) * num(4)), "14");
+        assert_eq!(simplified(num(2) - num(3)), "18446744069414584320");
+        assert_eq!(simplified(neg(num(0)) + num(7)), "7");
+    }
+
+    #[test]
+    fn neutral_elements() {
+        assert_eq!(simplified(col("T.first_step", true) * num(0)), "0");
+        assert_eq!(simplified(num(1) * col("x", false)), "x");
+        assert_eq!(simplified(col("x", false) * (num(3) - num(2))), "x");
+        assert_eq!(simplified(num(0) + col("x", false) - num(0)), "x");
+        assert_eq!(simplified(col("x", false) + num(0) * col("y", false)), "x");
+        // Zero on the left of a subtraction has to stay.
+        assert_eq!(simplified(num(0) - col("x", false)), "(0 - x)");
+    }
+
+    #[test]
+    fn double_negation() {
+        assert_eq!(simplified(neg(neg(col("x", false)))), "x");
+        assert_eq!(simplified(neg(neg(neg(col("x", false))))), "-x");
+    }
+
+    #[test]
+    fn constant_factors() {
+        assert_eq!(simplified(num(2) * (num(3) * col("x", false))), "(6 * x)");
+        assert_eq!(simplified((col("x", false) * num(3)) * num(2)), "(6 * x)");
+        assert_eq!(
+            simplified(num(2) * (num(3) * (num(4) * col("x", false)))),
+            "(24 * x)"
+        );
+        assert_eq!(
+            simplified(num(2) * (col("x", false) * col("y", false))),
+            "(2 * (x * y))"
+        );
+    }
+}
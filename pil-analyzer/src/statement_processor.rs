@@ -12,10 +12,9 @@ use powdr_number::{BigInt, DegreeType, GoldilocksField};
 
 use powdr_ast::analyzed::{
     Expression, FunctionValueDefinition, Identity, IdentityKind, PolynomialType, PublicDeclaration,
-    Symbol, SymbolKind,
+    SparseArray, Symbol, SymbolKind,
 };
 
-use crate::evaluator::EvalError;
 use crate::AnalysisDriver;
 
 use crate::{evaluator, expression_processor::ExpressionProcessor};
@@ -91,7 +90,9 @@ where
         }
     }
 
-    pub fn handle_statement(&mut self, statement: PilStatement) -> Vec<PILItem> {
+    /// Processes the statement and returns the resulting items, or a description of
+    /// the problem if the statement is invalid.
+    pub fn handle_statement(&mut self, statement: PilStatement) -> Result<Vec<PILItem>, String> {
         match statement {
             PilStatement::Include(_, _) => {
                 panic!("Includes must be handled outside the statement processor.")
@@ -131,7 +132,7 @@ where
             ) => {
                 assert!(polynomials.len() == 1);
                 let (name, ty) =
-                    self.name_and_type_from_polynomial_name(polynomials.pop().unwrap());
+                    self.name_and_type_from_polynomial_name(polynomials.pop().unwrap())?;
 
                 self.handle_symbol_definition(
                     source,
@@ -151,6 +152,15 @@ where
             PilStatement::LetStatement(source, name, type_scheme, value) => {
                 self.handle_generic_definition(source, name, type_scheme, value)
             }
+            PilStatement::LabeledIdentity(_, label, identity) => {
+                let mut items = self.handle_identity_statement(*identity)?;
+                for item in &mut items {
+                    if let PILItem::Identity(identity) = item {
+                        identity.label = Some(label.clone());
+                    }
+                }
+                Ok(items)
+            }
             _ => self.handle_identity_statement(statement),
         }
     }
@@ -158,28 +168,23 @@ where
     fn name_and_type_from_polynomial_name(
         &mut self,
         PolynomialName { name, array_size }: PolynomialName,
-    ) -> (String, Option<Type>) {
+    ) -> Result<(String, Option<Type>), String> {
         let ty = Some(match array_size {
             None => Type::Col,
             Some(len) => {
-                let length = self
-                    .evaluate_expression_to_int(len)
-                    .map(|length| {
-                        length
-                            .try_into()
-                            .unwrap_or_else(|_| panic!("Array length too large."))
-                    })
-                    .map_err(|e| {
-                        panic!("Error evaluating length of array of witness columns {name}:\n{e}")
-                    })
-                    .ok();
+                let length = self.evaluate_expression_to_int(len).map_err(|e| {
+                    format!("Error evaluating length of array of witness columns {name}:\n{e}")
+                })?;
+                let length = u64::try_from(&length).map_err(|_| {
+                    format!("Length of array of witness columns {name} has to be a non-negative integer that fits u64, but it is {length}.")
+                })?;
                 Type::Array(ArrayType {
                     base: Box::new(Type::Col),
-                    length,
+                    length: Some(length),
                 })
             }
         });
-        (name, ty)
+        Ok((name, ty))
     }
 
     fn handle_generic_definition(
@@ -188,17 +193,16 @@ where
         name: String,
         type_scheme: Option<TypeScheme<parsed::Expression>>,
         value: Option<parsed::Expression>,
-    ) -> Vec<PILItem> {
+    ) -> Result<Vec<PILItem>, String> {
         let type_scheme = type_scheme.map(|ts| {
             let vars = ts.vars;
             let duplicates = vars.vars().duplicates().collect::<Vec<_>>();
             if !duplicates.is_empty() {
-                panic!("Duplicate type variables in declaration of \"{name}\":\n{}", duplicates.iter().format(", "));
+                return Err(format!("Duplicate type variables in declaration of \"{name}\":\n{}", duplicates.iter().format(", ")));
             }
 
             let ty = self.resolve_type_name(ts.ty.clone())
-                .map_err(|e| panic!("Error evaluating expressions in type name \"{}\" to reduce it to a type:\n{e})", ts.ty))
-                .unwrap();
+                .map_err(|e| format!("Error evaluating expressions in type name \"{}\" to reduce it to a type:\n{e})", ts.ty))?;
             let contained_type_vars = ty.contained_type_vars().collect::<HashSet<_>>();
             let declared_type_vars = vars.vars().collect::<HashSet<_>>();
             if contained_type_vars != declared_type_vars {
@@ -209,30 +213,33 @@ where
                 ).iter().chain((!excess_contained.is_empty()).then(||
                     format!("Excess type variables in type: {excess_contained}")
                 ).iter()).format("\n").to_string();
-                panic!("Set of declared and used type variables are not the same in declaration:\nlet<{vars}> {name}: {ty}\n{details}");
+                return Err(format!("Set of declared and used type variables are not the same in declaration:\nlet<{vars}> {name}: {ty}\n{details}"));
             };
-            TypeScheme{vars, ty}
-        });
+            Ok(TypeScheme{vars, ty})
+        }).transpose()?;
 
         match value {
             None => {
                 // No value provided => treat it as a witness column.
                 let ty = type_scheme
                     .map(|ts| {
-                        assert!(ts.vars.is_empty());
+                        if !ts.vars.is_empty() {
+                            return Err(format!("Symbol {name} is declared without value and thus must be a witness column, but it has type variables."));
+                        }
                         let ty = ts.ty;
                         if let Type::Array(ArrayType { base, length }) = &ty {
                             if base.as_ref() != &Type::Col {
-                                panic!("Symbol {name} is declared without value and thus must be a witness column array, but its type is {ty} instead of col[].");
+                                return Err(format!("Symbol {name} is declared without value and thus must be a witness column array, but its type is {ty} instead of col[]."));
                             }
                             if length.is_none() {
-                                panic!("Explicit array length required for column {name}: {ty}");
+                                return Err(format!("Explicit array length required for column {name}: {ty}"));
                             }
                         } else if ty != Type::Col {
-                            panic!("Symbol {name} is declared without value and thus must be a witness column, but its type is {ty} instead of col.");
+                            return Err(format!("Symbol {name} is declared without value and thus must be a witness column, but its type is {ty} instead of col."));
                         }
-                        ty
+                        Ok(ty)
                     })
+                    .transpose()?
                     .unwrap_or(Type::Col);
                 self.handle_symbol_definition(
                     source,
@@ -279,13 +286,16 @@ where
         }
     }
 
-    fn handle_identity_statement(&mut self, statement: PilStatement) -> Vec<PILItem> {
+    fn handle_identity_statement(
+        &mut self,
+        statement: PilStatement,
+    ) -> Result<Vec<PILItem>, String> {
         let (source, kind, left, right) = match statement {
             PilStatement::Expression(source, expression) => (
                 source,
                 IdentityKind::Polynomial,
                 SelectedExpressions {
-                    selector: Some(self.process_expression(expression)),
+                    selector: Some(self.process_expression(expression)?),
                     expressions: vec![],
                 },
                 SelectedExpressions::default(),
@@ -293,25 +303,25 @@ where
             PilStatement::PlookupIdentity(source, key, haystack) => (
                 source,
                 IdentityKind::Plookup,
-                self.process_selected_expressions(key),
-                self.process_selected_expressions(haystack),
+                self.process_selected_expressions(key)?,
+                self.process_selected_expressions(haystack)?,
             ),
             PilStatement::PermutationIdentity(source, left, right) => (
                 source,
                 IdentityKind::Permutation,
-                self.process_selected_expressions(left),
-                self.process_selected_expressions(right),
+                self.process_selected_expressions(left)?,
+                self.process_selected_expressions(right)?,
             ),
             PilStatement::ConnectIdentity(source, left, right) => (
                 source,
                 IdentityKind::Connect,
                 SelectedExpressions {
                     selector: None,
-                    expressions: self.expression_processor().process_expressions(left),
+                    expressions: self.expression_processor().process_expressions(left)?,
                 },
                 SelectedExpressions {
                     selector: None,
-                    expressions: self.expression_processor().process_expressions(right),
+                    expressions: self.expression_processor().process_expressions(right)?,
                 },
             ),
             // TODO at some point, these should all be caught by the type checker.
@@ -320,13 +330,14 @@ where
             }
         };
 
-        vec![PILItem::Identity(Identity {
+        Ok(vec![PILItem::Identity(Identity {
             id: self.counters.dispense_identity_id(kind),
             kind,
             source,
+            label: None,
             left,
             right,
-        })]
+        })])
     }
 
     fn handle_polynomial_declarations(
@@ -334,20 +345,19 @@ where
         source: SourceRef,
         polynomials: Vec<PolynomialName>,
         polynomial_type: PolynomialType,
-    ) -> Vec<PILItem> {
-        polynomials
-            .into_iter()
-            .flat_map(|poly_name| {
-                let (name, ty) = self.name_and_type_from_polynomial_name(poly_name);
-                self.handle_symbol_definition(
-                    source.clone(),
-                    name,
-                    SymbolKind::Poly(polynomial_type),
-                    ty.map(Into::into),
-                    None,
-                )
-            })
-            .collect()
+    ) -> Result<Vec<PILItem>, String> {
+        let mut items = vec![];
+        for poly_name in polynomials {
+            let (name, ty) = self.name_and_type_from_polynomial_name(poly_name)?;
+            items.extend(self.handle_symbol_definition(
+                source.clone(),
+                name,
+                SymbolKind::Poly(polynomial_type),
+                ty.map(Into::into),
+                None,
+            )?);
+        }
+        Ok(items)
     }
 
     fn handle_symbol_definition(
@@ -357,19 +367,18 @@ where
         symbol_kind: SymbolKind,
         type_scheme: Option<TypeScheme>,
         value: Option<FunctionDefinition>,
-    ) -> Vec<PILItem> {
-        let length = type_scheme.as_ref().and_then(|t| {
-            if symbol_kind == SymbolKind::Other() {
-                None
-            } else if let Type::Array(ArrayType { length, base: _ }) = t.ty {
-                if length.is_none() && symbol_kind != SymbolKind::Other() {
-                    panic!("Explicit array length required for column {name}.");
+    ) -> Result<Vec<PILItem>, String> {
+        let length = match type_scheme.as_ref().map(|t| &t.ty) {
+            Some(Type::Array(ArrayType { length, base: _ }))
+                if symbol_kind != SymbolKind::Other() =>
+            {
+                if length.is_none() {
+                    return Err(format!("Explicit array length required for column {name}."));
                 }
-                length
-            } else {
-                None
+                *length
             }
-        });
+            _ => None,
+        };
         let id = self.counters.dispense_symbol_id(symbol_kind, length);
         let name = self.driver.resolve_decl(&name);
         let symbol = Symbol {
@@ -380,33 +389,97 @@ where
             length,
         };
 
-        let value = value.map(|v| match v {
-            FunctionDefinition::Expression(expr) => {
-                assert!(symbol_kind != SymbolKind::Poly(PolynomialType::Committed));
-                FunctionValueDefinition::Expression(TypedExpression {
-                    e: self.process_expression(expr),
-                    type_scheme,
+        let value = value
+            .map(|v| {
+                Ok(match v {
+                    FunctionDefinition::Expression(expr) => {
+                        if symbol_kind == SymbolKind::Poly(PolynomialType::Committed) {
+                            return Err(format!(
+                                "Witness column {name} cannot be defined by an expression."
+                            ));
+                        }
+                        FunctionValueDefinition::Expression(TypedExpression {
+                            e: self.process_expression(expr)?,
+                            type_scheme,
+                        })
+                    }
+                    FunctionDefinition::Query(expr) => {
+                        assert_eq!(symbol_kind, SymbolKind::Poly(PolynomialType::Committed));
+                        if type_scheme.is_some() && type_scheme != Some(Type::Col.into()) {
+                            return Err(format!("Query column {name} has to be a single column."));
+                        }
+                        FunctionValueDefinition::Query(self.process_expression(expr)?)
+                    }
+                    FunctionDefinition::Array(value) => {
+                        let degree = self.degree.ok_or_else(|| {
+                            format!("Fixed column {name} is defined by an array, which requires the degree of a namespace.")
+                        })?;
+                        let size = value.solve(degree)?;
+                        let expression = self
+                            .expression_processor()
+                            .process_array_expression(value, size)?;
+                        let rows = expression.iter().map(|e| e.size()).sum::<DegreeType>();
+                        if rows != degree {
+                            return Err(format!(
+                                "Fixed column {name} has {rows} rows, but the degree is {degree}."
+                            ));
+                        }
+                        if type_scheme.is_some() && type_scheme != Some(Type::Col.into()) {
+                            return Err(format!(
+                                "Fixed column {name} defined by an array has to be a single column."
+                            ));
+                        }
+                        FunctionValueDefinition::Array(expression)
+                    }
+                    FunctionDefinition::Sparse(sparse) => {
+                        let degree = self.degree.ok_or_else(|| {
+                            format!("Fixed column {name} is defined by a sparse array, which requires the degree of a namespace.")
+                        })?;
+                        if type_scheme.is_some() && type_scheme != Some(Type::Col.into()) {
+                            return Err(format!(
+                                "Fixed column {name} defined by a sparse array has to be a single column."
+                            ));
+                        }
+                        FunctionValueDefinition::Sparse(
+                            self.process_sparse_array(&name, sparse, degree)?,
+                        )
+                    }
                 })
-            }
-            FunctionDefinition::Query(expr) => {
-                assert_eq!(symbol_kind, SymbolKind::Poly(PolynomialType::Committed));
-                assert!(type_scheme.is_none() || type_scheme == Some(Type::Col.into()));
-                FunctionValueDefinition::Query(self.process_expression(expr))
-            }
-            FunctionDefinition::Array(value) => {
-                let size = value.solve(self.degree.unwrap());
-                let expression = self
-                    .expression_processor()
-                    .process_array_expression(value, size);
-                assert_eq!(
-                    expression.iter().map(|e| e.size()).sum::<DegreeType>(),
-                    self.degree.unwrap()
-                );
-                assert!(type_scheme.is_none() || type_scheme == Some(Type::Col.into()));
-                FunctionValueDefinition::Array(expression)
-            }
-        });
-        vec![PILItem::Definition(symbol, value)]
+            })
+            .transpose()?;
+        Ok(vec![PILItem::Definition(symbol, value)])
+    }
+
+    /// Evaluates the rows of a sparse array and checks that they are distinct
+    /// and smaller than the degree.
+    fn process_sparse_array(
+        &self,
+        name: &str,
+        sparse: parsed::SparseArrayExpression,
+        degree: DegreeType,
+    ) -> Result<SparseArray, String> {
+        let mut rows = HashSet::new();
+        let entries = sparse
+            .entries
+            .into_iter()
+            .map(|(row, value)| {
+                let row = self.evaluate_expression_to_int(row)?;
+                let row = DegreeType::try_from(&row)
+                    .ok()
+                    .filter(|row| *row < degree)
+                    .ok_or_else(|| {
+                        format!("Row {row} of fixed column {name} is out of bounds, the degree is {degree}.")
+                    })?;
+                if !rows.insert(row) {
+                    return Err(format!(
+                        "Row {row} of fixed column {name} is defined more than once."
+                    ));
+                }
+                Ok((row, self.process_expression(value)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let default = self.process_expression(sparse.default)?;
+        Ok(SparseArray::new(entries, default, degree))
     }
 
     fn handle_public_declaration(
@@ -416,72 +489,75 @@ where
         poly: parsed::NamespacedPolynomialReference,
         array_index: Option<parsed::Expression>,
         index: parsed::Expression,
-    ) -> Vec<PILItem> {
+    ) -> Result<Vec<PILItem>, String> {
         let id = self.counters.dispense_public_id();
+        let name = self.driver.resolve_decl(&name);
         let polynomial = self
             .expression_processor()
-            .process_namespaced_polynomial_reference(&poly.path);
-        let array_index = array_index.map(|i| {
-            let index: u64 = self
-                .evaluate_expression_to_int(i)
-                .unwrap()
-                .try_into()
-                .unwrap();
-            assert!(index <= usize::MAX as u64);
-            index as usize
-        });
-        vec![PILItem::PublicDeclaration(PublicDeclaration {
+            .process_namespaced_polynomial_reference(&poly.path)?;
+        let array_index = array_index
+            .map(|i| {
+                let index = self.evaluate_expression_to_int(i)?;
+                usize::try_from(&index).map_err(|_| {
+                    format!("Array index of public declaration {name} has to be a non-negative integer, but it is {index}.")
+                })
+            })
+            .transpose()?;
+        let index = self.evaluate_expression_to_int(index)?;
+        let index = u64::try_from(&index).map_err(|_| {
+            format!("Row of public declaration {name} has to be a non-negative integer that fits u64, but it is {index}.")
+        })?;
+        Ok(vec![PILItem::PublicDeclaration(PublicDeclaration {
             id,
             source,
-            name: name.to_string(),
+            name,
             polynomial,
             array_index,
-            index: self
-                .evaluate_expression_to_int(index)
-                .unwrap()
-                .try_into()
-                .unwrap(),
-        })]
+            index,
+        })])
     }
 
     /// Resolves a type name into a concrete type.
     /// This routine mainly evaluates array length expressions.
-    fn resolve_type_name(&self, mut n: Type<parsed::Expression>) -> Result<Type, EvalError> {
+    fn resolve_type_name(&self, mut n: Type<parsed::Expression>) -> Result<Type, String> {
         // Replace all expressions by number literals.
         // Any expression inside a type name has to be an array length,
         // so we expect an integer that fits u64.
         for e in n.expressions_mut() {
             let v = self.evaluate_expression_to_int(e.clone())?;
-            let v_u64: u64 = v.clone().try_into().map_err(|_| {
-                EvalError::TypeError(format!("Number too large, expected u64, but got {v}"))
-            })?;
-            *e = parsed::Expression::Number(v_u64.into(), None);
+            let v_u64: u64 = v
+                .clone()
+                .try_into()
+                .map_err(|_| format!("Number too large, expected u64, but got {v}"))?;
+            *e = parsed::Expression::Number(v_u64.into(), None, parsed::Radix::Decimal);
         }
         Ok(n.into())
     }
 
-    fn evaluate_expression_to_int(&self, expr: parsed::Expression) -> Result<BigInt, EvalError> {
+    /// Evaluates the expression, which can only reference symbols defined before, to an integer.
+    fn evaluate_expression_to_int(&self, expr: parsed::Expression) -> Result<BigInt, String> {
         // TODO we should maybe implement a separate evaluator that is able to run before type checking
         // and is field-independent (only uses integers)?
         evaluator::evaluate_expression::<GoldilocksField>(
-            &ExpressionProcessor::new(self.driver).process_expression(expr),
+            &ExpressionProcessor::new(self.driver).process_expression(expr)?,
             self.driver.definitions(),
-        )?
-        .try_to_integer()
+        )
+        .and_then(|v| v.try_to_integer())
+        .map_err(|e| e.to_string())
     }
 
     fn expression_processor(&self) -> ExpressionProcessor<D> {
         ExpressionProcessor::new(self.driver)
     }
 
-    fn process_expression(&self, expr: parsed::Expression) -> Expression {
+    fn process_expression(&self, expr: parsed::Expression) -> Result<Expression, String> {
         self.expression_processor().process_expression(expr)
     }
 
     fn process_selected_expressions(
         &self,
         expr: parsed::SelectedExpressions<parsed::Expression>,
-    ) -> SelectedExpressions<Expression> {
+    ) -> Result<SelectedExpressions<Expression>, String> {
         self.expression_processor()
             .process_selected_expressions(expr)
     }
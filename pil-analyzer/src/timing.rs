@@ -0,0 +1,93 @@
+use std::fmt::{self, Display};
+use std::time::{Duration, Instant};
+
+/// The wall time spent in a phase of the analysis, with the times of its sub-phases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub duration: Duration,
+    /// The sub-phases in the order in which they were run.
+    pub phases: Vec<PhaseTiming>,
+}
+
+impl PhaseTiming {
+    /// Returns the first phase with the given name, searching this phase and
+    /// all its sub-phases in depth-first order.
+    pub fn find(&self, name: &str) -> Option<&PhaseTiming> {
+        if self.name == name {
+            Some(self)
+        } else {
+            self.phases.iter().find_map(|phase| phase.find(name))
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{}{}: {:.3} ms",
+            "  ".repeat(depth),
+            self.name,
+            self.duration.as_secs_f64() * 1000.0
+        )?;
+        self.phases
+            .iter()
+            .try_for_each(|phase| phase.fmt_indented(f, depth + 1))
+    }
+}
+
+impl Display for PhaseTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Records the phases of the analysis. Phases started while another phase
+/// is running become its sub-phases.
+pub(crate) struct Timer {
+    /// The phases that are currently running, outermost first.
+    running: Vec<(Instant, PhaseTiming)>,
+}
+
+impl Timer {
+    pub fn new(name: &str) -> Self {
+        let mut timer = Timer { running: vec![] };
+        timer.start(name);
+        timer
+    }
+
+    pub fn start(&mut self, name: &str) {
+        self.running.push((
+            Instant::now(),
+            PhaseTiming {
+                name: name.to_string(),
+                duration: Duration::ZERO,
+                phases: vec![],
+            },
+        ));
+    }
+
+    /// Ends the innermost running phase.
+    pub fn end(&mut self) {
+        assert!(self.running.len() > 1, "No phase to end.");
+        let phase = self.pop();
+        self.running.last_mut().unwrap().1.phases.push(phase);
+    }
+
+    /// Ends all running phases, logs the timings and returns them.
+    pub fn finish(mut self) -> PhaseTiming {
+        while self.running.len() > 1 {
+            self.end();
+        }
+        let timing = self.pop();
+        for line in timing.to_string().lines() {
+            log::debug!("{line}");
+        }
+        timing
+    }
+
+    fn pop(&mut self) -> PhaseTiming {
+        let (start, mut phase) = self.running.pop().unwrap();
+        phase.duration = start.elapsed();
+        phase
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display};
 
 use powdr_ast::{
     parsed::types::{ArrayType, Type, TypeScheme},
@@ -34,15 +34,37 @@ pub fn type_for_reference(declared: &Type) -> Type {
 
 lazy_static! {
     static ref BUILTIN_SCHEMES: HashMap<String, TypeScheme> = [
+        (
+            "std::array::fold",
+            ("T1, T2", "T1[], T2, (T2, T1 -> T2) -> T2")
+        ),
         ("std::array::len", ("T", "T[] -> int")),
+        ("std::array::map", ("T1, T2", "T1[], (T1 -> T2) -> T2[]")),
+        ("std::array::new", ("T", "int, (int -> T) -> T[]")),
+        ("std::array::sum", ("T: Add + FromLiteral", "T[] -> T")),
         ("std::check::panic", ("", "string -> !")),
+        (
+            "std::constraints::lookup",
+            ("", "expr, expr[], expr, expr[] -> constr"),
+        ),
+        (
+            "std::constraints::permutation",
+            ("", "expr, expr[], expr, expr[] -> constr"),
+        ),
         ("std::convert::expr", ("T: FromLiteral", "T -> expr")),
         ("std::convert::fe", ("T: FromLiteral", "T -> fe")),
         ("std::convert::int", ("T: FromLiteral", "T -> int")),
+        ("std::convert::to_string", ("T", "T -> string")),
         ("std::convert::expr", ("T: FromLiteral", "T -> expr")),
-        ("std::debug::print", ("", "string -> constr[]")),
+        ("std::debug::print", ("T", "T -> constr[]")),
         ("std::field::modulus", ("", "-> int")),
         ("std::prover::eval", ("", "expr -> fe")),
+        ("std::prover::new_witness_col", ("", "string -> expr")),
+        ("std::string::len", ("", "string -> int")),
+        ("std::utils::from_bits", ("", "int[] -> int")),
+        ("std::utils::from_bytes", ("", "int[] -> int")),
+        ("std::utils::to_bits", ("", "int, int -> int[]")),
+        ("std::utils::to_bytes", ("", "int, int -> int[]")),
     ]
     .into_iter()
     .map(|(name, (vars, ty))| {
@@ -116,6 +138,40 @@ pub fn unary_operator_scheme(op: UnaryOperator) -> TypeScheme {
     UNARY_OPERATOR_SCHEMES[&op].clone()
 }
 
+/// Returns an error message with a conversion hint if the operands of `op`
+/// are an `int` and an `fe` and the operator cannot be applied to them.
+pub fn int_fe_mixing_error(
+    left: impl Display,
+    left_type: &Type,
+    op: BinaryOperator,
+    right: impl Display,
+    right_type: &Type,
+) -> Option<String> {
+    let hint = match (left_type, op, right_type) {
+        (Type::Fe, BinaryOperator::Pow, Type::Int) => return None,
+        (Type::Int, BinaryOperator::Pow, Type::Fe) => {
+            "wrap the exponent with std::convert::int(...)"
+        }
+        (Type::Int, _, Type::Fe) | (Type::Fe, _, Type::Int) => match op {
+            BinaryOperator::Div
+            | BinaryOperator::Mod
+            | BinaryOperator::ShiftLeft
+            | BinaryOperator::ShiftRight
+            | BinaryOperator::BinaryAnd
+            | BinaryOperator::BinaryOr
+            | BinaryOperator::BinaryXor => "wrap the fe operand with std::convert::int(...)",
+            _ => "wrap the int operand with std::convert::fe(...) or the fe operand with std::convert::int(...)",
+        },
+        _ => return None,
+    };
+    Some(format!(
+        "Operator {op} cannot be applied to an int and an fe: {left} {op} {right}\n\
+        Left operand type: {left_type}\n\
+        Right operand type: {right_type}\n\
+        Hint: {hint}."
+    ))
+}
+
 pub fn elementary_type_bounds(ty: &Type) -> &'static [&'static str] {
     match ty {
         Type::Bottom => &[],
@@ -141,7 +197,7 @@ pub fn elementary_type_bounds(ty: &Type) -> &'static [&'static str] {
             "Neg",
             "Eq",
         ],
-        Type::String => &["Add"],
+        Type::String => &["Add", "Ord", "Eq"],
         Type::Expr => &[
             "FromLiteral",
             "Add",
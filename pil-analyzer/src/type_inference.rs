@@ -4,17 +4,20 @@ use itertools::Itertools;
 use powdr_ast::{
     analyzed::{Expression, PolynomialReference, Reference},
     parsed::{
+        asm::AbsoluteSymbolPath,
         display::format_type_scheme_around_name,
         types::{ArrayType, FunctionType, TupleType, Type, TypeBounds, TypeScheme},
         visitor::ExpressionVisitable,
-        ArrayLiteral, FunctionCall, IndexAccess, LambdaExpression, MatchArm, MatchPattern,
+        ArrayLiteral, BlockExpression, FunctionCall, IndexAccess, LambdaExpression,
+        LetStatementInsideBlock, LiteralType, MatchArm, MatchPattern,
     },
 };
 
 use crate::{
     call_graph::sort_called_first,
     type_builtins::{
-        binary_operator_scheme, builtin_schemes, type_for_reference, unary_operator_scheme,
+        binary_operator_scheme, builtin_schemes, int_fe_mixing_error, type_for_reference,
+        unary_operator_scheme,
     },
     type_unifier::Unifier,
 };
@@ -24,9 +27,9 @@ use crate::{
 /// Sets the generic arguments for references and the literal types in all expressions.
 /// Returns the types for symbols without explicit type.
 pub fn infer_types(
-    definitions: HashMap<String, (Option<TypeScheme>, Option<&mut Expression>)>,
+    definitions: HashMap<AbsoluteSymbolPath, (Option<TypeScheme>, Option<&mut Expression>)>,
     expressions: &mut [(&mut Expression, ExpectedType)],
-) -> Result<Vec<(String, Type)>, String> {
+) -> Result<Vec<(AbsoluteSymbolPath, Type)>, String> {
     TypeChecker::default().infer_types(definitions, expressions)
 }
 
@@ -52,7 +55,7 @@ struct TypeChecker {
     local_var_types: Vec<Type>,
     /// Declared types for all symbols. Contains the unmodified type scheme for symbols
     /// with generic types and newly created type variables for symbols without declared type.
-    declared_types: HashMap<String, TypeScheme>,
+    declared_types: HashMap<AbsoluteSymbolPath, TypeScheme>,
     unifier: Unifier,
     /// Last used type variable index.
     last_type_var: usize,
@@ -63,9 +66,9 @@ impl TypeChecker {
     /// returns the types for symbols without explicit type.
     pub fn infer_types(
         mut self,
-        mut definitions: HashMap<String, (Option<TypeScheme>, Option<&mut Expression>)>,
+        mut definitions: HashMap<AbsoluteSymbolPath, (Option<TypeScheme>, Option<&mut Expression>)>,
         expressions: &mut [(&mut Expression, ExpectedType)],
-    ) -> Result<Vec<(String, Type)>, String> {
+    ) -> Result<Vec<(AbsoluteSymbolPath, Type)>, String> {
         let type_var_mapping = self.infer_types_inner(&mut definitions, expressions)?;
         self.update_generic_args(&mut definitions, expressions, &type_var_mapping)?;
         Ok(definitions
@@ -85,27 +88,31 @@ impl TypeChecker {
     /// the type variables used by the type checker to those used in the declaration.
     fn infer_types_inner(
         &mut self,
-        definitions: &mut HashMap<String, (Option<TypeScheme>, Option<&mut Expression>)>,
+        definitions: &mut HashMap<
+            AbsoluteSymbolPath,
+            (Option<TypeScheme>, Option<&mut Expression>),
+        >,
         expressions: &mut [(&mut Expression, ExpectedType)],
-    ) -> Result<HashMap<String, HashMap<String, Type>>, String> {
+    ) -> Result<HashMap<AbsoluteSymbolPath, HashMap<String, Type>>, String> {
         // TODO in order to fix type inference on recursive functions, we need to:
         // - collect all groups of functions that call each other recursively
         // - analyze each such group in an environment, where their type schemes
         //   are instantiated once at the start and not anymore for the symbol lookup.
 
         // Sort the names such that called names occur first.
-        let names = sort_called_first(
-            definitions
-                .iter()
-                .map(|(n, (_, v))| (n.as_str(), v.as_deref())),
-        );
+        // The call graph uses the names as they appear in references.
+        let symbols = definitions
+            .iter()
+            .map(|(n, (_, v))| (n.to_dotted_string(), v.as_deref()))
+            .collect::<Vec<_>>();
+        let names = sort_called_first(symbols.iter().map(|(n, v)| (n.as_str(), *v)));
 
         self.setup_declared_types(definitions);
 
         // These are the inferred types for symbols that are declared
         // as type schemes. They are compared to the declared types
         // at the end.
-        let mut inferred_types: HashMap<String, Type> = Default::default();
+        let mut inferred_types: HashMap<AbsoluteSymbolPath, Type> = Default::default();
 
         // Now go through all symbols and derive types for the expressions.
         // While analyzing a symbol, we ignore its declared type (unless the
@@ -113,16 +120,17 @@ impl TypeChecker {
         // type is done at the end.
         for name in names {
             // Ignore builtins (removed from definitions) and definitions without value.
-            let Some((_, Some(value))) = definitions.get_mut(&name) else {
+            let path = AbsoluteSymbolPath::from_dotted_string(&name);
+            let Some((_, Some(value))) = definitions.get_mut(&path) else {
                 continue;
             };
 
-            let declared_type = self.declared_types[&name].clone();
+            let declared_type = self.declared_types[&path].clone();
             let result = if declared_type.vars.is_empty() {
                 self.process_concrete_symbol(&name, declared_type.ty.clone(), value)
             } else {
                 self.infer_type_of_expression(value).map(|ty| {
-                    inferred_types.insert(name.to_string(), ty);
+                    inferred_types.insert(path, ty);
                 })
             };
             if let Err(e) = result {
@@ -144,10 +152,11 @@ impl TypeChecker {
                 let inferred = self.type_into_substituted(declared_type.ty.clone());
                 if !inferred.is_concrete_type() {
                     let inferred_scheme = self.to_type_scheme(inferred);
+                    let name = name.to_dotted_string();
                     return Err(format!(
                         "Could not derive a concrete type for symbol {name}.\nInferred type scheme: {}\n",
                         format_type_scheme_around_name(
-                            name,
+                            &name,
                             &Some(inferred_scheme),
                         )
                     ));
@@ -166,11 +175,13 @@ impl TypeChecker {
     /// Fills self.declared_types and checks and removes builtins from the definitions.
     fn setup_declared_types<T>(
         &mut self,
-        definitions: &mut HashMap<String, (Option<TypeScheme>, T)>,
+        definitions: &mut HashMap<AbsoluteSymbolPath, (Option<TypeScheme>, T)>,
     ) {
         // Remove builtins from definitions and check their types are correct.
         for (name, ty) in builtin_schemes() {
-            if let Some((_, (Some(defined_ty), _))) = definitions.remove_entry(name) {
+            if let Some((_, (Some(defined_ty), _))) =
+                definitions.remove_entry(&AbsoluteSymbolPath::from_dotted_string(name))
+            {
                 assert!(
                     ty == &defined_ty,
                     "Invalid type for built-in scheme {name}: {}",
@@ -179,7 +190,10 @@ impl TypeChecker {
             }
         }
 
-        self.declared_types = builtin_schemes().clone();
+        self.declared_types = builtin_schemes()
+            .iter()
+            .map(|(name, ty)| (AbsoluteSymbolPath::from_dotted_string(name), ty.clone()))
+            .collect();
         // Add types from declarations. Type schemes are added without instantiating.
         for (name, (type_scheme, _)) in definitions.iter() {
             // This stores an (uninstantiated) type scheme for symbols with a declared
@@ -279,9 +293,12 @@ impl TypeChecker {
     /// the type variable names used by the type checker to those from the declaration.
     fn update_generic_args(
         &mut self,
-        definitions: &mut HashMap<String, (Option<TypeScheme>, Option<&mut Expression>)>,
+        definitions: &mut HashMap<
+            AbsoluteSymbolPath,
+            (Option<TypeScheme>, Option<&mut Expression>),
+        >,
         expressions: &mut [(&mut Expression, ExpectedType)],
-        type_var_mapping: &HashMap<String, HashMap<String, Type>>,
+        type_var_mapping: &HashMap<AbsoluteSymbolPath, HashMap<String, Type>>,
     ) -> Result<(), String> {
         let mut errors = vec![];
         definitions
@@ -295,7 +312,8 @@ impl TypeChecker {
                         // TODO cannot borrow the value here for printing it.
                         // We should fix this properly by using source references.
                         errors.push(format!(
-                            "Error specializing generic references in {name}:\n{e}",
+                            "Error specializing generic references in {}:\n{e}",
+                            name.to_dotted_string()
                         ))
                     }
                 });
@@ -327,9 +345,10 @@ impl TypeChecker {
         type_var_mapping: &HashMap<String, Type>,
     ) -> Result<(), String> {
         match e {
-            Expression::Number(n, annotated_type) => match annotated_type {
-                Some(Type::Int) | Some(Type::Fe) | Some(Type::Expr) => {}
-                Some(Type::TypeVar(tv)) => {
+            Expression::Number(n, annotated_type, _) => match annotated_type {
+                Some(LiteralType::Explicit(_))
+                | Some(LiteralType::Inferred(Type::Int | Type::Fe | Type::Expr)) => {}
+                Some(LiteralType::Inferred(Type::TypeVar(tv))) => {
                     let mut ty = Type::TypeVar(tv.clone());
                     // Apply regular substitution obtained from unification.
                     self.substitute(&mut ty);
@@ -341,15 +360,11 @@ impl TypeChecker {
                     }
                     // Rename type vars (hopefully just a single one) to match the declaration scheme.
                     ty.substitute_type_vars(type_var_mapping);
-                    if let Type::TypeVar(tv) = ty {
-                        *annotated_type = Some(Type::TypeVar(tv.clone()));
-                    } else {
-                        match ty {
-                            Type::Int => *annotated_type = Some(Type::Int),
-                            Type::Fe => *annotated_type = Some(Type::Fe),
-                            Type::Expr => *annotated_type = Some(Type::Expr),
-                            t => panic!("Invalid resolved type literal number: {t}"),
+                    match ty {
+                        Type::TypeVar(_) | Type::Int | Type::Fe | Type::Expr => {
+                            *annotated_type = Some(LiteralType::Inferred(ty))
                         }
+                        t => panic!("Invalid resolved type literal number: {t}"),
                     }
                 }
                 _ => panic!("Invalid annotation for literal number."),
@@ -433,13 +448,13 @@ impl TypeChecker {
             })) => {
                 // The generic args (some of them) could be pre-filled by the parser, but we do not yet support that.
                 assert!(generic_args.is_none());
-                let (ty, gen_args) = self.instantiate_scheme(self.declared_types[name].clone());
+                let (ty, gen_args) = self.instantiate_scheme(self.declared_type(name).clone());
                 *generic_args = Some(gen_args);
                 type_for_reference(&ty)
             }
             Expression::PublicReference(_) => Type::Expr,
-            Expression::Number(_, annotated_type) => {
-                let ty = match annotated_type {
+            Expression::Number(_, annotated_type, _) => {
+                let ty = match annotated_type.as_ref().map(LiteralType::ty) {
                     Some(Type::Int) => Type::Int,
                     Some(Type::Fe) => Type::Fe,
                     Some(Type::Expr) => Type::Expr,
@@ -447,7 +462,7 @@ impl TypeChecker {
                     Some(t) => panic!("Type name annotation for number is not supported: {t}"),
                     None => {
                         let tv = self.new_type_var_name();
-                        *annotated_type = Some(Type::TypeVar(tv.clone()));
+                        *annotated_type = Some(LiteralType::Inferred(Type::TypeVar(tv.clone())));
                         Type::TypeVar(tv)
                     }
                 };
@@ -489,11 +504,37 @@ impl TypeChecker {
             Expression::BinaryOperation(left, op, right) => {
                 // TODO at some point, also store the generic args for operators
                 let fun_type = self.instantiate_scheme(binary_operator_scheme(*op)).0;
-                self.infer_type_of_function_call(
-                    fun_type,
-                    [left, right].into_iter().map(AsMut::as_mut),
-                    || format!("applying operator {op}"),
-                )?
+                let left_type = self.infer_type_of_expression(left)?;
+                let right_type = self.infer_type_of_expression(right)?;
+                let Type::Function(FunctionType { params, value }) = fun_type else {
+                    unreachable!()
+                };
+                for (arg, (param, arg_type)) in [&left, &right]
+                    .into_iter()
+                    .zip(params.into_iter().zip([&left_type, &right_type]))
+                {
+                    self.unifier
+                        .unify_types(arg_type.clone(), param.clone())
+                        .map_err(|err| {
+                            // Mixing int and fe is a common mistake, so we provide
+                            // a hint about how to fix it.
+                            int_fe_mixing_error(
+                                &left,
+                                &self.type_into_substituted(left_type.clone()),
+                                *op,
+                                &right,
+                                &self.type_into_substituted(right_type.clone()),
+                            )
+                            .unwrap_or_else(|| {
+                                format!(
+                                    "Error checking sub-expression {arg}:\nExpected type: {}\nInferred type: {}\n{err}",
+                                    self.type_into_substituted(param),
+                                    self.type_into_substituted(arg_type.clone())
+                                )
+                            })
+                        })?;
+                }
+                *value
             }
             Expression::UnaryOperation(op, inner) => {
                 // TODO at some point, also store the generic args for operators
@@ -520,21 +561,57 @@ impl TypeChecker {
             Expression::FunctionCall(FunctionCall {
                 function,
                 arguments,
-            }) => {
-                let ft = self.infer_type_of_expression(function)?;
-                self.infer_type_of_function_call(ft, arguments.iter_mut(), || {
-                    format!("calling function {function}")
-                })?
-            }
+            }) => match function.as_mut() {
+                Expression::Reference(Reference::Poly(PolynomialReference {
+                    name,
+                    poly_id: _,
+                    generic_args,
+                })) if self.declared_type(name).ty == Type::Col => {
+                    // Applying a column to a row index evaluates it at that row,
+                    // which is only possible outside of constraints.
+                    assert!(generic_args.is_none());
+                    *generic_args = Some(vec![]);
+                    let [row] = &mut arguments[..] else {
+                        return Err(format!(
+                            "Column {name} has to be evaluated at a single row, but {} arguments were supplied.",
+                            arguments.len()
+                        ));
+                    };
+                    self.expect_type(&Type::Int, row).map_err(|err| {
+                        format!(
+                            "Columns can only be evaluated at a row of type int, which is not possible \
+                            inside constraints. Use the column {name} directly there instead.\n{err}"
+                        )
+                    })?;
+                    Type::Fe
+                }
+                _ => {
+                    let ft = self.infer_type_of_expression(function)?;
+                    self.infer_type_of_function_call(ft, arguments.iter_mut(), || {
+                        format!("calling function {function}")
+                    })?
+                }
+            },
             Expression::FreeInput(_) => todo!(),
             Expression::MatchExpression(scrutinee, arms) => {
                 let scrutinee_type = self.infer_type_of_expression(scrutinee)?;
                 let result = self.new_type_var();
-                for MatchArm { pattern, value } in arms {
-                    if let MatchPattern::Pattern(pattern) = pattern {
-                        self.expect_type(&scrutinee_type, pattern)?;
+                for (i, MatchArm { patterns, value }) in arms.iter_mut().enumerate() {
+                    for pattern in patterns {
+                        self.expect_pattern_type(&scrutinee_type, scrutinee, pattern)?;
                     }
-                    self.expect_type(&result, value)?;
+                    let previous_type = self.type_into_substituted(result.clone());
+                    self.expect_type(&result, value).map_err(|err| {
+                        if i == 0 {
+                            err
+                        } else {
+                            format!(
+                                "The arms of a match expression have to be of the same type, but arm {} \
+                                does not evaluate to {previous_type} like the previous arms:\n{err}",
+                                i + 1
+                            )
+                        }
+                    })?;
                 }
                 result
             }
@@ -544,9 +621,28 @@ impl TypeChecker {
                 self.expect_type(&result, &mut if_expr.else_body)?;
                 result
             }
+            Expression::BlockExpression(block) => {
+                let old_len = self.local_var_types.len();
+                let result = self.infer_type_of_block_expression(block);
+                self.local_var_types.truncate(old_len);
+                result?
+            }
         })
     }
 
+    /// Infers the types of the let statements in a block, adding them to the local variables,
+    /// and returns the type of the final expression.
+    fn infer_type_of_block_expression(
+        &mut self,
+        BlockExpression { statements, expr }: &mut BlockExpression<Reference>,
+    ) -> Result<Type, String> {
+        for LetStatementInsideBlock { value, .. } in statements {
+            let value_type = self.infer_type_of_expression(value)?;
+            self.local_var_types.push(value_type);
+        }
+        self.infer_type_of_expression(expr)
+    }
+
     /// Process a function call and return the type of the expression.
     /// The error message is used to clarify which kind of function call it is
     /// (it might be an operator).
@@ -585,18 +681,50 @@ impl TypeChecker {
         Ok(result_type)
     }
 
+    /// Checks that the pattern can match values of the type of the scrutinee.
+    fn expect_pattern_type(
+        &mut self,
+        scrutinee_type: &Type,
+        scrutinee: &Expression,
+        pattern: &mut MatchPattern<Reference>,
+    ) -> Result<(), String> {
+        match pattern {
+            MatchPattern::CatchAll => Ok(()),
+            MatchPattern::Pattern(pattern) => {
+                self.expect_type(scrutinee_type, pattern).map_err(|err| {
+                    format!(
+                        "The patterns of a match expression have to be of the same type \
+                        as the value {scrutinee} that is matched on:\n{err}"
+                    )
+                })
+            }
+            MatchPattern::Range(start, end, _) => {
+                self.unifier
+                    .unify_types(scrutinee_type.clone(), Type::Int)
+                    .map_err(|err| {
+                        format!(
+                            "Range patterns can only be used to match values of type int, \
+                            but {scrutinee} has type {}:\n{err}",
+                            self.type_into_substituted(scrutinee_type.clone())
+                        )
+                    })?;
+                self.expect_type(&Type::Int, start)?;
+                self.expect_type(&Type::Int, end)
+            }
+        }
+    }
+
     /// Process the expression and unify it with the given type.
     /// This function should be preferred over `infer_type_of_expression` if an expected type is known
     /// because we can create better error messages.
     fn expect_type(&mut self, expected_type: &Type, expr: &mut Expression) -> Result<(), String> {
         // For literals, we try to store the type here already.
         // This avoids creating tons of type variables for large arrays.
-        if let Expression::Number(_, annotated_type @ None) = expr {
+        if let Expression::Number(_, annotated_type @ None, _) = expr {
             match expected_type {
-                Type::Int => *annotated_type = Some(Type::Int),
-                Type::Fe => *annotated_type = Some(Type::Fe),
-                Type::Expr => *annotated_type = Some(Type::Expr),
-                Type::TypeVar(tv) => *annotated_type = Some(Type::TypeVar(tv.clone())),
+                Type::Int | Type::Fe | Type::Expr | Type::TypeVar(_) => {
+                    *annotated_type = Some(LiteralType::Inferred(expected_type.clone()))
+                }
                 _ => {}
             };
         }
@@ -604,6 +732,12 @@ impl TypeChecker {
         self.unifier
             .unify_types(inferred_type.clone(), expected_type.clone())
             .map_err(|err| {
+                if let Expression::Number(_, Some(LiteralType::Explicit(ty)), _) = &*expr {
+                    return format!(
+                        "Type suffix of literal {expr} does not match the context:\nExpected type: {}\nSuffix type: {ty}\n{err}",
+                        self.type_into_substituted(expected_type.clone()),
+                    );
+                }
                 format!(
                     "Error checking sub-expression {expr}:\nExpected type: {}\nInferred type: {}\n{err}",
                     self.type_into_substituted(expected_type.clone()),
@@ -616,10 +750,11 @@ impl TypeChecker {
     /// the type variables used by the type checker to those used in the declaration.
     fn verify_type_schemes(
         &self,
-        inferred_types: HashMap<String, Type>,
-    ) -> Result<HashMap<String, HashMap<String, Type>>, String> {
-        inferred_types.into_iter().map(|(name, inferred_type)| {
-            let declared_type = self.declared_types[&name].clone();
+        inferred_types: HashMap<AbsoluteSymbolPath, Type>,
+    ) -> Result<HashMap<AbsoluteSymbolPath, HashMap<String, Type>>, String> {
+        inferred_types.into_iter().map(|(path, inferred_type)| {
+            let declared_type = self.declared_types[&path].clone();
+            let name = path.to_dotted_string();
             let inferred_type = self.type_into_substituted(inferred_type.clone());
             let inferred = self.to_type_scheme(inferred_type.clone());
             let declared = declared_type.clone().simplify_type_vars();
@@ -632,7 +767,7 @@ impl TypeChecker {
             }
             let declared_type_vars = declared_type.ty.contained_type_vars();
             let inferred_type_vars = inferred_type.contained_type_vars();
-            Ok((name.clone(),
+            Ok((path,
                 inferred_type_vars
                     .into_iter()
                     .cloned()
@@ -642,6 +777,11 @@ impl TypeChecker {
         }).collect::<Result<_, String>>()
     }
 
+    /// Returns the declared type of the symbol referenced by `name`.
+    fn declared_type(&self, name: &str) -> &TypeScheme {
+        &self.declared_types[&AbsoluteSymbolPath::from_dotted_string(name)]
+    }
+
     fn type_into_substituted(&self, mut ty: Type) -> Type {
         self.substitute(&mut ty);
         ty
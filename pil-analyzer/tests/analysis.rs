@@ -0,0 +1,444 @@
+mod common;
+
+use common::errors;
+
+mod panic_free {
+    use itertools::Itertools;
+    use powdr_number::{BabyBearField, GoldilocksField};
+    use powdr_pil_analyzer::{try_analyze_string, AnalysisError};
+
+    use pretty_assertions::assert_eq;
+
+    use super::errors;
+
+    /// Malformed inputs, each of which has to be rejected without panicking.
+    const MALFORMED: &[&str] = &[
+        // Parse errors
+        "namespace",
+        "namespace N(16); col witness",
+        "namespace N(16); col witness x; x = ;",
+        "}}}",
+        // Array lengths
+        "namespace N(16); col witness x[-1];",
+        "namespace N(16); col witness x[2**70];",
+        "namespace N(16); col witness x[unknown];",
+        "namespace N(16); col witness x[\"abc\"];",
+        "namespace N(16); col witness x[1 / 0];",
+        "namespace N(16); let x: col[-1];",
+        "namespace N(16); let x: col[];",
+        "namespace N(16); let x: int[2**70] = [];",
+        "namespace N(16); let x: fe;",
+        "namespace N(16); let<T> x: T;",
+        "namespace N(16); let<T, T> x: T -> T = |i| i;",
+        // Public declarations
+        "namespace N(16); col witness x; public P = N.x(-1);",
+        "namespace N(16); col witness x; public P = N.x(2**70);",
+        "namespace N(16); col witness x[2]; public P = N.x[-1](0);",
+        "namespace N(16); col witness x; public P = N.y(0);",
+        "namespace N(16); col witness x; public P = N.x(16);",
+        "namespace N(16); col witness x; x = :Q;",
+        // Unknown symbols
+        "namespace N(16); col witness x; x = y;",
+        "namespace N(16); col witness x; { x } in { y };",
+        "namespace N(16); let f = |i| g(i);",
+        "namespace N(16); col fixed a(i) { unknown::f(i) };",
+        // Namespaces
+        "namespace N(-1);",
+        "namespace N(2**70);",
+        "namespace N(unknown);",
+        "namespace N(16); namespace M(8);",
+        // Fixed columns defined by arrays
+        "col fixed a = [1]*;",
+        "namespace N(4); col fixed a = [1]* + [2]*;",
+        "namespace N(4); col fixed a = [1, 2, 3, 4, 5];",
+        "namespace N(4); col fixed a = [1, 2];",
+        "namespace N(4); col fixed a = [1, 2, 3, 4, 5] + [0]*;",
+        "namespace N(4); let a: col[2] = [1]*;",
+        // Witness columns with definitions
+        "namespace N(4); let x: col[2] = |i| i;",
+        // Duplicate symbols
+        "namespace N(16); col witness x; col witness x;",
+        "namespace N(16); col witness x; let x = 1;",
+        // Type errors
+        "namespace N(16); col witness x; x = \"abc\";",
+        "namespace N(16); let a: int = 1; let b: fe = 2; let c = a + b;",
+        "namespace N(16); col witness x; x;",
+        // Evaluation errors
+        "namespace N(16); col witness x; x = 2**(2**70);",
+        "namespace N(16); col witness x; x = x**(2**70);",
+        "namespace N(16); col witness x; x = 1 / 0;",
+        "namespace N(16); col witness x; let a = [1, 2]; x = a[2];",
+        "namespace N(16); col witness x; let a = [1, 2]; x = a[-1];",
+        "namespace N(16); col witness x; x = 0x10000000000000000;",
+        "namespace N(16); col witness x; let f = |i| f(i); x = f(1);",
+        "namespace N(16); col witness x; std::check::panic(\"abc\");",
+        "namespace N(16); col witness x; col i = i; x = i;",
+    ];
+
+    #[test]
+    fn malformed_inputs_are_rejected_without_panic() {
+        for input in MALFORMED {
+            assert!(
+                try_analyze_string::<GoldilocksField>(input).is_err(),
+                "Input was accepted: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        let errors =
+            try_analyze_string::<GoldilocksField>("namespace N(16); col witness").unwrap_err();
+        assert!(matches!(&errors[..], [AnalysisError::Parse(_)]));
+        assert!(errors[0]
+            .to_string()
+            .starts_with("Error parsing .pil file: input:"));
+    }
+
+    #[test]
+    fn array_length() {
+        assert_eq!(
+            errors("namespace N(16); col witness x[-1];"),
+            "input:1:17: Length of array of witness columns x has to be a non-negative integer that fits u64, but it is -1."
+        );
+        assert_eq!(
+            errors("namespace N(16); col witness x[2**70];"),
+            "input:1:17: Length of array of witness columns x has to be a non-negative integer that fits u64, but it is 1180591620717411303424."
+        );
+        assert_eq!(
+            errors("namespace N(16); col witness x[unknown];"),
+            "input:1:17: Error evaluating length of array of witness columns x:\nSymbol not found: unknown"
+        );
+        assert_eq!(
+            errors("namespace N(16); let x: col[-1];"),
+            "input:1:17: Error evaluating expressions in type name \"col[-1]\" to reduce it to a type:\nNumber too large, expected u64, but got -1)"
+        );
+    }
+
+    #[test]
+    fn public_declaration() {
+        assert_eq!(
+            errors("namespace N(16); col witness x; public P = N.x(-1);"),
+            "input:1:32: Row of public declaration N.P has to be a non-negative integer that fits u64, but it is -1."
+        );
+        assert_eq!(
+            errors("namespace N(16); col witness x[2]; public P = N.x[-1](0);"),
+            "input:1:35: Array index of public declaration N.P has to be a non-negative integer, but it is -1."
+        );
+        assert_eq!(
+            errors("namespace N(16); col witness x; x = :Q;"),
+            "input:1:32: Symbol not found: Public Q is referenced but not declared.\nIn identity #0 [polynomial] input:1:32: N.x = :Q"
+        );
+        assert_eq!(
+            errors("namespace N(16); col witness x; public x = N.x(0);"),
+            "input:1:32: Duplicate symbol definition: N.x"
+        );
+    }
+
+    #[test]
+    fn unknown_symbols() {
+        assert_eq!(
+            errors("namespace N(16); col witness x; x = y; x = z;"),
+            "input:1:32: Symbol not found: y\ninput:1:39: Symbol not found: z"
+        );
+    }
+
+    #[test]
+    fn namespace_degree() {
+        assert_eq!(
+            errors("namespace N(-1);"),
+            "input:1:0: The degree of a namespace has to be a non-negative integer that fits u64, but it is -1."
+        );
+        assert_eq!(
+            errors("namespace N(16); namespace M(8);"),
+            "input:1:17: All namespaces must have the same degree, but found 8 after 16."
+        );
+    }
+
+    #[test]
+    fn fixed_column_arrays() {
+        assert_eq!(
+            errors("col fixed a = [1]*;"),
+            "input:1:0: Fixed column a is defined by an array, which requires the degree of a namespace."
+        );
+        assert_eq!(
+            errors("namespace N(4); col fixed a = [1, 2];"),
+            "input:1:16: Fixed column N.a has 2 rows, but the degree is 4."
+        );
+    }
+
+    #[test]
+    fn fixed_column_sparse_arrays() {
+        assert_eq!(
+            errors("col fixed a = { 0: 1, _: 0 };"),
+            "input:1:0: Fixed column a is defined by a sparse array, which requires the degree of a namespace."
+        );
+        assert_eq!(
+            errors("namespace N(4); col fixed a = { 4: 1, _: 0 };"),
+            "input:1:16: Row 4 of fixed column N.a is out of bounds, the degree is 4."
+        );
+        assert_eq!(
+            errors("namespace N(4); col fixed a = { -1: 1, _: 0 };"),
+            "input:1:16: Row -1 of fixed column N.a is out of bounds, the degree is 4."
+        );
+        assert_eq!(
+            errors("namespace N(4); col fixed a = { 1: 1, 2 - 1: 2, _: 0 };"),
+            "input:1:16: Row 1 of fixed column N.a is defined more than once."
+        );
+    }
+
+    #[test]
+    fn duplicate_symbols() {
+        assert_eq!(
+            errors("namespace N(16); col witness x; col witness x;"),
+            "input:1:32: Duplicate symbol definition: N.x"
+        );
+    }
+
+    #[test]
+    fn intermediate_column_length() {
+        assert_eq!(
+            errors("namespace N(16); col witness x; let y: expr[2] = [x, x, x];"),
+            "input:1:32: Type error: Error condensing intermediate column N.y: Declared length is 2, but its value has 3 elements."
+        );
+    }
+
+    #[test]
+    fn exponent_too_large_for_field() {
+        let errors = try_analyze_string::<BabyBearField>(
+            "namespace N(16); col witness x; x = x**3000000000;",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors.iter().format("\n").to_string(),
+            "input:1:32: Type error: Exponent in N.x**3000000000 is too large for the field.\nIn identity #0 [polynomial] input:1:32: N.x = (N.x ** 3000000000)"
+        );
+    }
+}
+
+mod next_operator {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use pretty_assertions::assert_eq;
+
+    use super::errors;
+
+    #[test]
+    fn linear_combinations() {
+        let input = r#"namespace N(16);
+    col witness x, y;
+    (x + 2 * y)' = 0;
+    (-x - y * 3)' = 0;
+"#;
+        let expected = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    (N.x' + (2 * N.y')) = 0;
+    (-N.x' - (N.y' * 3)) = 0;
+"#;
+        assert_eq!(
+            analyze_string::<GoldilocksField>(input).to_string(),
+            expected
+        );
+    }
+
+    #[test]
+    fn lambda_parameter() {
+        // The parameter is substituted before the operator is applied.
+        let input = r#"namespace N(16);
+    col witness x, y;
+    let f = |t| t' - 7;
+    f(x + y) = 0;
+"#;
+        let analyzed = analyze_string::<GoldilocksField>(input);
+        assert_eq!(
+            analyzed.identities[0].to_string(),
+            "((N.x' + N.y') - 7) = 0;"
+        );
+    }
+
+    #[test]
+    fn non_linear() {
+        let input = r#"namespace N(16);
+    col witness x, y;
+    (x * y)' = 0;
+"#;
+        assert_eq!(
+            errors(input),
+            "input:3:4: Type error: The next-row operator \"'\" can only be applied to columns and linear combinations of columns, but it is applied to (N.x * N.y).\nIn identity #0 [polynomial] input:3:4: (N.x * N.y)' = 0"
+        );
+    }
+
+    #[test]
+    fn constant() {
+        let input = r#"namespace N(16);
+    col witness x;
+    5' = x;
+"#;
+        assert_eq!(
+            errors(input),
+            "input:3:4: Type error: The next-row operator \"'\" can only be applied to columns and linear combinations of columns, but it is applied to 5.\nIn identity #0 [polynomial] input:3:4: 5' = N.x"
+        );
+    }
+
+    #[test]
+    fn double_next() {
+        let input = r#"namespace N(16);
+    col witness x, y;
+    x'' = y;
+"#;
+        assert_eq!(
+            errors(input),
+            "input:3:4: Type error: The next-row operator \"'\" is applied twice to N.x', which already refers to the next row.\nIn identity #0 [polynomial] input:3:4: N.x'' = N.y"
+        );
+    }
+
+    #[test]
+    fn double_next_through_lambda() {
+        let input = r#"namespace N(16);
+    col witness x;
+    let f = |t| t' - 7;
+    f(x') = 0;
+"#;
+        assert_eq!(
+            errors(input),
+            "input:4:4: Type error: The next-row operator \"'\" is applied twice to N.x', which already refers to the next row.\nCall stack (innermost call first):\n    N.f(N.x')\nIn identity #0 [polynomial] input:4:4: N.f(N.x') = 0"
+        );
+    }
+}
+
+mod interning {
+    use powdr_ast::analyzed::{AlgebraicExpression, AlgebraicReference, Analyzed};
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    const INPUT: &str = r#"namespace N(16);
+    col witness x;
+    col witness y[2];
+    col fixed LINE(i) { i };
+    x' = x * LINE + y[0];
+    y[1] = x * x + y[0];
+    col inter = x * y[1];
+    inter = LINE;
+"#;
+
+    fn references(analyzed: &mut Analyzed<GoldilocksField>) -> Vec<AlgebraicReference> {
+        let mut references = vec![];
+        analyzed.post_visit_expressions_in_identities_mut(&mut |e| {
+            if let AlgebraicExpression::Reference(reference) = e {
+                references.push(reference.clone());
+            }
+        });
+        references
+    }
+
+    fn assert_shared(references: &[AlgebraicReference]) {
+        for a in references {
+            for b in references.iter().filter(|b| b.name == a.name) {
+                assert_eq!(a.name.as_ptr(), b.name.as_ptr(), "{} is not shared", a.name);
+            }
+        }
+    }
+
+    #[test]
+    fn references_share_names() {
+        let mut analyzed = analyze_string::<GoldilocksField>(INPUT);
+        let references = references(&mut analyzed);
+        assert_eq!(references.iter().filter(|r| r.name == "N.x").count(), 5);
+        assert_shared(&references);
+        // N.x, N.y[0], N.y[1], N.LINE and N.inter
+        assert_eq!(analyzed.names.len(), 5);
+    }
+
+    #[test]
+    fn names_are_shared_after_deserialization() {
+        let analyzed = analyze_string::<GoldilocksField>(INPUT);
+        let serialized = analyzed.serialize().unwrap();
+        let mut deserialized = Analyzed::<GoldilocksField>::deserialize(&serialized).unwrap();
+        assert_eq!(deserialized, analyzed);
+        assert_shared(&references(&mut deserialized));
+    }
+
+    #[test]
+    fn serialization_does_not_depend_on_interning() {
+        let analyzed = analyze_string::<GoldilocksField>(INPUT);
+        let mut copied = analyzed.clone();
+        copied.names = Default::default();
+        copied.post_visit_expressions_in_identities_mut(&mut |e| {
+            if let AlgebraicExpression::Reference(reference) = e {
+                reference.name = reference.name.to_string().into();
+            }
+        });
+        assert_eq!(copied.serialize(), analyzed.serialize());
+        let json = serde_json::to_string(&analyzed.identities).unwrap();
+        assert_eq!(serde_json::to_string(&copied.identities).unwrap(), json);
+        assert!(json.contains(r#""name":"N.y[0]""#), "{json}");
+    }
+
+    #[test]
+    fn renamed_references_share_names() {
+        let mut analyzed = analyze_string::<GoldilocksField>(INPUT);
+        analyzed
+            .rename_symbols(&[("N.x".to_string(), "N.z".to_string())].into())
+            .unwrap();
+        let references = references(&mut analyzed);
+        assert!(references.iter().all(|r| r.name != "N.x"));
+        assert_eq!(references.iter().filter(|r| r.name == "N.z").count(), 5);
+        assert_shared(&references);
+    }
+}
+
+mod timing {
+    use powdr_number::GoldilocksField;
+    use std::time::Duration;
+
+    use powdr_pil_analyzer::{analyze_string, analyze_string_with_timings, PhaseTiming};
+
+    const INPUT: &str = r#"namespace N(16);
+    col witness x, y;
+    col fixed LINE(i) { i };
+    let square = |e| e * e;
+    col inter = x * y;
+    x' = square(x) + LINE * inter;
+"#;
+
+    fn names(phases: &[PhaseTiming]) -> Vec<&str> {
+        phases.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    fn total(phases: &[PhaseTiming]) -> Duration {
+        phases.iter().map(|p| p.duration).sum()
+    }
+
+    #[test]
+    fn phases_are_nested() {
+        let (analyzed, timing) = analyze_string_with_timings::<GoldilocksField>(INPUT);
+        assert_eq!(analyzed, analyze_string::<GoldilocksField>(INPUT));
+        assert_eq!(timing.name, "analysis");
+        assert_eq!(
+            names(&timing.phases),
+            [
+                "parsing",
+                "statement processing",
+                "type inference",
+                "condensation"
+            ]
+        );
+        let condensation = timing.find("condensation").unwrap();
+        assert_eq!(
+            names(&condensation.phases),
+            ["identities", "intermediate columns"]
+        );
+        assert!(total(&condensation.phases) <= condensation.duration);
+        assert!(total(&timing.phases) <= timing.duration);
+
+        let lines = timing.to_string();
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 7);
+        assert!(lines[0].starts_with("analysis: "));
+        assert!(lines[4].starts_with("  condensation: "));
+        assert!(lines[5].starts_with("    identities: "));
+        assert!(lines[6].ends_with(" ms"));
+    }
+}
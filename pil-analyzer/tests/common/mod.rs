@@ -0,0 +1,11 @@
+use itertools::Itertools;
+use powdr_number::GoldilocksField;
+use powdr_pil_analyzer::try_analyze_string;
+
+/// Analyzes the input, expecting it to fail, and returns all error messages.
+pub fn errors(input: &str) -> String {
+    match try_analyze_string::<GoldilocksField>(input) {
+        Ok(analyzed) => panic!("Expected errors, but the input was accepted:\n{analyzed}"),
+        Err(errors) => errors.iter().format("\n").to_string(),
+    }
+}
@@ -0,0 +1,339 @@
+mod evaluate_function {
+    use powdr_number::{BigInt, GoldilocksField};
+    use powdr_pil_analyzer::{analyze_string, evaluate_function, evaluator::Value};
+
+    use pretty_assertions::assert_eq;
+
+    const INPUT: &str = r#"
+namespace std::array(16);
+    let len = [];
+namespace std::math::ff(16);
+    let reduce: int, int -> int = |x, modulus|
+        if x % modulus < 0 { x % modulus + modulus } else { x % modulus };
+namespace Main(16);
+    let square: int -> int = |i| i * i;
+    let one: int = 1;
+    col fixed BYTE(i) { std::convert::fe(i & 0xff) };
+namespace std::convert(16);
+    let fe = [];
+"#;
+
+    #[test]
+    fn std_helper_at_several_inputs() {
+        let analyzed = analyze_string::<GoldilocksField>(INPUT);
+        for (x, expected) in [(-22, 6), (-7, 0), (-1, 6), (0, 0), (5, 5), (13, 6), (21, 0)] {
+            let result = evaluate_function(
+                &analyzed,
+                "std::math::ff::reduce",
+                vec![BigInt::from(x).into(), BigInt::from(7).into()],
+            )
+            .unwrap();
+            assert_eq!(i64::try_from(result).unwrap(), expected, "reduce({x}, 7)");
+        }
+    }
+
+    #[test]
+    fn name_resolution() {
+        let analyzed = analyze_string::<GoldilocksField>(INPUT);
+        for name in ["Main.square", "Main::square", "::Main::square"] {
+            let result = evaluate_function(&analyzed, name, vec![9i64.into()]).unwrap();
+            assert_eq!(result.to_string(), "81");
+        }
+        let result = evaluate_function(
+            &analyzed,
+            "::std::math::ff.reduce",
+            vec![10i64.into(), 3i64.into()],
+        )
+        .unwrap();
+        assert_eq!(result.to_string(), "1");
+    }
+
+    #[test]
+    fn builtins_and_fixed_columns() {
+        let analyzed = analyze_string::<GoldilocksField>(INPUT);
+        let result = evaluate_function(
+            &analyzed,
+            "std::array::len",
+            vec![Value::from(vec![1i64, 2, 3])],
+        )
+        .unwrap();
+        assert_eq!(result.to_string(), "3");
+        for (row, expected) in [(0u64, 0u64), (7, 7), (255, 255), (256, 0), (300, 44)] {
+            let result = evaluate_function(&analyzed, "Main.BYTE", vec![row.into()]).unwrap();
+            assert_eq!(
+                GoldilocksField::try_from(result).unwrap(),
+                expected.into(),
+                "BYTE({row})"
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let analyzed = analyze_string::<GoldilocksField>(INPUT);
+        let error = |name: &str, arguments: Vec<Value<GoldilocksField>>| {
+            evaluate_function(&analyzed, name, arguments)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("Main.cube", vec![2i64.into()]),
+            "Symbol not found: Symbol Main.cube not found."
+        );
+        assert_eq!(
+            error("Main.one", vec![]),
+            "Type error: Symbol Main.one of type int is not a function."
+        );
+        assert_eq!(
+            error("Main.square", vec![2i64.into(), 3i64.into()]),
+            "Type error: Function Main.square of type int -> int expects 1 arguments, but 2 were supplied."
+        );
+    }
+}
+
+mod fixed_columns {
+    use std::sync::Arc;
+
+    use powdr_ast::{analyzed::FunctionValueDefinition, parsed::types::Type};
+    use powdr_number::{FieldElement, GoldilocksField, KnownField, LargeInt};
+    use powdr_pil_analyzer::{
+        analyze_string,
+        evaluator::{
+            evaluate_fixed_column, CallCache, Definitions, EvalError, FieldOverflow, SymbolLookup,
+            Value,
+        },
+    };
+
+    const DEGREE: u64 = 512;
+
+    const INPUT: &str = r#"
+namespace std::convert(512);
+    let fe = [];
+namespace Main(512);
+    let fib: int -> int = |i| if i < 2 { i } else { fib(i - 1) + fib(i - 2) };
+    col fixed F(i) { std::convert::fe(fib(i % 16)) };
+    let G: col[2] = [|i| std::convert::fe(i * i), |i| F(i) + 1];
+    col fixed P = [1, 2, 3]* + [7];
+    let arr: int[] = [1, 2];
+    col fixed E(i) { if i < 300 { std::convert::fe(i) } else { std::convert::fe(arr[i]) } };
+    col fixed O = [0xffffffff00000001]*;
+    col fixed C(i) { std::convert::fe(0xffffffff00000000 + i) };
+    col fixed R(i) { match i { 0..16 => 1, 16..=31 => 2, _ => 0 } };
+"#;
+
+    /// Definitions with a call cache, like the one used to generate fixed columns.
+    struct CachedDefinitions<'a> {
+        definitions: Definitions<'a>,
+        call_cache: CallCache<'a, GoldilocksField>,
+    }
+
+    impl<'a> SymbolLookup<'a, GoldilocksField> for CachedDefinitions<'a> {
+        fn lookup(
+            &self,
+            name: &'a str,
+            generic_args: Option<Vec<Type>>,
+        ) -> Result<Arc<Value<'a, GoldilocksField>>, EvalError> {
+            self.definitions
+                .lookup_with_symbols(name, generic_args, self)
+        }
+
+        fn fixed_column_definition(
+            &self,
+            name: &str,
+        ) -> Result<&'a FunctionValueDefinition, EvalError> {
+            self.definitions.lookup_fixed_column(name)
+        }
+
+        fn call_cache(&self) -> Option<&CallCache<'a, GoldilocksField>> {
+            Some(&self.call_cache)
+        }
+    }
+
+    fn evaluate_column(
+        name: &str,
+        array_index: Option<u64>,
+        threads: usize,
+        cached: bool,
+    ) -> Result<Vec<GoldilocksField>, EvalError> {
+        let analyzed = analyze_string::<GoldilocksField>(INPUT);
+        let definition = analyzed.definition(name).unwrap().1.as_ref().unwrap();
+        if cached {
+            let symbols = CachedDefinitions {
+                definitions: Definitions(&analyzed.definitions),
+                call_cache: Default::default(),
+            };
+            evaluate_fixed_column(definition, array_index, DEGREE, threads, &symbols)
+        } else {
+            let symbols = Definitions(&analyzed.definitions);
+            evaluate_fixed_column(definition, array_index, DEGREE, threads, &symbols)
+        }
+    }
+
+    #[test]
+    fn recursive_definition() {
+        let sequential = evaluate_column("Main.F", None, 1, false).unwrap();
+        let fib = [
+            0u64, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144, 233, 377, 610,
+        ];
+        assert_eq!(sequential.len(), DEGREE as usize);
+        for (row, value) in sequential.iter().enumerate() {
+            assert_eq!(*value, fib[row % 16].into(), "row {row}");
+        }
+        for threads in [1, 2, 3, 8] {
+            for cached in [false, true] {
+                assert_eq!(
+                    evaluate_column("Main.F", None, threads, cached).unwrap(),
+                    sequential,
+                    "threads: {threads}, cached: {cached}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn range_patterns() {
+        let values = evaluate_column("Main.R", None, 4, true).unwrap();
+        assert_eq!(values[0], 1.into());
+        assert_eq!(values[15], 1.into());
+        assert_eq!(values[16], 2.into());
+        assert_eq!(values[31], 2.into());
+        assert_eq!(values[32], 0.into());
+    }
+
+    #[test]
+    fn array_elements_and_repeated_values() {
+        for threads in [1, 4] {
+            let squares = evaluate_column("Main.G", Some(0), threads, true).unwrap();
+            assert_eq!(squares[21], 441.into());
+            let shifted = evaluate_column("Main.G", Some(1), threads, true).unwrap();
+            assert_eq!(shifted[..4], [1.into(), 2.into(), 2.into(), 3.into()]);
+            let repeated = evaluate_column("Main.P", None, threads, true).unwrap();
+            assert_eq!(repeated[..4], [1.into(), 2.into(), 3.into(), 1.into()]);
+            assert_eq!(repeated[511], 7.into());
+        }
+        assert_eq!(
+            evaluate_column("Main.G", Some(2), 4, true)
+                .unwrap_err()
+                .to_string(),
+            "Out of bounds access: Index 2 out of bounds for an array of 2 fixed columns."
+        );
+    }
+
+    #[test]
+    fn first_error_is_reported() {
+        let sequential = evaluate_column("Main.E", None, 1, true)
+            .unwrap_err()
+            .to_string();
+        assert!(sequential.contains("300"), "{sequential}");
+        for threads in [2, 8] {
+            assert_eq!(
+                evaluate_column("Main.E", None, threads, true)
+                    .unwrap_err()
+                    .to_string(),
+                sequential
+            );
+        }
+    }
+
+    #[test]
+    fn field_overflow() {
+        let modulus = GoldilocksField::modulus().to_arbitrary_integer();
+        let expected = FieldOverflow {
+            value: modulus.clone(),
+            field: Some(KnownField::GoldilocksField),
+            modulus,
+        };
+        for threads in [1, 4] {
+            // The first row of `C` is the largest field element, the second one overflows.
+            for column in ["Main.O", "Main.C"] {
+                let error = evaluate_column(column, None, threads, true).unwrap_err();
+                let EvalError::FieldOverflow(overflow) = error.without_call_stack() else {
+                    panic!("{error}");
+                };
+                assert_eq!(overflow, &expected);
+            }
+        }
+    }
+}
+
+mod host_symbols {
+    use powdr_ast::parsed::types::TypeScheme;
+    use powdr_number::GoldilocksField;
+    use powdr_parser::{parse_type, parse_type_var_bounds};
+    use powdr_pil_analyzer::{
+        analyze_string_with_host_symbols,
+        evaluator::{EvalError, HostFunction, HostSymbols, Value},
+    };
+
+    struct Host;
+
+    impl HostSymbols<GoldilocksField> for Host {
+        fn declarations(&self) -> Vec<(String, TypeScheme)> {
+            [("ext::double", "int -> int"), ("ext::offset", "int")]
+                .into_iter()
+                .map(|(name, ty)| {
+                    (
+                        name.to_string(),
+                        TypeScheme {
+                            vars: parse_type_var_bounds("").unwrap(),
+                            ty: parse_type(ty).unwrap().into(),
+                        },
+                    )
+                })
+                .collect()
+        }
+
+        fn lookup<'a>(&self, name: &str) -> Result<Value<'a, GoldilocksField>, EvalError> {
+            match name {
+                "ext::double" => Ok(HostFunction::new(name, |args| {
+                    Ok(Value::Integer(args[0].try_to_integer()? * 2))
+                })
+                .into()),
+                "ext::offset" => Ok(Value::Integer(7.into())),
+                _ => Err(EvalError::SymbolNotFound(name.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn host_symbols_in_identities() {
+        let input = r#"namespace N(16);
+    col witness x;
+    let y: int = ext::double(ext::offset);
+    x = std::convert::expr(ext::double(3) + y);
+namespace std::convert(16);
+    let expr = [];
+"#;
+        let analyzed = analyze_string_with_host_symbols::<GoldilocksField>(input, &Host);
+        let formatted = analyzed.to_string();
+        assert!(formatted.contains("N.x = 20;"), "{formatted}");
+        assert!(
+            formatted.contains("let y: int = ext.double(ext.offset);"),
+            "{formatted}"
+        );
+    }
+
+    #[test]
+    fn pil_definitions_take_precedence() {
+        let input = r#"namespace ext(16);
+    let double: int -> int = |i| i * 3;
+namespace N(16);
+    col witness x;
+    x = std::convert::expr(ext::double(2) + ext::offset);
+namespace std::convert(16);
+    let expr = [];
+"#;
+        let analyzed = analyze_string_with_host_symbols::<GoldilocksField>(input, &Host);
+        let formatted = analyzed.to_string();
+        assert!(formatted.contains("N.x = 13;"), "{formatted}");
+    }
+
+    #[test]
+    #[should_panic = "Error checking sub-expression \"abc\""]
+    fn host_symbol_types_are_checked() {
+        let input = r#"namespace N(16);
+    let y: int = ext::double("abc");
+"#;
+        analyze_string_with_host_symbols::<GoldilocksField>(input, &Host);
+    }
+}
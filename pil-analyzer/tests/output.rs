@@ -0,0 +1,149 @@
+mod serialize {
+    use powdr_ast::analyzed::Analyzed;
+    use powdr_number::{Bn254Field, GoldilocksField};
+    use powdr_pil_analyzer::analyze_string;
+
+    use pretty_assertions::assert_eq;
+
+    const INPUT: &str = r#"
+let N = 16;
+namespace std::convert(N);
+    let fe = [];
+namespace Main(N);
+    col fixed ISLAST(i) { if i == N - 1 { 1 } else { 0 } };
+    col fixed BYTE(i) { std::convert::fe(i & 0xff) };
+    col fixed LATCH = [0, 1]*;
+    col witness x[2];
+    col witness y;
+    col witness sel;
+    let double: int -> int = |i| 2 * i;
+    let inter = x[0] + x[1];
+    pol sum = inter + y;
+    ISLAST * (x[1]' - 1) = 0;
+    (1 - ISLAST) * (x[0]' - x[1]) = 0;
+    sel { y } in LATCH { BYTE };
+    sel { sum } is LATCH { x[1] };
+    public out = x[1](N - 1);
+"#;
+
+    fn serialized(analyzed: &Analyzed<GoldilocksField>) -> Vec<u8> {
+        let mut bytes = vec![];
+        analyzed.serialize_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let analyzed = analyze_string::<GoldilocksField>(INPUT);
+        assert!(!analyzed.intermediate_columns.is_empty());
+        assert!(!analyzed.public_declarations.is_empty());
+        let deserialized = Analyzed::deserialize_from(serialized(&analyzed).as_slice()).unwrap();
+        assert_eq!(analyzed, deserialized);
+        assert_eq!(analyzed.to_string(), deserialized.to_string());
+    }
+
+    #[test]
+    fn invalid_header() {
+        let analyzed = analyze_string::<GoldilocksField>(INPUT);
+        let bytes = serialized(&analyzed);
+
+        let error =
+            |bytes: &[u8]| Analyzed::<GoldilocksField>::deserialize_from(bytes).unwrap_err();
+        assert_eq!(
+            error(&bytes[8..]),
+            "Not a binary analyzed file: invalid magic bytes"
+        );
+        assert_eq!(
+            error(&bytes[..6]),
+            "Failed to read analyzed header: failed to fill whole buffer"
+        );
+
+        let mut other_version = bytes.clone();
+        other_version[8] = 9;
+        assert_eq!(
+            error(&other_version),
+            "Unsupported analyzed format version 9, expected version 8"
+        );
+
+        assert_eq!(
+            Analyzed::<Bn254Field>::deserialize_from(bytes.as_slice()).unwrap_err(),
+            "The analyzed file was written for the field with modulus 0xffffffff00000001, but the field with modulus 0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001 is used"
+        );
+    }
+}
+
+mod write_pil {
+    use std::{fs, io, path::Path};
+
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_file;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixtures() -> Vec<std::path::PathBuf> {
+        let mut files = fs::read_dir("../test_data/pil")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "pil"))
+            .collect::<Vec<_>>();
+        files.sort();
+        files.push(Path::new("../test_data/polygon-hermez/main.pil").to_path_buf());
+        files
+    }
+
+    #[test]
+    fn analyzed_output_is_identical() {
+        for file in fixtures() {
+            let analyzed = analyze_file::<GoldilocksField>(&file);
+            let mut written = vec![];
+            analyzed.write_pil(&mut written).unwrap();
+            assert_eq!(
+                String::from_utf8(written).unwrap(),
+                analyzed.to_string(),
+                "{}",
+                file.display()
+            );
+        }
+    }
+
+    #[test]
+    fn parsed_output_is_identical() {
+        for file in fixtures() {
+            let input = fs::read_to_string(&file).unwrap();
+            let parsed = powdr_parser::parse(Some(file.to_str().unwrap()), &input).unwrap();
+            let mut written = vec![];
+            parsed.write_pil(&mut written).unwrap();
+            assert_eq!(
+                String::from_utf8(written).unwrap(),
+                parsed.to_string(),
+                "{}",
+                file.display()
+            );
+        }
+    }
+
+    /// Accepts a limited number of bytes and fails afterwards.
+    struct LimitedWriter(usize);
+
+    impl io::Write for LimitedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "writer is full"));
+            }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn io_errors_are_returned() {
+        let analyzed = analyze_file::<GoldilocksField>(Path::new("../test_data/pil/fibonacci.pil"));
+        let error = analyzed.write_pil(&mut LimitedWriter(10)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+        assert_eq!(error.to_string(), "writer is full");
+    }
+}
@@ -1,5 +1,6 @@
-use powdr_number::GoldilocksField;
-use powdr_pil_analyzer::analyze_string;
+use powdr_ast::analyzed::IdentityKind;
+use powdr_number::{Bn254Field, GoldilocksField};
+use powdr_pil_analyzer::{analyze_string, analyze_string_with_limits, evaluator::EvaluationLimits};
 use test_log::test;
 
 use pretty_assertions::assert_eq;
@@ -114,6 +115,22 @@ namespace N(65536);
     assert_eq!(formatted, expected);
 }
 
+#[test]
+fn literal_suffixes() {
+    let input = r#"namespace N(16);
+    let f = |i| i + 1_000_fe * 2;
+    let z = [1_int, 2];
+"#;
+    let expected = r#"namespace N(16);
+    let f: fe -> fe = (|i| (i + (1000_fe * 2)));
+    let z: int[] = [1_int, 2];
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+    let reparsed = analyze_string::<GoldilocksField>(&formatted).to_string();
+    assert_eq!(reparsed, expected);
+}
+
 #[test]
 fn reparse_arrays() {
     let input = r#"public out = N.y[1](2);
@@ -126,6 +143,22 @@ namespace N(16);
     assert_eq!(formatted, input);
 }
 
+#[test]
+fn sparse_arrays() {
+    let input = r#"namespace N(16);
+    col fixed boundary = { 16 - 1: 1, 0: 1, _: 0 };
+    col fixed values = { 3: -1, _: 7 };
+"#;
+    let expected = r#"namespace N(16);
+    col fixed boundary = { 0: 1, 15: 1, _: 0 };
+    col fixed values = { 3: -1, _: 7 };
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+    let reparsed = analyze_string::<GoldilocksField>(&formatted).to_string();
+    assert_eq!(reparsed, expected);
+}
+
 #[test]
 #[should_panic = "Type expr[] does not satisfy trait Sub."]
 fn no_direct_array_references() {
@@ -148,6 +181,18 @@ fn no_out_of_bounds() {
     assert_eq!(formatted, input);
 }
 
+#[test]
+#[should_panic = "input:3:4: Out of bounds access: Index access out of bounds: Tried to access element 3 of array of size 3 in: N.y[3].\nIn identity #0 [polynomial] input:3:4: (N.y[3] - 2) = 0\ninput:5:4: Out of bounds access: Index access out of bounds: Tried to access element 4 of array of size 3 in: N.y[4].\nIn identity #2 [polynomial] input:5:4: (N.y[4] - 2) = 0"]
+fn condensation_errors_are_collected() {
+    let input = r#"namespace N(16);
+    col witness y[3];
+    (N.y[3] - 2) = 0;
+    (N.y[2] - 2) = 0;
+    (N.y[4] - 2) = 0;
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
 #[test]
 fn namespaced_call() {
     let input = r#"namespace Assembly(2);
@@ -180,6 +225,44 @@ fn if_expr() {
     assert_eq!(formatted, expected);
 }
 
+#[test]
+fn block_expr() {
+    let input = r#"namespace N(16);
+    let f = |i| { let x = i * i; let y = x + 1; let x = y * x; x - 2 };
+    col fixed A(i) { { let k = N.f(i); k * k } };
+"#;
+    let expected = r#"namespace N(16);
+    let f: int -> int = (|i| { let x = (i * i); let y = (x + 1); let x = (y * x); (x - 2) });
+    col fixed A(i) { { let k = N.f(i); (k * k) } };
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+    let reparsed = analyze_string::<GoldilocksField>(&formatted).to_string();
+    assert_eq!(reparsed, expected);
+}
+
+#[test]
+fn hex_and_binary_literals() {
+    let input = r#"namespace N(16);
+    let mask: int = 0xffff_ffff;
+    col fixed BITS = [0b1010]*;
+    let f: int -> int = |i| (i & 0xFF) + 0b0_11 + 10;
+    col witness x;
+    x = 0x10;
+"#;
+    let expected = r#"namespace N(16);
+    let mask: int = 0xffffffff;
+    col fixed BITS = [0b1010]*;
+    let f: int -> int = (|i| (((i & 0xff) + 0b11) + 10));
+    col witness x;
+    N.x = 16;
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+    let reparsed = analyze_string::<GoldilocksField>(&formatted).to_string();
+    assert_eq!(reparsed, expected);
+}
+
 #[test]
 fn symbolic_functions() {
     let input = r#"namespace N(16);
@@ -260,6 +343,31 @@ fn parentheses_lambda() {
     assert_eq!(formatted, expected);
 }
 
+#[test]
+fn parentheses_lambda_with_captures() {
+    let input = r#"namespace N(16);
+    let w = || 2;
+    let x: fe = (|i| || i + w())(w())();
+    let y: fe = (|i| |j| || i * j + w())(3)(4)();
+    let adder: expr -> (expr -> expr) = |a| |b| a + b;
+    col witness v, z;
+    let add_v: expr -> expr = adder(v);
+    add_v(z) = adder(z)(z');
+    "#;
+    let expected = r#"namespace N(16);
+    let w: -> fe = (|| 2);
+    constant x = (|i| (|| (i + N.w())))(N.w())();
+    constant y = (|i| (|j| (|| ((i * j) + N.w()))))(3)(4)();
+    let adder: expr -> (expr -> expr) = (|a| (|b| (a + b)));
+    col witness v;
+    col witness z;
+    let add_v: expr -> expr = N.adder(N.v);
+    (N.v + N.z) = (N.z + N.z');
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+}
+
 #[test]
 fn simple_type_resolution() {
     let input = r#"namespace N(16);
@@ -335,7 +443,7 @@ fn expression_but_expected_constraint() {
 }
 
 #[test]
-#[should_panic = "Expected type: expr\\nInferred type: constr\\n"]
+#[should_panic = "Expected type: expr\nInferred type: constr\n"]
 fn constraint_but_expected_expression() {
     let input = r#"namespace N(16);
     col witness y;
@@ -418,3 +526,630 @@ namespace main(16);
 "#;
     assert_eq!(formatted, expected);
 }
+
+#[test]
+fn lookups_from_functions() {
+    let input = r#"namespace std::constraints(16);
+    let lookup = 1;
+    let permutation = 1;
+namespace N(16);
+    col witness x;
+    col witness y;
+    col fixed LINE(i) { i };
+    let gadget = |a, b| [a = b, std::constraints::lookup(1, [a], 1, [LINE]), std::constraints::permutation(x, [a, b], 1, [LINE, LINE])];
+    N.x' = N.y;
+    gadget(x, y);
+    N.y = 1;
+"#;
+    let expected = r#"namespace std::constraints(16);
+    let lookup = 1;
+    let permutation = 1;
+namespace N(16);
+    col witness x;
+    col witness y;
+    col fixed LINE(i) { i };
+    let gadget: expr, expr -> constr[] = (|a, b| [(a = b), std::constraints::lookup(1, [a], 1, [N.LINE]), std::constraints::permutation(N.x, [a, b], 1, [N.LINE, N.LINE])]);
+    N.x' = N.y;
+    N.x = N.y;
+    { N.x } in { N.LINE };
+    N.x { N.x, N.y } is { N.LINE, N.LINE };
+    N.y = 1;
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+fn intermediate_arrays_referencing_intermediates() {
+    let input = r#"namespace N(65536);
+    col witness x[4];
+    let inter2: expr[2] = [inter[0] + inter[1], inter[2] * single];
+    let inter: expr[4] = [x[0], x[1], x[2] + single, x[3]];
+    col single = x[0] + x[1];
+    inter2[0] = inter2[1];
+"#;
+    let expected = r#"namespace N(65536);
+    col witness x[4];
+    col inter2[2] = [(N.inter[0] + N.inter[1]), (N.inter[2] * N.single)];
+    col inter[4] = [N.x[0], N.x[1], (N.x[2] + N.single), N.x[3]];
+    col single = (N.x[0] + N.x[1]);
+    N.inter2[0] = N.inter2[1];
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+#[should_panic = "Cyclic dependency between intermediate columns: N.a -> N.b -> N.a"]
+fn cyclic_intermediate_columns() {
+    let input = r#"namespace N(65536);
+    col witness x;
+    col a = b[0] + x;
+    let b: expr[1] = [a];
+    a = 0;
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "input:7:4: Assertion failed: bit width must be at most 32, but is 40"]
+fn panic_during_condensation() {
+    let input = r#"namespace std::check(16);
+    let panic = 1;
+namespace N(16);
+    col witness x;
+    let bits: int -> expr = |width| if width > 32 { std::check::panic("bit width must be at most 32, but is 40") } else { 2**width };
+    x * bits(8) = 0;
+    x * bits(40) = 0;
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "input:8:4: Assertion failed: bit width must be at most 32, but is 40\nCall stack (innermost call first):\n    N.bits(40)\n    N.double_bits(20)\n    N.quad_bits(10)"]
+fn call_stack_during_condensation() {
+    let input = r#"namespace std::check(16);
+    let panic = 1;
+namespace N(16);
+    col witness x;
+    let bits: int -> expr = |width| if width > 32 { std::check::panic("bit width must be at most 32, but is 40") } else { 2**width };
+    let double_bits: int -> expr = |width| bits(width * 2);
+    let quad_bits: int -> expr = |width| double_bits(width * 2);
+    x * quad_bits(10) = 0;
+    x * quad_bits(2) = 0;
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "input:8:4: Assertion failed: bit width must be at most 32, but is 40\nCall stack (innermost call first):\n    N.bits(40)\n    … 1 frames omitted\n    N.quad_bits(10)"]
+fn limited_call_stack_during_condensation() {
+    let input = r#"namespace std::check(16);
+    let panic = 1;
+namespace N(16);
+    col witness x;
+    let bits: int -> expr = |width| if width > 32 { std::check::panic("bit width must be at most 32, but is 40") } else { 2**width };
+    let double_bits: int -> expr = |width| bits(width * 2);
+    let quad_bits: int -> expr = |width| double_bits(width * 2);
+    x * quad_bits(10) = 0;
+    x * quad_bits(2) = 0;
+"#;
+    let limits = EvaluationLimits {
+        max_printed_stack_frames: 2,
+        ..Default::default()
+    };
+    analyze_string_with_limits::<GoldilocksField>(input, limits);
+}
+
+#[test]
+#[should_panic = "input:8:4: Assertion failed: x has to be binary"]
+fn failing_assert_during_condensation() {
+    let input = r#"namespace std::check(16);
+    let panic = 1;
+    let assert: bool, (-> string) -> constr[] = |condition, reason| if !condition { panic(reason()) } else { [] };
+namespace N(16);
+    col witness x;
+    let is_binary: int -> bool = |v| v == 0 || v == 1;
+    std::check::assert(is_binary(1), || "x has to be binary");
+    std::check::assert(is_binary(2), || "x has to be binary");
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "input:5:4: Evaluation limit exceeded: Maximum call depth of 1000 reached when calling N.f."]
+fn call_depth_limit() {
+    let input = r#"namespace N(16);
+    col witness x;
+    let f: int -> constr = |i| f(i + 1);
+    x = 0;
+    f(1);
+"#;
+    let limits = EvaluationLimits {
+        max_call_depth: 1000,
+        ..Default::default()
+    };
+    analyze_string_with_limits::<GoldilocksField>(input, limits);
+}
+
+#[test]
+#[should_panic = "input:7:4: Evaluation limit exceeded: Maximum number of 1000 evaluation steps reached in N.count."]
+fn step_limit() {
+    let input = r#"namespace std::convert(16);
+    let expr = [];
+namespace N(16);
+    col witness x;
+    let count: int -> int = |i| if i == 0 { 0 } else { 1 + count(i - 1) };
+    x = 0;
+    x = std::convert::expr(count(10000));
+"#;
+    let limits = EvaluationLimits {
+        max_steps: 1000,
+        ..Default::default()
+    };
+    analyze_string_with_limits::<GoldilocksField>(input, limits);
+}
+
+#[test]
+fn new_witness_columns() {
+    let input = r#"namespace std::prover(16);
+    let new_witness_col = 1;
+namespace N(16);
+    col witness x;
+    col witness b;
+    let bool_equal = |c, e| [c * (1 - c) = 0, c = e];
+    let force_bool = |e| bool_equal(std::prover::new_witness_col("b"), e);
+    force_bool(x);
+    force_bool(x');
+    b = x;
+"#;
+    let expected = r#"namespace std::prover(16);
+    let new_witness_col = 1;
+namespace N(16);
+    col witness x;
+    col witness b;
+    let bool_equal: expr, expr -> constr[] = (|c, e| [((c * (1 - c)) = 0), (c = e)]);
+    let force_bool: expr -> constr[] = (|e| N.bool_equal(std::prover::new_witness_col("b"), e));
+    col witness b_1;
+    (N.b_1 * (1 - N.b_1)) = 0;
+    N.b_1 = N.x;
+    col witness b_2;
+    (N.b_2 * (1 - N.b_2)) = 0;
+    N.b_2 = N.x';
+    N.b = N.x;
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(analyzed.to_string(), expected);
+    assert_eq!(analyzed.commitment_count(), 4);
+    assert_eq!(analyzed.definition("N.b_2").unwrap().0.id, 3);
+}
+
+#[test]
+fn identity_provenance() {
+    let input = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    let constraints = |a, b| [a = b, a' = b'];
+    x = y;
+    constraints(x, y);
+    { x } in { y };
+"#;
+    let expected = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    let constraints: expr, expr -> constr[] = (|a, b| [(a = b), (a' = b')]);
+    N.x = N.y; // identity 0 at input:5:4
+    N.x = N.y; // identity 1 at input:6:4, element 0
+    N.x' = N.y'; // identity 1 at input:6:4, element 1
+    { N.x } in { N.y }; // identity 2 at input:7:4
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(format!("{analyzed:#}"), expected);
+    assert_eq!(
+        analyzed.identity_provenance(2).unwrap().array_index,
+        Some(1)
+    );
+}
+
+#[test]
+fn display_with_sources() {
+    let input = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    let constraints = |a, b| [a = b, a' = b'];
+    constraints(x, y);
+    public out = x(2);
+"#;
+    let expected = r#"namespace N(16);
+// from input:2
+    col witness x;
+// from input:3
+    col witness y;
+// from input:4
+    let constraints: expr, expr -> constr[] = (|a, b| [(a = b), (a' = b')]);
+// from input:5
+    N.x = N.y;
+    N.x' = N.y';
+// from input:6
+    public out = N.x(2);
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(analyzed.display_with_sources().to_string(), expected);
+    assert!(!analyzed.to_string().contains("// from"));
+}
+
+#[test]
+fn unit_selectors_are_removed() {
+    let input = r#"namespace N(16);
+    col witness x;
+    col fixed LINE(i) { i };
+    1 { x } in 1 { LINE };
+    1 { x } is x { LINE };
+    x { x } in 1 { LINE };
+"#;
+    let expected = r#"namespace N(16);
+    col witness x;
+    col fixed LINE(i) { i };
+    { N.x } in { N.LINE };
+    { N.x } is N.x { N.LINE };
+    N.x { N.x } in { N.LINE };
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+fn zero_selectors_remove_identities() {
+    let input = r#"namespace std::constraints(16);
+    let lookup = 1;
+    let permutation = 1;
+namespace N(16);
+    col witness x;
+    col fixed LINE(i) { i };
+    let conditional_lookup = |sel, a| std::constraints::lookup(sel, [a], 1, [LINE]);
+    conditional_lookup(0, x);
+    conditional_lookup(x, x);
+    0 { x } is 0 { LINE };
+    0 { x } is 1 { LINE };
+    x { x } in 0 { LINE };
+"#;
+    let expected = r#"namespace std::constraints(16);
+    let lookup = 1;
+    let permutation = 1;
+namespace N(16);
+    col witness x;
+    col fixed LINE(i) { i };
+    let conditional_lookup: expr, expr -> constr = (|sel, a| std::constraints::lookup(sel, [a], 1, [N.LINE]));
+    N.x { N.x } in { N.LINE };
+    0 { N.x } is { N.LINE };
+    N.x { N.x } in 0 { N.LINE };
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(analyzed.to_string(), expected);
+    let removed = "// removed because of zero selector: identity 0 at input:8:4
+// removed because of zero selector: identity 2 at input:10:4
+";
+    assert!(format!("{analyzed:#}").ends_with(removed));
+}
+
+#[test]
+fn remove_lookups() {
+    let input = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    col fixed LINE(i) { i };
+    x { x } in { LINE };
+    x' = x + 1;
+    { y } in { LINE };
+    { x } is { y };
+    y = x * x;
+    { x, y } in { LINE, LINE };
+"#;
+    let expected = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    col fixed LINE(i) { i };
+    N.x' = (N.x + 1);
+    { N.x } is { N.y };
+    N.y = (N.x * N.x);
+"#;
+    let mut analyzed = analyze_string::<GoldilocksField>(input);
+    let new_ids = analyzed.retain_identities(|identity| identity.kind != IdentityKind::Plookup);
+    assert_eq!(analyzed.to_string(), expected);
+    assert_eq!(
+        new_ids.into_iter().collect::<Vec<_>>(),
+        [
+            ((IdentityKind::Polynomial, 0), 0),
+            ((IdentityKind::Polynomial, 1), 1),
+            ((IdentityKind::Permutation, 0), 0),
+        ]
+    );
+    assert_eq!(
+        analyzed
+            .identities
+            .iter()
+            .map(|identity| identity.id)
+            .collect::<Vec<_>>(),
+        [0, 0, 1]
+    );
+}
+
+#[test]
+fn identity_degrees() {
+    let input = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    col fixed LINE(i) { i };
+    pol inter = x * y;
+    x' = x + 1;
+    y = x * x * LINE;
+    inter * x = 0;
+    (x - y)**3 = -y;
+    x { x } in { LINE };
+    x * y { x + 1, y * y } is LINE { LINE, 7 };
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(analyzed.identity_degrees(), [1, 3, 3, 3, 2, 4]);
+    assert_eq!(analyzed.max_identity_degree(), 4);
+    // without inlining, the intermediate column counts as degree one
+    assert_eq!(analyzed.identities[2].degree(), 2);
+}
+
+#[test]
+fn describe_identities() {
+    let input = r#"namespace N(16);
+    col witness a, b;
+    col fixed LINE(i) { i };
+    a' = a + b;
+    { a, b } in { LINE, a };
+    b { a } is LINE { LINE };
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    let descriptions = analyzed
+        .identities
+        .iter()
+        .map(|identity| identity.describe())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        descriptions,
+        [
+            "#0 [polynomial] input:4:4: N.a' = (N.a + N.b)",
+            "#0 [plookup] input:5:4: { N.a, N.b } in { N.LINE, N.a }",
+            "#0 [permutation] input:6:4: N.b { N.a } is N.LINE { N.LINE }",
+        ]
+    );
+}
+
+#[test]
+fn match_returning_constraints() {
+    let input = r#"namespace N(16);
+    let FLAVOR: int = 1;
+    col witness x;
+    let constraints = |flavor| match flavor { 0 => [], 1 => [x = 2, x' = 3], _ => [x = 4] };
+    match FLAVOR { 0 => x = 0, _ => x = 1 };
+    constraints(FLAVOR);
+    constraints(2);
+"#;
+    let expected = r#"namespace N(16);
+    let FLAVOR: int = 1;
+    col witness x;
+    let constraints: int -> constr[] = (|flavor| match flavor { 0 => [], 1 => [(N.x = 2), (N.x' = 3)], _ => [(N.x = 4)], });
+    N.x = 1;
+    N.x = 2;
+    N.x' = 3;
+    N.x = 4;
+"#;
+    let formatted = analyze_string::<GoldilocksField>(input).to_string();
+    assert_eq!(formatted, expected);
+}
+
+#[test]
+#[should_panic = "The arms of a match expression have to be of the same type, but arm 2 does not evaluate to constr like the previous arms"]
+fn match_arms_constraint_and_expression() {
+    let input = r#"namespace N(16);
+    let FLAVOR: int = 1;
+    col witness x;
+    match FLAVOR { 0 => x = 0, _ => x + 1 };
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "The arms of a match expression have to be of the same type, but arm 2 does not evaluate to constr like the previous arms"]
+fn match_arms_constraint_and_array() {
+    let input = r#"namespace N(16);
+    col witness x;
+    let f = |i| match i { 0 => x = 0, _ => [x = 1] };
+    f(1);
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+fn namespaced_public_declarations() {
+    let input = r#"namespace N(16);
+    col witness x;
+namespace T(16);
+    col witness pc;
+    public out = pc(2);
+    public other = N.x(3);
+    pc = :out + :T.other;
+"#;
+    let expected = r#"namespace N(16);
+    col witness x;
+namespace T(16);
+    col witness pc;
+    public out = T.pc(2);
+    public other = N.x(3);
+    T.pc = (:T.out + :T.other);
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(
+        analyzed
+            .public_declarations_in_source_order()
+            .into_iter()
+            .map(|(name, declaration)| (
+                name.to_dotted_string(),
+                declaration.referenced_poly_name()
+            ))
+            .collect::<Vec<_>>(),
+        [
+            ("T.out".to_string(), "T.pc".to_string()),
+            ("T.other".to_string(), "N.x".to_string())
+        ]
+    );
+    let formatted = analyzed.to_string();
+    assert_eq!(formatted, expected);
+    let reparsed = analyze_string::<GoldilocksField>(&formatted).to_string();
+    assert_eq!(reparsed, expected);
+}
+
+#[test]
+fn namespaced_and_top_level_public() {
+    let input = r#"public out = T.pc(0);
+namespace T(16);
+    col witness pc;
+    public out = pc(2);
+    pc = :out;
+namespace N(16);
+    col witness x;
+    x = :out;
+"#;
+    let expected = r#"public out = T.pc(0);
+namespace T(16);
+    col witness pc;
+    public out = T.pc(2);
+    T.pc = :T.out;
+namespace N(16);
+    col witness x;
+    N.x = :out;
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(analyzed.public_declaration("out").unwrap().index, 0);
+    assert_eq!(analyzed.public_declaration("T.out").unwrap().index, 2);
+    let formatted = analyzed.to_string();
+    assert_eq!(formatted, expected);
+    let reparsed = analyze_string::<GoldilocksField>(&formatted).to_string();
+    assert_eq!(reparsed, expected);
+}
+
+#[test]
+#[should_panic = "input:4:4: Type error: Public declaration N.out has to reference a witness column, but N.f is not a witness column."]
+fn public_of_fixed_column() {
+    let input = r#"namespace N(16);
+    col witness x;
+    col fixed f(i) { i };
+    public out = f(2);
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "input:3:4: Out of bounds access: Public declaration N.out references N.y[3], but N.y only has length 3."]
+fn public_array_index_out_of_bounds() {
+    let input = r#"namespace N(16);
+    col witness y[3];
+    public out = y[3](2);
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+#[should_panic = "input:3:4: Out of bounds access: Public declaration N.out references row 16 of N.x, but the degree is 16."]
+fn public_row_out_of_bounds() {
+    let input = r#"namespace N(16);
+    col witness x;
+    public out = x(16);
+"#;
+    analyze_string::<GoldilocksField>(input);
+}
+
+#[test]
+fn field_modulus_selects_constraints() {
+    let input = r#"namespace std::field(16);
+    let modulus = [];
+namespace N(16);
+    col witness x;
+    let is_goldilocks: bool = std::field::modulus() == 2**64 - 2**32 + 1;
+    let limb_bits: int = if std::field::modulus() < 2**64 { 16 } else { 32 };
+    if is_goldilocks { x = 1 } else { x = 2 };
+    if limb_bits == 16 { x' = x } else { x' = x + x };
+"#;
+    let goldilocks = analyze_string::<GoldilocksField>(input).to_string();
+    assert!(
+        goldilocks.contains("    N.x = 1;\n    N.x' = N.x;\n"),
+        "{goldilocks}"
+    );
+    let bn254 = analyze_string::<Bn254Field>(input).to_string();
+    assert!(
+        bn254.contains("    N.x = 2;\n    N.x' = (N.x + N.x);\n"),
+        "{bn254}"
+    );
+}
+
+#[test]
+fn labeled_identities() {
+    let input = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    #[label("x is y")] x = y;
+    #[label("lookup")] { x } in { y };
+    x' = y';
+"#;
+    let expected = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    #[label("x is y")] N.x = N.y;
+    #[label("lookup")] { N.x } in { N.y };
+    N.x' = N.y';
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(analyzed.to_string(), expected);
+    let reparsed = analyze_string::<GoldilocksField>(&analyzed.to_string());
+    assert_eq!(reparsed.to_string(), expected);
+
+    let lookup = analyzed.identity_by_label("lookup").unwrap();
+    assert_eq!(lookup.id, 0);
+    assert_eq!(
+        lookup.describe(),
+        "#0 [plookup] input:5:23: #[label(\"lookup\")] { N.x } in { N.y }"
+    );
+    assert!(analyzed.identity_by_label("x' is y'").is_none());
+}
+
+#[test]
+fn labels_of_arrays_are_suffixed() {
+    let input = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    let constraints = |a, b| [a = b, a' = b'];
+    #[label("equal")] constraints(x, y);
+"#;
+    let expected = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    let constraints: expr, expr -> constr[] = (|a, b| [(a = b), (a' = b')]);
+    #[label("equal[0]")] N.x = N.y;
+    #[label("equal[1]")] N.x' = N.y';
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(analyzed.to_string(), expected);
+    assert_eq!(
+        analyzed.identity_by_label("equal[1]").unwrap().to_string(),
+        "#[label(\"equal[1]\")] N.x' = N.y';"
+    );
+    assert!(analyzed.identity_by_label("equal").is_none());
+}
+
+#[test]
+fn duplicate_labels_are_accepted() {
+    let input = r#"namespace N(16);
+    col witness x;
+    col witness y;
+    #[label("twice")] x = y;
+    #[label("twice")] x' = y';
+"#;
+    let analyzed = analyze_string::<GoldilocksField>(input);
+    assert_eq!(analyzed.identities.len(), 2);
+    assert_eq!(analyzed.identity_by_label("twice").unwrap().id, 0);
+}
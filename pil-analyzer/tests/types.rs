@@ -5,6 +5,8 @@ use powdr_pil_analyzer::analyze_string;
 
 use pretty_assertions::assert_eq;
 
+mod common;
+
 fn parse_type_scheme(vars: &str, ty: &str) -> TypeScheme {
     let vars = parse_type_var_bounds(vars).unwrap();
     let ty = parse_type(ty).unwrap();
@@ -49,7 +51,7 @@ fn type_scheme_simplify_type_vars() {
 }
 
 #[test]
-#[should_panic = "Error checking sub-expression N.id:\\nExpected type: expr\\n"]
+#[should_panic = "Error checking sub-expression N.id:\nExpected type: expr\n"]
 fn use_fun_in_expr_context() {
     let input = r#"namespace N(16);
     let id = |i| i;
@@ -261,7 +263,7 @@ fn type_check_arrays() {
 }
 
 #[test]
-#[should_panic = "Error type checking the symbol x = (|i| (i, \\\"abc\\\")):\\nExpected either int -> int or int -> fe, but got: int -> (int, string).\\nCannot unify types (int, string) and fe"]
+#[should_panic = "Error type checking the symbol x = (|i| (i, \"abc\")):\nExpected either int -> int or int -> fe, but got: int -> (int, string).\nCannot unify types (int, string) and fe"]
 fn error_for_column_type() {
     let input = "
         let x: col = |i| (i, \"abc\");
@@ -282,3 +284,593 @@ fn col_array_is_array() {
     ";
     type_check(input, &[]);
 }
+
+#[test]
+#[should_panic = "Operator + cannot be applied to an int and an fe: N.a + N.b\nLeft operand type: int\nRight operand type: fe\nHint: wrap the int operand with std::convert::fe(...) or the fe operand with std::convert::int(...)."]
+fn int_fe_mixing() {
+    let input = "
+    namespace N(16);
+        let a: int = 1;
+        let b: fe = 2;
+        let c: int = a + b;
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+#[should_panic = "Operator == cannot be applied to an int and an fe: N.b == N.a\nLeft operand type: fe\nRight operand type: int\nHint: wrap the int operand with std::convert::fe(...) or the fe operand with std::convert::int(...)."]
+fn int_fe_mixing_comparison() {
+    let input = "
+    namespace N(16);
+        let a: int = 1;
+        let b: fe = 2;
+        let c: bool = b == a;
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+#[should_panic = "Operator << cannot be applied to an int and an fe: N.a << N.b\nLeft operand type: int\nRight operand type: fe\nHint: wrap the fe operand with std::convert::int(...)."]
+fn int_fe_mixing_int_operator() {
+    let input = "
+    namespace N(16);
+        let a: int = 1;
+        let b: fe = 2;
+        let c: int = a << b;
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+fn literals_next_to_typed_operands() {
+    let input = "
+    namespace N(16);
+        let a: int = 1;
+        let b: fe = 2;
+        let c = a + 1;
+        let d = 2 * b;
+        let e = 3 < a;
+        let f = b == 7;
+        let g = b ** 2;
+    ";
+    type_check(
+        input,
+        &[
+            ("N.c", "", "int"),
+            ("N.d", "", "fe"),
+            ("N.e", "", "bool"),
+            ("N.f", "", "bool"),
+            ("N.g", "", "fe"),
+        ],
+    );
+}
+
+#[test]
+#[should_panic = "The patterns of a match expression have to be of the same type as the value N.kind that is matched on:\nError checking sub-expression 1:\nExpected type: string"]
+fn match_pattern_type_mismatch() {
+    let input = r#"
+    namespace N(16);
+        let kind: string = "load";
+        let x: int = match kind {
+            "load" => 1,
+            1 => 2,
+            _ => 0,
+        };
+    "#;
+    type_check(input, &[]);
+}
+
+#[test]
+fn match_on_ranges() {
+    let input = r#"
+    namespace N(16);
+        let f = |i| match i {
+            0..16 => "small",
+            16..=31 => "medium",
+            _ => "large",
+        };
+    "#;
+    type_check(input, &[("N.f", "", "int -> string")]);
+}
+
+#[test]
+#[should_panic = "Range patterns can only be used to match values of type int, but N.kind has type string:"]
+fn match_range_on_string() {
+    let input = r#"
+    namespace N(16);
+        let kind: string = "load";
+        let x: int = match kind {
+            0..16 => 1,
+            _ => 0,
+        };
+    "#;
+    type_check(input, &[]);
+}
+
+#[test]
+#[should_panic = "The patterns of a match expression have to be of the same type as the value N.kind that is matched on:\nError checking sub-expression 1:\nExpected type: string"]
+fn or_pattern_type_mismatch() {
+    let input = r#"
+    namespace N(16);
+        let kind: string = "load";
+        let x: int = match kind {
+            "load" | 1 => 1,
+            _ => 0,
+        };
+    "#;
+    type_check(input, &[]);
+}
+
+#[test]
+fn match_on_strings_and_tuples() {
+    let input = r#"
+    namespace N(16);
+        let f = |k| match k {
+            "load" => 1,
+            _ => 0,
+        };
+        let i: int = f("store");
+        let g = |t| match t {
+            (1, "a") => 7,
+            _ => 0,
+        };
+        let n: int = 2;
+        let h: int = g((n, "b"));
+    "#;
+    type_check(
+        input,
+        &[
+            ("N.f", "", "string -> int"),
+            ("N.g", "", "(int, string) -> int"),
+        ],
+    );
+}
+
+#[test]
+fn column_at_row_index() {
+    let input = "
+    namespace N(16);
+        col fixed line(i) { i };
+        let f = |i| line(i) + line(i + 1);
+    ";
+    type_check(input, &[("N.f", "", "int -> fe")]);
+}
+
+#[test]
+#[should_panic = "Columns can only be evaluated at a row of type int, which is not possible inside constraints. Use the column N.line directly there instead."]
+fn column_at_row_index_in_constraint() {
+    let input = "
+    namespace N(16);
+        col fixed line(i) { i };
+        col witness w;
+        w = line(w);
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+fn literal_suffix_resolves_ambiguity() {
+    // Without the suffixes, `x` and `f` would need `std::convert::fe`.
+    let input = "
+    namespace N(16);
+        let x = 7_fe;
+        let f = |i| i + 1_fe;
+        let g = |i| i * 2_int;
+        let h = || 3_expr;
+    ";
+    type_check(
+        input,
+        &[
+            ("N.x", "", "fe"),
+            ("N.f", "", "fe -> fe"),
+            ("N.g", "", "int -> int"),
+            ("N.h", "", "-> expr"),
+        ],
+    );
+}
+
+#[test]
+#[should_panic = "Type suffix of literal 7_int does not match the context:\nExpected type: fe\nSuffix type: int"]
+fn literal_suffix_mismatch() {
+    let input = "
+    namespace N(16);
+        let x: fe = 7_int;
+    ";
+    type_check(input, &[]);
+}
+
+#[test]
+#[should_panic = "Operator + cannot be applied to an int and an fe"]
+fn literal_suffix_mismatch_in_operation() {
+    let input = "
+    namespace N(16);
+        let a: int = 1;
+        let b = a + 2_fe;
+    ";
+    type_check(input, &[]);
+}
+
+mod type_checked {
+    use std::path::Path;
+
+    use powdr_number::{BabyBearField, Bn254Field, GoldilocksField};
+    use powdr_pil_analyzer::{analyze_file, analyze_string, type_check_file, type_check_string};
+
+    use pretty_assertions::assert_eq;
+
+    const INPUT: &str = r#"namespace std::field(16);
+    let modulus = [];
+namespace N(16);
+    col witness x;
+    let bits: int = if std::field::modulus() < 2**64 { 32 } else { 128 };
+    x' = x + 2**bits;
+"#;
+
+    #[test]
+    fn condense_for_two_fields() {
+        let type_checked = type_check_string(INPUT);
+        let goldilocks = type_checked.condense::<GoldilocksField>().unwrap();
+        let bn254 = type_checked.condense::<Bn254Field>().unwrap();
+        assert_eq!(
+            goldilocks.to_string(),
+            analyze_string::<GoldilocksField>(INPUT).to_string()
+        );
+        assert_eq!(
+            bn254.to_string(),
+            analyze_string::<Bn254Field>(INPUT).to_string()
+        );
+        assert!(goldilocks
+            .to_string()
+            .contains("N.x' = (N.x + 4294967296);"));
+        assert!(bn254
+            .to_string()
+            .contains("N.x' = (N.x + 340282366920938463463374607431768211456);"));
+    }
+
+    #[test]
+    fn literals_are_checked_when_condensing() {
+        let type_checked =
+            type_check_string("namespace N(16);\n    col witness x;\n    x = 0x100000000;\n");
+        assert!(type_checked.condense::<GoldilocksField>().is_ok());
+        let errors = type_checked.condense::<BabyBearField>().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "input:3:4: Field overflow: Number 4294967296 is too large for the BabyBear field with modulus 2013265921.\nIn identity #0 [polynomial] input:3:4: N.x = 0x100000000"
+        );
+    }
+
+    #[test]
+    fn condense_file() {
+        let path = Path::new("../test_data/pil/fibonacci.pil");
+        let type_checked = type_check_file(path);
+        for _ in 0..2 {
+            assert_eq!(
+                type_checked.condense::<GoldilocksField>().unwrap(),
+                analyze_file::<GoldilocksField>(path)
+            );
+        }
+    }
+}
+
+mod negative_literals {
+    use powdr_number::{BabyBearField, GoldilocksField};
+    use powdr_pil_analyzer::{
+        analyze_string, evaluate_function,
+        evaluator::{evaluate_fixed_column, Definitions, EvalError},
+    };
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn constraints() {
+        let input = r#"namespace N(16);
+    col witness x;
+    x = -1;
+    x = 0 - 1;
+    x * -1 = -x;
+    x = (-2)**3;
+    x = -0;
+    x = -0xffffffff00000000;
+"#;
+        let expected = r#"namespace N(16);
+    col witness x;
+    N.x = 18446744069414584320;
+    N.x = 18446744069414584320;
+    (N.x * 18446744069414584320) = -N.x;
+    N.x = 18446744069414584313;
+    N.x = 0;
+    N.x = 1;
+"#;
+        assert_eq!(
+            analyze_string::<GoldilocksField>(input).to_string(),
+            expected
+        );
+        let baby_bear = analyze_string::<BabyBearField>(
+            "namespace N(16);\n    col witness x;\n    x = -1;\n    x = -2013265920;\n",
+        );
+        assert_eq!(
+            baby_bear.to_string(),
+            "namespace N(16);\n    col witness x;\n    N.x = 2013265920;\n    N.x = 1;\n"
+        );
+    }
+
+    #[test]
+    fn int_constants() {
+        let input = r#"namespace N(16);
+    let minus_one: int -> int = |i| -1;
+    let below_modulus: int -> int = |i| -0xffffffff00000001;
+    let negate: int -> int = |i| -i;
+    let minus_one_fe: int -> fe = |i| -1;
+"#;
+        let analyzed = analyze_string::<GoldilocksField>(input);
+        let evaluate = |name: &str, argument: i64| {
+            evaluate_function(&analyzed, name, vec![argument.into()])
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(evaluate("N.minus_one", 0), "-1");
+        assert_eq!(evaluate("N.below_modulus", 0), "-18446744069414584321");
+        assert_eq!(evaluate("N.negate", -5), "5");
+        assert_eq!(evaluate("N.minus_one_fe", 0), "18446744069414584320");
+    }
+
+    #[test]
+    fn fixed_arrays() {
+        let input = r#"namespace N(8);
+    col fixed p = [1, 0, -1, 0] + [0]*;
+    col fixed boundary = [-0xffffffff00000000, -0]*;
+    col fixed overflow = [-0xffffffff00000001]*;
+"#;
+        let analyzed = analyze_string::<GoldilocksField>(input);
+        let evaluate = |name: &str| {
+            let definition = analyzed.definition(name).unwrap().1.as_ref().unwrap();
+            evaluate_fixed_column::<GoldilocksField>(
+                definition,
+                None,
+                8,
+                1,
+                &Definitions(&analyzed.definitions),
+            )
+        };
+        let minus_one = -GoldilocksField::from(1);
+        assert_eq!(
+            evaluate("N.p").unwrap(),
+            [1.into(), 0.into(), minus_one, 0.into()]
+                .into_iter()
+                .chain([0.into(); 4])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            evaluate("N.boundary").unwrap(),
+            [1, 0, 1, 0, 1, 0, 1, 0].map(GoldilocksField::from).to_vec()
+        );
+        // The literal is checked before it is negated.
+        let EvalError::FieldOverflow(overflow) = evaluate("N.overflow").unwrap_err() else {
+            panic!()
+        };
+        assert_eq!(overflow.value, overflow.modulus);
+    }
+}
+
+mod small_fields {
+    use std::{fs, panic};
+
+    use powdr_number::{BabyBearField, FieldElement, GoldilocksField, Mersenne31Field};
+    use powdr_pil_analyzer::{analyze_file, analyze_string};
+
+    use pretty_assertions::assert_eq;
+
+    /// Fixtures with constants that do not fit into 31 bits.
+    const LARGE_CONSTANTS: [&str; 1] = ["conditional_fixed_constraints.pil"];
+
+    fn fixtures() -> Vec<std::path::PathBuf> {
+        let mut files = fs::read_dir("../test_data/pil")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "pil"))
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    /// Returns the message of the error the analyzer panics with.
+    fn analysis_error(analyze: impl FnOnce()) -> String {
+        let payload = panic::catch_unwind(panic::AssertUnwindSafe(analyze)).unwrap_err();
+        payload.downcast::<String>().map(|s| *s).unwrap()
+    }
+
+    fn check_fixtures<T: FieldElement>() {
+        for file in fixtures() {
+            let name = file.file_name().unwrap().to_str().unwrap();
+            if LARGE_CONSTANTS.contains(&name) {
+                let error = analysis_error(|| {
+                    analyze_file::<T>(&file);
+                });
+                assert!(
+                    error.contains("Constant 2 ** 32 does not fit into the field"),
+                    "{error}"
+                );
+            } else {
+                assert_eq!(
+                    analyze_file::<T>(&file).to_string(),
+                    analyze_file::<GoldilocksField>(&file).to_string(),
+                    "{}",
+                    file.display()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fixtures_baby_bear() {
+        check_fixtures::<BabyBearField>();
+    }
+
+    #[test]
+    fn fixtures_mersenne31() {
+        check_fixtures::<Mersenne31Field>();
+    }
+
+    #[test]
+    fn literals_are_range_checked() {
+        let input = |n: &str| format!("namespace N(4);\n    col witness x;\n    x = {n};\n");
+        assert!(analyze_string::<BabyBearField>(&input("2013265920"))
+            .to_string()
+            .contains("N.x = 2013265920;"));
+        let error = analysis_error(|| {
+            analyze_string::<BabyBearField>(&input("2013265921"));
+        });
+        assert!(
+            error.contains(
+                "Number 2013265921 is too large for the BabyBear field with modulus 2013265921."
+            ),
+            "{error}"
+        );
+        let error = analysis_error(|| {
+            analyze_string::<Mersenne31Field>(&input("0x80000000"));
+        });
+        assert!(
+            error.contains(
+                "Number 2147483648 is too large for the Mersenne31 field with modulus 2147483647."
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn constant_folding() {
+        let input = r#"namespace std::convert(4);
+    let expr = [];
+namespace N(4);
+    col witness x;
+    x = 2**30 + 3 * 5 - 2**4;
+    x' = (1 - 2) * 2**15 * 2**15;
+    let c: int = 2**30 + 7;
+    x = std::convert::expr(c);
+"#;
+        let expected = r#"namespace std::convert(4);
+    let expr = [];
+namespace N(4);
+    col witness x;
+    N.x = 1073741823;
+    N.x' = 1073741824;
+    let c: int = ((2 ** 30) + 7);
+    N.x = 1073741831;
+"#;
+        let baby_bear = analyze_string::<BabyBearField>(input).to_string();
+        assert_eq!(
+            baby_bear,
+            expected.replace("N.x' = 1073741824", "N.x' = 939524097")
+        );
+        let mersenne31 = analyze_string::<Mersenne31Field>(input).to_string();
+        assert_eq!(
+            mersenne31,
+            expected.replace("N.x' = 1073741824", "N.x' = 1073741823")
+        );
+
+        let overflows = [
+            ("x = 2**31;", "Constant 2 ** 31 does not fit"),
+            ("x = 2**16 * 2**16;", "Constant 65536 * 65536 does not fit"),
+            ("x = (1 - 3)**40;", "Constant -2 ** 40 does not fit"),
+            (
+                "let c: int = 2**31;\n    x = std::convert::expr(c);",
+                "Number 2147483648 is too large for the Mersenne31 field",
+            ),
+        ];
+        for (constraint, message) in overflows {
+            let input = format!(
+                "namespace std::convert(4);\n    let expr = [];\nnamespace N(4);\n    col witness x;\n    {constraint}\n"
+            );
+            let error = analysis_error(|| {
+                analyze_string::<Mersenne31Field>(&input);
+            });
+            assert!(error.contains(message), "{error}");
+        }
+        // Powers of one do not overflow.
+        assert!(analyze_string::<BabyBearField>(
+            "namespace N(4);\n    col witness x;\n    x = (0 - 1)**100;\n"
+        )
+        .to_string()
+        .contains("N.x = 1;"));
+    }
+}
+
+mod query_context {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::try_analyze_string;
+
+    use pretty_assertions::assert_eq;
+
+    use super::common::errors;
+
+    const PRELUDE: &str = r#"namespace std::prover(16);
+    let eval: expr -> fe = [];
+namespace std::convert(16);
+    let expr = [];
+"#;
+
+    #[test]
+    fn eval_in_query() {
+        let input = r#"namespace N(16);
+    let get_input = |x| ("input", std::prover::eval(x));
+    col witness x;
+    col witness y(i) query get_input(x);
+    col witness z(i) query ("hint", std::prover::eval(x) + 1);
+    x = y + z;
+"#;
+        try_analyze_string::<GoldilocksField>(&format!("{PRELUDE}{input}")).unwrap();
+    }
+
+    #[test]
+    fn bit_decomposition_in_query() {
+        let input = r#"namespace std::utils(16);
+    let to_bytes = [];
+    let from_bytes = [];
+namespace std::convert(16);
+    let int = [];
+    let fe = [];
+namespace N(16);
+    let low_byte = |x| std::utils::from_bytes([std::utils::to_bytes(std::convert::int(std::prover::eval(x)), 8)[0]]);
+    col witness x;
+    col witness y(i) query ("hint", std::convert::fe(low_byte(x)));
+    x = y;
+"#;
+        try_analyze_string::<GoldilocksField>(&format!("{PRELUDE}{input}")).unwrap();
+    }
+
+    #[test]
+    fn eval_in_identity() {
+        let input = r#"namespace N(16);
+    col witness x;
+    x = std::convert::expr(std::prover::eval(x));
+"#;
+        assert_eq!(
+            errors(&format!("{PRELUDE}{input}")),
+            "input:7:4: `std::prover::eval` can only be used in query functions of witness columns and in hints, but it is used in the identity `N.x = std::convert::expr::<fe>(std::prover::eval(N.x));`."
+        );
+    }
+
+    #[test]
+    fn eval_in_fixed_column_through_function() {
+        let input = r#"namespace N(16);
+    col witness x;
+    let current = |e| std::prover::eval(e);
+    let plus_one = |e| current(e) + 1;
+    col fixed f(i) { plus_one(x) };
+"#;
+        assert_eq!(
+            errors(&format!("{PRELUDE}{input}")),
+            "input:9:4: `std::prover::eval` can only be used in query functions of witness columns and in hints, but it is used (through `N.plus_one`) in the definition of `N.f`."
+        );
+    }
+
+    #[test]
+    fn eval_looked_up_with_dots() {
+        let analyzed =
+            try_analyze_string::<GoldilocksField>(&format!("{PRELUDE}namespace N(16);\n")).unwrap();
+        for name in ["std::prover::eval", "std.prover.eval", "::std::prover.eval"] {
+            assert_eq!(analyzed.type_of_symbol(name).ty.to_string(), "expr -> fe");
+        }
+    }
+}
@@ -6,11 +6,12 @@ use std::collections::{BTreeMap, BTreeSet, HashSet};
 use powdr_ast::analyzed::{
     AlgebraicBinaryOperator, AlgebraicExpression, AlgebraicReference, AlgebraicUnaryOperator,
     Analyzed, Expression, FunctionValueDefinition, IdentityKind, PolyID, PolynomialReference,
-    Reference,
+    Reference, UnaryOperator,
 };
 use powdr_ast::parsed::types::Type;
 use powdr_ast::parsed::visitor::ExpressionVisitable;
-use powdr_number::{BigUint, FieldElement};
+use powdr_ast::parsed::{LiteralType, Radix};
+use powdr_number::FieldElement;
 
 pub fn optimize<T: FieldElement>(mut pil_file: Analyzed<T>) -> Analyzed<T> {
     let col_count_pre = (pil_file.commitment_count(), pil_file.constant_count());
@@ -58,7 +59,7 @@ fn remove_constant_fixed_columns<T: FieldElement>(pil_file: &mut Analyzed<T>) {
 
 /// Checks if a fixed column defined through a function has a constant
 /// value and returns it in that case.
-fn constant_value(function: &FunctionValueDefinition) -> Option<BigUint> {
+fn constant_value<T: FieldElement>(function: &FunctionValueDefinition) -> Option<T> {
     match function {
         FunctionValueDefinition::Array(expressions) => {
             // TODO use a proper evaluator at some point,
@@ -67,22 +68,40 @@ fn constant_value(function: &FunctionValueDefinition) -> Option<BigUint> {
                 .iter()
                 .filter(|e| !e.is_empty())
                 .flat_map(|e| e.pattern().iter())
-                .map(|e| match e {
-                    Expression::Number(n, _) => Some(n),
-                    _ => None,
-                });
+                .map(literal_value);
             let first = values.next()??;
             if values.all(|x| x == Some(first)) {
-                Some(first.clone())
+                Some(first)
             } else {
                 None
             }
         }
+        FunctionValueDefinition::Sparse(sparse) => {
+            let first = literal_value(sparse.default_value())?;
+            sparse
+                .entries()
+                .iter()
+                .all(|(_, e)| literal_value(e) == Some(first))
+                .then_some(first)
+        }
         FunctionValueDefinition::Query(_) => None,
         FunctionValueDefinition::Expression(_) => None,
     }
 }
 
+/// Returns the field element of a number literal, where a negated literal `-n`
+/// is the field element `modulus - n`. Literals that do not fit into the field
+/// are not evaluated, so that the error is reported when the column is generated.
+fn literal_value<T: FieldElement>(e: &Expression) -> Option<T> {
+    match e {
+        Expression::Number(n, _, _) => T::checked_from(n.clone()),
+        Expression::UnaryOperation(UnaryOperator::Minus, inner) => {
+            literal_value(inner).map(|v: T| -v)
+        }
+        _ => None,
+    }
+}
+
 /// Simplifies multiplications by zero and one.
 fn simplify_identities<T: FieldElement>(pil_file: &mut Analyzed<T>) {
     pil_file.post_visit_expressions_in_identities_mut(&mut simplify_expression_single);
@@ -261,7 +280,7 @@ fn remove_constant_witness_columns<T: FieldElement>(pil_file: &mut Analyzed<T>)
 /// Substitutes all references to certain polynomials by the given field elements.
 fn substitute_polynomial_references<T: FieldElement>(
     pil_file: &mut Analyzed<T>,
-    substitutions: &BTreeMap<PolyID, BigUint>,
+    substitutions: &BTreeMap<PolyID, T>,
 ) {
     pil_file.post_visit_expressions_in_definitions_mut(&mut |e: &mut Expression| {
         if let Expression::Reference(Reference::Poly(PolynomialReference {
@@ -271,22 +290,24 @@ fn substitute_polynomial_references<T: FieldElement>(
         })) = e
         {
             if let Some(value) = substitutions.get(poly_id) {
-                *e = Expression::Number(value.clone(), Some(Type::Fe));
+                *e = Expression::Number(
+                    value.to_arbitrary_integer(),
+                    Some(LiteralType::Inferred(Type::Fe)),
+                    Radix::Decimal,
+                );
             }
         }
     });
     pil_file.post_visit_expressions_in_identities_mut(&mut |e: &mut AlgebraicExpression<_>| {
         if let AlgebraicExpression::Reference(AlgebraicReference { poly_id, .. }) = e {
             if let Some(value) = substitutions.get(poly_id) {
-                *e = AlgebraicExpression::Number(T::checked_from(value.clone()).unwrap());
+                *e = AlgebraicExpression::Number(*value);
             }
         }
     });
 }
 
-fn constrained_to_constant<T: FieldElement>(
-    expr: &AlgebraicExpression<T>,
-) -> Option<(PolyID, BigUint)> {
+fn constrained_to_constant<T: FieldElement>(expr: &AlgebraicExpression<T>) -> Option<(PolyID, T)> {
     match expr {
         AlgebraicExpression::BinaryOperation(left, AlgebraicBinaryOperator::Sub, right) => {
             match (left.as_ref(), right.as_ref()) {
@@ -294,7 +315,7 @@ fn constrained_to_constant<T: FieldElement>(
                 | (AlgebraicExpression::Reference(poly), AlgebraicExpression::Number(n)) => {
                     if poly.is_witness() {
                         // This also works if "next" is true.
-                        return Some((poly.poly_id, n.to_arbitrary_integer()));
+                        return Some((poly.poly_id, *n));
                     }
                 }
                 _ => {}
@@ -302,7 +323,7 @@ fn constrained_to_constant<T: FieldElement>(
         }
         AlgebraicExpression::Reference(poly) => {
             if poly.is_witness() {
-                return Some((poly.poly_id, 0u32.into()));
+                return Some((poly.poly_id, 0.into()));
             }
         }
         _ => {}
@@ -387,6 +408,24 @@ mod test {
         assert_eq!(optimized, expectation);
     }
 
+    #[test]
+    fn replace_negative_fixed() {
+        let input = r#"namespace N(65536);
+    col fixed minus_one = [-1]*;
+    col fixed mixed = [-1, 18446744069414584320]*;
+    col fixed alternating = [1, -1]*;
+    col witness X;
+    X = minus_one * alternating + mixed;
+"#;
+        let expectation = r#"namespace N(65536);
+    col fixed alternating = [1, -1]*;
+    col witness X;
+    N.X = ((18446744069414584320 * N.alternating) + 18446744069414584320);
+"#;
+        let optimized = optimize(analyze_string::<GoldilocksField>(input)).to_string();
+        assert_eq!(optimized, expectation);
+    }
+
     #[test]
     fn replace_lookup() {
         let input = r#"namespace N(65536);
@@ -100,6 +100,8 @@ struct Arguments<T: FieldElement> {
     vkey_file: Option<PathBuf>,
     /// The optional existing proof file to use for aggregation.
     existing_proof_file: Option<PathBuf>,
+    /// Whether instances of the same machine with identical fixed columns share them.
+    share_fixed_columns: bool,
 }
 
 #[derive(Clone)]
@@ -267,6 +269,13 @@ impl<T: FieldElement> Pipeline<T> {
         self
     }
 
+    /// Lets instances of the same machine with identical fixed columns share them,
+    /// see `powdr_linker::share_fixed_columns`.
+    pub fn with_shared_fixed_columns(mut self) -> Self {
+        self.arguments.share_fixed_columns = true;
+        self
+    }
+
     pub fn with_name(mut self, name: String) -> Self {
         self.name = Some(name);
         self
@@ -558,14 +567,16 @@ impl<T: FieldElement> Pipeline<T> {
                 let (path, asm_string) = self.compute_asm_string()?;
                 let path = path.clone();
 
-                let parsed_asm = powdr_parser::parse_asm(None, asm_string).unwrap_or_else(|err| {
-                    match path.as_ref() {
-                        Some(path) => eprintln!("Error parsing .asm file: {}", path.display()),
-                        None => eprintln!("Error parsing .asm file:"),
-                    }
-                    err.output_to_stderr();
-                    panic!();
-                });
+                let file_name = path.as_ref().and_then(|path| path.to_str());
+                let parsed_asm =
+                    powdr_parser::parse_asm(file_name, asm_string).unwrap_or_else(|err| {
+                        match path.as_ref() {
+                            Some(path) => eprintln!("Error parsing .asm file: {}", path.display()),
+                            None => eprintln!("Error parsing .asm file:"),
+                        }
+                        err.output_to_stderr();
+                        panic!();
+                    });
 
                 (path.clone(), parsed_asm)
             });
@@ -647,8 +658,17 @@ impl<T: FieldElement> Pipeline<T> {
                 let analyzed_asm = self.compute_constrained_machine_collection()?.clone();
 
                 self.log("Run airgen");
-                let graph = powdr_airgen::compile(analyzed_asm);
+                let mut graph = powdr_airgen::compile(analyzed_asm).map_err(|e| vec![e])?;
                 self.log("Airgen done");
+                powdr_linker::infer_degrees(&mut graph).map_err(|errors| {
+                    errors
+                        .into_iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<_>>()
+                })?;
+                if self.arguments.share_fixed_columns {
+                    powdr_linker::share_fixed_columns(&mut graph);
+                }
                 log::trace!("{graph}");
 
                 graph
@@ -669,7 +689,12 @@ impl<T: FieldElement> Pipeline<T> {
 
                 let graph = self.compute_linked_machine_graph()?;
 
-                let linked = powdr_linker::link(graph.clone())?;
+                let linked = powdr_linker::link(graph.clone()).map_err(|errors| {
+                    errors
+                        .into_iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<_>>()
+                })?;
                 log::trace!("{linked}");
                 self.maybe_write_pil(&linked, "")?;
 
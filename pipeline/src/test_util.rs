@@ -1,10 +1,9 @@
 use powdr_ast::analyzed::Analyzed;
 use powdr_backend::BackendType;
 use powdr_number::{BigInt, Bn254Field, FieldElement, GoldilocksField};
-use powdr_pil_analyzer::evaluator::{self, SymbolLookup};
+use powdr_pil_analyzer::evaluator;
 use std::path::PathBuf;
 
-use std::sync::Arc;
 #[cfg(feature = "halo2")]
 use std::{fs::File, io::BufWriter};
 
@@ -156,15 +155,10 @@ pub fn std_analyzed<T: FieldElement>() -> Analyzed<T> {
 /// Evaluates a function call.
 pub fn evaluate_function<'a, T: FieldElement>(
     analyzed: &'a Analyzed<T>,
-    function: &'a str,
-    arguments: Vec<Arc<evaluator::Value<'a, T>>>,
+    function: &str,
+    arguments: Vec<evaluator::Value<'a, T>>,
 ) -> evaluator::Value<'a, T> {
-    let symbols = evaluator::Definitions(&analyzed.definitions);
-    let function = symbols.lookup(function, None).unwrap();
-    evaluator::evaluate_function_call(function, arguments, &symbols)
-        .unwrap()
-        .as_ref()
-        .clone()
+    powdr_pil_analyzer::evaluate_function(analyzed, function, arguments).unwrap()
 }
 
 /// Evaluates a function call assuming inputs and outputs are integers.
@@ -173,15 +167,10 @@ pub fn evaluate_integer_function<T: FieldElement>(
     function: &str,
     arguments: Vec<BigInt>,
 ) -> BigInt {
-    let arguments = arguments
-        .into_iter()
-        .map(|x| Arc::new(evaluator::Value::Integer(x)))
-        .collect();
-    if let evaluator::Value::Integer(x) = evaluate_function(analyzed, function, arguments) {
-        x
-    } else {
-        panic!("Expected integer.");
-    }
+    let arguments = arguments.into_iter().map(|x| x.into()).collect();
+    evaluate_function(analyzed, function, arguments)
+        .try_into()
+        .unwrap()
 }
 
 fn convert_witness<T: FieldElement>(witness: &[(String, Vec<u64>)]) -> Vec<(String, Vec<T>)> {
@@ -1,3 +1,5 @@
+use itertools::Itertools;
+use powdr_ast::analyzed::SourceMap;
 use powdr_number::{Bn254Field, FieldElement, GoldilocksField};
 use powdr_pipeline::{
     test_util::{gen_estark_proof, resolve_test_file, test_halo2, verify_test_file},
@@ -295,6 +297,22 @@ fn functional_instructions() {
     //gen_estark_proof(f, slice_to_vec(&i));
 }
 
+#[test]
+fn instruction_aliases() {
+    let f = "asm/instruction_aliases.asm";
+    let i = [3];
+    verify_asm(f, slice_to_vec(&i));
+    test_halo2(f, slice_to_vec(&i));
+}
+
+#[test]
+fn register_ranges() {
+    let f = "asm/register_ranges.asm";
+    let i = [3];
+    verify_asm(f, slice_to_vec(&i));
+    test_halo2(f, slice_to_vec(&i));
+}
+
 #[test]
 fn full_pil_constant() {
     let f = "asm/full_pil_constant.asm";
@@ -357,6 +375,45 @@ fn read_poly_files() {
     }
 }
 
+#[test]
+fn source_map() {
+    let f = "asm/simple_sum.asm";
+    let mut pipeline = Pipeline::<GoldilocksField>::default().from_file(resolve_test_file(f));
+    let pil = pipeline.compute_analyzed_pil().unwrap();
+    let source_map = pil.source_map();
+
+    // `instr assert_zero X { XIsZero = 1 }` is in line 33.
+    let (index, _) = pil
+        .identities
+        .iter()
+        .find_position(|identity| identity.to_string().contains("main.instr_assert_zero"))
+        .unwrap();
+    let identity = &source_map.identities[index];
+    assert_eq!(identity.sources.len(), 1);
+    assert!(identity.sources[0]
+        .file
+        .as_deref()
+        .unwrap()
+        .ends_with("simple_sum.asm"));
+    assert_eq!(identity.sources[0].line, 33);
+
+    // `reg A;` is in line 17.
+    let column = source_map
+        .columns
+        .iter()
+        .find(|column| column.name == "main.A")
+        .unwrap();
+    assert_eq!(column.source.line, 17);
+    assert_eq!(source_map.column(column.poly_id), Some(column));
+
+    let mut json = vec![];
+    source_map.write_json(&mut json).unwrap();
+    assert_eq!(
+        serde_json::from_slice::<SourceMap>(&json).unwrap(),
+        source_map
+    );
+}
+
 mod book {
     use super::*;
     use test_log::test;
@@ -382,7 +439,7 @@ fn hello_world_asm_fail() {
 }
 
 #[test]
-#[should_panic = "FailedAssertion(\"This should fail.\")"]
+#[should_panic = "Assertion failed: This should fail."]
 fn test_failing_assertion() {
     let f = "asm/failing_assertion.asm";
     let i = [];
@@ -759,7 +759,7 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
                 vec![val]
             }
             Expression::PublicReference(_) => todo!(),
-            Expression::Number(n, _) => {
+            Expression::Number(n, _, _) => {
                 let unsigned: u32 = n
                     .try_into()
                     .unwrap_or_else(|_| panic!("Value does not fit in 32 bits."));
@@ -868,6 +868,7 @@ impl<'a, 'b, F: FieldElement> Executor<'a, 'b, F> {
             }
             Expression::MatchExpression(_, _) => todo!(),
             Expression::IfExpression(_) => panic!(),
+            Expression::BlockExpression(_) => panic!(),
             Expression::IndexAccess(_) => todo!(),
         }
     }
@@ -156,10 +156,10 @@ fn load_initial_memory(program: &AnalysisASMFile) -> MemoryState {
                 panic!("initial_memory entry is not a tuple");
             };
             assert_eq!(tuple.len(), 2);
-            let Expression::Number(key, None) = &tuple[0] else {
+            let Expression::Number(key, None, _) = &tuple[0] else {
                 panic!("initial_memory entry key is not a number");
             };
-            let Expression::Number(value, None) = &tuple[1] else {
+            let Expression::Number(value, None, _) = &tuple[1] else {
                 panic!("initial_memory entry value is not a number");
             };
 
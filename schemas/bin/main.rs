@@ -64,5 +64,18 @@ pub fn run() -> Result<(), String> {
         println!("No changes to schemas");
     }
 
+    // The parsed AST is not stored in a versioned format, so its schemas are only updated.
+    for (file_name, schema) in powdr_schemas::parsed_ast_schemas() {
+        let path = output_dir.join(file_name);
+        let current = serde_json::to_string_pretty(&schema)
+            .map_err(|e| format!("Failed to serialize {file_name}: {}", e))?
+            + "\n";
+        if std::fs::read_to_string(&path).ok().as_ref() != Some(&current) {
+            std::fs::write(&path, current)
+                .map_err(|e| format!("Failed to write {file_name}: {}", e))?;
+            println!("Updated {file_name}");
+        }
+    }
+
     Ok(())
 }
@@ -1,3 +1,5 @@
 mod analyzed;
+mod parsed;
 
 pub use analyzed::SerializedAnalyzed;
+pub use parsed::parsed_ast_schemas;
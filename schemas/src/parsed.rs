@@ -0,0 +1,11 @@
+use powdr_ast::parsed::{asm::ASMProgram, PILFile};
+use schemars::schema::RootSchema;
+
+/// The JSON schemas of the serialized parsed AST, together with the names
+/// of the files in `files/` they are checked against.
+pub fn parsed_ast_schemas() -> Vec<(&'static str, RootSchema)> {
+    vec![
+        ("pil_file.schema.json", PILFile::get_struct_schema()),
+        ("asm_program.schema.json", ASMProgram::get_struct_schema()),
+    ]
+}
@@ -0,0 +1,45 @@
+use std::{fs, path::Path};
+
+use powdr_ast::parsed::{asm::ASMProgram, PILFile};
+use powdr_schemas::parsed_ast_schemas;
+
+use pretty_assertions::assert_eq;
+
+#[test]
+fn schemas_are_up_to_date() {
+    for (file_name, schema) in parsed_ast_schemas() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("files")
+            .join(file_name);
+        let expected = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            serde_json::to_string_pretty(&schema).unwrap() + "\n",
+            expected,
+            "The schema in {file_name} is outdated, run `cargo run -p powdr-schemas` to update it."
+        );
+    }
+}
+
+#[test]
+fn pil_file_round_trip() {
+    let input = fs::read_to_string("../test_data/pil/fibonacci.pil").unwrap();
+    let parsed = powdr_parser::parse(None, &input).unwrap();
+    let json = serde_json::to_string(&parsed).unwrap();
+    let deserialized: PILFile = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, parsed);
+}
+
+#[test]
+fn asm_program_round_trip() {
+    let files = fs::read_dir("../test_data/asm/book")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "asm"));
+    for path in files {
+        let input = fs::read_to_string(&path).unwrap();
+        let parsed = powdr_parser::parse_asm(None, &input).unwrap();
+        let json = serde_json::to_string(&parsed).unwrap();
+        let deserialized: ASMProgram = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, parsed, "{}", path.display());
+    }
+}
@@ -3,6 +3,9 @@
 /// This symbol is not an empty array, the actual semantics are overridden.
 let<T> len: T[] -> int = [];
 
+// The functions `new`, `map`, `fold` and `sum` are implemented natively in the evaluator.
+// The definitions below specify their semantics and serve as the reference implementation.
+
 /// Evaluates to the array [f(0), f(1), ..., f(length - 1)].
 let<T> new: int, (int -> T) -> T[] = |length, f| std::utils::fold(length, f, [], |acc, e| (acc + [e]));
 
@@ -1,5 +1,8 @@
 /// This is a built-in function taking a string argument and terminating
 /// evaluation unsuccessfully with this argument as explanation.
+/// Since it does not return, it can be used in any position, for example
+/// in one of the branches of an `if` expression.
+/// If it is called while condensing an identity, the error points at that identity.
 /// This symbol is not an empty array, the actual semantics are overridden.
 let panic: string -> ! = [];
 
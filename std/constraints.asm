@@ -0,0 +1,11 @@
+/// This is a built-in function that constructs the lookup constraint
+/// `sel_left { left } in sel_right { right }`.
+/// The selectors can be set to 1 if the lookup is unconditional.
+/// This symbol is not an empty array, the actual semantics are overridden.
+let lookup: expr, expr[], expr, expr[] -> constr = [];
+
+/// This is a built-in function that constructs the permutation constraint
+/// `sel_left { left } is sel_right { right }`.
+/// The selectors can be set to 1 if the permutation is unconditional.
+/// This symbol is not an empty array, the actual semantics are overridden.
+let permutation: expr, expr[], expr, expr[] -> constr = [];
@@ -1,14 +1,22 @@
-/// A function that takes a field element or integer and converts
+/// A function that takes a field element, integer or constant expression and converts
 /// it to a (non-negative) integer.
+/// Fails if the input is an expression that is not a constant.
 /// The actual implementation is replaced by a built-in function.
 let int = [];
 
-/// A function that takes a field element or integer and converts
+/// A function that takes a field element, integer or constant expression and converts
 /// it to a field element.
-/// Panics if the input is negative or larger or equal to the field modulus.
+/// Fails if the input is negative or larger or equal to the field modulus.
 /// The actual implementation is replaced by a built-in function.
 let fe = [];
 
-/// A function that takes an integer or a field element and converts it to an expression.
+/// A function that takes an integer or a field element and converts it to a constant expression.
+/// Fails if the input is negative or larger or equal to the field modulus.
 /// The actual implementation is replaced by a built-in function.
-let expr = [];
\ No newline at end of file
+let expr = [];
+
+/// A function that takes a value of any type and converts it to its string representation.
+/// Strings are returned unchanged and functions are represented by their type.
+/// It is used by format strings like `f"x = {x}"`.
+/// The actual implementation is replaced by a built-in function.
+let to_string = [];
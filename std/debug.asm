@@ -1,7 +1,9 @@
-/// This is a built-in function taking a string argument and printing it on stdout
-/// when evaluated.
+/// This is a built-in function taking an argument of any type and printing it
+/// when evaluated. Strings are printed without quotes, closures by their parameters.
+/// The output goes to stderr by default, but the caller of the evaluator can
+/// redirect it and it can be disabled through the evaluation limits.
 /// It returns an empty array so that it can be used at constraint level.
 /// This symbol is not an empty array, the actual semantics are overridden.
-let print: string -> constr[] = [];
+let<T> print: T -> constr[] = [];
 
-let println: string -> constr[] = |msg| print(msg + "\n");
\ No newline at end of file
+let println: string -> constr[] = |msg| print(msg + "\n");
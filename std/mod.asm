@@ -2,6 +2,7 @@ mod arith;
 mod array;
 mod binary;
 mod check;
+mod constraints;
 mod convert;
 mod debug;
 mod field;
@@ -10,4 +11,5 @@ mod math;
 mod prover;
 mod shift;
 mod split;
+mod string;
 mod utils;
\ No newline at end of file
@@ -1,4 +1,11 @@
 /// A function that evaluates an algebraic expression on the current row.
 /// Since this needs the concept of a "current row", it is only
 /// valid in query functions.
-let eval: expr -> fe = [];
\ No newline at end of file
+let eval: expr -> fe = [];
+
+/// Creates a new witness column and returns a reference to it.
+/// The name of the column is derived from the given name hint
+/// and the namespace of the constraint that is being condensed.
+/// This is only valid during the condensation of constraints.
+/// This symbol is not an empty array, the actual semantics are overridden.
+let new_witness_col: string -> expr = [];
\ No newline at end of file
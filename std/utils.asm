@@ -43,3 +43,23 @@ let cross_product_internal: int, int, int[] -> (int -> int)[] = |cycle_len, pos,
         [|i| (i / cycle_len) % sizes[pos]] +
             cross_product_internal(cycle_len * sizes[pos], pos + 1, sizes)
     };
+
+/// This is a built-in function returning the lowest `n` bits of the non-negative integer
+/// `value`, least significant bit first. Fails if `value` does not fit into `n` bits.
+/// This symbol is not an empty array, the actual semantics are overridden.
+let to_bits: int, int -> int[] = [];
+
+/// This is a built-in function combining bits, least significant bit first, into an integer.
+/// It is the inverse of `to_bits` and fails if an element is not 0 or 1.
+/// This symbol is not an empty array, the actual semantics are overridden.
+let from_bits: int[] -> int = [];
+
+/// This is a built-in function returning the lowest `n` bytes of the non-negative integer
+/// `value`, least significant byte first. Fails if `value` does not fit into `n` bytes.
+/// This symbol is not an empty array, the actual semantics are overridden.
+let to_bytes: int, int -> int[] = [];
+
+/// This is a built-in function combining bytes, least significant byte first, into an integer.
+/// It is the inverse of `to_bytes` and fails if an element is not in the range 0 to 255.
+/// This symbol is not an empty array, the actual semantics are overridden.
+let from_bytes: int[] -> int = [];
@@ -0,0 +1,37 @@
+// calls a constrained machine from a constrained machine through a permutation,
+// such that every row of the callee is used by exactly one call
+machine Arith(latch, operation_id) {
+
+    degree 8;
+
+    operation add<0> x, y -> z;
+
+    col fixed operation_id = [0]*;
+    col fixed latch = [1]*;
+    col witness x;
+    col witness y;
+    col witness z;
+    z = x + y;
+}
+
+machine Main(latch, operation_id) {
+
+    degree 8;
+
+    Arith arith;
+
+    operation main<0>;
+
+    link instr_add ~> arith.add x, y -> z;
+
+    col fixed operation_id = [0]*;
+    col fixed x(i) { i / 4 };
+    col fixed y(i) { i / 4 + 1 };
+    col witness z;
+    col fixed latch = [0, 0, 0, 1]*;
+
+    // add locally when `instr_add` is off
+    (1 - instr_add) * (x + y - z) = 0;
+    // add using `arith` every other row
+    col fixed instr_add = [0, 1]*;
+}
@@ -8,6 +8,9 @@ instr add X, Y -> Z {
     X + Y = Z
 }
 // ANCHOR_END: local
+// ANCHOR: alias
+instr inc X -> Y = add(X, 1) -> Y;
+// ANCHOR_END: alias
 }
 
 // ANCHOR: submachine
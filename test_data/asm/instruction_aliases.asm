@@ -0,0 +1,26 @@
+machine InstructionAliases {
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+    reg Z[<=];
+    reg A;
+    reg B;
+
+    instr add X, Y -> Z { X + Y = Z }
+    instr assert_eq X, Y { X = Y }
+
+    // Aliases reuse the constraints of the aliased instruction.
+    instr inc X -> Y = add(X, 1) -> Y;
+    instr add_three X -> Y = inc(X + 2) -> Y;
+    instr assert_zero X = assert_eq(X, 0);
+
+    function main {
+        A <=X= ${ ("input", 0) };
+        B <== inc(A);
+        B <== add_three(B);
+        assert_eq B, A + 4;
+        A <== add(A, 7);
+        assert_zero A - 10;
+        return;
+    }
+}
@@ -0,0 +1,25 @@
+machine RegisterRanges {
+    degree 512;
+
+    reg pc[@pc];
+    reg X[<=];
+    reg Y[<=];
+// ANCHOR: declaration
+    reg A: range(0, 255);
+    // uses the same fixed column as `A`
+    reg B: bits(8);
+    // small ranges are enforced without a lookup
+    reg C: range(0, 1);
+    reg D: range(0, 2**4 - 1);
+// ANCHOR_END: declaration
+
+    instr assert_eq X, Y { X = Y }
+
+    function main {
+        A <=X= ${ ("input", 0) };
+        B <=X= 255 - A;
+        C <=X= 1;
+        D <=X= A + 12;
+        return;
+    }
+}